@@ -30,6 +30,7 @@ impl BenchDevice for Device {
                 #[cfg(not(feature = "metal"))]
                 panic!("Metal device without metal feature enabled: {:?}", device)
             }
+            Device::Custom(_) => self.synchronize(),
         }
     }
 
@@ -47,6 +48,7 @@ impl BenchDevice for Device {
             }
             Device::Cuda(_) => format!("cuda_{}", name.into()),
             Device::Metal(_) => format!("metal_{}", name.into()),
+            Device::Custom(device) => format!("{}_{}", device.inner().name(), name.into()),
         }
     }
 }
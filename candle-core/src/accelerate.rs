@@ -161,6 +161,24 @@ mod ffi {
             _: c_long,
             _: c_ulong,
         );
+        pub fn vDSP_vsmsaD(
+            _: *const c_double,
+            _: c_long,
+            _: *const c_double,
+            _: *const c_double,
+            _: *mut c_double,
+            _: c_long,
+            _: c_ulong,
+        );
+        pub fn vDSP_vsmsa(
+            _: *const c_float,
+            _: c_long,
+            _: *const c_float,
+            _: *const c_float,
+            _: *mut c_float,
+            _: c_long,
+            _: c_ulong,
+        );
     }
 }
 
@@ -232,6 +250,26 @@ pub unsafe fn dgemm(
     )
 }
 
+#[inline]
+pub fn vs_affine(a: &[f32], mul: f32, add: f32, y: &mut [f32]) {
+    let a_len = a.len();
+    let y_len = y.len();
+    if a_len != y_len {
+        panic!("a and y have different lengths {a_len} <> {y_len}")
+    }
+    unsafe { ffi::vDSP_vsmsa(a.as_ptr(), 1, &mul, &add, y.as_mut_ptr(), 1, a_len as u64) }
+}
+
+#[inline]
+pub fn vd_affine(a: &[f64], mul: f64, add: f64, y: &mut [f64]) {
+    let a_len = a.len();
+    let y_len = y.len();
+    if a_len != y_len {
+        panic!("a and y have different lengths {a_len} <> {y_len}")
+    }
+    unsafe { ffi::vDSP_vsmsaD(a.as_ptr(), 1, &mul, &add, y.as_mut_ptr(), 1, a_len as u64) }
+}
+
 #[inline]
 pub fn vs_exp(a: &[f32], y: &mut [f32]) {
     let a_len = a.len();
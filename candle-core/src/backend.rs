@@ -88,6 +88,17 @@ pub trait BackendStorage: Sized {
         _: &Layout,
         _: usize,
     ) -> Result<Self>;
+    // Same as index_add but the values are overwritten, when an index appears multiple times the
+    // last value is used.
+    fn index_set(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: usize,
+    ) -> Result<Self>;
 
     fn matmul(
         &self,
@@ -1,7 +1,9 @@
 /// Methods for backpropagation of gradients.
-use crate::op::{BinaryOp, Op, ReduceOp, UnaryOp};
-use crate::{Error, Result, Tensor, TensorId};
+use crate::op::{BackpropOp, BinaryOp, Op, ReduceOp, UnaryOp};
+use crate::{DType, Error, Result, Shape, Tensor, TensorId};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 // arg has been reduced to node via reduce_dims, expand it back to arg.
 // This has to handle keepdims.
@@ -27,12 +29,48 @@ thread_local! {
     }
 }
 
+thread_local! {
+    // Set to false by `no_grad` for the current thread.
+    static GRAD_ENABLED: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+/// Returns false when called within [`no_grad`], in which case the operations are not recorded
+/// for backpropagation.
+pub fn is_grad_enabled() -> bool {
+    GRAD_ENABLED.with(|g| g.get())
+}
+
+/// Runs `f` without recording the operations for backpropagation, even for operations that use
+/// variables. This reduces the memory usage and speeds things up when the gradients are not
+/// needed, e.g. for evaluation. The tensors returned by `f` are not part of the computation graph.
+///
+/// This only applies to the current thread.
+///
+/// ```rust
+/// use candle_core::{Device, Var};
+/// let x = Var::new(&[1f32, 2., 3.], &Device::Cpu)?;
+/// let y = candle_core::no_grad(|| x.sqr())?;
+/// assert!(y.sum_all()?.backward()?.get(&x).is_none());
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn no_grad<R, F: FnOnce() -> R>(f: F) -> R {
+    struct Reset(bool);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            GRAD_ENABLED.with(|g| g.set(self.0))
+        }
+    }
+    let _reset = Reset(GRAD_ENABLED.with(|g| g.replace(false)));
+    f()
+}
+
 impl Tensor {
     /// Return all the nodes that lead to this value in a topologically sorted vec, the first
     /// elements having dependencies on the latter ones, e.g. the first element if any is the
     /// argument.
-    /// This assumes that the op graph is a DAG.
-    fn sorted_nodes(&self) -> Vec<&Tensor> {
+    /// This assumes that the op graph is a DAG. The gradient does not flow through the `stop`
+    /// nodes so they are excluded together with the nodes only reachable through them.
+    fn sorted_nodes(&self, stop: &[TensorId]) -> Vec<&Tensor> {
         // The vec of sorted nodes is passed as an owned value rather than a mutable reference
         // to get around some lifetime limitations.
         fn walk<'a>(
@@ -45,14 +83,15 @@ impl Tensor {
             }
             let mut track_grad = false;
             let mut nodes = if node.is_variable() {
-                // Do not call recursively on the "leaf" nodes.
-                track_grad = true;
+                // Do not call recursively on the "leaf" nodes, frozen variables are constants.
+                track_grad = !node.is_frozen();
                 nodes
             } else if node.dtype().is_int() {
                 nodes
             } else if let Some(op) = node.op() {
                 match op {
                     Op::IndexAdd(t1, t2, t3, _)
+                    | Op::IndexSet(t1, t2, t3, _)
                     | Op::ScatterAdd(t1, t2, t3, _)
                     | Op::CustomOp3(t1, t2, t3, _)
                     | Op::WhereCond(t1, t2, t3) => {
@@ -88,6 +127,7 @@ impl Tensor {
                     | Op::Binary(lhs, rhs, _)
                     | Op::Gather(lhs, rhs, _)
                     | Op::IndexSelect(lhs, rhs, _)
+                    | Op::SparseIndexSelect(lhs, rhs)
                     | Op::Matmul(lhs, rhs)
                     | Op::SliceScatter0(lhs, rhs, _) => {
                         let (tg, nodes) = walk(lhs, nodes, already_seen);
@@ -101,6 +141,18 @@ impl Tensor {
                         track_grad |= tg;
                         nodes
                     }),
+                    Op::CustomBwd(args, _) => args.iter().fold(nodes, |nodes, arg| {
+                        let (tg, nodes) = walk(arg, nodes, already_seen);
+                        track_grad |= tg;
+                        nodes
+                    }),
+                    Op::Checkpoint(args, _) => {
+                        // The checkpointed function may use variables that are not part of its
+                        // arguments so the gradient is always tracked.
+                        track_grad = true;
+                        args.iter()
+                            .fold(nodes, |nodes, arg| walk(arg, nodes, already_seen).1)
+                    }
                     Op::Affine { arg, mul, .. } => {
                         if *mul == 0. {
                             nodes
@@ -127,6 +179,7 @@ impl Tensor {
                     | Op::Transpose(node, _, _)
                     | Op::Permute(node, _)
                     | Op::Narrow(node, _, _, _)
+                    | Op::SliceStep(node, _, _, _)
                     | Op::Unary(node, _)
                     | Op::Elu(node, _)
                     | Op::Powf(node, _)
@@ -155,15 +208,137 @@ impl Tensor {
             }
             (track_grad, nodes)
         }
-        let (_tg, mut nodes) = walk(self, vec![], &mut HashMap::new());
+        let mut already_seen: HashMap<TensorId, bool> =
+            stop.iter().map(|&id| (id, false)).collect();
+        let (_tg, mut nodes) = walk(self, vec![], &mut already_seen);
         nodes.reverse();
         nodes
     }
 
+    /// Computes the gradients of this tensor with respect to the variables it depends on, as well
+    /// as with respect to the intermediary tensors.
+    ///
+    /// The computation graph is not consumed by the backward pass, it is kept alive for as long
+    /// as the tensors referencing it are. So multiple backward passes can be run on the same
+    /// forward pass, e.g. for different losses that share a part of the graph, without computing
+    /// the forward pass again.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Var};
+    /// let x = Var::new(&[1f32, 2.], &Device::Cpu)?;
+    /// let h = x.sqr()?;
+    /// let grads1 = h.sum_all()?.backward()?;
+    /// let grads2 = h.affine(3., 0.)?.sum_all()?.backward()?;
+    /// assert_eq!(grads1.get(&x).unwrap().to_vec1::<f32>()?, [2., 4.]);
+    /// assert_eq!(grads2.get(&x).unwrap().to_vec1::<f32>()?, [6., 12.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
     pub fn backward(&self) -> Result<GradStore> {
-        let sorted_nodes = self.sorted_nodes();
+        self.backward_and_hooks(false, &[])
+    }
+
+    /// Similar to [`Tensor::backward`] but the gradient does not flow through the `stop` tensors,
+    /// these are treated as constants. The parts of the graph that are only reachable through
+    /// them are not visited, e.g. this can be used to freeze an encoder and only compute the
+    /// gradients of a trainable head. The returned store has no gradient for the `stop` tensors.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Var};
+    /// let w_enc = Var::new(&[2f32], &Device::Cpu)?;
+    /// let w_head = Var::new(&[3f32], &Device::Cpu)?;
+    /// let x = Var::new(&[1f32], &Device::Cpu)?;
+    /// let h = x.mul(&w_enc)?;
+    /// let y = h.mul(&w_head)?;
+    /// let grads = y.backward_stop_gradient_through(&[&h])?;
+    /// assert_eq!(grads.get(&w_head).unwrap().to_vec1::<f32>()?, [2.]);
+    /// assert!(grads.get(&w_enc).is_none());
+    /// assert!(grads.get(&x).is_none());
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn backward_stop_gradient_through<T: AsRef<Tensor>>(
+        &self,
+        stop: &[T],
+    ) -> Result<GradStore> {
+        let stop: Vec<TensorId> = stop.iter().map(|t| t.as_ref().id()).collect();
+        self.backward_and_hooks(false, &stop)
+    }
+
+    /// Similar to [`Tensor::backward`] but the operations used to compute the gradients are
+    /// themselves tracked, so the returned gradients can be differentiated again. This can be
+    /// used for gradient penalties or meta-learning. This is slower and uses more memory than
+    /// `backward` as the graph for the backward pass is kept alive with the gradients.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Var};
+    /// let x = Var::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// let grads = x.powf(3.)?.sum_all()?.backward_create_graph()?;
+    /// // d/dx x^3 = 3 x^2
+    /// let dx = grads.get(&x).unwrap();
+    /// let grads = dx.sum_all()?.backward()?;
+    /// // d/dx 3 x^2 = 6 x
+    /// assert_eq!(grads.get(&x).unwrap().to_vec1::<f32>()?, [6., 12., 18.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn backward_create_graph(&self) -> Result<GradStore> {
+        self.backward_and_hooks(true, &[])
+    }
+
+    fn backward_and_hooks(&self, create_graph: bool, stop: &[TensorId]) -> Result<GradStore> {
+        let mut grads = self.backward_with_grad(self.ones_like()?, create_graph, stop)?;
+        // Some gradients are accumulated for the arguments of the visited nodes even if these
+        // arguments are not part of the graph.
+        for id in stop.iter() {
+            grads.grads.remove(id);
+            grads.sparse_grads.remove(id);
+        }
+        // A variable used both through sparse and dense ops ends up with a dense gradient.
+        let ids: Vec<TensorId> = grads.sparse_grads.keys().copied().collect();
+        for id in ids {
+            if let Some(grad) = grads.grads.get(&id) {
+                let sparse_grad = grads.sparse_grads.remove(&id).unwrap();
+                let grad = grad.index_add(&sparse_grad.indices, &sparse_grad.values, 0)?;
+                grads.grads.insert(id, grad);
+            }
+        }
+        // The gradients left in the store, i.e. the ones of the variables and of the other leaves,
+        // are only complete once the whole graph has been processed. Their hooks are applied
+        // here, this includes the variables only used within a checkpointed function and the
+        // sparse gradients. The hooks of the other nodes are applied during the backward pass.
+        if NUM_GRAD_HOOKS.load(Ordering::Relaxed) > 0 {
+            let ids: Vec<TensorId> = grads.grads.keys().copied().collect();
+            for id in ids {
+                let grad = grads.grads.remove(&id).unwrap();
+                grads.grads.insert(id, apply_grad_hooks(id, grad)?);
+            }
+            let ids: Vec<TensorId> = grads.sparse_grads.keys().copied().collect();
+            for id in ids {
+                if !has_grad_hooks(id) {
+                    continue;
+                }
+                // The hooks get the dense gradient, the gradient stays sparse unless a hook
+                // replaces it.
+                let sparse_grad = grads.sparse_grads.remove(&id).unwrap();
+                let mut replaced = false;
+                let grad = apply_grad_hooks_with(id, sparse_grad.to_dense()?, &mut replaced)?;
+                if replaced {
+                    grads.grads.insert(id, grad);
+                } else {
+                    grads.sparse_grads.insert(id, sparse_grad);
+                }
+            }
+        }
+        Ok(grads)
+    }
+
+    pub(crate) fn backward_with_grad(
+        &self,
+        grad: Tensor,
+        create_graph: bool,
+        stop: &[TensorId],
+    ) -> Result<GradStore> {
+        let sorted_nodes = self.sorted_nodes(stop);
         let mut grads = GradStore::new();
-        grads.insert(self, self.ones_like()?.contiguous()?);
+        grads.insert(self, grad.contiguous()?);
         for node in sorted_nodes.iter() {
             if node.is_variable() {
                 continue;
@@ -171,12 +346,13 @@ impl Tensor {
             let grad = grads
                 .remove(node)
                 .expect("candle internal error - grad not populated");
+            let grad = apply_grad_hooks(node.id(), grad)?;
             // https://github.com/huggingface/candle/issues/1241
             // Ideally, we would make these operations in place where possible to ensure that we
             // do not have to allocate too often. Here we just call `.detach` to avoid computing
-            // the backprop graph of the backprop itself. This would be an issue for second order
-            // derivatives but these are out of scope at the moment.
-            let do_not_detach = CANDLE_GRAD_DO_NOT_DETACH.with(|b| *b);
+            // the backprop graph of the backprop itself, unless this graph is required for second
+            // order derivatives.
+            let do_not_detach = create_graph || CANDLE_GRAD_DO_NOT_DETACH.with(|b| *b);
             let grad = if do_not_detach { grad } else { grad.detach() };
             if let Some(op) = node.op() {
                 match op {
@@ -280,11 +456,18 @@ impl Tensor {
                     } => {
                         // The output height for conv_transpose2d is:
                         // (i_h - 1) * stride - 2 * padding + dilation * (k_h - 1) + out_padding + 1
-                        let grad_h = grad.dim(2)?;
-                        let k_h = kernel.dim(2)?;
-                        let out_size =
-                            (grad_h - 1) * stride + dilation * (k_h - 1) + 1 - 2 * padding;
-                        let out_padding = arg.dim(2)? - out_size;
+                        let (_, _, grad_h, grad_w) = grad.dims4()?;
+                        let (_, _, k_h, k_w) = kernel.dims4()?;
+                        let (_, _, i_h, i_w) = arg.dims4()?;
+                        let out_size = |grad_len: usize, k_len: usize| {
+                            (grad_len - 1) * stride + dilation * (k_len - 1) + 1 - 2 * padding
+                        };
+                        // The height and width can require different output paddings when the
+                        // input and kernel are not square and stride > 1. conv_transpose2d only
+                        // takes a single value so the larger one is used and the extra positions
+                        // are removed below.
+                        let out_padding =
+                            usize::max(i_h - out_size(grad_h, k_h), i_w - out_size(grad_w, k_w));
                         let grad_arg = grad.conv_transpose2d(
                             kernel,
                             *padding,
@@ -292,6 +475,12 @@ impl Tensor {
                             *stride,
                             *dilation,
                         )?;
+                        let (_, _, g_h, g_w) = grad_arg.dims4()?;
+                        let grad_arg = if g_h != i_h || g_w != i_w {
+                            grad_arg.narrow(2, 0, i_h)?.narrow(3, 0, i_w)?
+                        } else {
+                            grad_arg
+                        };
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
 
@@ -320,13 +509,22 @@ impl Tensor {
                         dilation,
                         output_padding: _output_padding,
                     } => {
-                        let grad_arg = grad.conv2d(kernel, *padding, *dilation, *stride, 1)?;
+                        let grad_arg = grad.conv2d(kernel, *padding, *stride, *dilation, 1)?;
+                        // The output padding can result in extra rows or columns that do not
+                        // correspond to any input position.
+                        let (_, _, i_h, i_w) = arg.dims4()?;
+                        let (_, _, g_h, g_w) = grad_arg.dims4()?;
+                        let grad_arg = if g_h != i_h || g_w != i_w {
+                            grad_arg.narrow(2, 0, i_h)?.narrow(3, 0, i_w)?
+                        } else {
+                            grad_arg
+                        };
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
 
                         let grad_kernel = grad
                             .transpose(0, 1)?
-                            .conv2d(&arg.transpose(0, 1)?, *padding, *stride, *dilation, 1)?
+                            .conv2d(&arg.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
                             .transpose(0, 1)?;
                         let sum_grad = grads.or_insert(kernel)?;
                         let (_, _, k0, k1) = kernel.dims4()?;
@@ -435,10 +633,43 @@ impl Tensor {
                         let src_sum_grad = grads.or_insert(src)?;
                         *src_sum_grad = src_sum_grad.add(&src_grad)?;
                     }
+                    Op::IndexSet(init, indexes, src, dim) => {
+                        // The positions that have been overwritten do not depend on init.
+                        let init_grad = grad.index_set(indexes, &src.zeros_like()?, *dim)?;
+                        let init_sum_grad = grads.or_insert(init)?;
+                        *init_sum_grad = init_sum_grad.add(&init_grad)?;
+
+                        // Only the last write to each index contributes to the result.
+                        let n = indexes.dim(0)?;
+                        let dev = indexes.device();
+                        let pos = Tensor::arange(0u32, n as u32, dev)?;
+                        let last = Tensor::zeros(init.dim(*dim)?, DType::U32, dev)?
+                            .index_set(indexes, &pos, 0)?
+                            .index_select(indexes, 0)?
+                            .eq(&pos)?;
+                        let mut mask_dims = vec![1; src.rank()];
+                        mask_dims[*dim] = n;
+                        let mask = last.to_dtype(grad.dtype())?.reshape(mask_dims)?;
+                        let src_grad = grad.index_select(indexes, *dim)?.broadcast_mul(&mask)?;
+                        let src_sum_grad = grads.or_insert(src)?;
+                        *src_sum_grad = src_sum_grad.add(&src_grad)?;
+                    }
                     Op::IndexSelect(arg, indexes, dim) => {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.index_add(indexes, &grad, *dim)?;
                     }
+                    Op::SparseIndexSelect(arg, indexes) => {
+                        // The gradient of the intermediary tensors has to be dense to be
+                        // propagated further, and so do the gradients that may get
+                        // differentiated again.
+                        if arg.is_variable() && !create_graph {
+                            let arg_grad = SparseGrad::new(indexes, &grad, arg.shape())?;
+                            grads.accumulate_sparse(arg, arg_grad)?;
+                        } else {
+                            let sum_grad = grads.or_insert(arg)?;
+                            *sum_grad = sum_grad.index_add(indexes, &grad, 0)?;
+                        }
+                    }
                     Op::Matmul(lhs, rhs) => {
                         // Skipping checks, the op went ok, we can skip
                         // the matmul size checks for now.
@@ -578,6 +809,13 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
+                    &Op::SliceStep(ref arg, dim, start, step) => {
+                        let len = grad.dim(dim)?;
+                        let indexes = (0..len).map(|i| (start + i * step) as u32).collect();
+                        let indexes = Tensor::from_vec(indexes, len, grad.device())?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.index_add(&indexes, &grad, dim)?
+                    }
                     Op::Unary(_, UnaryOp::Floor)
                     | Op::Unary(_, UnaryOp::Round)
                     | Op::Reduce(_, ReduceOp::ArgMin, _)
@@ -676,6 +914,57 @@ impl Tensor {
                             *sum_grad = sum_grad.add(&arg_grad3)?
                         }
                     }
+                    Op::CustomBwd(args, bwd) => {
+                        let arg_grads = bwd(args, node, &grad)?;
+                        if arg_grads.len() != args.len() {
+                            crate::bail!(
+                                "custom backward returned {} gradients for {} arguments",
+                                arg_grads.len(),
+                                args.len()
+                            )
+                        }
+                        for (arg, arg_grad) in args.iter().zip(arg_grads) {
+                            if let Some(arg_grad) = arg_grad {
+                                let sum_grad = grads.or_insert(arg)?;
+                                *sum_grad = sum_grad.add(&arg_grad)?
+                            }
+                        }
+                    }
+                    Op::Checkpoint(_, _) if create_graph => Err(Error::BackwardNotSupported {
+                        op: "checkpoint with create_graph",
+                    })?,
+                    Op::Checkpoint(args, f) => {
+                        // Recompute the forward pass, this time keeping track of the intermediary
+                        // values, and backpropagate through it.
+                        let vars = args
+                            .iter()
+                            .map(|arg| arg.with_op(BackpropOp::none(), arg.dtype().is_float()))
+                            .collect::<Vec<_>>();
+                        let res = f(&vars)?;
+                        let mut res_grads = res.backward_with_grad(grad, false, stop)?;
+                        for (arg, var) in args.iter().zip(vars.iter()) {
+                            if let Some(arg_grad) = res_grads.remove(var) {
+                                let sum_grad = grads.or_insert(arg)?;
+                                *sum_grad = sum_grad.add(&arg_grad)?
+                            }
+                            if let Some(arg_grad) = res_grads.remove_sparse(var) {
+                                grads.accumulate_sparse(arg, arg_grad)?
+                            }
+                        }
+                        // The variables captured by the function.
+                        for var in res.sorted_nodes(stop) {
+                            if !var.is_variable() {
+                                continue;
+                            }
+                            if let Some(var_grad) = res_grads.remove(var) {
+                                let sum_grad = grads.or_insert(var)?;
+                                *sum_grad = sum_grad.add(&var_grad)?
+                            }
+                            if let Some(var_grad) = res_grads.remove_sparse(var) {
+                                grads.accumulate_sparse(var, var_grad)?
+                            }
+                        }
+                    }
                     Op::Unary(arg, UnaryOp::Sqr) => {
                         let arg_grad = arg.mul(&grad)?.affine(2., 0.)?;
                         let sum_grad = grads.or_insert(arg)?;
@@ -712,41 +1001,377 @@ impl Tensor {
     }
 }
 
+/// Applies `f` to `xs` without keeping the intermediary values computed by `f` in the
+/// computation graph, these are computed again when backpropagating through the result. This
+/// trades some compute for a lower memory usage when training, the memory used by the activations
+/// of each checkpointed block being only needed for the duration of its own backward pass.
+///
+/// `f` can use variables that are not part of `xs`, e.g. the weights of a layer, and has to be
+/// deterministic as its result is recomputed, so dropout or other random ops should not be used
+/// in `f`.
+///
+/// ```rust
+/// use candle_core::{backprop::checkpoint, Device, Tensor, Var};
+/// let w = Var::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+/// let x = Tensor::new(&[[1f32, 1.]], &Device::Cpu)?;
+/// let layer = {
+///     let w = w.as_tensor().clone();
+///     move |xs: &[Tensor]| xs[0].matmul(&w)?.relu()?.sqr()
+/// };
+/// let y = checkpoint(&[x], layer)?;
+/// let grads = y.sum_all()?.backward()?;
+/// let grad_w = grads.get(&w).unwrap();
+/// assert_eq!(grad_w.to_vec2::<f32>()?, [[8., 12.], [8., 12.]]);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn checkpoint<F>(xs: &[Tensor], f: F) -> Result<Tensor>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor> + Send + Sync + 'static,
+{
+    let detached = xs.iter().map(|x| x.detach()).collect::<Vec<_>>();
+    let res = f(&detached)?;
+    if !is_grad_enabled() || (!res.track_op() && !xs.iter().any(|x| x.track_op())) {
+        return Ok(res);
+    }
+    Ok(res.with_op(BackpropOp::checkpoint(xs, std::sync::Arc::new(f)), false))
+}
+
+type GradHook = dyn Fn(&Tensor) -> Result<Option<Tensor>> + Send + Sync;
+
+// The hooks registered via `register_grad_hook` indexed by tensor, each hook has a unique id so
+// that it can be removed. Only the ids are used as keys so that the registry does not keep the
+// tensors alive, the hooks of a tensor are removed when it is dropped.
+type GradHooks = HashMap<TensorId, Vec<(usize, Arc<GradHook>)>>;
+static GRAD_HOOKS: Mutex<Option<GradHooks>> = Mutex::new(None);
+
+// The number of registered hooks, this avoids locking `GRAD_HOOKS` when there are no hooks.
+static NUM_GRAD_HOOKS: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_GRAD_HOOK_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn has_grad_hooks(id: TensorId) -> bool {
+    if NUM_GRAD_HOOKS.load(Ordering::Relaxed) == 0 {
+        return false;
+    }
+    match GRAD_HOOKS.lock().unwrap().as_ref() {
+        None => false,
+        Some(hooks) => hooks.contains_key(&id),
+    }
+}
+
+fn apply_grad_hooks(id: TensorId, grad: Tensor) -> Result<Tensor> {
+    apply_grad_hooks_with(id, grad, &mut false)
+}
+
+// Runs the hooks of the tensor with id `id`, `replaced` is set if a hook returned a new gradient.
+fn apply_grad_hooks_with(id: TensorId, grad: Tensor, replaced: &mut bool) -> Result<Tensor> {
+    if NUM_GRAD_HOOKS.load(Ordering::Relaxed) == 0 {
+        return Ok(grad);
+    }
+    // The lock is not held while running the hooks as these can register or drop tensors.
+    let hooks: Vec<Arc<GradHook>> = match GRAD_HOOKS.lock().unwrap().as_ref() {
+        None => return Ok(grad),
+        Some(hooks) => match hooks.get(&id) {
+            None => return Ok(grad),
+            Some(hooks) => hooks.iter().map(|(_, hook)| hook.clone()).collect(),
+        },
+    };
+    let mut grad = grad;
+    for hook in hooks.iter() {
+        if let Some(new_grad) = hook(&grad)? {
+            if new_grad.shape() != grad.shape() {
+                crate::bail!(
+                    "gradient hook returned a gradient of shape {:?}, expected {:?}",
+                    new_grad.shape(),
+                    grad.shape()
+                )
+            }
+            grad = new_grad;
+            *replaced = true
+        }
+    }
+    Ok(grad)
+}
+
+// Called when a tensor is dropped.
+pub(crate) fn remove_grad_hooks(id: TensorId) {
+    if NUM_GRAD_HOOKS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    // The removed hooks are dropped once the lock has been released as they can own tensors
+    // which would then call this function when dropped.
+    let removed = match GRAD_HOOKS.lock().unwrap().as_mut() {
+        None => return,
+        Some(hooks) => hooks.remove(&id),
+    };
+    if let Some(removed) = removed {
+        NUM_GRAD_HOOKS.fetch_sub(removed.len(), Ordering::Relaxed);
+    }
+}
+
+/// A handle on a gradient hook, see [`Tensor::register_grad_hook`].
+#[derive(Debug)]
+pub struct GradHookHandle {
+    tensor_id: TensorId,
+    hook_id: usize,
+}
+
+impl GradHookHandle {
+    /// Unregisters the hook, dropping the handle does not unregister it.
+    pub fn remove(self) {
+        // As in `remove_grad_hooks`, the hook is only dropped after the lock has been released.
+        let mut removed = vec![];
+        let mut guard = GRAD_HOOKS.lock().unwrap();
+        let hooks = match guard.as_mut() {
+            None => return,
+            Some(hooks) => hooks,
+        };
+        if let Some(tensor_hooks) = hooks.get_mut(&self.tensor_id) {
+            let (r, kept) = std::mem::take(tensor_hooks)
+                .into_iter()
+                .partition(|(hook_id, _)| *hook_id == self.hook_id);
+            removed = r;
+            *tensor_hooks = kept;
+            NUM_GRAD_HOOKS.fetch_sub(removed.len(), Ordering::Relaxed);
+            if tensor_hooks.is_empty() {
+                hooks.remove(&self.tensor_id);
+            }
+        }
+        drop(guard);
+        drop(removed)
+    }
+}
+
+impl Tensor {
+    /// Registers a hook that is called with the gradient of this tensor during the backward
+    /// passes. The hook can observe the gradient, e.g. for logging, or return a new gradient
+    /// with the same shape that replaces it. For intermediary tensors the new gradient is the one
+    /// propagated to the tensors this one has been computed from.
+    ///
+    /// The hook is called once per backward pass with the total gradient of this tensor,
+    /// including the contributions from checkpointed functions. For a variable with a sparse
+    /// gradient, see [`Tensor::sparse_index_select`], the hook gets the dense gradient and the
+    /// gradient stays sparse unless the hook returns a new one. The hooks are kept until the
+    /// handle is removed or the tensor is dropped.
+    ///
+    /// The hooks are stored in a global registry indexed by the tensor id and are owned by this
+    /// registry. A hook capturing this tensor, or a tensor computed from it, keeps it alive so it
+    /// is never dropped and the hook is not removed automatically: this reference cycle has to
+    /// be broken by calling [`GradHookHandle::remove`].
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Var};
+    /// let x = Var::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// let y = x.sqr()?;
+    /// // Scale the gradient flowing through y.
+    /// let handle = y.register_grad_hook(|g| Ok(Some(g.affine(0.5, 0.)?)));
+    /// let grads = y.sum_all()?.backward()?;
+    /// assert_eq!(grads.get(&x).unwrap().to_vec1::<f32>()?, [1., 2., 3.]);
+    /// handle.remove();
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn register_grad_hook<F>(&self, f: F) -> GradHookHandle
+    where
+        F: Fn(&Tensor) -> Result<Option<Tensor>> + Send + Sync + 'static,
+    {
+        let hook_id = NEXT_GRAD_HOOK_ID.fetch_add(1, Ordering::Relaxed);
+        let mut hooks = GRAD_HOOKS.lock().unwrap();
+        hooks
+            .get_or_insert_with(HashMap::new)
+            .entry(self.id())
+            .or_default()
+            .push((hook_id, Arc::new(f)));
+        NUM_GRAD_HOOKS.fetch_add(1, Ordering::Relaxed);
+        GradHookHandle {
+            tensor_id: self.id(),
+            hook_id,
+        }
+    }
+}
+
+/// A gradient for which only some rows along the first dimension are non-zero, e.g. the
+/// gradient of an embedding table. Row `indices[i]` of the dense gradient gets `values[i]`
+/// added, the same row can appear multiple times in `indices`.
+#[derive(Debug, Clone)]
+pub struct SparseGrad {
+    indices: Tensor,
+    values: Tensor,
+    shape: Shape,
+}
+
+impl SparseGrad {
+    /// Creates a sparse gradient for a tensor of shape `shape`, `indices` is a 1D integer tensor
+    /// and `values` has one row per index, each row having the shape of `shape` without its first
+    /// dimension.
+    pub fn new<S: Into<Shape>>(indices: &Tensor, values: &Tensor, shape: S) -> Result<Self> {
+        let shape = shape.into();
+        let n = indices.dims1()?;
+        if !indices.dtype().is_int() {
+            crate::bail!(
+                "sparse grad indices should use an integer dtype, got {:?}",
+                indices.dtype()
+            )
+        }
+        let dims = shape.dims();
+        if dims.is_empty() || values.dims().first() != Some(&n) || values.dims()[1..] != dims[1..] {
+            crate::bail!(
+                "sparse grad values should have shape ({n}, {:?}), got {:?}",
+                &dims[1.min(dims.len())..],
+                values.shape()
+            )
+        }
+        Ok(Self {
+            indices: indices.clone(),
+            values: values.clone(),
+            shape,
+        })
+    }
+
+    /// The row indexes, a 1D tensor.
+    pub fn indices(&self) -> &Tensor {
+        &self.indices
+    }
+
+    /// The rows to be added at each index, the first dimension matches the number of indexes.
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    /// The shape of the dense gradient.
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    /// Returns the dense gradient.
+    pub fn to_dense(&self) -> Result<Tensor> {
+        Tensor::zeros(&self.shape, self.values.dtype(), self.values.device())?.index_add(
+            &self.indices,
+            &self.values,
+            0,
+        )
+    }
+
+    /// Returns an equivalent sparse gradient where the indexes are sorted and unique, the values
+    /// for duplicate indexes get summed. The indexes are processed on the host.
+    pub fn coalesce(&self) -> Result<Self> {
+        let indices = self.indices.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        let mut unique = indices.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let positions = indices
+            .iter()
+            .map(|i| unique.binary_search(i).unwrap() as u32)
+            .collect::<Vec<_>>();
+        let device = self.values.device();
+        let positions = Tensor::from_vec(positions, indices.len(), device)?;
+        let mut dims = self.values.dims().to_vec();
+        dims[0] = unique.len();
+        let values = Tensor::zeros(dims, self.values.dtype(), device)?.index_add(
+            &positions,
+            &self.values,
+            0,
+        )?;
+        let indices = Tensor::new(unique, device)?.to_dtype(self.indices.dtype())?;
+        Ok(Self {
+            indices,
+            values,
+            shape: self.shape.clone(),
+        })
+    }
+
+    fn add(&self, rhs: &Self) -> Result<Self> {
+        let rhs_indices = rhs.indices.to_dtype(self.indices.dtype())?;
+        Ok(Self {
+            indices: Tensor::cat(&[&self.indices, &rhs_indices], 0)?,
+            values: Tensor::cat(&[&self.values, &rhs.values], 0)?,
+            shape: self.shape.clone(),
+        })
+    }
+}
+
 /// A store for gradients, associating a tensor id to the corresponding gradient tensor, used for back propagation.
+///
+/// The gradients of the variables used through [`Tensor::sparse_index_select`] are stored
+/// separately as [`SparseGrad`], see [`GradStore::get_sparse`].
 #[derive(Debug)]
-pub struct GradStore(HashMap<TensorId, Tensor>);
+pub struct GradStore {
+    grads: HashMap<TensorId, Tensor>,
+    sparse_grads: HashMap<TensorId, SparseGrad>,
+}
 
 impl GradStore {
     /// Create a new gradient store
     fn new() -> Self {
-        GradStore(HashMap::new())
+        GradStore {
+            grads: HashMap::new(),
+            sparse_grads: HashMap::new(),
+        }
     }
 
     /// Get the gradient tensor corresponding to the given tensor id
     pub fn get_id(&self, id: TensorId) -> Option<&Tensor> {
-        self.0.get(&id)
+        self.grads.get(&id)
     }
 
-    /// Get the gradient tensor associated with the given tensor
+    /// Get the gradient tensor associated with the given tensor, frozen variables have no
+    /// gradient.
     pub fn get(&self, tensor: &Tensor) -> Option<&Tensor> {
-        self.0.get(&tensor.id())
+        if tensor.is_frozen() {
+            return None;
+        }
+        self.grads.get(&tensor.id())
     }
 
     /// Remove the gradient tensor associated with the given tensor, returning it if it exists
     pub fn remove(&mut self, tensor: &Tensor) -> Option<Tensor> {
-        self.0.remove(&tensor.id())
+        self.grads.remove(&tensor.id())
     }
 
     /// Insert a gradient tensor associated with the given tensor, returning the previous gradient tensor if it existed
     pub fn insert(&mut self, tensor: &Tensor, grad: Tensor) -> Option<Tensor> {
-        self.0.insert(tensor.id(), grad)
+        self.grads.insert(tensor.id(), grad)
+    }
+
+    /// Get the sparse gradient associated with the given tensor. A tensor has either a dense or a
+    /// sparse gradient, never both.
+    pub fn get_sparse(&self, tensor: &Tensor) -> Option<&SparseGrad> {
+        if tensor.is_frozen() {
+            return None;
+        }
+        self.sparse_grads.get(&tensor.id())
+    }
+
+    /// Remove the sparse gradient associated with the given tensor, returning it if it exists
+    pub fn remove_sparse(&mut self, tensor: &Tensor) -> Option<SparseGrad> {
+        self.sparse_grads.remove(&tensor.id())
+    }
+
+    /// Insert a sparse gradient associated with the given tensor, returning the previous sparse
+    /// gradient if it existed
+    pub fn insert_sparse(&mut self, tensor: &Tensor, grad: SparseGrad) -> Option<SparseGrad> {
+        self.sparse_grads.insert(tensor.id(), grad)
+    }
+
+    // Adds a sparse gradient to the gradient of a tensor, the result stays sparse for variables.
+    fn accumulate_sparse(&mut self, tensor: &Tensor, grad: SparseGrad) -> Result<()> {
+        if !tensor.is_variable() {
+            let sum_grad = self.or_insert(tensor)?;
+            *sum_grad = sum_grad.index_add(&grad.indices, &grad.values, 0)?;
+            return Ok(());
+        }
+        let grad = match self.sparse_grads.remove(&tensor.id()) {
+            None => grad,
+            Some(prev) => prev.add(&grad)?,
+        };
+        self.sparse_grads.insert(tensor.id(), grad);
+        Ok(())
     }
 
     /// Get the gradient tensor associated with the given tensor, or, if it does not exist,
     /// insert a tensor of zeroes, with the same shape and type as the given tensors and return it
     fn or_insert(&mut self, tensor: &Tensor) -> Result<&mut Tensor> {
         use std::collections::hash_map::Entry;
-        let grad = match self.0.entry(tensor.id()) {
+        let grad = match self.grads.entry(tensor.id()) {
             Entry::Occupied(entry) => entry.into_mut(),
             Entry::Vacant(entry) => {
                 let grad = tensor.zeros_like()?;
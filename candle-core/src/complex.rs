@@ -0,0 +1,143 @@
+//! Complex number helpers.
+//!
+//! Complex tensors are not represented by a dedicated dtype, instead they use the same layout as
+//! the [`crate::fft`] module: a trailing dimension of size 2 that holds the real and the imaginary
+//! parts. The operations below are composed of the usual real tensor ops so they can run on all
+//! devices and support backpropagation.
+use crate::{CpuStorage, Layout, Result, Shape, Tensor, D};
+
+#[derive(Debug, Clone, Copy)]
+struct Atan2;
+
+impl crate::CustomOp2 for Atan2 {
+    fn name(&self) -> &'static str {
+        "atan2"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &CpuStorage,
+        l1: &Layout,
+        s2: &CpuStorage,
+        l2: &Layout,
+    ) -> Result<(CpuStorage, Shape)> {
+        use crate::backend::BackendStorage;
+        use crate::cpu_backend::binary_map;
+        use num_traits::Float;
+        let storage = match (s1, s2) {
+            (CpuStorage::BF16(ys), CpuStorage::BF16(xs)) => {
+                CpuStorage::BF16(binary_map(l1, l2, ys, xs, |y, x| y.atan2(x)))
+            }
+            (CpuStorage::F16(ys), CpuStorage::F16(xs)) => {
+                CpuStorage::F16(binary_map(l1, l2, ys, xs, |y, x| y.atan2(x)))
+            }
+            (CpuStorage::F32(ys), CpuStorage::F32(xs)) => {
+                CpuStorage::F32(binary_map(l1, l2, ys, xs, |y, x| y.atan2(x)))
+            }
+            (CpuStorage::F64(ys), CpuStorage::F64(xs)) => {
+                CpuStorage::F64(binary_map(l1, l2, ys, xs, |y, x| y.atan2(x)))
+            }
+            _ => crate::bail!(
+                "unsupported dtypes for atan2 {:?} {:?}",
+                s1.dtype(),
+                s2.dtype()
+            ),
+        };
+        Ok((storage, l1.shape().clone()))
+    }
+
+    fn bwd(
+        &self,
+        ys: &Tensor,
+        xs: &Tensor,
+        _res: &Tensor,
+        grad_res: &Tensor,
+    ) -> Result<(Option<Tensor>, Option<Tensor>)> {
+        let norm2 = (ys.sqr()? + xs.sqr()?)?;
+        let grad_ys = (grad_res * xs)?.div(&norm2)?;
+        let grad_xs = (grad_res * ys)?.div(&norm2)?.neg()?;
+        Ok((Some(grad_ys), Some(grad_xs)))
+    }
+}
+
+fn check_complex(xs: &Tensor, op: &'static str) -> Result<()> {
+    if xs.rank() == 0 || xs.dim(D::Minus1)? != 2 {
+        crate::bail!(
+            "{op} expects a trailing dimension of size 2, got {:?}",
+            xs.shape()
+        )
+    }
+    Ok(())
+}
+
+impl Tensor {
+    /// Creates a complex tensor from its real and imaginary parts, the result has the same shape
+    /// as the inputs with an additional trailing dimension of size 2.
+    pub fn complex(re: &Tensor, im: &Tensor) -> Result<Tensor> {
+        Tensor::stack(&[re, im], D::Minus1)
+    }
+
+    /// Creates a complex tensor from its absolute value and its angle in radians.
+    pub fn polar(abs: &Tensor, angle: &Tensor) -> Result<Tensor> {
+        Tensor::complex(&(abs * angle.cos()?)?, &(abs * angle.sin()?)?)
+    }
+
+    /// The real part of a complex tensor.
+    pub fn real(&self) -> Result<Tensor> {
+        check_complex(self, "real")?;
+        self.narrow(D::Minus1, 0, 1)?.squeeze(D::Minus1)
+    }
+
+    /// The imaginary part of a complex tensor.
+    pub fn imag(&self) -> Result<Tensor> {
+        check_complex(self, "imag")?;
+        self.narrow(D::Minus1, 1, 1)?.squeeze(D::Minus1)
+    }
+
+    /// The complex conjugate of a complex tensor.
+    pub fn conj(&self) -> Result<Tensor> {
+        Tensor::complex(&self.real()?, &self.imag()?.neg()?)
+    }
+
+    /// The absolute value, or modulus, of a complex tensor.
+    pub fn complex_abs(&self) -> Result<Tensor> {
+        (self.real()?.sqr()? + self.imag()?.sqr()?)?.sqrt()
+    }
+
+    /// The angle, or argument, of a complex tensor in radians. The result is between `-pi` and
+    /// `pi`.
+    ///
+    /// This operation is only available on the cpu.
+    pub fn angle(&self) -> Result<Tensor> {
+        self.imag()?.apply_op2(&self.real()?, Atan2)
+    }
+
+    /// Element-wise multiplication of two complex tensors, broadcasting is applied to all the
+    /// dimensions except the trailing one.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [0., 1.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[3f32, -1.], &Device::Cpu)?;
+    /// let c = a.complex_mul(&b)?;
+    /// assert_eq!(c.to_vec2::<f32>()?, &[[5., 5.], [1., 3.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn complex_mul(&self, rhs: &Tensor) -> Result<Tensor> {
+        let (a, b) = (self.real()?, self.imag()?);
+        let (c, d) = (rhs.real()?, rhs.imag()?);
+        let re = a.broadcast_mul(&c)?.broadcast_sub(&b.broadcast_mul(&d)?)?;
+        let im = a.broadcast_mul(&d)?.broadcast_add(&b.broadcast_mul(&c)?)?;
+        Tensor::complex(&re, &im)
+    }
+
+    /// Matrix multiplication of two complex tensors, the inputs have shapes `(.., m, k, 2)` and
+    /// `(.., k, n, 2)` and the result has shape `(.., m, n, 2)`.
+    pub fn complex_matmul(&self, rhs: &Tensor) -> Result<Tensor> {
+        let (a, b) = (self.real()?, self.imag()?);
+        let (c, d) = (rhs.real()?, rhs.imag()?);
+        let re = (a.broadcast_matmul(&c)? - b.broadcast_matmul(&d)?)?;
+        let im = (a.broadcast_matmul(&d)? + b.broadcast_matmul(&c)?)?;
+        Tensor::complex(&re, &im)
+    }
+}
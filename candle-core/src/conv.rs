@@ -142,6 +142,9 @@ impl Tensor {
     }
 
     /// Applies a 1D convolution over the input tensor.
+    ///
+    /// Depthwise convolutions, i.e. with `groups` equal to the number of input channels, use a
+    /// dedicated kernel on cpu and cuda.
     pub fn conv1d(
         &self,
         kernel: &Self,
@@ -149,6 +152,18 @@ impl Tensor {
         stride: usize,
         dilation: usize,
         groups: usize,
+    ) -> Result<Self> {
+        self.conv1d_impl(kernel, padding, stride, dilation, groups, true)
+    }
+
+    fn conv1d_impl(
+        &self,
+        kernel: &Self,
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+        groups: usize,
+        use_depthwise_kernel: bool,
     ) -> Result<Self> {
         let (c_out, c_in_k, k_size) = kernel.dims3()?;
         let (b_size, c_in, l_in) = self.dims3()?;
@@ -175,6 +190,13 @@ impl Tensor {
         };
         if groups == 1 {
             self.conv1d_single_group(kernel, &params)
+        } else if use_depthwise_kernel && DepthwiseConv::is_supported(self, kernel, groups) {
+            let op = DepthwiseConv {
+                padding,
+                stride,
+                dilation,
+            };
+            self.contiguous()?.apply_op2(&kernel.contiguous()?, op)
         } else {
             let blocks = self.chunk(groups, 1)?;
             let kernel = kernel.chunk(groups, 0)?;
@@ -269,6 +291,9 @@ impl Tensor {
     }
 
     /// Applies a 2D convolution over the input tensor.
+    ///
+    /// Depthwise convolutions, i.e. with `groups` equal to the number of input channels, use a
+    /// dedicated kernel on cpu and cuda.
     pub fn conv2d(
         &self,
         kernel: &Self,
@@ -276,6 +301,18 @@ impl Tensor {
         stride: usize,
         dilation: usize,
         groups: usize,
+    ) -> Result<Self> {
+        self.conv2d_impl(kernel, padding, stride, dilation, groups, true)
+    }
+
+    fn conv2d_impl(
+        &self,
+        kernel: &Self,
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+        groups: usize,
+        use_depthwise_kernel: bool,
     ) -> Result<Self> {
         let (b_size, c_in, i_h, i_w) = self.dims4()?;
         let (c_out, c_in_k, k_h, k_w) = kernel.dims4()?;
@@ -299,6 +336,13 @@ impl Tensor {
         };
         if groups == 1 {
             self.conv2d_single_group(kernel, &params)
+        } else if use_depthwise_kernel && DepthwiseConv::is_supported(self, kernel, groups) {
+            let op = DepthwiseConv {
+                padding,
+                stride,
+                dilation,
+            };
+            self.contiguous()?.apply_op2(&kernel.contiguous()?, op)
         } else {
             let blocks = self.chunk(groups, 1)?;
             let kernel = kernel.chunk(groups, 0)?;
@@ -355,4 +399,307 @@ impl Tensor {
         let out_dims = params.out_dims();
         Ok(crate::tensor::from_storage(storage, out_dims, op, false))
     }
+
+    /// Applies a 3D convolution over an input tensor of shape `(b, c_in, d, h, w)` using a kernel
+    /// of shape `(c_out, c_in / groups, k_d, k_h, k_w)`.
+    ///
+    /// This is computed as a sum of 2D convolutions, one per depth position of the kernel, each
+    /// applied to all the selected input frames at once. It is available on all the devices that
+    /// support 2D convolutions and has a gradient.
+    pub fn conv3d(
+        &self,
+        kernel: &Self,
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+        groups: usize,
+    ) -> Result<Self> {
+        let (b_size, c_in, i_d, _i_h, _i_w) = self.dims5()?;
+        let (c_out, c_in_k, k_d, _k_h, _k_w) = kernel.dims5()?;
+        if c_in != c_in_k * groups {
+            crate::bail!(
+                "in_channel mismatch between input ({c_in}, groups {groups}) and kernel ({c_in_k})"
+            )
+        }
+        if k_d == 0 || stride == 0 || dilation == 0 {
+            crate::bail!("conv3d: kernel depth, stride and dilation have to be positive")
+        }
+        let span = dilation * (k_d - 1) + 1;
+        if i_d + 2 * padding < span {
+            crate::bail!("conv3d: kernel depth {span} is larger than the padded input depth")
+        }
+        let d_out = (i_d + 2 * padding - span) / stride + 1;
+        let xs = self.pad_with_zeros(2, padding, padding)?;
+        let mut ys = Vec::with_capacity(k_d);
+        for kd in 0..k_d {
+            let offset = kd * dilation;
+            let frames = if stride == 1 {
+                xs.narrow(2, offset, d_out)?
+            } else {
+                let indexes: Vec<u32> = (0..d_out).map(|i| (offset + i * stride) as u32).collect();
+                xs.index_select(&Tensor::new(indexes, xs.device())?, 2)?
+            };
+            let (_, _, _, h, w) = frames.dims5()?;
+            let frames = frames
+                .transpose(1, 2)?
+                .reshape((b_size * d_out, c_in, h, w))?;
+            let kernel = kernel.narrow(2, kd, 1)?.squeeze(2)?;
+            ys.push(frames.conv2d(&kernel, padding, stride, dilation, groups)?)
+        }
+        let ys = ys
+            .iter()
+            .skip(1)
+            .try_fold(ys[0].clone(), |acc, y| acc + y)?;
+        let (_, _, o_h, o_w) = ys.dims4()?;
+        ys.reshape((b_size, d_out, c_out, o_h, o_w))?
+            .transpose(1, 2)
+    }
+}
+
+// A depthwise convolution, each input channel is convolved with its own `c_out / c_in` filters.
+// This applies to both 1D and 2D convolutions, the 1D case being handled as a 2D convolution with
+// an input and kernel height of 1.
+#[derive(Debug, Clone, Copy)]
+struct DepthwiseConv {
+    padding: usize,
+    stride: usize,
+    dilation: usize,
+}
+
+impl DepthwiseConv {
+    fn is_supported(xs: &Tensor, kernel: &Tensor, groups: usize) -> bool {
+        let dtype_ok = matches!(
+            xs.dtype(),
+            crate::DType::F16 | crate::DType::BF16 | crate::DType::F32 | crate::DType::F64
+        );
+        let device_ok = xs.device().is_cpu() || xs.device().is_cuda();
+        dtype_ok && device_ok && groups == xs.dim(1).unwrap_or(0) && kernel.dim(1).ok() == Some(1)
+    }
+
+    // Returns the padding, stride and dilation along the height dimension, the 1D convolutions
+    // have a height of 1.
+    fn h_params(&self, rank: usize) -> (usize, usize, usize) {
+        if rank == 3 {
+            (0, 1, 1)
+        } else {
+            (self.padding, self.stride, self.dilation)
+        }
+    }
+
+    // Returns (b_size, c_in, h_in, w_in, c_out, h_k, w_k, h_out, w_out).
+    fn dims(&self, xs: &crate::Shape, k: &crate::Shape) -> Result<[usize; 9]> {
+        let ((b_size, c_in, h_in, w_in), (c_out, h_k, w_k)) = match (xs.dims(), k.dims()) {
+            (&[b, c, w], &[c_out, 1, w_k]) => ((b, c, 1, w), (c_out, 1, w_k)),
+            (&[b, c, h, w], &[c_out, 1, h_k, w_k]) => ((b, c, h, w), (c_out, h_k, w_k)),
+            _ => crate::bail!("depthwise-conv: unexpected shapes {xs:?} {k:?}"),
+        };
+        let (p_h, s_h, d_h) = self.h_params(xs.rank());
+        let out_len = |len: usize, k: usize, p: usize, s: usize, d: usize| {
+            (len + 2 * p)
+                .checked_sub(d * (k - 1) + 1)
+                .map(|l| l / s + 1)
+        };
+        let h_out = out_len(h_in, h_k, p_h, s_h, d_h);
+        let w_out = out_len(w_in, w_k, self.padding, self.stride, self.dilation);
+        match (h_out, w_out) {
+            (Some(h_out), Some(w_out)) if c_out % c_in == 0 => {
+                Ok([b_size, c_in, h_in, w_in, c_out, h_k, w_k, h_out, w_out])
+            }
+            _ => crate::bail!("depthwise-conv: invalid shapes {xs:?} {k:?}"),
+        }
+    }
+
+    fn out_shape(&self, xs: &crate::Shape, dims: &[usize; 9]) -> crate::Shape {
+        let [b_size, _, _, _, c_out, _, _, h_out, w_out] = *dims;
+        if xs.rank() == 3 {
+            (b_size, c_out, w_out).into()
+        } else {
+            (b_size, c_out, h_out, w_out).into()
+        }
+    }
+
+    fn cpu_conv<T: crate::WithDType>(
+        &self,
+        xs: &[T],
+        xs_l: &crate::Layout,
+        k: &[T],
+        k_l: &crate::Layout,
+    ) -> Result<(Vec<T>, crate::Shape)> {
+        use rayon::prelude::*;
+
+        let (xs, k) = match (xs_l.contiguous_offsets(), k_l.contiguous_offsets()) {
+            (Some((o1, o2)), Some((k1, k2))) => (&xs[o1..o2], &k[k1..k2]),
+            _ => crate::bail!("depthwise-conv: inputs have to be contiguous"),
+        };
+        let dims = self.dims(xs_l.shape(), k_l.shape())?;
+        let [b_size, c_in, h_in, w_in, c_out, h_k, w_k, h_out, w_out] = dims;
+        let (p_h, s_h, d_h) = self.h_params(xs_l.shape().rank());
+        let (p_w, s_w, d_w) = (self.padding, self.stride, self.dilation);
+        let mult = c_out / c_in;
+        let mut dst = vec![T::zero(); b_size * c_out * h_out * w_out];
+        crate::utils::install(|| {
+            dst.par_chunks_exact_mut(h_out * w_out)
+                .enumerate()
+                .for_each(|(idx, dst)| {
+                    let (b_idx, c_idx) = (idx / c_out, idx % c_out);
+                    let src = &xs[(b_idx * c_in + c_idx / mult) * h_in * w_in..];
+                    let k = &k[c_idx * h_k * w_k..];
+                    for offset_w in 0..w_k {
+                        let offset_w = offset_w * d_w;
+                        // The output columns for which the source column is not in the padding.
+                        let min_w = p_w.saturating_sub(offset_w).div_ceil(s_w);
+                        let max_w = match (w_in + p_w).checked_sub(offset_w + 1) {
+                            None => continue,
+                            Some(v) => usize::min(v / s_w + 1, w_out),
+                        };
+                        if min_w >= max_w {
+                            continue;
+                        }
+                        for offset_h in 0..h_k {
+                            let weight = k[offset_h * w_k + offset_w / d_w];
+                            for dst_h in 0..h_out {
+                                let src_h = dst_h * s_h + offset_h * d_h;
+                                if src_h < p_h || src_h >= h_in + p_h {
+                                    continue;
+                                }
+                                let src =
+                                    &src[(src_h - p_h) * w_in + min_w * s_w + offset_w - p_w..];
+                                let dst = &mut dst[dst_h * w_out + min_w..dst_h * w_out + max_w];
+                                if s_w == 1 {
+                                    for (d, &s) in dst.iter_mut().zip(src.iter()) {
+                                        *d += weight * s
+                                    }
+                                } else {
+                                    for (d, &s) in dst.iter_mut().zip(src.iter().step_by(s_w)) {
+                                        *d += weight * s
+                                    }
+                                }
+                            }
+                        }
+                    }
+                })
+        });
+        Ok((dst, self.out_shape(xs_l.shape(), &dims)))
+    }
+}
+
+impl crate::CustomOp2 for DepthwiseConv {
+    fn name(&self) -> &'static str {
+        "depthwise-conv"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &crate::CpuStorage,
+        l1: &crate::Layout,
+        s2: &crate::CpuStorage,
+        l2: &crate::Layout,
+    ) -> Result<(crate::CpuStorage, crate::Shape)> {
+        use crate::backend::BackendStorage;
+        use crate::CpuStorage as S;
+        let (storage, shape) = match (s1, s2) {
+            (S::F32(xs), S::F32(k)) => {
+                let (dst, shape) = self.cpu_conv(xs, l1, k, l2)?;
+                (S::F32(dst), shape)
+            }
+            (S::F64(xs), S::F64(k)) => {
+                let (dst, shape) = self.cpu_conv(xs, l1, k, l2)?;
+                (S::F64(dst), shape)
+            }
+            (S::F16(xs), S::F16(k)) => {
+                let (dst, shape) = self.cpu_conv(xs, l1, k, l2)?;
+                (S::F16(dst), shape)
+            }
+            (S::BF16(xs), S::BF16(k)) => {
+                let (dst, shape) = self.cpu_conv(xs, l1, k, l2)?;
+                (S::BF16(dst), shape)
+            }
+            _ => crate::bail!(
+                "depthwise-conv: unsupported dtypes {:?} {:?}",
+                s1.dtype(),
+                s2.dtype()
+            ),
+        };
+        Ok((storage, shape))
+    }
+
+    #[cfg(feature = "cuda")]
+    fn cuda_fwd(
+        &self,
+        s1: &crate::CudaStorage,
+        l1: &crate::Layout,
+        s2: &crate::CudaStorage,
+        l2: &crate::Layout,
+    ) -> Result<(crate::CudaStorage, crate::Shape)> {
+        use crate::cuda_backend::cudarc::driver::{
+            CudaSlice, DeviceRepr, LaunchAsync, LaunchConfig, ValidAsZeroBits,
+        };
+        use crate::cuda_backend::{kernel_name, kernels, Map2, WrapErr};
+        use crate::{CudaDevice, WithDType};
+
+        impl Map2 for DepthwiseConv {
+            fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
+                &self,
+                xs: &CudaSlice<T>,
+                xs_l: &crate::Layout,
+                k: &CudaSlice<T>,
+                k_l: &crate::Layout,
+                dev: &CudaDevice,
+            ) -> Result<CudaSlice<T>> {
+                let (xs, k) = match (xs_l.contiguous_offsets(), k_l.contiguous_offsets()) {
+                    (Some((o1, o2)), Some((k1, k2))) => (xs.slice(o1..o2), k.slice(k1..k2)),
+                    _ => crate::bail!("depthwise-conv: inputs have to be contiguous"),
+                };
+                let dims = self.dims(xs_l.shape(), k_l.shape())?;
+                let [b_size, _, _, _, c_out, _, _, h_out, w_out] = dims;
+                let (p_h, s_h, d_h) = self.h_params(xs_l.shape().rank());
+                let info = [
+                    &dims[..],
+                    &[p_h, self.padding, s_h, self.stride, d_h, self.dilation],
+                ]
+                .concat();
+                let info = dev.htod_copy(info).w()?;
+                let dst_el = b_size * c_out * h_out * w_out;
+                // SAFETY: Set later by running the kernel.
+                let dst = unsafe { dev.alloc::<T>(dst_el) }.w()?;
+                let func =
+                    dev.get_or_load_func(&kernel_name::<T>("depthwise_conv2d"), kernels::CONV)?;
+                let cfg = LaunchConfig::for_num_elems(dst_el as u32);
+                let params = (dst_el, &info, &xs, &k, &dst);
+                // SAFETY: ffi.
+                unsafe { func.launch(cfg, params) }.w()?;
+                Ok(dst)
+            }
+        }
+
+        use crate::backend::BackendStorage;
+        let dev = s1.device();
+        let dims = self.dims(l1.shape(), l2.shape())?;
+        let slice = self.map(&s1.slice, l1, &s2.slice, l2, dev)?;
+        let dst = crate::cuda_backend::CudaStorage {
+            slice,
+            device: dev.clone(),
+        };
+        Ok((dst, self.out_shape(l1.shape(), &dims)))
+    }
+
+    fn bwd(
+        &self,
+        xs: &Tensor,
+        kernel: &Tensor,
+        _res: &Tensor,
+        grad_res: &Tensor,
+    ) -> Result<(Option<Tensor>, Option<Tensor>)> {
+        // Backpropagate through the generic grouped convolution.
+        let xs_v = xs.with_op(BackpropOp::none(), true);
+        let kernel_v = kernel.with_op(BackpropOp::none(), true);
+        let (p, s, d, groups) = (self.padding, self.stride, self.dilation, xs.dim(1)?);
+        let res = if xs.rank() == 3 {
+            xs_v.conv1d_impl(&kernel_v, p, s, d, groups, false)?
+        } else {
+            xs_v.conv2d_impl(&kernel_v, p, s, d, groups, false)?
+        };
+        let grads = res.backward_with_grad(grad_res.clone(), false, &[])?;
+        Ok((grads.get(&xs_v).cloned(), grads.get(&kernel_v).cloned()))
+    }
 }
@@ -139,6 +139,20 @@ impl Tensor {
                 let vs = vs.to_vec1::<u8>()?;
                 f.write_all(&vs)?;
             }
+            DType::Bool => {
+                let vs = vs.to_dtype(DType::U8)?.to_vec1::<u8>()?;
+                f.write_all(&vs)?;
+            }
+            DType::F8E4M3 => {
+                for v in vs.to_vec1::<crate::F8E4M3>()? {
+                    f.write_u8(v.to_bits())?
+                }
+            }
+            DType::F8E5M2 => {
+                for v in vs.to_vec1::<crate::F8E5M2>()? {
+                    f.write_u8(v.to_bits())?
+                }
+            }
         }
         Ok(())
     }
@@ -0,0 +1,99 @@
+//! Tracking of the host memory, see [`TrackingAllocator`].
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static PEAK_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that wraps the system allocator and keeps track of the memory allocated
+/// by the process. The cpu device can only report its memory usage via
+/// [`crate::Device::memory_stats`] when this allocator is installed.
+///
+/// The `tracking-allocator` feature installs it as the global allocator. Without this feature
+/// the binary has to install it itself, a program can only have a single global allocator so
+/// the feature cannot be used if the binary already sets a different one.
+///
+/// ```rust
+/// use candle_core::{cpu::alloc::TrackingAllocator, DType, Device, Tensor};
+///
+/// # #[cfg(not(feature = "tracking-allocator"))]
+/// #[global_allocator]
+/// static ALLOC: TrackingAllocator = TrackingAllocator;
+///
+/// let before = Device::Cpu.memory_stats()?;
+/// let t = Tensor::zeros(1024, DType::F32, &Device::Cpu)?;
+/// let after = Device::Cpu.memory_stats()?;
+/// assert!(after.allocated >= before.allocated + 4096);
+/// assert!(after.peak_allocated >= after.allocated);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub struct TrackingAllocator;
+
+#[cfg(feature = "tracking-allocator")]
+#[global_allocator]
+static GLOBAL: TrackingAllocator = TrackingAllocator;
+
+impl TrackingAllocator {
+    /// Returns the number of bytes currently allocated and the peak since the start of the
+    /// process or the last call to [`TrackingAllocator::reset_peak`], `None` is returned if the
+    /// allocator is not installed.
+    pub fn stats() -> Option<(u64, u64)> {
+        if INSTALLED.load(Ordering::Relaxed) {
+            let allocated = ALLOCATED.load(Ordering::Relaxed);
+            let peak = PEAK_ALLOCATED.load(Ordering::Relaxed);
+            Some((allocated, peak))
+        } else {
+            None
+        }
+    }
+
+    /// Resets the peak to the number of bytes currently allocated.
+    pub fn reset_peak() {
+        PEAK_ALLOCATED.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed)
+    }
+
+    fn add(size: usize) {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            INSTALLED.store(true, Ordering::Relaxed)
+        }
+        let allocated = ALLOCATED.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+    }
+
+    fn sub(size: usize) {
+        ALLOCATED.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::add(layout.size())
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::add(layout.size())
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::sub(layout.size())
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::sub(layout.size());
+            Self::add(new_size)
+        }
+        new_ptr
+    }
+}
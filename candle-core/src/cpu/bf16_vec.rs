@@ -0,0 +1,55 @@
+//! Vectorized kernels for bf16 values.
+//!
+//! The values are widened to f32, processed, and rounded back to bf16 in a single pass. The
+//! conversions only involve integer shifts, additions, and selects on each lane so the loops get
+//! auto-vectorized for the enabled target features, e.g. AVX2/AVX-512 on x86 or NEON on arm.
+//! The rounding matches `half::bf16::from_f32` so the results are the same as the ones from the
+//! scalar kernels applying the same f32 computation.
+use half::bf16;
+
+#[inline(always)]
+pub fn to_f32(v: bf16) -> f32 {
+    f32::from_bits((v.to_bits() as u32) << 16)
+}
+
+#[inline(always)]
+pub fn from_f32(v: f32) -> bf16 {
+    let bits = v.to_bits();
+    // Round to nearest, ties to even.
+    let rounded = (bits.wrapping_add(0x7fff + ((bits >> 16) & 1)) >> 16) as u16;
+    // Keep NaN values quiet rather than letting the rounding turn them into infinities.
+    let nan = ((bits >> 16) | 0x0040) as u16;
+    bf16::from_bits(if v.is_nan() { nan } else { rounded })
+}
+
+/// Converts `xs` to f32 values, `ys` must have the same length as `xs`.
+#[inline(always)]
+pub fn to_f32_slice(xs: &[bf16], ys: &mut [f32]) {
+    for (x, y) in xs.iter().zip(ys.iter_mut()) {
+        *y = to_f32(*x)
+    }
+}
+
+/// Converts `xs` to bf16 values, `ys` must have the same length as `xs`.
+#[inline(always)]
+pub fn from_f32_slice(xs: &[f32], ys: &mut [bf16]) {
+    for (x, y) in xs.iter().zip(ys.iter_mut()) {
+        *y = from_f32(*x)
+    }
+}
+
+/// Applies `f` to each element of `xs` using f32 arithmetic.
+#[inline(always)]
+pub fn unary_map<F: Fn(f32) -> f32>(xs: &[bf16], ys: &mut [bf16], f: F) {
+    for (x, y) in xs.iter().zip(ys.iter_mut()) {
+        *y = from_f32(f(to_f32(*x)))
+    }
+}
+
+/// Applies `f` to each pair of elements of `xs1` and `xs2` using f32 arithmetic.
+#[inline(always)]
+pub fn binary_map<F: Fn(f32, f32) -> f32>(xs1: &[bf16], xs2: &[bf16], ys: &mut [bf16], f: F) {
+    for ((x1, x2), y) in xs1.iter().zip(xs2.iter()).zip(ys.iter_mut()) {
+        *y = from_f32(f(to_f32(*x1), to_f32(*x2)))
+    }
+}
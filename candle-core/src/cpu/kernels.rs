@@ -161,11 +161,13 @@ pub fn par_for_each(n_threads: usize, func: impl Fn(usize) + Send + Sync) {
     if n_threads == 1 {
         func(0)
     } else {
-        rayon::scope(|s| {
-            for thread_idx in 0..n_threads {
-                let func = &func;
-                s.spawn(move |_| func(thread_idx));
-            }
+        crate::utils::install(|| {
+            rayon::scope(|s| {
+                for thread_idx in 0..n_threads {
+                    let func = &func;
+                    s.spawn(move |_| func(thread_idx));
+                }
+            })
         })
     }
 }
@@ -177,15 +179,17 @@ pub fn par_range(lo: usize, up: usize, n_threads: usize, func: impl Fn(usize) +
             func(i)
         }
     } else {
-        rayon::scope(|s| {
-            for thread_idx in 0..n_threads {
-                let func = &func;
-                s.spawn(move |_| {
-                    for i in (thread_idx..up).step_by(n_threads) {
-                        func(i)
-                    }
-                });
-            }
+        crate::utils::install(|| {
+            rayon::scope(|s| {
+                for thread_idx in 0..n_threads {
+                    let func = &func;
+                    s.spawn(move |_| {
+                        for i in (thread_idx..up).step_by(n_threads) {
+                            func(i)
+                        }
+                    });
+                }
+            })
         })
     }
 }
@@ -1,5 +1,9 @@
+pub mod alloc;
+pub mod bf16_vec;
 pub mod erf;
 pub mod kernels;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+pub mod numa;
 
 #[allow(unused)]
 trait Cpu<const ARR: usize> {
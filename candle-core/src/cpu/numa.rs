@@ -0,0 +1,171 @@
+//! NUMA support for the cpu backend, this is only available on linux.
+//!
+//! On multi-socket machines, the memory attached to a different node than the cpu accessing it
+//! is significantly slower. The large tensors, e.g. model weights, can be interleaved across all
+//! the nodes or bound to a single node with [`bind_tensor`], and [`thread_pool`] creates a pool
+//! of threads pinned to a node that can be used by the cpu backend via
+//! [`crate::utils::set_thread_pool`].
+use crate::{CpuStorage, Error, Result, Storage, Tensor};
+
+const MPOL_DEFAULT: libc::c_long = 0;
+const MPOL_PREFERRED: libc::c_long = 1;
+const MPOL_BIND: libc::c_long = 2;
+const MPOL_INTERLEAVE: libc::c_long = 3;
+const MPOL_MF_MOVE: libc::c_ulong = 1 << 1;
+
+/// A policy for the placement of memory across the NUMA nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemPolicy {
+    /// Allocate on the node of the cpu that first accesses the memory.
+    Default,
+    /// Allocate on the given node when possible, falling back on the other nodes.
+    Preferred(usize),
+    /// Only allocate on the given nodes.
+    Bind(Vec<usize>),
+    /// Interleave the pages across the given nodes.
+    Interleave(Vec<usize>),
+}
+
+impl MemPolicy {
+    /// Interleaves the pages across all the nodes of the machine.
+    pub fn interleave_all() -> Result<Self> {
+        Ok(Self::Interleave((0..num_nodes()?).collect()))
+    }
+
+    fn mode_and_mask(&self) -> Result<(libc::c_long, Vec<libc::c_ulong>)> {
+        let (mode, nodes) = match self {
+            Self::Default => return Ok((MPOL_DEFAULT, vec![])),
+            Self::Preferred(node) => (MPOL_PREFERRED, std::slice::from_ref(node)),
+            Self::Bind(nodes) => (MPOL_BIND, nodes.as_slice()),
+            Self::Interleave(nodes) => (MPOL_INTERLEAVE, nodes.as_slice()),
+        };
+        if nodes.is_empty() {
+            crate::bail!("numa policy {self:?} requires at least one node")
+        }
+        let bits = libc::c_ulong::BITS as usize;
+        let max_node = nodes.iter().max().copied().unwrap_or(0);
+        let mut mask = vec![0; max_node / bits + 1];
+        for &node in nodes.iter() {
+            mask[node / bits] |= 1 << (node % bits)
+        }
+        Ok((mode, mask))
+    }
+}
+
+fn read_sys_file(path: &str) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| Error::wrap(e).with_path(path))
+}
+
+// Parses a list such as "0-3,8,10-11" as used by sysfs.
+fn parse_list(list: &str) -> Result<Vec<usize>> {
+    let mut res = vec![];
+    for range in list.trim().split(',').filter(|s| !s.is_empty()) {
+        let parse = |s: &str| s.parse::<usize>().map_err(Error::wrap);
+        match range.split_once('-') {
+            Some((lo, up)) => res.extend(parse(lo)?..=parse(up)?),
+            None => res.push(parse(range)?),
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the number of NUMA nodes of the machine.
+pub fn num_nodes() -> Result<usize> {
+    let nodes = parse_list(&read_sys_file("/sys/devices/system/node/possible")?)?;
+    Ok(nodes.iter().max().map_or(1, |n| n + 1))
+}
+
+/// Returns the indexes of the cpus attached to a NUMA node.
+pub fn node_cpus(node: usize) -> Result<Vec<usize>> {
+    parse_list(&read_sys_file(&format!(
+        "/sys/devices/system/node/node{node}/cpulist"
+    ))?)
+}
+
+/// Sets the policy used for the memory allocated by the current thread.
+pub fn set_thread_mem_policy(policy: &MemPolicy) -> Result<()> {
+    let (mode, mask) = policy.mode_and_mask()?;
+    let max_node = mask.len() * libc::c_ulong::BITS as usize + 1;
+    let ret = unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, mask.as_ptr(), max_node) };
+    if ret != 0 {
+        crate::bail!("set_mempolicy failed: {}", std::io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// Applies a policy to the memory used by a cpu tensor, the pages that have already been
+/// allocated are moved to match the policy. The memory is shared with the tensors derived from
+/// `tensor` without a copy.
+pub fn bind_tensor(tensor: &Tensor, policy: &MemPolicy) -> Result<()> {
+    fn bytes<T>(vs: &[T]) -> (usize, usize) {
+        (vs.as_ptr() as usize, std::mem::size_of_val(vs))
+    }
+    let storage = tensor.storage();
+    let (addr, len) = match &*storage {
+        Storage::Cpu(storage) => match storage {
+            CpuStorage::U8(vs) | CpuStorage::Bool(vs) => bytes(vs),
+            CpuStorage::U32(vs) => bytes(vs),
+            CpuStorage::I64(vs) => bytes(vs),
+            CpuStorage::BF16(vs) => bytes(vs),
+            CpuStorage::F16(vs) => bytes(vs),
+            CpuStorage::F32(vs) => bytes(vs),
+            CpuStorage::F64(vs) => bytes(vs),
+            CpuStorage::F8E4M3(vs) => bytes(vs),
+            CpuStorage::F8E5M2(vs) => bytes(vs),
+        },
+        _ => crate::bail!(
+            "bind_tensor expects a cpu tensor, got {:?}",
+            tensor.device()
+        ),
+    };
+    if len == 0 {
+        return Ok(());
+    }
+    // mbind operates on whole pages, the pages at both ends may be shared with other buffers.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let start = addr / page_size * page_size;
+    let len = addr + len - start;
+    let (mode, mask) = policy.mode_and_mask()?;
+    let max_node = mask.len() * libc::c_ulong::BITS as usize + 1;
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            start,
+            len,
+            mode,
+            mask.as_ptr(),
+            max_node,
+            MPOL_MF_MOVE,
+        )
+    };
+    if ret != 0 {
+        crate::bail!("mbind failed: {}", std::io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// Creates a thread pool with one thread per cpu of a NUMA node. The threads are pinned to these
+/// cpus and allocate their memory on this node.
+pub fn thread_pool(node: usize) -> Result<rayon::ThreadPool> {
+    let cpus = node_cpus(node)?;
+    if cpus.is_empty() {
+        crate::bail!("numa node {node} has no cpus")
+    }
+    let num_threads = cpus.len();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(move |idx| format!("candle-numa{node}-{idx}"))
+        .start_handler(move |_idx| {
+            // Failing to pin the threads only impacts performance, so errors are ignored.
+            unsafe {
+                let mut set: libc::cpu_set_t = std::mem::zeroed();
+                for &cpu in cpus.iter() {
+                    libc::CPU_SET(cpu, &mut set)
+                }
+                libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+            }
+            let _ = set_thread_mem_policy(&MemPolicy::Preferred(node));
+        })
+        .build()
+        .map_err(Error::wrap)
+}
@@ -1,14 +1,30 @@
 use crate::backend::{BackendDevice, BackendStorage};
 use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
 use crate::{DType, Error, IntDType, Layout, Result, Shape, WithDType};
+use crate::{F8E4M3, F8E5M2};
 use half::{bf16, f16};
 use rayon::prelude::*;
 
 mod utils;
 pub use utils::{
-    binary_map, binary_map_vec, unary_map, unary_map_vec, Map1, Map1Any, Map2, Map2U8,
+    binary_map, binary_map_inplace, binary_map_vec, unary_map, unary_map_vec, Map1, Map1Any, Map2,
+    Map2U8,
 };
 
+thread_local! {
+    // The rng used by the cpu device on the current thread once a seed has been set via
+    // `set_seed`, the thread rng is used otherwise.
+    static SEEDED_RNG: std::cell::RefCell<Option<rand::rngs::StdRng>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+fn with_rng<T>(f: impl FnOnce(&mut dyn rand::RngCore) -> T) -> T {
+    SEEDED_RNG.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    })
+}
+
 const USE_IM2COL_CONV1D: bool = true;
 const USE_COL2IM_CONV1D_TR: bool = true;
 const USE_IM2COL_CONV2D: bool = true;
@@ -24,6 +40,10 @@ pub enum CpuStorage {
     F16(Vec<f16>),
     F32(Vec<f32>),
     F64(Vec<f64>),
+    F8E4M3(Vec<F8E4M3>),
+    F8E5M2(Vec<F8E5M2>),
+    // Booleans are stored using one byte per element with values 0 or 1.
+    Bool(Vec<u8>),
 }
 
 #[derive(Debug, Clone)]
@@ -35,11 +55,21 @@ pub enum CpuStorageRef<'a> {
     F16(&'a [f16]),
     F32(&'a [f32]),
     F64(&'a [f64]),
+    F8E4M3(&'a [F8E4M3]),
+    F8E5M2(&'a [F8E5M2]),
 }
 
 #[derive(Debug, Clone)]
 pub struct CpuDevice;
 
+impl CpuDevice {
+    /// Removes the seed set on the current thread via [`crate::Device::set_seed`], the random
+    /// values are then drawn from the unseeded thread rng again.
+    pub fn clear_seed() {
+        SEEDED_RNG.with(|rng| *rng.borrow_mut() = None)
+    }
+}
+
 struct Cmp(CmpOp);
 impl Map2U8 for Cmp {
     const OP: &'static str = "cmp";
@@ -615,20 +645,52 @@ struct IndexAdd<'a, I: IntDType> {
     dim: usize,
 }
 
-impl<'a, I: IntDType> Map2 for IndexAdd<'a, I> {
+// The index ops that update the slices of a contiguous destination buffer selected by some
+// indexes, either in a fresh buffer or directly in a storage.
+trait IndexUpdate {
+    const OP: &'static str;
+
+    // Updates the contiguous `dst` buffer which has the shape `dst_dims` with the source values.
+    fn update<T: WithDType>(
+        &self,
+        dst: &mut [T],
+        dst_dims: &[usize],
+        src: &[T],
+        src_l: &Layout,
+    ) -> Result<()>;
+
+    // Same as `update` but `dst` is a contiguous view of a larger storage.
+    fn update_inplace<T: WithDType>(
+        &self,
+        dst: &mut [T],
+        dst_l: &Layout,
+        src: &[T],
+        src_l: &Layout,
+    ) -> Result<()> {
+        match dst_l.contiguous_offsets() {
+            None => Err(Error::RequiresContiguous { op: Self::OP }.bt())?,
+            Some((o1, o2)) => self.update(&mut dst[o1..o2], dst_l.dims(), src, src_l),
+        }
+    }
+}
+
+impl<'a, I: IntDType> IndexUpdate for IndexAdd<'a, I> {
     const OP: &'static str = "index-add";
-    // https://pytorch.org/docs/stable/generated/torch.Tensor.index_add_.html#torch.Tensor.index_add_
-    // v1, l1 -> self
-    fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
-        let dst_len = l1.shape().elem_count();
-        let mut dst = vec![T::zero(); dst_len];
-        copy_strided_src_(v1, &mut dst, 0, l1);
+
+    // Accumulates the source values, duplicated indexes are summed.
+    fn update<T: WithDType>(
+        &self,
+        dst: &mut [T],
+        dst_dims: &[usize],
+        src: &[T],
+        src_l: &Layout,
+    ) -> Result<()> {
         let src = match src_l.contiguous_offsets() {
             None => Err(Error::RequiresContiguous { op: "index-add" }.bt())?,
             Some((o1, o2)) => &src[o1..o2],
         };
         let dim = self.dim;
-        let max_idx = l1.dims()[dim];
+        let max_idx = dst_dims[dim];
         let pre_dim = src_l.dims()[..dim].iter().product::<usize>();
         let src_dim_sz = src_l.dims()[dim];
         let post_dim = src_l.dims()[dim + 1..].iter().product::<usize>();
@@ -671,6 +733,75 @@ impl<'a, I: IntDType> Map2 for IndexAdd<'a, I> {
                 }
             }
         }
+        Ok(())
+    }
+}
+
+impl<'a, I: IntDType> Map2 for IndexAdd<'a, I> {
+    const OP: &'static str = "index-add";
+    // https://pytorch.org/docs/stable/generated/torch.Tensor.index_add_.html#torch.Tensor.index_add_
+    // v1, l1 -> self
+    fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
+        let dst_len = l1.shape().elem_count();
+        let mut dst = vec![T::zero(); dst_len];
+        copy_strided_src_(v1, &mut dst, 0, l1);
+        self.update(&mut dst, l1.dims(), src, src_l)?;
+        Ok(dst)
+    }
+}
+
+struct IndexSet<'a, I: IntDType> {
+    ids: &'a [I],
+    dim: usize,
+}
+
+impl<'a, I: IntDType> IndexUpdate for IndexSet<'a, I> {
+    const OP: &'static str = "index-set";
+
+    // Overwrites the destination values, the last write wins for duplicated indexes.
+    fn update<T: WithDType>(
+        &self,
+        dst: &mut [T],
+        dst_dims: &[usize],
+        src: &[T],
+        src_l: &Layout,
+    ) -> Result<()> {
+        let src = match src_l.contiguous_offsets() {
+            None => Err(Error::RequiresContiguous { op: "index-set" }.bt())?,
+            Some((o1, o2)) => &src[o1..o2],
+        };
+        let dim = self.dim;
+        let max_idx = dst_dims[dim];
+        let pre_dim = src_l.dims()[..dim].iter().product::<usize>();
+        let src_dim_sz = src_l.dims()[dim];
+        let post_dim = src_l.dims()[dim + 1..].iter().product::<usize>();
+        for (src_idx, dst_idx) in self.ids.iter().enumerate() {
+            let dst_idx = dst_idx.as_usize();
+            if dst_idx >= max_idx {
+                Err(Error::InvalidIndex {
+                    index: dst_idx,
+                    op: "index-set",
+                    size: max_idx,
+                })?
+            }
+            for pre_i in 0..pre_dim {
+                let pre_src_i = (pre_i * src_dim_sz + src_idx) * post_dim;
+                let pre_dst_i = (pre_i * max_idx + dst_idx) * post_dim;
+                dst[pre_dst_i..pre_dst_i + post_dim]
+                    .copy_from_slice(&src[pre_src_i..pre_src_i + post_dim]);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, I: IntDType> Map2 for IndexSet<'a, I> {
+    const OP: &'static str = "index-set";
+    fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
+        let dst_len = l1.shape().elem_count();
+        let mut dst = vec![T::zero(); dst_len];
+        copy_strided_src_(v1, &mut dst, 0, l1);
+        self.update(&mut dst, l1.dims(), src, src_l)?;
         Ok(dst)
     }
 }
@@ -760,35 +891,39 @@ impl<'a> Map2 for Conv1D<'a> {
         }
 
         for offset in 0..p.k_size {
-            (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
-                let dst_idx = dst_c_idx * l_out;
-                let k_cont = (0..p.c_in)
-                    .map(|c_in_idx| k[dst_c_idx * k_s0 + c_in_idx * k_s1 + offset * k_s2])
-                    .collect::<Vec<_>>();
-                for b_idx in 0..p.b_size {
-                    let dst_idx = dst_idx + b_idx * p.c_out * l_out;
-                    for dst_l in 0..l_out {
-                        let dst_idx = dst_idx + dst_l;
-                        let src_l = p.stride * dst_l + offset * p.dilation;
-                        if src_l < p.padding || src_l >= p.padding + p.l_in {
-                            continue;
-                        }
-                        let src_l = src_l - p.padding;
-                        let inp_cont = &inp_cont[b_idx * p.l_in * p.c_in + src_l * p.c_in..];
-                        assert!(inp_cont.len() >= p.c_in);
-                        assert!(k_cont.len() >= p.c_in);
-                        let mut d = T::zero();
-                        unsafe { T::vec_dot(inp_cont.as_ptr(), k_cont.as_ptr(), &mut d, p.c_in) }
-                        let dst_p = dst.as_ptr();
-                        // Safety: dst_idx are uniques per dst_c_idx which is used to parallelise
-                        // the different tasks so no two threads can try to write at the same
-                        // location.
-                        unsafe {
-                            let ptr = dst_p.add(dst_idx) as *mut T;
-                            *ptr += d
+            crate::utils::install(|| {
+                (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
+                    let dst_idx = dst_c_idx * l_out;
+                    let k_cont = (0..p.c_in)
+                        .map(|c_in_idx| k[dst_c_idx * k_s0 + c_in_idx * k_s1 + offset * k_s2])
+                        .collect::<Vec<_>>();
+                    for b_idx in 0..p.b_size {
+                        let dst_idx = dst_idx + b_idx * p.c_out * l_out;
+                        for dst_l in 0..l_out {
+                            let dst_idx = dst_idx + dst_l;
+                            let src_l = p.stride * dst_l + offset * p.dilation;
+                            if src_l < p.padding || src_l >= p.padding + p.l_in {
+                                continue;
+                            }
+                            let src_l = src_l - p.padding;
+                            let inp_cont = &inp_cont[b_idx * p.l_in * p.c_in + src_l * p.c_in..];
+                            assert!(inp_cont.len() >= p.c_in);
+                            assert!(k_cont.len() >= p.c_in);
+                            let mut d = T::zero();
+                            unsafe {
+                                T::vec_dot(inp_cont.as_ptr(), k_cont.as_ptr(), &mut d, p.c_in)
+                            }
+                            let dst_p = dst.as_ptr();
+                            // Safety: dst_idx are uniques per dst_c_idx which is used to parallelise
+                            // the different tasks so no two threads can try to write at the same
+                            // location.
+                            unsafe {
+                                let ptr = dst_p.add(dst_idx) as *mut T;
+                                *ptr += d
+                            }
                         }
                     }
-                }
+                })
             })
         }
         Ok(dst)
@@ -991,35 +1126,38 @@ impl<'a> Map2 for ConvTranspose1D<'a> {
         }
 
         for k_idx in 0..p.k_size {
-            (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
-                let k_cont = (0..p.c_in)
-                    .map(|c_in_idx| k[c_in_idx * k_s0 + dst_c_idx * k_s1 + k_idx * k_s2])
-                    .collect::<Vec<_>>();
-                for b_idx in 0..p.b_size {
-                    for l_idx in 0..p.l_in {
-                        let out_idx = l_idx * p.stride + k_idx * p.dilation;
-                        if out_idx < p.padding {
-                            continue;
-                        }
-                        let out_idx = out_idx - p.padding;
-                        if out_idx < l_out {
-                            let inp_cont = &inp_cont[b_idx * cont_s0 + l_idx * cont_s1..];
-                            let dst_idx = b_idx * dst_s0 + out_idx * dst_s2 + dst_c_idx * dst_s1;
-                            let mut d = T::zero();
-                            unsafe {
-                                T::vec_dot(inp_cont.as_ptr(), k_cont.as_ptr(), &mut d, p.c_in)
+            crate::utils::install(|| {
+                (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
+                    let k_cont = (0..p.c_in)
+                        .map(|c_in_idx| k[c_in_idx * k_s0 + dst_c_idx * k_s1 + k_idx * k_s2])
+                        .collect::<Vec<_>>();
+                    for b_idx in 0..p.b_size {
+                        for l_idx in 0..p.l_in {
+                            let out_idx = l_idx * p.stride + k_idx * p.dilation;
+                            if out_idx < p.padding {
+                                continue;
                             }
-                            let dst_p = dst.as_ptr();
-                            // Safety: dst_idx are uniques per dst_c_idx which is used to
-                            // parallelise the different tasks so no two threads can try to
-                            // write at the same location.
-                            unsafe {
-                                let ptr = dst_p.add(dst_idx) as *mut T;
-                                *ptr += d
+                            let out_idx = out_idx - p.padding;
+                            if out_idx < l_out {
+                                let inp_cont = &inp_cont[b_idx * cont_s0 + l_idx * cont_s1..];
+                                let dst_idx =
+                                    b_idx * dst_s0 + out_idx * dst_s2 + dst_c_idx * dst_s1;
+                                let mut d = T::zero();
+                                unsafe {
+                                    T::vec_dot(inp_cont.as_ptr(), k_cont.as_ptr(), &mut d, p.c_in)
+                                }
+                                let dst_p = dst.as_ptr();
+                                // Safety: dst_idx are uniques per dst_c_idx which is used to
+                                // parallelise the different tasks so no two threads can try to
+                                // write at the same location.
+                                unsafe {
+                                    let ptr = dst_p.add(dst_idx) as *mut T;
+                                    *ptr += d
+                                }
                             }
                         }
                     }
-                }
+                })
             })
         }
         Ok(dst)
@@ -1061,51 +1199,58 @@ impl<'a> Map2 for Conv2D<'a> {
 
         for offset_h in 0..p.k_h {
             for offset_w in 0..p.k_w {
-                (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
-                    let dst_idx = dst_c_idx * out_w * out_h;
-                    let k_cont = (0..p.c_in)
-                        .map(|c_in_idx| {
-                            k[dst_c_idx * k_s0
-                                + c_in_idx * k_s1
-                                + offset_h * k_s2
-                                + offset_w * k_s3]
-                        })
-                        .collect::<Vec<_>>();
-                    for b_idx in 0..p.b_size {
-                        let dst_idx = dst_idx + b_idx * p.c_out * out_h * out_w;
-                        for dst_h in 0..out_h {
-                            let dst_idx = dst_idx + dst_h * out_w;
-                            let src_h = p.stride * dst_h + offset_h * p.dilation;
-                            if src_h < p.padding || src_h >= p.i_h + p.padding {
-                                continue;
-                            }
-                            let src_h = src_h - p.padding;
-                            for dst_w in 0..out_w {
-                                let dst_idx = dst_idx + dst_w;
-                                let src_w = p.stride * dst_w + offset_w * p.dilation;
-                                if src_w < p.padding || src_w >= p.i_w + p.padding {
+                crate::utils::install(|| {
+                    (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
+                        let dst_idx = dst_c_idx * out_w * out_h;
+                        let k_cont = (0..p.c_in)
+                            .map(|c_in_idx| {
+                                k[dst_c_idx * k_s0
+                                    + c_in_idx * k_s1
+                                    + offset_h * k_s2
+                                    + offset_w * k_s3]
+                            })
+                            .collect::<Vec<_>>();
+                        for b_idx in 0..p.b_size {
+                            let dst_idx = dst_idx + b_idx * p.c_out * out_h * out_w;
+                            for dst_h in 0..out_h {
+                                let dst_idx = dst_idx + dst_h * out_w;
+                                let src_h = p.stride * dst_h + offset_h * p.dilation;
+                                if src_h < p.padding || src_h >= p.i_h + p.padding {
                                     continue;
                                 }
-                                let src_w = src_w - p.padding;
-                                let inp_cont = &inp_cont
-                                    [b_idx * cont_s0 + src_h * cont_s1 + src_w * cont_s2..];
-                                assert!(inp_cont.len() >= p.c_in);
-                                assert!(k_cont.len() >= p.c_in);
-                                let mut d = T::zero();
-                                unsafe {
-                                    T::vec_dot(inp_cont.as_ptr(), k_cont.as_ptr(), &mut d, p.c_in)
-                                }
-                                let dst_p = dst.as_ptr();
-                                // Safety: dst_idx are uniques per dst_c_idx which is used to parallelise
-                                // the different tasks so no two threads can try to write at the same
-                                // location.
-                                unsafe {
-                                    let ptr = dst_p.add(dst_idx) as *mut T;
-                                    *ptr += d
+                                let src_h = src_h - p.padding;
+                                for dst_w in 0..out_w {
+                                    let dst_idx = dst_idx + dst_w;
+                                    let src_w = p.stride * dst_w + offset_w * p.dilation;
+                                    if src_w < p.padding || src_w >= p.i_w + p.padding {
+                                        continue;
+                                    }
+                                    let src_w = src_w - p.padding;
+                                    let inp_cont = &inp_cont
+                                        [b_idx * cont_s0 + src_h * cont_s1 + src_w * cont_s2..];
+                                    assert!(inp_cont.len() >= p.c_in);
+                                    assert!(k_cont.len() >= p.c_in);
+                                    let mut d = T::zero();
+                                    unsafe {
+                                        T::vec_dot(
+                                            inp_cont.as_ptr(),
+                                            k_cont.as_ptr(),
+                                            &mut d,
+                                            p.c_in,
+                                        )
+                                    }
+                                    let dst_p = dst.as_ptr();
+                                    // Safety: dst_idx are uniques per dst_c_idx which is used to parallelise
+                                    // the different tasks so no two threads can try to write at the same
+                                    // location.
+                                    unsafe {
+                                        let ptr = dst_p.add(dst_idx) as *mut T;
+                                        *ptr += d
+                                    }
                                 }
                             }
                         }
-                    }
+                    })
                 });
             }
         }
@@ -1153,50 +1298,52 @@ impl<'a> Map2 for ConvTranspose2D<'a> {
 
         for k_y in 0..p.k_h {
             for k_x in 0..p.k_w {
-                (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
-                    let k_cont = (0..p.c_in)
-                        .map(|c_in_idx| {
-                            k[c_in_idx * k_s0 + dst_c_idx * k_s1 + k_y * k_s2 + k_x * k_s3]
-                        })
-                        .collect::<Vec<_>>();
-                    for b_idx in 0..p.b_size {
-                        for inp_y in 0..p.i_h {
-                            for inp_x in 0..p.i_w {
-                                let out_x = inp_x * p.stride + k_x * p.dilation;
-                                let out_y = inp_y * p.stride + k_y * p.dilation;
-                                if out_x < p.padding || out_y < p.padding {
-                                    continue;
-                                }
-                                let out_x = out_x - p.padding;
-                                let out_y = out_y - p.padding;
-                                if out_x < out_w && out_y < out_h {
-                                    let inp_cont = &inp_cont
-                                        [b_idx * cont_s0 + inp_y * cont_s1 + inp_x * cont_s2..];
-                                    let dst_idx = b_idx * dst_s0
-                                        + out_y * dst_s2
-                                        + out_x * dst_s3
-                                        + dst_c_idx * dst_s1;
-                                    let mut d = T::zero();
-                                    unsafe {
-                                        T::vec_dot(
-                                            inp_cont.as_ptr(),
-                                            k_cont.as_ptr(),
-                                            &mut d,
-                                            p.c_in,
-                                        )
+                crate::utils::install(|| {
+                    (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
+                        let k_cont = (0..p.c_in)
+                            .map(|c_in_idx| {
+                                k[c_in_idx * k_s0 + dst_c_idx * k_s1 + k_y * k_s2 + k_x * k_s3]
+                            })
+                            .collect::<Vec<_>>();
+                        for b_idx in 0..p.b_size {
+                            for inp_y in 0..p.i_h {
+                                for inp_x in 0..p.i_w {
+                                    let out_x = inp_x * p.stride + k_x * p.dilation;
+                                    let out_y = inp_y * p.stride + k_y * p.dilation;
+                                    if out_x < p.padding || out_y < p.padding {
+                                        continue;
                                     }
-                                    let dst_p = dst.as_ptr();
-                                    // Safety: dst_idx are uniques per dst_c_idx which is used to
-                                    // parallelise the different tasks so no two threads can try to
-                                    // write at the same location.
-                                    unsafe {
-                                        let ptr = dst_p.add(dst_idx) as *mut T;
-                                        *ptr += d
+                                    let out_x = out_x - p.padding;
+                                    let out_y = out_y - p.padding;
+                                    if out_x < out_w && out_y < out_h {
+                                        let inp_cont = &inp_cont
+                                            [b_idx * cont_s0 + inp_y * cont_s1 + inp_x * cont_s2..];
+                                        let dst_idx = b_idx * dst_s0
+                                            + out_y * dst_s2
+                                            + out_x * dst_s3
+                                            + dst_c_idx * dst_s1;
+                                        let mut d = T::zero();
+                                        unsafe {
+                                            T::vec_dot(
+                                                inp_cont.as_ptr(),
+                                                k_cont.as_ptr(),
+                                                &mut d,
+                                                p.c_in,
+                                            )
+                                        }
+                                        let dst_p = dst.as_ptr();
+                                        // Safety: dst_idx are uniques per dst_c_idx which is used to
+                                        // parallelise the different tasks so no two threads can try to
+                                        // write at the same location.
+                                        unsafe {
+                                            let ptr = dst_p.add(dst_idx) as *mut T;
+                                            *ptr += d
+                                        }
                                     }
                                 }
                             }
                         }
-                    }
+                    })
                 })
             }
         }
@@ -1288,34 +1435,37 @@ impl Map2 for MatMul {
         } else {
             Parallelism::None
         };
-        for step in 0..b {
-            let lhs_p = &lhs[step * a_skip..];
-            let rhs_p = &rhs[step * b_skip..];
-            let dst_p = &mut dst[step * c_skip..];
-            unsafe {
-                gemm(
-                    /* m: usize = */ m,
-                    /* n: usize = */ n,
-                    /* k: usize = */ k,
-                    /* dst: *mut T = */ dst_p.as_mut_ptr(),
-                    /* dst_cs: isize = */ dst_cs as isize,
-                    /* dst_rs: isize = */ dst_rs as isize,
-                    /* read_dst: bool = */ false,
-                    /* lhs: *const T = */ lhs_p.as_ptr(),
-                    /* lhs_cs: isize = */ lhs_cs as isize,
-                    /* lhs_rs: isize = */ lhs_rs as isize,
-                    /* rhs: *const T = */ rhs_p.as_ptr(),
-                    /* rhs_cs: isize = */ rhs_cs as isize,
-                    /* rhs_rs: isize = */ rhs_rs as isize,
-                    /* alpha: T = */ T::zero(),
-                    /* beta: T = */ T::one(),
-                    /* conj_dst: bool = */ false,
-                    /* conj_lhs: bool = */ false,
-                    /* conj_rhs: bool = */ false,
-                    parallelism,
-                )
+        // The gemm parallelism relies on the current rayon thread pool.
+        crate::utils::install(|| {
+            for step in 0..b {
+                let lhs_p = &lhs[step * a_skip..];
+                let rhs_p = &rhs[step * b_skip..];
+                let dst_p = &mut dst[step * c_skip..];
+                unsafe {
+                    gemm(
+                        /* m: usize = */ m,
+                        /* n: usize = */ n,
+                        /* k: usize = */ k,
+                        /* dst: *mut T = */ dst_p.as_mut_ptr(),
+                        /* dst_cs: isize = */ dst_cs as isize,
+                        /* dst_rs: isize = */ dst_rs as isize,
+                        /* read_dst: bool = */ false,
+                        /* lhs: *const T = */ lhs_p.as_ptr(),
+                        /* lhs_cs: isize = */ lhs_cs as isize,
+                        /* lhs_rs: isize = */ lhs_rs as isize,
+                        /* rhs: *const T = */ rhs_p.as_ptr(),
+                        /* rhs_cs: isize = */ rhs_cs as isize,
+                        /* rhs_rs: isize = */ rhs_rs as isize,
+                        /* alpha: T = */ T::zero(),
+                        /* beta: T = */ T::one(),
+                        /* conj_dst: bool = */ false,
+                        /* conj_lhs: bool = */ false,
+                        /* conj_rhs: bool = */ false,
+                        parallelism,
+                    )
+                }
             }
-        }
+        });
         Ok(dst)
     }
 
@@ -1360,8 +1510,18 @@ impl Map2 for MatMul {
 
         let mut dst = vec![T::zero(); b * m * n];
         match T::DTYPE {
-            DType::F16 => {
-                crate::bail!("the accelerate backend does not support f16 matmul")
+            DType::F16 | DType::BF16 => {
+                // Accelerate has no half precision gemm, the operands are converted to f32 and
+                // the result is converted back.
+                let lhs = lhs.iter().map(|v| v.to_f64() as f32).collect::<Vec<_>>();
+                let rhs = rhs.iter().map(|v| v.to_f64() as f32).collect::<Vec<_>>();
+                // The operands have already been offset above.
+                let lhs_l = Layout::new(lhs_l.shape().clone(), lhs_l.stride().to_vec(), 0);
+                let rhs_l = Layout::new(rhs_l.shape().clone(), rhs_l.stride().to_vec(), 0);
+                let dst_f32 = self.f::<f32>(&lhs, &lhs_l, &rhs, &rhs_l)?;
+                for (d, v) in dst.iter_mut().zip(dst_f32) {
+                    *d = T::from_f64(v as f64)
+                }
             }
             DType::F32 => {
                 for step in 0..b {
@@ -1622,9 +1782,173 @@ impl CpuStorage {
                     .concat();
                 Self::F64(storages)
             }
+            Self::F8E4M3(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::F8E4M3(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::F8E4M3(storages)
+            }
+            Self::F8E5M2(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::F8E5M2(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::F8E5M2(storages)
+            }
+            Self::Bool(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::Bool(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::Bool(storages)
+            }
         };
         Ok(s)
     }
+
+    /// Applies the binary operation `B` in place, `self` has to be contiguous and `rhs` has to
+    /// have the same shape, possibly through broadcasting.
+    pub(crate) fn binary_impl_inplace<B: BinaryOpT>(
+        &mut self,
+        rhs: &Self,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<()> {
+        let lhs_dtype = self.dtype();
+        match (self, rhs) {
+            (Self::BF16(lhs), Self::BF16(rhs)) => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::bf16)
+            }
+            (Self::F16(lhs), Self::F16(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f16),
+            (Self::F32(lhs), Self::F32(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f32),
+            (Self::F64(lhs), Self::F64(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f64),
+            (Self::U32(lhs), Self::U32(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::u32),
+            (Self::I64(lhs), Self::I64(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::i64),
+            (Self::U8(lhs), Self::U8(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::u8),
+            (Self::Bool(lhs), Self::Bool(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::u8),
+            (Self::F8E4M3(lhs), Self::F8E4M3(rhs)) => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, |l, r| {
+                    F8E4M3::from_f32(B::f32(l.to_f32(), r.to_f32()))
+                })
+            }
+            (Self::F8E5M2(lhs), Self::F8E5M2(rhs)) => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, |l, r| {
+                    F8E5M2::from_f32(B::f32(l.to_f32(), r.to_f32()))
+                })
+            }
+            (_, rhs) => Err(Error::DTypeMismatchBinaryOp {
+                lhs: lhs_dtype,
+                rhs: rhs.dtype(),
+                op: B::NAME,
+            }
+            .bt()),
+        }
+    }
+
+    /// Accumulates the values of `src` at the positions `ids` along `dim` directly in the storage
+    /// of `self`, the layout `l` has to be contiguous and the indexes are not deduplicated.
+    pub(crate) fn index_add_inplace(
+        &mut self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<()> {
+        self.index_update_inplace(l, ids, ids_l, src, src_l, dim, true)
+    }
+
+    /// Writes the values of `src` at the positions `ids` along `dim` directly in the storage of
+    /// `self`, the layout `l` has to be contiguous and the last write wins for duplicated indexes.
+    pub(crate) fn index_set_inplace(
+        &mut self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<()> {
+        self.index_update_inplace(l, ids, ids_l, src, src_l, dim, false)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn index_update_inplace(
+        &mut self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+        accumulate: bool,
+    ) -> Result<()> {
+        fn update<O: IndexUpdate>(
+            op: O,
+            dst: &mut CpuStorage,
+            l: &Layout,
+            src: &CpuStorage,
+            src_l: &Layout,
+        ) -> Result<()> {
+            let dst_dtype = dst.dtype();
+            match (dst, src) {
+                (CpuStorage::BF16(d), CpuStorage::BF16(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::F16(d), CpuStorage::F16(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::F32(d), CpuStorage::F32(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::F64(d), CpuStorage::F64(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::U8(d), CpuStorage::U8(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::U32(d), CpuStorage::U32(s)) => op.update_inplace(d, l, s, src_l),
+                (CpuStorage::I64(d), CpuStorage::I64(s)) => op.update_inplace(d, l, s, src_l),
+                (_, src) => Err(Error::DTypeMismatchBinaryOp {
+                    lhs: dst_dtype,
+                    rhs: src.dtype(),
+                    op: O::OP,
+                }
+                .bt()),
+            }
+        }
+        fn inner<I: IntDType>(
+            dst: &mut CpuStorage,
+            l: &Layout,
+            ids: &[I],
+            src: &CpuStorage,
+            src_l: &Layout,
+            dim: usize,
+            accumulate: bool,
+        ) -> Result<()> {
+            if accumulate {
+                update(IndexAdd { ids, dim }, dst, l, src, src_l)
+            } else {
+                update(IndexSet { ids, dim }, dst, l, src, src_l)
+            }
+        }
+        let op = if accumulate { "index-add" } else { "index-set" };
+        let ids_offsets = match ids_l.contiguous_offsets() {
+            Some(offsets) => offsets,
+            None => Err(Error::RequiresContiguous { op }.bt())?,
+        };
+        let (a, b) = ids_offsets;
+        match ids {
+            Self::U8(ids) => inner(self, l, &ids[a..b], src, src_l, dim, accumulate),
+            Self::U32(ids) => inner(self, l, &ids[a..b], src, src_l, dim, accumulate),
+            Self::I64(ids) => inner(self, l, &ids[a..b], src, src_l, dim, accumulate),
+            _ => Err(Error::UnsupportedDTypeForOp(ids.dtype(), op).bt()),
+        }
+    }
 }
 
 impl BackendStorage for CpuStorage {
@@ -1639,6 +1963,9 @@ impl BackendStorage for CpuStorage {
             Self::F16(_) => DType::F16,
             Self::F32(_) => DType::F32,
             Self::F64(_) => DType::F64,
+            Self::F8E4M3(_) => DType::F8E4M3,
+            Self::F8E5M2(_) => DType::F8E5M2,
+            Self::Bool(_) => DType::Bool,
         }
     }
 
@@ -1841,6 +2168,82 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| v);
                 Ok(Self::F64(data))
             }
+            // The fp8 conversions go through f32.
+            (Self::F8E4M3(storage), DType::F8E4M3) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::F8E4M3(data))
+            }
+            (Self::F8E5M2(storage), DType::F8E5M2) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::F8E5M2(data))
+            }
+            (Self::Bool(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::Bool(data))
+            }
+            (Self::Bool(storage), dtype) => {
+                let data = unary_map(storage, layout, |v| v);
+                let layout = Layout::contiguous(layout.shape());
+                Self::U8(data).to_dtype(&layout, dtype)
+            }
+            (Self::U8(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != 0));
+                Ok(Self::Bool(data))
+            }
+            (Self::U32(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != 0));
+                Ok(Self::Bool(data))
+            }
+            (Self::I64(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != 0));
+                Ok(Self::Bool(data))
+            }
+            (Self::BF16(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != bf16::ZERO));
+                Ok(Self::Bool(data))
+            }
+            (Self::F16(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != f16::ZERO));
+                Ok(Self::Bool(data))
+            }
+            (Self::F32(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != 0.));
+                Ok(Self::Bool(data))
+            }
+            (Self::F64(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v != 0.));
+                Ok(Self::Bool(data))
+            }
+            (Self::F8E4M3(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v.to_f32() != 0.));
+                Ok(Self::Bool(data))
+            }
+            (Self::F8E5M2(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| u8::from(v.to_f32() != 0.));
+                Ok(Self::Bool(data))
+            }
+            (Self::F8E4M3(storage), dtype) => {
+                let data = unary_map(storage, layout, |v| v.to_f32());
+                let layout = Layout::contiguous(layout.shape());
+                Self::F32(data).to_dtype(&layout, dtype)
+            }
+            (Self::F8E5M2(storage), dtype) => {
+                let data = unary_map(storage, layout, |v| v.to_f32());
+                let layout = Layout::contiguous(layout.shape());
+                Self::F32(data).to_dtype(&layout, dtype)
+            }
+            (_, DType::F8E4M3) => match self.to_dtype(layout, DType::F32)? {
+                Self::F32(data) => Ok(Self::F8E4M3(
+                    data.into_iter().map(F8E4M3::from_f32).collect(),
+                )),
+                _ => unreachable!(),
+            },
+            (_, DType::F8E5M2) => match self.to_dtype(layout, DType::F32)? {
+                Self::F32(data) => Ok(Self::F8E5M2(
+                    data.into_iter().map(F8E5M2::from_f32).collect(),
+                )),
+                _ => unreachable!(),
+            },
         }
     }
 
@@ -1905,6 +2308,22 @@ impl BackendStorage for CpuStorage {
     }
 
     fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
+        #[cfg(feature = "accelerate")]
+        if let Some((o1, o2)) = layout.contiguous_offsets() {
+            match self {
+                Self::F32(vs) => {
+                    let mut ys = vec![0f32; o2 - o1];
+                    crate::accelerate::vs_affine(&vs[o1..o2], mul as f32, add as f32, &mut ys);
+                    return Ok(Self::F32(ys));
+                }
+                Self::F64(vs) => {
+                    let mut ys = vec![0f64; o2 - o1];
+                    crate::accelerate::vd_affine(&vs[o1..o2], mul, add, &mut ys);
+                    return Ok(Self::F64(ys));
+                }
+                _ => {}
+            }
+        }
         Affine(mul, add).map(self, layout)
     }
 
@@ -1954,9 +2373,22 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| v.powf(e));
                 Ok(Self::F64(data))
             }
+            Self::F8E4M3(storage) => {
+                let data = unary_map(storage, layout, |v| {
+                    F8E4M3::from_f32(v.to_f32().powf(e as f32))
+                });
+                Ok(Self::F8E4M3(data))
+            }
+            Self::F8E5M2(storage) => {
+                let data = unary_map(storage, layout, |v| {
+                    F8E5M2::from_f32(v.to_f32().powf(e as f32))
+                });
+                Ok(Self::F8E5M2(data))
+            }
             Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
             Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
             Self::I64(_) => Err(Error::UnsupportedDTypeForOp(DType::I64, "elu").bt()),
+            Self::Bool(_) => Err(Error::UnsupportedDTypeForOp(DType::Bool, "powf").bt()),
         }
     }
 
@@ -1979,9 +2411,22 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| elu(v, alpha));
                 Ok(Self::F64(data))
             }
+            Self::F8E4M3(storage) => {
+                let data = unary_map(storage, layout, |v| {
+                    F8E4M3::from_f32(elu(v.to_f32(), alpha as f32))
+                });
+                Ok(Self::F8E4M3(data))
+            }
+            Self::F8E5M2(storage) => {
+                let data = unary_map(storage, layout, |v| {
+                    F8E5M2::from_f32(elu(v.to_f32(), alpha as f32))
+                });
+                Ok(Self::F8E5M2(data))
+            }
             Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
             Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
             Self::I64(_) => Err(Error::UnsupportedDTypeForOp(DType::I64, "elu").bt()),
+            Self::Bool(_) => Err(Error::UnsupportedDTypeForOp(DType::Bool, "elu").bt()),
         }
     }
 
@@ -2035,6 +2480,15 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, B::i64);
                 Ok(Self::I64(data))
             }
+            Self::F8E4M3(storage) => {
+                let data = unary_map(storage, layout, |v| F8E4M3::from_f32(B::f32(v.to_f32())));
+                Ok(Self::F8E4M3(data))
+            }
+            Self::F8E5M2(storage) => {
+                let data = unary_map(storage, layout, |v| F8E5M2::from_f32(B::f32(v.to_f32())));
+                Ok(Self::F8E5M2(data))
+            }
+            Self::Bool(_) => Err(Error::UnsupportedDTypeForOp(DType::Bool, B::NAME).bt()),
         }
     }
 
@@ -2101,6 +2555,23 @@ impl BackendStorage for CpuStorage {
                 };
                 Ok(Self::U8(data))
             }
+            (Self::F8E4M3(lhs), Self::F8E4M3(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, |l, r| {
+                    F8E4M3::from_f32(B::f32(l.to_f32(), r.to_f32()))
+                });
+                Ok(Self::F8E4M3(data))
+            }
+            (Self::F8E5M2(lhs), Self::F8E5M2(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, |l, r| {
+                    F8E5M2::from_f32(B::f32(l.to_f32(), r.to_f32()))
+                });
+                Ok(Self::F8E5M2(data))
+            }
+            // Only the logical ops are allowed on booleans, see `Storage::binary_impl`.
+            (Self::Bool(lhs), Self::Bool(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::u8);
+                Ok(Self::Bool(data))
+            }
             _ => {
                 // This should be covered by the dtype check above.
                 Err(Error::DTypeMismatchBinaryOp {
@@ -2143,6 +2614,15 @@ impl BackendStorage for CpuStorage {
             (Self::F64(src), Self::F64(dst)) => {
                 copy2d_(src, dst, d1, d2, src_s, dst_s, src_o, dst_o)
             }
+            (Self::Bool(src), Self::Bool(dst)) => {
+                copy2d_(src, dst, d1, d2, src_s, dst_s, src_o, dst_o)
+            }
+            (Self::F8E4M3(src), Self::F8E4M3(dst)) => {
+                copy2d_(src, dst, d1, d2, src_s, dst_s, src_o, dst_o)
+            }
+            (Self::F8E5M2(src), Self::F8E5M2(dst)) => {
+                copy2d_(src, dst, d1, d2, src_s, dst_s, src_o, dst_o)
+            }
             (_, dst) => {
                 return Err(Error::DTypeMismatchBinaryOp {
                     lhs: self.dtype(),
@@ -2164,6 +2644,13 @@ impl BackendStorage for CpuStorage {
             (Self::F16(src), Self::F16(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::F32(src), Self::F32(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::F64(src), Self::F64(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::Bool(src), Self::Bool(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::F8E4M3(src), Self::F8E4M3(dst)) => {
+                copy_strided_src_(src, dst, dst_offset, src_l)
+            }
+            (Self::F8E5M2(src), Self::F8E5M2(dst)) => {
+                copy_strided_src_(src, dst, dst_offset, src_l)
+            }
             (_, dst) => {
                 // This should be covered by the dtype check above.
                 return Err(Error::DTypeMismatchBinaryOp {
@@ -2189,6 +2676,7 @@ impl BackendStorage for CpuStorage {
             Self::U8(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             Self::U32(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             Self::I64(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
+            Self::Bool(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "where-cond")),
         }
     }
@@ -2228,10 +2716,10 @@ impl BackendStorage for CpuStorage {
                     .alloc_uninit(kernel_l.shape(), kernel.dtype())?
             };
             kernel.copy_strided_src(&mut kernel_c, 0, kernel_l)?;
-            let kernel_l = Layout::contiguous_with_offset((1, n, k), kernel_l.start_offset())
+            let kernel_l = Layout::contiguous((1, n, k))
                 .transpose(1, 2)?
                 .broadcast_as((b, k, n))?;
-            col.matmul(kernel, (b, m, n, k), &col_l, &kernel_l)?
+            col.matmul(&kernel_c, (b, m, n, k), &col_l, &kernel_l)?
         };
         let res_l = Layout::contiguous((b, l_out, params.c_out)).transpose(1, 2)?;
         let mut res_t = unsafe { self.device().alloc_uninit(res_l.shape(), res.dtype())? };
@@ -2330,10 +2818,10 @@ impl BackendStorage for CpuStorage {
                     .alloc_uninit(kernel_l.shape(), kernel.dtype())?
             };
             kernel.copy_strided_src(&mut kernel_c, 0, kernel_l)?;
-            let kernel_l = Layout::contiguous_with_offset((1, n, k), kernel_l.start_offset())
+            let kernel_l = Layout::contiguous((1, n, k))
                 .transpose(1, 2)?
                 .broadcast_as((b, k, n))?;
-            col.matmul(kernel, (b, m, n, k), &col_l, &kernel_l)?
+            col.matmul(&kernel_c, (b, m, n, k), &col_l, &kernel_l)?
         };
         let res_l = Layout::contiguous((b, h_out, w_out, params.c_out))
             .transpose(1, 2)?
@@ -2423,6 +2911,41 @@ impl BackendStorage for CpuStorage {
         }
     }
 
+    fn index_set(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        match ids {
+            Self::U8(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-set" }.bt())?,
+                };
+                IndexSet { ids, dim }.map(self, l, src, src_l)
+            }
+            Self::U32(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-set" }.bt())?,
+                };
+                IndexSet { ids, dim }.map(self, l, src, src_l)
+            }
+            Self::I64(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-set" }.bt())?,
+                };
+                IndexSet { ids, dim }.map(self, l, src, src_l)
+            }
+            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "index-set").bt()),
+        }
+    }
+
     fn matmul(
         &self,
         rhs: &Self,
@@ -2430,7 +2953,53 @@ impl BackendStorage for CpuStorage {
         lhs_l: &Layout,
         rhs_l: &Layout,
     ) -> Result<Self> {
-        MatMul(bmnk).map(self, lhs_l, rhs, rhs_l)
+        match (self, rhs) {
+            // The gemm kernels do not support bf16 so the operands are widened to f32, the
+            // accumulation then happens in f32 and the result is rounded back to bf16 once.
+            // Only the part of the storage addressed by the layouts gets converted so that
+            // matmuls on small views of large buffers do not widen the whole buffer.
+            (Self::BF16(lhs), Self::BF16(rhs)) => {
+                use crate::cpu::bf16_vec;
+                let widen = |data: &[bf16], l: &Layout| {
+                    let start = l.start_offset();
+                    let len = if l.shape().elem_count() == 0 {
+                        0
+                    } else {
+                        let dims = l.dims().iter().zip(l.stride());
+                        dims.map(|(&d, &s)| (d - 1) * s).sum::<usize>() + 1
+                    };
+                    let data = &data[start..start + len];
+                    let mut data_f32 = vec![0f32; len];
+                    bf16_vec::to_f32_slice(data, &mut data_f32);
+                    let l = Layout::new(l.shape().clone(), l.stride().to_vec(), 0);
+                    (data_f32, l)
+                };
+                let (lhs_f32, lhs_l) = widen(lhs, lhs_l);
+                let (rhs_f32, rhs_l) = widen(rhs, rhs_l);
+                let dst_f32 = MatMul(bmnk).f(&lhs_f32, &lhs_l, &rhs_f32, &rhs_l)?;
+                let mut dst = vec![bf16::ZERO; dst_f32.len()];
+                bf16_vec::from_f32_slice(&dst_f32, &mut dst);
+                Ok(Self::BF16(dst))
+            }
+            // Same for the 8 bits floats.
+            (Self::F8E4M3(lhs), Self::F8E4M3(rhs)) => {
+                let lhs: Vec<f32> = lhs.iter().map(|v| v.to_f32()).collect();
+                let rhs: Vec<f32> = rhs.iter().map(|v| v.to_f32()).collect();
+                let dst = MatMul(bmnk).f(&lhs, lhs_l, &rhs, rhs_l)?;
+                Ok(Self::F8E4M3(
+                    dst.into_iter().map(F8E4M3::from_f32).collect(),
+                ))
+            }
+            (Self::F8E5M2(lhs), Self::F8E5M2(rhs)) => {
+                let lhs: Vec<f32> = lhs.iter().map(|v| v.to_f32()).collect();
+                let rhs: Vec<f32> = rhs.iter().map(|v| v.to_f32()).collect();
+                let dst = MatMul(bmnk).f(&lhs, lhs_l, &rhs, rhs_l)?;
+                Ok(Self::F8E5M2(
+                    dst.into_iter().map(F8E5M2::from_f32).collect(),
+                ))
+            }
+            _ => MatMul(bmnk).map(self, lhs_l, rhs, rhs_l),
+        }
     }
 
     fn device(&self) -> &Self::Device {
@@ -2473,17 +3042,18 @@ impl BackendDevice for CpuDevice {
         Ok(Self)
     }
 
-    fn set_seed(&self, _seed: u64) -> Result<()> {
-        crate::bail!("cannot seed the CPU rng with set_seed")
+    fn set_seed(&self, seed: u64) -> Result<()> {
+        use rand::SeedableRng;
+        SEEDED_RNG.with(|rng| *rng.borrow_mut() = Some(rand::rngs::StdRng::seed_from_u64(seed)));
+        Ok(())
     }
 
     fn rand_uniform(&self, shape: &Shape, dtype: DType, min: f64, max: f64) -> Result<CpuStorage> {
         use rand::prelude::*;
 
         let elem_count = shape.elem_count();
-        let mut rng = rand::thread_rng();
-        match dtype {
-            DType::U8 | DType::U32 | DType::I64 => {
+        with_rng(|rng| match dtype {
+            DType::U8 | DType::U32 | DType::I64 | DType::F8E4M3 | DType::F8E5M2 | DType::Bool => {
                 Err(Error::UnsupportedDTypeForOp(dtype, "rand_uniform").bt())
             }
             DType::BF16 => {
@@ -2520,16 +3090,15 @@ impl BackendDevice for CpuDevice {
                 }
                 Ok(CpuStorage::F64(data))
             }
-        }
+        })
     }
 
     fn rand_normal(&self, shape: &Shape, dtype: DType, mean: f64, std: f64) -> Result<CpuStorage> {
         use rand::prelude::*;
 
         let elem_count = shape.elem_count();
-        let mut rng = rand::thread_rng();
-        match dtype {
-            DType::U8 | DType::U32 | DType::I64 => {
+        with_rng(|rng| match dtype {
+            DType::U8 | DType::U32 | DType::I64 | DType::F8E4M3 | DType::F8E5M2 | DType::Bool => {
                 Err(Error::UnsupportedDTypeForOp(dtype, "rand_normal").bt())
             }
             DType::BF16 => {
@@ -2537,7 +3106,7 @@ impl BackendDevice for CpuDevice {
                 let normal = rand_distr::Normal::new(bf16::from_f64(mean), bf16::from_f64(std))
                     .map_err(Error::wrap)?;
                 for _i in 0..elem_count {
-                    data.push(normal.sample(&mut rng))
+                    data.push(normal.sample(rng))
                 }
                 Ok(CpuStorage::BF16(data))
             }
@@ -2546,7 +3115,7 @@ impl BackendDevice for CpuDevice {
                 let normal = rand_distr::Normal::new(f16::from_f64(mean), f16::from_f64(std))
                     .map_err(Error::wrap)?;
                 for _i in 0..elem_count {
-                    data.push(normal.sample(&mut rng))
+                    data.push(normal.sample(rng))
                 }
                 Ok(CpuStorage::F16(data))
             }
@@ -2555,7 +3124,7 @@ impl BackendDevice for CpuDevice {
                 let normal =
                     rand_distr::Normal::new(mean as f32, std as f32).map_err(Error::wrap)?;
                 for _i in 0..elem_count {
-                    data.push(normal.sample(&mut rng))
+                    data.push(normal.sample(rng))
                 }
                 Ok(CpuStorage::F32(data))
             }
@@ -2563,11 +3132,11 @@ impl BackendDevice for CpuDevice {
                 let mut data = Vec::with_capacity(elem_count);
                 let normal = rand_distr::Normal::new(mean, std).map_err(Error::wrap)?;
                 for _i in 0..elem_count {
-                    data.push(normal.sample(&mut rng))
+                    data.push(normal.sample(rng))
                 }
                 Ok(CpuStorage::F64(data))
             }
-        }
+        })
     }
 
     #[allow(clippy::uninit_vec)]
@@ -2613,6 +3182,21 @@ impl BackendDevice for CpuDevice {
                 v.set_len(elem_count);
                 CpuStorage::F64(v)
             }
+            DType::F8E4M3 => {
+                let mut v = Vec::with_capacity(elem_count);
+                v.set_len(elem_count);
+                CpuStorage::F8E4M3(v)
+            }
+            DType::F8E5M2 => {
+                let mut v = Vec::with_capacity(elem_count);
+                v.set_len(elem_count);
+                CpuStorage::F8E5M2(v)
+            }
+            DType::Bool => {
+                let mut v = Vec::with_capacity(elem_count);
+                v.set_len(elem_count);
+                CpuStorage::Bool(v)
+            }
         };
         Ok(storage)
     }
@@ -2627,6 +3211,9 @@ impl BackendDevice for CpuDevice {
             DType::F16 => CpuStorage::F16(vec![f16::ONE; elem_count]),
             DType::F32 => CpuStorage::F32(vec![1f32; elem_count]),
             DType::F64 => CpuStorage::F64(vec![1f64; elem_count]),
+            DType::F8E4M3 => CpuStorage::F8E4M3(vec![F8E4M3::from_f32(1.); elem_count]),
+            DType::F8E5M2 => CpuStorage::F8E5M2(vec![F8E5M2::from_f32(1.); elem_count]),
+            DType::Bool => CpuStorage::Bool(vec![1u8; elem_count]),
         };
         Ok(storage)
     }
@@ -2641,6 +3228,9 @@ impl BackendDevice for CpuDevice {
             DType::F16 => CpuStorage::F16(vec![f16::ZERO; elem_count]),
             DType::F32 => CpuStorage::F32(vec![0f32; elem_count]),
             DType::F64 => CpuStorage::F64(vec![0f64; elem_count]),
+            DType::F8E4M3 => CpuStorage::F8E4M3(vec![F8E4M3::ZERO; elem_count]),
+            DType::F8E5M2 => CpuStorage::F8E5M2(vec![F8E5M2::ZERO; elem_count]),
+            DType::Bool => CpuStorage::Bool(vec![0u8; elem_count]),
         };
         Ok(storage)
     }
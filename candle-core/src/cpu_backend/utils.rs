@@ -15,6 +15,9 @@ pub trait Map1 {
             C::F16(vs) => Ok(C::F16(self.f(vs, layout)?)),
             C::F32(vs) => Ok(C::F32(self.f(vs, layout)?)),
             C::F64(vs) => Ok(C::F64(self.f(vs, layout)?)),
+            C::F8E4M3(vs) => Ok(C::F8E4M3(self.f(vs, layout)?)),
+            C::F8E5M2(vs) => Ok(C::F8E5M2(self.f(vs, layout)?)),
+            C::Bool(vs) => Ok(C::Bool(self.f(vs, layout)?)),
         }
     }
 }
@@ -31,6 +34,9 @@ pub trait Map1Any {
             C::F16(vs) => Ok(self.f(vs, layout, C::F16)?),
             C::F32(vs) => Ok(self.f(vs, layout, C::F32)?),
             C::F64(vs) => Ok(self.f(vs, layout, C::F64)?),
+            C::F8E4M3(vs) => Ok(self.f(vs, layout, C::F8E4M3)?),
+            C::F8E5M2(vs) => Ok(self.f(vs, layout, C::F8E5M2)?),
+            C::Bool(vs) => Ok(self.f(vs, layout, C::Bool)?),
         }
     }
 }
@@ -48,6 +54,9 @@ pub trait Map2 {
             (C::F16(v1), C::F16(v2)) => Ok(C::F16(self.f(v1, l1, v2, l2)?)),
             (C::F32(v1), C::F32(v2)) => Ok(C::F32(self.f(v1, l1, v2, l2)?)),
             (C::F64(v1), C::F64(v2)) => Ok(C::F64(self.f(v1, l1, v2, l2)?)),
+            (C::F8E4M3(v1), C::F8E4M3(v2)) => Ok(C::F8E4M3(self.f(v1, l1, v2, l2)?)),
+            (C::F8E5M2(v1), C::F8E5M2(v2)) => Ok(C::F8E5M2(self.f(v1, l1, v2, l2)?)),
+            (C::Bool(v1), C::Bool(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
             _ => Err(Error::DTypeMismatchBinaryOp {
                 lhs: v1.dtype(),
                 rhs: v2.dtype(),
@@ -71,6 +80,9 @@ pub trait Map2U8 {
             (C::F16(v1), C::F16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
             (C::F32(v1), C::F32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
             (C::F64(v1), C::F64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
+            (C::F8E4M3(v1), C::F8E4M3(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
+            (C::F8E5M2(v1), C::F8E5M2(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
+            (C::Bool(v1), C::Bool(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
             _ => Err(Error::DTypeMismatchBinaryOp {
                 lhs: v1.dtype(),
                 rhs: v2.dtype(),
@@ -160,6 +172,35 @@ pub fn binary_map<T: Copy, U: Copy, F: FnMut(T, T) -> U>(
     }
 }
 
+// Similar to binary_map but the result is written back in the contiguous lhs buffer.
+pub fn binary_map_inplace<T: Copy, F: FnMut(T, T) -> T>(
+    lhs_l: &Layout,
+    rhs_l: &Layout,
+    lhs: &mut [T],
+    rhs: &[T],
+    mut f: F,
+) -> Result<()> {
+    let (o_l1, o_l2) = match lhs_l.contiguous_offsets() {
+        Some(offsets) => offsets,
+        None => Err(Error::RequiresContiguous {
+            op: "binary-inplace",
+        }
+        .bt())?,
+    };
+    let lhs = &mut lhs[o_l1..o_l2];
+    match rhs_l.contiguous_offsets() {
+        Some((o_r1, o_r2)) => lhs
+            .iter_mut()
+            .zip(rhs[o_r1..o_r2].iter())
+            .for_each(|(l, &r)| *l = f(*l, r)),
+        None => lhs
+            .iter_mut()
+            .zip(rhs_l.strided_index())
+            .for_each(|(l, rhs_i)| *l = f(*l, rhs[rhs_i])),
+    }
+    Ok(())
+}
+
 // Similar to binary_map but with vectorized variants.
 pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [T])>(
     lhs_l: &Layout,
@@ -1,10 +1,12 @@
 use crate::backend::BackendDevice;
-use crate::{CpuStorage, CpuStorageRef, DType, Layout, Result, Shape};
+use crate::{CpuStorage, CpuStorageRef, DType, Layout, Result, Shape, F8E4M3, F8E5M2};
 pub use candle_kernels as kernels;
 pub use cudarc;
-use cudarc::driver::{CudaFunction, LaunchAsync, LaunchConfig};
+use cudarc::driver::{
+    CudaFunction, CudaSlice, DeviceRepr, DriverError, LaunchAsync, LaunchConfig, ValidAsZeroBits,
+};
 use half::{bf16, f16};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use super::{CudaError, CudaStorage, CudaStorageSlice, WrapErr};
 
@@ -21,6 +23,18 @@ impl DeviceId {
     }
 }
 
+/// The memory usage of the memory pool of a cuda device, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemPoolStats {
+    /// The memory reserved by the pool, including the cached memory that is not in use.
+    pub reserved: u64,
+    /// The memory used by the live allocations.
+    pub used: u64,
+    /// The peak of the used memory since the device creation or the last call to
+    /// [`CudaDevice::reset_mem_pool_peak`].
+    pub peak_used: u64,
+}
+
 struct CudaRng(cudarc::curand::CudaRng);
 unsafe impl Send for CudaRng {}
 
@@ -30,6 +44,8 @@ pub struct CudaDevice {
     device: Arc<cudarc::driver::CudaDevice>,
     pub(crate) blas: Arc<cudarc::cublas::CudaBlas>,
     curand: Arc<Mutex<CudaRng>>,
+    pinned: Arc<Mutex<super::pinned::PinnedPool>>,
+    caching_pool: Arc<OnceLock<super::mem_pool::MemPool>>,
 }
 
 impl std::fmt::Debug for CudaDevice {
@@ -55,6 +71,333 @@ impl CudaDevice {
         self.id
     }
 
+    /// Creates a device whose operations run on a dedicated stream rather than on the legacy
+    /// default stream, this is required to capture cuda graphs.
+    pub fn new_with_stream(ordinal: usize) -> Result<Self> {
+        let device = cudarc::driver::CudaDevice::new_with_stream(ordinal).w()?;
+        Self::from_cudarc_device(device)
+    }
+
+    fn from_cudarc_device(device: Arc<cudarc::driver::CudaDevice>) -> Result<Self> {
+        let blas = cudarc::cublas::CudaBlas::new(device.clone()).w()?;
+        let curand = cudarc::curand::CudaRng::new(299792458, device.clone()).w()?;
+        let device = Self {
+            id: DeviceId::new(),
+            device,
+            blas: Arc::new(blas),
+            curand: Arc::new(Mutex::new(CudaRng(curand))),
+            pinned: Arc::new(Mutex::new(Default::default())),
+            caching_pool: Arc::new(OnceLock::new()),
+        };
+        let caching =
+            std::env::var("CANDLE_CUDA_CACHING_ALLOCATOR").is_ok_and(|v| !v.is_empty() && v != "0");
+        if caching && device.mem_pool_supported()? {
+            device.enable_caching_allocator()?
+        }
+        Ok(device)
+    }
+
+    /// Enables the caching allocator for this device and its clones.
+    ///
+    /// The tensor buffers are then allocated from a memory pool dedicated to this device rather
+    /// than from the default memory pool of the gpu, which is shared with the other libraries of
+    /// the process and left untouched. The allocation sizes are rounded up to a small set of
+    /// buckets, and the memory freed by a tensor is kept reserved by the pool so that the
+    /// following allocations of the same bucket reuse it without calling the driver. The reuse is
+    /// ordered by streams: a freed block is immediately available on the stream that freed it and
+    /// on other streams once the work queued before the free has completed.
+    ///
+    /// [`CudaDevice::mem_pool_stats`] reports the memory reserved by the pool and
+    /// [`CudaDevice::trim_mem_pool`] releases the cached memory. The caching allocator can also be
+    /// enabled for all the devices by setting the `CANDLE_CUDA_CACHING_ALLOCATOR` environment
+    /// variable to `1`. This returns an error if the gpu does not support memory pools.
+    pub fn enable_caching_allocator(&self) -> Result<()> {
+        if self.caching_pool.get().is_some() {
+            return Ok(());
+        }
+        if !self.mem_pool_supported()? {
+            crate::bail!("the caching allocator requires a gpu with memory pool support")
+        }
+        let pool = super::mem_pool::MemPool::new(self.ordinal())?;
+        // Another thread may have enabled the allocator in the meantime, its pool is kept.
+        let _ = self.caching_pool.set(pool);
+        Ok(())
+    }
+
+    /// Returns true if the buffers of this device are allocated by the caching allocator, see
+    /// [`CudaDevice::enable_caching_allocator`].
+    pub fn caching_allocator_enabled(&self) -> bool {
+        self.caching_pool.get().is_some()
+    }
+
+    /// Allocates a buffer of `len` elements on `stream`, using the caching allocator when it is
+    /// enabled.
+    ///
+    /// # Safety
+    /// The content of the buffer is uninitialized.
+    pub(crate) unsafe fn alloc_on_stream<T: DeviceRepr>(
+        &self,
+        len: usize,
+        stream: cudarc::driver::sys::CUstream,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        self.bind_to_thread()?;
+        let num_bytes = len * std::mem::size_of::<T>();
+        let ptr = match self.caching_pool.get() {
+            Some(pool) => pool.alloc(num_bytes, stream)?,
+            None => cudarc::driver::result::malloc_async(stream, num_bytes.max(1))?,
+        };
+        // The slice frees the memory with cuMemFreeAsync, which returns it to the pool it was
+        // allocated from.
+        Ok(self.upgrade_device_ptr(ptr, len))
+    }
+
+    /// Allocates a buffer of `len` elements on the stream of the device, the buffers of the
+    /// tensors should be allocated with this method rather than with the one from the underlying
+    /// cudarc device so that the caching allocator gets used.
+    ///
+    /// # Safety
+    /// The content of the buffer is uninitialized.
+    pub unsafe fn alloc<T: DeviceRepr>(
+        &self,
+        len: usize,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        if self.caching_pool.get().is_none() {
+            return self.device.alloc(len);
+        }
+        self.alloc_on_stream(len, *self.cu_stream())
+    }
+
+    /// Allocates a zeroed buffer of `len` elements on the stream of the device, see
+    /// [`CudaDevice::alloc`].
+    pub fn alloc_zeros<T: ValidAsZeroBits + DeviceRepr>(
+        &self,
+        len: usize,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        if self.caching_pool.get().is_none() {
+            return self.device.alloc_zeros(len);
+        }
+        // SAFETY: Set right after by the memset.
+        let mut slice = unsafe { self.alloc::<T>(len) }?;
+        self.device.memset_zeros(&mut slice)?;
+        Ok(slice)
+    }
+
+    fn mem_pool_supported(&self) -> Result<bool> {
+        use cudarc::driver::sys::{self, CUdevice_attribute};
+        let mut supported = 0;
+        unsafe {
+            sys::lib().cuDeviceGetAttribute(
+                &mut supported,
+                CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED,
+                *self.cu_device(),
+            )
+        }
+        .result()
+        .w()?;
+        Ok(supported != 0)
+    }
+
+    // The pool of the caching allocator if enabled, the default pool of the gpu otherwise.
+    fn mem_pool(&self) -> Result<cudarc::driver::sys::CUmemoryPool> {
+        use cudarc::driver::sys;
+        if let Some(pool) = self.caching_pool.get() {
+            return Ok(pool.handle());
+        }
+        let mut pool = std::ptr::null_mut();
+        unsafe { sys::lib().cuDeviceGetDefaultMemPool(&mut pool, *self.cu_device()) }
+            .result()
+            .w()?;
+        Ok(pool)
+    }
+
+    pub(crate) fn mem_pool_attribute(
+        &self,
+        attr: cudarc::driver::sys::CUmemPool_attribute,
+    ) -> Result<u64> {
+        let pool = self.mem_pool()?;
+        let mut value = 0u64;
+        let ptr = &mut value as *mut u64 as *mut std::ffi::c_void;
+        unsafe { cudarc::driver::sys::lib().cuMemPoolGetAttribute(pool, attr, ptr) }
+            .result()
+            .w()?;
+        Ok(value)
+    }
+
+    pub(crate) fn set_mem_pool_attribute(
+        &self,
+        attr: cudarc::driver::sys::CUmemPool_attribute,
+        mut value: u64,
+    ) -> Result<()> {
+        let pool = self.mem_pool()?;
+        let ptr = &mut value as *mut u64 as *mut std::ffi::c_void;
+        unsafe { cudarc::driver::sys::lib().cuMemPoolSetAttribute(pool, attr, ptr) }
+            .result()
+            .w()
+    }
+
+    /// Sets the amount of unused memory that the memory pool of the device keeps cached when the
+    /// device synchronizes, the rest is released to the system. The pool of the caching allocator
+    /// keeps all its memory by default, the default pool of the gpu releases all of it.
+    pub fn set_mem_pool_release_threshold(&self, bytes: u64) -> Result<()> {
+        use cudarc::driver::sys::CUmemPool_attribute;
+        let attr = CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD;
+        self.set_mem_pool_attribute(attr, bytes)
+    }
+
+    /// Returns the memory used and reserved by the memory pool of the device.
+    pub fn mem_pool_stats(&self) -> Result<MemPoolStats> {
+        use cudarc::driver::sys::CUmemPool_attribute as A;
+        Ok(MemPoolStats {
+            reserved: self.mem_pool_attribute(A::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT)?,
+            used: self.mem_pool_attribute(A::CU_MEMPOOL_ATTR_USED_MEM_CURRENT)?,
+            peak_used: self.mem_pool_attribute(A::CU_MEMPOOL_ATTR_USED_MEM_HIGH)?,
+        })
+    }
+
+    /// Resets the peak memory usage of the memory pool of the device.
+    pub fn reset_mem_pool_peak(&self) -> Result<()> {
+        use cudarc::driver::sys::CUmemPool_attribute as A;
+        // The driver only accepts 0 for the high watermarks, which resets them to the current
+        // values.
+        self.set_mem_pool_attribute(A::CU_MEMPOOL_ATTR_USED_MEM_HIGH, 0)?;
+        self.set_mem_pool_attribute(A::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH, 0)
+    }
+
+    /// Releases the memory cached by the memory pool of the device, the pool keeps at least
+    /// `min_bytes_to_keep` bytes reserved. The memory used by live tensors is never released.
+    pub fn trim_mem_pool(&self, min_bytes_to_keep: usize) -> Result<()> {
+        let pool = self.mem_pool()?;
+        self.synchronize()?;
+        unsafe { cudarc::driver::sys::lib().cuMemPoolTrimTo(pool, min_bytes_to_keep) }
+            .result()
+            .w()
+    }
+
+    /// Captures the operations run by `f` on this device in a graph, these operations are
+    /// recorded but not executed. The graph can then be replayed with a single launch which
+    /// removes most of the kernel launch overhead, e.g. in the decoding loop of a language model.
+    ///
+    /// The replayed operations read and write the same memory as during the capture so the
+    /// inputs have to be allocated beforehand and updated in place, and the results have to be
+    /// copied in tensors allocated beforehand, e.g. with `Tensor::slice_set`. The tensors created
+    /// within `f` must be dropped before it returns. Operations that copy data from the host, e.g.
+    /// the kernels on non-contiguous tensors, or that synchronize the device cannot be captured.
+    /// The device must have been created with [`CudaDevice::new_with_stream`].
+    pub fn capture<F: FnOnce() -> Result<()>>(&self, f: F) -> Result<super::CudaGraph> {
+        super::CudaGraph::capture(self, f)
+    }
+
+    fn htod_pinned<T: DeviceRepr>(&self, src: &[T]) -> Result<CudaSlice<T>> {
+        use cudarc::driver::DevicePtrMut;
+        let len = src.len();
+        let num_bytes = std::mem::size_of_val(src);
+        let src = unsafe { std::slice::from_raw_parts(src.as_ptr() as *const u8, num_bytes) };
+        let mut pinned = self.pinned.lock().unwrap();
+        let mut buffer = pinned.get(num_bytes)?;
+        buffer.copy_from(src);
+        // SAFETY: Set right after by the copy.
+        let mut dst = unsafe { self.alloc::<T>(len) }.w()?;
+        unsafe {
+            cudarc::driver::sys::lib().cuMemcpyHtoDAsync_v2(
+                *dst.device_ptr_mut(),
+                buffer.as_ptr() as *const std::ffi::c_void,
+                num_bytes,
+                *self.cu_stream(),
+            )
+        }
+        .result()
+        .w()?;
+        pinned.release(buffer, *self.cu_stream())?;
+        Ok(dst)
+    }
+
+    /// Copies some cpu storage to the device without waiting for the copy to complete. The data
+    /// is first copied to a page-locked staging buffer so that the transfer can run concurrently
+    /// with the host, the operations queued later on the device wait for the copy to finish.
+    pub fn storage_from_cpu_storage_async(&self, storage: &CpuStorage) -> Result<CudaStorage> {
+        let slice = match storage {
+            CpuStorage::U8(storage) => CudaStorageSlice::U8(self.htod_pinned(storage)?),
+            CpuStorage::U32(storage) => CudaStorageSlice::U32(self.htod_pinned(storage)?),
+            CpuStorage::I64(storage) => CudaStorageSlice::I64(self.htod_pinned(storage)?),
+            CpuStorage::BF16(storage) => CudaStorageSlice::BF16(self.htod_pinned(storage)?),
+            CpuStorage::F16(storage) => CudaStorageSlice::F16(self.htod_pinned(storage)?),
+            CpuStorage::F32(storage) => CudaStorageSlice::F32(self.htod_pinned(storage)?),
+            CpuStorage::F64(storage) => CudaStorageSlice::F64(self.htod_pinned(storage)?),
+            CpuStorage::F8E4M3(storage) => CudaStorageSlice::F8E4M3(self.htod_pinned(storage)?),
+            CpuStorage::F8E5M2(storage) => CudaStorageSlice::F8E5M2(self.htod_pinned(storage)?),
+            CpuStorage::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: "storage_from_cpu_storage_async",
+            })
+            .w()?,
+        };
+        Ok(CudaStorage {
+            slice,
+            device: self.clone(),
+        })
+    }
+
+    /// Frees the page-locked staging buffers that are not used by a pending copy.
+    pub fn trim_pinned_pool(&self) -> Result<()> {
+        self.pinned.lock().unwrap().trim()
+    }
+
+    /// Creates a new stream, the work queued on this stream starts after the work currently
+    /// queued on the default stream.
+    pub fn new_stream(&self) -> Result<super::CudaStream> {
+        super::CudaStream::new(self)
+    }
+
+    /// Enables `self` to access the memory of `peer` when the hardware supports it, this is a
+    /// no-op if peer access is not supported or has already been enabled.
+    pub fn enable_peer_access(&self, peer: &Self) -> Result<()> {
+        use cudarc::driver::sys::{self, CUresult};
+        let mut can_access = 0;
+        unsafe {
+            sys::lib().cuDeviceCanAccessPeer(&mut can_access, *self.cu_device(), *peer.cu_device())
+        }
+        .result()
+        .w()?;
+        if can_access == 0 {
+            return Ok(());
+        }
+        self.bind_to_thread().w()?;
+        match unsafe { sys::lib().cuCtxEnablePeerAccess(*peer.cu_primary_ctx(), 0) } {
+            CUresult::CUDA_SUCCESS | CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED => Ok(()),
+            err => err.result().w(),
+        }
+    }
+
+    /// Copies a slice from the memory of `self` to the memory of `dst`. The copy is direct when
+    /// peer access is supported, otherwise it is staged through host memory by the driver. It is
+    /// queued on the stream of `dst` so that later operations on `dst` are ordered after it.
+    pub(crate) fn copy_peer<T: DeviceRepr>(
+        &self,
+        src: &CudaSlice<T>,
+        dst: &Self,
+    ) -> Result<CudaSlice<T>> {
+        use cudarc::driver::{sys, DevicePtr, DevicePtrMut, DeviceSlice};
+        dst.enable_peer_access(self)?;
+        // SAFETY: Set right after by the peer copy.
+        let mut out = unsafe { dst.alloc::<T>(src.len()) }.w()?;
+        // The source values are produced on the stream of `self`, make sure that they are
+        // available before the copy starts on the stream of `dst`.
+        self.synchronize()?;
+        unsafe {
+            sys::lib().cuMemcpyPeerAsync(
+                *out.device_ptr_mut(),
+                *dst.cu_primary_ctx(),
+                *src.device_ptr(),
+                *self.cu_primary_ctx(),
+                src.num_bytes(),
+                *dst.cu_stream(),
+            )
+        }
+        .result()
+        .w()?;
+        Ok(out)
+    }
+
     fn const_impl(&self, v: f64, shape: &Shape, dtype: DType) -> Result<CudaStorage> {
         let elem_count = shape.elem_count();
         let cfg = LaunchConfig::for_num_elems(elem_count as u32);
@@ -115,6 +458,29 @@ impl CudaDevice {
                 unsafe { func.launch(cfg, params) }.w()?;
                 CudaStorageSlice::F64(data)
             }
+            DType::F8E4M3 => {
+                // SAFETY: Set later by running the fill kernel, the u8 kernel is used on the bit
+                // representation.
+                let data = unsafe { self.alloc::<F8E4M3>(elem_count) }.w()?;
+                let func = self.get_or_load_func("fill_u8", kernels::FILL)?;
+                let params = (&data, F8E4M3::from_f64(v), elem_count);
+                unsafe { func.launch(cfg, params) }.w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            DType::F8E5M2 => {
+                // SAFETY: Set later by running the fill kernel, the u8 kernel is used on the bit
+                // representation.
+                let data = unsafe { self.alloc::<F8E5M2>(elem_count) }.w()?;
+                let func = self.get_or_load_func("fill_u8", kernels::FILL)?;
+                let params = (&data, F8E5M2::from_f64(v), elem_count);
+                unsafe { func.launch(cfg, params) }.w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
+            DType::Bool => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "const_impl",
+            })
+            .w()?,
         };
         Ok(CudaStorage {
             slice,
@@ -149,14 +515,7 @@ impl BackendDevice for CudaDevice {
 
     fn new(ordinal: usize) -> Result<Self> {
         let device = cudarc::driver::CudaDevice::new(ordinal).w()?;
-        let blas = cudarc::cublas::CudaBlas::new(device.clone()).w()?;
-        let curand = cudarc::curand::CudaRng::new(299792458, device.clone()).w()?;
-        Ok(Self {
-            id: DeviceId::new(),
-            device,
-            blas: Arc::new(blas),
-            curand: Arc::new(Mutex::new(CudaRng(curand))),
-        })
+        Self::from_cudarc_device(device)
     }
 
     fn set_seed(&self, seed: u64) -> Result<()> {
@@ -208,6 +567,15 @@ impl BackendDevice for CudaDevice {
                 let data = self.alloc_zeros::<f64>(elem_count).w()?;
                 CudaStorageSlice::F64(data)
             }
+            DType::F8E4M3 => {
+                let data = self.alloc_zeros::<F8E4M3>(elem_count).w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            DType::F8E5M2 => {
+                let data = self.alloc_zeros::<F8E5M2>(elem_count).w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
+            DType::Bool => Err(CudaError::UnsupportedDtype { dtype, op: "zeros" }).w()?,
         };
         Ok(CudaStorage {
             slice,
@@ -221,13 +589,18 @@ impl BackendDevice for CudaDevice {
         let slice = match dtype {
             // TODO: Add support for F16 and BF16 though this is likely to require some upstream
             // cudarc changes.
-            DType::U8 | DType::U32 | DType::I64 | DType::F16 | DType::BF16 => {
-                Err(CudaError::UnsupportedDtype {
-                    dtype,
-                    op: "rand_uniform",
-                })
-                .w()?
-            }
+            DType::U8
+            | DType::U32
+            | DType::I64
+            | DType::F16
+            | DType::BF16
+            | DType::F8E4M3
+            | DType::F8E5M2
+            | DType::Bool => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "rand_uniform",
+            })
+            .w()?,
             DType::F32 => {
                 let mut data = unsafe { self.alloc::<f32>(elem_count) }.w()?;
                 curand.0.fill_with_uniform(&mut data).w()?;
@@ -265,13 +638,18 @@ impl BackendDevice for CudaDevice {
             elem_count
         };
         let slice = match dtype {
-            DType::U8 | DType::U32 | DType::I64 | DType::F16 | DType::BF16 => {
-                Err(CudaError::UnsupportedDtype {
-                    dtype,
-                    op: "rand_normal",
-                })
-                .w()?
-            }
+            DType::U8
+            | DType::U32
+            | DType::I64
+            | DType::F16
+            | DType::BF16
+            | DType::F8E4M3
+            | DType::F8E5M2
+            | DType::Bool => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "rand_normal",
+            })
+            .w()?,
             DType::F32 => {
                 let mut data = unsafe { self.alloc::<f32>(elem_count_round) }.w()?;
                 curand
@@ -327,6 +705,19 @@ impl BackendDevice for CudaDevice {
                 let data = self.alloc::<f64>(elem_count).w()?;
                 CudaStorageSlice::F64(data)
             }
+            DType::F8E4M3 => {
+                let data = self.alloc::<F8E4M3>(elem_count).w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            DType::F8E5M2 => {
+                let data = self.alloc::<F8E5M2>(elem_count).w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
+            DType::Bool => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "alloc_uninit",
+            })
+            .w()?,
         };
         Ok(CudaStorage {
             slice,
@@ -364,6 +755,14 @@ impl BackendDevice for CudaDevice {
                 let data = self.htod_sync_copy(storage).w()?;
                 CudaStorageSlice::F64(data)
             }
+            CpuStorageRef::F8E4M3(storage) => {
+                let data = self.htod_sync_copy(storage).w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            CpuStorageRef::F8E5M2(storage) => {
+                let data = self.htod_sync_copy(storage).w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
         };
         Ok(CudaStorage {
             slice,
@@ -401,6 +800,19 @@ impl BackendDevice for CudaDevice {
                 let data = self.htod_sync_copy(storage).w()?;
                 CudaStorageSlice::F64(data)
             }
+            CpuStorage::F8E4M3(storage) => {
+                let data = self.htod_sync_copy(storage).w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            CpuStorage::F8E5M2(storage) => {
+                let data = self.htod_sync_copy(storage).w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
+            CpuStorage::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: "storage_from_cpu_storage",
+            })
+            .w()?,
         };
         Ok(CudaStorage {
             slice,
@@ -438,6 +850,19 @@ impl BackendDevice for CudaDevice {
                 let data = self.htod_copy(storage).w()?;
                 CudaStorageSlice::F64(data)
             }
+            CpuStorage::F8E4M3(storage) => {
+                let data = self.htod_copy(storage).w()?;
+                CudaStorageSlice::F8E4M3(data)
+            }
+            CpuStorage::F8E5M2(storage) => {
+                let data = self.htod_copy(storage).w()?;
+                CudaStorageSlice::F8E5M2(data)
+            }
+            CpuStorage::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: "storage_from_cpu_storage",
+            })
+            .w()?,
         };
         Ok(CudaStorage {
             slice,
@@ -0,0 +1,84 @@
+use crate::Result;
+use cudarc::driver::sys;
+
+use super::{CudaDevice, WrapErr};
+
+/// A sequence of operations captured by [`CudaDevice::capture`] that can be replayed with a
+/// single launch.
+pub struct CudaGraph {
+    device: CudaDevice,
+    graph: sys::CUgraph,
+    exec: sys::CUgraphExec,
+}
+
+// The graph is only modified on creation and destruction, launching it from different threads
+// is safe.
+unsafe impl Send for CudaGraph {}
+unsafe impl Sync for CudaGraph {}
+
+impl std::fmt::Debug for CudaGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CudaGraph({:?})", self.device.id())
+    }
+}
+
+impl CudaGraph {
+    pub(crate) fn capture<F: FnOnce() -> Result<()>>(device: &CudaDevice, f: F) -> Result<Self> {
+        let stream = *device.cu_stream();
+        if stream.is_null() {
+            crate::bail!("graph capture requires a device created with CudaDevice::new_with_stream")
+        }
+        device.bind_to_thread().w()?;
+        unsafe {
+            sys::lib().cuStreamBeginCapture_v2(
+                stream,
+                sys::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_THREAD_LOCAL,
+            )
+        }
+        .result()
+        .w()?;
+        let res = f();
+        let mut graph = std::ptr::null_mut();
+        let end = unsafe { sys::lib().cuStreamEndCapture(stream, &mut graph) }.result();
+        if let Err(err) = res.and_then(|()| end.w()) {
+            if !graph.is_null() {
+                unsafe { sys::lib().cuGraphDestroy(graph) };
+            }
+            return Err(err);
+        }
+        let mut exec = std::ptr::null_mut();
+        if let Err(err) = unsafe { sys::lib().cuGraphInstantiateWithFlags(&mut exec, graph, 0) }
+            .result()
+            .w()
+        {
+            unsafe { sys::lib().cuGraphDestroy(graph) };
+            return Err(err);
+        }
+        Ok(Self {
+            device: device.clone(),
+            graph,
+            exec,
+        })
+    }
+
+    pub fn device(&self) -> &CudaDevice {
+        &self.device
+    }
+
+    /// Queues all the captured operations on the stream of the device.
+    pub fn replay(&self) -> Result<()> {
+        self.device.bind_to_thread().w()?;
+        unsafe { sys::lib().cuGraphLaunch(self.exec, *self.device.cu_stream()) }
+            .result()
+            .w()
+    }
+}
+
+impl Drop for CudaGraph {
+    fn drop(&mut self) {
+        unsafe {
+            sys::lib().cuGraphExecDestroy(self.exec);
+            sys::lib().cuGraphDestroy(self.graph);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::Result;
+use cudarc::driver::{sys, DriverError};
+
+use super::WrapErr;
+
+// Allocations below this size are rounded up to a multiple of `SMALL_ALIGN`, larger ones are
+// rounded up to one of `STEPS_PER_POWER` sizes between two consecutive powers of two. This bounds
+// the memory wasted by the rounding to 1/STEPS_PER_POWER of the requested size while keeping the
+// number of distinct sizes low, so that a freed block is likely to be reused by a later
+// allocation of a slightly different size.
+const SMALL_ALIGN: usize = 512;
+const SMALL_MAX: usize = 1 << 20;
+const STEPS_PER_POWER: usize = 4;
+
+/// Returns the size of the bucket used for an allocation of `num_bytes` bytes.
+pub(crate) fn bucket_size(num_bytes: usize) -> usize {
+    let num_bytes = num_bytes.max(1);
+    if num_bytes <= SMALL_MAX {
+        return num_bytes.div_ceil(SMALL_ALIGN) * SMALL_ALIGN;
+    }
+    let power = num_bytes.next_power_of_two() / 2;
+    let step = power / STEPS_PER_POWER;
+    num_bytes.div_ceil(step) * step
+}
+
+/// A memory pool dedicated to a candle device, the device buffers are allocated from it when the
+/// caching allocator is enabled.
+///
+/// The memory freed by a tensor stays reserved by the pool and is reused by the following
+/// allocations of the same bucket size. The driver orders the reuse with the streams: a block
+/// freed on a stream is immediately available to the later allocations on the same stream while
+/// the other streams only get it once the work queued before the free has completed.
+pub(crate) struct MemPool {
+    pool: sys::CUmemoryPool,
+}
+
+// The pool handle can be used from any thread.
+unsafe impl Send for MemPool {}
+unsafe impl Sync for MemPool {}
+
+impl MemPool {
+    pub(crate) fn new(ordinal: usize) -> Result<Self> {
+        // SAFETY: The properties are plain data, the zeroed fields are the driver defaults.
+        let mut props: sys::CUmemPoolProps = unsafe { std::mem::zeroed() };
+        props.allocType = sys::CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+        props.handleTypes = sys::CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE;
+        props.location.type_ = sys::CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE;
+        props.location.id = ordinal as i32;
+        let mut pool = std::ptr::null_mut();
+        unsafe { sys::lib().cuMemPoolCreate(&mut pool, &props) }
+            .result()
+            .w()?;
+        let pool = Self { pool };
+        // Keep the freed memory when the device synchronizes, the pool only shrinks on trim.
+        let attr = sys::CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD;
+        pool.set_attribute(attr, u64::MAX)?;
+        Ok(pool)
+    }
+
+    pub(crate) fn handle(&self) -> sys::CUmemoryPool {
+        self.pool
+    }
+
+    /// Allocates at least `num_bytes` bytes on `stream`, the memory gets returned to the pool
+    /// when the pointer is freed with `cuMemFreeAsync`.
+    pub(crate) fn alloc(
+        &self,
+        num_bytes: usize,
+        stream: sys::CUstream,
+    ) -> std::result::Result<sys::CUdeviceptr, DriverError> {
+        let mut ptr = 0;
+        let num_bytes = bucket_size(num_bytes);
+        unsafe { sys::lib().cuMemAllocFromPoolAsync(&mut ptr, num_bytes, self.pool, stream) }
+            .result()?;
+        Ok(ptr)
+    }
+
+    fn set_attribute(&self, attr: sys::CUmemPool_attribute, mut value: u64) -> Result<()> {
+        let ptr = &mut value as *mut u64 as *mut std::ffi::c_void;
+        unsafe { sys::lib().cuMemPoolSetAttribute(self.pool, attr, ptr) }
+            .result()
+            .w()
+    }
+}
+
+impl Drop for MemPool {
+    fn drop(&mut self) {
+        // The driver defers the destruction until the outstanding allocations have been freed.
+        let _ = unsafe { sys::lib().cuMemPoolDestroy(self.pool) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_size, STEPS_PER_POWER};
+
+    #[test]
+    fn bucket_sizes() {
+        assert_eq!(bucket_size(0), 512);
+        assert_eq!(bucket_size(1), 512);
+        assert_eq!(bucket_size(512), 512);
+        assert_eq!(bucket_size(513), 1024);
+        assert_eq!(bucket_size(1 << 20), 1 << 20);
+        // Between 1MiB and 2MiB the buckets are 256KiB apart.
+        assert_eq!(bucket_size((1 << 20) + 1), (1 << 20) + (1 << 18));
+        assert_eq!(bucket_size(3 << 19), 3 << 19);
+        for num_bytes in [1usize << 21, 12345678, 1 << 30, (1 << 30) + 7] {
+            let bucket = bucket_size(num_bytes);
+            assert!(bucket >= num_bytes);
+            assert!(bucket - num_bytes <= num_bytes / STEPS_PER_POWER);
+        }
+    }
+}
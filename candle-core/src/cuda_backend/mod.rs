@@ -1,6 +1,6 @@
 use crate::backend::{BackendDevice, BackendStorage};
 use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
-use crate::{CpuStorage, DType, Layout, Result, Shape, WithDType};
+use crate::{CpuStorage, DType, Layout, Result, Shape, WithDType, F8E4M3, F8E5M2};
 pub use candle_kernels as kernels;
 pub use cudarc;
 use cudarc::cublas::{Gemm, GemmConfig, StridedBatchedConfig};
@@ -13,9 +13,15 @@ use half::{bf16, f16};
 pub mod cudnn;
 mod device;
 mod error;
+mod graph;
+mod mem_pool;
+mod pinned;
+mod stream;
 mod utils;
-pub use device::{CudaDevice, DeviceId};
+pub use device::{CudaDevice, DeviceId, MemPoolStats};
 pub use error::{CudaError, WrapErr};
+pub use graph::CudaGraph;
+pub use stream::CudaStream;
 pub use utils::{Map1, Map1Any, Map2, Map2Any, Map2InPlace, Map3, S};
 
 pub enum SlicePtrOrNull<T> {
@@ -52,8 +58,17 @@ pub enum CudaStorageSlice {
     F16(CudaSlice<f16>),
     F32(CudaSlice<f32>),
     F64(CudaSlice<f64>),
+    F8E4M3(CudaSlice<F8E4M3>),
+    F8E5M2(CudaSlice<F8E5M2>),
 }
 
+// SAFETY: the 8 bits float types are plain bytes, the kernels operate on their bit
+// representation.
+unsafe impl DeviceRepr for F8E4M3 {}
+unsafe impl ValidAsZeroBits for F8E4M3 {}
+unsafe impl DeviceRepr for F8E5M2 {}
+unsafe impl ValidAsZeroBits for F8E5M2 {}
+
 struct Clone;
 impl Map1 for Clone {
     fn f<T: DeviceRepr>(
@@ -504,6 +519,54 @@ impl<'a> Map2InPlace for IndexAdd<'a> {
     }
 }
 
+struct IndexSet<'a>(&'a CudaStorage, &'a Layout, usize);
+impl<'a> Map2InPlace for IndexSet<'a> {
+    fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
+        &self,
+        dst: &mut CudaSlice<T>,
+        dst_shape: &Shape,
+        src: &CudaSlice<T>,
+        src_l: &Layout,
+        dev: &CudaDevice,
+    ) -> Result<()> {
+        let ids = &self.0;
+        let ids_l = &self.1;
+        let dim = self.2;
+        let (ids_o1, ids_o2) = match ids_l.contiguous_offsets() {
+            Some(o12) => o12,
+            None => Err(crate::Error::RequiresContiguous { op: "index-set" }.bt())?,
+        };
+        let (name, ids) = match &ids.slice {
+            CudaStorageSlice::U32(slice) => ("iset_u32", *slice.slice(ids_o1..ids_o2).device_ptr()),
+            CudaStorageSlice::I64(slice) => ("iset_i64", *slice.slice(ids_o1..ids_o2).device_ptr()),
+            CudaStorageSlice::U8(slice) => ("iset_u8", *slice.slice(ids_o1..ids_o2).device_ptr()),
+            _ => Err(CudaError::UnexpectedDType {
+                msg: "index-set ids should be u8/u32/i64",
+                expected: DType::U32,
+                got: ids.dtype(),
+            })?,
+        };
+        let src = match src_l.contiguous_offsets() {
+            Some((o1, o2)) => src.slice(o1..o2),
+            None => Err(crate::Error::RequiresContiguous { op: "index-set" }.bt())?,
+        };
+        let left_sz: usize = src_l.dims()[..dim].iter().product();
+        let right_sz: usize = src_l.dims()[dim + 1..].iter().product();
+        let src_dim_sz = src_l.dims()[dim];
+        let dst_dim_sz = dst_shape.dims()[dim];
+        let ids_dim_sz = ids_l.dims()[0];
+        let cfg = LaunchConfig::for_num_elems((left_sz * right_sz) as u32);
+        let func = dev.get_or_load_func(&kernel_name::<T>(name), kernels::INDEXING)?;
+        // SAFETY: Set later by running the kernel.
+        let params = (
+            ids, ids_dim_sz, &src, dst, left_sz, src_dim_sz, dst_dim_sz, right_sz,
+        );
+        // SAFETY: ffi.
+        unsafe { func.launch(cfg, params) }.w()?;
+        Ok(())
+    }
+}
+
 struct ScatterAdd<'a>(&'a CudaStorage, &'a Layout, usize);
 impl<'a> Map2InPlace for ScatterAdd<'a> {
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
@@ -1029,6 +1092,8 @@ cuda_dtype!(f16, F16);
 cuda_dtype!(bf16, BF16);
 cuda_dtype!(f32, F32);
 cuda_dtype!(f64, F64);
+cuda_dtype!(F8E4M3, F8E4M3);
+cuda_dtype!(F8E5M2, F8E5M2);
 
 impl CudaStorage {
     pub fn wrap_cuda_slice<T: CudaDType>(slice: CudaSlice<T>, device: CudaDevice) -> CudaStorage {
@@ -1038,6 +1103,27 @@ impl CudaStorage {
     pub fn as_cuda_slice<T: CudaDType>(&self) -> Result<&CudaSlice<T>> {
         T::as_cuda_slice(self)
     }
+
+    /// Copies the storage to another cuda device using a peer to peer copy, see
+    /// [`CudaDevice::enable_peer_access`].
+    pub fn transfer_to_device(&self, dst: &CudaDevice) -> Result<Self> {
+        let dev = &self.device;
+        let slice = match &self.slice {
+            CudaStorageSlice::U8(s) => CudaStorageSlice::U8(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::U32(s) => CudaStorageSlice::U32(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::I64(s) => CudaStorageSlice::I64(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::BF16(s) => CudaStorageSlice::BF16(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::F16(s) => CudaStorageSlice::F16(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::F32(s) => CudaStorageSlice::F32(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::F64(s) => CudaStorageSlice::F64(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::F8E4M3(s) => CudaStorageSlice::F8E4M3(dev.copy_peer(s, dst)?),
+            CudaStorageSlice::F8E5M2(s) => CudaStorageSlice::F8E5M2(dev.copy_peer(s, dst)?),
+        };
+        Ok(Self {
+            slice,
+            device: dst.clone(),
+        })
+    }
 }
 
 fn gemm_config<T>(
@@ -1151,6 +1237,8 @@ impl BackendStorage for CudaStorage {
             CudaStorageSlice::F16(_) => DType::F16,
             CudaStorageSlice::F32(_) => DType::F32,
             CudaStorageSlice::F64(_) => DType::F64,
+            CudaStorageSlice::F8E4M3(_) => DType::F8E4M3,
+            CudaStorageSlice::F8E5M2(_) => DType::F8E5M2,
         }
     }
 
@@ -1159,6 +1247,13 @@ impl BackendStorage for CudaStorage {
     }
 
     fn to_dtype(&self, layout: &Layout, dtype: DType) -> Result<Self> {
+        if dtype == DType::Bool {
+            Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "to_dtype",
+            })
+            .w()?
+        }
         let shape = layout.shape();
         let dims = shape.dims();
         let el = shape.elem_count();
@@ -1177,6 +1272,8 @@ impl BackendStorage for CudaStorage {
             CudaStorageSlice::F16(inp) => *inp.slice(start_o..).device_ptr(),
             CudaStorageSlice::F32(inp) => *inp.slice(start_o..).device_ptr(),
             CudaStorageSlice::F64(inp) => *inp.slice(start_o..).device_ptr(),
+            CudaStorageSlice::F8E4M3(inp) => *inp.slice(start_o..).device_ptr(),
+            CudaStorageSlice::F8E5M2(inp) => *inp.slice(start_o..).device_ptr(),
         };
         let inp = &inp;
 
@@ -1225,6 +1322,19 @@ impl BackendStorage for CudaStorage {
                 unsafe { func.launch(cfg, params) }.w()?;
                 CudaStorageSlice::F64(out)
             }
+            DType::F8E4M3 => {
+                let out = unsafe { dev.alloc::<F8E4M3>(el) }.w()?;
+                let params = (el, dims.len(), &ds, *inp, &out);
+                unsafe { func.launch(cfg, params) }.w()?;
+                CudaStorageSlice::F8E4M3(out)
+            }
+            DType::F8E5M2 => {
+                let out = unsafe { dev.alloc::<F8E5M2>(el) }.w()?;
+                let params = (el, dims.len(), &ds, *inp, &out);
+                unsafe { func.launch(cfg, params) }.w()?;
+                CudaStorageSlice::F8E5M2(out)
+            }
+            DType::Bool => unreachable!(),
         };
         Ok(Self {
             slice,
@@ -1316,6 +1426,16 @@ impl BackendStorage for CudaStorage {
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
                 Ok(CpuStorage::F64(cpu_storage))
             }
+            CudaStorageSlice::F8E4M3(slice) => {
+                let dev = slice.device();
+                let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
+                Ok(CpuStorage::F8E4M3(cpu_storage))
+            }
+            CudaStorageSlice::F8E5M2(slice) => {
+                let dev = slice.device();
+                let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
+                Ok(CpuStorage::F8E5M2(cpu_storage))
+            }
         }
     }
 
@@ -1653,6 +1773,22 @@ impl BackendStorage for CudaStorage {
         Ok(acc)
     }
 
+    fn index_set(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        let device = self.device().clone();
+        let mut acc = unsafe { device.alloc_uninit(l.shape(), self.dtype())? };
+        self.copy_strided_src(&mut acc, 0, l)?;
+        IndexSet(ids, ids_l, dim).map(&mut acc.slice, l.shape(), &src.slice, src_l, &device)?;
+        Ok(acc)
+    }
+
     fn matmul(
         &self,
         rhs: &Self,
@@ -1703,6 +1839,14 @@ impl BackendStorage for CudaStorage {
                 .w()?;
                 CudaStorageSlice::F64(out)
             }
+            // TODO: Add an fp8 matmul based on cublasLt.
+            (CudaStorageSlice::F8E4M3(_), CudaStorageSlice::F8E4M3(_))
+            | (CudaStorageSlice::F8E5M2(_), CudaStorageSlice::F8E5M2(_)) => {
+                Err(CudaError::UnsupportedDtype {
+                    dtype: self.dtype(),
+                    op: "matmul",
+                })?
+            }
             _ => Err(CudaError::InternalError("dtype mismatch in matmul op"))?,
         };
         let device = dev.clone();
@@ -1765,6 +1909,17 @@ impl BackendStorage for CudaStorage {
                 *d.slice(dst_o..).device_ptr(),
                 "copy2d_f64",
             ),
+            // The 8 bits floats are copied through their bit representation.
+            (S::F8E4M3(s), S::F8E4M3(d)) => (
+                *s.slice(src_o..).device_ptr(),
+                *d.slice(dst_o..).device_ptr(),
+                "copy2d_u8",
+            ),
+            (S::F8E5M2(s), S::F8E5M2(d)) => (
+                *s.slice(src_o..).device_ptr(),
+                *d.slice(dst_o..).device_ptr(),
+                "copy2d_u8",
+            ),
             _ => Err(CudaError::InternalError("dtype mismatch in copy2d"))?,
         };
         let func = dev.get_or_load_func(kname, kernels::FILL)?;
@@ -1870,6 +2025,30 @@ impl BackendStorage for CudaStorage {
                     unsafe { func.launch(cfg, params) }.w()?;
                 }
             }
+            (CudaStorageSlice::F8E4M3(src), CudaStorageSlice::F8E4M3(dst)) => {
+                let (src, mut dst) = slice_src_and_dst(src, src_l, dst, dst_offset);
+                if src_l.is_contiguous() {
+                    dev.dtod_copy(&src, &mut dst).w()?
+                } else {
+                    let func = dev.get_or_load_func("ucopy_u8", kernels::UNARY)?;
+                    // SAFETY: Set later by running the kernel.
+                    let params = (el_count, dims.len(), &ds, &src, &mut dst);
+                    // SAFETY: ffi.
+                    unsafe { func.launch(cfg, params) }.w()?;
+                }
+            }
+            (CudaStorageSlice::F8E5M2(src), CudaStorageSlice::F8E5M2(dst)) => {
+                let (src, mut dst) = slice_src_and_dst(src, src_l, dst, dst_offset);
+                if src_l.is_contiguous() {
+                    dev.dtod_copy(&src, &mut dst).w()?
+                } else {
+                    let func = dev.get_or_load_func("ucopy_u8", kernels::UNARY)?;
+                    // SAFETY: Set later by running the kernel.
+                    let params = (el_count, dims.len(), &ds, &src, &mut dst);
+                    // SAFETY: ffi.
+                    unsafe { func.launch(cfg, params) }.w()?;
+                }
+            }
             _ => Err(CudaError::InternalError(
                 "dtype mismatch in copy_strided op",
             ))?,
@@ -1878,49 +2057,40 @@ impl BackendStorage for CudaStorage {
     }
 }
 
-// Default for the reduced precision setting is false, similar to pytorch.
-// https://github.com/pytorch/pytorch/issues/123157
-static MM_F16_REDUCED_PRECISION: std::sync::atomic::AtomicBool =
-    std::sync::atomic::AtomicBool::new(false);
-static MM_BF16_REDUCED_PRECISION: std::sync::atomic::AtomicBool =
-    std::sync::atomic::AtomicBool::new(false);
-static MM_F32_REDUCED_PRECISION: std::sync::atomic::AtomicBool =
-    std::sync::atomic::AtomicBool::new(false);
-
 /// This bool controls whether reduced precision reductions (e.g., with tf32 accumulation type) are
-/// allowed with f32 GEMMs.
+/// allowed with f32 GEMMs, see [`crate::utils::matmul_precision`].
 pub fn gemm_reduced_precision_f32() -> bool {
-    MM_F32_REDUCED_PRECISION.load(std::sync::atomic::Ordering::Relaxed)
+    crate::utils::matmul_precision().tf32
 }
 
 /// This bool controls whether reduced precision reductions (e.g., with tf32 accumulation type) are
 /// allowed with f32 GEMMs.
 pub fn set_gemm_reduced_precision_f32(b: bool) {
-    MM_F32_REDUCED_PRECISION.store(b, std::sync::atomic::Ordering::Relaxed)
+    crate::utils::update_matmul_precision(|p| p.tf32 = b)
 }
 
 /// This bool controls whether reduced precision reductions (e.g., with fp16 accumulation type) are
-/// allowed with f16 GEMMs.
+/// allowed with f16 GEMMs, see [`crate::utils::matmul_precision`].
 pub fn gemm_reduced_precision_f16() -> bool {
-    MM_F16_REDUCED_PRECISION.load(std::sync::atomic::Ordering::Relaxed)
+    crate::utils::matmul_precision().reduced_f16
 }
 
 /// This bool controls whether reduced precision reductions (e.g., with fp16 accumulation type) are
 /// allowed with f16 GEMMs.
 pub fn set_gemm_reduced_precision_f16(b: bool) {
-    MM_F16_REDUCED_PRECISION.store(b, std::sync::atomic::Ordering::Relaxed)
+    crate::utils::update_matmul_precision(|p| p.reduced_f16 = b)
 }
 
 /// This bool controls whether reduced precision reductions (e.g., with fp16 accumulation type) are
-/// allowed with bf16 GEMMs.
+/// allowed with bf16 GEMMs, see [`crate::utils::matmul_precision`].
 pub fn gemm_reduced_precision_bf16() -> bool {
-    MM_BF16_REDUCED_PRECISION.load(std::sync::atomic::Ordering::Relaxed)
+    crate::utils::matmul_precision().reduced_bf16
 }
 
 /// This bool controls whether reduced precision reductions (e.g., with fp16 accumulation type) are
 /// allowed with bf16 GEMMs.
 pub fn set_gemm_reduced_precision_bf16(b: bool) {
-    MM_BF16_REDUCED_PRECISION.store(b, std::sync::atomic::Ordering::Relaxed)
+    crate::utils::update_matmul_precision(|p| p.reduced_bf16 = b)
 }
 
 unsafe fn gemm_strided_batched_f32(
@@ -0,0 +1,119 @@
+use crate::Result;
+use cudarc::driver::{result, sys};
+
+use super::WrapErr;
+
+/// A host buffer allocated in page-locked memory, copies from such a buffer to the device run
+/// asynchronously.
+pub(crate) struct PinnedBuffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The buffer is only accessed through the pool mutex or by the device.
+unsafe impl Send for PinnedBuffer {}
+
+impl PinnedBuffer {
+    fn new(len: usize) -> Result<Self> {
+        let ptr = unsafe { result::malloc_host(len.max(1), 0) }.w()?;
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    pub(crate) fn copy_from(&mut self, src: &[u8]) {
+        assert!(src.len() <= self.len);
+        unsafe { std::ptr::copy_nonoverlapping(src.as_ptr(), self.ptr, src.len()) }
+    }
+}
+
+impl Drop for PinnedBuffer {
+    fn drop(&mut self) {
+        let _ = unsafe { result::free_host(self.ptr as *mut std::ffi::c_void) };
+    }
+}
+
+/// The pinned buffers used to stage the asynchronous uploads to a device.
+#[derive(Default)]
+pub(crate) struct PinnedPool {
+    // Buffers used by copies that may still be running, together with an event recorded right
+    // after the copy.
+    in_flight: Vec<(sys::CUevent, PinnedBuffer)>,
+    free: Vec<PinnedBuffer>,
+}
+
+unsafe impl Send for PinnedPool {}
+
+impl Drop for PinnedPool {
+    fn drop(&mut self) {
+        for (event, _buffer) in self.in_flight.drain(..) {
+            unsafe {
+                let _ = sys::lib().cuEventSynchronize(event);
+                let _ = sys::lib().cuEventDestroy_v2(event);
+            }
+        }
+    }
+}
+
+impl PinnedPool {
+    /// Moves the buffers for which the copy has completed to the free list.
+    fn reclaim(&mut self) -> Result<()> {
+        let mut in_flight = Vec::with_capacity(self.in_flight.len());
+        for (event, buffer) in self.in_flight.drain(..) {
+            match unsafe { sys::lib().cuEventQuery(event) } {
+                sys::CUresult::CUDA_ERROR_NOT_READY => in_flight.push((event, buffer)),
+                err => {
+                    unsafe { sys::lib().cuEventDestroy_v2(event) }
+                        .result()
+                        .w()?;
+                    err.result().w()?;
+                    self.free.push(buffer)
+                }
+            }
+        }
+        self.in_flight = in_flight;
+        Ok(())
+    }
+
+    /// Returns a buffer of at least `len` bytes, reusing the smallest such free buffer if any.
+    pub(crate) fn get(&mut self, len: usize) -> Result<PinnedBuffer> {
+        self.reclaim()?;
+        let best = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len >= len)
+            .min_by_key(|(_, b)| b.len)
+            .map(|(idx, _)| idx);
+        match best {
+            Some(idx) => Ok(self.free.swap_remove(idx)),
+            None => PinnedBuffer::new(len),
+        }
+    }
+
+    /// Returns a buffer to the pool once the work currently queued on `stream` has completed.
+    pub(crate) fn release(&mut self, buffer: PinnedBuffer, stream: sys::CUstream) -> Result<()> {
+        let mut event = std::ptr::null_mut();
+        let flags = sys::CUevent_flags::CU_EVENT_DISABLE_TIMING as u32;
+        unsafe { sys::lib().cuEventCreate(&mut event, flags) }
+            .result()
+            .w()?;
+        unsafe { sys::lib().cuEventRecord(event, stream) }
+            .result()
+            .w()?;
+        self.in_flight.push((event, buffer));
+        Ok(())
+    }
+
+    /// Frees the buffers that are not used by a pending copy.
+    pub(crate) fn trim(&mut self) -> Result<()> {
+        self.reclaim()?;
+        self.free.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{CpuStorage, DType, Result, Tensor};
+use cudarc::driver::{result, CudaSlice, DeviceRepr};
+use std::sync::{Arc, Mutex};
+
+use super::{CudaDevice, CudaError, CudaStorage, CudaStorageSlice, WrapErr};
+
+/// A cuda stream that runs concurrently with the default stream of a device.
+///
+/// Tensor operations always run on the default stream, a separate stream can be used to upload
+/// data while the default stream is busy, e.g. to prefetch the next batch during training. The
+/// default stream has to wait for the uploads via [`CudaStream::join`] before the uploaded tensors
+/// get used.
+#[derive(Clone)]
+pub struct CudaStream {
+    device: CudaDevice,
+    stream: Arc<cudarc::driver::CudaStream>,
+    // Tensors used by the work queued on the stream, these are kept alive until the stream is
+    // joined or synchronized so that their memory cannot be reused in the meantime.
+    recorded: Arc<Mutex<Vec<Tensor>>>,
+}
+
+impl std::fmt::Debug for CudaStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CudaStream({:?})", self.device.id())
+    }
+}
+
+impl CudaStream {
+    pub(crate) fn new(device: &CudaDevice) -> Result<Self> {
+        let stream = device.fork_default_stream().w()?;
+        Ok(Self {
+            device: device.clone(),
+            stream: Arc::new(stream),
+            recorded: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    pub fn device(&self) -> &CudaDevice {
+        &self.device
+    }
+
+    pub fn cuda_stream(&self) -> Arc<cudarc::driver::CudaStream> {
+        self.stream.clone()
+    }
+
+    /// Makes the work queued on this stream wait for the work currently queued on the default
+    /// stream of the device.
+    pub fn wait_for_default(&self) -> Result<()> {
+        self.stream.wait_for_default().w()
+    }
+
+    /// Makes the default stream of the device wait for the work currently queued on this stream,
+    /// the following tensor operations can use the tensors uploaded on this stream.
+    pub fn join(&self) -> Result<()> {
+        self.device.wait_for(&self.stream).w()?;
+        self.recorded.lock().unwrap().clear();
+        Ok(())
+    }
+
+    /// Blocks until all the work queued on this stream has completed.
+    pub fn synchronize(&self) -> Result<()> {
+        self.device.bind_to_thread().w()?;
+        unsafe { result::stream::synchronize(self.stream.stream) }.w()?;
+        self.recorded.lock().unwrap().clear();
+        Ok(())
+    }
+
+    /// Keeps `tensor` alive until the stream is joined or synchronized. This has to be used for
+    /// the tensors accessed by the work queued on this stream.
+    pub fn record_tensor(&self, tensor: &Tensor) -> Result<()> {
+        if !tensor
+            .device()
+            .same_device(&crate::Device::Cuda(self.device.clone()))
+        {
+            crate::bail!(
+                "cannot record a tensor on {:?} in {self:?}",
+                tensor.device()
+            )
+        }
+        self.recorded.lock().unwrap().push(tensor.clone());
+        Ok(())
+    }
+
+    fn htod<T: DeviceRepr>(&self, src: &[T]) -> Result<CudaSlice<T>> {
+        use cudarc::driver::DevicePtrMut;
+        // The buffer is allocated on this stream so that the copy does not have to wait for the
+        // allocations queued on the default stream.
+        let mut dst = unsafe {
+            self.device
+                .alloc_on_stream::<T>(src.len(), self.stream.stream)
+        }
+        .w()?;
+        unsafe { result::memcpy_htod_async(*dst.device_ptr_mut(), src, self.stream.stream) }.w()?;
+        Ok(dst)
+    }
+
+    /// Copies some cpu storage to the device, the copy is queued on this stream.
+    pub fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<CudaStorage> {
+        let slice = match storage {
+            CpuStorage::U8(storage) => CudaStorageSlice::U8(self.htod(storage)?),
+            CpuStorage::U32(storage) => CudaStorageSlice::U32(self.htod(storage)?),
+            CpuStorage::I64(storage) => CudaStorageSlice::I64(self.htod(storage)?),
+            CpuStorage::BF16(storage) => CudaStorageSlice::BF16(self.htod(storage)?),
+            CpuStorage::F16(storage) => CudaStorageSlice::F16(self.htod(storage)?),
+            CpuStorage::F32(storage) => CudaStorageSlice::F32(self.htod(storage)?),
+            CpuStorage::F64(storage) => CudaStorageSlice::F64(self.htod(storage)?),
+            CpuStorage::F8E4M3(storage) => CudaStorageSlice::F8E4M3(self.htod(storage)?),
+            CpuStorage::F8E5M2(storage) => CudaStorageSlice::F8E5M2(self.htod(storage)?),
+            CpuStorage::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: "storage_from_cpu_storage",
+            })
+            .w()?,
+        };
+        Ok(CudaStorage {
+            slice,
+            device: self.device.clone(),
+        })
+    }
+}
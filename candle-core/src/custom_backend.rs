@@ -0,0 +1,561 @@
+//! Devices implemented outside of candle-core.
+//!
+//! A crate providing a new kind of device implements [`CustomBackendDevice`] and
+//! [`CustomBackendStorage`], the device can then be used as any other device through
+//! [`crate::Device::new_custom`]. Compared to [`crate::backend::BackendStorage`] these traits are
+//! object safe: the operations are identified by name rather than by a type parameter, and the
+//! other storages involved in an operation are passed as trait objects that the backend can
+//! downcast to its own storage type.
+//!
+//! The user-defined ops, e.g. [`crate::CustomOp1`], have no implementation for these devices, they
+//! run on the cpu and the data is moved back and forth.
+use crate::backend::{BackendDevice, BackendStorage};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::{CpuStorage, DType, DeviceLocation, Error, Layout, Result, Shape};
+use std::any::Any;
+use std::sync::Arc;
+
+pub type DynStorage = Box<dyn CustomBackendStorage>;
+
+/// The storage of a custom device, see the [module](self) level documentation.
+///
+/// The storages passed as arguments are always on the same device as `self`.
+pub trait CustomBackendStorage: std::fmt::Debug + Send + Sync + 'static {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn try_clone(&self, _: &Layout) -> Result<DynStorage>;
+
+    fn dtype(&self) -> DType;
+
+    fn to_cpu_storage(&self) -> Result<CpuStorage>;
+
+    fn affine(&self, _: &Layout, _: f64, _: f64) -> Result<DynStorage>;
+
+    fn powf(&self, _: &Layout, _: f64) -> Result<DynStorage>;
+
+    fn elu(&self, _: &Layout, _: f64) -> Result<DynStorage>;
+
+    fn reduce_op(&self, _: ReduceOp, _: &Layout, _: &[usize]) -> Result<DynStorage>;
+
+    fn cmp(
+        &self,
+        _: CmpOp,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &Layout,
+    ) -> Result<DynStorage>;
+
+    fn to_dtype(&self, _: &Layout, _: DType) -> Result<DynStorage>;
+
+    /// Applies an element-wise unary op, `op` is the name of the op, e.g. `"exp"` or `"gelu"`.
+    fn unary(&self, _: &Layout, op: &'static str) -> Result<DynStorage>;
+
+    /// Applies an element-wise binary op, `op` is the name of the op, e.g. `"add"` or
+    /// `"maximum"`.
+    fn binary(
+        &self,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &Layout,
+        op: &'static str,
+    ) -> Result<DynStorage>;
+
+    fn where_cond(
+        &self,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+    ) -> Result<DynStorage>;
+
+    fn conv1d(
+        &self,
+        _l: &Layout,
+        _kernel: &dyn CustomBackendStorage,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConv1D,
+    ) -> Result<DynStorage>;
+
+    fn conv_transpose1d(
+        &self,
+        _l: &Layout,
+        _kernel: &dyn CustomBackendStorage,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConvTranspose1D,
+    ) -> Result<DynStorage>;
+
+    fn conv2d(
+        &self,
+        _l: &Layout,
+        _kernel: &dyn CustomBackendStorage,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConv2D,
+    ) -> Result<DynStorage>;
+
+    fn conv_transpose2d(
+        &self,
+        _l: &Layout,
+        _kernel: &dyn CustomBackendStorage,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConvTranspose2D,
+    ) -> Result<DynStorage>;
+
+    fn avg_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<DynStorage>;
+    fn max_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<DynStorage>;
+    fn upsample_nearest1d(&self, _: &Layout, _: usize) -> Result<DynStorage>;
+    fn upsample_nearest2d(&self, _: &Layout, _: usize, _: usize) -> Result<DynStorage>;
+
+    fn gather(
+        &self,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: usize,
+    ) -> Result<DynStorage>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn scatter_add(
+        &self,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: usize,
+    ) -> Result<DynStorage>;
+
+    fn index_select(
+        &self,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &Layout,
+        _: usize,
+    ) -> Result<DynStorage>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn index_add(
+        &self,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: usize,
+    ) -> Result<DynStorage>;
+
+    fn index_set(
+        &self,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: &dyn CustomBackendStorage,
+        _: &Layout,
+        _: usize,
+    ) -> Result<DynStorage>;
+
+    fn matmul(
+        &self,
+        _: &dyn CustomBackendStorage,
+        _: (usize, usize, usize, usize),
+        _: &Layout,
+        _: &Layout,
+    ) -> Result<DynStorage>;
+
+    fn copy_strided_src(
+        &self,
+        _: &mut dyn CustomBackendStorage,
+        _: usize,
+        _: &Layout,
+    ) -> Result<()>;
+
+    #[allow(clippy::too_many_arguments)]
+    // Similar to cudaMemcpy2D, though values are in elements and not in bytes.
+    fn copy2d(
+        &self,
+        _: &mut dyn CustomBackendStorage,
+        _d1: usize,
+        _d2: usize,
+        _src_stride1: usize,
+        _dst_stride1: usize,
+        _src_offset: usize,
+        _dst_offset: usize,
+    ) -> Result<()>;
+}
+
+/// A custom device, see the [module](self) level documentation.
+pub trait CustomBackendDevice: std::fmt::Debug + Send + Sync + 'static {
+    fn as_any(&self) -> &dyn Any;
+
+    /// The name of this kind of device, e.g. `"npu"`, this is used in the device location.
+    fn name(&self) -> &'static str;
+
+    /// The index of this device among the devices of the same kind.
+    fn ordinal(&self) -> usize;
+
+    /// Whether the tensors of both devices can be used in the same operations, the default is to
+    /// compare the device locations.
+    fn same_device(&self, rhs: &dyn CustomBackendDevice) -> bool {
+        self.name() == rhs.name() && self.ordinal() == rhs.ordinal()
+    }
+
+    fn zeros_impl(&self, _shape: &Shape, _dtype: DType) -> Result<DynStorage>;
+
+    fn ones_impl(&self, _shape: &Shape, _dtype: DType) -> Result<DynStorage>;
+
+    /// # Safety
+    /// This function is unsafe as it doesn't initialize the underlying data store.
+    /// The caller should ensure that the data is properly initialized as early as possible
+    /// after this call.
+    unsafe fn alloc_uninit(&self, _shape: &Shape, _dtype: DType) -> Result<DynStorage>;
+
+    fn storage_from_cpu_storage(&self, _: &CpuStorage) -> Result<DynStorage>;
+
+    fn storage_from_cpu_storage_owned(&self, storage: CpuStorage) -> Result<DynStorage> {
+        self.storage_from_cpu_storage(&storage)
+    }
+
+    fn rand_uniform(&self, _: &Shape, _: DType, _: f64, _: f64) -> Result<DynStorage>;
+
+    fn rand_normal(&self, _: &Shape, _: DType, _: f64, _: f64) -> Result<DynStorage>;
+
+    fn set_seed(&self, _: u64) -> Result<()>;
+
+    /// Synchronize should block until all the operations on the device are completed.
+    fn synchronize(&self) -> Result<()>;
+}
+
+/// A handle to a custom device, this is cheap to clone.
+#[derive(Debug, Clone)]
+pub struct CustomDevice(Arc<dyn CustomBackendDevice>);
+
+impl CustomDevice {
+    pub fn new<D: CustomBackendDevice>(device: D) -> Self {
+        Self(Arc::new(device))
+    }
+
+    pub fn inner(&self) -> &dyn CustomBackendDevice {
+        self.0.as_ref()
+    }
+
+    /// Returns the underlying device if it has type `D`.
+    pub fn downcast_ref<D: CustomBackendDevice>(&self) -> Option<&D> {
+        self.0.as_any().downcast_ref()
+    }
+
+    fn wrap(&self, storage: Result<DynStorage>) -> Result<CustomStorage> {
+        Ok(CustomStorage {
+            storage: storage?,
+            device: self.clone(),
+        })
+    }
+}
+
+/// The storage for a tensor on a custom device.
+#[derive(Debug)]
+pub struct CustomStorage {
+    storage: DynStorage,
+    device: CustomDevice,
+}
+
+impl CustomStorage {
+    pub fn inner(&self) -> &dyn CustomBackendStorage {
+        self.storage.as_ref()
+    }
+
+    /// Returns the underlying storage if it has type `S`.
+    pub fn downcast_ref<S: CustomBackendStorage>(&self) -> Option<&S> {
+        self.storage.as_any().downcast_ref()
+    }
+
+    /// Returns the underlying storage if it has type `S`.
+    pub fn downcast_mut<S: CustomBackendStorage>(&mut self) -> Option<&mut S> {
+        self.storage.as_any_mut().downcast_mut()
+    }
+
+    fn wrap(&self, storage: Result<DynStorage>) -> Result<Self> {
+        self.device.wrap(storage)
+    }
+}
+
+impl BackendStorage for CustomStorage {
+    type Device = CustomDevice;
+
+    fn try_clone(&self, l: &Layout) -> Result<Self> {
+        self.wrap(self.storage.try_clone(l))
+    }
+
+    fn dtype(&self) -> DType {
+        self.storage.dtype()
+    }
+
+    fn device(&self) -> &Self::Device {
+        &self.device
+    }
+
+    fn to_cpu_storage(&self) -> Result<CpuStorage> {
+        self.storage.to_cpu_storage()
+    }
+
+    fn affine(&self, l: &Layout, mul: f64, add: f64) -> Result<Self> {
+        self.wrap(self.storage.affine(l, mul, add))
+    }
+
+    fn powf(&self, l: &Layout, alpha: f64) -> Result<Self> {
+        self.wrap(self.storage.powf(l, alpha))
+    }
+
+    fn elu(&self, l: &Layout, alpha: f64) -> Result<Self> {
+        self.wrap(self.storage.elu(l, alpha))
+    }
+
+    fn reduce_op(&self, op: ReduceOp, l: &Layout, dims: &[usize]) -> Result<Self> {
+        self.wrap(self.storage.reduce_op(op, l, dims))
+    }
+
+    fn cmp(&self, op: CmpOp, rhs: &Self, lhs_l: &Layout, rhs_l: &Layout) -> Result<Self> {
+        self.wrap(self.storage.cmp(op, rhs.inner(), lhs_l, rhs_l))
+    }
+
+    fn to_dtype(&self, l: &Layout, dtype: DType) -> Result<Self> {
+        self.wrap(self.storage.to_dtype(l, dtype))
+    }
+
+    fn unary_impl<B: UnaryOpT>(&self, l: &Layout) -> Result<Self> {
+        self.wrap(self.storage.unary(l, B::NAME))
+    }
+
+    fn binary_impl<B: BinaryOpT>(
+        &self,
+        rhs: &Self,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<Self> {
+        self.wrap(self.storage.binary(rhs.inner(), lhs_l, rhs_l, B::NAME))
+    }
+
+    fn where_cond(
+        &self,
+        l: &Layout,
+        t: &Self,
+        t_l: &Layout,
+        f: &Self,
+        f_l: &Layout,
+    ) -> Result<Self> {
+        self.wrap(self.storage.where_cond(l, t.inner(), t_l, f.inner(), f_l))
+    }
+
+    fn conv1d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv1D,
+    ) -> Result<Self> {
+        self.wrap(self.storage.conv1d(l, kernel.inner(), kernel_l, params))
+    }
+
+    fn conv_transpose1d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConvTranspose1D,
+    ) -> Result<Self> {
+        self.wrap(
+            self.storage
+                .conv_transpose1d(l, kernel.inner(), kernel_l, params),
+        )
+    }
+
+    fn conv2d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        self.wrap(self.storage.conv2d(l, kernel.inner(), kernel_l, params))
+    }
+
+    fn conv_transpose2d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConvTranspose2D,
+    ) -> Result<Self> {
+        self.wrap(
+            self.storage
+                .conv_transpose2d(l, kernel.inner(), kernel_l, params),
+        )
+    }
+
+    fn avg_pool2d(&self, l: &Layout, k: (usize, usize), s: (usize, usize)) -> Result<Self> {
+        self.wrap(self.storage.avg_pool2d(l, k, s))
+    }
+
+    fn max_pool2d(&self, l: &Layout, k: (usize, usize), s: (usize, usize)) -> Result<Self> {
+        self.wrap(self.storage.max_pool2d(l, k, s))
+    }
+
+    fn upsample_nearest1d(&self, l: &Layout, sz: usize) -> Result<Self> {
+        self.wrap(self.storage.upsample_nearest1d(l, sz))
+    }
+
+    fn upsample_nearest2d(&self, l: &Layout, h: usize, w: usize) -> Result<Self> {
+        self.wrap(self.storage.upsample_nearest2d(l, h, w))
+    }
+
+    fn gather(&self, l: &Layout, ids: &Self, ids_l: &Layout, dim: usize) -> Result<Self> {
+        self.wrap(self.storage.gather(l, ids.inner(), ids_l, dim))
+    }
+
+    fn scatter_add(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        self.wrap(
+            self.storage
+                .scatter_add(l, ids.inner(), ids_l, src.inner(), src_l, dim),
+        )
+    }
+
+    fn index_select(&self, ids: &Self, l: &Layout, ids_l: &Layout, dim: usize) -> Result<Self> {
+        self.wrap(self.storage.index_select(ids.inner(), l, ids_l, dim))
+    }
+
+    fn index_add(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        self.wrap(
+            self.storage
+                .index_add(l, ids.inner(), ids_l, src.inner(), src_l, dim),
+        )
+    }
+
+    fn index_set(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        self.wrap(
+            self.storage
+                .index_set(l, ids.inner(), ids_l, src.inner(), src_l, dim),
+        )
+    }
+
+    fn matmul(
+        &self,
+        rhs: &Self,
+        bmnk: (usize, usize, usize, usize),
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<Self> {
+        self.wrap(self.storage.matmul(rhs.inner(), bmnk, lhs_l, rhs_l))
+    }
+
+    fn copy_strided_src(&self, dst: &mut Self, dst_offset: usize, src_l: &Layout) -> Result<()> {
+        self.storage
+            .copy_strided_src(dst.storage.as_mut(), dst_offset, src_l)
+    }
+
+    fn copy2d(
+        &self,
+        dst: &mut Self,
+        d1: usize,
+        d2: usize,
+        src_s: usize,
+        dst_s: usize,
+        src_o: usize,
+        dst_o: usize,
+    ) -> Result<()> {
+        self.storage
+            .copy2d(dst.storage.as_mut(), d1, d2, src_s, dst_s, src_o, dst_o)
+    }
+}
+
+impl BackendDevice for CustomDevice {
+    type Storage = CustomStorage;
+
+    fn new(_: usize) -> Result<Self> {
+        Err(Error::Msg("custom devices have to be created by their backend".to_string()).bt())
+    }
+
+    fn location(&self) -> DeviceLocation {
+        DeviceLocation::Custom {
+            name: self.0.name(),
+            id: self.0.ordinal(),
+        }
+    }
+
+    fn same_device(&self, rhs: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &rhs.0) || self.0.same_device(rhs.inner())
+    }
+
+    fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<CustomStorage> {
+        self.wrap(self.0.zeros_impl(shape, dtype))
+    }
+
+    fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<CustomStorage> {
+        self.wrap(self.0.ones_impl(shape, dtype))
+    }
+
+    unsafe fn alloc_uninit(&self, shape: &Shape, dtype: DType) -> Result<CustomStorage> {
+        self.wrap(self.0.alloc_uninit(shape, dtype))
+    }
+
+    fn storage_from_slice<T: crate::WithDType>(&self, data: &[T]) -> Result<CustomStorage> {
+        self.wrap(
+            self.0
+                .storage_from_cpu_storage_owned(T::to_cpu_storage(data)),
+        )
+    }
+
+    fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<CustomStorage> {
+        self.wrap(self.0.storage_from_cpu_storage(storage))
+    }
+
+    fn storage_from_cpu_storage_owned(&self, storage: CpuStorage) -> Result<CustomStorage> {
+        self.wrap(self.0.storage_from_cpu_storage_owned(storage))
+    }
+
+    fn rand_uniform(&self, shape: &Shape, dtype: DType, lo: f64, up: f64) -> Result<CustomStorage> {
+        self.wrap(self.0.rand_uniform(shape, dtype, lo, up))
+    }
+
+    fn rand_normal(
+        &self,
+        shape: &Shape,
+        dtype: DType,
+        mean: f64,
+        std: f64,
+    ) -> Result<CustomStorage> {
+        self.wrap(self.0.rand_normal(shape, dtype, mean, std))
+    }
+
+    fn set_seed(&self, seed: u64) -> Result<()> {
+        self.0.set_seed(seed)
+    }
+
+    fn synchronize(&self) -> Result<()> {
+        self.0.synchronize()
+    }
+}
@@ -243,6 +243,44 @@ impl Tensor {
     }
 }
 
+// The backward function used by `Tensor::apply_with_bwd`, it takes as arguments the tensors
+// used in the forward pass, the result of the forward pass and the gradient of this result, and
+// returns the gradient for each of the arguments.
+pub(crate) type BackwardFn =
+    dyn Fn(&[Tensor], &Tensor, &Tensor) -> Result<Vec<Option<Tensor>>> + Send + Sync;
+
+impl Tensor {
+    /// Applies `fwd` to `args` and uses `bwd` to compute the gradients of `args` when
+    /// backpropagating through the result. The operations run by `fwd` are not tracked, so this
+    /// can be used with custom kernels that have been applied via `apply_op*_no_bwd` or with any
+    /// number of arguments.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Tensor, Var};
+    /// let x = Var::new(&[1f32, 4., 9.], &Device::Cpu)?;
+    /// // A square root with a custom gradient.
+    /// let y = Tensor::apply_with_bwd(
+    ///     &[x.as_tensor().clone()],
+    ///     |xs| xs[0].sqrt(),
+    ///     |_xs, res, grad| Ok(vec![Some(grad.div(&res.affine(2., 0.)?)?)]),
+    /// )?;
+    /// let grads = y.sum_all()?.backward()?;
+    /// assert_eq!(grads.get(&x).unwrap().to_vec1::<f32>()?, [0.5, 0.25, 1. / 6.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn apply_with_bwd<F, B>(args: &[Tensor], fwd: F, bwd: B) -> Result<Self>
+    where
+        F: FnOnce(&[Tensor]) -> Result<Tensor>,
+        B: Fn(&[Tensor], &Tensor, &Tensor) -> Result<Vec<Option<Tensor>>> + Send + Sync + 'static,
+    {
+        let detached = args.iter().map(|arg| arg.detach()).collect::<Vec<_>>();
+        let res = fwd(&detached)?;
+        let bwd: Arc<BackwardFn> = Arc::new(bwd);
+        let op = BackpropOp::new(args, |args| Op::CustomBwd(args, bwd.clone()));
+        Ok(res.with_op(op, false))
+    }
+}
+
 // In place ops.
 
 /// Unary ops that can be defined in user-land.
@@ -9,6 +9,20 @@ pub enum DeviceLocation {
     Cpu,
     Cuda { gpu_id: usize },
     Metal { gpu_id: usize },
+    Custom { name: &'static str, id: usize },
+}
+
+/// The memory usage of a device in bytes, see [`Device::memory_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryStats {
+    /// The memory used by the live allocations.
+    pub allocated: u64,
+    /// The memory reserved by the device allocator, this includes the memory that is cached
+    /// for later allocations.
+    pub reserved: u64,
+    /// The peak of the allocated memory since the start of the process or the last call to
+    /// [`Device::reset_peak_memory_stats`].
+    pub peak_allocated: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +30,7 @@ pub enum Device {
     Cpu,
     Cuda(crate::CudaDevice),
     Metal(crate::MetalDevice),
+    Custom(crate::CustomDevice),
 }
 
 pub trait NdArray {
@@ -130,15 +145,80 @@ impl Device {
         Ok(Self::Cuda(crate::CudaDevice::new(ordinal)?))
     }
 
+    /// Creates a cuda device using a dedicated stream, see [`crate::CudaDevice::new_with_stream`].
+    pub fn new_cuda_with_stream(ordinal: usize) -> Result<Self> {
+        Ok(Self::Cuda(crate::CudaDevice::new_with_stream(ordinal)?))
+    }
+
+    /// Returns the memory usage of the device.
+    ///
+    /// On the cpu this requires [`crate::cpu::alloc::TrackingAllocator`] to be the global
+    /// allocator, either by enabling the `tracking-allocator` feature of this crate or by
+    /// installing it in the binary with `#[global_allocator]`. The values then cover all the
+    /// allocations of the process. Otherwise the usage is not known and an error is returned.
+    pub fn memory_stats(&self) -> Result<MemoryStats> {
+        match self {
+            Self::Cpu => match crate::cpu::alloc::TrackingAllocator::stats() {
+                Some((allocated, peak_allocated)) => Ok(MemoryStats {
+                    allocated,
+                    reserved: allocated,
+                    peak_allocated,
+                }),
+                None => crate::bail!(
+                    "memory_stats on the cpu requires TrackingAllocator as the global allocator, see the tracking-allocator feature"
+                ),
+            },
+            Self::Cuda(device) => {
+                let stats = device.mem_pool_stats()?;
+                Ok(MemoryStats {
+                    allocated: stats.used,
+                    reserved: stats.reserved,
+                    peak_allocated: stats.peak_used,
+                })
+            }
+            Self::Metal(_) => crate::bail!("memory_stats is not supported on metal"),
+            Self::Custom(_) => crate::bail!("memory_stats is not supported on custom devices"),
+        }
+    }
+
+    /// Resets the peak memory usage reported by [`Device::memory_stats`] to the current usage.
+    /// This has the same requirements as [`Device::memory_stats`].
+    pub fn reset_peak_memory_stats(&self) -> Result<()> {
+        match self {
+            Self::Cpu => {
+                if crate::cpu::alloc::TrackingAllocator::stats().is_none() {
+                    crate::bail!(
+                        "memory_stats on the cpu requires TrackingAllocator as the global allocator, see the tracking-allocator feature"
+                    )
+                }
+                crate::cpu::alloc::TrackingAllocator::reset_peak();
+                Ok(())
+            }
+            Self::Cuda(device) => device.reset_mem_pool_peak(),
+            Self::Metal(_) => crate::bail!("memory_stats is not supported on metal"),
+            Self::Custom(_) => crate::bail!("memory_stats is not supported on custom devices"),
+        }
+    }
+
     pub fn new_metal(ordinal: usize) -> Result<Self> {
         Ok(Self::Metal(crate::MetalDevice::new(ordinal)?))
     }
 
+    /// Creates a device implemented outside of candle-core, see [`crate::custom_backend`].
+    pub fn new_custom<D: crate::custom_backend::CustomBackendDevice>(device: D) -> Self {
+        Self::Custom(crate::CustomDevice::new(device))
+    }
+
+    /// Seeds the random number generator of the device, this is used by the random tensor
+    /// constructors such as [`crate::Tensor::rand`] and [`crate::Tensor::randn`]. On the cpu the
+    /// seed only applies to the current thread, the other threads keep their own state and
+    /// [`crate::cpu_backend::CpuDevice::clear_seed`] goes back to unseeded values.
     pub fn set_seed(&self, seed: u64) -> Result<()> {
         match self {
             Self::Cpu => CpuDevice.set_seed(seed),
             Self::Cuda(c) => c.set_seed(seed),
             Self::Metal(m) => m.set_seed(seed),
+            Self::Custom(m) => m.set_seed(seed),
         }
     }
 
@@ -147,6 +227,7 @@ impl Device {
             (Self::Cpu, Self::Cpu) => true,
             (Self::Cuda(lhs), Self::Cuda(rhs)) => lhs.same_device(rhs),
             (Self::Metal(lhs), Self::Metal(rhs)) => lhs.same_device(rhs),
+            (Self::Custom(lhs), Self::Custom(rhs)) => lhs.same_device(rhs),
             _ => false,
         }
     }
@@ -156,6 +237,7 @@ impl Device {
             Self::Cpu => DeviceLocation::Cpu,
             Self::Cuda(device) => device.location(),
             Device::Metal(device) => device.location(),
+            Device::Custom(device) => device.location(),
         }
     }
 
@@ -171,6 +253,10 @@ impl Device {
         matches!(self, Self::Metal(_))
     }
 
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Self::Custom(_))
+    }
+
     pub fn cuda_if_available(ordinal: usize) -> Result<Self> {
         if crate::utils::cuda_is_available() {
             Self::new_cuda(ordinal)
@@ -179,6 +265,31 @@ impl Device {
         }
     }
 
+    /// Creates a new 1D tensor on this device with `steps` values evenly spaced from `start` to
+    /// `end`, both included, see [`crate::Tensor::linspace`].
+    ///
+    /// ```rust
+    /// use candle_core::Device;
+    /// let t = Device::Cpu.linspace(0f32, 1., 5)?;
+    /// assert_eq!(t.to_vec1::<f32>()?, &[0., 0.25, 0.5, 0.75, 1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn linspace<D: WithDType>(&self, start: D, end: D, steps: usize) -> Result<crate::Tensor> {
+        crate::Tensor::linspace(start, end, steps, self)
+    }
+
+    /// Creates a new 1D tensor on this device with `steps` values evenly spaced on a log scale
+    /// from `base^start` to `base^end`, both included, see [`crate::Tensor::logspace`].
+    pub fn logspace<D: WithDType>(
+        &self,
+        start: D,
+        end: D,
+        steps: usize,
+        base: f64,
+    ) -> Result<crate::Tensor> {
+        crate::Tensor::logspace(start, end, steps, base, self)
+    }
+
     pub(crate) fn rand_uniform_f64(
         &self,
         lo: f64,
@@ -205,6 +316,10 @@ impl Device {
                 let storage = device.rand_uniform(shape, dtype, lo, up)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.rand_uniform(shape, dtype, lo, up)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -243,6 +358,10 @@ impl Device {
                 let storage = device.rand_normal(shape, dtype, mean, std)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.rand_normal(shape, dtype, mean, std)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -269,6 +388,10 @@ impl Device {
                 let storage = device.ones_impl(shape, dtype)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.ones_impl(shape, dtype)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -286,6 +409,10 @@ impl Device {
                 let storage = device.zeros_impl(shape, dtype)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.zeros_impl(shape, dtype)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -303,6 +430,10 @@ impl Device {
                 let storage = device.alloc_uninit(shape, dtype)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.alloc_uninit(shape, dtype)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -317,6 +448,10 @@ impl Device {
                 let storage = device.storage_from_slice(data)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = device.storage_from_slice(data)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -333,6 +468,11 @@ impl Device {
                 let storage = device.storage_from_cpu_storage_owned(storage)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = array.to_cpu_storage();
+                let storage = device.storage_from_cpu_storage_owned(storage)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -349,6 +489,11 @@ impl Device {
                 let storage = device.storage_from_cpu_storage_owned(storage)?;
                 Ok(Storage::Metal(storage))
             }
+            Device::Custom(device) => {
+                let storage = S::to_cpu_storage_owned(data);
+                let storage = device.storage_from_cpu_storage_owned(storage)?;
+                Ok(Storage::Custom(storage))
+            }
         }
     }
 
@@ -357,6 +502,7 @@ impl Device {
             Self::Cpu => Ok(()),
             Self::Cuda(d) => d.synchronize(),
             Self::Metal(d) => d.synchronize(),
+            Self::Custom(d) => d.synchronize(),
         }
     }
 }
@@ -1,7 +1,7 @@
 /// Pretty printing of tensors
 /// This implementation should be in line with the PyTorch version.
 /// https://github.com/pytorch/pytorch/blob/7b419e8513a024e172eae767e24ec1b849976b13/torch/_tensor_str.py
-use crate::{DType, Result, Tensor, WithDType};
+use crate::{DType, Result, Tensor, WithDType, F8E4M3, F8E5M2};
 use half::{bf16, f16};
 
 impl Tensor {
@@ -17,17 +17,25 @@ impl Tensor {
             crate::DeviceLocation::Metal { gpu_id } => {
                 format!(", metal:{}", gpu_id)
             }
+            crate::DeviceLocation::Custom { name, id } => {
+                format!(", {name}:{id}")
+            }
         };
 
+        // Booleans are displayed through their u8 representation.
+        let values = match self.dtype() {
+            DType::Bool => self.to_dtype(DType::U8).unwrap_or_else(|_| self.clone()),
+            _ => self.clone(),
+        };
         write!(f, "Tensor[")?;
         match self.dims() {
             [] => {
-                if let Ok(v) = self.to_scalar::<T>() {
+                if let Ok(v) = values.to_scalar::<T>() {
                     write!(f, "{v}")?
                 }
             }
             [s] if *s < 10 => {
-                if let Ok(vs) = self.to_vec1::<T>() {
+                if let Ok(vs) = values.to_vec1::<T>() {
                     for (i, v) in vs.iter().enumerate() {
                         if i > 0 {
                             write!(f, ", ")?;
@@ -60,6 +68,9 @@ impl std::fmt::Debug for Tensor {
             DType::F16 => self.fmt_dt::<f16>(f),
             DType::F32 => self.fmt_dt::<f32>(f),
             DType::F64 => self.fmt_dt::<f64>(f),
+            DType::F8E4M3 => self.fmt_dt::<F8E4M3>(f),
+            DType::F8E5M2 => self.fmt_dt::<F8E5M2>(f),
+            DType::Bool => self.fmt_dt::<u8>(f),
         }
     }
 }
@@ -469,6 +480,17 @@ impl std::fmt::Display for Tensor {
                 tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
                 writeln!(f)?;
             }
+            DType::Bool => {
+                let (t, to_display) =
+                    match (self.to_dtype(DType::U8), to_display.to_dtype(DType::U8)) {
+                        (Ok(t), Ok(to_display)) => (t, to_display),
+                        (Err(err), _) | (_, Err(err)) => return write!(f, "{err:?}"),
+                    };
+                let tf: IntFormatter<u8> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
             DType::BF16 => {
                 if let Ok(tf) = FloatFormatter::<bf16>::new(&to_display, &po) {
                     let max_w = tf.max_width(&to_display);
@@ -497,6 +519,19 @@ impl std::fmt::Display for Tensor {
                     writeln!(f)?;
                 }
             }
+            DType::F8E4M3 | DType::F8E5M2 => {
+                // The 8 bits float values are formatted through their f32 representation.
+                let (t, to_display) =
+                    match (self.to_dtype(DType::F32), to_display.to_dtype(DType::F32)) {
+                        (Ok(t), Ok(to_display)) => (t, to_display),
+                        (Err(err), _) | (_, Err(err)) => return write!(f, "{err:?}"),
+                    };
+                if let Ok(tf) = FloatFormatter::<f32>::new(&to_display, &po) {
+                    let max_w = tf.max_width(&to_display);
+                    tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
+                    writeln!(f)?;
+                }
+            }
         };
 
         let device_str = match self.device().location() {
@@ -507,6 +542,9 @@ impl std::fmt::Display for Tensor {
             crate::DeviceLocation::Metal { gpu_id } => {
                 format!(", metal:{}", gpu_id)
             }
+            crate::DeviceLocation::Custom { name, id } => {
+                format!(", {name}:{id}")
+            }
         };
 
         write!(
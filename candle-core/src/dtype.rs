@@ -20,6 +20,12 @@ pub enum DType {
     F32,
     // Floating-point using double precision (64 bits).
     F64,
+    // 8 bits floating-point with 4 exponent bits and 3 mantissa bits.
+    F8E4M3,
+    // 8 bits floating-point with 5 exponent bits and 2 mantissa bits.
+    F8E5M2,
+    // Boolean, each element uses a byte.
+    Bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -44,6 +50,9 @@ impl std::str::FromStr for DType {
             "f16" => Ok(Self::F16),
             "f32" => Ok(Self::F32),
             "f64" => Ok(Self::F64),
+            "f8e4m3" => Ok(Self::F8E4M3),
+            "f8e5m2" => Ok(Self::F8E5M2),
+            "bool" => Ok(Self::Bool),
             _ => Err(DTypeParseError(s.to_string())),
         }
     }
@@ -60,6 +69,9 @@ impl DType {
             Self::F16 => "f16",
             Self::F32 => "f32",
             Self::F64 => "f64",
+            Self::F8E4M3 => "f8e4m3",
+            Self::F8E5M2 => "f8e5m2",
+            Self::Bool => "bool",
         }
     }
 
@@ -73,20 +85,23 @@ impl DType {
             Self::F16 => 2,
             Self::F32 => 4,
             Self::F64 => 8,
+            Self::F8E4M3 | Self::F8E5M2 => 1,
+            Self::Bool => 1,
         }
     }
 
     pub fn is_int(&self) -> bool {
         match self {
             Self::U8 | Self::U32 | Self::I64 => true,
-            Self::BF16 | Self::F16 | Self::F32 | Self::F64 => false,
+            Self::BF16 | Self::F16 | Self::F32 | Self::F64 | Self::F8E4M3 | Self::F8E5M2 => false,
+            Self::Bool => false,
         }
     }
 
     pub fn is_float(&self) -> bool {
         match self {
-            Self::U8 | Self::U32 | Self::I64 => false,
-            Self::BF16 | Self::F16 | Self::F32 | Self::F64 => true,
+            Self::U8 | Self::U32 | Self::I64 | Self::Bool => false,
+            Self::BF16 | Self::F16 | Self::F32 | Self::F64 | Self::F8E4M3 | Self::F8E5M2 => true,
         }
     }
 }
@@ -165,6 +180,7 @@ macro_rules! with_dtype {
         }
     };
 }
+use crate::{F8E4M3, F8E5M2};
 use half::{bf16, f16};
 
 with_dtype!(u8, U8, |v: f64| v as u8, |v: u8| v as f64);
@@ -174,6 +190,8 @@ with_dtype!(f16, F16, f16::from_f64, f16::to_f64);
 with_dtype!(bf16, BF16, bf16::from_f64, bf16::to_f64);
 with_dtype!(f32, F32, |v: f64| v as f32, |v: f32| v as f64);
 with_dtype!(f64, F64, |v: f64| v, |v: f64| v);
+with_dtype!(F8E4M3, F8E4M3, F8E4M3::from_f64, F8E4M3::to_f64);
+with_dtype!(F8E5M2, F8E5M2, F8E5M2::from_f64, F8E5M2::to_f64);
 
 pub trait IntDType: WithDType {
     fn is_true(&self) -> bool;
@@ -8,12 +8,117 @@ pub struct CudaDevice;
 #[derive(Debug)]
 pub struct CudaStorage;
 
+#[derive(Debug, Clone)]
+pub struct CudaStream;
+
+#[derive(Debug)]
+pub struct CudaGraph;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemPoolStats {
+    pub reserved: u64,
+    pub used: u64,
+    pub peak_used: u64,
+}
+
 macro_rules! fail {
     () => {
         unimplemented!("cuda support has not been enabled, add `cuda` feature to enable.")
     };
 }
 
+impl CudaDevice {
+    pub fn new_with_stream(_: usize) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn capture<F: FnOnce() -> Result<()>>(&self, _: F) -> Result<CudaGraph> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn new_stream(&self) -> Result<CudaStream> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn enable_peer_access(&self, _: &Self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn storage_from_cpu_storage_async(&self, _: &CpuStorage) -> Result<CudaStorage> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn trim_pinned_pool(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn enable_caching_allocator(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn caching_allocator_enabled(&self) -> bool {
+        false
+    }
+
+    pub fn set_mem_pool_release_threshold(&self, _: u64) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn mem_pool_stats(&self) -> Result<MemPoolStats> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn trim_mem_pool(&self, _: usize) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn reset_mem_pool_peak(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
+impl CudaGraph {
+    pub fn device(&self) -> &CudaDevice {
+        fail!()
+    }
+
+    pub fn replay(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
+impl CudaStream {
+    pub fn device(&self) -> &CudaDevice {
+        fail!()
+    }
+
+    pub fn wait_for_default(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn join(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn synchronize(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn record_tensor(&self, _: &crate::Tensor) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn storage_from_cpu_storage(&self, _: &CpuStorage) -> Result<CudaStorage> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
+impl CudaStorage {
+    pub fn transfer_to_device(&self, _: &CudaDevice) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
 impl crate::backend::BackendStorage for CudaStorage {
     type Device = CudaDevice;
 
@@ -140,6 +245,18 @@ impl crate::backend::BackendStorage for CudaStorage {
         Err(Error::NotCompiledWithCudaSupport)
     }
 
+    fn index_set(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: usize,
+    ) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
     fn matmul(
         &self,
         _: &Self,
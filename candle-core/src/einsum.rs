@@ -0,0 +1,477 @@
+//! Einstein summation over an arbitrary number of tensors, tensor contractions, and outer, cross
+//! and Kronecker products.
+//!
+//! The contraction is decomposed into a sequence of pairwise operations that only rely on
+//! `permute`, `reshape`, `sum` and batched `matmul`, so the result works on all the devices and
+//! supports backpropagation.
+use crate::{bail, shape::Dim, shape::Dims, Result, Tensor};
+
+// Ellipsis dimensions are mapped to labels in the unicode private use area so that they cannot
+// conflict with user provided labels.
+const ELLIPSIS_LABEL_START: u32 = 0xE000;
+
+fn ellipsis_label(idx: usize) -> char {
+    // The tensor rank is always far below the size of the private use area.
+    char::from_u32(ELLIPSIS_LABEL_START + idx as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+#[derive(Debug)]
+struct Equation {
+    inputs: Vec<Vec<char>>,
+    output: Vec<char>,
+}
+
+fn parse_labels(term: &str) -> Result<(Vec<char>, Option<usize>)> {
+    let mut labels = vec![];
+    let mut ellipsis_pos = None;
+    let mut chars = term.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if chars.next() != Some('.') || chars.next() != Some('.') {
+                    bail!("einsum: invalid ellipsis in '{term}'")
+                }
+                if ellipsis_pos.is_some() {
+                    bail!("einsum: more than one ellipsis in '{term}'")
+                }
+                ellipsis_pos = Some(labels.len())
+            }
+            c if c.is_ascii_alphabetic() => labels.push(c),
+            c if c.is_whitespace() => {}
+            c => bail!("einsum: invalid label '{c}' in '{term}'"),
+        }
+    }
+    Ok((labels, ellipsis_pos))
+}
+
+impl Equation {
+    fn parse(equation: &str, ranks: &[usize]) -> Result<Self> {
+        let (lhs, rhs) = match equation.split_once("->") {
+            None => (equation, None),
+            Some((lhs, rhs)) => (lhs, Some(rhs)),
+        };
+        let terms: Vec<&str> = lhs.split(',').collect();
+        if terms.len() != ranks.len() {
+            bail!(
+                "einsum: equation '{equation}' expects {} operands, got {}",
+                terms.len(),
+                ranks.len()
+            )
+        }
+        let mut max_ellipsis_dims = 0;
+        let mut parsed = Vec::with_capacity(terms.len());
+        for (term, &rank) in terms.iter().zip(ranks.iter()) {
+            let (labels, ellipsis_pos) = parse_labels(term)?;
+            let ellipsis_dims = match ellipsis_pos {
+                None => {
+                    if labels.len() != rank {
+                        bail!("einsum: term '{term}' does not match an operand of rank {rank}")
+                    }
+                    0
+                }
+                Some(_) => {
+                    if labels.len() > rank {
+                        bail!("einsum: term '{term}' does not match an operand of rank {rank}")
+                    }
+                    rank - labels.len()
+                }
+            };
+            max_ellipsis_dims = usize::max(max_ellipsis_dims, ellipsis_dims);
+            parsed.push((labels, ellipsis_pos, ellipsis_dims))
+        }
+        // Ellipsis dimensions are aligned on the right, as for broadcasting.
+        let inputs = parsed
+            .iter()
+            .map(|(labels, ellipsis_pos, ellipsis_dims)| match ellipsis_pos {
+                None => labels.clone(),
+                Some(pos) => {
+                    let mut l = labels[..*pos].to_vec();
+                    let start = max_ellipsis_dims - ellipsis_dims;
+                    l.extend((start..max_ellipsis_dims).map(ellipsis_label));
+                    l.extend_from_slice(&labels[*pos..]);
+                    l
+                }
+            })
+            .collect::<Vec<_>>();
+        let output = match rhs {
+            Some(rhs) => {
+                let (labels, ellipsis_pos) = parse_labels(rhs)?;
+                let output = match ellipsis_pos {
+                    None => labels.clone(),
+                    Some(pos) => {
+                        let mut l = labels[..pos].to_vec();
+                        l.extend((0..max_ellipsis_dims).map(ellipsis_label));
+                        l.extend_from_slice(&labels[pos..]);
+                        l
+                    }
+                };
+                for (i, c) in output.iter().enumerate() {
+                    if output[..i].contains(c) {
+                        bail!("einsum: label '{c}' appears more than once in the output")
+                    }
+                    if !inputs.iter().any(|l| l.contains(c)) {
+                        bail!("einsum: output label '{c}' does not appear in any input")
+                    }
+                }
+                output
+            }
+            None => {
+                // Implicit mode: the ellipsis dimensions come first, followed by the labels that
+                // appear exactly once in alphabetical order.
+                let mut output: Vec<char> = (0..max_ellipsis_dims).map(ellipsis_label).collect();
+                let mut once = vec![];
+                for labels in inputs.iter() {
+                    for &c in labels.iter() {
+                        if (c as u32) < ELLIPSIS_LABEL_START {
+                            let count: usize = inputs
+                                .iter()
+                                .map(|l| l.iter().filter(|&&v| v == c).count())
+                                .sum();
+                            if count == 1 {
+                                once.push(c)
+                            }
+                        }
+                    }
+                }
+                once.sort();
+                output.extend(once);
+                output
+            }
+        };
+        Ok(Self { inputs, output })
+    }
+}
+
+/// Extracts the diagonal for the repeated labels and sums over the labels that are not part of
+/// `keep`.
+fn reduce(t: Tensor, labels: Vec<char>, keep: &[char]) -> Result<(Tensor, Vec<char>)> {
+    let mut t = t;
+    let mut labels = labels;
+    // Take the diagonal for each label that appears multiple times.
+    while let Some((i, j)) = (0..labels.len()).find_map(|j| {
+        labels[..j]
+            .iter()
+            .position(|&c| c == labels[j])
+            .map(|i| (i, j))
+    }) {
+        let (n1, n2) = (t.dim(i)?, t.dim(j)?);
+        if n1 != n2 {
+            bail!(
+                "einsum: size mismatch for repeated label '{}', {n1} <> {n2}",
+                labels[i]
+            )
+        }
+        let mut perm: Vec<usize> = (0..labels.len()).filter(|&d| d != i && d != j).collect();
+        perm.push(i);
+        perm.push(j);
+        let eye = Tensor::eye(n1, t.dtype(), t.device())?;
+        t = t
+            .permute(perm.as_slice())?
+            .broadcast_mul(&eye)?
+            .sum(perm.len() - 1)?;
+        let label = labels[i];
+        labels = perm[..perm.len() - 2].iter().map(|&d| labels[d]).collect();
+        labels.push(label);
+    }
+    let sum_dims: Vec<usize> = (0..labels.len())
+        .filter(|&d| !keep.contains(&labels[d]))
+        .collect();
+    if !sum_dims.is_empty() {
+        t = t.sum(sum_dims.as_slice())?;
+        labels.retain(|c| keep.contains(c));
+    }
+    Ok((t, labels))
+}
+
+/// Contracts two operands, only the labels from `keep` are preserved in the result.
+fn contract(
+    (a, la): (Tensor, Vec<char>),
+    (b, lb): (Tensor, Vec<char>),
+    keep: &[char],
+) -> Result<(Tensor, Vec<char>)> {
+    let keep_a: Vec<char> = keep.iter().chain(lb.iter()).copied().collect();
+    let (a, la) = reduce(a, la, &keep_a)?;
+    let keep_b: Vec<char> = keep.iter().chain(la.iter()).copied().collect();
+    let (b, lb) = reduce(b, lb, &keep_b)?;
+
+    let mut batch = vec![];
+    let mut contracted = vec![];
+    for &c in la.iter() {
+        if lb.contains(&c) {
+            if keep.contains(&c) {
+                batch.push(c)
+            } else {
+                contracted.push(c)
+            }
+        }
+    }
+    let left: Vec<char> = la.iter().filter(|c| !lb.contains(c)).copied().collect();
+    let right: Vec<char> = lb.iter().filter(|c| !la.contains(c)).copied().collect();
+
+    // Broadcast the shared dimensions that have a single element on one side.
+    let size = |t: &Tensor, l: &[char], c: char| -> Result<usize> {
+        let pos = l.iter().position(|&v| v == c).unwrap_or(0);
+        t.dim(pos)
+    };
+    let mut a_dims = a.dims().to_vec();
+    let mut b_dims = b.dims().to_vec();
+    for &c in batch.iter().chain(contracted.iter()) {
+        let (ia, ib) = (
+            la.iter().position(|&v| v == c).unwrap_or(0),
+            lb.iter().position(|&v| v == c).unwrap_or(0),
+        );
+        match (a_dims[ia], b_dims[ib]) {
+            (n1, n2) if n1 == n2 => {}
+            (1, n) => a_dims[ia] = n,
+            (n, 1) => b_dims[ib] = n,
+            (n1, n2) => bail!("einsum: size mismatch for label '{c}', {n1} <> {n2}"),
+        }
+    }
+    let a = if a_dims != a.dims() {
+        a.broadcast_as(a_dims)?
+    } else {
+        a
+    };
+    let b = if b_dims != b.dims() {
+        b.broadcast_as(b_dims)?
+    } else {
+        b
+    };
+
+    let prod = |t: &Tensor, l: &[char], cs: &[char]| -> Result<(Vec<usize>, usize)> {
+        let dims = cs
+            .iter()
+            .map(|&c| size(t, l, c))
+            .collect::<Result<Vec<_>>>()?;
+        let p = dims.iter().product();
+        Ok((dims, p))
+    };
+    let (batch_dims, b_sz) = prod(&a, &la, &batch)?;
+    let (left_dims, l_sz) = prod(&a, &la, &left)?;
+    let (_, c_sz) = prod(&a, &la, &contracted)?;
+    let (right_dims, r_sz) = prod(&b, &lb, &right)?;
+
+    let pos = |l: &[char], cs: &[char]| -> Vec<usize> {
+        cs.iter()
+            .filter_map(|c| l.iter().position(|v| v == c))
+            .collect()
+    };
+    let a_perm: Vec<usize> = [pos(&la, &batch), pos(&la, &left), pos(&la, &contracted)].concat();
+    let b_perm: Vec<usize> = [pos(&lb, &batch), pos(&lb, &contracted), pos(&lb, &right)].concat();
+    let a = a.permute(a_perm)?.reshape((b_sz, l_sz, c_sz))?;
+    let b = b.permute(b_perm)?.reshape((b_sz, c_sz, r_sz))?;
+    let res = a.matmul(&b)?;
+    let res_dims = [batch_dims, left_dims, right_dims].concat();
+    let res = res.reshape(res_dims)?;
+    let labels = [batch, left, right].concat();
+    Ok((res, labels))
+}
+
+impl Tensor {
+    /// Evaluates the Einstein summation convention on the operands.
+    ///
+    /// The `equation` uses one letter per dimension for each operand, operands are separated by
+    /// commas and the output labels are optionally specified after `->`. When the output is not
+    /// specified, it is made of the labels that appear exactly once, sorted in alphabetical order.
+    /// An ellipsis `...` can be used to match an arbitrary number of leading dimensions, these
+    /// are broadcasted between operands.
+    ///
+    /// Labels that appear in multiple operands but not in the output are summed over. A label
+    /// repeated within a single operand selects the diagonal.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+    /// let b = Tensor::arange(0f32, 12f32, &Device::Cpu)?.reshape((3, 4))?;
+    /// let c = Tensor::einsum("ij,jk->ik", &[&a, &b])?;
+    /// assert_eq!(c.to_vec2::<f32>()?, a.matmul(&b)?.to_vec2::<f32>()?);
+    /// let t = Tensor::einsum("ij->ji", &[&a])?;
+    /// assert_eq!(t.dims(), &[3, 2]);
+    /// let trace = Tensor::einsum("ii", &[&Tensor::eye(3, a.dtype(), a.device())?])?;
+    /// assert_eq!(trace.to_scalar::<f32>()?, 3.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn einsum<A: AsRef<Tensor>>(equation: &str, operands: &[A]) -> Result<Self> {
+        if operands.is_empty() {
+            bail!("einsum requires at least one operand")
+        }
+        let ranks: Vec<usize> = operands.iter().map(|t| t.as_ref().rank()).collect();
+        let Equation { inputs, output } = Equation::parse(equation, &ranks)?;
+        let mut inputs = inputs.into_iter();
+        let mut operands = operands.iter().map(|t| t.as_ref().clone());
+        let mut acc = match (operands.next(), inputs.next()) {
+            (Some(t), Some(l)) => (t, l),
+            _ => bail!("einsum requires at least one operand"),
+        };
+        let remaining: Vec<(Tensor, Vec<char>)> = operands.zip(inputs).collect();
+        for (idx, rhs) in remaining.iter().enumerate() {
+            // The labels that have to be kept are the ones used in the output or in one of the
+            // operands that have not been contracted yet.
+            let keep: Vec<char> = output
+                .iter()
+                .chain(remaining[idx + 1..].iter().flat_map(|(_, l)| l.iter()))
+                .copied()
+                .collect();
+            acc = contract(acc, rhs.clone(), &keep)?;
+        }
+        let (t, labels) = reduce(acc.0, acc.1, &output)?;
+        let perm: Vec<usize> = output
+            .iter()
+            .filter_map(|c| labels.iter().position(|v| v == c))
+            .collect();
+        if perm.len() != labels.len() {
+            bail!("einsum: internal error, unexpected labels {labels:?} for output {output:?}")
+        }
+        if perm.iter().enumerate().all(|(i, &p)| i == p) {
+            Ok(t)
+        } else {
+            t.permute(perm)
+        }
+    }
+
+    /// Contracts the dimensions `dims.0` of `self` with the dimensions `dims.1` of `rhs`, the
+    /// contracted dimensions are paired in order and must have the same sizes. The result has the
+    /// remaining dimensions of `self` followed by the remaining dimensions of `rhs`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    /// let b = Tensor::arange(0f32, 12f32, &Device::Cpu)?.reshape((4, 3))?;
+    /// let c = a.tensordot(&b, ((1, 2), (1, 0)))?;
+    /// assert_eq!(c.dims(), &[2]);
+    /// let c = a.tensordot(&b, ([2], [0]))?;
+    /// assert_eq!(c.dims(), &[2, 3, 3]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn tensordot<D1: Dims, D2: Dims>(&self, rhs: &Tensor, dims: (D1, D2)) -> Result<Self> {
+        let lhs_dims = dims.0.to_indexes(self.shape(), "tensordot")?;
+        let rhs_dims = dims.1.to_indexes(rhs.shape(), "tensordot")?;
+        if lhs_dims.len() != rhs_dims.len() {
+            bail!(
+                "tensordot: got {} dims for lhs and {} dims for rhs",
+                lhs_dims.len(),
+                rhs_dims.len()
+            )
+        }
+        for (&l, &r) in lhs_dims.iter().zip(rhs_dims.iter()) {
+            if self.dim(l)? != rhs.dim(r)? {
+                Err(crate::Error::ShapeMismatchBinaryOp {
+                    lhs: self.shape().clone(),
+                    rhs: rhs.shape().clone(),
+                    op: "tensordot",
+                }
+                .bt())?
+            }
+        }
+        let lhs_free: Vec<usize> = (0..self.rank()).filter(|d| !lhs_dims.contains(d)).collect();
+        let rhs_free: Vec<usize> = (0..rhs.rank()).filter(|d| !rhs_dims.contains(d)).collect();
+        let k: usize = lhs_dims.iter().map(|&d| self.dims()[d]).product();
+        let m: usize = lhs_free.iter().map(|&d| self.dims()[d]).product();
+        let n: usize = rhs_free.iter().map(|&d| rhs.dims()[d]).product();
+        let out_dims: Vec<usize> = lhs_free
+            .iter()
+            .map(|&d| self.dims()[d])
+            .chain(rhs_free.iter().map(|&d| rhs.dims()[d]))
+            .collect();
+        let lhs = self
+            .permute([lhs_free.as_slice(), &lhs_dims].concat())?
+            .reshape((m, k))?;
+        let rhs = rhs
+            .permute([rhs_dims.as_slice(), &rhs_free].concat())?
+            .reshape((k, n))?;
+        lhs.matmul(&rhs)?.reshape(out_dims)
+    }
+
+    /// Computes the Kronecker product of `self` and `rhs`. If the tensors have different ranks,
+    /// the smaller one is extended with leading dimensions of size 1. Each dimension of the
+    /// result has the size of the corresponding dimensions of `self` and `rhs` multiplied
+    /// together.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[[0f32, 1.], [1., 0.]], &Device::Cpu)?;
+    /// let c = a.kron(&b)?;
+    /// assert_eq!(
+    ///     c.to_vec2::<f32>()?,
+    ///     &[[0., 1., 0., 2.], [1., 0., 2., 0.], [0., 3., 0., 4.], [3., 0., 4., 0.]]
+    /// );
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn kron(&self, rhs: &Tensor) -> Result<Self> {
+        let rank = usize::max(self.rank(), rhs.rank());
+        let extend = |t: &Tensor| [vec![1; rank - t.rank()], t.dims().to_vec()].concat();
+        let (lhs_dims, rhs_dims) = (extend(self), extend(rhs));
+        // Interleave the dimensions so that the broadcasted product has shape
+        // (l0, r0, l1, r1, ...) and can be reshaped to (l0 * r0, l1 * r1, ...).
+        let lhs = self.reshape(lhs_dims.iter().flat_map(|&d| [d, 1]).collect::<Vec<_>>())?;
+        let rhs = rhs.reshape(rhs_dims.iter().flat_map(|&d| [1, d]).collect::<Vec<_>>())?;
+        let out_dims: Vec<usize> = lhs_dims
+            .iter()
+            .zip(rhs_dims.iter())
+            .map(|(l, r)| l * r)
+            .collect();
+        lhs.broadcast_mul(&rhs)?.reshape(out_dims)
+    }
+
+    /// Computes the outer product of the vectors in the last dimension of `self` and `rhs`. The
+    /// leading dimensions are broadcast together, the result has shape `(..., n, m)` for inputs
+    /// of shape `(..., n)` and `(..., m)`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 10., 100.], &Device::Cpu)?;
+    /// let c = a.outer(&b)?;
+    /// assert_eq!(c.to_vec2::<f32>()?, &[[1., 10., 100.], [2., 20., 200.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn outer(&self, rhs: &Tensor) -> Result<Self> {
+        if self.rank() == 0 || rhs.rank() == 0 {
+            bail!(
+                "outer expects tensors with at least one dim, got {:?} and {:?}",
+                self.shape(),
+                rhs.shape()
+            )
+        }
+        self.unsqueeze(self.rank())?
+            .broadcast_mul(&rhs.unsqueeze(rhs.rank() - 1)?)
+    }
+
+    /// Computes the cross product of the 3D vectors in dimension `dim` of `self` and `rhs`, this
+    /// dimension must have size 3. The other dimensions are broadcast together.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let x = Tensor::new(&[[1f32, 0., 0.], [0., 1., 0.]], &Device::Cpu)?;
+    /// let y = Tensor::new(&[0f32, 1., 0.], &Device::Cpu)?;
+    /// let z = x.cross(&y, 1)?;
+    /// assert_eq!(z.to_vec2::<f32>()?, &[[0., 0., 1.], [0., 0., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn cross<D: Dim>(&self, rhs: &Tensor, dim: D) -> Result<Self> {
+        let shape = self
+            .shape()
+            .broadcast_shape_binary_op(rhs.shape(), "cross")?;
+        let dim = dim.to_index(&shape, "cross")?;
+        if shape.dims()[dim] != 3 {
+            bail!("cross expects dim {dim} to have size 3, got shape {shape:?}")
+        }
+        let lhs = self.broadcast_as(&shape)?;
+        let rhs = rhs.broadcast_as(&shape)?;
+        let (a0, a1, a2) = (
+            lhs.narrow(dim, 0, 1)?,
+            lhs.narrow(dim, 1, 1)?,
+            lhs.narrow(dim, 2, 1)?,
+        );
+        let (b0, b1, b2) = (
+            rhs.narrow(dim, 0, 1)?,
+            rhs.narrow(dim, 1, 1)?,
+            rhs.narrow(dim, 2, 1)?,
+        );
+        let c0 = ((&a1 * &b2)? - (&a2 * &b1)?)?;
+        let c1 = ((&a2 * &b0)? - (&a0 * &b2)?)?;
+        let c2 = ((&a0 * &b1)? - (&a1 * &b0)?)?;
+        Tensor::cat(&[c0, c1, c2], dim)
+    }
+}
@@ -0,0 +1,314 @@
+//! Fast Fourier transforms and spectrograms.
+//!
+//! Complex values are represented using a trailing dimension of size 2 that holds the real and
+//! the imaginary parts. The transforms are computed on the cpu using `rustfft`, `f16` and `bf16`
+//! tensors are converted to `f32` for the transform. Other devices are not supported yet and
+//! return an error, the tensors have to be moved to the cpu first.
+use crate::backend::BackendStorage;
+use crate::{CpuStorage, DType, Device, Layout, Result, Shape, Tensor, D};
+use rayon::prelude::*;
+use rustfft::num_complex::Complex;
+
+/// Returns the periodic Hann window of size `n`.
+pub fn hann_window(n: usize, dtype: DType, device: &Device) -> Result<Tensor> {
+    cosine_window(n, 0.5, 0.5, dtype, device)
+}
+
+/// Returns the periodic Hamming window of size `n`.
+pub fn hamming_window(n: usize, dtype: DType, device: &Device) -> Result<Tensor> {
+    cosine_window(n, 0.54, 0.46, dtype, device)
+}
+
+fn cosine_window(n: usize, a: f64, b: f64, dtype: DType, device: &Device) -> Result<Tensor> {
+    let window = (0..n)
+        .map(|i| a - b * (2. * std::f64::consts::PI * i as f64 / n as f64).cos())
+        .collect::<Vec<_>>();
+    Tensor::from_vec(window, n, device)?.to_dtype(dtype)
+}
+
+// The weights applied to each frequency when inverting a one-sided spectrum, the frequencies that
+// have a symmetric counterpart are counted twice.
+fn irfft_weights(n: usize, n_freqs: usize, dtype: DType, device: &Device) -> Result<Tensor> {
+    let weights = (0..n_freqs)
+        .map(|k| {
+            if k == 0 || 2 * k == n {
+                1. / n as f64
+            } else {
+                2. / n as f64
+            }
+        })
+        .collect::<Vec<_>>();
+    Tensor::from_vec(weights, (n_freqs, 1), device)?.to_dtype(dtype)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rfft;
+
+impl Rfft {
+    fn fwd<T: rustfft::FftNum>(&self, vs: &[T], n: usize) -> Vec<T> {
+        let n_freqs = n / 2 + 1;
+        let fft = rustfft::FftPlanner::<T>::new().plan_fft_forward(n);
+        let mut dst = vec![T::zero(); vs.len() / n * n_freqs * 2];
+        crate::utils::install(|| {
+            dst.par_chunks_exact_mut(n_freqs * 2)
+                .zip(vs.par_chunks_exact(n))
+                .for_each(|(dst, vs)| {
+                    let mut buffer = vs
+                        .iter()
+                        .map(|&v| Complex::new(v, T::zero()))
+                        .collect::<Vec<_>>();
+                    fft.process(&mut buffer);
+                    for (dst, v) in dst.chunks_exact_mut(2).zip(buffer.iter()) {
+                        dst[0] = v.re;
+                        dst[1] = v.im;
+                    }
+                })
+        });
+        dst
+    }
+}
+
+impl crate::CustomOp1 for Rfft {
+    fn name(&self) -> &'static str {
+        "rfft"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let (o1, o2) = match layout.contiguous_offsets() {
+            None => crate::bail!("input has to be contiguous"),
+            Some(offsets) => offsets,
+        };
+        let mut dims = layout.dims().to_vec();
+        let n = match dims.last_mut() {
+            None => crate::bail!("rfft expects at least one dimension"),
+            Some(n) => {
+                let v = *n;
+                *n = v / 2 + 1;
+                v
+            }
+        };
+        dims.push(2);
+        let storage = match storage {
+            CpuStorage::F32(vs) => CpuStorage::F32(self.fwd(&vs[o1..o2], n)),
+            CpuStorage::F64(vs) => CpuStorage::F64(self.fwd(&vs[o1..o2], n)),
+            _ => crate::bail!("unsupported dtype {:?} for rfft", storage.dtype()),
+        };
+        Ok((storage, dims.into()))
+    }
+
+    fn bwd(&self, arg: &Tensor, _res: &Tensor, grad_res: &Tensor) -> Result<Option<Tensor>> {
+        // The adjoint of the one-sided transform is the inverse transform without the weights
+        // that irfft applies to each frequency.
+        let n = arg.dim(D::Minus1)?;
+        let weights = irfft_weights(n, n / 2 + 1, grad_res.dtype(), grad_res.device())?;
+        let grad_arg = grad_res.broadcast_div(&weights)?.irfft(n)?;
+        Ok(Some(grad_arg))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Irfft {
+    n: usize,
+}
+
+impl Irfft {
+    fn fwd<T: rustfft::FftNum>(&self, vs: &[T], n_freqs: usize) -> Vec<T> {
+        let n = self.n;
+        let scale = T::from_f64(1. / n as f64).unwrap_or_else(T::one);
+        let fft = rustfft::FftPlanner::<T>::new().plan_fft_inverse(n);
+        let mut dst = vec![T::zero(); vs.len() / (n_freqs * 2) * n];
+        crate::utils::install(|| {
+            dst.par_chunks_exact_mut(n)
+                .zip(vs.par_chunks_exact(n_freqs * 2))
+                .for_each(|(dst, vs)| {
+                    // Rebuild the full hermitian spectrum from the one-sided one.
+                    let mut buffer = vec![Complex::new(T::zero(), T::zero()); n];
+                    for (k, v) in vs.chunks_exact(2).enumerate().take(n / 2 + 1) {
+                        buffer[k] = Complex::new(v[0], v[1]);
+                        if k > 0 && k < n - k {
+                            buffer[n - k] = Complex::new(v[0], -v[1]);
+                        }
+                    }
+                    fft.process(&mut buffer);
+                    for (dst, v) in dst.iter_mut().zip(buffer.iter()) {
+                        *dst = v.re * scale
+                    }
+                })
+        });
+        dst
+    }
+}
+
+impl crate::CustomOp1 for Irfft {
+    fn name(&self) -> &'static str {
+        "irfft"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let (o1, o2) = match layout.contiguous_offsets() {
+            None => crate::bail!("input has to be contiguous"),
+            Some(offsets) => offsets,
+        };
+        let mut dims = layout.dims().to_vec();
+        dims.pop();
+        let n_freqs = match dims.last_mut() {
+            None => crate::bail!("irfft expects at least two dimensions"),
+            Some(n_freqs) => {
+                let v = *n_freqs;
+                *n_freqs = self.n;
+                v
+            }
+        };
+        let storage = match storage {
+            CpuStorage::F32(vs) => CpuStorage::F32(self.fwd(&vs[o1..o2], n_freqs)),
+            CpuStorage::F64(vs) => CpuStorage::F64(self.fwd(&vs[o1..o2], n_freqs)),
+            _ => crate::bail!("unsupported dtype {:?} for irfft", storage.dtype()),
+        };
+        Ok((storage, dims.into()))
+    }
+
+    fn bwd(&self, arg: &Tensor, _res: &Tensor, grad_res: &Tensor) -> Result<Option<Tensor>> {
+        let n = self.n;
+        let arg_freqs = arg.dim(D::Minus2)?;
+        let n_freqs = usize::min(arg_freqs, n / 2 + 1);
+        // The adjoint of the inverse transform is the forward transform scaled by the weights
+        // of each frequency, the frequencies that were not used get a zero gradient.
+        let weights = irfft_weights(n, n_freqs, grad_res.dtype(), grad_res.device())?;
+        let grad_arg = grad_res
+            .rfft()?
+            .narrow(D::Minus2, 0, n_freqs)?
+            .broadcast_mul(&weights)?;
+        let grad_arg = grad_arg.pad_with_zeros(D::Minus2, 0, arg_freqs - n_freqs)?;
+        Ok(Some(grad_arg))
+    }
+}
+
+// Returns the dtype used by rustfft for `xs` on the cpu.
+fn fft_dtype(xs: &Tensor, op: &'static str) -> Result<DType> {
+    if !xs.device().is_cpu() {
+        crate::bail!(
+            "{op} is only implemented on the cpu, got a tensor on {:?}",
+            xs.device().location()
+        )
+    }
+    match xs.dtype() {
+        DType::F32 | DType::F64 => Ok(xs.dtype()),
+        DType::F16 | DType::BF16 => Ok(DType::F32),
+        dtype => Err(crate::Error::UnsupportedDTypeForOp(dtype, op).bt()),
+    }
+}
+
+impl Tensor {
+    /// Computes the one-sided discrete fourier transform of a real signal over the last
+    /// dimension. This is only supported on the cpu.
+    ///
+    /// For an input of shape `(.., n)`, the output has shape `(.., n / 2 + 1, 2)` where the last
+    /// dimension holds the real and imaginary parts of each frequency.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[1f32, 2., 3., 4.], &Device::Cpu)?;
+    /// let t = t.rfft()?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[10., 0.], [-2., 2.], [-2., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn rfft(&self) -> Result<Self> {
+        if self.rank() == 0 || self.dim(D::Minus1)? == 0 {
+            crate::bail!(
+                "rfft expects a non-empty last dimension, got {:?}",
+                self.shape()
+            )
+        }
+        let dtype = fft_dtype(self, "rfft")?;
+        if dtype == self.dtype() {
+            self.contiguous()?.apply_op1(Rfft)
+        } else {
+            self.to_dtype(dtype)?.rfft()?.to_dtype(self.dtype())
+        }
+    }
+
+    /// Computes the inverse of [`Tensor::rfft`], returning a real signal of length `n`.
+    ///
+    /// The input has shape `(.., n_freqs, 2)`, only the first `n / 2 + 1` frequencies are used
+    /// and the missing ones are assumed to be zero. The output is scaled by `1 / n` so that
+    /// `xs.rfft()?.irfft(n)?` returns `xs`.
+    pub fn irfft(&self, n: usize) -> Result<Self> {
+        if self.rank() < 2 || self.dim(D::Minus1)? != 2 {
+            crate::bail!(
+                "irfft expects a trailing dimension of size 2, got {:?}",
+                self.shape()
+            )
+        }
+        if n == 0 || self.dim(D::Minus2)? == 0 {
+            crate::bail!("irfft expects non-empty input and output")
+        }
+        let dtype = fft_dtype(self, "irfft")?;
+        if dtype == self.dtype() {
+            self.contiguous()?.apply_op1(Irfft { n })
+        } else {
+            self.to_dtype(dtype)?.irfft(n)?.to_dtype(self.dtype())
+        }
+    }
+
+    /// Computes the short-time fourier transform of a signal over the last dimension.
+    ///
+    /// The signal is split in frames of `n_fft` samples spaced by `hop_length`, each frame is
+    /// multiplied by `window` which must have `n_fft` elements before computing its one-sided
+    /// fourier transform. When `center` is `true`, the signal is padded on both sides by
+    /// reflecting `n_fft / 2` samples so that frame `t` is centered on sample `t * hop_length`.
+    ///
+    /// For an input of shape `(.., len)` the output has shape `(.., n_fft / 2 + 1, n_frames, 2)`.
+    pub fn stft(
+        &self,
+        n_fft: usize,
+        hop_length: usize,
+        window: &Tensor,
+        center: bool,
+    ) -> Result<Self> {
+        if n_fft == 0 || hop_length == 0 {
+            crate::bail!("stft expects non-zero n_fft and hop_length")
+        }
+        if window.dims() != [n_fft] {
+            crate::bail!(
+                "stft expects a window of size {n_fft}, got {:?}",
+                window.shape()
+            )
+        }
+        let len = self.dim(D::Minus1)?;
+        let pad = if center { n_fft / 2 } else { 0 };
+        if center && pad >= len {
+            crate::bail!(
+                "stft reflect padding {pad} has to be smaller than the signal length {len}"
+            )
+        }
+        let padded_len = len + 2 * pad;
+        if padded_len < n_fft {
+            crate::bail!("stft expects a signal of at least {n_fft} samples, got {padded_len}")
+        }
+        let n_frames = 1 + (padded_len - n_fft) / hop_length;
+        let mut indexes = Vec::with_capacity(n_frames * n_fft);
+        for frame in 0..n_frames {
+            for i in 0..n_fft {
+                let index = (frame * hop_length + i) as i64 - pad as i64;
+                let index = if index < 0 {
+                    -index
+                } else if index >= len as i64 {
+                    2 * (len as i64 - 1) - index
+                } else {
+                    index
+                };
+                indexes.push(index as u32)
+            }
+        }
+        let indexes = Tensor::from_vec(indexes, n_frames * n_fft, self.device())?;
+        let rank = self.rank();
+        let mut dims = self.dims().to_vec();
+        dims[rank - 1] = n_frames;
+        dims.push(n_fft);
+        let frames = self
+            .index_select(&indexes, rank - 1)?
+            .reshape(dims)?
+            .broadcast_mul(window)?;
+        frames.rfft()?.transpose(rank - 1, rank)
+    }
+}
@@ -0,0 +1,258 @@
+//! 8 bits floating-point types.
+//!
+//! [`F8E4M3`] uses 4 exponent bits and 3 mantissa bits, it has no infinities and a single NaN
+//! encoding per sign. [`F8E5M2`] uses 5 exponent bits and 2 mantissa bits and follows the IEEE 754
+//! conventions. These are the `float8_e4m3fn` and `float8_e5m2` types from PyTorch. The
+//! conversions from f32 round to the nearest value with ties to even, values that are out of range
+//! result in NaN for [`F8E4M3`] and in infinities for [`F8E5M2`].
+//!
+//! The arithmetic operations are performed in f32 and the results are rounded back to 8 bits.
+
+// Returns the 8 bits encoding of `v` for a format with `M` mantissa bits and an exponent bias of
+// `BIAS`, `max` is the largest finite encoding and `overflow` the encoding used for the values
+// that are larger in magnitude than the largest finite value, and `nan` the NaN encoding.
+#[inline(always)]
+fn encode<const M: u32, const BIAS: i32>(v: f32, max: u8, overflow: u8, nan: u8) -> u8 {
+    let sign = ((v.to_bits() >> 24) & 0x80) as u8;
+    if v.is_nan() {
+        return sign | nan;
+    }
+    let a = v.abs();
+    let min_exp = 1 - BIAS;
+    let code = if a < f32::powi(2., min_exp) {
+        // Subnormal values, a code of 2^M is the smallest normal value.
+        (a * f32::powi(2., M as i32 - min_exp)).round_ties_even() as u32
+    } else if a.is_infinite() {
+        u32::MAX
+    } else {
+        let exp = ((a.to_bits() >> 23) & 0xff) as i32 - 127;
+        let m = (a * f32::powi(2., -exp) - 1.) * (1 << M) as f32;
+        let m = m.round_ties_even() as u32;
+        // A mantissa of 2^M carries into the exponent.
+        (((exp + BIAS) as u32) << M) + m
+    };
+    if code > max as u32 {
+        sign | overflow
+    } else {
+        sign | code as u8
+    }
+}
+
+#[inline(always)]
+fn decode<const M: u32, const BIAS: i32>(v: u8) -> f32 {
+    let sign = if v & 0x80 == 0 { 1f32 } else { -1f32 };
+    let exp = ((v & 0x7f) >> M) as i32;
+    let m = (v & ((1 << M) - 1)) as f32;
+    if exp == 0 {
+        sign * m * f32::powi(2., 1 - BIAS - M as i32)
+    } else {
+        sign * (1. + m / (1 << M) as f32) * f32::powi(2., exp - BIAS)
+    }
+}
+
+macro_rules! fp8_type {
+    ($ty:ident, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Default)]
+        #[repr(transparent)]
+        pub struct $ty(u8);
+
+        impl $ty {
+            pub const ZERO: Self = Self(0);
+
+            /// Creates a value from its bit representation.
+            pub const fn from_bits(bits: u8) -> Self {
+                Self(bits)
+            }
+
+            /// Returns the bit representation of the value.
+            pub const fn to_bits(self) -> u8 {
+                self.0
+            }
+
+            pub fn from_f64(v: f64) -> Self {
+                Self::from_f32(v as f32)
+            }
+
+            pub fn to_f64(self) -> f64 {
+                self.to_f32() as f64
+            }
+
+            pub fn is_nan(self) -> bool {
+                self.to_f32().is_nan()
+            }
+
+            pub fn min(self, rhs: Self) -> Self {
+                Self::from_f32(self.to_f32().min(rhs.to_f32()))
+            }
+
+            pub fn max(self, rhs: Self) -> Self {
+                Self::from_f32(self.to_f32().max(rhs.to_f32()))
+            }
+        }
+
+        impl std::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Debug::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl PartialEq for $ty {
+            fn eq(&self, rhs: &Self) -> bool {
+                self.to_f32() == rhs.to_f32()
+            }
+        }
+
+        impl PartialOrd for $ty {
+            fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
+                self.to_f32().partial_cmp(&rhs.to_f32())
+            }
+        }
+
+        impl std::ops::Neg for $ty {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self(self.0 ^ 0x80)
+            }
+        }
+
+        fp8_type!(@binary $ty, Add, add, AddAssign, add_assign, +);
+        fp8_type!(@binary $ty, Sub, sub, SubAssign, sub_assign, -);
+        fp8_type!(@binary $ty, Mul, mul, MulAssign, mul_assign, *);
+        fp8_type!(@binary $ty, Div, div, DivAssign, div_assign, /);
+        fp8_type!(@binary $ty, Rem, rem, RemAssign, rem_assign, %);
+
+        impl num_traits::Zero for $ty {
+            fn zero() -> Self {
+                Self(0)
+            }
+            fn is_zero(&self) -> bool {
+                self.0 & 0x7f == 0
+            }
+        }
+
+        impl num_traits::One for $ty {
+            fn one() -> Self {
+                Self::from_f32(1.)
+            }
+        }
+
+        impl num_traits::Num for $ty {
+            type FromStrRadixErr = num_traits::ParseFloatError;
+            fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+                <f32 as num_traits::Num>::from_str_radix(s, radix).map(Self::from_f32)
+            }
+        }
+
+        impl num_traits::ToPrimitive for $ty {
+            fn to_i64(&self) -> Option<i64> {
+                num_traits::ToPrimitive::to_i64(&$ty::to_f32(*self))
+            }
+            fn to_u64(&self) -> Option<u64> {
+                num_traits::ToPrimitive::to_u64(&$ty::to_f32(*self))
+            }
+            fn to_f32(&self) -> Option<f32> {
+                Some($ty::to_f32(*self))
+            }
+            fn to_f64(&self) -> Option<f64> {
+                Some($ty::to_f64(*self))
+            }
+        }
+
+        impl From<$ty> for f32 {
+            fn from(v: $ty) -> f32 {
+                v.to_f32()
+            }
+        }
+
+        impl From<$ty> for f64 {
+            fn from(v: $ty) -> f64 {
+                v.to_f64()
+            }
+        }
+
+        impl crate::cpu::kernels::VecOps for $ty {
+            #[inline(always)]
+            fn min(self, rhs: Self) -> Self {
+                $ty::min(self, rhs)
+            }
+
+            #[inline(always)]
+            fn max(self, rhs: Self) -> Self {
+                $ty::max(self, rhs)
+            }
+        }
+    };
+
+    (@binary $ty:ident, $trait:ident, $fn:ident, $trait_assign:ident, $fn_assign:ident, $op:tt) => {
+        impl std::ops::$trait for $ty {
+            type Output = Self;
+            fn $fn(self, rhs: Self) -> Self {
+                Self::from_f32(self.to_f32() $op rhs.to_f32())
+            }
+        }
+
+        impl std::ops::$trait_assign for $ty {
+            fn $fn_assign(&mut self, rhs: Self) {
+                *self = *self $op rhs
+            }
+        }
+    };
+}
+
+fp8_type!(
+    F8E4M3,
+    "An 8 bits floating-point value with 4 exponent bits and 3 mantissa bits."
+);
+fp8_type!(
+    F8E5M2,
+    "An 8 bits floating-point value with 5 exponent bits and 2 mantissa bits."
+);
+
+impl F8E4M3 {
+    /// The largest finite value, 448.
+    pub const MAX: Self = Self(0x7e);
+    pub const NAN: Self = Self(0x7f);
+
+    pub fn from_f32(v: f32) -> Self {
+        Self(encode::<3, 7>(v, 0x7e, 0x7f, 0x7f))
+    }
+
+    pub fn to_f32(self) -> f32 {
+        if self.0 & 0x7f == 0x7f {
+            return f32::NAN;
+        }
+        decode::<3, 7>(self.0)
+    }
+}
+
+impl F8E5M2 {
+    /// The largest finite value, 57344.
+    pub const MAX: Self = Self(0x7b);
+    pub const INFINITY: Self = Self(0x7c);
+    pub const NAN: Self = Self(0x7f);
+
+    pub fn from_f32(v: f32) -> Self {
+        Self(encode::<2, 15>(v, 0x7b, 0x7c, 0x7f))
+    }
+
+    pub fn to_f32(self) -> f32 {
+        match self.0 & 0x7f {
+            0x7c => {
+                if self.0 & 0x80 == 0 {
+                    f32::INFINITY
+                } else {
+                    f32::NEG_INFINITY
+                }
+            }
+            0x7d..=0x7f => f32::NAN,
+            _ => decode::<2, 15>(self.0),
+        }
+    }
+}
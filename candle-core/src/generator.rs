@@ -0,0 +1,147 @@
+//! Seedable random number generators.
+//!
+//! The devices each have their own random number generator which can be seeded with
+//! [`crate::Device::set_seed`], the sequence of values generated from a given seed differs
+//! between device types. A [`Generator`] is an explicit handle on a random number generator that
+//! draws the values on the host before moving them to the target device, so the same seed
+//! results in the same values on all devices.
+use crate::{bail, DType, Device, Result, Shape, Tensor};
+use rand::{Rng, SeedableRng};
+use rand_distr::Distribution;
+use std::sync::{Arc, Mutex};
+
+/// A handle on a seedable random number generator, the clones of a generator share the same
+/// state.
+///
+/// ```rust
+/// use candle_core::{DType, Device, Generator};
+/// let g1 = Generator::new(42);
+/// let g2 = Generator::new(42);
+/// let t1 = g1.rand_normal(0., 1., (2, 3), DType::F32, &Device::Cpu)?;
+/// let t2 = g2.rand_normal(0., 1., (2, 3), DType::F32, &Device::Cpu)?;
+/// assert_eq!(t1.to_vec2::<f32>()?, t2.to_vec2::<f32>()?);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rng: Arc<Mutex<rand::rngs::StdRng>>,
+}
+
+impl Generator {
+    /// Creates a new generator initialized with `seed`.
+    pub fn new(seed: u64) -> Self {
+        let rng = rand::rngs::StdRng::seed_from_u64(seed);
+        Self {
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// Resets the state of the generator using `seed`.
+    pub fn set_seed(&self, seed: u64) {
+        *self.rng.lock().unwrap() = rand::rngs::StdRng::seed_from_u64(seed)
+    }
+
+    fn sample<S: Into<Shape>>(
+        &self,
+        shape: S,
+        dtype: DType,
+        device: &Device,
+        mut f: impl FnMut(&mut rand::rngs::StdRng) -> f64,
+    ) -> Result<Tensor> {
+        let shape = shape.into();
+        let data: Vec<f64> = {
+            let mut rng = self.rng.lock().unwrap();
+            (0..shape.elem_count()).map(|_| f(&mut rng)).collect()
+        };
+        Tensor::from_vec(data, shape, &Device::Cpu)?
+            .to_dtype(dtype)?
+            .to_device(device)
+    }
+
+    // Draws one value for each element of `params`, the result has the same shape, dtype, and
+    // device as `params`.
+    fn sample_with(
+        &self,
+        params: &Tensor,
+        mut f: impl FnMut(f64, &mut rand::rngs::StdRng) -> Result<f64>,
+    ) -> Result<Tensor> {
+        let values = params
+            .flatten_all()?
+            .to_dtype(DType::F64)?
+            .to_vec1::<f64>()?;
+        let data = {
+            let mut rng = self.rng.lock().unwrap();
+            values
+                .into_iter()
+                .map(|v| f(v, &mut rng))
+                .collect::<Result<Vec<_>>>()?
+        };
+        Tensor::from_vec(data, params.shape(), &Device::Cpu)?
+            .to_dtype(params.dtype())?
+            .to_device(params.device())
+    }
+
+    /// Creates a tensor with values sampled uniformly between `lo` and `up`.
+    pub fn rand_uniform<S: Into<Shape>>(
+        &self,
+        lo: f64,
+        up: f64,
+        shape: S,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Tensor> {
+        if !dtype.is_float() {
+            bail!("rand_uniform expects a float dtype, got {dtype:?}")
+        }
+        self.sample(shape, dtype, device, |rng| rng.gen_range(lo..up))
+    }
+
+    /// Creates a tensor with values sampled from a normal distribution with the specified `mean`
+    /// and standard deviation `std`.
+    pub fn rand_normal<S: Into<Shape>>(
+        &self,
+        mean: f64,
+        std: f64,
+        shape: S,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Tensor> {
+        if !dtype.is_float() {
+            bail!("rand_normal expects a float dtype, got {dtype:?}")
+        }
+        let normal = rand_distr::Normal::new(mean, std).map_err(crate::Error::wrap)?;
+        self.sample(shape, dtype, device, |rng| normal.sample(rng))
+    }
+
+    /// Returns a tensor with the same shape as `probs` where each element is 1 with the
+    /// probability given by the matching element of `probs` and 0 otherwise.
+    pub fn bernoulli(&self, probs: &Tensor) -> Result<Tensor> {
+        self.sample_with(probs, |p, rng| {
+            if !(0. ..=1.).contains(&p) {
+                bail!("bernoulli expects probabilities between 0 and 1, got {p}")
+            }
+            Ok(if rng.gen::<f64>() < p { 1. } else { 0. })
+        })
+    }
+
+    /// Returns a tensor with the same shape as `concentration` where each element is sampled
+    /// from a gamma distribution with the matching concentration and a rate of 1.
+    pub fn gamma(&self, concentration: &Tensor) -> Result<Tensor> {
+        self.sample_with(concentration, |alpha, rng| {
+            let gamma = rand_distr::Gamma::new(alpha, 1.).map_err(crate::Error::wrap)?;
+            Ok(gamma.sample(rng))
+        })
+    }
+
+    /// Returns a tensor with the same shape as `rates` where each element is sampled from a
+    /// Poisson distribution with the matching rate.
+    pub fn poisson(&self, rates: &Tensor) -> Result<Tensor> {
+        self.sample_with(rates, |rate, rng| {
+            if rate == 0. {
+                return Ok(0.);
+            }
+            let poisson = rand_distr::Poisson::new(rate).map_err(crate::Error::wrap)?;
+            Ok(poisson.sample(rng))
+        })
+    }
+}
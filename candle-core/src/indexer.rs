@@ -26,9 +26,8 @@ impl Tensor {
     /// ```
     fn index(&self, indexers: &[TensorIndexer]) -> Result<Self, Error> {
         let mut x = self.clone();
-        let dims = self.shape().dims();
         let mut current_dim = 0;
-        for (i, indexer) in indexers.iter().enumerate() {
+        for indexer in indexers.iter() {
             x = match indexer {
                 TensorIndexer::Select(n) => x.narrow(current_dim, *n, 1)?.squeeze(current_dim)?,
                 TensorIndexer::Narrow(left_bound, right_bound) => {
@@ -40,12 +39,17 @@ impl Tensor {
                     let stop = match right_bound {
                         Bound::Included(n) => *n + 1,
                         Bound::Excluded(n) => *n,
-                        Bound::Unbounded => dims[i],
+                        Bound::Unbounded => x.dim(current_dim)?,
                     };
                     let out = x.narrow(current_dim, start, stop.saturating_sub(start))?;
                     current_dim += 1;
                     out
                 }
+                TensorIndexer::Strided(left_bound, right_bound, step) => {
+                    let out = x.slice_step(current_dim, (*left_bound, *right_bound), *step)?;
+                    current_dim += 1;
+                    out
+                }
                 TensorIndexer::IndexSelect(indexes) => {
                     if indexes.rank() != 1 {
                         crate::bail!("multi-dimensional tensor indexing is not supported")
@@ -54,6 +58,26 @@ impl Tensor {
                     current_dim += 1;
                     out
                 }
+                TensorIndexer::Mask(mask) => {
+                    let mask_dims = mask.dims();
+                    let end = current_dim + mask_dims.len();
+                    if mask_dims.is_empty()
+                        || x.rank() < end
+                        || &x.dims()[current_dim..end] != mask_dims
+                    {
+                        crate::bail!(
+                            "mask indexing: mask shape {:?} does not match the tensor shape {:?} from dim {current_dim}",
+                            mask.shape(),
+                            x.shape()
+                        )
+                    }
+                    let indexes = mask.flatten_all()?.nonzero()?.squeeze(1)?;
+                    let out = x
+                        .flatten(current_dim, end - 1)?
+                        .index_select(&indexes.to_device(x.device())?, current_dim)?;
+                    current_dim += 1;
+                    out
+                }
                 TensorIndexer::Err(e) => crate::bail!("indexing error {e:?}"),
             };
         }
@@ -68,11 +92,70 @@ pub enum TensorIndexer {
     Select(usize),
     /// This is a regular slice, purely indexing a chunk of the tensor
     Narrow(Bound<usize>, Bound<usize>),
+    /// A slice keeping one element every `step`, see [`IndexStep`]
+    Strided(Bound<usize>, Bound<usize>, usize),
     /// Indexing via a 1d tensor
     IndexSelect(Tensor),
+    /// Selects the elements for which the mask is not zero, the mask dimensions are flattened
+    /// into a single one
+    Mask(Tensor),
     Err(Error),
 }
 
+/// A mask used to select the elements of a tensor, see [`IndexOp`].
+///
+/// The mask covers one or more consecutive dimensions of the indexed tensor starting from the
+/// position where it is used, these dimensions are replaced by a single dimension containing the
+/// elements at which the mask is not zero. The size of this dimension depends on the mask content.
+///
+/// ```rust
+/// use candle_core::{IndexMask, IndexOp, Tensor, Device};
+/// let t = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], &Device::Cpu)?;
+/// let valid = t.sum(1)?.gt(4f32)?;
+/// assert_eq!(t.i(IndexMask(&valid))?.to_vec2::<f32>()?, &[[3., 4.], [5., 6.]]);
+/// let cols = Tensor::new(&[0u8, 1], &Device::Cpu)?;
+/// assert_eq!(t.i((.., IndexMask(&cols)))?.to_vec2::<f32>()?, &[[2.], [4.], [6.]]);
+/// let mask = t.ge(4f32)?;
+/// assert_eq!(t.i(IndexMask(&mask))?.to_vec1::<f32>()?, &[4., 5., 6.]);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IndexMask<'a>(pub &'a Tensor);
+
+impl From<IndexMask<'_>> for TensorIndexer {
+    fn from(mask: IndexMask<'_>) -> Self {
+        TensorIndexer::Mask(mask.0.clone())
+    }
+}
+
+/// A range with a step used to index a tensor, see [`IndexOp`].
+///
+/// Only one element every `step` is kept in the range, the result is a view on the tensor data.
+///
+/// ```rust
+/// use candle_core::{IndexOp, IndexStep, Tensor, Device};
+/// let t = Tensor::arange(0f32, 12., &Device::Cpu)?.reshape((3, 4))?;
+/// // Split the interleaved real and imaginary parts.
+/// let re = t.i((.., IndexStep(.., 2)))?;
+/// let im = t.i((.., IndexStep(1.., 2)))?;
+/// assert_eq!(re.to_vec2::<f32>()?, &[[0., 2.], [4., 6.], [8., 10.]]);
+/// assert_eq!(im.to_vec2::<f32>()?, &[[1., 3.], [5., 7.], [9., 11.]]);
+/// assert_eq!(t.i((IndexStep(0..=2, 2), 1))?.to_vec1::<f32>()?, &[1., 9.]);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexStep<R>(pub R, pub usize);
+
+impl<R: RB> From<IndexStep<R>> for TensorIndexer {
+    fn from(IndexStep(range, step): IndexStep<R>) -> Self {
+        TensorIndexer::Strided(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+            step,
+        )
+    }
+}
+
 impl From<usize> for TensorIndexer {
     fn from(index: usize) -> Self {
         TensorIndexer::Select(index)
@@ -0,0 +1,167 @@
+//! Resizing of images using bilinear and bicubic interpolation.
+//!
+//! The interpolation along each spatial dimension is a linear map, the weights for these maps are
+//! computed on the host following the PyTorch conventions and the resizing is performed with two
+//! matmuls. This works on all devices and supports backpropagation.
+use crate::{bail, Result, Tensor};
+
+/// The interpolation method used by [`Tensor::interpolate2d_with_mode`].
+///
+/// When `align_corners` is `true`, the centers of the corner pixels of the input and output are
+/// aligned, otherwise their corners are aligned. When `antialias` is `true`, the interpolation
+/// kernel is stretched when downsampling so that all the input pixels contribute to the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolateMode {
+    Nearest,
+    Bilinear {
+        align_corners: bool,
+        antialias: bool,
+    },
+    Bicubic {
+        align_corners: bool,
+        antialias: bool,
+    },
+}
+
+// The coefficient used by PyTorch for bicubic interpolation.
+const BICUBIC_A: f64 = -0.75;
+// The coefficient used by PyTorch for antialiased bicubic interpolation.
+const BICUBIC_AA_A: f64 = -0.5;
+
+fn cubic_filter(x: f64, a: f64) -> f64 {
+    let x = x.abs();
+    if x < 1. {
+        ((a + 2.) * x - (a + 3.)) * x * x + 1.
+    } else if x < 2. {
+        ((x - 5.) * x + 8.) * x * a - 4. * a
+    } else {
+        0.
+    }
+}
+
+fn linear_filter(x: f64) -> f64 {
+    f64::max(0., 1. - x.abs())
+}
+
+// Returns the (out_size, in_size) row-major matrix that maps the input values to the
+// interpolated values along one dimension.
+fn weights(
+    in_size: usize,
+    out_size: usize,
+    cubic: bool,
+    align_corners: bool,
+    aa: bool,
+) -> Vec<f64> {
+    let mut ws = vec![0f64; out_size * in_size];
+    let scale = if align_corners {
+        if out_size > 1 {
+            (in_size as f64 - 1.) / (out_size as f64 - 1.)
+        } else {
+            0.
+        }
+    } else {
+        in_size as f64 / out_size as f64
+    };
+    for (dst, ws) in ws.chunks_exact_mut(in_size.max(1)).enumerate() {
+        if aa {
+            let interp_size = if cubic { 2. } else { 1. };
+            let (support, inv_scale) = if scale >= 1. {
+                (interp_size * scale, 1. / scale)
+            } else {
+                (interp_size, 1.)
+            };
+            let center = scale * (dst as f64 + 0.5);
+            // The truncation towards zero matches the PyTorch implementation.
+            let min = ((center - support + 0.5) as i64).max(0) as usize;
+            let max = usize::min((center + support + 0.5) as usize, in_size);
+            let mut total = 0.;
+            for (src, w) in ws.iter_mut().enumerate().take(max).skip(min) {
+                let x = (src as f64 - center + 0.5) * inv_scale;
+                *w = if cubic {
+                    cubic_filter(x, BICUBIC_AA_A)
+                } else {
+                    linear_filter(x)
+                };
+                total += *w;
+            }
+            if total != 0. {
+                ws.iter_mut().for_each(|w| *w /= total)
+            }
+            continue;
+        }
+        let src = if align_corners {
+            scale * dst as f64
+        } else {
+            scale * (dst as f64 + 0.5) - 0.5
+        };
+        let last = in_size as i64 - 1;
+        if cubic {
+            let i0 = src.floor();
+            let t = src - i0;
+            for k in -1..3 {
+                let w = cubic_filter(t - k as f64, BICUBIC_A);
+                ws[(i0 as i64 + k).clamp(0, last) as usize] += w
+            }
+        } else {
+            let src = f64::max(src, 0.);
+            let i0 = src.floor();
+            let t = src - i0;
+            let i0 = i0 as i64;
+            ws[i0.min(last) as usize] += 1. - t;
+            ws[(i0 + 1).min(last) as usize] += t;
+        }
+    }
+    ws
+}
+
+impl Tensor {
+    /// Interpolate the input tensor to the `(target_h, target_w)` size using the given `mode`.
+    ///
+    /// The input tensor should have four dimensions, `(batch, channels, h, w)`, the returned
+    /// tensor also has four dimensions, `(batch, channels, target_h, target_w)`. The results
+    /// match the ones from PyTorch `interpolate` for the same mode and options.
+    ///
+    /// ```rust
+    /// use candle_core::{InterpolateMode, Tensor, Device};
+    /// let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], &Device::Cpu)?;
+    /// let mode = InterpolateMode::Bilinear { align_corners: false, antialias: false };
+    /// let t = t.interpolate2d_with_mode(2, 4, mode)?.squeeze(0)?.squeeze(0)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., 1.25, 1.75, 2.], [3., 3.25, 3.75, 4.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn interpolate2d_with_mode(
+        &self,
+        target_h: usize,
+        target_w: usize,
+        mode: InterpolateMode,
+    ) -> Result<Self> {
+        let (_n, _c, h, w) = self.dims4()?;
+        let (cubic, align_corners, antialias) = match mode {
+            InterpolateMode::Nearest => return self.interpolate2d(target_h, target_w),
+            InterpolateMode::Bilinear {
+                align_corners,
+                antialias,
+            } => (false, align_corners, antialias),
+            InterpolateMode::Bicubic {
+                align_corners,
+                antialias,
+            } => (true, align_corners, antialias),
+        };
+        if antialias && align_corners {
+            bail!("interpolate2d: antialias is not supported with align_corners")
+        }
+        if h == 0 || w == 0 {
+            bail!(
+                "interpolate2d: cannot interpolate an empty input {:?}",
+                self.shape()
+            )
+        }
+        let weights = |in_size, out_size| {
+            let ws = weights(in_size, out_size, cubic, align_corners, antialias);
+            Tensor::from_vec(ws, (out_size, in_size), self.device())?.to_dtype(self.dtype())
+        };
+        let w_h = weights(h, target_h)?;
+        let w_w = weights(w, target_w)?;
+        w_h.broadcast_matmul(&self.broadcast_matmul(&w_w.t()?)?)
+    }
+}
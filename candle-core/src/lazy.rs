@@ -0,0 +1,884 @@
+//! Lazy evaluation of tensor operations.
+//!
+//! The operations on a [`LazyTensor`] only record a node in a graph, nothing gets computed until
+//! [`LazyTensor::materialize`] or [`materialize`] is called. The graph is then optimized before
+//! being executed:
+//! - the nodes that are not needed for the requested outputs are never computed,
+//! - identical nodes are only computed once,
+//! - consecutive affine operations are folded together and chains of element-wise operations
+//!   are fused into a single pass over the data on the cpu,
+//! - the intermediary tensors are released as soon as their last consumer has run.
+//!
+//! ```rust
+//! use candle_core::{Device, Tensor};
+//!
+//! let x = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?.lazy();
+//! // The three element-wise operations run as a single pass.
+//! let y = x.affine(2., 1.)?.relu()?.affine(0.5, 0.)?;
+//! let z = y.matmul(&x)?.sum_keepdim(1)?;
+//! assert_eq!(z.materialize()?.to_vec2::<f32>()?, [[22.], [42.]]);
+//! # Ok::<(), candle_core::Error>(())
+//! ```
+//!
+//! The materialized tensors are detached from the graph used for backpropagation.
+//!
+//! The graphs can also be rewritten before being executed, [`vmap`] uses this to turn a function
+//! written for a single example into one that processes a whole batch.
+use crate::backend::BackendStorage;
+use crate::op::{BinaryOp, ReduceOp, UnaryOp};
+use crate::shape::{Dim, Dims};
+use crate::{CpuStorage, CustomOp1, DType, Device, Error, Layout, Result, Shape, Tensor, TensorId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+enum LazyOp {
+    Input(Tensor),
+    Unary(LazyTensor, UnaryOp),
+    Binary(LazyTensor, LazyTensor, BinaryOp),
+    Affine(LazyTensor, f64, f64),
+    Matmul(LazyTensor, LazyTensor),
+    Reduce(LazyTensor, ReduceOp, Vec<usize>),
+    Reshape(LazyTensor),
+    Transpose(LazyTensor, usize, usize),
+    Broadcast(LazyTensor),
+    ToDType(LazyTensor),
+}
+
+struct Node {
+    op: LazyOp,
+    shape: Shape,
+    dtype: DType,
+    device: Device,
+    // Set once the node has been materialized, the node is then used as an input by the
+    // following graph executions.
+    value: Mutex<Option<Tensor>>,
+}
+
+/// A tensor whose value is only computed on materialization, see the [module](self) level
+/// documentation.
+#[derive(Clone)]
+pub struct LazyTensor(Arc<Node>);
+
+impl std::fmt::Debug for LazyTensor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "LazyTensor[{:?}, {:?}, {:?}]",
+            self.shape().dims(),
+            self.dtype(),
+            self.device().location()
+        )
+    }
+}
+
+macro_rules! unary_op {
+    ($fn_name:ident, $op_name:ident) => {
+        pub fn $fn_name(&self) -> Result<Self> {
+            Ok(self.unary(UnaryOp::$op_name))
+        }
+    };
+}
+
+macro_rules! binary_op {
+    ($fn_name:ident, $op_name:ident) => {
+        /// This operation broadcasts its arguments.
+        pub fn $fn_name(&self, rhs: &Self) -> Result<Self> {
+            self.binary(rhs, BinaryOp::$op_name, stringify!($fn_name))
+        }
+    };
+}
+
+impl LazyTensor {
+    fn new(op: LazyOp, shape: Shape, dtype: DType, device: Device) -> Self {
+        Self(Arc::new(Node {
+            op,
+            shape,
+            dtype,
+            device,
+            value: Mutex::new(None),
+        }))
+    }
+
+    /// Creates a lazy tensor that uses the value of `tensor`.
+    pub fn from_tensor(tensor: &Tensor) -> Self {
+        let (shape, dtype, device) = (tensor.shape(), tensor.dtype(), tensor.device());
+        Self::new(
+            LazyOp::Input(tensor.detach()),
+            shape.clone(),
+            dtype,
+            device.clone(),
+        )
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.0.shape
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        self.0.shape.dims()
+    }
+
+    pub fn rank(&self) -> usize {
+        self.0.shape.rank()
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.0.dtype
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.0.device
+    }
+
+    fn same_arg(&self, op: LazyOp) -> Self {
+        Self::new(
+            op,
+            self.shape().clone(),
+            self.dtype(),
+            self.device().clone(),
+        )
+    }
+
+    fn unary(&self, op: UnaryOp) -> Self {
+        self.same_arg(LazyOp::Unary(self.clone(), op))
+    }
+
+    fn binary(&self, rhs: &Self, op: BinaryOp, name: &'static str) -> Result<Self> {
+        if !self.device().same_device(rhs.device()) {
+            Err(Error::DeviceMismatchBinaryOp {
+                lhs: self.device().location(),
+                rhs: rhs.device().location(),
+                op: name,
+            }
+            .bt())?
+        }
+        if self.dtype() != rhs.dtype() {
+            Err(Error::DTypeMismatchBinaryOp {
+                lhs: self.dtype(),
+                rhs: rhs.dtype(),
+                op: name,
+            }
+            .bt())?
+        }
+        let shape = self.shape().broadcast_shape_binary_op(rhs.shape(), name)?;
+        let op = LazyOp::Binary(self.clone(), rhs.clone(), op);
+        Ok(Self::new(op, shape, self.dtype(), self.device().clone()))
+    }
+
+    unary_op!(exp, Exp);
+    unary_op!(log, Log);
+    unary_op!(sin, Sin);
+    unary_op!(cos, Cos);
+    unary_op!(tanh, Tanh);
+    unary_op!(abs, Abs);
+    unary_op!(neg, Neg);
+    unary_op!(recip, Recip);
+    unary_op!(sqr, Sqr);
+    unary_op!(sqrt, Sqrt);
+    unary_op!(gelu, Gelu);
+    unary_op!(gelu_erf, GeluErf);
+    unary_op!(erf, Erf);
+    unary_op!(relu, Relu);
+    unary_op!(silu, Silu);
+    unary_op!(ceil, Ceil);
+    unary_op!(floor, Floor);
+    unary_op!(round, Round);
+    unary_op!(sign, Sign);
+
+    binary_op!(add, Add);
+    binary_op!(sub, Sub);
+    binary_op!(mul, Mul);
+    binary_op!(div, Div);
+    binary_op!(maximum, Maximum);
+    binary_op!(minimum, Minimum);
+
+    /// Lazy version of [`Tensor::affine`].
+    pub fn affine(&self, mul: f64, add: f64) -> Result<Self> {
+        if self.dtype() == DType::Bool {
+            crate::bail!("affine is not supported for bool tensors")
+        }
+        Ok(self.same_arg(LazyOp::Affine(self.clone(), mul, add)))
+    }
+
+    /// Lazy version of [`Tensor::matmul`].
+    pub fn matmul(&self, rhs: &Self) -> Result<Self> {
+        let (a_dims, b_dims) = (self.dims(), rhs.dims());
+        let dim = a_dims.len();
+        if dim < 2
+            || b_dims.len() != dim
+            || a_dims[..dim - 2] != b_dims[..dim - 2]
+            || a_dims[dim - 1] != b_dims[dim - 2]
+        {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: rhs.shape().clone(),
+                op: "matmul",
+            }
+            .bt())?
+        }
+        if self.dtype() != rhs.dtype() {
+            Err(Error::DTypeMismatchBinaryOp {
+                lhs: self.dtype(),
+                rhs: rhs.dtype(),
+                op: "matmul",
+            }
+            .bt())?
+        }
+        let mut dims = a_dims.to_vec();
+        dims[dim - 1] = b_dims[dim - 1];
+        let op = LazyOp::Matmul(self.clone(), rhs.clone());
+        Ok(Self::new(
+            op,
+            dims.into(),
+            self.dtype(),
+            self.device().clone(),
+        ))
+    }
+
+    fn reduce(&self, op: ReduceOp, dims: Vec<usize>) -> Self {
+        let mut shape = self.dims().to_vec();
+        for &dim in dims.iter() {
+            shape[dim] = 1
+        }
+        let op = LazyOp::Reduce(self.clone(), op, dims);
+        Self::new(op, shape.into(), self.dtype(), self.device().clone())
+    }
+
+    /// Lazy version of [`Tensor::sum_keepdim`].
+    pub fn sum_keepdim<D: Dims>(&self, sum_dims: D) -> Result<Self> {
+        let dims = sum_dims.to_indexes(self.shape(), "sum")?;
+        Ok(self.reduce(ReduceOp::Sum, dims))
+    }
+
+    /// Lazy version of [`Tensor::max_keepdim`].
+    pub fn max_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "max")?;
+        Ok(self.reduce(ReduceOp::Max, vec![dim]))
+    }
+
+    /// Lazy version of [`Tensor::min_keepdim`].
+    pub fn min_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "min")?;
+        Ok(self.reduce(ReduceOp::Min, vec![dim]))
+    }
+
+    /// Lazy version of [`Tensor::reshape`].
+    pub fn reshape<S: Into<Shape>>(&self, shape: S) -> Result<Self> {
+        let shape = shape.into();
+        if shape.elem_count() != self.shape().elem_count() {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: shape.clone(),
+                op: "reshape",
+            }
+            .bt())?
+        }
+        let op = LazyOp::Reshape(self.clone());
+        Ok(Self::new(op, shape, self.dtype(), self.device().clone()))
+    }
+
+    /// Lazy version of [`Tensor::transpose`].
+    pub fn transpose<D1: Dim, D2: Dim>(&self, dim1: D1, dim2: D2) -> Result<Self> {
+        let dim1 = dim1.to_index(self.shape(), "transpose")?;
+        let dim2 = dim2.to_index(self.shape(), "transpose")?;
+        let mut dims = self.dims().to_vec();
+        dims.swap(dim1, dim2);
+        let op = LazyOp::Transpose(self.clone(), dim1, dim2);
+        Ok(Self::new(
+            op,
+            dims.into(),
+            self.dtype(),
+            self.device().clone(),
+        ))
+    }
+
+    /// Lazy version of [`Tensor::t`].
+    pub fn t(&self) -> Result<Self> {
+        let rank = self.rank();
+        if rank < 2 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 2,
+                got: rank,
+                shape: self.shape().clone(),
+            }
+            .bt())?
+        }
+        self.transpose(rank - 2, rank - 1)
+    }
+
+    /// Lazy version of [`Tensor::broadcast_as`].
+    pub fn broadcast_as<S: Into<Shape>>(&self, shape: S) -> Result<Self> {
+        let shape = shape.into();
+        let broadcast = self
+            .shape()
+            .broadcast_shape_binary_op(&shape, "broadcast_as")?;
+        if broadcast != shape {
+            Err(Error::BroadcastIncompatibleShapes {
+                src_shape: self.shape().clone(),
+                dst_shape: shape.clone(),
+            }
+            .bt())?
+        }
+        let op = LazyOp::Broadcast(self.clone());
+        Ok(Self::new(op, shape, self.dtype(), self.device().clone()))
+    }
+
+    /// Lazy version of [`Tensor::to_dtype`].
+    pub fn to_dtype(&self, dtype: DType) -> Result<Self> {
+        let op = LazyOp::ToDType(self.clone());
+        Ok(Self::new(
+            op,
+            self.shape().clone(),
+            dtype,
+            self.device().clone(),
+        ))
+    }
+
+    /// Computes the value of this tensor, the value is cached so that the graph is only executed
+    /// once.
+    pub fn materialize(&self) -> Result<Tensor> {
+        let mut values = materialize(std::slice::from_ref(self))?;
+        Ok(values.remove(0))
+    }
+}
+
+impl Tensor {
+    /// Returns a [`LazyTensor`] that can be used to build a graph of operations starting from
+    /// this tensor.
+    pub fn lazy(&self) -> LazyTensor {
+        LazyTensor::from_tensor(self)
+    }
+}
+
+// A f64 compared and hashed via its bit pattern so that the steps can be deduplicated.
+#[derive(Debug, Clone, Copy)]
+struct F64Bits(f64);
+
+impl PartialEq for F64Bits {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for F64Bits {}
+
+impl std::hash::Hash for F64Bits {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Elementwise {
+    Unary(UnaryOp),
+    Affine(F64Bits, F64Bits),
+}
+
+// The optimized graph, the arguments are indexes of previous steps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Step {
+    Input(TensorId),
+    Unary(usize, UnaryOp),
+    Binary(usize, usize, BinaryOp),
+    Affine(usize, F64Bits, F64Bits),
+    Matmul(usize, usize),
+    Reduce(usize, ReduceOp, Vec<usize>),
+    Reshape(usize, Vec<usize>),
+    Transpose(usize, usize, usize),
+    Broadcast(usize, Vec<usize>),
+    ToDType(usize, DType),
+    Fused(usize, Vec<Elementwise>),
+    // A step that has been merged in a fused step.
+    Removed,
+}
+
+impl Step {
+    fn args(&self) -> Vec<usize> {
+        match self {
+            Self::Input(_) | Self::Removed => vec![],
+            Self::Binary(lhs, rhs, _) | Self::Matmul(lhs, rhs) => vec![*lhs, *rhs],
+            Self::Unary(arg, _)
+            | Self::Affine(arg, _, _)
+            | Self::Reduce(arg, _, _)
+            | Self::Reshape(arg, _)
+            | Self::Transpose(arg, _, _)
+            | Self::Broadcast(arg, _)
+            | Self::ToDType(arg, _)
+            | Self::Fused(arg, _) => vec![*arg],
+        }
+    }
+
+    // Returns the argument and the element-wise operations applied to it.
+    fn elementwise(&self) -> Option<(usize, Vec<Elementwise>)> {
+        match self {
+            Self::Unary(arg, op) => Some((*arg, vec![Elementwise::Unary(*op)])),
+            Self::Affine(arg, mul, add) => Some((*arg, vec![Elementwise::Affine(*mul, *add)])),
+            Self::Fused(arg, ops) => Some((*arg, ops.clone())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Plan {
+    steps: Vec<Step>,
+    inputs: HashMap<TensorId, Tensor>,
+    dedup: HashMap<Step, usize>,
+    // Maps the nodes of the lazy graph to their step, the key is the node address.
+    visited: HashMap<usize, usize>,
+}
+
+impl Plan {
+    fn push(&mut self, step: Step) -> usize {
+        // Consecutive affine operations are folded together.
+        let step = match step {
+            Step::Affine(arg, F64Bits(mul), F64Bits(add)) => {
+                if mul == 1. && add == 0. {
+                    return arg;
+                }
+                match self.steps[arg] {
+                    Step::Affine(arg, F64Bits(mul1), F64Bits(add1)) => {
+                        Step::Affine(arg, F64Bits(mul1 * mul), F64Bits(add1 * mul + add))
+                    }
+                    _ => step,
+                }
+            }
+            step => step,
+        };
+        if let Some(&idx) = self.dedup.get(&step) {
+            return idx;
+        }
+        let idx = self.steps.len();
+        self.steps.push(step.clone());
+        self.dedup.insert(step, idx);
+        idx
+    }
+
+    // Returns the number of consumers of each step, only counting the steps that are needed to
+    // compute the outputs. The outputs count as a consumer so that they are never fused away nor
+    // released.
+    fn uses(&self, outputs: &[usize]) -> Vec<usize> {
+        let mut uses = vec![0usize; self.steps.len()];
+        for &idx in outputs.iter() {
+            uses[idx] += 1
+        }
+        // The arguments of a step always come before it.
+        for idx in (0..self.steps.len()).rev() {
+            if uses[idx] > 0 {
+                for arg in self.steps[idx].args() {
+                    uses[arg] += 1
+                }
+            }
+        }
+        uses
+    }
+
+    fn add_input(&mut self, tensor: Tensor) -> usize {
+        let id = tensor.id();
+        self.inputs.entry(id).or_insert(tensor);
+        self.push(Step::Input(id))
+    }
+
+    // Adds the steps needed to compute `node`, the nodes are visited in post-order with an
+    // explicit stack as the graphs can be deep.
+    fn add(&mut self, node: &LazyTensor) -> usize {
+        let key = |n: &LazyTensor| Arc::as_ptr(&n.0) as usize;
+        let mut stack = vec![(node.clone(), false)];
+        while let Some((node, args_done)) = stack.pop() {
+            if self.visited.contains_key(&key(&node)) {
+                continue;
+            }
+            if let Some(value) = node.0.value.lock().unwrap().clone() {
+                let idx = self.add_input(value);
+                self.visited.insert(key(&node), idx);
+                continue;
+            }
+            let args: Vec<&LazyTensor> = match &node.0.op {
+                LazyOp::Input(_) => vec![],
+                LazyOp::Binary(lhs, rhs, _) | LazyOp::Matmul(lhs, rhs) => vec![lhs, rhs],
+                LazyOp::Unary(arg, _)
+                | LazyOp::Affine(arg, _, _)
+                | LazyOp::Reduce(arg, _, _)
+                | LazyOp::Reshape(arg)
+                | LazyOp::Transpose(arg, _, _)
+                | LazyOp::Broadcast(arg)
+                | LazyOp::ToDType(arg) => vec![arg],
+            };
+            if !args_done {
+                stack.push((node.clone(), true));
+                for arg in args.into_iter().rev() {
+                    stack.push((arg.clone(), false))
+                }
+                continue;
+            }
+            let arg = |i: usize| self.visited[&key(args[i])];
+            let (shape, dtype) = (node.dims().to_vec(), node.dtype());
+            let step = match &node.0.op {
+                LazyOp::Input(tensor) => {
+                    let idx = self.add_input(tensor.clone());
+                    self.visited.insert(key(&node), idx);
+                    continue;
+                }
+                LazyOp::Unary(_, op) => Step::Unary(arg(0), *op),
+                LazyOp::Binary(_, _, op) => Step::Binary(arg(0), arg(1), *op),
+                LazyOp::Affine(_, mul, add) => Step::Affine(arg(0), F64Bits(*mul), F64Bits(*add)),
+                LazyOp::Matmul(_, _) => Step::Matmul(arg(0), arg(1)),
+                LazyOp::Reduce(_, op, dims) => Step::Reduce(arg(0), *op, dims.clone()),
+                LazyOp::Reshape(_) => Step::Reshape(arg(0), shape),
+                LazyOp::Transpose(_, dim1, dim2) => Step::Transpose(arg(0), *dim1, *dim2),
+                LazyOp::Broadcast(_) => Step::Broadcast(arg(0), shape),
+                LazyOp::ToDType(_) => Step::ToDType(arg(0), dtype),
+            };
+            let idx = self.push(step);
+            self.visited.insert(key(&node), idx);
+        }
+        self.visited[&key(node)]
+    }
+
+    // Merges the chains of element-wise operations, the intermediary values of a chain must not
+    // be used elsewhere.
+    fn fuse(&mut self, uses: &[usize]) {
+        for idx in 0..self.steps.len() {
+            if uses[idx] == 0 {
+                continue;
+            }
+            let (arg, ops) = match self.steps[idx].elementwise() {
+                Some(v) => v,
+                None => continue,
+            };
+            if uses[arg] != 1 {
+                continue;
+            }
+            if let Some((src, mut src_ops)) = self.steps[arg].elementwise() {
+                src_ops.extend(ops);
+                self.steps[idx] = Step::Fused(src, src_ops);
+                self.steps[arg] = Step::Removed;
+            }
+        }
+    }
+}
+
+struct FusedElementwise<'a>(&'a [Elementwise]);
+
+macro_rules! fused_fn {
+    ($fn_name:ident, $ty:ty) => {
+        fn $fn_name(&self, v: $ty) -> $ty {
+            use crate::op::*;
+            self.0.iter().fold(v, |v, op| match op {
+                Elementwise::Affine(mul, add) => v * mul.0 as $ty + add.0 as $ty,
+                Elementwise::Unary(op) => match op {
+                    UnaryOp::Exp => Exp::$fn_name(v),
+                    UnaryOp::Log => Log::$fn_name(v),
+                    UnaryOp::Sin => Sin::$fn_name(v),
+                    UnaryOp::Cos => Cos::$fn_name(v),
+                    UnaryOp::Abs => Abs::$fn_name(v),
+                    UnaryOp::Neg => Neg::$fn_name(v),
+                    UnaryOp::Recip => Recip::$fn_name(v),
+                    UnaryOp::Sqr => Sqr::$fn_name(v),
+                    UnaryOp::Sqrt => Sqrt::$fn_name(v),
+                    UnaryOp::Gelu => Gelu::$fn_name(v),
+                    UnaryOp::GeluErf => GeluErf::$fn_name(v),
+                    UnaryOp::Erf => Erf::$fn_name(v),
+                    UnaryOp::Relu => Relu::$fn_name(v),
+                    UnaryOp::Silu => Silu::$fn_name(v),
+                    UnaryOp::Tanh => Tanh::$fn_name(v),
+                    UnaryOp::Floor => Floor::$fn_name(v),
+                    UnaryOp::Ceil => Ceil::$fn_name(v),
+                    UnaryOp::Round => Round::$fn_name(v),
+                    UnaryOp::Sign => Sign::$fn_name(v),
+                },
+            })
+        }
+    };
+}
+
+impl FusedElementwise<'_> {
+    fused_fn!(f32, f32);
+    fused_fn!(f64, f64);
+}
+
+impl CustomOp1 for FusedElementwise<'_> {
+    fn name(&self) -> &'static str {
+        "fused-elementwise"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let (o1, o2) = match layout.contiguous_offsets() {
+            Some(offsets) => offsets,
+            None => crate::bail!("fused-elementwise expects a contiguous input"),
+        };
+        let storage = match storage {
+            CpuStorage::F32(vs) => {
+                CpuStorage::F32(vs[o1..o2].iter().map(|&v| self.f32(v)).collect())
+            }
+            CpuStorage::F64(vs) => {
+                CpuStorage::F64(vs[o1..o2].iter().map(|&v| self.f64(v)).collect())
+            }
+            s => crate::bail!("fused-elementwise is not supported for {:?}", s.dtype()),
+        };
+        Ok((storage, layout.shape().clone()))
+    }
+}
+
+fn apply_unary(t: &Tensor, op: UnaryOp) -> Result<Tensor> {
+    match op {
+        UnaryOp::Exp => t.exp(),
+        UnaryOp::Log => t.log(),
+        UnaryOp::Sin => t.sin(),
+        UnaryOp::Cos => t.cos(),
+        UnaryOp::Abs => t.abs(),
+        UnaryOp::Neg => t.neg(),
+        UnaryOp::Recip => t.recip(),
+        UnaryOp::Sqr => t.sqr(),
+        UnaryOp::Sqrt => t.sqrt(),
+        UnaryOp::Gelu => t.gelu(),
+        UnaryOp::GeluErf => t.gelu_erf(),
+        UnaryOp::Erf => t.erf(),
+        UnaryOp::Relu => t.relu(),
+        UnaryOp::Silu => t.silu(),
+        UnaryOp::Tanh => t.tanh(),
+        UnaryOp::Floor => t.floor(),
+        UnaryOp::Ceil => t.ceil(),
+        UnaryOp::Round => t.round(),
+        UnaryOp::Sign => t.sign(),
+    }
+}
+
+fn apply_elementwise(t: &Tensor, ops: &[Elementwise]) -> Result<Tensor> {
+    if t.device().is_cpu() && matches!(t.dtype(), DType::F32 | DType::F64) {
+        return t.contiguous()?.apply_op1_no_bwd(&FusedElementwise(ops));
+    }
+    let mut t = t.clone();
+    for op in ops.iter() {
+        t = match op {
+            Elementwise::Unary(op) => apply_unary(&t, *op)?,
+            Elementwise::Affine(mul, add) => t.affine(mul.0, add.0)?,
+        }
+    }
+    Ok(t)
+}
+
+/// Computes the values of multiple lazy tensors, the parts of the graph that are shared between
+/// these tensors are only computed once.
+pub fn materialize(outputs: &[LazyTensor]) -> Result<Vec<Tensor>> {
+    let mut plan = Plan::default();
+    let out_idxs: Vec<usize> = outputs.iter().map(|o| plan.add(o)).collect();
+
+    let uses = plan.uses(&out_idxs);
+    plan.fuse(&uses);
+    let mut uses = plan.uses(&out_idxs);
+    // The steps are already in topological order.
+    let mut values: Vec<Option<Tensor>> = vec![None; plan.steps.len()];
+    for (idx, step) in plan.steps.iter().enumerate() {
+        if uses[idx] == 0 {
+            continue;
+        }
+        let v = |i: usize| values[i].as_ref().expect("missing value in lazy graph");
+        let value = match step {
+            Step::Removed => continue,
+            Step::Input(id) => plan.inputs[id].clone(),
+            Step::Unary(arg, op) => apply_unary(v(*arg), *op)?,
+            Step::Binary(lhs, rhs, op) => {
+                let (lhs, rhs) = (v(*lhs), v(*rhs));
+                match op {
+                    BinaryOp::Add => lhs.broadcast_add(rhs)?,
+                    BinaryOp::Sub => lhs.broadcast_sub(rhs)?,
+                    BinaryOp::Mul => lhs.broadcast_mul(rhs)?,
+                    BinaryOp::Div => lhs.broadcast_div(rhs)?,
+                    BinaryOp::Maximum => lhs.broadcast_maximum(rhs)?,
+                    BinaryOp::Minimum => lhs.broadcast_minimum(rhs)?,
+                }
+            }
+            Step::Affine(arg, mul, add) => v(*arg).affine(mul.0, add.0)?,
+            Step::Fused(arg, ops) => apply_elementwise(v(*arg), ops)?,
+            Step::Matmul(lhs, rhs) => v(*lhs).matmul(v(*rhs))?,
+            Step::Reduce(arg, op, dims) => match op {
+                ReduceOp::Sum => v(*arg).sum_keepdim(dims.as_slice())?,
+                ReduceOp::Max => v(*arg).max_keepdim(dims[0])?,
+                ReduceOp::Min => v(*arg).min_keepdim(dims[0])?,
+                ReduceOp::ArgMin | ReduceOp::ArgMax => {
+                    crate::bail!("unexpected {} in lazy graph", op.name())
+                }
+            },
+            Step::Reshape(arg, shape) => v(*arg).reshape(shape.as_slice())?,
+            Step::Transpose(arg, dim1, dim2) => v(*arg).transpose(*dim1, *dim2)?,
+            Step::Broadcast(arg, shape) => v(*arg).broadcast_as(shape.as_slice())?,
+            Step::ToDType(arg, dtype) => v(*arg).to_dtype(*dtype)?,
+        };
+        // Release the arguments once their last consumer has run.
+        for arg in step.args() {
+            uses[arg] -= 1;
+            if uses[arg] == 0 {
+                values[arg] = None
+            }
+        }
+        values[idx] = Some(value)
+    }
+
+    let mut res = Vec::with_capacity(outputs.len());
+    for (output, idx) in outputs.iter().zip(out_idxs) {
+        let value = values[idx].clone().expect("missing output in lazy graph");
+        *output.0.value.lock().unwrap() = Some(value.clone());
+        res.push(value)
+    }
+    Ok(res)
+}
+
+// Rewrites a graph traced on a single example so that it operates on a batch of examples, each
+// node is mapped to its batched version and a flag indicating whether it has a leading batch
+// dimension. The nodes that do not depend on the inputs are left unchanged.
+struct Batcher {
+    batch_size: usize,
+    // Maps the nodes of the traced graph to their batched version, the key is the node address.
+    visited: HashMap<usize, (LazyTensor, bool)>,
+}
+
+impl Batcher {
+    fn with_batch_dim(&self, dims: &[usize]) -> Vec<usize> {
+        let mut res = Vec::with_capacity(dims.len() + 1);
+        res.push(self.batch_size);
+        res.extend_from_slice(dims);
+        res
+    }
+
+    // Inserts unit dimensions after the batch dimension so that the example part has `rank`
+    // dimensions, broadcasting then aligns the example dimensions as in the traced graph.
+    fn align(&self, t: &LazyTensor, batched: bool, rank: usize) -> Result<LazyTensor> {
+        if !batched || t.rank() > rank {
+            return Ok(t.clone());
+        }
+        let mut dims = vec![1; rank + 1];
+        dims[0] = self.batch_size;
+        dims[rank + 1 - (t.rank() - 1)..].copy_from_slice(&t.dims()[1..]);
+        t.reshape(dims)
+    }
+
+    // Adds a batch dimension to an unbatched tensor.
+    fn expand(&self, t: &LazyTensor, batched: bool) -> Result<LazyTensor> {
+        if batched {
+            return Ok(t.clone());
+        }
+        let mut dims = vec![1];
+        dims.extend_from_slice(t.dims());
+        t.reshape(dims)?.broadcast_as(self.with_batch_dim(t.dims()))
+    }
+
+    fn add(&mut self, node: &LazyTensor) -> Result<(LazyTensor, bool)> {
+        let key = |n: &LazyTensor| Arc::as_ptr(&n.0) as usize;
+        let mut stack = vec![(node.clone(), false)];
+        while let Some((node, args_done)) = stack.pop() {
+            if self.visited.contains_key(&key(&node)) {
+                continue;
+            }
+            let args: Vec<&LazyTensor> = match &node.0.op {
+                LazyOp::Input(_) => vec![],
+                LazyOp::Binary(lhs, rhs, _) | LazyOp::Matmul(lhs, rhs) => vec![lhs, rhs],
+                LazyOp::Unary(arg, _)
+                | LazyOp::Affine(arg, _, _)
+                | LazyOp::Reduce(arg, _, _)
+                | LazyOp::Reshape(arg)
+                | LazyOp::Transpose(arg, _, _)
+                | LazyOp::Broadcast(arg)
+                | LazyOp::ToDType(arg) => vec![arg],
+            };
+            if !args_done {
+                stack.push((node.clone(), true));
+                for arg in args.into_iter().rev() {
+                    stack.push((arg.clone(), false))
+                }
+                continue;
+            }
+            let args: Vec<(LazyTensor, bool)> =
+                args.iter().map(|a| self.visited[&key(a)].clone()).collect();
+            if !args.iter().any(|(_, batched)| *batched) {
+                self.visited.insert(key(&node), (node.clone(), false));
+                continue;
+            }
+            let (a, a_batched) = &args[0];
+            let batched = match &node.0.op {
+                LazyOp::Input(_) => unreachable!(),
+                LazyOp::Unary(_, op) => a.unary(*op),
+                LazyOp::Affine(_, mul, add) => a.affine(*mul, *add)?,
+                LazyOp::ToDType(_) => a.to_dtype(node.dtype())?,
+                LazyOp::Binary(_, _, op) => {
+                    let (b, b_batched) = &args[1];
+                    let a = self.align(a, *a_batched, node.rank())?;
+                    let b = self.align(b, *b_batched, node.rank())?;
+                    a.binary(&b, *op, "vmap")?
+                }
+                LazyOp::Matmul(_, _) => {
+                    let (b, b_batched) = &args[1];
+                    let a = self.expand(a, *a_batched)?;
+                    let b = self.expand(b, *b_batched)?;
+                    a.matmul(&b)?
+                }
+                LazyOp::Reduce(_, op, dims) => a.reduce(*op, dims.iter().map(|d| d + 1).collect()),
+                LazyOp::Reshape(_) => a.reshape(self.with_batch_dim(node.dims()))?,
+                LazyOp::Transpose(_, dim1, dim2) => a.transpose(dim1 + 1, dim2 + 1)?,
+                LazyOp::Broadcast(_) => self
+                    .align(a, true, node.rank())?
+                    .broadcast_as(self.with_batch_dim(node.dims()))?,
+            };
+            self.visited.insert(key(&node), (batched, true));
+        }
+        Ok(self.visited[&key(node)].clone())
+    }
+}
+
+/// Vectorizes `f`, a function written for a single example, so that it applies to a batch of
+/// examples. The returned function takes tensors that have the batch as their first dimension
+/// and returns a tensor with the same leading batch dimension.
+///
+/// `f` is traced once on lazy tensors holding the first example, the recorded operations are
+/// then rewritten to their batched equivalent, e.g. the reduction dimensions are shifted and the
+/// matmuls become batched matmuls, and the batched graph runs in a single pass. Tensors captured
+/// by `f` are shared across the batch. `f` should only use lazy operations on its arguments,
+/// materializing them would only use the first example.
+///
+/// ```rust
+/// use candle_core::{lazy::vmap, Device, Tensor};
+/// let w = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?.lazy();
+/// // Applies a linear layer to a single example of shape (2,).
+/// let f = vmap(|xs| xs[0].reshape((1, 2))?.matmul(&w)?.reshape(2));
+/// let xs = Tensor::new(&[[1f32, 0.], [0., 1.], [1., 1.]], &Device::Cpu)?;
+/// assert_eq!(f(&[xs])?.to_vec2::<f32>()?, [[1., 2.], [3., 4.], [4., 6.]]);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn vmap<F>(f: F) -> impl Fn(&[Tensor]) -> Result<Tensor>
+where
+    F: Fn(&[LazyTensor]) -> Result<LazyTensor>,
+{
+    move |xs: &[Tensor]| {
+        let batch_size = match xs.first().and_then(|x| x.dims().first()) {
+            None => crate::bail!("vmap expects at least one argument with a batch dimension"),
+            Some(&b) => b,
+        };
+        for x in xs.iter() {
+            if x.dims().first() != Some(&batch_size) {
+                crate::bail!(
+                    "vmap expects all the arguments to have the same batch size, got {:?}",
+                    xs.iter().map(|x| x.shape()).collect::<Vec<_>>()
+                )
+            }
+        }
+        if batch_size == 0 {
+            crate::bail!("vmap expects a non-empty batch")
+        }
+        let examples = xs
+            .iter()
+            .map(|x| Ok(x.get(0)?.lazy()))
+            .collect::<Result<Vec<_>>>()?;
+        let res = f(&examples)?;
+        let mut batcher = Batcher {
+            batch_size,
+            visited: HashMap::new(),
+        };
+        for (example, x) in examples.iter().zip(xs.iter()) {
+            let key = Arc::as_ptr(&example.0) as usize;
+            batcher.visited.insert(key, (x.lazy(), true));
+        }
+        let (res, batched) = batcher.add(&res)?;
+        batcher.expand(&res, batched)?.materialize()
+    }
+}
@@ -37,21 +37,30 @@
 mod accelerate;
 pub mod backend;
 pub mod backprop;
+mod complex;
 pub mod conv;
 mod convert;
 pub mod cpu;
 pub mod cpu_backend;
 #[cfg(feature = "cuda")]
 pub mod cuda_backend;
+pub mod custom_backend;
 mod custom_op;
 mod device;
 pub mod display;
 mod dtype;
 pub mod dummy_cuda_backend;
 mod dummy_metal_backend;
+mod einsum;
 pub mod error;
+pub mod fft;
+mod fp8;
+mod generator;
 mod indexer;
+mod interpolate;
 pub mod layout;
+pub mod lazy;
+mod linalg;
 #[cfg(feature = "metal")]
 pub mod metal_backend;
 #[cfg(feature = "mkl")]
@@ -64,6 +73,7 @@ pub mod safetensors;
 pub mod scalar;
 pub mod shape;
 mod sort;
+pub mod sparse;
 mod storage;
 mod strided_index;
 mod tensor;
@@ -75,17 +85,23 @@ mod variable;
 #[cfg(feature = "cudnn")]
 pub use cuda_backend::cudnn;
 
+pub use backprop::no_grad;
 pub use cpu_backend::{CpuStorage, CpuStorageRef};
+pub use custom_backend::{CustomDevice, CustomStorage};
 pub use custom_op::{CustomOp1, CustomOp2, CustomOp3, InplaceOp1, InplaceOp2, InplaceOp3};
-pub use device::{Device, DeviceLocation, NdArray};
+pub use device::{Device, DeviceLocation, MemoryStats, NdArray};
 pub use dtype::{DType, DTypeParseError, FloatDType, IntDType, WithDType};
 pub use error::{Error, Result};
-pub use indexer::IndexOp;
+pub use fp8::{F8E4M3, F8E5M2};
+pub use generator::Generator;
+pub use indexer::{IndexMask, IndexOp, IndexStep};
+pub use interpolate::InterpolateMode;
 pub use layout::Layout;
+pub use lazy::LazyTensor;
 pub use shape::{Shape, D};
 pub use storage::Storage;
 pub use strided_index::{StridedBlocks, StridedIndex};
-pub use tensor::{Tensor, TensorId};
+pub use tensor::{Indexing, PadMode, Tensor, TensorId, TensorSplit};
 pub use variable::Var;
 
 #[cfg(feature = "cuda")]
@@ -94,7 +110,7 @@ pub use cuda_backend as cuda;
 #[cfg(not(feature = "cuda"))]
 pub use dummy_cuda_backend as cuda;
 
-pub use cuda::{CudaDevice, CudaStorage};
+pub use cuda::{CudaDevice, CudaGraph, CudaStorage, CudaStream};
 
 #[cfg(feature = "metal")]
 pub use metal_backend::{MetalDevice, MetalError, MetalStorage};
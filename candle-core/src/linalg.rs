@@ -0,0 +1,801 @@
+//! Linear algebra operations on batches of matrices.
+//!
+//! The decompositions are computed on the cpu using `f64` precision, tensors stored on other
+//! devices or using other dtypes are moved to the host and the results are moved back, these
+//! conversions are tracked so that backpropagation still works across them.
+//!
+//! The host kernels are plain implementations of the textbook algorithms, they are not backed by
+//! LAPACK on the cpu nor by cuSOLVER on cuda. Each matrix costs `O(n^3)` operations and the
+//! matrices of a batch are processed sequentially, so these ops are meant for small to medium
+//! sized matrices.
+use crate::{bail, CpuStorage, DType, Device, Layout, Result, Shape, Tensor, D};
+
+// Moves a tensor to the cpu and converts it to f64 for the host kernels.
+fn to_host(xs: &Tensor) -> Result<Tensor> {
+    xs.to_device(&Device::Cpu)?
+        .to_dtype(DType::F64)?
+        .contiguous()
+}
+
+// Converts the result of a host kernel back to the dtype and device of `like`.
+fn from_host(xs: &Tensor, like: &Tensor) -> Result<Tensor> {
+    xs.to_dtype(like.dtype())?.to_device(like.device())
+}
+
+// Returns the size of the square matrices at the end of `xs`.
+fn square_dim(xs: &Tensor, op: &'static str) -> Result<usize> {
+    let dims = xs.dims();
+    match dims {
+        [.., n, m] if n == m => Ok(*n),
+        _ => bail!(
+            "{op} expects a batch of square matrices, got {:?}",
+            xs.shape()
+        ),
+    }
+}
+
+// The decompositions that are extracted from the host values do not record a backward op, these
+// reject the inputs that would require one rather than silently detaching the results.
+fn check_no_grad(xs: &Tensor, op: &'static str) -> Result<()> {
+    if crate::backprop::is_grad_enabled() && xs.track_op() {
+        bail!("{op} does not support backpropagation, detach the input or use no_grad")
+    }
+    Ok(())
+}
+
+fn f64_slice<'a>(storage: &'a CpuStorage, layout: &Layout, op: &'static str) -> Result<&'a [f64]> {
+    match (storage, layout.contiguous_offsets()) {
+        (CpuStorage::F64(vs), Some((o1, o2))) => Ok(&vs[o1..o2]),
+        (CpuStorage::F64(_), None) => bail!("{op} expects a contiguous input"),
+        _ => bail!("{op} expects a f64 input"),
+    }
+}
+
+fn cholesky(a: &[f64], l: &mut [f64], n: usize) -> Result<()> {
+    for j in 0..n {
+        let mut d = a[j * n + j];
+        for k in 0..j {
+            d -= l[j * n + k] * l[j * n + k];
+        }
+        if d <= 0. || d.is_nan() {
+            bail!("cholesky: the input matrix is not positive-definite")
+        }
+        let d = d.sqrt();
+        l[j * n + j] = d;
+        for i in j + 1..n {
+            let mut v = a[i * n + j];
+            for k in 0..j {
+                v -= l[i * n + k] * l[j * n + k];
+            }
+            l[i * n + j] = v / d;
+        }
+    }
+    Ok(())
+}
+
+// Solves a x = b in place where a is a triangular n x n matrix and b a n x k matrix.
+fn triangular_solve(a: &[f64], b: &mut [f64], n: usize, k: usize, upper: bool) -> Result<()> {
+    for step in 0..n {
+        let i = if upper { n - 1 - step } else { step };
+        let d = a[i * n + i];
+        if d == 0. {
+            bail!("triangular-solve: the input matrix is singular")
+        }
+        let others = if upper { i + 1..n } else { 0..i };
+        for col in 0..k {
+            let mut v = b[i * k + col];
+            for j in others.clone() {
+                v -= a[i * n + j] * b[j * k + col];
+            }
+            b[i * k + col] = v / d;
+        }
+    }
+    Ok(())
+}
+
+// Returns the batch size and the matrix dimensions of a tensor of shape (.., m, n).
+fn matrix_dims(xs: &Tensor, op: &'static str) -> Result<(usize, usize, usize)> {
+    match xs.dims() {
+        [batch @ .., m, n] => Ok((batch.iter().product(), *m, *n)),
+        _ => bail!("{op} expects a batch of matrices, got {:?}", xs.shape()),
+    }
+}
+
+// Builds a tensor with the batch dimensions of `like` followed by `dims`.
+fn batched_from_host(vs: Vec<f64>, like: &Tensor, dims: &[usize]) -> Result<Tensor> {
+    let batch = &like.dims()[..like.rank() - 2];
+    let xs = Tensor::from_vec(vs, [batch, dims].concat(), &Device::Cpu)?;
+    from_host(&xs, like)
+}
+
+fn dot(xs: &[f64], ys: &[f64]) -> f64 {
+    xs.iter().zip(ys.iter()).map(|(x, y)| x * y).sum()
+}
+
+// Completes `cols` into `n_cols` orthonormal vectors. The vectors for which `valid` is false are
+// replaced, the valid ones are assumed to be orthonormal already.
+fn complete_orthonormal(cols: &mut Vec<Vec<f64>>, valid: &[bool], n_cols: usize) {
+    let dim = cols.first().map_or(0, |c| c.len());
+    let mut basis: Vec<Vec<f64>> = cols
+        .iter()
+        .zip(valid.iter())
+        .filter(|(_, &v)| v)
+        .map(|(c, _)| c.clone())
+        .collect();
+    let mut candidates = 0..dim;
+    let mut next_vector = |basis: &Vec<Vec<f64>>| -> Vec<f64> {
+        for i in candidates.by_ref() {
+            let mut v = vec![0f64; dim];
+            v[i] = 1.;
+            // Orthogonalize twice for numerical stability.
+            for _ in 0..2 {
+                for b in basis.iter() {
+                    let d = dot(&v, b);
+                    v.iter_mut().zip(b.iter()).for_each(|(v, b)| *v -= d * b);
+                }
+            }
+            let norm = dot(&v, &v).sqrt();
+            if norm > 1e-6 {
+                v.iter_mut().for_each(|v| *v /= norm);
+                return v;
+            }
+        }
+        vec![0f64; dim]
+    };
+    for (col, _) in cols.iter_mut().zip(valid.iter()).filter(|(_, &v)| !v) {
+        *col = next_vector(&basis);
+        basis.push(col.clone());
+    }
+    while cols.len() < n_cols {
+        let col = next_vector(&basis);
+        basis.push(col.clone());
+        cols.push(col);
+    }
+}
+
+// One-sided Jacobi singular value decomposition of a m x n matrix given by its n columns with
+// m >= n. Returns the left singular vectors, the singular values in decreasing order, and the
+// right singular vectors as columns.
+fn jacobi_svd(mut u: Vec<Vec<f64>>) -> (Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>) {
+    let n = u.len();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1. } else { 0. }).collect())
+        .collect();
+    for _sweep in 0..100 {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let alpha = dot(&u[p], &u[p]);
+                let beta = dot(&u[q], &u[q]);
+                let gamma = dot(&u[p], &u[q]);
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() || gamma == 0. {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2. * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1. + zeta * zeta).sqrt());
+                let c = 1. / (1. + t * t).sqrt();
+                let s = c * t;
+                for vs in [&mut u, &mut v] {
+                    let (left, right) = vs.split_at_mut(q);
+                    for (xp, xq) in left[p].iter_mut().zip(right[0].iter_mut()) {
+                        let (yp, yq) = (c * *xp - s * *xq, s * *xp + c * *xq);
+                        *xp = yp;
+                        *xq = yq;
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let mut sigma: Vec<f64> = u.iter().map(|c| dot(c, c).sqrt()).collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| sigma[j].total_cmp(&sigma[i]));
+    let mut u: Vec<Vec<f64>> = order.iter().map(|&i| u[i].clone()).collect();
+    let v: Vec<Vec<f64>> = order.iter().map(|&i| v[i].clone()).collect();
+    sigma = order.iter().map(|&i| sigma[i]).collect();
+    let tol = sigma
+        .first()
+        .map_or(0., |s| s * f64::EPSILON * u.len().max(1) as f64 * 8.);
+    let mut valid = vec![true; n];
+    for ((col, s), valid) in u.iter_mut().zip(sigma.iter()).zip(valid.iter_mut()) {
+        if *s > tol && *s > 0. {
+            col.iter_mut().for_each(|x| *x /= s)
+        } else {
+            *valid = false
+        }
+    }
+    let n_cols = u.len();
+    complete_orthonormal(&mut u, &valid, n_cols);
+    (u, sigma, v)
+}
+
+// Returns the columns of a row-major m x n matrix.
+fn columns(a: &[f64], m: usize, n: usize) -> Vec<Vec<f64>> {
+    (0..n)
+        .map(|j| (0..m).map(|i| a[i * n + j]).collect())
+        .collect()
+}
+
+// Decomposes a row-major m x n matrix, returns u, s and vt as row-major buffers.
+fn svd(a: &[f64], m: usize, n: usize, full_matrices: bool) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let (mut u, s, mut v) = if m >= n {
+        jacobi_svd(columns(a, m, n))
+    } else {
+        let at: Vec<f64> = columns(a, m, n).concat();
+        let (v, s, u) = jacobi_svd(columns(&at, n, m));
+        (u, s, v)
+    };
+    if full_matrices {
+        let valid_u = vec![true; u.len()];
+        complete_orthonormal(&mut u, &valid_u, m);
+        let valid_v = vec![true; v.len()];
+        complete_orthonormal(&mut v, &valid_v, n);
+    }
+    // u is stored by columns so it gets transposed, v is transposed by construction.
+    let u = (0..m)
+        .flat_map(|i| u.iter().map(move |c| c[i]))
+        .collect::<Vec<_>>();
+    let vt = v.concat();
+    (u, s, vt)
+}
+
+// Householder QR decomposition of a row-major m x n matrix, returns q (m x m) and r (m x n) as
+// row-major buffers, the diagonal of r is made non-negative.
+fn qr(a: &[f64], m: usize, n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut r = a.to_vec();
+    let mut q = vec![0f64; m * m];
+    for i in 0..m {
+        q[i * m + i] = 1.
+    }
+    let k = usize::min(m, n);
+    for j in 0..k {
+        let mut v: Vec<f64> = (j..m).map(|i| r[i * n + j]).collect();
+        let norm = dot(&v, &v).sqrt();
+        if norm == 0. {
+            continue;
+        }
+        v[0] += if v[0] >= 0. { norm } else { -norm };
+        let v_norm = dot(&v, &v).sqrt();
+        v.iter_mut().for_each(|x| *x /= v_norm);
+        // r = (I - 2 v v^T) r on rows j.., q = q (I - 2 v v^T) on columns j..
+        for col in 0..n {
+            let d: f64 = v
+                .iter()
+                .enumerate()
+                .map(|(i, v)| v * r[(j + i) * n + col])
+                .sum();
+            v.iter()
+                .enumerate()
+                .for_each(|(i, v)| r[(j + i) * n + col] -= 2. * d * v);
+        }
+        for row in 0..m {
+            let d: f64 = v
+                .iter()
+                .enumerate()
+                .map(|(i, v)| v * q[row * m + j + i])
+                .sum();
+            v.iter()
+                .enumerate()
+                .for_each(|(i, v)| q[row * m + j + i] -= 2. * d * v);
+        }
+    }
+    for i in 0..k {
+        if r[i * n + i] < 0. {
+            (0..n).for_each(|col| r[i * n + col] = -r[i * n + col]);
+            (0..m).for_each(|row| q[row * m + i] = -q[row * m + i]);
+        }
+    }
+    // Zero out the values below the diagonal that only contain rounding errors.
+    for i in 0..m {
+        for col in 0..usize::min(i, n) {
+            r[i * n + col] = 0.
+        }
+    }
+    (q, r)
+}
+
+// Cyclic Jacobi eigenvalue decomposition of a symmetric n x n matrix, only the lower triangle of
+// `a` is read. Returns the eigenvalues in ascending order and the eigenvectors as the columns of
+// a row-major buffer.
+fn eigh(a: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut a: Vec<f64> = (0..n * n)
+        .map(|idx| {
+            let (i, j) = (idx / n, idx % n);
+            a[usize::max(i, j) * n + usize::min(i, j)]
+        })
+        .collect();
+    let mut v = vec![0f64; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.
+    }
+    let norm = dot(&a, &a).sqrt();
+    for _sweep in 0..100 {
+        let off: f64 = (0..n * n)
+            .filter(|idx| idx / n != idx % n)
+            .map(|idx| a[idx] * a[idx])
+            .sum();
+        if off.sqrt() <= f64::EPSILON * norm {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                let apq = a[p * n + q];
+                if apq == 0. {
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2. * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+                let c = 1. / (t * t + 1.).sqrt();
+                let s = c * t;
+                // a = j^T a j and v = v j where j is the rotation in the (p, q) plane.
+                for k in 0..n {
+                    let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                    let (vkp, vkq) = (v[k * n + p], v[k * n + q]);
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[i * n + i].total_cmp(&a[j * n + j]));
+    let values = order.iter().map(|&i| a[i * n + i]).collect();
+    let vectors = (0..n * n)
+        .map(|idx| v[idx / n * n + order[idx % n]])
+        .collect();
+    (values, vectors)
+}
+
+// LU decomposition with partial pivoting of a n x n matrix, done in place so that `a` contains
+// both the unit lower triangular factor and the upper triangular one. Returns the row permutation
+// and its sign, or `None` if the matrix is singular.
+fn lu(a: &mut [f64], n: usize) -> Option<(Vec<usize>, f64)> {
+    let mut perm: Vec<usize> = (0..n).collect();
+    let mut sign = 1.;
+    for j in 0..n {
+        let pivot = (j..n).max_by(|&i, &k| a[i * n + j].abs().total_cmp(&a[k * n + j].abs()))?;
+        if a[pivot * n + j] == 0. {
+            return None;
+        }
+        if pivot != j {
+            for k in 0..n {
+                a.swap(pivot * n + k, j * n + k)
+            }
+            perm.swap(pivot, j);
+            sign = -sign;
+        }
+        for i in j + 1..n {
+            let f = a[i * n + j] / a[j * n + j];
+            a[i * n + j] = f;
+            for k in j + 1..n {
+                a[i * n + k] -= f * a[j * n + k]
+            }
+        }
+    }
+    Some((perm, sign))
+}
+
+fn inverse(a: &[f64], inv: &mut [f64], n: usize) -> Result<()> {
+    let mut a = a.to_vec();
+    let perm = match lu(&mut a, n) {
+        Some((perm, _)) => perm,
+        None => bail!("inverse expects an invertible matrix"),
+    };
+    // Solve l u inv = p by forward and backward substitution, one column at a time.
+    for col in 0..n {
+        for i in 0..n {
+            let mut v = if perm[i] == col { 1. } else { 0. };
+            for k in 0..i {
+                v -= a[i * n + k] * inv[k * n + col]
+            }
+            inv[i * n + col] = v
+        }
+        for i in (0..n).rev() {
+            let mut v = inv[i * n + col];
+            for k in i + 1..n {
+                v -= a[i * n + k] * inv[k * n + col]
+            }
+            inv[i * n + col] = v / a[i * n + i]
+        }
+    }
+    Ok(())
+}
+
+// Returns the sign and the log of the absolute value of the determinant of a n x n matrix, the
+// sign is 0 and the log is -inf for singular matrices.
+fn slogdet(a: &[f64], n: usize) -> (f64, f64) {
+    let mut a = a.to_vec();
+    match lu(&mut a, n) {
+        None => (0., f64::NEG_INFINITY),
+        Some((_, sign)) => (0..n).fold((sign, 0.), |(sign, logdet), i| {
+            let u = a[i * n + i];
+            (sign * u.signum(), logdet + u.abs().ln())
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Inverse;
+
+impl crate::CustomOp1 for Inverse {
+    fn name(&self) -> &'static str {
+        "inverse"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let a = f64_slice(storage, layout, "inverse")?;
+        let n = layout.dims()[layout.dims().len() - 1];
+        let mut inv = vec![0f64; a.len()];
+        if n > 0 {
+            for (a, inv) in a.chunks_exact(n * n).zip(inv.chunks_exact_mut(n * n)) {
+                inverse(a, inv, n)?
+            }
+        }
+        Ok((CpuStorage::F64(inv), layout.shape().clone()))
+    }
+
+    fn bwd(&self, _arg: &Tensor, inv: &Tensor, grad_inv: &Tensor) -> Result<Option<Tensor>> {
+        // grad_a = -inv^T grad_inv inv^T
+        let inv_t = inv.t()?;
+        let grad_a = inv_t.matmul(grad_inv)?.matmul(&inv_t)?.neg()?;
+        Ok(Some(grad_a))
+    }
+}
+
+// Returns the sign and the log absolute value of the determinant stacked on a trailing
+// dimension of size 2.
+#[derive(Debug, Clone, Copy)]
+struct SlogDet;
+
+impl crate::CustomOp1 for SlogDet {
+    fn name(&self) -> &'static str {
+        "slogdet"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let a = f64_slice(storage, layout, "slogdet")?;
+        let dims = layout.dims();
+        let n = dims[dims.len() - 1];
+        let batch = &dims[..dims.len() - 2];
+        let mut res = Vec::with_capacity(2 * a.len() / (n * n).max(1));
+        if n > 0 {
+            for a in a.chunks_exact(n * n) {
+                let (sign, logdet) = slogdet(a, n);
+                res.push(sign);
+                res.push(logdet);
+            }
+        } else {
+            // The determinant of an empty matrix is 1.
+            for _ in 0..batch.iter().product() {
+                res.push(1.);
+                res.push(0.);
+            }
+        }
+        Ok((CpuStorage::F64(res), [batch, &[2]].concat().into()))
+    }
+
+    fn bwd(&self, arg: &Tensor, _res: &Tensor, grad_res: &Tensor) -> Result<Option<Tensor>> {
+        // Only the log absolute value has a gradient: grad_a = grad_logdet a^-T
+        let grad_logdet = grad_res.narrow(D::Minus1, 1, 1)?.unsqueeze(D::Minus1)?;
+        let grad_a = arg.inverse()?.t()?.broadcast_mul(&grad_logdet)?;
+        Ok(Some(grad_a))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cholesky;
+
+impl crate::CustomOp1 for Cholesky {
+    fn name(&self) -> &'static str {
+        "cholesky"
+    }
+
+    fn cpu_fwd(&self, storage: &CpuStorage, layout: &Layout) -> Result<(CpuStorage, Shape)> {
+        let a = f64_slice(storage, layout, "cholesky")?;
+        let n = layout.dims()[layout.dims().len() - 1];
+        let mut l = vec![0f64; a.len()];
+        if n > 0 {
+            for (a, l) in a.chunks_exact(n * n).zip(l.chunks_exact_mut(n * n)) {
+                cholesky(a, l, n)?
+            }
+        }
+        Ok((CpuStorage::F64(l), layout.shape().clone()))
+    }
+
+    fn bwd(&self, _arg: &Tensor, l: &Tensor, grad_l: &Tensor) -> Result<Option<Tensor>> {
+        // grad_a = l^-T phi(l^T grad_l) l^-1 where phi symmetrizes the lower triangular part.
+        let n = l.dim(D::Minus1)?;
+        let tril = Tensor::tril2(n, l.dtype(), l.device())?;
+        let strict_tril = (&tril - Tensor::eye(n, l.dtype(), l.device())?)?;
+        let m = l.t()?.matmul(grad_l)?.broadcast_mul(&tril)?;
+        let m = ((&m + m.broadcast_mul(&strict_tril)?.t()?)? * 0.5)?;
+        let lt = l.t()?;
+        let s = lt.triangular_solve(&m, true)?;
+        let grad_a = lt.triangular_solve(&s.t()?, true)?.t()?;
+        Ok(Some(grad_a))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TriangularSolve {
+    upper: bool,
+}
+
+impl crate::CustomOp2 for TriangularSolve {
+    fn name(&self) -> &'static str {
+        "triangular-solve"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &CpuStorage,
+        l1: &Layout,
+        s2: &CpuStorage,
+        l2: &Layout,
+    ) -> Result<(CpuStorage, Shape)> {
+        let a = f64_slice(s1, l1, "triangular-solve")?;
+        let b = f64_slice(s2, l2, "triangular-solve")?;
+        let n = l1.dims()[l1.dims().len() - 1];
+        let k = l2.dims()[l2.dims().len() - 1];
+        let mut x = b.to_vec();
+        if n > 0 && k > 0 {
+            for (a, x) in a.chunks_exact(n * n).zip(x.chunks_exact_mut(n * k)) {
+                triangular_solve(a, x, n, k, self.upper)?
+            }
+        }
+        Ok((CpuStorage::F64(x), l2.shape().clone()))
+    }
+
+    fn bwd(
+        &self,
+        a: &Tensor,
+        _b: &Tensor,
+        x: &Tensor,
+        grad_x: &Tensor,
+    ) -> Result<(Option<Tensor>, Option<Tensor>)> {
+        let n = a.dim(D::Minus1)?;
+        let grad_b = a.t()?.triangular_solve(grad_x, !self.upper)?;
+        let mask = if self.upper {
+            Tensor::triu2(n, a.dtype(), a.device())?
+        } else {
+            Tensor::tril2(n, a.dtype(), a.device())?
+        };
+        let grad_a = grad_b.matmul(&x.t()?)?.neg()?.broadcast_mul(&mask)?;
+        Ok((Some(grad_a), Some(grad_b)))
+    }
+}
+
+impl Tensor {
+    /// Computes the Cholesky decomposition of a batch of symmetric positive-definite matrices.
+    ///
+    /// The input has shape `(.., n, n)` and only its lower triangular part is used. The result is
+    /// the lower triangular matrix `l` such that `l.matmul(&l.t()?)?` is equal to the input.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[4f32, 2.], [2., 5.]], &Device::Cpu)?;
+    /// let l = a.cholesky()?;
+    /// assert_eq!(l.to_vec2::<f32>()?, &[[2., 0.], [1., 2.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    ///
+    /// The decomposition is computed on the host in `f64`, without LAPACK or cuSOLVER, and the
+    /// result is moved back to the device of the input.
+    pub fn cholesky(&self) -> Result<Self> {
+        square_dim(self, "cholesky")?;
+        let l = to_host(self)?.apply_op1(Cholesky)?;
+        from_host(&l, self)
+    }
+
+    /// Solves `self.matmul(x) = rhs` for `x` where `self` is a batch of triangular matrices.
+    ///
+    /// `self` has shape `(.., n, n)` and is upper triangular if `upper` is `true`, lower
+    /// triangular otherwise, the elements from the other triangle are ignored. `rhs` has shape
+    /// `(.., n, k)`, the batch dimensions of both arguments are broadcasted together.
+    ///
+    /// As for [`Tensor::cholesky`], the system is solved on the host in `f64`.
+    pub fn triangular_solve(&self, rhs: &Self, upper: bool) -> Result<Self> {
+        let n = square_dim(self, "triangular-solve")?;
+        let (rhs_n, k) = match rhs.dims() {
+            [.., rhs_n, k] => (*rhs_n, *k),
+            _ => bail!(
+                "triangular-solve expects a matrix rhs, got {:?}",
+                rhs.shape()
+            ),
+        };
+        if rhs_n != n {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: rhs.shape().clone(),
+                op: "triangular-solve",
+            }
+            .bt())?
+        }
+        let a_batch: Shape = self.dims()[..self.rank() - 2].into();
+        let b_batch: Shape = rhs.dims()[..rhs.rank() - 2].into();
+        let batch = a_batch.broadcast_shape_binary_op(&b_batch, "triangular-solve")?;
+        let a = self.broadcast_as([batch.dims(), &[n, n]].concat())?;
+        let b = rhs.broadcast_as([batch.dims(), &[n, k]].concat())?;
+        let x = to_host(&a)?.apply_op2(&to_host(&b)?, TriangularSolve { upper })?;
+        from_host(&x, rhs)
+    }
+
+    /// Computes the singular value decomposition of a batch of matrices.
+    ///
+    /// For an input of shape `(.., m, n)`, returns `(u, s, vt)` such that `u * diag(s) * vt` is
+    /// equal to the input. With `k = min(m, n)`, `s` has shape `(.., k)` and contains the singular
+    /// values in decreasing order. When `full_matrices` is `false`, `u` has shape `(.., m, k)` and
+    /// `vt` has shape `(.., k, n)`, otherwise they are square with shapes `(.., m, m)` and
+    /// `(.., n, n)`.
+    ///
+    /// This operation does not support backpropagation, an error is returned if the input
+    /// tracks gradients, e.g. when computed from a [`crate::Var`], unless it is detached or the
+    /// call is made within [`crate::no_grad`]. It runs on the host in `f64` using one-sided
+    /// Jacobi rotations, without LAPACK or cuSOLVER.
+    pub fn svd(&self, full_matrices: bool) -> Result<(Self, Self, Self)> {
+        let (batch, m, n) = matrix_dims(self, "svd")?;
+        check_no_grad(self, "svd")?;
+        let k = usize::min(m, n);
+        let (u_cols, vt_rows) = if full_matrices { (m, n) } else { (k, k) };
+        let a = to_host(self)?.flatten_all()?.to_vec1::<f64>()?;
+        let mut us = Vec::with_capacity(batch * m * u_cols);
+        let mut ss = Vec::with_capacity(batch * k);
+        let mut vts = Vec::with_capacity(batch * vt_rows * n);
+        if m * n > 0 {
+            for a in a.chunks_exact(m * n) {
+                let (u, s, vt) = svd(a, m, n, full_matrices);
+                us.extend(u);
+                ss.extend(s);
+                vts.extend(vt);
+            }
+        }
+        let u = batched_from_host(us, self, &[m, u_cols])?;
+        let s = batched_from_host(ss, self, &[k])?;
+        let vt = batched_from_host(vts, self, &[vt_rows, n])?;
+        Ok((u, s, vt))
+    }
+
+    /// Computes the QR decomposition of a batch of matrices.
+    ///
+    /// For an input of shape `(.., m, n)`, returns `(q, r)` where `q` has orthonormal columns, `r`
+    /// is upper triangular with a non-negative diagonal, and `q.matmul(&r)?` is equal to the
+    /// input. With `k = min(m, n)`, `q` has shape `(.., m, k)` and `r` has shape `(.., k, n)` when
+    /// `full_matrices` is `false`, otherwise `q` has shape `(.., m, m)` and `r` has shape
+    /// `(.., m, n)`.
+    ///
+    /// This operation does not support backpropagation, an error is returned if the input
+    /// tracks gradients unless it is detached or the call is made within [`crate::no_grad`]. It
+    /// runs on the host in `f64` using Householder reflections, without LAPACK or cuSOLVER.
+    ///
+    /// ```rust
+    /// use candle_core::{test_utils::to_vec2_round, Tensor, Device};
+    /// let a = Tensor::new(&[[0f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let (q, r) = a.qr(false)?;
+    /// assert_eq!(to_vec2_round(&q, 4)?, &[[0., 1.], [1., 0.]]);
+    /// assert_eq!(to_vec2_round(&r, 4)?, &[[3., 4.], [0., 2.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn qr(&self, full_matrices: bool) -> Result<(Self, Self)> {
+        let (batch, m, n) = matrix_dims(self, "qr")?;
+        check_no_grad(self, "qr")?;
+        let k = if full_matrices { m } else { usize::min(m, n) };
+        let a = to_host(self)?.flatten_all()?.to_vec1::<f64>()?;
+        let mut qs = Vec::with_capacity(batch * m * k);
+        let mut rs = Vec::with_capacity(batch * k * n);
+        for b in 0..batch {
+            let (q, r) = qr(&a[b * m * n..(b + 1) * m * n], m, n);
+            for row in q.chunks_exact(m.max(1)).take(m) {
+                qs.extend_from_slice(&row[..k])
+            }
+            rs.extend_from_slice(&r[..k * n]);
+        }
+        let q = batched_from_host(qs, self, &[m, k])?;
+        let r = batched_from_host(rs, self, &[k, n])?;
+        Ok((q, r))
+    }
+
+    /// Computes the eigenvalues and eigenvectors of a batch of real symmetric matrices.
+    ///
+    /// For an input of shape `(.., n, n)`, returns `(eigenvalues, eigenvectors)` with shapes
+    /// `(.., n)` and `(.., n, n)`. The eigenvalues are sorted in ascending order and the
+    /// eigenvector for the `i`-th eigenvalue is stored in the `i`-th column. Only the lower
+    /// triangle of the input is used, the upper triangle is assumed to mirror it.
+    ///
+    /// The decomposition is computed on the host in `f64` with the cyclic Jacobi method, inputs
+    /// stored on other devices are copied to the cpu and the results are copied back. Complex
+    /// hermitian matrices, using the `(.., n, n, 2)` layout of the complex helpers, are not
+    /// supported and return an error.
+    ///
+    /// This operation does not support backpropagation, an error is returned if the input
+    /// tracks gradients unless it is detached or the call is made within [`crate::no_grad`].
+    ///
+    /// ```rust
+    /// use candle_core::{test_utils::to_vec1_round, Tensor, Device};
+    /// let a = Tensor::new(&[[2f32, 1.], [1., 2.]], &Device::Cpu)?;
+    /// let (values, vectors) = a.eigh()?;
+    /// assert_eq!(to_vec1_round(&values, 4)?, &[1., 3.]);
+    /// assert_eq!(vectors.dims(), &[2, 2]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn eigh(&self) -> Result<(Self, Self)> {
+        if let [.., n1, n2, 2] = self.dims() {
+            if n1 == n2 && *n1 != 2 {
+                bail!(
+                    "eigh only supports real symmetric matrices, got {:?} which looks like a \
+                     complex input",
+                    self.shape()
+                )
+            }
+        }
+        let n = square_dim(self, "eigh")?;
+        check_no_grad(self, "eigh")?;
+        let a = to_host(self)?.flatten_all()?.to_vec1::<f64>()?;
+        let mut values = Vec::with_capacity(a.len() / n.max(1));
+        let mut vectors = Vec::with_capacity(a.len());
+        if n > 0 {
+            for a in a.chunks_exact(n * n) {
+                let (w, v) = eigh(a, n);
+                values.extend(w);
+                vectors.extend(v);
+            }
+        }
+        let values = batched_from_host(values, self, &[n])?;
+        let vectors = batched_from_host(vectors, self, &[n, n])?;
+        Ok((values, vectors))
+    }
+
+    /// Computes the inverse of a batch of square matrices of shape `(.., n, n)`, an error is
+    /// returned if one of the matrices is singular. The inverse is computed on the host in `f64`
+    /// from an LU decomposition with partial pivoting.
+    ///
+    /// ```rust
+    /// use candle_core::{test_utils::to_vec2_round, Tensor, Device};
+    /// let a = Tensor::new(&[[4f32, 7.], [2., 6.]], &Device::Cpu)?;
+    /// let inv = a.inverse()?;
+    /// assert_eq!(to_vec2_round(&inv, 4)?, &[[0.6, -0.7], [-0.2, 0.4]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn inverse(&self) -> Result<Self> {
+        square_dim(self, "inverse")?;
+        let inv = to_host(self)?.apply_op1(Inverse)?;
+        from_host(&inv, self)
+    }
+
+    /// Computes the sign and the natural logarithm of the absolute value of the determinant for a
+    /// batch of square matrices of shape `(.., n, n)`, both results have shape `(..)`. For
+    /// singular matrices the sign is 0 and the log absolute value is `-inf`.
+    ///
+    /// The gradient flows through the log absolute value, this is more accurate than going
+    /// through [`Tensor::det`] when the determinant is very large or very small.
+    pub fn slogdet(&self) -> Result<(Self, Self)> {
+        square_dim(self, "slogdet")?;
+        let res = from_host(&to_host(self)?.apply_op1(SlogDet)?, self)?;
+        let sign = res.narrow(D::Minus1, 0, 1)?.squeeze(D::Minus1)?;
+        let logdet = res.narrow(D::Minus1, 1, 1)?.squeeze(D::Minus1)?;
+        Ok((sign, logdet))
+    }
+
+    /// Computes the determinant of a batch of square matrices of shape `(.., n, n)`, the result
+    /// has shape `(..)`.
+    ///
+    /// ```rust
+    /// use candle_core::{test_utils::to_vec1_round, Tensor, Device};
+    /// let a = Tensor::new(&[[[4f32, 7.], [2., 6.]], [[0., 1.], [1., 0.]]], &Device::Cpu)?;
+    /// assert_eq!(to_vec1_round(&a.det()?, 4)?, &[10., -1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn det(&self) -> Result<Self> {
+        let (sign, logdet) = self.slogdet()?;
+        sign * logdet.exp()?
+    }
+}
@@ -101,6 +101,9 @@ impl BackendStorage for MetalStorage {
             DType::BF16 => Ok(CpuStorage::BF16(self.to_cpu()?)),
             DType::F32 => Ok(CpuStorage::F32(self.to_cpu()?)),
             DType::F64 => Ok(CpuStorage::F64(self.to_cpu()?)),
+            DType::F8E4M3 => Ok(CpuStorage::F8E4M3(self.to_cpu()?)),
+            DType::F8E5M2 => Ok(CpuStorage::F8E5M2(self.to_cpu()?)),
+            DType::Bool => Ok(CpuStorage::Bool(self.to_cpu()?)),
         }
     }
 
@@ -1396,6 +1399,68 @@ impl BackendStorage for MetalStorage {
         .map_err(MetalError::from)?;
         Ok(acc)
     }
+    fn index_set(
+        &self,
+        l: &Layout,
+        ids: &Self,
+        ids_l: &Layout,
+        src: &Self,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        let mut acc = self.device.zeros_impl(l.shape(), self.dtype())?;
+        self.copy_strided_src(&mut acc, 0, l)?;
+        if !ids_l.is_contiguous() || !src_l.is_contiguous() {
+            return Err(crate::Error::RequiresContiguous { op: "index-set" }.bt());
+        };
+        let name = match (ids.dtype, self.dtype) {
+            (DType::I64, DType::BF16) => "iset_i64_bf16",
+            (DType::I64, DType::F16) => "iset_i64_f16",
+            (DType::I64, DType::F32) => "iset_i64_f32",
+            (DType::I64, DType::I64) => "iset_i64_i64",
+            (DType::I64, DType::U32) => "iset_i64_u32",
+            (DType::I64, DType::U8) => "iset_i64_u8",
+
+            (DType::U32, DType::BF16) => "iset_u32_bf16",
+            (DType::U32, DType::F16) => "iset_u32_f16",
+            (DType::U32, DType::F32) => "iset_u32_f32",
+            (DType::U32, DType::I64) => "iset_u32_i64",
+            (DType::U32, DType::U32) => "iset_u32_u32",
+            (DType::U32, DType::U8) => "iset_u32_u8",
+
+            (DType::U8, DType::BF16) => "iset_u8_bf16",
+            (DType::U8, DType::F16) => "iset_u8_f16",
+            (DType::U8, DType::F32) => "iset_u8_f32",
+            (DType::U8, DType::I64) => "iset_u8_i64",
+            (DType::U8, DType::U32) => "iset_u8_u32",
+            (DType::U8, DType::U8) => "iset_u8_u8",
+
+            _ => Err(MetalError::UnexpectedDType {
+                msg: "index-set ids should be u8/u32/i64",
+                expected: DType::U32,
+                got: ids.dtype(),
+            })?,
+        };
+        let command_buffer = self.device.command_buffer()?;
+        let src = buffer_o(&src.buffer, src_l, src.dtype);
+        let ids = buffer_o(&ids.buffer, ids_l, ids.dtype);
+        // The index-set kernels have the same signature as the index-add ones.
+        candle_metal_kernels::call_index_add(
+            &self.device.device,
+            &command_buffer,
+            &self.device.kernels,
+            name,
+            src_l.dims(),
+            l.dims(),
+            ids_l.dims(),
+            dim,
+            src,
+            ids,
+            &acc.buffer,
+        )
+        .map_err(MetalError::from)?;
+        Ok(acc)
+    }
     fn matmul(
         &self,
         rhs: &Self,
@@ -1475,7 +1540,10 @@ impl BackendStorage for MetalStorage {
                 DType::BF16 => candle_metal_kernels::copy2d::BFLOAT,
                 DType::I64 => candle_metal_kernels::copy2d::I64,
                 DType::U32 => candle_metal_kernels::copy2d::U32,
-                DType::U8 => candle_metal_kernels::copy2d::U8,
+                // The 8 bits floats and booleans are copied through their bit representation.
+                DType::U8 | DType::F8E4M3 | DType::F8E5M2 | DType::Bool => {
+                    candle_metal_kernels::copy2d::U8
+                }
                 dtype => crate::bail!("Metal copy2d {dtype:?} not implemented"),
             };
             candle_metal_kernels::call_copy2d(
@@ -1521,7 +1589,9 @@ impl BackendStorage for MetalStorage {
                 DType::BF16 => candle_metal_kernels::unary::strided::copy::BFLOAT,
                 DType::I64 => candle_metal_kernels::unary::strided::copy::I64,
                 DType::U32 => candle_metal_kernels::unary::strided::copy::U32,
-                DType::U8 => candle_metal_kernels::unary::strided::copy::U8,
+                DType::U8 | DType::F8E4M3 | DType::F8E5M2 | DType::Bool => {
+                    candle_metal_kernels::unary::strided::copy::U8
+                }
                 dtype => crate::bail!("Metal copy_strided {dtype:?} not implemented"),
             };
             let src = buffer_o(&self.buffer, src_l, self.dtype);
@@ -1863,6 +1933,8 @@ impl BackendDevice for MetalDevice {
             CpuStorageRef::F16(storage) => (storage.len(), self.new_buffer_with_data(storage)),
             CpuStorageRef::F32(storage) => (storage.len(), self.new_buffer_with_data(storage)),
             CpuStorageRef::F64(storage) => (storage.len(), self.new_buffer_with_data(storage)),
+            CpuStorageRef::F8E4M3(storage) => (storage.len(), self.new_buffer_with_data(storage)),
+            CpuStorageRef::F8E5M2(storage) => (storage.len(), self.new_buffer_with_data(storage)),
         };
         Ok(Self::Storage::new(buffer?, self.clone(), count, T::DTYPE))
     }
@@ -1876,6 +1948,9 @@ impl BackendDevice for MetalDevice {
             CpuStorage::F16(storage) => (storage.len(), self.new_buffer_with_data(storage)),
             CpuStorage::F32(storage) => (storage.len(), self.new_buffer_with_data(storage)),
             CpuStorage::F64(storage) => (storage.len(), self.new_buffer_with_data(storage)),
+            CpuStorage::F8E4M3(storage) => (storage.len(), self.new_buffer_with_data(storage)),
+            CpuStorage::F8E5M2(storage) => (storage.len(), self.new_buffer_with_data(storage)),
+            CpuStorage::Bool(storage) => (storage.len(), self.new_buffer_with_data(storage)),
         };
         Ok(Self::Storage::new(
             buffer?,
@@ -88,6 +88,11 @@ impl Header {
             DType::I64 => "i8",
             DType::U32 => "u4",
             DType::U8 => "u1",
+            DType::Bool => "b1",
+            DType::F8E4M3 | DType::F8E5M2 => Err(Error::Npy(format!(
+                "{} is not supported",
+                self.descr.as_str()
+            )))?,
         };
         if !shape.is_empty() {
             shape.push(',')
@@ -166,7 +171,7 @@ impl Header {
                     // "b" | "i1" => DType::S8,
                     "B" | "u1" => DType::U8,
                     "I" | "u4" => DType::U32,
-                    "?" | "b1" => DType::U8,
+                    "?" | "b1" => DType::Bool,
                     // "F" | "F4" => DType::C64,
                     // "D" | "F8" => DType::C128,
                     descr => return Err(Error::Npy(format!("unrecognized descr {descr}"))),
@@ -229,6 +234,11 @@ impl Tensor {
                 reader.read_exact(&mut data_t)?;
                 Tensor::from_vec(data_t, shape, &Device::Cpu)
             }
+            DType::Bool => {
+                let mut data_t = vec![0u8; elem_count];
+                reader.read_exact(&mut data_t)?;
+                Tensor::from_vec(data_t, shape, &Device::Cpu)?.to_dtype(DType::Bool)
+            }
             DType::U32 => {
                 let mut data_t = vec![0u32; elem_count];
                 reader.read_u32_into::<LittleEndian>(&mut data_t)?;
@@ -239,6 +249,9 @@ impl Tensor {
                 reader.read_i64_into::<LittleEndian>(&mut data_t)?;
                 Tensor::from_vec(data_t, shape, &Device::Cpu)
             }
+            DType::F8E4M3 | DType::F8E5M2 => {
+                Err(Error::Npy(format!("{} is not supported", dtype.as_str())))
+            }
         }
     }
 
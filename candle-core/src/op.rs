@@ -13,7 +13,7 @@ pub enum CmpOp {
     Gt,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ReduceOp {
     Sum,
     Min,
@@ -35,7 +35,7 @@ impl ReduceOp {
 }
 
 // These ops return the same type as their input type.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOp {
     Add,
     Mul,
@@ -46,7 +46,7 @@ pub enum BinaryOp {
 }
 
 // Unary ops with no argument
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UnaryOp {
     Exp,
     Log,
@@ -80,7 +80,10 @@ pub enum Op {
     Gather(Tensor, Tensor, usize),
     ScatterAdd(Tensor, Tensor, Tensor, usize),
     IndexSelect(Tensor, Tensor, usize),
+    // An index-select along the first dimension for which the gradient of variables is sparse.
+    SparseIndexSelect(Tensor, Tensor),
     IndexAdd(Tensor, Tensor, Tensor, usize),
+    IndexSet(Tensor, Tensor, Tensor, usize),
     WhereCond(Tensor, Tensor, Tensor),
 
     #[allow(dead_code)]
@@ -155,6 +158,7 @@ pub enum Op {
     Copy(Tensor),
     Broadcast(Tensor),
     Narrow(Tensor, usize, usize, usize),
+    SliceStep(Tensor, usize, usize, usize),
     SliceScatter0(Tensor, Tensor, usize),
     Reshape(Tensor),
     ToDevice(Tensor),
@@ -177,8 +181,12 @@ pub enum Op {
         Tensor,
         std::sync::Arc<Box<dyn crate::CustomOp3 + Send + Sync>>,
     ),
+    Checkpoint(Vec<Tensor>, std::sync::Arc<CheckpointFn>),
+    CustomBwd(Vec<Tensor>, std::sync::Arc<crate::custom_op::BackwardFn>),
 }
 
+pub(crate) type CheckpointFn = dyn Fn(&[Tensor]) -> crate::Result<Tensor> + Send + Sync;
+
 pub trait UnaryOpT {
     const NAME: &'static str;
     const KERNEL: &'static str;
@@ -237,6 +245,11 @@ pub(crate) struct Mul;
 pub(crate) struct Sub;
 pub(crate) struct Maximum;
 pub(crate) struct Minimum;
+pub(crate) struct BitwiseAnd;
+pub(crate) struct BitwiseOr;
+pub(crate) struct BitwiseXor;
+pub(crate) struct Shl;
+pub(crate) struct Shr;
 pub(crate) struct Exp;
 pub(crate) struct Log;
 pub(crate) struct Sin;
@@ -257,8 +270,10 @@ pub(crate) struct Ceil;
 pub(crate) struct Round;
 pub(crate) struct Sign;
 
+// The integer versions use the `$int` method, e.g. the wrapping arithmetic, so that they behave
+// the same in debug and release builds.
 macro_rules! bin_op {
-    ($op:ident, $name: literal, $e: expr, $f32_vec: ident, $f64_vec: ident) => {
+    ($op:ident, $name: literal, $e: expr, $int: ident, $f32_vec: ident, $f64_vec: ident) => {
         impl BinaryOpT for $op {
             const NAME: &'static str = $name;
             const KERNEL: &'static str = concat!("b", $name);
@@ -281,15 +296,21 @@ macro_rules! bin_op {
             }
             #[inline(always)]
             fn u8(v1: u8, v2: u8) -> u8 {
-                $e(v1, v2)
+                v1.$int(v2)
             }
             #[inline(always)]
             fn u32(v1: u32, v2: u32) -> u32 {
-                $e(v1, v2)
+                v1.$int(v2)
             }
             #[inline(always)]
             fn i64(v1: i64, v2: i64) -> i64 {
-                $e(v1, v2)
+                v1.$int(v2)
+            }
+
+            const BF16_VEC: bool = true;
+            #[inline(always)]
+            fn bf16_vec(xs1: &[bf16], xs2: &[bf16], ys: &mut [bf16]) {
+                crate::cpu::bf16_vec::binary_map(xs1, xs2, ys, $e)
             }
 
             #[cfg(feature = "mkl")]
@@ -325,14 +346,15 @@ macro_rules! bin_op {
     };
 }
 
-bin_op!(Add, "add", |v1, v2| v1 + v2, vs_add, vd_add);
-bin_op!(Sub, "sub", |v1, v2| v1 - v2, vs_sub, vd_sub);
-bin_op!(Mul, "mul", |v1, v2| v1 * v2, vs_mul, vd_mul);
-bin_op!(Div, "div", |v1, v2| v1 / v2, vs_div, vd_div);
+bin_op!(Add, "add", |v1, v2| v1 + v2, wrapping_add, vs_add, vd_add);
+bin_op!(Sub, "sub", |v1, v2| v1 - v2, wrapping_sub, vs_sub, vd_sub);
+bin_op!(Mul, "mul", |v1, v2| v1 * v2, wrapping_mul, vs_mul, vd_mul);
+bin_op!(Div, "div", |v1, v2| v1 / v2, wrapping_div, vs_div, vd_div);
 bin_op!(
     Minimum,
     "minimum",
     |v1, v2| if v1 > v2 { v2 } else { v1 },
+    min,
     vs_min,
     vd_min
 );
@@ -340,10 +362,78 @@ bin_op!(
     Maximum,
     "maximum",
     |v1, v2| if v1 < v2 { v2 } else { v1 },
+    max,
     vs_max,
     vd_max
 );
 
+// Bitwise operations are only exposed for integer dtypes, the float versions operate on the
+// bit representation of the values.
+macro_rules! bitwise_op {
+    ($op:ident, $name: literal, $e: expr) => {
+        impl BinaryOpT for $op {
+            const NAME: &'static str = $name;
+            const KERNEL: &'static str = concat!("b", $name);
+            const V: Self = $op;
+            #[inline(always)]
+            fn bf16(v1: bf16, v2: bf16) -> bf16 {
+                bf16::from_bits($e(v1.to_bits(), v2.to_bits()))
+            }
+            #[inline(always)]
+            fn f16(v1: f16, v2: f16) -> f16 {
+                f16::from_bits($e(v1.to_bits(), v2.to_bits()))
+            }
+            #[inline(always)]
+            fn f32(v1: f32, v2: f32) -> f32 {
+                f32::from_bits($e(v1.to_bits(), v2.to_bits()))
+            }
+            #[inline(always)]
+            fn f64(v1: f64, v2: f64) -> f64 {
+                f64::from_bits($e(v1.to_bits(), v2.to_bits()))
+            }
+            #[inline(always)]
+            fn u8(v1: u8, v2: u8) -> u8 {
+                $e(v1, v2)
+            }
+            #[inline(always)]
+            fn u32(v1: u32, v2: u32) -> u32 {
+                $e(v1, v2)
+            }
+            #[inline(always)]
+            fn i64(v1: i64, v2: i64) -> i64 {
+                $e(v1, v2)
+            }
+        }
+    };
+}
+
+// Shifting by a negative amount or by at least the number of bits results in 0, or in -1 when
+// shifting a negative value to the right.
+#[inline(always)]
+fn shl<T: num_traits::PrimInt + num_traits::CheckedShl>(v1: T, v2: T) -> T {
+    v2.to_u32()
+        .and_then(|v2| v1.checked_shl(v2))
+        .unwrap_or(T::zero())
+}
+
+#[inline(always)]
+fn shr<T: num_traits::PrimInt + num_traits::CheckedShr>(v1: T, v2: T) -> T {
+    let overflow = if v1 < T::zero() {
+        !T::zero()
+    } else {
+        T::zero()
+    };
+    v2.to_u32()
+        .and_then(|v2| v1.checked_shr(v2))
+        .unwrap_or(overflow)
+}
+
+bitwise_op!(BitwiseAnd, "bitwise_and", std::ops::BitAnd::bitand);
+bitwise_op!(BitwiseOr, "bitwise_or", std::ops::BitOr::bitor);
+bitwise_op!(BitwiseXor, "bitwise_xor", std::ops::BitXor::bitxor);
+bitwise_op!(Shl, "shl", shl);
+bitwise_op!(Shr, "shr", shr);
+
 #[allow(clippy::redundant_closure_call)]
 macro_rules! unary_op {
     ($op: ident, $name: literal, $a: ident, $e: expr) => {
@@ -379,6 +469,12 @@ macro_rules! unary_op {
             fn i64(_: i64) -> i64 {
                 todo!("no unary function for i64")
             }
+
+            const BF16_VEC: bool = true;
+            #[inline(always)]
+            fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+                crate::cpu::bf16_vec::unary_map(xs, ys, |$a: f32| $e)
+            }
         }
     };
 
@@ -416,6 +512,12 @@ macro_rules! unary_op {
                 todo!("no unary function for i64")
             }
 
+            const BF16_VEC: bool = true;
+            #[inline(always)]
+            fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+                crate::cpu::bf16_vec::unary_map(xs, ys, |$a: f32| $e)
+            }
+
             #[cfg(feature = "mkl")]
             const F32_VEC: bool = true;
             #[cfg(feature = "mkl")]
@@ -454,9 +556,7 @@ unary_op!(Log, "log", v, v.ln(), vs_ln, vd_ln);
 unary_op!(Sin, "sin", v, v.sin(), vs_sin, vd_sin);
 unary_op!(Cos, "cos", v, v.cos(), vs_cos, vd_cos);
 unary_op!(Tanh, "tanh", v, v.tanh(), vs_tanh, vd_tanh);
-unary_op!(Neg, "neg", v, -v);
 unary_op!(Recip, "recip", v, v.recip());
-unary_op!(Sqr, "sqr", v, v * v, vs_sqr, vd_sqr);
 unary_op!(Sqrt, "sqrt", v, v.sqrt(), vs_sqrt, vd_sqrt);
 
 // Hardcode the value for sqrt(2/pi)
@@ -621,6 +721,12 @@ impl UnaryOpT for Silu {
     fn i64(_: i64) -> i64 {
         0
     }
+
+    const BF16_VEC: bool = true;
+    #[inline(always)]
+    fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+        crate::cpu::bf16_vec::unary_map(xs, ys, |v| v / (1.0 + (-v).exp()))
+    }
     const KERNEL: &'static str = "usilu";
 
     #[cfg(feature = "mkl")]
@@ -660,6 +766,117 @@ impl UnaryOpT for Silu {
     }
 }
 
+// The integer versions of neg and sqr wrap around on overflow.
+impl UnaryOpT for Neg {
+    const NAME: &'static str = "neg";
+    const KERNEL: &'static str = "uneg";
+    const V: Self = Neg;
+    #[inline(always)]
+    fn bf16(v: bf16) -> bf16 {
+        -v
+    }
+    #[inline(always)]
+    fn f16(v: f16) -> f16 {
+        -v
+    }
+    #[inline(always)]
+    fn f32(v: f32) -> f32 {
+        -v
+    }
+    #[inline(always)]
+    fn f64(v: f64) -> f64 {
+        -v
+    }
+    #[inline(always)]
+    fn u8(v: u8) -> u8 {
+        v.wrapping_neg()
+    }
+    #[inline(always)]
+    fn u32(v: u32) -> u32 {
+        v.wrapping_neg()
+    }
+    #[inline(always)]
+    fn i64(v: i64) -> i64 {
+        v.wrapping_neg()
+    }
+
+    const BF16_VEC: bool = true;
+    #[inline(always)]
+    fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+        crate::cpu::bf16_vec::unary_map(xs, ys, |v| -v)
+    }
+}
+
+impl UnaryOpT for Sqr {
+    const NAME: &'static str = "sqr";
+    const KERNEL: &'static str = "usqr";
+    const V: Self = Sqr;
+    #[inline(always)]
+    fn bf16(v: bf16) -> bf16 {
+        v * v
+    }
+    #[inline(always)]
+    fn f16(v: f16) -> f16 {
+        v * v
+    }
+    #[inline(always)]
+    fn f32(v: f32) -> f32 {
+        v * v
+    }
+    #[inline(always)]
+    fn f64(v: f64) -> f64 {
+        v * v
+    }
+    #[inline(always)]
+    fn u8(v: u8) -> u8 {
+        v.wrapping_mul(v)
+    }
+    #[inline(always)]
+    fn u32(v: u32) -> u32 {
+        v.wrapping_mul(v)
+    }
+    #[inline(always)]
+    fn i64(v: i64) -> i64 {
+        v.wrapping_mul(v)
+    }
+
+    const BF16_VEC: bool = true;
+    #[inline(always)]
+    fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+        crate::cpu::bf16_vec::unary_map(xs, ys, |v| v * v)
+    }
+
+    #[cfg(feature = "mkl")]
+    const F32_VEC: bool = true;
+    #[cfg(feature = "mkl")]
+    const F64_VEC: bool = true;
+    #[cfg(feature = "mkl")]
+    #[inline(always)]
+    fn f32_vec(xs: &[f32], ys: &mut [f32]) {
+        crate::mkl::vs_sqr(xs, ys)
+    }
+    #[cfg(feature = "mkl")]
+    #[inline(always)]
+    fn f64_vec(xs: &[f64], ys: &mut [f64]) {
+        crate::mkl::vd_sqr(xs, ys)
+    }
+
+    #[cfg(feature = "accelerate")]
+    const F32_VEC: bool = true;
+    #[cfg(feature = "accelerate")]
+    const F64_VEC: bool = true;
+    #[cfg(feature = "accelerate")]
+    #[inline(always)]
+    fn f32_vec(xs: &[f32], ys: &mut [f32]) {
+        crate::accelerate::vs_sqr(xs, ys)
+    }
+    #[cfg(feature = "accelerate")]
+    #[inline(always)]
+    fn f64_vec(xs: &[f64], ys: &mut [f64]) {
+        crate::accelerate::vd_sqr(xs, ys)
+    }
+}
+
 impl UnaryOpT for Abs {
     const NAME: &'static str = "abs";
     const KERNEL: &'static str = "uabs";
@@ -692,6 +909,12 @@ impl UnaryOpT for Abs {
     fn i64(v: i64) -> i64 {
         v.abs()
     }
+
+    const BF16_VEC: bool = true;
+    #[inline(always)]
+    fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+        crate::cpu::bf16_vec::unary_map(xs, ys, f32::abs)
+    }
 }
 
 impl UnaryOpT for Ceil {
@@ -860,7 +1083,13 @@ impl UnaryOpT for Relu {
     }
     #[inline(always)]
     fn i64(v: i64) -> i64 {
-        v
+        v.max(0)
+    }
+
+    const BF16_VEC: bool = true;
+    #[inline(always)]
+    fn bf16_vec(xs: &[bf16], ys: &mut [bf16]) {
+        crate::cpu::bf16_vec::unary_map(xs, ys, |v| v.max(0f32))
     }
 }
 
@@ -874,8 +1103,12 @@ impl BackpropOp {
         BackpropOp(None)
     }
 
+    pub(crate) fn checkpoint(args: &[Tensor], f: std::sync::Arc<CheckpointFn>) -> Self {
+        BackpropOp(Some(Op::Checkpoint(args.to_vec(), f)))
+    }
+
     pub(crate) fn new1(arg: &Tensor, f: impl Fn(Tensor) -> Op) -> Self {
-        let op = if arg.track_op() {
+        let op = if crate::backprop::is_grad_enabled() && arg.track_op() {
             Some(f(arg.clone()))
         } else {
             None
@@ -884,7 +1117,7 @@ impl BackpropOp {
     }
 
     pub(crate) fn new2(arg1: &Tensor, arg2: &Tensor, f: impl Fn(Tensor, Tensor) -> Op) -> Self {
-        let op = if arg1.track_op() || arg2.track_op() {
+        let op = if crate::backprop::is_grad_enabled() && (arg1.track_op() || arg2.track_op()) {
             Some(f(arg1.clone(), arg2.clone()))
         } else {
             None
@@ -898,7 +1131,9 @@ impl BackpropOp {
         arg3: &Tensor,
         f: impl Fn(Tensor, Tensor, Tensor) -> Op,
     ) -> Self {
-        let op = if arg1.track_op() || arg2.track_op() || arg3.track_op() {
+        let op = if crate::backprop::is_grad_enabled()
+            && (arg1.track_op() || arg2.track_op() || arg3.track_op())
+        {
             Some(f(arg1.clone(), arg2.clone(), arg3.clone()))
         } else {
             None
@@ -907,7 +1142,9 @@ impl BackpropOp {
     }
 
     pub(crate) fn new<A: AsRef<Tensor>>(args: &[A], f: impl Fn(Vec<Tensor>) -> Op) -> Self {
-        let op = if args.iter().any(|arg| arg.as_ref().track_op()) {
+        let op = if crate::backprop::is_grad_enabled()
+            && args.iter().any(|arg| arg.as_ref().track_op())
+        {
             let args: Vec<Tensor> = args.iter().map(|arg| arg.as_ref().clone()).collect();
             Some(f(args))
         } else {
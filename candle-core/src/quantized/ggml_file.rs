@@ -130,6 +130,9 @@ fn from_raw_data<T: super::GgmlType + Send + Sync + 'static>(
         Device::Cpu => QStorage::Cpu(Box::new(data.to_vec())),
         Device::Metal(metal) => super::metal::load_quantized(metal, data)?,
         Device::Cuda(cuda) => super::cuda::load_quantized(cuda, data)?,
+        Device::Custom(_) => {
+            crate::bail!("quantized tensors are not supported on custom devices")
+        }
     };
     super::QTensor::new(data, dims)
 }
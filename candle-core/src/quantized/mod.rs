@@ -51,6 +51,9 @@ impl Device {
                 let storage = cuda::QCudaStorage::zeros(cuda, elem_count, dtype)?;
                 Ok(QStorage::Cuda(storage))
             }
+            Device::Custom(_) => {
+                crate::bail!("quantized tensors are not supported on custom devices")
+            }
         }
     }
 }
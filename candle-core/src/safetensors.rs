@@ -15,6 +15,9 @@ impl From<DType> for st::Dtype {
             DType::F16 => st::Dtype::F16,
             DType::F32 => st::Dtype::F32,
             DType::F64 => st::Dtype::F64,
+            DType::F8E4M3 => st::Dtype::F8_E4M3,
+            DType::F8E5M2 => st::Dtype::F8_E5M2,
+            DType::Bool => st::Dtype::BOOL,
         }
     }
 }
@@ -30,6 +33,9 @@ impl TryFrom<st::Dtype> for DType {
             st::Dtype::F16 => Ok(DType::F16),
             st::Dtype::F32 => Ok(DType::F32),
             st::Dtype::F64 => Ok(DType::F64),
+            st::Dtype::F8_E4M3 => Ok(DType::F8E4M3),
+            st::Dtype::F8_E5M2 => Ok(DType::F8E5M2),
+            st::Dtype::BOOL => Ok(DType::Bool),
             dtype => Err(Error::UnsupportedSafeTensorDtype(dtype)),
         }
     }
@@ -192,6 +198,9 @@ impl Tensor {
             DType::F16 => convert_slice::<half::f16>(data, shape, device),
             DType::F32 => convert_slice::<f32>(data, shape, device),
             DType::F64 => convert_slice::<f64>(data, shape, device),
+            DType::F8E4M3 => convert_slice::<crate::F8E4M3>(data, shape, device),
+            DType::F8E5M2 => convert_slice::<crate::F8E5M2>(data, shape, device),
+            DType::Bool => convert_slice::<u8>(data, shape, device)?.to_dtype(DType::Bool),
         }
     }
 }
@@ -213,6 +222,9 @@ fn convert(view: &st::TensorView<'_>, device: &Device) -> Result<Tensor> {
         st::Dtype::F16 => convert_::<half::f16>(view, device),
         st::Dtype::F32 => convert_::<f32>(view, device),
         st::Dtype::F64 => convert_::<f64>(view, device),
+        st::Dtype::F8_E4M3 => convert_::<crate::F8E4M3>(view, device),
+        st::Dtype::F8_E5M2 => convert_::<crate::F8E5M2>(view, device),
+        st::Dtype::BOOL => convert_::<u8>(view, device)?.to_dtype(DType::Bool),
         dtype => Err(Error::UnsupportedSafeTensorDtype(dtype)),
     }
 }
@@ -228,6 +240,9 @@ fn convert_back(tensor: &Tensor) -> Result<Vec<u8>> {
         DType::BF16 => Ok(convert_back_::<half::bf16>(tensor.to_vec1()?)),
         DType::F32 => Ok(convert_back_::<f32>(tensor.to_vec1()?)),
         DType::F64 => Ok(convert_back_::<f64>(tensor.to_vec1()?)),
+        DType::F8E4M3 => Ok(convert_back_::<crate::F8E4M3>(tensor.to_vec1()?)),
+        DType::F8E5M2 => Ok(convert_back_::<crate::F8E5M2>(tensor.to_vec1()?)),
+        DType::Bool => Ok(convert_back_::<u8>(tensor.to_dtype(DType::U8)?.to_vec1()?)),
     }
 }
 
@@ -18,35 +18,39 @@ impl ArgSort {
             v
         };
         if self.asc {
-            sort_indexes
-                .par_chunks_exact_mut(self.last_dim)
-                .zip(vs.par_chunks_exact(self.last_dim))
-                .for_each(|(indexes, vs)| {
-                    indexes
-                        .iter_mut()
-                        .enumerate()
-                        .for_each(|(i, v)| *v = i as u32);
-                    indexes.sort_by(|&i, &j| {
-                        vs[i as usize]
-                            .partial_cmp(&vs[j as usize])
-                            .unwrap_or(std::cmp::Ordering::Greater)
+            crate::utils::install(|| {
+                sort_indexes
+                    .par_chunks_exact_mut(self.last_dim)
+                    .zip(vs.par_chunks_exact(self.last_dim))
+                    .for_each(|(indexes, vs)| {
+                        indexes
+                            .iter_mut()
+                            .enumerate()
+                            .for_each(|(i, v)| *v = i as u32);
+                        indexes.sort_by(|&i, &j| {
+                            vs[i as usize]
+                                .partial_cmp(&vs[j as usize])
+                                .unwrap_or(std::cmp::Ordering::Greater)
+                        })
                     })
-                });
+            });
         } else {
-            sort_indexes
-                .par_chunks_exact_mut(self.last_dim)
-                .zip(vs.par_chunks_exact(self.last_dim))
-                .for_each(|(indexes, vs)| {
-                    indexes
-                        .iter_mut()
-                        .enumerate()
-                        .for_each(|(i, v)| *v = i as u32);
-                    indexes.sort_by(|&j, &i| {
-                        vs[i as usize]
-                            .partial_cmp(&vs[j as usize])
-                            .unwrap_or(std::cmp::Ordering::Greater)
+            crate::utils::install(|| {
+                sort_indexes
+                    .par_chunks_exact_mut(self.last_dim)
+                    .zip(vs.par_chunks_exact(self.last_dim))
+                    .for_each(|(indexes, vs)| {
+                        indexes
+                            .iter_mut()
+                            .enumerate()
+                            .for_each(|(i, v)| *v = i as u32);
+                        indexes.sort_by(|&j, &i| {
+                            vs[i as usize]
+                                .partial_cmp(&vs[j as usize])
+                                .unwrap_or(std::cmp::Ordering::Greater)
+                        })
                     })
-                });
+            });
         }
         sort_indexes
     }
@@ -70,6 +74,9 @@ impl crate::CustomOp1 for ArgSort {
             crate::CpuStorage::F16(vs) => self.asort(vs, layout),
             crate::CpuStorage::F32(vs) => self.asort(vs, layout),
             crate::CpuStorage::F64(vs) => self.asort(vs, layout),
+            crate::CpuStorage::F8E4M3(vs) => self.asort(vs, layout),
+            crate::CpuStorage::F8E5M2(vs) => self.asort(vs, layout),
+            crate::CpuStorage::Bool(vs) => self.asort(vs, layout),
         };
         let sort_indexes = crate::CpuStorage::U32(sort_indexes);
         Ok((sort_indexes, layout.shape().into()))
@@ -149,6 +156,7 @@ impl crate::CustomOp1 for ArgSort {
                     DType::U8 => "asort_asc_u8",
                     DType::U32 => "asort_asc_u32",
                     DType::I64 => "asort_asc_i64",
+                    dtype => crate::bail!("Metal argsort {dtype:?} not implemented"),
                 }
             } else {
                 match storage.dtype() {
@@ -159,6 +167,7 @@ impl crate::CustomOp1 for ArgSort {
                     DType::U8 => "asort_desc_u8",
                     DType::U32 => "asort_desc_u32",
                     DType::I64 => "asort_desc_i64",
+                    dtype => crate::bail!("Metal argsort {dtype:?} not implemented"),
                 }
             }
         };
@@ -204,8 +213,7 @@ impl Tensor {
     /// Returns the indices that sort the tensor along the last dimension.
     ///
     /// If `asc` is `true`, sorting is in ascending order. Otherwise sorting is performed in
-    /// descending order. The sort is unstable so there is no guarantees on the final order when it
-    /// comes to ties.
+    /// descending order. The sort is stable, elements that compare equal keep their relative order.
     pub fn arg_sort_last_dim(&self, asc: bool) -> Result<Tensor> {
         if !self.is_contiguous() {
             return Err(crate::Error::RequiresContiguous {
@@ -224,8 +232,7 @@ impl Tensor {
     /// sorted indexes.
     ///
     /// If `asc` is `true`, sorting is in ascending order. Otherwise sorting is performed in
-    /// descending order. The sort is unstable so there is no guarantees on the final order when it
-    /// comes to ties.
+    /// descending order. The sort is stable, elements that compare equal keep their relative order.
     pub fn sort_last_dim(&self, asc: bool) -> Result<(Tensor, Tensor)> {
         if !self.is_contiguous() {
             return Err(crate::Error::RequiresContiguous {
@@ -236,4 +243,250 @@ impl Tensor {
         let sorted = self.gather(&asort, crate::D::Minus1)?;
         Ok((sorted, asort))
     }
+
+    /// Returns the indices that sort the tensor along dimension `dim`.
+    ///
+    /// If `descending` is `true`, sorting is performed in descending order. The sort is stable,
+    /// elements that compare equal keep their relative order.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[3u32, 1], [1, 4], [3, 0]], &Device::Cpu)?;
+    /// let indexes = t.argsort(0, false)?;
+    /// assert_eq!(indexes.to_vec2::<u32>()?, &[[1, 2], [0, 0], [2, 1]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn argsort<D: crate::shape::Dim>(&self, dim: D, descending: bool) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "argsort")?;
+        let last_dim = self.rank() - 1;
+        if dim == last_dim {
+            return self.contiguous()?.arg_sort_last_dim(!descending);
+        }
+        self.transpose(dim, last_dim)?
+            .contiguous()?
+            .arg_sort_last_dim(!descending)?
+            .transpose(dim, last_dim)?
+            .contiguous()
+    }
+
+    /// Sorts the tensor along dimension `dim`, returns the sorted tensor together with the sorted
+    /// indexes.
+    ///
+    /// If `descending` is `true`, sorting is performed in descending order. The sort is stable,
+    /// elements that compare equal keep their relative order.
+    pub fn sort<D: crate::shape::Dim>(&self, dim: D, descending: bool) -> Result<(Tensor, Tensor)> {
+        let dim = dim.to_index(self.shape(), "sort")?;
+        let asort = self.argsort(dim, descending)?;
+        let sorted = self.gather(&asort, dim)?;
+        Ok((sorted, asort))
+    }
+
+    /// Returns the `k` largest elements along dimension `dim` together with their indexes, the
+    /// values are sorted in descending order.
+    ///
+    /// The computation relies on the arg-sort kernels so it stays on the tensor device.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[3f32, 1., 4., 1., 5.], [9., 2., 6., 5., 3.]], &Device::Cpu)?;
+    /// let (values, indexes) = t.topk(2, 1)?;
+    /// assert_eq!(values.to_vec2::<f32>()?, &[[5., 4.], [9., 6.]]);
+    /// assert_eq!(indexes.to_vec2::<u32>()?, &[[4, 2], [0, 2]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn topk<D: crate::shape::Dim>(&self, k: usize, dim: D) -> Result<(Tensor, Tensor)> {
+        let dim = dim.to_index(self.shape(), "topk")?;
+        let dim_size = self.dim(dim)?;
+        if k > dim_size {
+            crate::bail!("topk: k ({k}) is larger than the dimension size {dim_size}")
+        }
+        let indexes = self.argsort(dim, true)?.narrow(dim, 0, k)?.contiguous()?;
+        let values = self.gather(&indexes, dim)?;
+        Ok((values, indexes))
+    }
+
+    /// Returns the unique values of the flattened tensor in ascending order, together with the
+    /// inverse indices and the counts.
+    ///
+    /// The inverse indices is a `u32` tensor with the same shape as `self` that contains for each
+    /// element its position in the unique values. The counts is a `u32` tensor with the number of
+    /// occurrences of each unique value.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[3u32, 1, 3], [2, 1, 1]], &Device::Cpu)?;
+    /// let (values, inverse, counts) = t.unique()?;
+    /// assert_eq!(values.to_vec1::<u32>()?, &[1, 2, 3]);
+    /// assert_eq!(inverse.to_vec2::<u32>()?, &[[2, 0, 2], [1, 0, 0]]);
+    /// assert_eq!(counts.to_vec1::<u32>()?, &[3, 1, 2]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn unique(&self) -> Result<(Tensor, Tensor, Tensor)> {
+        let flat = self.flatten_all()?;
+        if flat.elem_count() == 0 {
+            return self.unique_consecutive();
+        }
+        let perm = flat.arg_sort_last_dim(true)?;
+        let sorted = flat.gather(&perm, 0)?;
+        let (values, sorted_inverse, counts) = sorted.unique_consecutive()?;
+        let inverse = sorted_inverse
+            .zeros_like()?
+            .scatter_add(&perm, &sorted_inverse, 0)?
+            .reshape(self.shape())?;
+        Ok((values, inverse, counts))
+    }
+
+    /// Removes consecutive duplicates from the flattened tensor, returns the remaining values
+    /// together with the inverse indices and the counts.
+    ///
+    /// This has the same outputs as [`Tensor::unique`] but the values are not sorted beforehand,
+    /// so only runs of equal values get merged.
+    pub fn unique_consecutive(&self) -> Result<(Tensor, Tensor, Tensor)> {
+        let device = self.device();
+        let flat = self.flatten_all()?;
+        let n = flat.elem_count();
+        if n == 0 {
+            let empty = Tensor::from_vec(Vec::<u32>::new(), 0, device)?;
+            return Ok((flat, empty.reshape(self.shape())?, empty));
+        }
+        // A new group starts at each position where the value differs from its predecessor.
+        let boundaries = flat
+            .narrow(0, 1, n - 1)?
+            .ne(&flat.narrow(0, 0, n - 1)?)?
+            .to_vec1::<u8>()?;
+        let mut starts = vec![0u32];
+        let mut inverse = Vec::with_capacity(n);
+        inverse.push(0u32);
+        for (i, &b) in boundaries.iter().enumerate() {
+            if b != 0 {
+                starts.push(i as u32 + 1)
+            }
+            inverse.push(starts.len() as u32 - 1)
+        }
+        let n_unique = starts.len();
+        let counts = starts
+            .iter()
+            .zip(starts.iter().skip(1).chain(std::iter::once(&(n as u32))))
+            .map(|(start, end)| end - start)
+            .collect::<Vec<_>>();
+        let values = flat.index_select(&Tensor::from_vec(starts, n_unique, device)?, 0)?;
+        let inverse = Tensor::from_vec(inverse, self.shape(), device)?;
+        let counts = Tensor::from_vec(counts, n_unique, device)?;
+        Ok((values, inverse, counts))
+    }
+
+    /// Returns for each value in `self` the index at which it would be inserted in `sorted_seq`
+    /// so that the sequence stays sorted, this can be used to bucketize values given some
+    /// boundaries.
+    ///
+    /// `sorted_seq` has to be sorted in ascending order along its last dimension. If it is one
+    /// dimensional, it is used for all the values. Otherwise its leading dimensions have to match
+    /// the leading dimensions of `self` and each row is used for the corresponding row of values.
+    /// When `right` is `false` the returned index is the first position `i` such that
+    /// `value <= sorted_seq[i]`, when it is `true` it is the first position such that
+    /// `value < sorted_seq[i]`. The result is a `u32` tensor with the same shape as `self`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let boundaries = Tensor::new(&[1f32, 3., 5., 7.], &Device::Cpu)?;
+    /// let values = Tensor::new(&[0f32, 3., 6., 9.], &Device::Cpu)?;
+    /// let idx = values.searchsorted(&boundaries, false)?;
+    /// assert_eq!(idx.to_vec1::<u32>()?, &[0, 1, 3, 4]);
+    /// let idx = values.searchsorted(&boundaries, true)?;
+    /// assert_eq!(idx.to_vec1::<u32>()?, &[0, 2, 3, 4]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn searchsorted(&self, sorted_seq: &Tensor, right: bool) -> Result<Tensor> {
+        let seq = match sorted_seq.rank() {
+            0 => crate::bail!("searchsorted expects a sorted sequence with at least one dim"),
+            1 => sorted_seq.clone(),
+            rank => {
+                let batch = &sorted_seq.dims()[..rank - 1];
+                if self.rank() != rank || &self.dims()[..rank - 1] != batch {
+                    crate::bail!(
+                        "searchsorted: leading dims of the sorted sequence {:?} and values {:?} do not match",
+                        sorted_seq.shape(),
+                        self.shape()
+                    )
+                }
+                sorted_seq.unsqueeze(rank - 1)?
+            }
+        };
+        // Count the number of elements in the sequence that are strictly smaller (or smaller or
+        // equal when right is set) than each value.
+        let values = self.unsqueeze(self.rank())?;
+        let before = if right {
+            seq.broadcast_le(&values)?
+        } else {
+            seq.broadcast_lt(&values)?
+        };
+        before.to_dtype(crate::DType::U32)?.sum(crate::D::Minus1)
+    }
+
+    /// Returns the `q`-th quantile of the values along dimension `dim`, `q` should be between 0
+    /// and 1. When the quantile lies between two values, the result is linearly interpolated
+    /// between them. The reduced dimension is kept with a size of 1.
+    pub fn quantile_keepdim<D: crate::shape::Dim>(&self, q: f64, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "quantile")?;
+        let n = self.dim(dim)?;
+        if !(0. ..=1.).contains(&q) {
+            crate::bail!("quantile expects q to be between 0 and 1, got {q}")
+        }
+        if n == 0 {
+            crate::bail!("quantile cannot be computed on an empty dimension {dim}")
+        }
+        let (sorted, _) = self.sort(dim, false)?;
+        let pos = q * (n - 1) as f64;
+        let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+        let lo_values = sorted.narrow(dim, lo, 1)?;
+        if lo == hi {
+            return Ok(lo_values);
+        }
+        let frac = pos - lo as f64;
+        let hi_values = sorted.narrow(dim, hi, 1)?;
+        lo_values.affine(1. - frac, 0.)? + hi_values.affine(frac, 0.)?
+    }
+
+    /// Returns the `q`-th quantile of the values along dimension `dim`, `q` should be between 0
+    /// and 1. When the quantile lies between two values, the result is linearly interpolated
+    /// between them. The reduced dimension is removed.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[4f32, 1., 3., 2.], [0., 10., 20., 30.]], &Device::Cpu)?;
+    /// assert_eq!(t.quantile(0.5, 1)?.to_vec1::<f32>()?, &[2.5, 15.]);
+    /// assert_eq!(t.quantile(0.9, 1)?.to_vec1::<f32>()?, &[3.7, 27.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn quantile<D: crate::shape::Dim>(&self, q: f64, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "quantile")?;
+        self.quantile_keepdim(q, dim)?.squeeze(dim)
+    }
+
+    /// Returns the median of the values along dimension `dim`, keeping the reduced dimension with
+    /// a size of 1. For an even number of values, the lower of the two middle values is used.
+    pub fn median_keepdim<D: crate::shape::Dim>(&self, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "median")?;
+        let n = self.dim(dim)?;
+        if n == 0 {
+            crate::bail!("median cannot be computed on an empty dimension {dim}")
+        }
+        let (sorted, _) = self.sort(dim, false)?;
+        sorted.narrow(dim, (n - 1) / 2, 1)
+    }
+
+    /// Returns the median of the values along dimension `dim`, the reduced dimension is removed.
+    /// For an even number of values, the lower of the two middle values is used.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[4f32, 1., 3., 2.], [0., 10., 20., 30.]], &Device::Cpu)?;
+    /// assert_eq!(t.median(1)?.to_vec1::<f32>()?, &[2., 10.]);
+    /// assert_eq!(t.median(0)?.to_vec1::<f32>()?, &[0., 1., 3., 2.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn median<D: crate::shape::Dim>(&self, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "median")?;
+        self.median_keepdim(dim)?.squeeze(dim)
+    }
 }
@@ -0,0 +1,275 @@
+//! Sparse matrices using the COO and CSR layouts.
+//!
+//! A [`SparseTensor`] stores the non-zero values of a 2D matrix in a dense 1D tensor alongside
+//! integer index tensors, all of them living on the same device. Converting to and from dense
+//! tensors as well as multiplying by a dense matrix are supported, the multiplication only uses
+//! gather/scatter operations so it runs on all devices and supports backpropagation through the
+//! values and the dense argument.
+use crate::{bail, DType, Device, Result, Tensor};
+
+/// The layout used to store the positions of the non-zero values.
+#[derive(Debug, Clone)]
+pub enum SparseLayout {
+    /// Coordinate format, the row and column index of each value are stored explicitly.
+    Coo {
+        row_indices: Tensor,
+        col_indices: Tensor,
+    },
+    /// Compressed sparse row format, the values are sorted by row and `row_offsets` has
+    /// `rows + 1` elements, the values for row `i` are stored between `row_offsets[i]` and
+    /// `row_offsets[i + 1]`.
+    Csr {
+        row_offsets: Tensor,
+        col_indices: Tensor,
+    },
+}
+
+/// A sparse 2D matrix.
+#[derive(Debug, Clone)]
+pub struct SparseTensor {
+    layout: SparseLayout,
+    values: Tensor,
+    shape: (usize, usize),
+}
+
+fn check_indices(indices: &Tensor, nnz: usize, name: &'static str) -> Result<()> {
+    if indices.dims() != [nnz] {
+        bail!(
+            "sparse {name} should have shape ({nnz},), got {:?}",
+            indices.shape()
+        )
+    }
+    if !indices.dtype().is_int() {
+        bail!(
+            "sparse {name} should use an integer dtype, got {:?}",
+            indices.dtype()
+        )
+    }
+    Ok(())
+}
+
+impl SparseTensor {
+    /// Creates a sparse matrix in the COO layout, `indices` has shape `(2, nnz)` and contains the
+    /// row and column index for each value in `values`. Duplicate positions are summed when
+    /// converting to a dense tensor.
+    pub fn coo(indices: &Tensor, values: &Tensor, shape: (usize, usize)) -> Result<Self> {
+        let nnz = values.dims1()?;
+        let (two, indices_nnz) = indices.dims2()?;
+        if two != 2 || indices_nnz != nnz {
+            bail!(
+                "sparse coo indices should have shape (2, {nnz}), got {:?}",
+                indices.shape()
+            )
+        }
+        let row_indices = indices.get(0)?;
+        let col_indices = indices.get(1)?;
+        Self::from_layout(
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            },
+            values.clone(),
+            shape,
+        )
+    }
+
+    /// Creates a sparse matrix in the CSR layout.
+    pub fn csr(
+        row_offsets: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        shape: (usize, usize),
+    ) -> Result<Self> {
+        Self::from_layout(
+            SparseLayout::Csr {
+                row_offsets: row_offsets.clone(),
+                col_indices: col_indices.clone(),
+            },
+            values.clone(),
+            shape,
+        )
+    }
+
+    fn from_layout(layout: SparseLayout, values: Tensor, shape: (usize, usize)) -> Result<Self> {
+        let nnz = values.dims1()?;
+        match &layout {
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            } => {
+                check_indices(row_indices, nnz, "row indices")?;
+                check_indices(col_indices, nnz, "col indices")?;
+            }
+            SparseLayout::Csr {
+                row_offsets,
+                col_indices,
+            } => {
+                check_indices(row_offsets, shape.0 + 1, "row offsets")?;
+                check_indices(col_indices, nnz, "col indices")?;
+            }
+        }
+        Ok(Self {
+            layout,
+            values,
+            shape,
+        })
+    }
+
+    /// Creates a sparse matrix in the COO layout from the non-zero elements of a dense 2D tensor.
+    pub fn from_dense(xs: &Tensor) -> Result<Self> {
+        let shape = xs.dims2()?;
+        let indices = xs.nonzero()?.t()?.contiguous()?;
+        let row_indices = indices.get(0)?;
+        let col_indices = indices.get(1)?;
+        let flat_indices = (row_indices.affine(shape.1 as f64, 0.)? + &col_indices)?;
+        let values = xs.flatten_all()?.index_select(&flat_indices, 0)?;
+        Self::from_layout(
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            },
+            values,
+            shape,
+        )
+    }
+
+    /// Returns the dense 2D tensor for this sparse matrix.
+    pub fn to_dense(&self) -> Result<Tensor> {
+        let (row_indices, col_indices) = self.coo_indices()?;
+        Tensor::zeros(self.shape, self.dtype(), self.device())?.index_put(
+            &[&row_indices, &col_indices],
+            &self.values,
+            true,
+        )
+    }
+
+    /// Converts the sparse matrix to the COO layout.
+    pub fn to_coo(&self) -> Result<Self> {
+        let (row_indices, col_indices) = self.coo_indices()?;
+        Self::from_layout(
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            },
+            self.values.clone(),
+            self.shape,
+        )
+    }
+
+    /// Converts the sparse matrix to the CSR layout, the values get sorted by row and the order
+    /// is preserved within a row.
+    pub fn to_csr(&self) -> Result<Self> {
+        let (row_indices, col_indices) = match &self.layout {
+            SparseLayout::Csr { .. } => return Ok(self.clone()),
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            } => (row_indices, col_indices),
+        };
+        if self.nnz() == 0 {
+            let row_offsets = Tensor::zeros(self.shape.0 + 1, DType::I64, self.device())?;
+            return Self::csr(&row_offsets, col_indices, &self.values, self.shape);
+        }
+        let perm = row_indices.argsort(0, false)?;
+        let row_indices = row_indices.gather(&perm, 0)?;
+        let col_indices = col_indices.gather(&perm, 0)?;
+        let values = self.values.gather(&perm, 0)?;
+        let mut row_offsets = vec![0i64; self.shape.0 + 1];
+        for row in row_indices.to_dtype(DType::I64)?.to_vec1::<i64>()? {
+            if row < 0 || row as usize >= self.shape.0 {
+                bail!("sparse row index {row} out of range for {:?}", self.shape)
+            }
+            row_offsets[row as usize + 1] += 1
+        }
+        for i in 0..self.shape.0 {
+            row_offsets[i + 1] += row_offsets[i]
+        }
+        let row_offsets = Tensor::new(row_offsets, self.device())?;
+        Self::from_layout(
+            SparseLayout::Csr {
+                row_offsets,
+                col_indices,
+            },
+            values,
+            self.shape,
+        )
+    }
+
+    // Returns the row and column index of each value.
+    fn coo_indices(&self) -> Result<(Tensor, Tensor)> {
+        match &self.layout {
+            SparseLayout::Coo {
+                row_indices,
+                col_indices,
+            } => Ok((row_indices.clone(), col_indices.clone())),
+            SparseLayout::Csr {
+                row_offsets,
+                col_indices,
+            } => {
+                let row_offsets = row_offsets.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+                let mut row_indices = Vec::with_capacity(self.nnz());
+                for (row, w) in row_offsets.windows(2).enumerate() {
+                    if w[1] < w[0] {
+                        bail!("sparse row offsets should be non-decreasing {row_offsets:?}")
+                    }
+                    row_indices.resize(row_indices.len() + (w[1] - w[0]) as usize, row as u32)
+                }
+                if row_indices.len() != self.nnz() {
+                    bail!(
+                        "sparse row offsets cover {} values, expected {}",
+                        row_indices.len(),
+                        self.nnz()
+                    )
+                }
+                let n = row_indices.len();
+                let row_indices = Tensor::from_vec(row_indices, n, self.device())?;
+                Ok((row_indices, col_indices.clone()))
+            }
+        }
+    }
+
+    /// Multiplies this sparse matrix of shape `(m, k)` by a dense matrix of shape `(k, n)`, the
+    /// result is a dense matrix of shape `(m, n)`.
+    pub fn matmul(&self, rhs: &Tensor) -> Result<Tensor> {
+        let (m, k) = self.shape;
+        let (rhs_k, n) = rhs.dims2()?;
+        if rhs_k != k {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: (m, k).into(),
+                rhs: rhs.shape().clone(),
+                op: "sparse-matmul",
+            }
+            .bt())?
+        }
+        let (row_indices, col_indices) = self.coo_indices()?;
+        let rows = rhs
+            .index_select(&col_indices, 0)?
+            .broadcast_mul(&self.values.unsqueeze(1)?)?;
+        Tensor::zeros((m, n), rows.dtype(), rows.device())?.index_add(&row_indices, &rows, 0)
+    }
+
+    pub fn layout(&self) -> &SparseLayout {
+        &self.layout
+    }
+
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// The number of stored values.
+    pub fn nnz(&self) -> usize {
+        self.values.elem_count()
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.values.dtype()
+    }
+
+    pub fn device(&self) -> &Device {
+        self.values.device()
+    }
+}
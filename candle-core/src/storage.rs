@@ -1,4 +1,5 @@
-use crate::backend::BackendStorage;
+use crate::backend::{BackendDevice, BackendStorage};
+use crate::custom_backend::CustomStorage;
 use crate::op::{self, CmpOp, ReduceOp};
 use crate::{CpuStorage, CudaStorage, DType, Device, Error, Layout, MetalStorage, Result, Shape};
 use crate::{CustomOp1, CustomOp2, CustomOp3, InplaceOp1, InplaceOp2, InplaceOp3};
@@ -10,6 +11,7 @@ pub enum Storage {
     Cpu(CpuStorage),
     Cuda(CudaStorage),
     Metal(MetalStorage),
+    Custom(CustomStorage),
 }
 
 impl Storage {
@@ -24,6 +26,10 @@ impl Storage {
                 let storage = storage.try_clone(layout)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.try_clone(layout)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -32,6 +38,7 @@ impl Storage {
             Self::Cpu(_) => Device::Cpu,
             Self::Cuda(storage) => Device::Cuda(storage.device().clone()),
             Self::Metal(storage) => Device::Metal(storage.device().clone()),
+            Self::Custom(storage) => Device::Custom(storage.device().clone()),
         }
     }
 
@@ -40,6 +47,7 @@ impl Storage {
             Self::Cpu(storage) => storage.dtype(),
             Self::Cuda(storage) => storage.dtype(),
             Self::Metal(storage) => storage.dtype(),
+            Self::Custom(storage) => storage.dtype(),
         }
     }
 
@@ -73,7 +81,18 @@ impl Storage {
         }
     }
 
+    // Boolean tensors only support the ops that move or select values and the logical ops so
+    // that masks cannot be used in arithmetic by accident.
+    pub(crate) fn check_not_bool(&self, op: &'static str) -> Result<()> {
+        if self.dtype() == DType::Bool {
+            Err(Error::UnsupportedDTypeForOp(DType::Bool, op).bt())
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
+        self.check_not_bool("affine")?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.affine(layout, mul, add)?;
@@ -87,10 +106,15 @@ impl Storage {
                 let storage = storage.affine(layout, mul, add)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.affine(layout, mul, add)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
     pub(crate) fn powf(&self, layout: &Layout, alpha: f64) -> Result<Self> {
+        self.check_not_bool("powf")?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.powf(layout, alpha)?;
@@ -104,10 +128,15 @@ impl Storage {
                 let storage = storage.powf(layout, alpha)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.powf(layout, alpha)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
     pub(crate) fn elu(&self, layout: &Layout, alpha: f64) -> Result<Self> {
+        self.check_not_bool("elu")?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.elu(layout, alpha)?;
@@ -121,6 +150,10 @@ impl Storage {
                 let storage = storage.elu(layout, alpha)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.elu(layout, alpha)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -146,6 +179,10 @@ impl Storage {
                 let storage = lhs.cmp(op, rhs, lhs_layout, rhs_layout)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(lhs), Self::Custom(rhs)) => {
+                let storage = lhs.cmp(op, rhs, lhs_layout, rhs_layout)?;
+                Ok(Self::Custom(storage))
+            }
             (lhs, rhs) => {
                 // Should not happen because of the same device check above but we're defensive
                 // anyway.
@@ -160,6 +197,7 @@ impl Storage {
     }
 
     pub(crate) fn reduce_op(&self, op: ReduceOp, layout: &Layout, s: &[usize]) -> Result<Self> {
+        self.check_not_bool(op.name())?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.reduce_op(op, layout, s)?;
@@ -173,6 +211,10 @@ impl Storage {
                 let storage = storage.reduce_op(op, layout, s)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.reduce_op(op, layout, s)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -190,6 +232,10 @@ impl Storage {
                 let storage = storage.to_dtype(layout, dtype)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.to_dtype(layout, dtype)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -207,6 +253,12 @@ impl Storage {
                 let (storage, shape) = c.metal_fwd(storage, l)?;
                 Ok((Self::Metal(storage), shape))
             }
+            Self::Custom(storage) => {
+                // User-defined ops run on the cpu for custom devices.
+                let (s, shape) = c.cpu_fwd(&storage.to_cpu_storage()?, l)?;
+                let storage = storage.device().storage_from_cpu_storage_owned(s)?;
+                Ok((Self::Custom(storage), shape))
+            }
         }
     }
 
@@ -231,6 +283,12 @@ impl Storage {
                 let (s, shape) = c.metal_fwd(s1, l1, s2, l2)?;
                 Ok((Self::Metal(s), shape))
             }
+            (Self::Custom(s1), Self::Custom(s2)) => {
+                let (s2, l2) = (&s2.to_cpu_storage()?, l2);
+                let (s, shape) = c.cpu_fwd(&s1.to_cpu_storage()?, l1, s2, l2)?;
+                let s = s1.device().storage_from_cpu_storage_owned(s)?;
+                Ok((Self::Custom(s), shape))
+            }
             _ => unreachable!(),
         }
     }
@@ -259,6 +317,12 @@ impl Storage {
                 let (s, shape) = c.metal_fwd(s1, l1, s2, l2, s3, l3)?;
                 Ok((Self::Metal(s), shape))
             }
+            (Self::Custom(s1), Self::Custom(s2), Self::Custom(s3)) => {
+                let (s2, s3) = (&s2.to_cpu_storage()?, &s3.to_cpu_storage()?);
+                let (s, shape) = c.cpu_fwd(&s1.to_cpu_storage()?, l1, s2, l2, s3, l3)?;
+                let s = s1.device().storage_from_cpu_storage_owned(s)?;
+                Ok((Self::Custom(s), shape))
+            }
             _ => unreachable!(),
         }
     }
@@ -268,6 +332,12 @@ impl Storage {
             Self::Cpu(storage) => c.cpu_fwd(storage, l),
             Self::Cuda(storage) => c.cuda_fwd(storage, l),
             Self::Metal(storage) => c.metal_fwd(storage, l),
+            Self::Custom(storage) => {
+                let mut s = storage.to_cpu_storage()?;
+                c.cpu_fwd(&mut s, l)?;
+                *storage = storage.device().storage_from_cpu_storage_owned(s)?;
+                Ok(())
+            }
         }
     }
 
@@ -283,6 +353,12 @@ impl Storage {
             (Self::Cpu(s1), Self::Cpu(s2)) => c.cpu_fwd(s1, l1, s2, l2),
             (Self::Cuda(s1), Self::Cuda(s2)) => c.cuda_fwd(s1, l1, s2, l2),
             (Self::Metal(s1), Self::Metal(s2)) => c.metal_fwd(s1, l1, s2, l2),
+            (Self::Custom(s1), Self::Custom(s2)) => {
+                let mut s = s1.to_cpu_storage()?;
+                c.cpu_fwd(&mut s, l1, &s2.to_cpu_storage()?, l2)?;
+                *s1 = s1.device().storage_from_cpu_storage_owned(s)?;
+                Ok(())
+            }
             _ => unreachable!(),
         }
     }
@@ -304,11 +380,19 @@ impl Storage {
             (Self::Metal(s1), Self::Metal(s2), Self::Metal(s3)) => {
                 c.metal_fwd(s1, l1, s2, l2, s3, l3)
             }
+            (Self::Custom(s1), Self::Custom(s2), Self::Custom(s3)) => {
+                let (s2, s3) = (&s2.to_cpu_storage()?, &s3.to_cpu_storage()?);
+                let mut s = s1.to_cpu_storage()?;
+                c.cpu_fwd(&mut s, l1, s2, l2, s3, l3)?;
+                *s1 = s1.device().storage_from_cpu_storage_owned(s)?;
+                Ok(())
+            }
             _ => unreachable!(),
         }
     }
 
     pub(crate) fn unary_impl<B: op::UnaryOpT>(&self, layout: &Layout) -> Result<Self> {
+        self.check_not_bool(B::NAME)?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.unary_impl::<B>(layout)?;
@@ -322,6 +406,10 @@ impl Storage {
                 let storage = storage.unary_impl::<B>(layout)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.unary_impl::<B>(layout)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -333,6 +421,9 @@ impl Storage {
     ) -> Result<Self> {
         self.same_device(rhs, B::NAME)?;
         self.same_dtype(rhs, B::NAME)?;
+        if !matches!(B::NAME, "bitwise_and" | "bitwise_or" | "bitwise_xor") {
+            self.check_not_bool(B::NAME)?;
+        }
         match (self, rhs) {
             (Storage::Cpu(lhs), Storage::Cpu(rhs)) => {
                 let storage = lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
@@ -346,6 +437,10 @@ impl Storage {
                 let storage = lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(lhs), Self::Custom(rhs)) => {
+                let storage = lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
+                Ok(Self::Custom(storage))
+            }
             (lhs, rhs) => {
                 // Should not happen because of the same device check above but we're defensive
                 // anyway.
@@ -367,6 +462,7 @@ impl Storage {
         params: &crate::conv::ParamsConv1D,
     ) -> Result<Self> {
         self.same_device(kernel, "conv1d")?;
+        self.check_not_bool("conv1d")?;
         self.same_dtype(kernel, "conv1d")?;
         match (self, &kernel) {
             (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
@@ -381,6 +477,10 @@ impl Storage {
                 let s = inp.conv1d(l, kernel, kernel_l, params)?;
                 Ok(Self::Metal(s))
             }
+            (Storage::Custom(inp), Storage::Custom(kernel)) => {
+                let s = inp.conv1d(l, kernel, kernel_l, params)?;
+                Ok(Self::Custom(s))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -398,6 +498,7 @@ impl Storage {
         params: &crate::conv::ParamsConvTranspose1D,
     ) -> Result<Self> {
         self.same_device(kernel, "conv-transpose1d")?;
+        self.check_not_bool("conv-transpose1d")?;
         self.same_dtype(kernel, "conv-transpose1d")?;
         match (self, &kernel) {
             (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
@@ -412,6 +513,10 @@ impl Storage {
                 let s = inp.conv_transpose1d(l, kernel, kernel_l, params)?;
                 Ok(Self::Metal(s))
             }
+            (Storage::Custom(inp), Storage::Custom(kernel)) => {
+                let s = inp.conv_transpose1d(l, kernel, kernel_l, params)?;
+                Ok(Self::Custom(s))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -429,6 +534,7 @@ impl Storage {
         params: &crate::conv::ParamsConv2D,
     ) -> Result<Self> {
         self.same_device(kernel, "conv2d")?;
+        self.check_not_bool("conv2d")?;
         self.same_dtype(kernel, "conv2d")?;
         match (self, &kernel) {
             (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
@@ -443,6 +549,10 @@ impl Storage {
                 let s = inp.conv2d(l, kernel, kernel_l, params)?;
                 Ok(Self::Metal(s))
             }
+            (Storage::Custom(inp), Storage::Custom(kernel)) => {
+                let s = inp.conv2d(l, kernel, kernel_l, params)?;
+                Ok(Self::Custom(s))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -460,6 +570,7 @@ impl Storage {
         params: &crate::conv::ParamsConvTranspose2D,
     ) -> Result<Self> {
         self.same_device(kernel, "conv_transpose2d")?;
+        self.check_not_bool("conv_transpose2d")?;
         self.same_dtype(kernel, "conv_transpose2d")?;
         match (self, &kernel) {
             (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
@@ -474,6 +585,10 @@ impl Storage {
                 let s = inp.conv_transpose2d(l, kernel, kernel_l, params)?;
                 Ok(Self::Metal(s))
             }
+            (Storage::Custom(inp), Storage::Custom(kernel)) => {
+                let s = inp.conv_transpose2d(l, kernel, kernel_l, params)?;
+                Ok(Self::Custom(s))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -489,6 +604,7 @@ impl Storage {
         kernel_size: (usize, usize),
         stride: (usize, usize),
     ) -> Result<Self> {
+        self.check_not_bool("avg-pool2d")?;
         match self {
             Storage::Cpu(storage) => {
                 let storage = storage.avg_pool2d(layout, kernel_size, stride)?;
@@ -502,6 +618,10 @@ impl Storage {
                 let storage = storage.avg_pool2d(layout, kernel_size, stride)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.avg_pool2d(layout, kernel_size, stride)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -524,6 +644,10 @@ impl Storage {
                 let storage = storage.max_pool2d(layout, kernel_size, stride)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.max_pool2d(layout, kernel_size, stride)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -541,6 +665,10 @@ impl Storage {
                 let storage = storage.upsample_nearest1d(layout, sz)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.upsample_nearest1d(layout, sz)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -558,6 +686,10 @@ impl Storage {
                 let storage = storage.upsample_nearest2d(layout, h, w)?;
                 Ok(Self::Metal(storage))
             }
+            Self::Custom(storage) => {
+                let storage = storage.upsample_nearest2d(layout, h, w)?;
+                Ok(Self::Custom(storage))
+            }
         }
     }
 
@@ -585,6 +717,10 @@ impl Storage {
                 let storage = cond.where_cond(layout, t, layout_t, f, layout_f)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(cond), Self::Custom(t), Self::Custom(f)) => {
+                let storage = cond.where_cond(layout, t, layout_t, f, layout_f)?;
+                Ok(Self::Custom(storage))
+            }
             (_, lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -615,6 +751,10 @@ impl Storage {
                 let storage = s.gather(l, indexes, indexes_l, d)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(s), Self::Custom(indexes)) => {
+                let storage = s.gather(l, indexes, indexes_l, d)?;
+                Ok(Self::Custom(storage))
+            }
             _ => unreachable!(),
         }
     }
@@ -629,6 +769,7 @@ impl Storage {
         d: usize,
     ) -> Result<Self> {
         self.same_device(indexes, "scatter-add")?;
+        self.check_not_bool("scatter-add")?;
         self.same_device(source, "scatter-add")?;
         match (self, indexes, source) {
             (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
@@ -643,6 +784,10 @@ impl Storage {
                 let storage = s.scatter_add(l, indexes, indexes_l, source, source_l, d)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(s), Self::Custom(indexes), Self::Custom(source)) => {
+                let storage = s.scatter_add(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Custom(storage))
+            }
             _ => unreachable!(),
         }
     }
@@ -658,6 +803,7 @@ impl Storage {
     ) -> Result<Self> {
         self.same_device(indexes, "index-add")?;
         self.same_device(source, "index-add")?;
+        self.check_not_bool("index-add")?;
         match (self, indexes, source) {
             (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
                 let storage = s.index_add(l, indexes, indexes_l, source, source_l, d)?;
@@ -671,6 +817,43 @@ impl Storage {
                 let storage = s.index_add(l, indexes, indexes_l, source, source_l, d)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(s), Self::Custom(indexes), Self::Custom(source)) => {
+                let storage = s.index_add(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Custom(storage))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn index_set(
+        &self,
+        l: &Layout,
+        indexes: &Self,
+        indexes_l: &Layout,
+        source: &Self,
+        source_l: &Layout,
+        d: usize,
+    ) -> Result<Self> {
+        self.same_device(indexes, "index-set")?;
+        self.same_device(source, "index-set")?;
+        self.check_not_bool("index-set")?;
+        match (self, indexes, source) {
+            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
+                let storage = s.index_set(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Cpu(storage))
+            }
+            (Self::Cuda(s), Self::Cuda(indexes), Self::Cuda(source)) => {
+                let storage = s.index_set(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Cuda(storage))
+            }
+            (Self::Metal(s), Self::Metal(indexes), Self::Metal(source)) => {
+                let storage = s.index_set(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Metal(storage))
+            }
+            (Self::Custom(s), Self::Custom(indexes), Self::Custom(source)) => {
+                let storage = s.index_set(l, indexes, indexes_l, source, source_l, d)?;
+                Ok(Self::Custom(storage))
+            }
             _ => unreachable!(),
         }
     }
@@ -696,6 +879,10 @@ impl Storage {
                 let storage = lhs.index_select(rhs, lhs_l, rhs_l, d)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(lhs), Self::Custom(rhs)) => {
+                let storage = lhs.index_select(rhs, lhs_l, rhs_l, d)?;
+                Ok(Self::Custom(storage))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -714,6 +901,7 @@ impl Storage {
     ) -> Result<Self> {
         self.same_device(rhs, "matmul")?;
         self.same_dtype(rhs, "matmul")?;
+        self.check_not_bool("matmul")?;
         match (self, rhs) {
             (Self::Cpu(lhs), Self::Cpu(rhs)) => {
                 let storage = lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout)?;
@@ -727,6 +915,10 @@ impl Storage {
                 let storage = lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout)?;
                 Ok(Self::Metal(storage))
             }
+            (Self::Custom(lhs), Self::Custom(rhs)) => {
+                let storage = lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout)?;
+                Ok(Self::Custom(storage))
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -749,6 +941,9 @@ impl Storage {
             (Self::Metal(src), Self::Metal(dst)) => {
                 Ok(src.copy_strided_src(dst, dst_offset, src_l)?)
             }
+            (Self::Custom(src), Self::Custom(dst)) => {
+                Ok(src.copy_strided_src(dst, dst_offset, src_l)?)
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
@@ -777,6 +972,9 @@ impl Storage {
             (Self::Metal(src), Self::Metal(dst)) => {
                 Ok(src.copy2d(dst, d1, d2, src_s, dst_s, src_o, dst_o)?)
             }
+            (Self::Custom(src), Self::Custom(dst)) => {
+                Ok(src.copy2d(dst, d1, d2, src_s, dst_s, src_o, dst_o)?)
+            }
             (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
                 lhs: lhs.device().location(),
                 rhs: rhs.device().location(),
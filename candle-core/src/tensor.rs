@@ -5,6 +5,7 @@ use crate::op::{BackpropOp, BinaryOp, CmpOp, Op, ReduceOp, UnaryOp};
 use crate::scalar::TensorOrScalar;
 use crate::shape::{Dim, Dims};
 use crate::{bail, storage::Storage, DType, Device, Error, Layout, Result, Shape};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 /// Unique identifier for tensors.
@@ -20,6 +21,80 @@ impl TensorId {
     }
 }
 
+/// The way a tensor is split by [`Tensor::tensor_split`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TensorSplit {
+    /// Splits into this number of parts of similar sizes, the first parts being one element
+    /// larger than the last ones when the dimension is not divisible.
+    Sections(usize),
+    /// Splits before each of these indexes, e.g. `[2, 3]` results in the parts `..2`, `2..3`
+    /// and `3..`.
+    Indices(Vec<usize>),
+}
+
+impl From<usize> for TensorSplit {
+    fn from(sections: usize) -> Self {
+        Self::Sections(sections)
+    }
+}
+
+impl From<&[usize]> for TensorSplit {
+    fn from(indices: &[usize]) -> Self {
+        Self::Indices(indices.to_vec())
+    }
+}
+
+impl<const N: usize> From<[usize; N]> for TensorSplit {
+    fn from(indices: [usize; N]) -> Self {
+        Self::Indices(indices.to_vec())
+    }
+}
+
+impl From<Vec<usize>> for TensorSplit {
+    fn from(indices: Vec<usize>) -> Self {
+        Self::Indices(indices)
+    }
+}
+
+/// The order of the output dimensions of [`Tensor::meshgrid`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indexing {
+    /// Cartesian indexing, the first two dimensions are swapped compared to the order of the
+    /// inputs, e.g. inputs with 3 and 4 elements result in grids of shape `(4, 3)`.
+    Xy,
+    /// Matrix indexing, the dimensions are in the same order as the inputs, e.g. inputs with 3
+    /// and 4 elements result in grids of shape `(3, 4)`.
+    Ij,
+}
+
+impl From<bool> for Indexing {
+    /// Converts the `xy_indexing` flag taken by the earlier versions of [`Tensor::meshgrid`],
+    /// `true` is [`Indexing::Xy`] and `false` is [`Indexing::Ij`].
+    fn from(xy_indexing: bool) -> Self {
+        if xy_indexing {
+            Self::Xy
+        } else {
+            Self::Ij
+        }
+    }
+}
+
+/// The way the values added by [`Tensor::pad`] are computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadMode {
+    /// Pads with zeros.
+    Zeros,
+    /// Pads with the values mirrored around the first and last elements, excluding them, e.g.
+    /// `[1, 2, 3]` padded by 2 on both sides is `[3, 2, 1, 2, 3, 2, 1]`.
+    Reflect,
+    /// Pads by repeating the first and last elements, e.g. `[1, 2, 3]` padded by 2 on both sides
+    /// is `[1, 1, 1, 2, 3, 3, 3]`.
+    Replicate,
+    /// Pads by wrapping around, e.g. `[1, 2, 3]` padded by 2 on both sides is
+    /// `[2, 3, 1, 2, 3, 1, 2]`.
+    Circular,
+}
+
 pub struct Tensor_ {
     id: TensorId,
     // As we provide inner mutability on the tensor content, the alternatives are:
@@ -38,10 +113,18 @@ pub struct Tensor_ {
     layout: Layout,
     op: BackpropOp,
     is_variable: bool,
+    // Only used for variables, frozen variables are treated as constants for backpropagation.
+    frozen: AtomicBool,
     dtype: DType,
     device: Device,
 }
 
+impl Drop for Tensor_ {
+    fn drop(&mut self) {
+        crate::backprop::remove_grad_hooks(self.id)
+    }
+}
+
 impl AsRef<Tensor> for Tensor {
     fn as_ref(&self) -> &Tensor {
         self
@@ -155,6 +238,43 @@ macro_rules! broadcast_binary_op {
     };
 }
 
+macro_rules! bitwise_op {
+    ($fn_name:ident, $op_name:ident, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// This is only supported for integer tensors, `rhs` can be a tensor with the same shape
+        /// as `self` or a scalar value. The result is not tracked by back-propagation.
+        pub fn $fn_name<T: TensorOrScalar>(&self, rhs: T) -> Result<Self> {
+            self.bitwise_impl::<crate::op::$op_name, T>(rhs, stringify!($fn_name))
+        }
+    };
+}
+
+macro_rules! binary_op_inplace {
+    ($fn_name:ident, $op_name:ident) => {
+        /// In place version of the binary operation, see [`Tensor::binary_op_inplace`].
+        pub fn $fn_name<T: TensorOrScalar>(&self, rhs: T) -> Result<()> {
+            self.binary_op_inplace::<crate::op::$op_name, T>(rhs, stringify!($fn_name))
+        }
+    };
+}
+
+// The values are computed from both ends so that `start` and `end` are exact.
+fn linspace_f64(start: f64, end: f64, steps: usize) -> impl Iterator<Item = f64> {
+    let step = if steps > 1 {
+        (end - start) / (steps - 1) as f64
+    } else {
+        0.
+    };
+    (0..steps).map(move |i| {
+        if 2 * i < steps {
+            start + step * i as f64
+        } else {
+            end - step * (steps - 1 - i) as f64
+        }
+    })
+}
+
 /// Creates a fresh tensor structure based on a storage and a shape, this uses contiguous strides.
 pub(crate) fn from_storage<S: Into<Shape>>(
     storage: Storage,
@@ -170,6 +290,7 @@ pub(crate) fn from_storage<S: Into<Shape>>(
         layout: Layout::contiguous(shape),
         op,
         is_variable,
+        frozen: AtomicBool::new(false),
         dtype,
         device,
     };
@@ -297,6 +418,80 @@ impl Tensor {
         Tensor::rand_f64_impl(lo, up, self.shape(), self.dtype(), self.device(), false)
     }
 
+    /// Returns a tensor with the same shape and dtype as `self` where each element is 1 with the
+    /// probability given by the matching element of `self` and 0 otherwise. The sampling uses the
+    /// random number generator of the tensor device, see [`crate::Generator::bernoulli`] for a
+    /// version using an explicit generator.
+    pub fn bernoulli(&self) -> Result<Self> {
+        self.rand_like(0., 1.)?.lt(self)?.to_dtype(self.dtype())
+    }
+
+    /// Draws `num_samples` indexes from the categorical distributions given by the last dimension
+    /// of `self`, using the random number generator of the tensor device.
+    ///
+    /// `self` has one or two dimensions and contains non-negative weights that do not have to sum
+    /// to one. The result is a `u32` tensor where the last dimension has size `num_samples`. When
+    /// `replacement` is `false`, an index is drawn at most once per distribution, so `num_samples`
+    /// cannot exceed the number of categories and should not exceed the number of non-zero
+    /// weights. The sampling uses the Gumbel-max trick so the distributions are never copied
+    /// back to the host.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let probs = Tensor::new(&[[0f32, 1., 0.], [0., 0., 0.5]], &Device::Cpu)?;
+    /// let samples = probs.multinomial(2, true)?;
+    /// assert_eq!(samples.to_vec2::<u32>()?, &[[1, 1], [2, 2]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn multinomial(&self, num_samples: usize, replacement: bool) -> Result<Self> {
+        let (batch, n) = match self.dims() {
+            [n] => (None, *n),
+            [b, n] => (Some(*b), *n),
+            _ => bail!("multinomial expects 1 or 2 dims, got {:?}", self.shape()),
+        };
+        if n == 0 {
+            bail!("multinomial expects at least one category")
+        }
+        if !replacement && num_samples > n {
+            bail!("multinomial cannot draw {num_samples} samples from {n} categories without replacement")
+        }
+        let log_w = match self.dtype() {
+            DType::F64 => self.clone(),
+            DType::F32 | DType::F16 | DType::BF16 | DType::F8E4M3 | DType::F8E5M2 => {
+                self.to_dtype(DType::F32)?
+            }
+            dtype => bail!("multinomial expects a float tensor, got {dtype:?}"),
+        }
+        .log()?;
+        let log_w = log_w.reshape((batch.unwrap_or(1), 1, n))?;
+        // Adding Gumbel noise to the log weights and taking the argmax samples an index, taking
+        // the top indexes instead samples without replacement.
+        let gumbel = |shape: (usize, usize, usize)| {
+            Tensor::rand_f64_impl(0., 1., shape, log_w.dtype(), self.device(), false)?
+                .log()?
+                .neg()?
+                .log()?
+                .neg()
+        };
+        let samples = if replacement {
+            let noise = gumbel((batch.unwrap_or(1), num_samples, n))?;
+            log_w.broadcast_add(&noise)?.argmax(2)?
+        } else {
+            let keys = log_w.broadcast_add(&gumbel((batch.unwrap_or(1), 1, n))?)?;
+            if num_samples == 1 {
+                keys.argmax(2)?
+            } else {
+                keys.squeeze(1)?
+                    .arg_sort_last_dim(false)?
+                    .narrow(1, 0, num_samples)?
+            }
+        };
+        match batch {
+            None => samples.reshape(num_samples),
+            Some(batch) => samples.reshape((batch, num_samples)),
+        }
+    }
+
     pub(crate) fn randn_impl<S: Into<Shape>, T: crate::FloatDType>(
         mean: T,
         std: T,
@@ -422,6 +617,49 @@ impl Tensor {
         Self::from_vec_impl(data, len, device, false)
     }
 
+    /// Creates a new 1D tensor with `steps` values evenly spaced from `start` to `end`, both
+    /// included.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::linspace(0f32, 1., 5, &Device::Cpu)?;
+    /// assert_eq!(t.to_vec1::<f32>()?, &[0., 0.25, 0.5, 0.75, 1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn linspace<D: crate::WithDType>(
+        start: D,
+        end: D,
+        steps: usize,
+        device: &Device,
+    ) -> Result<Self> {
+        let data = linspace_f64(start.to_f64(), end.to_f64(), steps)
+            .map(D::from_f64)
+            .collect::<Vec<_>>();
+        Self::from_vec_impl(data, steps, device, false)
+    }
+
+    /// Creates a new 1D tensor with `steps` values evenly spaced on a log scale from
+    /// `base^start` to `base^end`, both included.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::logspace(0f32, 3., 4, 10., &Device::Cpu)?;
+    /// assert_eq!(t.to_vec1::<f32>()?, &[1., 10., 100., 1000.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn logspace<D: crate::WithDType>(
+        start: D,
+        end: D,
+        steps: usize,
+        base: f64,
+        device: &Device,
+    ) -> Result<Self> {
+        let data = linspace_f64(start.to_f64(), end.to_f64(), steps)
+            .map(|v| D::from_f64(base.powf(v)))
+            .collect::<Vec<_>>();
+        Self::from_vec_impl(data, steps, device, false)
+    }
+
     pub(crate) fn from_vec_impl<S: Into<Shape>, D: crate::WithDType>(
         data: Vec<D>,
         shape: S,
@@ -485,17 +723,257 @@ impl Tensor {
     /// Returns true if the computation graph should track this op, that is if it is
     /// a variable or if it has some variable as dependencies.
     pub fn track_op(&self) -> bool {
-        self.is_variable || self.op.is_some()
+        (self.is_variable && !self.is_frozen()) || self.op.is_some()
+    }
+
+    fn bitwise_impl<B: crate::op::BinaryOpT, T: TensorOrScalar>(
+        &self,
+        rhs: T,
+        op: &'static str,
+    ) -> Result<Self> {
+        if !self.dtype().is_int() {
+            bail!("{op} expects an integer tensor, got {:?}", self.dtype())
+        }
+        let rhs = match rhs.to_tensor_scalar()? {
+            crate::scalar::TensorScalar::Tensor(rhs) => rhs,
+            crate::scalar::TensorScalar::Scalar(rhs) => rhs
+                .to_dtype(self.dtype())?
+                .to_device(self.device())?
+                .broadcast_as(self.shape())?,
+        };
+        let shape = self.same_shape_binary_op(&rhs, op)?;
+        if shape.elem_count() == 0 {
+            return Ok(self.clone());
+        }
+        let storage =
+            self.storage()
+                .binary_impl::<B>(&rhs.storage(), self.layout(), rhs.layout())?;
+        Ok(from_storage(
+            storage,
+            shape.clone(),
+            BackpropOp::none(),
+            false,
+        ))
+    }
+
+    /// Element-wise bitwise not, this is only supported for integer tensors.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[0b1010_0101u8, 0x0f], &Device::Cpu)?;
+    /// assert_eq!(t.bitwise_not()?.to_vec1::<u8>()?, &[0b0101_1010, 0xf0]);
+    /// // Decode two 4-bit values packed in each byte.
+    /// let low = t.bitwise_and(0x0fu8)?;
+    /// let high = t.shr(4u8)?;
+    /// assert_eq!(low.to_vec1::<u8>()?, &[0b0101, 0xf]);
+    /// assert_eq!(high.to_vec1::<u8>()?, &[0b1010, 0]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn bitwise_not(&self) -> Result<Self> {
+        match self.dtype() {
+            DType::U8 => self.bitwise_xor(u8::MAX),
+            DType::U32 => self.bitwise_xor(u32::MAX),
+            DType::I64 => self.bitwise_xor(-1i64),
+            dtype => bail!("bitwise_not expects an integer tensor, got {dtype:?}"),
+        }
+    }
+
+    fn logical_impl<B: crate::op::BinaryOpT>(&self, rhs: &Self, op: &'static str) -> Result<Self> {
+        if self.dtype() != DType::Bool || rhs.dtype() != DType::Bool {
+            bail!(
+                "{op} expects boolean tensors, got {:?} and {:?}",
+                self.dtype(),
+                rhs.dtype()
+            )
+        }
+        let shape = self.shape().broadcast_shape_binary_op(rhs.shape(), op)?;
+        let lhs = self.broadcast_as(&shape)?;
+        let rhs = rhs.broadcast_as(&shape)?;
+        let storage = lhs
+            .storage()
+            .binary_impl::<B>(&rhs.storage(), lhs.layout(), rhs.layout())?;
+        Ok(from_storage(storage, shape, BackpropOp::none(), false))
+    }
+
+    /// Element-wise logical and of two boolean tensors, the shapes are broadcast.
+    pub fn logical_and(&self, rhs: &Self) -> Result<Self> {
+        self.logical_impl::<crate::op::BitwiseAnd>(rhs, "logical_and")
+    }
+
+    /// Element-wise logical or of two boolean tensors, the shapes are broadcast.
+    pub fn logical_or(&self, rhs: &Self) -> Result<Self> {
+        self.logical_impl::<crate::op::BitwiseOr>(rhs, "logical_or")
+    }
+
+    /// Element-wise logical xor of two boolean tensors, the shapes are broadcast.
+    pub fn logical_xor(&self, rhs: &Self) -> Result<Self> {
+        self.logical_impl::<crate::op::BitwiseXor>(rhs, "logical_xor")
+    }
+
+    /// Element-wise logical negation of a boolean tensor.
+    pub fn logical_not(&self) -> Result<Self> {
+        self.logical_xor(&self.ones_like()?)
+    }
+
+    /// Packs a boolean tensor into the bits of a `u8` tensor along the last dimension, similar
+    /// to `numpy.packbits`. The first element of each group of 8 values is stored in the most
+    /// significant bit and the last dimension is padded with `false` to a multiple of 8.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, DType};
+    /// let t = Tensor::new(&[1u8, 0, 1, 1, 0, 0, 0, 0, 1], &Device::Cpu)?.to_dtype(DType::Bool)?;
+    /// let p = t.pack_bits()?;
+    /// assert_eq!(p.to_vec1::<u8>()?, &[0b1011_0000, 0b1000_0000]);
+    /// let u = p.unpack_bits(9)?;
+    /// assert_eq!(u.to_dtype(DType::U8)?.to_vec1::<u8>()?, &[1, 0, 1, 1, 0, 0, 0, 0, 1]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn pack_bits(&self) -> Result<Self> {
+        if self.dtype() != DType::Bool {
+            bail!("pack_bits expects a boolean tensor, got {:?}", self.dtype())
+        }
+        let mut dims = self.dims().to_vec();
+        let n = match dims.pop() {
+            Some(n) => n,
+            None => bail!("pack_bits expects a tensor with at least one dimension"),
+        };
+        let n_bytes = n.div_ceil(8);
+        dims.extend([n_bytes, 8]);
+        let t = self
+            .to_dtype(DType::U8)?
+            .pad_with_zeros(crate::D::Minus1, 0, n_bytes * 8 - n)?
+            .reshape(dims.as_slice())?;
+        let shifts =
+            Tensor::new(&[7u8, 6, 5, 4, 3, 2, 1, 0], self.device())?.broadcast_as(t.shape())?;
+        t.shl(&shifts)?.sum(crate::D::Minus1)
+    }
+
+    /// Unpacks the bits of a `u8` tensor into a boolean tensor, this is the reverse of
+    /// [`Tensor::pack_bits`]. `n` is the size of the last dimension of the result, it must be at
+    /// most 8 times the size of the last dimension of `self`.
+    pub fn unpack_bits(&self, n: usize) -> Result<Self> {
+        if self.dtype() != DType::U8 {
+            bail!("unpack_bits expects a u8 tensor, got {:?}", self.dtype())
+        }
+        let mut dims = self.dims().to_vec();
+        let n_bytes = match dims.last() {
+            Some(n_bytes) => *n_bytes,
+            None => bail!("unpack_bits expects a tensor with at least one dimension"),
+        };
+        if n > n_bytes * 8 {
+            bail!("unpack_bits: cannot unpack {n} values from {n_bytes} bytes")
+        }
+        dims.push(8);
+        let t = self
+            .unsqueeze(crate::D::Minus1)?
+            .broadcast_as(dims.as_slice())?;
+        let shifts =
+            Tensor::new(&[7u8, 6, 5, 4, 3, 2, 1, 0], self.device())?.broadcast_as(t.shape())?;
+        dims.pop();
+        *dims.last_mut().unwrap() = n_bytes * 8;
+        t.shr(&shifts)?
+            .bitwise_and(1u8)?
+            .reshape(dims.as_slice())?
+            .narrow(crate::D::Minus1, 0, n)?
+            .to_dtype(DType::Bool)
+    }
+
+    /// Applies a binary operation and writes the result in the storage of `self` rather than
+    /// allocating a new tensor. `rhs` is broadcast to the shape of `self`, it can also be a
+    /// scalar value.
+    ///
+    /// The storage is shared with the tensors that were derived from `self` without a copy, e.g.
+    /// via `narrow` or `reshape`, and these tensors will see the updated values. This is not
+    /// tracked by back-propagation so the operation is rejected when `self` results from the
+    /// computation of a variable, it can be used on variables themselves, e.g. in optimizers,
+    /// as long as the previous values are not needed to compute some gradients. On the cpu the
+    /// values are updated without an intermediary buffer, on other devices the result of the
+    /// operation is computed in a temporary buffer before being copied in place.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let view = t.narrow(0, 1, 1)?;
+    /// t.add_assign_(&Tensor::new(&[10f32, 20.], &Device::Cpu)?)?;
+    /// t.mul_assign_(2f32)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[22., 44.], [26., 48.]]);
+    /// assert_eq!(view.to_vec2::<f32>()?, &[[26., 48.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn binary_op_inplace<B: crate::op::BinaryOpT, T: TensorOrScalar>(
+        &self,
+        rhs: T,
+        op: &'static str,
+    ) -> Result<()> {
+        if self.op.is_some() {
+            bail!("{op}: cannot modify in place a tensor that is part of a compute graph")
+        }
+        if !self.is_contiguous() {
+            Err(Error::RequiresContiguous { op }.bt())?
+        }
+        let rhs = match rhs.to_tensor_scalar()? {
+            crate::scalar::TensorScalar::Tensor(rhs) => rhs,
+            crate::scalar::TensorScalar::Scalar(rhs) => {
+                rhs.to_dtype(self.dtype())?.to_device(self.device())?
+            }
+        };
+        let rhs = rhs.broadcast_as(self.shape())?;
+        if self.elem_count() == 0 {
+            return Ok(());
+        }
+        if !self.same_storage(&rhs) {
+            let (mut storage, layout) = self.storage_mut_and_layout();
+            let (rhs_storage, rhs_layout) = rhs.storage_and_layout();
+            storage.same_device(&rhs_storage, op)?;
+            storage.same_dtype(&rhs_storage, op)?;
+            storage.check_not_bool(op)?;
+            if let (Storage::Cpu(lhs), Storage::Cpu(rhs)) = (&mut *storage, &*rhs_storage) {
+                return lhs.binary_impl_inplace::<B>(rhs, layout, rhs_layout);
+            }
+        }
+        let storage =
+            self.storage()
+                .binary_impl::<B>(&rhs.storage(), self.layout(), rhs.layout())?;
+        let src_l = Layout::contiguous(self.shape());
+        storage.copy_strided_src(
+            &mut self.storage_mut(),
+            self.layout().start_offset(),
+            &src_l,
+        )
     }
 
-    // TODO: Also make an inplace version or a pre-allocated? This could be tricky
-    // if this can create cycles in the compute graph.
     binary_op!(add, Add);
     binary_op!(mul, Mul);
     binary_op!(sub, Sub);
     binary_op!(div, Div);
     binary_op_scalar!(maximum, Maximum);
     binary_op_scalar!(minimum, Minimum);
+    binary_op_inplace!(add_assign_, Add);
+    binary_op_inplace!(mul_assign_, Mul);
+    binary_op_inplace!(sub_assign_, Sub);
+    binary_op_inplace!(div_assign_, Div);
+    binary_op_inplace!(maximum_assign_, Maximum);
+    binary_op_inplace!(minimum_assign_, Minimum);
+    bitwise_op!(bitwise_and, BitwiseAnd, "Element-wise bitwise and.");
+    bitwise_op!(bitwise_or, BitwiseOr, "Element-wise bitwise or.");
+    bitwise_op!(
+        bitwise_xor,
+        BitwiseXor,
+        "Element-wise bitwise exclusive or."
+    );
+    bitwise_op!(
+        shl,
+        Shl,
+        "Element-wise left shift, shifting by a negative amount or by at least the number of \
+        bits of the dtype results in 0."
+    );
+    bitwise_op!(
+        shr,
+        Shr,
+        "Element-wise right shift, this is an arithmetic shift for `i64` values. Shifting by a \
+        negative amount or by at least the number of bits of the dtype results in 0, or in -1 \
+        for negative values."
+    );
     broadcast_binary_op!(broadcast_add, add);
     broadcast_binary_op!(broadcast_mul, mul);
     broadcast_binary_op!(broadcast_sub, sub);
@@ -557,6 +1035,7 @@ impl Tensor {
             Storage::Cpu(cpu_storage) => from_cpu_storage(cpu_storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Custom(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -584,24 +1063,94 @@ impl Tensor {
         Ok(inp)
     }
 
+    /// Repeats each element of the tensor `repeats` times along dimension `dim`, the repeated
+    /// copies are placed next to each other.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let b = a.repeat_interleave(2, 0)?;
+    /// assert_eq!(b.to_vec2::<f32>()?, &[[1., 2.], [1., 2.], [3., 4.], [3., 4.]]);
+    /// let b = a.repeat_interleave(2, 1)?;
+    /// assert_eq!(b.to_vec2::<f32>()?, &[[1., 1., 2., 2.], [3., 3., 4., 4.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn repeat_interleave<D: Dim>(&self, repeats: usize, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "repeat-interleave")?;
+        if repeats == 1 {
+            return Ok(self.clone());
+        }
+        let mut dims = self.dims().to_vec();
+        dims[dim] *= repeats;
+        if repeats == 0 {
+            return Tensor::zeros(dims, self.dtype(), self.device());
+        }
+        // Using cat is faster than a broadcast as it avoids going through a potentially
+        // strided copy.
+        // https://github.com/huggingface/candle/pull/2043
+        let xs = self.unsqueeze(dim + 1)?;
+        Tensor::cat(&vec![&xs; repeats], dim + 1)?.reshape(dims)
+    }
+
+    /// Repeats each element of the tensor along dimension `dim`, the number of repetitions for
+    /// each element is given by `repeats`, a 1D integer tensor with one value per element on
+    /// this dimension.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], &Device::Cpu)?;
+    /// let repeats = Tensor::new(&[1u32, 0, 2], &Device::Cpu)?;
+    /// let b = a.repeat_interleave_with(&repeats, 0)?;
+    /// assert_eq!(b.to_vec2::<f32>()?, &[[1., 2.], [5., 6.], [5., 6.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn repeat_interleave_with<D: Dim>(&self, repeats: &Tensor, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "repeat-interleave")?;
+        let dim_size = self.dim(dim)?;
+        if repeats.dims1()? != dim_size {
+            bail!(
+                "repeat-interleave expects {dim_size} repeats, got {:?}",
+                repeats.shape()
+            )
+        }
+        if !repeats.dtype().is_int() {
+            bail!(
+                "repeat-interleave expects integer repeats, got {:?}",
+                repeats.dtype()
+            )
+        }
+        let repeats = repeats.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        let mut indexes = vec![];
+        for (index, &repeat) in repeats.iter().enumerate() {
+            if repeat < 0 {
+                bail!("repeat-interleave expects non-negative repeats, got {repeat}")
+            }
+            indexes.resize(indexes.len() + repeat as usize, index as u32)
+        }
+        let n = indexes.len();
+        let indexes = Tensor::from_vec(indexes, n, self.device())?;
+        self.index_select(&indexes, dim)
+    }
+
     /// Creates grids of coordinates specified by the 1D inputs.
     ///
     /// # Arguments
     ///
     /// * `args` - A slice of 1D tensors.
-    /// * `xy_indexing` - Whether to use xy indexing or ij indexing. If xy is selected, the
-    /// first dimension corresponds to the cardinality of the second input and the second
-    /// dimension corresponds to the cardinality of the first input. If ij is selected, the
-    /// dimensions are in the same order as the cardinality of the inputs.
+    /// * `indexing` - The order of the output dimensions, see [`Indexing`]. With
+    ///   [`Indexing::Xy`] the first dimension corresponds to the cardinality of the second input
+    ///   and the second dimension corresponds to the cardinality of the first input. With
+    ///   [`Indexing::Ij`] the dimensions are in the same order as the cardinality of the inputs.
+    ///   A `bool` is accepted as well for compatibility, `true` meaning [`Indexing::Xy`].
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use candle_core::{Tensor, Device, Shape};
+    /// use candle_core::{Tensor, Device, Indexing};
     /// let x = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?;
     /// let y = Tensor::new(&[4f32, 5., 6.], &Device::Cpu)?;
     ///
-    /// let grids_xy = Tensor::meshgrid(&[&x, &y], true)?;
+    /// let grids_xy = Tensor::meshgrid(&[&x, &y], Indexing::Xy)?;
     ///
     /// assert_eq!(grids_xy.len(), 2);
     /// assert_eq!(grids_xy[0].dims(), &[3, 3]);
@@ -609,7 +1158,7 @@ impl Tensor {
     /// assert_eq!(grids_xy[0].to_vec2::<f32>()?, &[[1., 2., 3.], [1., 2., 3.], [1., 2., 3.]]);
     /// assert_eq!(grids_xy[1].to_vec2::<f32>()?, &[[4., 4., 4.], [5., 5., 5.], [6., 6., 6.]]);
     ///
-    /// let grids_ij = Tensor::meshgrid(&[&x, &y], false)?;
+    /// let grids_ij = Tensor::meshgrid(&[&x, &y], Indexing::Ij)?;
     ///
     /// assert_eq!(grids_ij[0].to_vec2::<f32>()?, &[[1., 1., 1.], [2., 2., 2.], [3., 3., 3.]]);
     /// assert_eq!(grids_ij[1].to_vec2::<f32>()?, &[[4., 5., 6.], [4., 5., 6.], [4., 5., 6.]]);
@@ -620,15 +1169,21 @@ impl Tensor {
     ///
     /// * Will return `Err` if `args` contains less than 2 tensors.
     ///
-    pub fn meshgrid<A: AsRef<Tensor>>(args: &[A], xy_indexing: bool) -> Result<Vec<Self>> {
+    pub fn meshgrid<A: AsRef<Tensor>, I: Into<Indexing>>(
+        args: &[A],
+        indexing: I,
+    ) -> Result<Vec<Self>> {
         if args.len() <= 1 {
             Err(Error::OpRequiresAtLeastTwoTensors { op: "meshgrid" }.bt())?
         }
-        let args: Vec<_> = if xy_indexing {
-            args.iter().rev().collect()
-        } else {
-            args.iter().collect()
-        };
+        let indexing = indexing.into();
+        // Xy indexing is ij indexing with the first two inputs, and so the first two outputs,
+        // swapped.
+        let xy_indexing = indexing == Indexing::Xy;
+        let mut args: Vec<_> = args.iter().collect();
+        if xy_indexing {
+            args.swap(0, 1)
+        }
 
         let mut shape = Vec::with_capacity(args.len());
         for arg in args.iter() {
@@ -646,7 +1201,7 @@ impl Tensor {
             grids.push(repeated_tensor);
         }
         if xy_indexing {
-            grids.reverse();
+            grids.swap(0, 1)
         }
         Ok(grids)
     }
@@ -730,6 +1285,67 @@ impl Tensor {
         }
     }
 
+    /// Split a tensor into views along dimension `dim`, either in a given number of parts or
+    /// at the given indexes. Contrary to [`Tensor::chunk`], the number of parts is always the
+    /// requested one and some parts can be empty. The indexes are clamped to the dimension size.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 7, &Device::Cpu)?;
+    /// let parts = t.tensor_split(3, 0)?;
+    /// assert_eq!(parts[0].to_vec1::<u32>()?, &[0, 1, 2]);
+    /// assert_eq!(parts[2].to_vec1::<u32>()?, &[5, 6]);
+    /// let parts = t.tensor_split([2, 3, 10], 0)?;
+    /// assert_eq!(parts[1].to_vec1::<u32>()?, &[2]);
+    /// assert_eq!(parts[2].to_vec1::<u32>()?, &[3, 4, 5, 6]);
+    /// assert_eq!(parts[3].dims(), &[0]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn tensor_split<S: Into<TensorSplit>, D: Dim>(
+        &self,
+        split: S,
+        dim: D,
+    ) -> Result<Vec<Self>> {
+        let dim = dim.to_index(self.shape(), "tensor-split")?;
+        let size = self.dim(dim)?;
+        let indices = match split.into() {
+            TensorSplit::Sections(0) => bail!("tensor-split: the number of sections must be > 0"),
+            TensorSplit::Sections(sections) => {
+                let (chunk_size, cnt_additional) = (size / sections, size % sections);
+                (1..sections)
+                    .map(|i| i * chunk_size + usize::min(i, cnt_additional))
+                    .collect()
+            }
+            TensorSplit::Indices(indices) => indices,
+        };
+        let mut start = 0;
+        let mut tensors = Vec::with_capacity(indices.len() + 1);
+        for end in indices.into_iter().chain(std::iter::once(size)) {
+            let end = end.clamp(start, size);
+            tensors.push(self.narrow(dim, start, end - start)?);
+            start = end
+        }
+        Ok(tensors)
+    }
+
+    /// Returns the slices of the tensor along dimension `dim`, these slices have one dimension
+    /// less than the input tensor.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1u32, 2], [3, 4], [5, 6]], &Device::Cpu)?;
+    /// let cols = t.unbind(1)?;
+    /// assert_eq!(cols.len(), 2);
+    /// assert_eq!(cols[1].to_vec1::<u32>()?, &[2, 4, 6]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn unbind<D: Dim>(&self, dim: D) -> Result<Vec<Self>> {
+        let dim = dim.to_index(self.shape(), "unbind")?;
+        (0..self.dim(dim)?)
+            .map(|i| self.narrow(dim, i, 1)?.squeeze(dim))
+            .collect()
+    }
+
     /// Returns a new tensor that is a narrowed version of the input, the dimension `dim`
     /// ranges from `start` to `start + len`.
     pub fn narrow<D: Dim>(&self, dim: D, start: usize, len: usize) -> Result<Self> {
@@ -764,6 +1380,7 @@ impl Tensor {
                 layout,
                 op,
                 is_variable: false,
+                frozen: AtomicBool::new(false),
                 dtype: self.dtype,
                 device: self.device.clone(),
             };
@@ -771,6 +1388,66 @@ impl Tensor {
         }
     }
 
+    /// Returns a strided view of the input along dimension `dim`, keeping the elements within
+    /// `range` that are `step` apart, similar to `start..end:step` slicing in PyTorch.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 10, &Device::Cpu)?.reshape((2, 5))?;
+    /// let t = t.slice_step(1, 1.., 2)?;
+    /// assert_eq!(t.to_vec2::<u32>()?, &[[1, 3], [6, 8]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn slice_step<D: Dim, R: std::ops::RangeBounds<usize>>(
+        &self,
+        dim: D,
+        range: R,
+        step: usize,
+    ) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "slice-step")?;
+        if step == 0 {
+            bail!("slice-step: step has to be positive")
+        }
+        let start = match range.start_bound() {
+            std::ops::Bound::Unbounded => 0,
+            std::ops::Bound::Included(v) => *v,
+            std::ops::Bound::Excluded(v) => *v + 1,
+        };
+        let end = match range.end_bound() {
+            std::ops::Bound::Unbounded => self.dim(dim)?,
+            std::ops::Bound::Included(v) => *v + 1,
+            std::ops::Bound::Excluded(v) => *v,
+        };
+        let len = end.saturating_sub(start);
+        if step == 1 || len == 0 {
+            return self.narrow(dim, start, len);
+        }
+        if end > self.dim(dim)? {
+            Err(Error::NarrowInvalidArgs {
+                shape: self.shape().clone(),
+                dim,
+                start,
+                len,
+                msg: "start + len > dim_len",
+            }
+            .bt())?
+        }
+        let len = len.div_ceil(step);
+        let op = BackpropOp::new1(self, |t| Op::SliceStep(t, dim, start, step));
+        let layout = self.layout().slice_step(dim, start, len, step)?;
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: self.storage.clone(),
+            layout,
+            op,
+            is_variable: false,
+            frozen: AtomicBool::new(false),
+            dtype: self.dtype,
+            device: self.device.clone(),
+        };
+        Ok(Tensor(Arc::new(tensor_)))
+    }
+
     fn squeeze_dims(self, dims: &[usize]) -> Result<Self> {
         match dims {
             [] => Ok(self),
@@ -849,6 +1526,9 @@ impl Tensor {
     {
         let dim = dim.to_index(self.shape(), "roll")?;
         let dim_size = self.dim(dim)?;
+        if dim_size == 0 {
+            return Ok(self.clone());
+        }
         let shift = shift.rem_euclid(dim_size as i32) as usize;
         if shift == 0 {
             Ok(self.clone())
@@ -859,6 +1539,52 @@ impl Tensor {
         }
     }
 
+    /// Roll the tensor input along multiple dimensions, `shifts[i]` is the shift applied to the
+    /// i-th dimension in `dims`.
+    ///
+    /// ```rust
+    /// # use candle_core::{Tensor, Device};
+    /// let tensor = Tensor::new(&[[0f32, 1., 2.], [3., 4., 5.]], &Device::Cpu)?;
+    /// let tensor = tensor.roll_dims(&[1, -1], (0, 1))?;
+    /// assert_eq!(tensor.to_vec2::<f32>()?, &[[4., 5., 3.], [1., 2., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn roll_dims<D: Dims>(&self, shifts: &[i32], dims: D) -> Result<Self> {
+        let dims = dims.to_indexes(self.shape(), "roll")?;
+        if shifts.len() != dims.len() {
+            bail!("roll: got {} shifts for {} dims", shifts.len(), dims.len())
+        }
+        let mut xs = self.clone();
+        for (&shift, &dim) in shifts.iter().zip(dims.iter()) {
+            xs = xs.roll(shift, dim)?
+        }
+        Ok(xs)
+    }
+
+    /// Reverses the order of the elements along the given dimensions.
+    ///
+    /// ```rust
+    /// # use candle_core::{Tensor, Device};
+    /// let tensor = Tensor::new(&[[0f32, 1., 2.], [3., 4., 5.]], &Device::Cpu)?;
+    /// assert_eq!(tensor.flip(1)?.to_vec2::<f32>()?, &[[2., 1., 0.], [5., 4., 3.]]);
+    /// assert_eq!(tensor.flip((0, 1))?.to_vec2::<f32>()?, &[[5., 4., 3.], [2., 1., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn flip<D: Dims>(&self, dims: D) -> Result<Self> {
+        let dims = dims.to_indexes(self.shape(), "flip")?;
+        let mut xs = self.clone();
+        for dim in dims {
+            let dim_size = self.dim(dim)?;
+            if dim_size <= 1 {
+                continue;
+            }
+            let indexes: Vec<u32> = (0..dim_size as u32).rev().collect();
+            let indexes = Tensor::from_vec(indexes, dim_size, self.device())?;
+            xs = xs.index_select(&indexes, dim)?
+        }
+        Ok(xs)
+    }
+
     /// Returns the sum of all elements in the input tensor. The sum is performed over all the
     /// input dimensions.
     ///
@@ -921,6 +1647,115 @@ impl Tensor {
         self.sum_impl(mean_dims, false)? * scale
     }
 
+    /// Returns a `u8` tensor with the same shape as `self` containing 1 where the values of
+    /// `self` are NaN and 0 elsewhere.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[0f32, f32::NAN, f32::INFINITY, -f32::INFINITY], &Device::Cpu)?;
+    /// assert_eq!(a.isnan()?.to_vec1::<u8>()?, &[0, 1, 0, 0]);
+    /// assert_eq!(a.isinf()?.to_vec1::<u8>()?, &[0, 0, 1, 1]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn isnan(&self) -> Result<Self> {
+        if self.dtype().is_float() {
+            self.ne(self)
+        } else {
+            Tensor::zeros(self.shape(), DType::U8, self.device())
+        }
+    }
+
+    /// Returns a `u8` tensor with the same shape as `self` containing 1 where the values of
+    /// `self` are positive or negative infinity and 0 elsewhere.
+    pub fn isinf(&self) -> Result<Self> {
+        if self.dtype().is_float() {
+            self.abs()?.eq(f64::INFINITY)
+        } else {
+            Tensor::zeros(self.shape(), DType::U8, self.device())
+        }
+    }
+
+    /// Replaces the NaN values with `nan`, the positive infinities with `posinf`, and the
+    /// negative infinities with `neginf`. When `posinf` or `neginf` are not set, the largest or
+    /// lowest finite value of the dtype is used.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, f32::NAN, f32::INFINITY, -f32::INFINITY], &Device::Cpu)?;
+    /// let a = a.nan_to_num(0., None, Some(-1.))?;
+    /// assert_eq!(a.to_vec1::<f32>()?, &[1., 0., f32::MAX, -1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn nan_to_num(&self, nan: f64, posinf: Option<f64>, neginf: Option<f64>) -> Result<Self> {
+        let (min, max) = match self.dtype() {
+            DType::BF16 => (half::bf16::MIN.into(), half::bf16::MAX.into()),
+            DType::F16 => (half::f16::MIN.into(), half::f16::MAX.into()),
+            DType::F32 => (f32::MIN.into(), f32::MAX.into()),
+            DType::F64 => (f64::MIN, f64::MAX),
+            DType::F8E4M3 => ((-crate::F8E4M3::MAX).to_f64(), crate::F8E4M3::MAX.to_f64()),
+            DType::F8E5M2 => ((-crate::F8E5M2::MAX).to_f64(), crate::F8E5M2::MAX.to_f64()),
+            DType::U8 | DType::U32 | DType::I64 | DType::Bool => return Ok(self.clone()),
+        };
+        let value = |v: f64| Tensor::new(v, self.device())?.to_dtype(self.dtype());
+        let xs = self.isnan()?.where_cond(&value(nan)?, self)?;
+        let xs = xs
+            .eq(f64::INFINITY)?
+            .where_cond(&value(posinf.unwrap_or(max))?, &xs)?;
+        xs.eq(f64::NEG_INFINITY)?
+            .where_cond(&value(neginf.unwrap_or(min))?, &xs)
+    }
+
+    /// Returns the sum over the selected dimensions treating the NaN values as zeros, the
+    /// reduced dimensions are kept with a size of 1.
+    pub fn nansum_keepdim<D: Dims>(&self, sum_dims: D) -> Result<Self> {
+        self.nansum_impl(sum_dims, true)
+    }
+
+    /// Returns the sum over the selected dimensions treating the NaN values as zeros, the
+    /// reduced dimensions are squeezed.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, f32::NAN, 2.], [f32::NAN, f32::NAN, 3.]], &Device::Cpu)?;
+    /// assert_eq!(a.nansum(1)?.to_vec1::<f32>()?, &[3., 3.]);
+    /// assert_eq!(a.nanmean(1)?.to_vec1::<f32>()?, &[1.5, 3.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn nansum<D: Dims>(&self, sum_dims: D) -> Result<Self> {
+        self.nansum_impl(sum_dims, false)
+    }
+
+    fn nansum_impl<D: Dims>(&self, sum_dims: D, keepdim: bool) -> Result<Self> {
+        if !self.dtype().is_float() {
+            return self.sum_impl(sum_dims, keepdim);
+        }
+        self.isnan()?
+            .where_cond(&self.zeros_like()?, self)?
+            .sum_impl(sum_dims, keepdim)
+    }
+
+    /// Returns the mean over the selected dimensions ignoring the NaN values, the reduced
+    /// dimensions are kept with a size of 1. The result is NaN when all the values are NaN.
+    pub fn nanmean_keepdim<D: Dims>(&self, mean_dims: D) -> Result<Self> {
+        self.nanmean_impl(mean_dims, true)
+    }
+
+    /// Returns the mean over the selected dimensions ignoring the NaN values, the reduced
+    /// dimensions are squeezed. The result is NaN when all the values are NaN.
+    pub fn nanmean<D: Dims>(&self, mean_dims: D) -> Result<Self> {
+        self.nanmean_impl(mean_dims, false)
+    }
+
+    fn nanmean_impl<D: Dims>(&self, mean_dims: D, keepdim: bool) -> Result<Self> {
+        let mean_dims = mean_dims.to_indexes(self.shape(), "nanmean")?;
+        let count = self
+            .isnan()?
+            .eq(0u8)?
+            .to_dtype(self.dtype())?
+            .sum_impl(mean_dims.as_slice(), keepdim)?;
+        self.nansum_impl(mean_dims.as_slice(), keepdim)?.div(&count)
+    }
+
     /// Returns the unbiased variance over the selected dimension.
     pub fn var_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
         let dim = dim.to_index(self.shape(), "var")?;
@@ -978,7 +1813,8 @@ impl Tensor {
     /// Element-wise comparison between two tensors, e.g. equality, greater than, ... The actual
     /// comparison operation is specified by the `op` argument.
     ///
-    /// The returned tensor has the same shape as the original tensors and uses `u8` elements.
+    /// The returned tensor has the same shape as the original tensors and uses `u8` elements,
+    /// it can be converted to a boolean mask with `to_dtype(DType::Bool)`.
     pub fn cmp<T: TensorOrScalar>(&self, rhs: T, op: CmpOp) -> Result<Self> {
         let rhs = match rhs.to_tensor_scalar()? {
             crate::scalar::TensorScalar::Tensor(rhs) => rhs,
@@ -1030,8 +1866,35 @@ impl Tensor {
     }
 
     /// Clamp the tensor values to be between `min` and `max`.
+    ///
+    /// The bounds can either be scalars or tensors, tensor bounds are broadcasted with `self` so
+    /// that they can for example hold a different range per channel.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[-2f32, 0.5, 3.], [-2., 0.5, 3.]], &Device::Cpu)?;
+    /// let min = Tensor::new(&[[-1f32], [0.]], &Device::Cpu)?;
+    /// let t = t.clamp(&min, 1f32)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[-1., 0.5, 1.], [0., 0.5, 1.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
     pub fn clamp<T1: TensorOrScalar, T2: TensorOrScalar>(&self, min: T1, max: T2) -> Result<Self> {
-        self.maximum(min)?.minimum(max)
+        let xs = match min.to_tensor_scalar()? {
+            crate::scalar::TensorScalar::Tensor(min) => self.broadcast_maximum(&min)?,
+            crate::scalar::TensorScalar::Scalar(min) => self.maximum(
+                &min.to_dtype(self.dtype())?
+                    .to_device(self.device())?
+                    .broadcast_as(self.shape())?,
+            )?,
+        };
+        match max.to_tensor_scalar()? {
+            crate::scalar::TensorScalar::Tensor(max) => xs.broadcast_minimum(&max),
+            crate::scalar::TensorScalar::Scalar(max) => xs.minimum(
+                &max.to_dtype(xs.dtype())?
+                    .to_device(xs.device())?
+                    .broadcast_as(xs.shape())?,
+            ),
+        }
     }
 
     /// Interpolate the input tensor to the `target_size` size, taking the value of the nearest element.
@@ -1191,56 +2054,288 @@ impl Tensor {
                 rhs: rhs.shape().clone(),
                 op: "matmul",
             }
-            .bt())?
-        }
-
-        let storage = self.storage().matmul(
-            &rhs.storage(),
-            (batching, m, n, k),
-            self.layout(),
-            rhs.layout(),
-        )?;
-        let op = BackpropOp::new2(self, rhs, Op::Matmul);
-        Ok(from_storage(storage, c_shape, op, false))
+            .bt())?
+        }
+
+        let storage = self.storage().matmul(
+            &rhs.storage(),
+            (batching, m, n, k),
+            self.layout(),
+            rhs.layout(),
+        )?;
+        let op = BackpropOp::new2(self, rhs, Op::Matmul);
+        Ok(from_storage(storage, c_shape, op, false))
+    }
+
+    /// Matrix-multiplication with broadcasting support.
+    ///
+    /// Compared to `matmul` the two matrixes are allowed to have different dimensions as long as
+    /// they are compatible for broadcast. E.g. if `self` has shape `(j, 1, n, k)` and `rhs` has
+    /// shape `(l, k, m)`, the output will have shape `(j, l, n, m)`.
+    pub fn broadcast_matmul(&self, rhs: &Self) -> Result<Self> {
+        let lhs = self;
+        let (l_shape, r_shape) = lhs.shape().broadcast_shape_matmul(rhs.shape())?;
+        let l_broadcast = l_shape != *lhs.shape();
+        let r_broadcast = r_shape != *rhs.shape();
+        // TODO: Avoid concretising the broadcasted matrixes via contiguous.
+        match (l_broadcast, r_broadcast) {
+            (true, true) => lhs
+                .broadcast_as(&l_shape)?
+                .contiguous()?
+                .matmul(&rhs.broadcast_as(&r_shape)?.contiguous()?),
+            (false, true) => lhs.matmul(&rhs.broadcast_as(&r_shape)?.contiguous()?),
+            (true, false) => lhs.broadcast_as(&l_shape)?.contiguous()?.matmul(rhs),
+            (false, false) => lhs.matmul(rhs),
+        }
+    }
+
+    /// Returns a tensor with the same shape as the input tensor, the values are taken from
+    /// `on_true` if the input tensor value is not zero, and `on_false` at the positions where the
+    /// input tensor is equal to zero.
+    ///
+    /// The three tensors are broadcasted to a common shape.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let mask = Tensor::new(&[[1u8, 0], [0, 1]], &Device::Cpu)?;
+    /// let on_true = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    /// let on_false = Tensor::new(0f32, &Device::Cpu)?;
+    /// let t = mask.where_cond(&on_true, &on_false)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., 0.], [0., 2.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn where_cond(&self, on_true: &Self, on_false: &Self) -> Result<Self> {
+        let shape = self
+            .shape()
+            .broadcast_shape_binary_op(on_true.shape(), "where_cond")?
+            .broadcast_shape_binary_op(on_false.shape(), "where_cond")?;
+        if self.shape() != &shape || on_true.shape() != &shape || on_false.shape() != &shape {
+            let broadcast = |t: &Tensor| {
+                if t.shape() == &shape {
+                    Ok(t.clone())
+                } else {
+                    t.broadcast_as(&shape)
+                }
+            };
+            return broadcast(self)?.where_cond(&broadcast(on_true)?, &broadcast(on_false)?);
+        }
+        let _shap = self.same_shape_binary_op(on_true, "where_cond")?;
+        let shape = self.same_shape_binary_op(on_false, "where_cond")?;
+        let storage = self.storage().where_cond(
+            self.layout(),
+            &on_true.storage(),
+            on_true.layout(),
+            &on_false.storage(),
+            on_false.layout(),
+        )?;
+        let op = BackpropOp::new3(self, on_true, on_false, Op::WhereCond);
+        Ok(from_storage(storage, shape, op, false))
+    }
+
+    /// Returns a copy of `self` where the positions at which `mask` is not zero have been
+    /// replaced by `value`.
+    ///
+    /// The mask is broadcasted to the shape of `self`, it is usually obtained by a comparison
+    /// operation and so has dtype `u8`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let mask = Tensor::new(&[0u8, 1], &Device::Cpu)?;
+    /// let t = t.masked_fill(&mask, f32::NEG_INFINITY)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., f32::NEG_INFINITY], [3., f32::NEG_INFINITY]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn masked_fill<T: crate::WithDType>(&self, mask: &Self, value: T) -> Result<Self> {
+        let shape = self.shape();
+        let value = Tensor::new(value, self.device())?
+            .to_dtype(self.dtype())?
+            .broadcast_as(shape)?;
+        mask.broadcast_as(shape)?.where_cond(&value, self)
+    }
+
+    /// Returns a 1D tensor containing the elements of `self` at the positions where `mask` is not
+    /// zero, the mask is broadcasted to the shape of `self`.
+    ///
+    /// The number of selected elements depends on the mask content, so the mask has to be copied
+    /// to the host to compute the output shape.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let mask = t.gt(2.5)?;
+    /// assert_eq!(t.masked_select(&mask)?.to_vec1::<f32>()?, &[3., 4.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn masked_select(&self, mask: &Self) -> Result<Self> {
+        let mask = mask
+            .broadcast_as(self.shape())?
+            .ne(0u8)?
+            .flatten_all()?
+            .to_vec1::<u8>()?;
+        let indexes = mask
+            .iter()
+            .enumerate()
+            .filter(|(_, &m)| m != 0)
+            .map(|(i, _)| i as u32)
+            .collect::<Vec<_>>();
+        let n_indexes = indexes.len();
+        let indexes = Tensor::from_vec(indexes, n_indexes, self.device())?;
+        self.flatten_all()?.index_select(&indexes, 0)
+    }
+
+    /// Returns the coordinates of the non-zero elements of `self` as a `i64` tensor of shape
+    /// `(n, rank)` where `n` is the number of non-zero elements. The coordinates are returned in
+    /// row-major order.
+    ///
+    /// As the output shape depends on the tensor content, the values are copied to the host to
+    /// find the non-zero positions and the result is then moved back to the tensor device.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[0f32, 2.], [3., 0.]], &Device::Cpu)?;
+    /// assert_eq!(t.nonzero()?.to_vec2::<i64>()?, &[[0, 1], [1, 0]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn nonzero(&self) -> Result<Self> {
+        let dims = self.dims();
+        let rank = dims.len();
+        let mask = self.ne(0u8)?.flatten_all()?.to_vec1::<u8>()?;
+        let mut coords = Vec::new();
+        let mut n_nonzero = 0;
+        for (flat_index, _) in mask.iter().enumerate().filter(|(_, &m)| m != 0) {
+            let start = coords.len();
+            let mut flat_index = flat_index;
+            coords.resize(start + rank, 0i64);
+            for (coord, &dim) in coords[start..].iter_mut().zip(dims.iter()).rev() {
+                *coord = (flat_index % dim) as i64;
+                flat_index /= dim;
+            }
+            n_nonzero += 1;
+        }
+        Tensor::from_vec(coords, (n_nonzero, rank), self.device())
+    }
+
+    /// Counts the occurrences of each value in a 1D tensor of non-negative integers.
+    ///
+    /// The result has `max(self) + 1` elements, or `minlength` if this is larger. When `weights`
+    /// is set, it should have the same shape as `self` and the result contains the sum of the
+    /// weights for each value rather than the count, using the dtype of `weights`. Otherwise the
+    /// result is a `u32` tensor.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[1u32, 3, 1, 0, 3, 3], &Device::Cpu)?;
+    /// assert_eq!(t.bincount(None, 0)?.to_vec1::<u32>()?, &[1, 2, 0, 3]);
+    /// let w = Tensor::new(&[0.5f32, 1., 0.5, 2., 1., 1.], &Device::Cpu)?;
+    /// assert_eq!(t.bincount(Some(&w), 6)?.to_vec1::<f32>()?, &[2., 1., 0., 3., 0., 0.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn bincount(&self, weights: Option<&Self>, minlength: usize) -> Result<Self> {
+        let n = self.dims1()?;
+        if !self.dtype().is_int() {
+            bail!("bincount expects an integer tensor, got {:?}", self.dtype())
+        }
+        let len = if n == 0 {
+            minlength
+        } else {
+            let min = self.min(0)?.to_dtype(DType::I64)?.to_scalar::<i64>()?;
+            if min < 0 {
+                bail!("bincount expects non-negative values, got {min}")
+            }
+            let max = self.max(0)?.to_dtype(DType::I64)?.to_scalar::<i64>()?;
+            usize::max(max as usize + 1, minlength)
+        };
+        let weights = match weights {
+            Some(weights) => {
+                if weights.shape() != self.shape() {
+                    Err(Error::ShapeMismatchBinaryOp {
+                        lhs: self.shape().clone(),
+                        rhs: weights.shape().clone(),
+                        op: "bincount",
+                    }
+                    .bt())?
+                }
+                weights.clone()
+            }
+            None => Tensor::ones(n, DType::U32, self.device())?,
+        };
+        Tensor::zeros(len, weights.dtype(), self.device())?.index_add(self, &weights, 0)
     }
 
-    /// Matrix-multiplication with broadcasting support.
+    /// One-hot encodes a tensor of class indexes, the result has an additional last dimension
+    /// of size `num_classes` and uses `dtype`. The values outside of `0..num_classes`, e.g. `-1`
+    /// for `i64` tensors, result in a vector of zeros.
     ///
-    /// Compared to `matmul` the two matrixes are allowed to have different dimensions as long as
-    /// they are compatible for broadcast. E.g. if `self` has shape `(j, 1, n, k)` and `rhs` has
-    /// shape `(l, k, m)`, the output will have shape `(j, l, n, m)`.
-    pub fn broadcast_matmul(&self, rhs: &Self) -> Result<Self> {
-        let lhs = self;
-        let (l_shape, r_shape) = lhs.shape().broadcast_shape_matmul(rhs.shape())?;
-        let l_broadcast = l_shape != *lhs.shape();
-        let r_broadcast = r_shape != *rhs.shape();
-        // TODO: Avoid concretising the broadcasted matrixes via contiguous.
-        match (l_broadcast, r_broadcast) {
-            (true, true) => lhs
-                .broadcast_as(&l_shape)?
-                .contiguous()?
-                .matmul(&rhs.broadcast_as(&r_shape)?.contiguous()?),
-            (false, true) => lhs.matmul(&rhs.broadcast_as(&r_shape)?.contiguous()?),
-            (true, false) => lhs.broadcast_as(&l_shape)?.contiguous()?.matmul(rhs),
-            (false, false) => lhs.matmul(rhs),
-        }
+    /// ```rust
+    /// use candle_core::{DType, Tensor, Device};
+    /// let t = Tensor::new(&[2u32, 0, 1], &Device::Cpu)?;
+    /// let t = t.one_hot(3, DType::F32)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[0., 0., 1.], [1., 0., 0.], [0., 1., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn one_hot(&self, num_classes: usize, dtype: DType) -> Result<Self> {
+        let (xs, classes) = match self.dtype() {
+            DType::U8 | DType::U32 => (
+                self.to_dtype(DType::U32)?,
+                Tensor::arange(0u32, num_classes as u32, self.device())?,
+            ),
+            DType::I64 => (
+                self.clone(),
+                Tensor::arange(0i64, num_classes as i64, self.device())?,
+            ),
+            dtype => bail!("one_hot expects an integer tensor, got {dtype:?}"),
+        };
+        xs.unsqueeze(crate::D::Minus1)?
+            .broadcast_eq(&classes)?
+            .to_dtype(dtype)
     }
 
-    /// Returns a tensor with the same shape as the input tensor, the values are taken from
-    /// `on_true` if the input tensor value is not zero, and `on_false` at the positions where the
-    /// input tensor is equal to zero.
-    pub fn where_cond(&self, on_true: &Self, on_false: &Self) -> Result<Self> {
-        let _shap = self.same_shape_binary_op(on_true, "where_cond")?;
-        let shape = self.same_shape_binary_op(on_false, "where_cond")?;
-        let storage = self.storage().where_cond(
-            self.layout(),
-            &on_true.storage(),
-            on_true.layout(),
-            &on_false.storage(),
-            on_false.layout(),
-        )?;
-        let op = BackpropOp::new3(self, on_true, on_false, Op::WhereCond);
-        Ok(from_storage(storage, shape, op, false))
+    /// Computes the histogram of a tensor using `bins` bins of equal width between `min` and
+    /// `max`, the elements outside of this range are ignored. If `min` and `max` are equal, the
+    /// minimum and maximum of the tensor are used instead. The result has the same dtype as
+    /// `self`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[0.5f32, 1., 1.5, 2.5, 4., 5.], &Device::Cpu)?;
+    /// let hist = t.histc(4, 0., 4.)?;
+    /// assert_eq!(hist.to_vec1::<f32>()?, &[1., 2., 1., 1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn histc(&self, bins: usize, min: f64, max: f64) -> Result<Self> {
+        if bins == 0 {
+            bail!("histc expects at least one bin")
+        }
+        let xs = self.flatten_all()?;
+        let (mut min, mut max) = (min, max);
+        if min == max && xs.elem_count() > 0 {
+            min = xs.min(0)?.to_dtype(DType::F64)?.to_scalar::<f64>()?;
+            max = xs.max(0)?.to_dtype(DType::F64)?.to_scalar::<f64>()?;
+        }
+        if min == max {
+            min -= 1.;
+            max += 1.;
+        }
+        if min > max || !min.is_finite() || !max.is_finite() {
+            bail!("histc expects a finite range with min < max, got {min} {max}")
+        }
+        let xs = match xs.dtype() {
+            DType::F64 => xs,
+            _ => xs.to_dtype(DType::F32)?,
+        };
+        let in_range = xs.ge(min)?.mul(&xs.le(max)?)?;
+        // The values equal to max end up in the last bin.
+        let bin_ids = xs
+            .affine(bins as f64 / (max - min), -min * bins as f64 / (max - min))?
+            .floor()?
+            .clamp(0f32, (bins - 1) as f32)?
+            .to_dtype(DType::U32)?;
+        Tensor::zeros(bins, self.dtype(), self.device())?.index_add(
+            &bin_ids,
+            &in_range.to_dtype(self.dtype())?,
+            0,
+        )
     }
 
     /// Returns a tensor with the values from the `self` tensor at the index corresponding to the
@@ -1274,6 +2369,15 @@ impl Tensor {
         self.index_select(ids, 0)
     }
 
+    /// Accumulate the values from `source` into `self` at the positions given by `indexes` on
+    /// the specified dimension.
+    ///
+    /// `indexes` must have the same shape as `source`, and `source` must have the same shape as
+    /// `self` except on dimension `dim`. For a 2D tensor and `dim = 1`, this computes
+    /// `self[i][indexes[i][j]] += source[i][j]`.
+    ///
+    /// The accumulation order does not depend on thread scheduling (no atomic operations are used
+    /// on the gpu backends) so the results are deterministic.
     pub fn scatter_add<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<Self> {
         let dim = dim.to_index(self.shape(), "scatter-add")?;
         let source_dims = source.dims();
@@ -1390,7 +2494,134 @@ impl Tensor {
     }
 
     /// Accumulate element from `source` at indexes `indexes` and add them to `self`.
+    ///
+    /// `indexes` is a 1D tensor with as many elements as `source` has on dimension `dim`. As for
+    /// `scatter_add`, the results are deterministic on all backends.
     pub fn index_add<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<Self> {
+        let dim = self.index_add_check(indexes, source, dim)?;
+        let storage = self.storage().index_add(
+            self.layout(),
+            &indexes.storage(),
+            indexes.layout(),
+            &source.storage(),
+            source.layout(),
+            dim,
+        )?;
+        let op = BackpropOp::new3(self, indexes, source, |t1, t2, t3| {
+            Op::IndexAdd(t1, t2, t3, dim)
+        });
+        Ok(from_storage(storage, self.shape(), op, false))
+    }
+
+    /// Returns a copy of `self` where the slices at indexes `indexes` along dimension `dim` have
+    /// been replaced by the ones from `source`.
+    ///
+    /// This has the same arguments as `index_add` but the values are overwritten rather than
+    /// accumulated. When an index appears multiple times, the value from the last occurrence is
+    /// used on all backends.
+    pub fn index_set<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<Self> {
+        let dim = self.index_add_check(indexes, source, dim)?;
+        let storage = self.storage().index_set(
+            self.layout(),
+            &indexes.storage(),
+            indexes.layout(),
+            &source.storage(),
+            source.layout(),
+            dim,
+        )?;
+        let op = BackpropOp::new3(self, indexes, source, |t1, t2, t3| {
+            Op::IndexSet(t1, t2, t3, dim)
+        });
+        Ok(from_storage(storage, self.shape(), op, false))
+    }
+
+    /// In-place version of `index_add`, the values from `source` are accumulated directly in the
+    /// storage of `self` so only the rows selected by `indexes` are written to.
+    ///
+    /// As for the in-place binary operations like `add_assign_`, `self` has to be contiguous and
+    /// cannot result from the computation of a variable. On the cpu no other buffer is allocated,
+    /// on other devices the result is computed in a temporary buffer before being copied in place.
+    pub fn index_add_<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<()> {
+        let dim = self.index_add_check(indexes, source, dim)?;
+        self.index_update_(self.layout(), indexes, source, dim, true)
+    }
+
+    /// In-place version of `index_set`, the slices selected by `indexes` are overwritten directly
+    /// in the storage of `self`. This has the same requirements as [`Tensor::index_add_`].
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, DType};
+    /// let cache = Tensor::zeros((4, 2), DType::F32, &Device::Cpu)?;
+    /// let pos = Tensor::new(&[2u32], &Device::Cpu)?;
+    /// let kv = Tensor::new(&[[1f32, 2.]], &Device::Cpu)?;
+    /// cache.index_set_(&pos, &kv, 0)?;
+    /// assert_eq!(cache.to_vec2::<f32>()?, &[[0., 0.], [0., 0.], [1., 2.], [0., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn index_set_<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<()> {
+        let dim = self.index_add_check(indexes, source, dim)?;
+        self.index_update_(self.layout(), indexes, source, dim, false)
+    }
+
+    // Updates in place the storage of `self` viewed with the contiguous `layout`, the arguments
+    // have already been checked against this layout.
+    fn index_update_(
+        &self,
+        layout: &Layout,
+        indexes: &Self,
+        source: &Self,
+        dim: usize,
+        accumulate: bool,
+    ) -> Result<()> {
+        let op = if accumulate { "index-add" } else { "index-set" };
+        if self.op.is_some() {
+            bail!("{op}: cannot modify in place a tensor that is part of a compute graph")
+        }
+        if !self.is_contiguous() {
+            Err(Error::RequiresContiguous { op }.bt())?
+        }
+        if self.same_storage(source) || self.same_storage(indexes) {
+            let source = source.copy()?;
+            let indexes = indexes.copy()?;
+            return self.index_update_(layout, &indexes, &source, dim, accumulate);
+        }
+        let (mut storage, _) = self.storage_mut_and_layout();
+        let (ids_storage, ids_layout) = indexes.storage_and_layout();
+        let (src_storage, src_layout) = source.storage_and_layout();
+        if let (Storage::Cpu(dst), Storage::Cpu(ids), Storage::Cpu(src)) =
+            (&mut *storage, &*ids_storage, &*src_storage)
+        {
+            return if accumulate {
+                dst.index_add_inplace(layout, ids, ids_layout, src, src_layout, dim)
+            } else {
+                dst.index_set_inplace(layout, ids, ids_layout, src, src_layout, dim)
+            };
+        }
+        let result = if accumulate {
+            storage.index_add(
+                layout,
+                &ids_storage,
+                ids_layout,
+                &src_storage,
+                src_layout,
+                dim,
+            )?
+        } else {
+            storage.index_set(
+                layout,
+                &ids_storage,
+                ids_layout,
+                &src_storage,
+                src_layout,
+                dim,
+            )?
+        };
+        let src_l = Layout::contiguous(layout.shape());
+        result.copy_strided_src(&mut storage, layout.start_offset(), &src_l)
+    }
+
+    // Checks the shapes of the index-add arguments and returns the dimension index.
+    fn index_add_check<D: Dim>(&self, indexes: &Self, source: &Self, dim: D) -> Result<usize> {
         let dim = dim.to_index(self.shape(), "index-add")?;
         let source_dims = source.dims();
         let self_dims = self.dims();
@@ -1426,18 +2657,123 @@ impl Tensor {
             }
             .bt())?
         }
-        let storage = self.storage().index_add(
-            self.layout(),
-            &indexes.storage(),
-            indexes.layout(),
-            &source.storage(),
-            source.layout(),
-            dim,
-        )?;
-        let op = BackpropOp::new3(self, indexes, source, |t1, t2, t3| {
-            Op::IndexAdd(t1, t2, t3, dim)
-        });
-        Ok(from_storage(storage, self.shape(), op, false))
+        Ok(dim)
+    }
+
+    /// Returns a copy of `self` where the values at the positions given by `indexes` have been
+    /// replaced by `values`.
+    ///
+    /// `indexes` contains one integer tensor per leading dimension of `self`, these tensors are
+    /// broadcasted together and the position `(indexes[0][i], indexes[1][i], ...)` is updated
+    /// with `values[i]`. The remaining dimensions of `self` are copied over as a whole so `values`
+    /// must be broadcastable to the broadcasted index shape followed by these trailing dimensions.
+    ///
+    /// When `accumulate` is false the values are written over the existing ones and when an index
+    /// appears multiple times, the value of its last occurrence is used. When `accumulate` is true
+    /// the values are added to the existing ones and the values for duplicated indexes are summed.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, DType};
+    /// let t = Tensor::zeros((3, 3), DType::F32, &Device::Cpu)?;
+    /// let rows = Tensor::new(&[0u32, 2], &Device::Cpu)?;
+    /// let cols = Tensor::new(&[1u32, 0], &Device::Cpu)?;
+    /// let values = Tensor::new(&[5f32, 7.], &Device::Cpu)?;
+    /// let t = t.index_put(&[&rows, &cols], &values, false)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[0., 5., 0.], [0., 0., 0.], [7., 0., 0.]]);
+    /// let t = t.index_put(&[&rows, &rows], &values, true)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[5., 5., 0.], [0., 0., 0.], [7., 0., 7.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn index_put<A: AsRef<Tensor>>(
+        &self,
+        indexes: &[A],
+        values: &Self,
+        accumulate: bool,
+    ) -> Result<Self> {
+        let (offsets, values, flat_dims) = self.index_put_args(indexes, values)?;
+        let flat = self.reshape(flat_dims)?;
+        let flat = if accumulate {
+            flat.index_add(&offsets, &values, 0)?
+        } else {
+            flat.index_set(&offsets, &values, 0)?
+        };
+        flat.reshape(self.dims())
+    }
+
+    /// In-place version of [`Tensor::index_put`], only the positions given by `indexes` are
+    /// written to in the storage of `self`. This has the same requirements as
+    /// [`Tensor::index_add_`], e.g. it can be used to update a preallocated kv cache without
+    /// copying it.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, DType};
+    /// let t = Tensor::zeros((2, 3), DType::F32, &Device::Cpu)?;
+    /// let rows = Tensor::new(&[1u32, 0], &Device::Cpu)?;
+    /// let cols = Tensor::new(&[2u32, 1], &Device::Cpu)?;
+    /// t.index_put_(&[&rows, &cols], &Tensor::new(&[5f32, 7.], &Device::Cpu)?, false)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[0., 7., 0.], [0., 0., 5.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn index_put_<A: AsRef<Tensor>>(
+        &self,
+        indexes: &[A],
+        values: &Self,
+        accumulate: bool,
+    ) -> Result<()> {
+        let (offsets, values, flat_dims) = self.index_put_args(indexes, values)?;
+        let flat_layout = Layout::contiguous_with_offset(flat_dims, self.layout().start_offset());
+        let values = values.contiguous()?;
+        self.index_update_(&flat_layout, &offsets, &values, 0, accumulate)
+    }
+
+    // Returns the linear offsets, the values and the dimensions of `self` with its leading
+    // dimensions flattened so that `index_put` can be expressed as an index op on dimension 0.
+    fn index_put_args<A: AsRef<Tensor>>(
+        &self,
+        indexes: &[A],
+        values: &Self,
+    ) -> Result<(Self, Self, Vec<usize>)> {
+        let n_indexes = indexes.len();
+        if n_indexes == 0 || n_indexes > self.rank() {
+            bail!(
+                "index-put expects between 1 and {} index tensors, got {n_indexes}",
+                self.rank()
+            )
+        }
+        let dims = self.dims();
+        let (index_dims, tail_dims) = dims.split_at(n_indexes);
+        let mut index_shape = indexes[0].as_ref().shape().clone();
+        for index in indexes.iter().skip(1) {
+            index_shape =
+                index_shape.broadcast_shape_binary_op(index.as_ref().shape(), "index-put")?;
+        }
+        let index_dtype = match indexes[0].as_ref().dtype() {
+            DType::I64 => DType::I64,
+            _ => DType::U32,
+        };
+        // Compute the linear offsets in the flattened leading dimensions.
+        let mut offsets: Option<Tensor> = None;
+        for (index, &dim) in indexes.iter().zip(index_dims.iter()) {
+            let index = index
+                .as_ref()
+                .to_dtype(index_dtype)?
+                .broadcast_as(&index_shape)?;
+            offsets = Some(match offsets {
+                None => index,
+                Some(offsets) => (offsets.affine(dim as f64, 0.)? + index)?,
+            })
+        }
+        let offsets = match offsets {
+            None => bail!("index-put expects at least one index tensor"),
+            Some(offsets) => offsets.flatten_all()?,
+        };
+        let n_values = offsets.elem_count();
+        let flat_dims = [&[index_dims.iter().product()], tail_dims].concat();
+        let values_dims = [index_shape.dims(), tail_dims].concat();
+        let values = values
+            .broadcast_as(values_dims)?
+            .reshape([&[n_values], tail_dims].concat())?;
+        Ok((offsets, values, flat_dims))
     }
 
     /// Gather values across the target dimension.
@@ -1512,6 +2848,29 @@ impl Tensor {
         Ok(from_storage(storage, dims, op, false))
     }
 
+    /// Similar to `index_select` along the first dimension, but when `self` is a variable its
+    /// gradient only contains the selected rows and is returned by
+    /// [`GradStore::get_sparse`](crate::backprop::GradStore::get_sparse) rather than
+    /// [`GradStore::get`](crate::backprop::GradStore::get). This avoids materializing a dense
+    /// gradient for large embedding tables, the grad hooks are not called on sparse gradients.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Tensor, Var};
+    /// let emb = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.]], &Device::Cpu)?;
+    /// let ids = Tensor::new(&[2u32, 0, 2], &Device::Cpu)?;
+    /// let grads = emb.sparse_index_select(&ids)?.sum_all()?.backward()?;
+    /// assert!(grads.get(&emb).is_none());
+    /// let grad = grads.get_sparse(&emb).unwrap();
+    /// assert_eq!(grad.indices().to_vec1::<u32>()?, [2, 0, 2]);
+    /// assert_eq!(grad.to_dense()?.to_vec2::<f32>()?, [[1., 1.], [0., 0.], [2., 2.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn sparse_index_select(&self, indexes: &Self) -> Result<Self> {
+        let res = self.detach().index_select(indexes, 0)?;
+        let op = BackpropOp::new2(self, indexes, Op::SparseIndexSelect);
+        Ok(res.with_op(op, false))
+    }
+
     /// Returns an iterator over position of the elements in the storage when ranging over the
     /// index tuples in lexicographic order.
     pub fn strided_index(&self) -> crate::StridedIndex {
@@ -1548,6 +2907,7 @@ impl Tensor {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Custom(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -1579,6 +2939,7 @@ impl Tensor {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Custom(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -1620,6 +2981,7 @@ impl Tensor {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Custom(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -1680,6 +3042,15 @@ impl Tensor {
         self.is_variable
     }
 
+    /// Whether this tensor is a frozen variable, see [`Var::freeze`](crate::Var::freeze).
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_frozen(&self, frozen: bool) {
+        self.frozen.store(frozen, Ordering::Relaxed)
+    }
+
     pub(crate) fn op(&self) -> &Option<Op> {
         &self.op
     }
@@ -1839,6 +3210,7 @@ impl Tensor {
             layout: self.layout.transpose(dim1, dim2)?,
             op,
             is_variable: false,
+            frozen: AtomicBool::new(false),
             dtype: self.dtype,
             device: self.device.clone(),
         };
@@ -1875,6 +3247,7 @@ impl Tensor {
             layout: self.layout.permute(&dims)?,
             op,
             is_variable: false,
+            frozen: AtomicBool::new(false),
             dtype: self.dtype,
             device: self.device.clone(),
         };
@@ -1901,16 +3274,37 @@ impl Tensor {
             layout: self.layout.clone(),
             op,
             is_variable: false,
+            frozen: AtomicBool::new(false),
             dtype: self.dtype,
             device: self.device.clone(),
         };
         Ok(Tensor(Arc::new(tensor_)))
     }
 
+    // Returns a new tensor sharing its storage and layout with the current one, but using a
+    // different backprop op.
+    pub(crate) fn with_op(&self, op: BackpropOp, is_variable: bool) -> Tensor {
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: self.storage.clone(),
+            layout: self.layout.clone(),
+            op,
+            is_variable,
+            frozen: AtomicBool::new(false),
+            dtype: self.dtype,
+            device: self.device.clone(),
+        };
+        Tensor(Arc::new(tensor_))
+    }
+
     /// Returns a new tensor detached from the current graph, gradient are not propagated through
     /// this new node. The storage of this tensor is shared with the initial tensor.
     ///
     /// If the tensor is already detached from the computation graph, the same tensor is returned.
+    /// Otherwise the returned tensor never references the graph of the initial tensor, so the
+    /// graph can be released once the initial tensor is dropped and no backward pass ever
+    /// visits it from the detached tensor. The detached tensor is also never a variable. Use
+    /// [`Tensor::backward_stop_gradient_through`] to cut the graph for a single backward pass.
     pub fn detach(&self) -> Tensor {
         if self.op.is_none() && !self.is_variable {
             self.clone()
@@ -1921,6 +3315,7 @@ impl Tensor {
                 layout: self.layout.clone(),
                 op: BackpropOp::none(),
                 is_variable: false,
+                frozen: AtomicBool::new(false),
                 dtype: self.dtype,
                 device: self.device.clone(),
             };
@@ -1942,11 +3337,15 @@ impl Tensor {
                 }
                 (Storage::Cuda(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
                 (Storage::Metal(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
+                (Storage::Cpu(storage), Device::Custom(custom)) => {
+                    Storage::Custom(custom.storage_from_cpu_storage(storage)?)
+                }
+                (Storage::Custom(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
+                (Storage::Custom(storage), Device::Custom(custom)) => Storage::Custom(
+                    custom.storage_from_cpu_storage_owned(storage.to_cpu_storage()?)?,
+                ),
                 (Storage::Cuda(storage), Device::Cuda(cuda)) => {
-                    // TODO: Avoid passing through the cpu storage here, especially if the gpu ids
-                    // are the same.
-                    let cpu_storage = storage.to_cpu_storage()?;
-                    Storage::Cuda(cuda.storage_from_cpu_storage(&cpu_storage)?)
+                    Storage::Cuda(storage.transfer_to_device(cuda)?)
                 }
                 (Storage::Cpu(storage), Device::Cpu) => Storage::Cpu(storage.clone()),
                 _ => {
@@ -1960,6 +3359,7 @@ impl Tensor {
                 layout: self.layout.clone(),
                 op,
                 is_variable: false,
+                frozen: AtomicBool::new(false),
                 dtype: self.dtype,
                 device: device.clone(),
             };
@@ -1967,6 +3367,67 @@ impl Tensor {
         }
     }
 
+    /// Similar to [`Tensor::to_device`] but a copy from the cpu to a cuda device returns without
+    /// waiting for the transfer to complete, the data is staged in page-locked memory. The
+    /// operations using the returned tensor are queued after the copy so they see the transferred
+    /// values. Other transfers are synchronous.
+    pub fn to_device_async(&self, device: &Device) -> Result<Tensor> {
+        if !self.device().is_cpu() || !device.is_cuda() {
+            return self.to_device(device);
+        }
+        let storage = match (&*self.storage(), device) {
+            (Storage::Cpu(storage), Device::Cuda(cuda)) => {
+                Storage::Cuda(cuda.storage_from_cpu_storage_async(storage)?)
+            }
+            _ => unreachable!(),
+        };
+        let op = BackpropOp::new1(self, Op::ToDevice);
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: Arc::new(RwLock::new(storage)),
+            layout: self.layout.clone(),
+            op,
+            is_variable: false,
+            frozen: AtomicBool::new(false),
+            dtype: self.dtype,
+            device: device.clone(),
+        };
+        Ok(Tensor(Arc::new(tensor_)))
+    }
+
+    /// Copies a cpu tensor to the device of `stream`, the copy is queued on `stream` so that it
+    /// can overlap with the operations running on the default stream of the device.
+    /// [`crate::CudaStream::join`] has to be called before the returned tensor gets used.
+    pub fn to_device_with_stream(&self, stream: &crate::CudaStream) -> Result<Tensor> {
+        let storage = match &*self.storage() {
+            Storage::Cpu(storage) => Storage::Cuda(stream.storage_from_cpu_storage(storage)?),
+            _ => bail!(
+                "to_device_with_stream expects a cpu tensor, got {:?}",
+                self.device()
+            ),
+        };
+        let op = BackpropOp::new1(self, Op::ToDevice);
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: Arc::new(RwLock::new(storage)),
+            layout: self.layout.clone(),
+            op,
+            is_variable: false,
+            frozen: AtomicBool::new(false),
+            dtype: self.dtype,
+            device: Device::Cuda(stream.device().clone()),
+        };
+        let tensor = Tensor(Arc::new(tensor_));
+        stream.record_tensor(&tensor)?;
+        Ok(tensor)
+    }
+
+    /// Marks this tensor as being used by the work queued on `stream`, the tensor memory is kept
+    /// alive until the stream is joined or synchronized.
+    pub fn record_stream(&self, stream: &crate::CudaStream) -> Result<()> {
+        stream.record_tensor(self)
+    }
+
     /// Returns a new tensor duplicating data from the original tensor. New dimensions are inserted
     /// on the left.
     pub fn broadcast_left<S: Into<Shape>>(&self, left_shape: S) -> Result<Self> {
@@ -1990,6 +3451,7 @@ impl Tensor {
             layout: self.layout.broadcast_as(shape)?,
             op: BackpropOp::new1(self, Op::Broadcast),
             is_variable: false,
+            frozen: AtomicBool::new(false),
             dtype: self.dtype,
             device: self.device.clone(),
         };
@@ -2099,6 +3561,7 @@ impl Tensor {
                 layout: Layout::contiguous_with_offset(shape, self.layout.start_offset()),
                 op,
                 is_variable: false,
+                frozen: AtomicBool::new(false),
                 dtype: self.dtype,
                 device: self.device.clone(),
             };
@@ -2140,6 +3603,7 @@ impl Tensor {
                 layout: Layout::new(dims.into(), strides, self.layout.start_offset()),
                 op: BackpropOp::new1(self, Op::Reshape),
                 is_variable: false,
+                frozen: AtomicBool::new(false),
                 dtype: self.dtype,
                 device: self.device.clone(),
             };
@@ -2178,6 +3642,7 @@ impl Tensor {
             layout: Layout::new(dims.into(), strides, self.layout.start_offset()),
             op: BackpropOp::new1(self, Op::Reshape),
             is_variable: false,
+            frozen: AtomicBool::new(false),
             dtype: self.dtype,
             device: self.device.clone(),
         };
@@ -2280,6 +3745,53 @@ impl Tensor {
         }
     }
 
+    /// Pad the input tensor along dimension `dim` using the given `mode`. This adds `left`
+    /// elements before the input tensor values and `right` elements after. For the reflect mode,
+    /// both paddings have to be smaller than the size of the dimension and for the circular mode
+    /// they cannot be larger than it.
+    ///
+    /// ```rust
+    /// use candle_core::{PadMode, Tensor, Device};
+    /// let t = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// let p = t.pad(0, 2, 1, PadMode::Reflect)?;
+    /// assert_eq!(p.to_vec1::<f32>()?, &[3., 2., 1., 2., 3., 2.]);
+    /// let p = t.pad(0, 2, 1, PadMode::Circular)?;
+    /// assert_eq!(p.to_vec1::<f32>()?, &[2., 3., 1., 2., 3., 1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn pad<D: Dim>(&self, dim: D, left: usize, right: usize, mode: PadMode) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "pad")?;
+        let size = self.dim(dim)?;
+        match mode {
+            PadMode::Zeros => return self.pad_with_zeros(dim, left, right),
+            PadMode::Replicate => return self.pad_with_same(dim, left, right),
+            PadMode::Reflect if left >= size || right >= size => bail!(
+                "reflect padding ({left}, {right}) has to be smaller than the dim size {size}"
+            ),
+            PadMode::Circular if left > size || right > size => bail!(
+                "circular padding ({left}, {right}) cannot be larger than the dim size {size}"
+            ),
+            PadMode::Reflect | PadMode::Circular => {}
+        }
+        if left == 0 && right == 0 {
+            return Ok(self.clone());
+        }
+        let (size, left) = (size as i64, left as i64);
+        let indexes: Vec<u32> = (-left..size + right as i64)
+            .map(|index| {
+                let index = match mode {
+                    PadMode::Reflect if index < 0 => -index,
+                    PadMode::Reflect if index >= size => 2 * (size - 1) - index,
+                    _ => index.rem_euclid(size),
+                };
+                index as u32
+            })
+            .collect();
+        let n = indexes.len();
+        let indexes = Tensor::from_vec(indexes, n, self.device())?;
+        self.index_select(&indexes, dim)
+    }
+
     /// Run the `forward` method of `m` on `self`.
     pub fn apply<M: crate::Module>(&self, m: &M) -> Result<Self> {
         m.forward(self)
@@ -2382,6 +3894,36 @@ impl Tensor {
         }
     }
 
+    /// Returns the cumulative product of elements of the input tensor over the specified
+    /// dimension.
+    ///
+    /// The product is computed with a parallel scan using `log2(n)` element-wise multiplications,
+    /// so the operation stays on the tensor device and supports backpropagation.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &Device::Cpu)?;
+    /// let t = t.cumprod(1)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., 2., 6.], [4., 20., 120.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn cumprod<D: Dim>(&self, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "cumprod")?;
+        let n_axis = self.dim(dim)?;
+        let mut acc = self.clone();
+        let mut offset = 1;
+        while offset < n_axis {
+            let mut dims = self.dims().to_vec();
+            dims[dim] = offset;
+            let ones = Tensor::ones(dims, self.dtype(), self.device())?;
+            let shifted = acc.narrow(dim, 0, n_axis - offset)?;
+            let shifted = Tensor::cat(&[&ones, &shifted], dim)?;
+            acc = acc.mul(&shifted)?;
+            offset *= 2;
+        }
+        Ok(acc)
+    }
+
     /// Returns a copy of `self` where the values within `ranges` have been replaced with the
     /// content of `src`.
     pub fn slice_assign<D: std::ops::RangeBounds<usize>>(
@@ -2438,11 +3980,102 @@ impl Tensor {
         mask.where_cond(/* on_true= */ &src, /* on_false= */ self)
     }
 
+    /// Returns log(sum(exp(tensor), dim)), the summed dimensions are kept with a size of 1.
+    ///
+    /// The maximum over the summed dimensions is subtracted before taking the exponential so
+    /// that large values do not overflow.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1000f32, 1000.], [-1000., -1000.]], &Device::Cpu)?;
+    /// let lse = a.log_sum_exp_keepdim(1)?;
+    /// assert_eq!(lse.to_vec2::<f32>()?, &[[1000.6932], [-999.3068]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn log_sum_exp_keepdim<D: Dims>(&self, sum_dims: D) -> Result<Self> {
+        let sum_dims = sum_dims.to_indexes(self.shape(), "log-sum-exp")?;
+        let mut max = self.detach();
+        for &dim in sum_dims.iter() {
+            max = max.max_keepdim(dim)?
+        }
+        // When all the values are infinite, the max is not subtracted to avoid getting nans.
+        let is_finite = max.abs()?.ne(f64::INFINITY)?;
+        let max = is_finite.where_cond(&max, &max.zeros_like()?)?;
+        let sum = self.broadcast_sub(&max)?.exp()?.sum_keepdim(sum_dims)?;
+        sum.log()? + max
+    }
+
     /// Returns log(sum(exp(tensor), dim)).
+    ///
+    /// The maximum over the summed dimensions is subtracted before taking the exponential so
+    /// that large values do not overflow.
     pub fn log_sum_exp<D: Dims>(&self, sum_dims: D) -> Result<Self> {
-        let exp = self.exp()?;
-        let sum = exp.sum(sum_dims)?;
-        sum.log()
+        let sum_dims = sum_dims.to_indexes(self.shape(), "log-sum-exp")?;
+        self.log_sum_exp_keepdim(sum_dims.as_slice())?
+            .squeeze_dims(&sum_dims)
+    }
+
+    /// Returns the `p`-norm of the values over the dimensions `dims`, these dimensions are kept
+    /// with a size of 1.
+    ///
+    /// `p` can be any positive value, as well as `f64::INFINITY` for the maximum absolute value,
+    /// `f64::NEG_INFINITY` for the minimum absolute value, and 0 for the number of non-zero
+    /// values. The Frobenius norm of a batch of matrices is the 2-norm over the last two
+    /// dimensions.
+    pub fn norm_keepdim<D: Dims>(&self, p: f64, dims: D) -> Result<Self> {
+        let dims = dims.to_indexes(self.shape(), "norm")?;
+        let abs = self.abs()?;
+        if p.is_infinite() {
+            let mut res = abs;
+            for &dim in dims.iter() {
+                res = if p > 0. {
+                    res.max_keepdim(dim)?
+                } else {
+                    res.min_keepdim(dim)?
+                }
+            }
+            return Ok(res);
+        }
+        match p {
+            0. => self.ne(0f64)?.to_dtype(self.dtype())?.sum_keepdim(dims),
+            1. => abs.sum_keepdim(dims),
+            2. => self.sqr()?.sum_keepdim(dims)?.sqrt(),
+            p if p > 0. => abs.powf(p)?.sum_keepdim(dims)?.powf(1. / p),
+            p => bail!("norm expects a non-negative p, got {p}"),
+        }
+    }
+
+    /// Returns the `p`-norm of the values over the dimensions `dims`, see
+    /// [`Tensor::norm_keepdim`] for the supported values of `p`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f32, -4.], [0., 1.]], &Device::Cpu)?;
+    /// assert_eq!(a.norm(2., 1)?.to_vec1::<f32>()?, &[5., 1.]);
+    /// assert_eq!(a.norm(1., 1)?.to_vec1::<f32>()?, &[7., 1.]);
+    /// assert_eq!(a.norm(f64::INFINITY, 0)?.to_vec1::<f32>()?, &[3., 4.]);
+    /// assert_eq!(a.norm(2., (0, 1))?.to_scalar::<f32>()?, 26f32.sqrt());
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn norm<D: Dims>(&self, p: f64, dims: D) -> Result<Self> {
+        let dims = dims.to_indexes(self.shape(), "norm")?;
+        self.norm_keepdim(p, dims.as_slice())?.squeeze_dims(&dims)
+    }
+
+    /// Divides the values by their `p`-norm over dimension `dim`, the norm is clamped to be at
+    /// least `eps` to avoid dividing by zero.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f32, -4.], [0., 0.]], &Device::Cpu)?;
+    /// let n = a.normalize(2., 1, 1e-12)?;
+    /// assert_eq!(n.to_vec2::<f32>()?, &[[0.6, -0.8], [0., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn normalize<D: Dim>(&self, p: f64, dim: D, eps: f64) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "normalize")?;
+        let norm = self.norm_keepdim(p, dim)?.maximum(eps)?;
+        self.broadcast_div(&norm)
     }
 
     /// Pointwise pow operation.
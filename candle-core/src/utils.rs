@@ -1,6 +1,51 @@
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
+// The number of threads set via `set_num_threads`, 0 when not set.
+static NUM_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // The number of threads set via `with_num_threads` for the current thread, 0 when not set.
+    static LOCAL_NUM_THREADS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// The thread pool set via `set_thread_pool`.
+static THREAD_POOL: RwLock<Option<Arc<rayon::ThreadPool>>> = RwLock::new(None);
+
+// The thread pools used when the number of threads has been set with their size, the most
+// recently used pool comes last. At most `MAX_THREAD_POOLS` pools are kept, the others are
+// dropped which stops their threads once they are not in use anymore.
+static THREAD_POOLS: Mutex<Vec<(usize, Arc<rayon::ThreadPool>)>> = Mutex::new(Vec::new());
+const MAX_THREAD_POOLS: usize = 4;
+
+enum ThreadPoolOverride {
+    NumThreads(usize),
+    Pool(Arc<rayon::ThreadPool>),
+}
+
+fn thread_pool_override() -> Option<ThreadPoolOverride> {
+    match LOCAL_NUM_THREADS.with(|n| n.get()) {
+        0 => {
+            if let Some(pool) = THREAD_POOL.read().unwrap().as_ref() {
+                return Some(ThreadPoolOverride::Pool(pool.clone()));
+            }
+            match NUM_THREADS.load(Ordering::Relaxed) {
+                0 => None,
+                n => Some(ThreadPoolOverride::NumThreads(n)),
+            }
+        }
+        n => Some(ThreadPoolOverride::NumThreads(n)),
+    }
+}
+
+/// Returns the number of threads used by the cpu backend.
 pub fn get_num_threads() -> usize {
+    match thread_pool_override() {
+        Some(ThreadPoolOverride::NumThreads(n)) => return n,
+        Some(ThreadPoolOverride::Pool(pool)) => return pool.current_num_threads(),
+        None => {}
+    }
     // Respond to the same environment variable as rayon.
     match std::env::var("RAYON_NUM_THREADS")
         .ok()
@@ -11,6 +56,150 @@ pub fn get_num_threads() -> usize {
     }
 }
 
+/// Sets the number of threads used by the cpu backend, this takes precedence over the
+/// `RAYON_NUM_THREADS` environment variable. The operations then run on a dedicated thread pool
+/// rather than on the global rayon pool, using 0 reverts to the global pool.
+pub fn set_num_threads(n: usize) {
+    NUM_THREADS.store(n, Ordering::Relaxed)
+}
+
+/// Sets the thread pool on which the cpu backend runs its operations, this takes precedence over
+/// [`set_num_threads`]. Using `None` reverts to the default pool.
+pub fn set_thread_pool(pool: Option<Arc<rayon::ThreadPool>>) {
+    *THREAD_POOL.write().unwrap() = pool
+}
+
+/// Runs `f` with the cpu operations from the current thread using `n` threads, this takes
+/// precedence over [`set_num_threads`] and [`set_thread_pool`]. The thread pools are cached for
+/// the last few distinct values of `n`, using more values than that results in the pools being
+/// rebuilt.
+pub fn with_num_threads<R, F: FnOnce() -> R>(n: usize, f: F) -> R {
+    struct Reset(usize);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            LOCAL_NUM_THREADS.with(|n| n.set(self.0))
+        }
+    }
+    let _reset = Reset(LOCAL_NUM_THREADS.with(|prev| prev.replace(n)));
+    f()
+}
+
+/// Runs `f` on the thread pool used by the cpu backend, this is the global rayon pool unless
+/// [`set_num_threads`], [`set_thread_pool`] or [`with_num_threads`] have been used. Custom ops
+/// with a parallel cpu implementation can use it to honor these settings.
+pub fn install<R: Send, F: FnOnce() -> R + Send>(f: F) -> R {
+    let n = match thread_pool_override() {
+        None => return f(),
+        Some(ThreadPoolOverride::Pool(pool)) => return pool.install(f),
+        Some(ThreadPoolOverride::NumThreads(n)) => n,
+    };
+    let pool = {
+        let mut pools = THREAD_POOLS.lock().unwrap();
+        match pools.iter().position(|(size, _)| *size == n) {
+            Some(idx) => {
+                let entry = pools.remove(idx);
+                let pool = entry.1.clone();
+                pools.push(entry);
+                Some(pool)
+            }
+            None => {
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(n).build();
+                pool.ok().map(|pool| {
+                    let pool = Arc::new(pool);
+                    if pools.len() >= MAX_THREAD_POOLS {
+                        pools.remove(0);
+                    }
+                    pools.push((n, pool.clone()));
+                    pool
+                })
+            }
+        }
+    };
+    match pool {
+        Some(pool) => pool.install(f),
+        // Fall back on the global pool if the threads could not be spawned.
+        None => f(),
+    }
+}
+
+/// The accumulation precision used by the matmuls on cuda devices, the cpu backend always
+/// accumulates in f32.
+///
+/// The default is to accumulate in f32 for all dtypes and not to use tf32, similar to pytorch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatmulPrecision {
+    /// Whether f16 matmuls can accumulate in f16.
+    pub reduced_f16: bool,
+    /// Whether bf16 matmuls can use reduced precision reductions.
+    pub reduced_bf16: bool,
+    /// Whether f32 matmuls can use tf32.
+    pub tf32: bool,
+}
+
+impl MatmulPrecision {
+    /// The fastest settings, all the reduced precision modes are allowed.
+    pub const FAST: Self = Self {
+        reduced_f16: true,
+        reduced_bf16: true,
+        tf32: true,
+    };
+
+    fn to_bits(self) -> u8 {
+        self.reduced_f16 as u8 | (self.reduced_bf16 as u8) << 1 | (self.tf32 as u8) << 2
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            reduced_f16: bits & 1 != 0,
+            reduced_bf16: bits & 2 != 0,
+            tf32: bits & 4 != 0,
+        }
+    }
+}
+
+// The matmul precision set via `set_matmul_precision`, see `MatmulPrecision::to_bits`.
+static MATMUL_PRECISION: AtomicU8 = AtomicU8::new(0);
+
+thread_local! {
+    // The precision set via `with_matmul_precision` for the current thread.
+    static LOCAL_MATMUL_PRECISION: std::cell::Cell<Option<MatmulPrecision>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Returns the matmul precision used by the current thread.
+pub fn matmul_precision() -> MatmulPrecision {
+    LOCAL_MATMUL_PRECISION
+        .with(|p| p.get())
+        .unwrap_or_else(|| MatmulPrecision::from_bits(MATMUL_PRECISION.load(Ordering::Relaxed)))
+}
+
+/// Sets the matmul precision for all the threads.
+pub fn set_matmul_precision(p: MatmulPrecision) {
+    MATMUL_PRECISION.store(p.to_bits(), Ordering::Relaxed)
+}
+
+#[cfg(feature = "cuda")]
+pub(crate) fn update_matmul_precision<F: Fn(&mut MatmulPrecision)>(f: F) {
+    let _ = MATMUL_PRECISION.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        let mut p = MatmulPrecision::from_bits(bits);
+        f(&mut p);
+        Some(p.to_bits())
+    });
+}
+
+/// Runs `f` with the matmuls from the current thread using precision `p`, this takes precedence
+/// over [`set_matmul_precision`].
+pub fn with_matmul_precision<R, F: FnOnce() -> R>(p: MatmulPrecision, f: F) -> R {
+    struct Reset(Option<MatmulPrecision>);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            LOCAL_MATMUL_PRECISION.with(|p| p.set(self.0))
+        }
+    }
+    let _reset = Reset(LOCAL_MATMUL_PRECISION.with(|prev| prev.replace(Some(p))));
+    f()
+}
+
 pub fn has_accelerate() -> bool {
     cfg!(feature = "accelerate")
 }
@@ -42,3 +231,21 @@ pub fn with_simd128() -> bool {
 pub fn with_f16c() -> bool {
     cfg!(target_feature = "f16c")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_pools_are_bounded() {
+        for n in 1..=2 * MAX_THREAD_POOLS {
+            let num_threads = with_num_threads(n, || install(rayon::current_num_threads));
+            assert_eq!(num_threads, n);
+            assert!(THREAD_POOLS.lock().unwrap().len() <= MAX_THREAD_POOLS);
+        }
+        // The most recently used pool is kept.
+        with_num_threads(1, || install(|| ()));
+        let pools = THREAD_POOLS.lock().unwrap();
+        assert!(pools.iter().any(|(n, _)| *n == 1));
+    }
+}
@@ -15,6 +15,12 @@ impl std::fmt::Display for Var {
     }
 }
 
+impl AsRef<Tensor> for Var {
+    fn as_ref(&self) -> &Tensor {
+        &self.0
+    }
+}
+
 impl std::ops::Deref for Var {
     type Target = Tensor;
 
@@ -34,6 +40,29 @@ impl Var {
         Ok(Self(inner))
     }
 
+    /// Marks the variable as non-trainable: the operations using it are not tracked anymore and
+    /// backpropagation does not return a gradient for it, so optimizers leave it unchanged. This
+    /// applies to all the clones of the variable, e.g. the ones held by a `VarMap`.
+    ///
+    /// ```rust
+    /// use candle_core::{Device, Var};
+    /// let w = Var::new(&[2f32], &Device::Cpu)?;
+    /// let b = Var::new(&[1f32], &Device::Cpu)?;
+    /// w.freeze();
+    /// let grads = w.mul(&b)?.sum_all()?.backward()?;
+    /// assert!(grads.get(&w).is_none());
+    /// assert_eq!(grads.get(&b).unwrap().to_vec1::<f32>()?, [2.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn freeze(&self) {
+        self.0.set_frozen(true)
+    }
+
+    /// Marks a frozen variable as trainable again.
+    pub fn unfreeze(&self) {
+        self.0.set_frozen(false)
+    }
+
     // Convert a tensor to a variable, if the tensor is already a variable then it is returned as is.
     pub fn from_tensor(t: &Tensor) -> Result<Self> {
         if t.is_variable() {
@@ -353,6 +353,62 @@ fn conv2d_non_square(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+// conv_transpose2d is the adjoint of conv2d, so the gradients of <conv_transpose2d(x, w), y>
+// match the ones of <x, conv2d(y, w)> for which the backward pass uses different kernels.
+fn conv_transpose2d_grad(dev: &Device) -> Result<()> {
+    use candle_core::Var;
+    let t = Var::from_tensor(
+        &Tensor::arange(0f32, 120., dev)?
+            .sin()?
+            .reshape((2, 3, 4, 5))?,
+    )?;
+    let w = Var::from_tensor(
+        &Tensor::arange(0f32, 36., dev)?
+            .cos()?
+            .reshape((3, 2, 3, 2))?,
+    )?;
+    for (padding, stride, dilation) in [(1, 2, 1), (2, 2, 2), (0, 3, 1), (1, 1, 2)] {
+        let res = t.conv_transpose2d(&w, padding, 1, stride, dilation)?;
+        let y = Tensor::arange(0f32, res.elem_count() as f32, dev)?
+            .cos()?
+            .reshape(res.shape())?;
+        let grads = (res * &y)?.sum_all()?.backward()?;
+        // The output padding can add positions that do not correspond to any input.
+        let conv = y
+            .conv2d(&w, padding, stride, dilation, 1)?
+            .narrow(2, 0, 4)?
+            .narrow(3, 0, 5)?;
+        let expected = conv.mul(&t)?.sum_all()?.backward()?;
+        for var in [&t, &w] {
+            let grad = grads.get(var).unwrap().flatten_all()?;
+            let expected = expected.get(var).unwrap().flatten_all()?;
+            assert_eq!(
+                test_utils::to_vec1_round(&grad, 3)?,
+                test_utils::to_vec1_round(&expected, 3)?
+            );
+        }
+    }
+    Ok(())
+}
+
+fn conv_non_contiguous_kernel(dev: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 24., dev)?.reshape((1, 3, 8))?;
+    let w = Tensor::arange(0f32, 18., dev)?.reshape((3, 2, 3))?;
+    let res = t.conv1d(&w.transpose(0, 1)?, 1, 2, 1, 1)?;
+    let expected = t.conv1d(&w.transpose(0, 1)?.contiguous()?, 1, 2, 1, 1)?;
+    assert_eq!(res.to_vec3::<f32>()?, expected.to_vec3::<f32>()?);
+
+    let t = t.reshape((1, 3, 2, 4))?;
+    let w = w.reshape((3, 2, 1, 3))?;
+    let res = t.conv2d(&w.transpose(0, 1)?, 1, 2, 1, 1)?;
+    let expected = t.conv2d(&w.transpose(0, 1)?.contiguous()?, 1, 2, 1, 1)?;
+    assert_eq!(
+        res.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
 /*
 import torch
 torch.manual_seed(4242)
@@ -383,6 +439,81 @@ print(t.grad[0])
 print(w.grad.shape)
 print(w.grad[0])
 */
+fn conv_depthwise(dev: &Device) -> Result<()> {
+    use candle_core::Var;
+    // Reference implementation convolving each output channel separately.
+    fn per_channel(t: &Tensor, w: &Tensor, p: usize, s: usize, d: usize) -> Result<Tensor> {
+        let (c_in, c_out) = (t.dim(1)?, w.dim(0)?);
+        let ys = (0..c_out)
+            .map(|c| {
+                let t = t.narrow(1, c / (c_out / c_in), 1)?;
+                match t.rank() {
+                    3 => t.conv1d(&w.narrow(0, c, 1)?, p, s, d, 1),
+                    _ => t.conv2d(&w.narrow(0, c, 1)?, p, s, d, 1),
+                }
+            })
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        Ok(Tensor::cat(&ys, 1)?)
+    }
+    let t = Var::from_tensor(
+        &Tensor::arange(0f32, 240., dev)?
+            .sin()?
+            .reshape((2, 4, 5, 6))?,
+    )?;
+    let t1 = Var::from_tensor(&Tensor::arange(0f32, 56., dev)?.cos()?.reshape((2, 4, 7))?)?;
+    for mult in [1, 2] {
+        let w = Var::from_tensor(
+            &Tensor::arange(0f32, 36. * mult as f32, dev)?
+                .cos()?
+                .reshape((4 * mult, 1, 3, 3))?,
+        )?;
+        let w1 = Var::from_tensor(&w.narrow(2, 1, 1)?.squeeze(2)?)?;
+        for (padding, stride, dilation) in [(0, 1, 1), (1, 1, 1), (1, 2, 1), (2, 1, 2), (3, 3, 2)] {
+            for (t, w) in [(&t, &w), (&t1, &w1)] {
+                let res = match t.rank() {
+                    3 => t.conv1d(w, padding, stride, dilation, 4)?,
+                    _ => t.conv2d(w, padding, stride, dilation, 4)?,
+                };
+                let expected = per_channel(t, w, padding, stride, dilation)?;
+                assert_eq!(res.dims(), expected.dims());
+                assert_eq!(
+                    test_utils::to_vec1_round(&res.flatten_all()?, 3)?,
+                    test_utils::to_vec1_round(&expected.flatten_all()?, 3)?
+                );
+                let y = Tensor::arange(0f32, res.elem_count() as f32, dev)?
+                    .sin()?
+                    .reshape(res.shape())?;
+                let grads = res.mul(&y)?.sum_all()?.backward()?;
+                let expected = expected.mul(&y)?.sum_all()?.backward()?;
+                for var in [t, w] {
+                    let grad = grads.get(var).unwrap().flatten_all()?;
+                    let expected = expected.get(var).unwrap().flatten_all()?;
+                    assert_eq!(
+                        test_utils::to_vec1_round(&grad, 2)?,
+                        test_utils::to_vec1_round(&expected, 2)?
+                    );
+                }
+            }
+        }
+    }
+    // Non-contiguous inputs.
+    let w = Tensor::arange(0f32, 36., dev)?.reshape((4, 1, 3, 3))?;
+    let tt = t.transpose(2, 3)?;
+    let res = tt.conv2d(&w.transpose(2, 3)?, 1, 1, 1, 4)?;
+    let expected = per_channel(
+        &tt.contiguous()?,
+        &w.transpose(2, 3)?.contiguous()?,
+        1,
+        1,
+        1,
+    )?;
+    assert_eq!(
+        test_utils::to_vec1_round(&res.flatten_all()?, 3)?,
+        test_utils::to_vec1_round(&expected.flatten_all()?, 3)?
+    );
+    Ok(())
+}
+
 fn conv2d_grad(dev: &Device) -> Result<()> {
     // conv-transposes are not implemented for metal
     use candle_core::Var;
@@ -733,6 +864,155 @@ fn conv2d_grad(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+// The loss <conv2d(x, w), y> is linear in x so each entry of the gradient with respect to x is
+// the loss obtained for the matching one-hot input. Using a non-square input and kernel with
+// strides > 1 exercises the output padding that differs between the height and width.
+fn conv2d_grad_non_square(dev: &Device) -> Result<()> {
+    use candle_core::Var;
+    let (c_in, i_h, i_w) = (2, 7, 6);
+    let t = Var::from_tensor(
+        &Tensor::arange(0f32, (c_in * i_h * i_w) as f32, dev)?
+            .sin()?
+            .reshape((1, c_in, i_h, i_w))?,
+    )?;
+    let w = Tensor::arange(0f32, 18., dev)?
+        .cos()?
+        .reshape((3, c_in, 3, 1))?;
+    for (padding, stride, dilation) in [(0, 2, 1), (1, 2, 1), (1, 3, 1), (0, 2, 2)] {
+        let res = t.conv2d(&w, padding, stride, dilation, 1)?;
+        let y = Tensor::arange(0f32, res.elem_count() as f32, dev)?
+            .cos()?
+            .reshape(res.shape())?;
+        let grads = res.mul(&y)?.sum_all()?.backward()?;
+        let grad = grads.get(&t).unwrap();
+        assert_eq!(grad.dims(), [1, c_in, i_h, i_w]);
+        let mut expected = Vec::with_capacity(t.elem_count());
+        for idx in 0..t.elem_count() {
+            let one_hot = Tensor::arange(0u32, t.elem_count() as u32, dev)?
+                .eq(idx as u32)?
+                .to_dtype(candle_core::DType::F32)?
+                .reshape(t.shape())?;
+            let loss = one_hot
+                .conv2d(&w, padding, stride, dilation, 1)?
+                .mul(&y)?
+                .sum_all()?;
+            expected.push(loss);
+        }
+        let expected = Tensor::stack(&expected, 0)?;
+        assert_eq!(
+            test_utils::to_vec1_round(&grad.flatten_all()?, 3)?,
+            test_utils::to_vec1_round(&expected, 3)?
+        );
+    }
+    Ok(())
+}
+
+// A direct 3D convolution, used as the reference for the conv3d tests.
+#[allow(clippy::too_many_arguments)]
+fn naive_conv3d(
+    xs: &[f32],
+    (b, c_in, d, h, w): (usize, usize, usize, usize, usize),
+    ws: &[f32],
+    (c_out, k_d, k_h, k_w): (usize, usize, usize, usize),
+    padding: usize,
+    stride: usize,
+    dilation: usize,
+    groups: usize,
+) -> (Vec<f32>, Vec<usize>) {
+    let out = |size: usize, k: usize| (size + 2 * padding - dilation * (k - 1) - 1) / stride + 1;
+    let (o_d, o_h, o_w) = (out(d, k_d), out(h, k_h), out(w, k_w));
+    let (c_in_g, c_out_g) = (c_in / groups, c_out / groups);
+    let mut ys = vec![0f32; b * c_out * o_d * o_h * o_w];
+    for bi in 0..b {
+        for co in 0..c_out {
+            let g = co / c_out_g;
+            for od in 0..o_d {
+                for oh in 0..o_h {
+                    for ow in 0..o_w {
+                        let mut acc = 0f32;
+                        for ci in 0..c_in_g {
+                            for kd in 0..k_d {
+                                for kh in 0..k_h {
+                                    for kw in 0..k_w {
+                                        let id = (od * stride + kd * dilation) as isize
+                                            - padding as isize;
+                                        let ih = (oh * stride + kh * dilation) as isize
+                                            - padding as isize;
+                                        let iw = (ow * stride + kw * dilation) as isize
+                                            - padding as isize;
+                                        if id < 0
+                                            || ih < 0
+                                            || iw < 0
+                                            || id as usize >= d
+                                            || ih as usize >= h
+                                            || iw as usize >= w
+                                        {
+                                            continue;
+                                        }
+                                        let (id, ih, iw) = (id as usize, ih as usize, iw as usize);
+                                        let c = g * c_in_g + ci;
+                                        let x = xs[(((bi * c_in + c) * d + id) * h + ih) * w + iw];
+                                        let k = ws[(((co * c_in_g + ci) * k_d + kd) * k_h + kh)
+                                            * k_w
+                                            + kw];
+                                        acc += x * k
+                                    }
+                                }
+                            }
+                        }
+                        ys[(((bi * c_out + co) * o_d + od) * o_h + oh) * o_w + ow] = acc
+                    }
+                }
+            }
+        }
+    }
+    (ys, vec![b, c_out, o_d, o_h, o_w])
+}
+
+fn conv3d(dev: &Device) -> Result<()> {
+    let x_dims = (2, 4, 5, 4, 6);
+    let w_dims = (6, 3, 2, 3);
+    let xs: Vec<f32> = (0..2 * 4 * 5 * 4 * 6)
+        .map(|i| (i as f32 * 0.37).sin())
+        .collect();
+    let t = Tensor::from_vec(xs.clone(), x_dims, dev)?;
+    for (padding, stride, dilation, groups) in [(0, 1, 1, 1), (1, 2, 1, 2), (1, 1, 2, 1)] {
+        let c_in_g = 4 / groups;
+        let ws: Vec<f32> = (0..6 * c_in_g * 3 * 2 * 3)
+            .map(|i| (i as f32 * 0.61).cos())
+            .collect();
+        let w = Tensor::from_vec(ws.clone(), (6, c_in_g, 3, 2, 3), dev)?;
+        let res = t.conv3d(&w, padding, stride, dilation, groups)?;
+        let (expected, dims) =
+            naive_conv3d(&xs, x_dims, &ws, w_dims, padding, stride, dilation, groups);
+        assert_eq!(res.dims(), dims);
+        let res = res.flatten_all()?.to_vec1::<f32>()?;
+        for (r, e) in res.iter().zip(expected.iter()) {
+            assert!(
+                (r - e).abs() < 1e-4,
+                "{padding} {stride} {dilation} {groups}: {r} {e}"
+            )
+        }
+    }
+
+    // The gradient of the sum with respect to the kernel is the sum of the matching input values.
+    let t = candle_core::Var::from_tensor(&t.narrow(1, 0, 1)?.narrow(0, 0, 1)?)?;
+    let w = candle_core::Var::ones((1, 1, 2, 1, 1), candle_core::DType::F32, dev)?;
+    let res = t.conv3d(&w, 0, 1, 1, 1)?;
+    let grads = res.sum_all()?.backward()?;
+    let grad_w = grads.get(&w).unwrap().flatten_all()?.to_vec1::<f32>()?;
+    let first = t.narrow(2, 0, 4)?.sum_all()?.to_scalar::<f32>()?;
+    let last = t.narrow(2, 1, 4)?.sum_all()?.to_scalar::<f32>()?;
+    assert!((grad_w[0] - first).abs() < 1e-4 && (grad_w[1] - last).abs() < 1e-4);
+    let grad_t = grads
+        .get(&t)
+        .unwrap()
+        .i((0, 0, .., 0, 0))?
+        .to_vec1::<f32>()?;
+    assert_eq!(grad_t, [1., 2., 2., 2., 1.]);
+    Ok(())
+}
+
 test_device!(conv1d, conv1d_cpu, conv1d_gpu, conv1d_metal);
 test_device!(
     conv1d_small,
@@ -759,9 +1039,34 @@ test_device!(
     conv2d_smaller_gpu,
     conv2d_smaller_metal
 );
+test_device!(
+    conv_transpose2d_grad,
+    conv_transpose2d_grad_cpu,
+    conv_transpose2d_grad_gpu,
+    conv_transpose2d_grad_metal
+);
+test_device!(
+    conv_non_contiguous_kernel,
+    conv_non_contiguous_kernel_cpu,
+    conv_non_contiguous_kernel_gpu,
+    conv_non_contiguous_kernel_metal
+);
 test_device!(
     conv2d_grad,
     conv2d_grad_cpu,
     conv2d_grad_gpu,
     conv2_grad_metal
 );
+test_device!(
+    conv2d_grad_non_square,
+    conv2d_grad_non_square_cpu,
+    conv2d_grad_non_square_gpu,
+    conv2d_grad_non_square_metal
+);
+test_device!(
+    conv_depthwise,
+    conv_depthwise_cpu,
+    conv_depthwise_gpu,
+    conv_depthwise_metal
+);
+test_device!(conv3d, conv3d_cpu, conv3d_gpu, conv3d_metal);
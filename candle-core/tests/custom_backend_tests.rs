@@ -0,0 +1,335 @@
+use candle::backend::{BackendDevice, BackendStorage};
+use candle::conv::{ParamsConv1D, ParamsConv2D, ParamsConvTranspose1D, ParamsConvTranspose2D};
+use candle::cpu_backend::CpuDevice;
+use candle::custom_backend::{CustomBackendDevice, CustomBackendStorage, DynStorage};
+use candle::op::{CmpOp, ReduceOp};
+use candle::{bail, CpuStorage, DType, Device, Layout, Result, Shape, Tensor};
+use candle_core as candle;
+use std::any::Any;
+
+// A custom device that stores its data on the cpu.
+#[derive(Debug)]
+struct TestDevice;
+
+#[derive(Debug)]
+struct TestStorage(CpuStorage);
+
+fn wrap(storage: Result<CpuStorage>) -> Result<DynStorage> {
+    Ok(Box::new(TestStorage(storage?)))
+}
+
+fn cpu(storage: &dyn CustomBackendStorage) -> &CpuStorage {
+    &storage.as_any().downcast_ref::<TestStorage>().unwrap().0
+}
+
+impl CustomBackendStorage for TestStorage {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn try_clone(&self, l: &Layout) -> Result<DynStorage> {
+        wrap(self.0.try_clone(l))
+    }
+
+    fn dtype(&self) -> DType {
+        self.0.dtype()
+    }
+
+    fn to_cpu_storage(&self) -> Result<CpuStorage> {
+        Ok(self.0.clone())
+    }
+
+    fn affine(&self, l: &Layout, mul: f64, add: f64) -> Result<DynStorage> {
+        wrap(self.0.affine(l, mul, add))
+    }
+
+    fn powf(&self, l: &Layout, e: f64) -> Result<DynStorage> {
+        wrap(self.0.powf(l, e))
+    }
+
+    fn elu(&self, l: &Layout, alpha: f64) -> Result<DynStorage> {
+        wrap(self.0.elu(l, alpha))
+    }
+
+    fn reduce_op(&self, op: ReduceOp, l: &Layout, dims: &[usize]) -> Result<DynStorage> {
+        wrap(self.0.reduce_op(op, l, dims))
+    }
+
+    fn cmp(
+        &self,
+        op: CmpOp,
+        rhs: &dyn CustomBackendStorage,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<DynStorage> {
+        wrap(self.0.cmp(op, cpu(rhs), lhs_l, rhs_l))
+    }
+
+    fn to_dtype(&self, l: &Layout, dtype: DType) -> Result<DynStorage> {
+        wrap(self.0.to_dtype(l, dtype))
+    }
+
+    fn unary(&self, l: &Layout, op: &'static str) -> Result<DynStorage> {
+        match op {
+            "neg" => wrap(self.0.affine(l, -1., 0.)),
+            op => bail!("unsupported unary op {op}"),
+        }
+    }
+
+    fn binary(
+        &self,
+        rhs: &dyn CustomBackendStorage,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+        op: &'static str,
+    ) -> Result<DynStorage> {
+        let f = match op {
+            "add" => |v1: f32, v2: f32| v1 + v2,
+            "mul" => |v1: f32, v2: f32| v1 * v2,
+            op => bail!("unsupported binary op {op}"),
+        };
+        let (lhs, rhs) = (self.0.as_slice::<f32>()?, cpu(rhs).as_slice::<f32>()?);
+        let vs = lhs_l
+            .strided_index()
+            .zip(rhs_l.strided_index())
+            .map(|(i1, i2)| f(lhs[i1], rhs[i2]))
+            .collect();
+        wrap(Ok(CpuStorage::F32(vs)))
+    }
+
+    fn where_cond(
+        &self,
+        l: &Layout,
+        t: &dyn CustomBackendStorage,
+        t_l: &Layout,
+        f: &dyn CustomBackendStorage,
+        f_l: &Layout,
+    ) -> Result<DynStorage> {
+        wrap(self.0.where_cond(l, cpu(t), t_l, cpu(f), f_l))
+    }
+
+    fn conv1d(
+        &self,
+        l: &Layout,
+        k: &dyn CustomBackendStorage,
+        k_l: &Layout,
+        p: &ParamsConv1D,
+    ) -> Result<DynStorage> {
+        wrap(self.0.conv1d(l, cpu(k), k_l, p))
+    }
+
+    fn conv_transpose1d(
+        &self,
+        l: &Layout,
+        k: &dyn CustomBackendStorage,
+        k_l: &Layout,
+        p: &ParamsConvTranspose1D,
+    ) -> Result<DynStorage> {
+        wrap(self.0.conv_transpose1d(l, cpu(k), k_l, p))
+    }
+
+    fn conv2d(
+        &self,
+        l: &Layout,
+        k: &dyn CustomBackendStorage,
+        k_l: &Layout,
+        p: &ParamsConv2D,
+    ) -> Result<DynStorage> {
+        wrap(self.0.conv2d(l, cpu(k), k_l, p))
+    }
+
+    fn conv_transpose2d(
+        &self,
+        l: &Layout,
+        k: &dyn CustomBackendStorage,
+        k_l: &Layout,
+        p: &ParamsConvTranspose2D,
+    ) -> Result<DynStorage> {
+        wrap(self.0.conv_transpose2d(l, cpu(k), k_l, p))
+    }
+
+    fn avg_pool2d(&self, l: &Layout, k: (usize, usize), s: (usize, usize)) -> Result<DynStorage> {
+        wrap(self.0.avg_pool2d(l, k, s))
+    }
+
+    fn max_pool2d(&self, l: &Layout, k: (usize, usize), s: (usize, usize)) -> Result<DynStorage> {
+        wrap(self.0.max_pool2d(l, k, s))
+    }
+
+    fn upsample_nearest1d(&self, l: &Layout, sz: usize) -> Result<DynStorage> {
+        wrap(self.0.upsample_nearest1d(l, sz))
+    }
+
+    fn upsample_nearest2d(&self, l: &Layout, h: usize, w: usize) -> Result<DynStorage> {
+        wrap(self.0.upsample_nearest2d(l, h, w))
+    }
+
+    fn gather(
+        &self,
+        l: &Layout,
+        ids: &dyn CustomBackendStorage,
+        ids_l: &Layout,
+        dim: usize,
+    ) -> Result<DynStorage> {
+        wrap(self.0.gather(l, cpu(ids), ids_l, dim))
+    }
+
+    fn scatter_add(
+        &self,
+        l: &Layout,
+        ids: &dyn CustomBackendStorage,
+        ids_l: &Layout,
+        src: &dyn CustomBackendStorage,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<DynStorage> {
+        wrap(self.0.scatter_add(l, cpu(ids), ids_l, cpu(src), src_l, dim))
+    }
+
+    fn index_select(
+        &self,
+        ids: &dyn CustomBackendStorage,
+        l: &Layout,
+        ids_l: &Layout,
+        dim: usize,
+    ) -> Result<DynStorage> {
+        wrap(self.0.index_select(cpu(ids), l, ids_l, dim))
+    }
+
+    fn index_add(
+        &self,
+        l: &Layout,
+        ids: &dyn CustomBackendStorage,
+        ids_l: &Layout,
+        src: &dyn CustomBackendStorage,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<DynStorage> {
+        wrap(self.0.index_add(l, cpu(ids), ids_l, cpu(src), src_l, dim))
+    }
+
+    fn index_set(
+        &self,
+        l: &Layout,
+        ids: &dyn CustomBackendStorage,
+        ids_l: &Layout,
+        src: &dyn CustomBackendStorage,
+        src_l: &Layout,
+        dim: usize,
+    ) -> Result<DynStorage> {
+        wrap(self.0.index_set(l, cpu(ids), ids_l, cpu(src), src_l, dim))
+    }
+
+    fn matmul(
+        &self,
+        rhs: &dyn CustomBackendStorage,
+        bmnk: (usize, usize, usize, usize),
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<DynStorage> {
+        wrap(self.0.matmul(cpu(rhs), bmnk, lhs_l, rhs_l))
+    }
+
+    fn copy_strided_src(
+        &self,
+        dst: &mut dyn CustomBackendStorage,
+        dst_offset: usize,
+        src_l: &Layout,
+    ) -> Result<()> {
+        let dst = &mut dst.as_any_mut().downcast_mut::<TestStorage>().unwrap().0;
+        self.0.copy_strided_src(dst, dst_offset, src_l)
+    }
+
+    fn copy2d(
+        &self,
+        dst: &mut dyn CustomBackendStorage,
+        d1: usize,
+        d2: usize,
+        src_s: usize,
+        dst_s: usize,
+        src_o: usize,
+        dst_o: usize,
+    ) -> Result<()> {
+        let dst = &mut dst.as_any_mut().downcast_mut::<TestStorage>().unwrap().0;
+        self.0.copy2d(dst, d1, d2, src_s, dst_s, src_o, dst_o)
+    }
+}
+
+impl CustomBackendDevice for TestDevice {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn ordinal(&self) -> usize {
+        0
+    }
+
+    fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<DynStorage> {
+        wrap(CpuDevice.zeros_impl(shape, dtype))
+    }
+
+    fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<DynStorage> {
+        wrap(CpuDevice.ones_impl(shape, dtype))
+    }
+
+    unsafe fn alloc_uninit(&self, shape: &Shape, dtype: DType) -> Result<DynStorage> {
+        wrap(CpuDevice.alloc_uninit(shape, dtype))
+    }
+
+    fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<DynStorage> {
+        wrap(Ok(storage.clone()))
+    }
+
+    fn rand_uniform(&self, s: &Shape, dtype: DType, lo: f64, up: f64) -> Result<DynStorage> {
+        wrap(CpuDevice.rand_uniform(s, dtype, lo, up))
+    }
+
+    fn rand_normal(&self, s: &Shape, dtype: DType, mean: f64, std: f64) -> Result<DynStorage> {
+        wrap(CpuDevice.rand_normal(s, dtype, mean, std))
+    }
+
+    fn set_seed(&self, seed: u64) -> Result<()> {
+        CpuDevice.set_seed(seed)
+    }
+
+    fn synchronize(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_device() -> Result<()> {
+    let device = Device::new_custom(TestDevice);
+    assert!(device.is_custom());
+    assert!(device.same_device(&Device::new_custom(TestDevice)));
+    let custom = match &device {
+        Device::Custom(custom) => custom,
+        _ => unreachable!(),
+    };
+    assert!(custom.downcast_ref::<TestDevice>().is_some());
+
+    let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &device)?;
+    let b = Tensor::ones((2, 2), DType::F32, &device)?;
+    let c = a.matmul(&b)?.add(&a.t()?)?.neg()?;
+    assert_eq!(c.to_vec2::<f32>()?, [[-4., -6.], [-9., -11.]]);
+    assert_eq!(
+        format!("{:?}", c.device().location()),
+        "Custom { name: \"test\", id: 0 }"
+    );
+
+    let c = c.to_device(&Device::Cpu)?.to_device(&device)?;
+    assert!(c.device().is_custom());
+    assert_eq!(c.sum_all()?.to_scalar::<f32>()?, -30.);
+
+    // Ops that the backend does not provide report an error.
+    assert!(c.exp().is_err());
+    Ok(())
+}
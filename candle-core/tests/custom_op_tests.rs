@@ -143,3 +143,39 @@ fn inplace_op1() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn apply_with_bwd() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = candle_core::Var::new(&[-2f32, -1., 0.5, 3.], device)?;
+    let y = candle_core::Var::new(&[1f32, 2., 3., 4.], device)?;
+    let z = Tensor::new(&[0.5f32, 0.5, 0.5, 0.5], device)?;
+    // elu(x) * y + z, with the elu computed by a custom op that has no backward.
+    let res = Tensor::apply_with_bwd(
+        &[x.as_tensor().clone(), y.as_tensor().clone(), z],
+        |xs| {
+            xs[0]
+                .apply_op1_no_bwd(&Elu { alpha: 1. })?
+                .mul(&xs[1])?
+                .add(&xs[2])
+        },
+        |xs, _res, grad| {
+            let elu = xs[0].apply_op1_no_bwd(&Elu { alpha: 1. })?;
+            let elu_bwd = xs[0].apply_op1_no_bwd(&EluBackward { alpha: 1. })?;
+            let grad_x = grad.mul(&elu_bwd)?.mul(&xs[1])?;
+            Ok(vec![Some(grad_x), Some(grad.mul(&elu)?), None])
+        },
+    )?;
+    let expected = x.elu(1.)?.mul(&y)?.affine(1., 0.5)?;
+    assert_eq!(to_vec1_round(&res, 4)?, to_vec1_round(&expected, 4)?);
+
+    let grads = res.sum_all()?.backward()?;
+    let expected_grads = expected.sum_all()?.backward()?;
+    for v in [&x, &y] {
+        assert_eq!(
+            to_vec1_round(grads.get(v).unwrap(), 4)?,
+            to_vec1_round(expected_grads.get(v).unwrap(), 4)?
+        );
+    }
+    Ok(())
+}
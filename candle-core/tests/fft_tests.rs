@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use candle_core::{fft, test_device, test_utils, DType, Device, Tensor, Var};
+
+fn rfft(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[1f32, 2., 3., 4., 5.], device)?;
+    if !device.is_cpu() {
+        // The transforms are only implemented on the cpu.
+        assert!(t.rfft().is_err());
+        assert!(t
+            .to_device(&Device::Cpu)?
+            .rfft()?
+            .to_device(device)?
+            .irfft(5)
+            .is_err());
+        return Ok(());
+    }
+    assert_eq!(
+        test_utils::to_vec2_round(&t.rfft()?, 4)?,
+        [[15.0, 0.0], [-2.5, 3.441], [-2.5, 0.8123]]
+    );
+    let t = Tensor::new(&[[0.5f32, -1., 2., 0., 3., 1.]], device)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&t.rfft()?, 4)?,
+        [[[5.5, 0.0], [-2.0, 2.5981], [-2.0, 0.866], [5.5, 0.0]]]
+    );
+    let t = Tensor::new(&[[1f32, 0.], [2., 1.], [0., -1.]], device)?;
+    assert_eq!(
+        test_utils::to_vec1_round(&t.irfft(4)?, 4)?,
+        [1.25, -0.25, -0.75, 0.75]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(&t.irfft(5)?, 4)?,
+        [1.0, 0.3019, -1.0628, 0.1683, 0.5925]
+    );
+    let t = Tensor::new(
+        &[[3f32, 1., 4., 1., 5., 9., 2.], [6., 5., 3., 5., 8., 9., 7.]],
+        device,
+    )?;
+    let roundtrip = t.rfft()?.irfft(7)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&roundtrip, 4)?,
+        t.to_vec2::<f32>()?
+    );
+    Ok(())
+}
+
+fn stft(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 8., device)?.sqr()?;
+    let window = Tensor::ones(4, DType::F32, device)?;
+    if !device.is_cpu() {
+        assert!(t.stft(4, 2, &window, false).is_err());
+        return Ok(());
+    }
+    let spec = t.stft(4, 2, &window, false)?;
+    assert_eq!(spec.dims(), [3, 3, 2]);
+    for frame in 0..3 {
+        let expected = t.narrow(0, 2 * frame, 4)?.rfft()?;
+        let frame = spec.narrow(1, frame, 1)?.squeeze(1)?;
+        assert_eq!(
+            test_utils::to_vec2_round(&frame, 3)?,
+            test_utils::to_vec2_round(&expected, 3)?
+        );
+    }
+    let window = fft::hann_window(4, DType::F32, device)?;
+    assert_eq!(window.to_vec1::<f32>()?, [0.0, 0.5, 1.0, 0.5]);
+    let spec = t.unsqueeze(0)?.stft(4, 2, &window, true)?;
+    assert_eq!(spec.dims(), [1, 3, 5, 2]);
+    // With center padding, the first frame is [4, 1, 0, 1] (reflected) times the window.
+    let first = spec.narrow(2, 0, 1)?.flatten_all()?;
+    assert_eq!(
+        test_utils::to_vec1_round(&first, 3)?,
+        [1.0, 0.0, 0.0, 0.0, -1.0, 0.0]
+    );
+    assert!(t
+        .stft(4, 2, &Tensor::ones(3, DType::F32, device)?, true)
+        .is_err());
+    Ok(())
+}
+
+test_device!(rfft, rfft_cpu, rfft_gpu, rfft_metal);
+test_device!(stft, stft_cpu, stft_gpu, stft_metal);
+
+#[test]
+fn rfft_half_precision() -> Result<()> {
+    // Half precision tensors are transformed in f32.
+    let t = Tensor::new(&[[0.5f32, -1., 2., 0., 3., 1.]], &Device::Cpu)?;
+    let expected = test_utils::to_vec3_round(&t.rfft()?, 2)?;
+    let spec = t.to_dtype(DType::F16)?.rfft()?.to_dtype(DType::F32)?;
+    assert_eq!(test_utils::to_vec3_round(&spec, 2)?, expected);
+    let roundtrip = t
+        .to_dtype(DType::F16)?
+        .rfft()?
+        .irfft(6)?
+        .to_dtype(DType::F32)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&roundtrip, 2)?,
+        t.to_vec2::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn rfft_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3., 4.], device)?;
+    let w = Tensor::new(&[[1f32, 0.], [1., 0.], [1., 0.]], device)?;
+    let grads = (x.rfft()? * w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(test_utils::to_vec1_round(grad_x, 4)?, [3., 0., 1., 0.]);
+    let w = Tensor::new(&[[0f32, 1.], [0., 1.], [0., 1.]], device)?;
+    let grads = (x.rfft()? * w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(test_utils::to_vec1_round(grad_x, 4)?, [0., -1., 0., 1.]);
+    let w = Tensor::new(&[2f32, -1., 0.5, 3.], device)?;
+    let grads = (x.rfft()?.irfft(4)? * &w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(test_utils::to_vec1_round(grad_x, 4)?, w.to_vec1::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn irfft_grad() -> Result<()> {
+    // Compare with the gradient of the explicit sum x_j = sum_k w_k (re_k cos - im_k sin) where
+    // only the first n / 2 + 1 frequencies are used.
+    let device = &Device::Cpu;
+    let n = 5;
+    let xs = Var::new(&[[1f32, 0.5], [2., 1.], [0., -1.], [3., 2.]], device)?;
+    let g = [2f64, -1., 0.5, 3., 1.5];
+    let w = Tensor::new(&g, device)?.to_dtype(DType::F32)?;
+    let grads = (xs.irfft(n)? * w)?.sum_all()?.backward()?;
+    let grad_xs = grads.get(&xs).context("no grad for xs")?;
+    let mut expected = vec![];
+    for k in 0..4 {
+        let weight = match k {
+            0 => 1. / n as f64,
+            k if k <= n / 2 => 2. / n as f64,
+            _ => 0.,
+        };
+        let angle = |j: usize| 2. * std::f64::consts::PI * (k * j) as f64 / n as f64;
+        let re: f64 = (0..n).map(|j| g[j] * angle(j).cos()).sum();
+        let im: f64 = (0..n).map(|j| -g[j] * angle(j).sin()).sum();
+        expected.push([
+            (weight * re * 1e4).round() as f32 / 1e4,
+            (weight * im * 1e4).round() as f32 / 1e4,
+        ]);
+    }
+    assert_eq!(
+        test_utils::to_vec2_round(grad_xs, 4)?,
+        expected.iter().map(|v| v.to_vec()).collect::<Vec<_>>()
+    );
+    Ok(())
+}
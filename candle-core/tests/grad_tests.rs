@@ -1,6 +1,6 @@
 #![allow(clippy::approx_constant)]
 use anyhow::{Context, Result};
-use candle_core::{test_device, test_utils, Device, Shape, Tensor, Var};
+use candle_core::{test_device, test_utils, DType, Device, PadMode, Shape, Tensor, Var};
 
 fn simple_grad(device: &Device) -> Result<()> {
     let x = Var::new(&[3f32, 1., 4.], device)?;
@@ -531,3 +531,619 @@ test_device!(
     binary_grad_gpu,
     binary_grad_metal
 );
+
+#[test]
+fn einsum_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let y = Var::new(&[[5f32, 6.], [7., 8.]], device)?;
+    let z = Tensor::einsum("ij,jk->", &[x.as_tensor(), y.as_tensor()])?;
+    let grads = z.backward()?;
+    // z = sum_ijk x_ij y_jk, so dz/dx_ij = sum_k y_jk and dz/dy_jk = sum_i x_ij
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    let grad_y = grads.get(&y).context("no grad for y")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[11., 15.], [11., 15.]]);
+    assert_eq!(grad_y.to_vec2::<f32>()?, [[4., 4.], [6., 6.]]);
+    Ok(())
+}
+
+#[test]
+fn cumulative_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3., 4.], device)?;
+    let grads = x.cumsum(0)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [4., 3., 2., 1.]);
+    // d/dx_i sum_j prod_{k<=j} x_k = sum_{j>=i} prod_{k<=j, k!=i} x_k
+    let grads = x.cumprod(0)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [33., 16., 10., 6.]);
+    Ok(())
+}
+
+#[test]
+fn scatter_index_add_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let init = Var::zeros((2, 3), DType::F32, device)?;
+    let src = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let ids = Tensor::new(&[[2u32, 0], [1, 1]], device)?;
+    let w = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let res = init.scatter_add(&ids, &src, 1)?;
+    assert_eq!(res.to_vec2::<f32>()?, [[2., 0., 1.], [0., 7., 0.]]);
+    let grads = (res * &w)?.sum_all()?.backward()?;
+    let grad_init = grads.get(&init).context("no grad for init")?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_init.to_vec2::<f32>()?, [[1., 2., 3.], [4., 5., 6.]]);
+    assert_eq!(grad_src.to_vec2::<f32>()?, [[3., 1.], [5., 5.]]);
+
+    let ids = Tensor::new(&[2u32, 2], device)?;
+    let res = init.index_add(&ids, &src, 1)?;
+    assert_eq!(res.to_vec2::<f32>()?, [[0., 0., 3.], [0., 0., 7.]]);
+    let grads = (res * &w)?.sum_all()?.backward()?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_src.to_vec2::<f32>()?, [[3., 3.], [6., 6.]]);
+
+    // With index_set only the last write to each position gets a gradient and the overwritten
+    // positions of init get none.
+    let init = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let ids = Tensor::new(&[2u32, 2], device)?;
+    let res = init.index_set(&ids, &src, 1)?;
+    assert_eq!(res.to_vec2::<f32>()?, [[1., 2., 2.], [4., 5., 4.]]);
+    let grads = (res * &w)?.sum_all()?.backward()?;
+    let grad_init = grads.get(&init).context("no grad for init")?;
+    let grad_src = grads.get(&src).context("no grad for src")?;
+    assert_eq!(grad_init.to_vec2::<f32>()?, [[1., 2., 0.], [4., 5., 0.]]);
+    assert_eq!(grad_src.to_vec2::<f32>()?, [[0., 3.], [0., 6.]]);
+    Ok(())
+}
+
+#[test]
+fn complex_angle_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let z = Var::new(&[[1f32, 1.], [-2., 0.], [0., -3.]], device)?;
+    let angle = z.angle()?;
+    assert_eq!(
+        test_utils::to_vec1_round(&angle, 4)?,
+        [0.7854, 3.1416, -1.5708]
+    );
+    let grads = angle.sum_all()?.backward()?;
+    let grad_z = grads.get(&z).context("no grad for z")?;
+    // d/dre atan2(im, re) = -im / |z|^2 and d/dim atan2(im, re) = re / |z|^2
+    assert_eq!(
+        test_utils::to_vec2_round(grad_z, 4)?,
+        [[-0.5, 0.5], [0., -0.5], [0.3333, 0.]]
+    );
+    Ok(())
+}
+
+#[test]
+fn roll_flip_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let w = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let rolled = x.roll_dims(&[1, 1], (0, 1))?;
+    assert_eq!(rolled.to_vec2::<f32>()?, [[6., 4., 5.], [3., 1., 2.]]);
+    let grads = (rolled * &w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[5., 6., 4.], [2., 3., 1.]]);
+    let flipped = x.flip((0, 1))?;
+    assert_eq!(flipped.to_vec2::<f32>()?, [[6., 5., 4.], [3., 2., 1.]]);
+    let grads = (flipped * &w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[6., 5., 4.], [3., 2., 1.]]);
+    Ok(())
+}
+
+#[test]
+fn pad_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3.], device)?;
+    let w = Tensor::new(&[1f32, 2., 3., 4., 5., 6., 7.], device)?;
+    // The padded values are [3, 2, 1, 2, 3, 2, 1].
+    let grads = (x.pad(0, 2, 2, PadMode::Reflect)? * &w)?
+        .sum_all()?
+        .backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [10., 12., 6.]);
+    // The padded values are [2, 3, 1, 2, 3, 1, 2].
+    let grads = (x.pad(0, 2, 2, PadMode::Circular)? * &w)?
+        .sum_all()?
+        .backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [9., 12., 7.]);
+    Ok(())
+}
+
+#[test]
+fn nan_ops_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, f32::NAN, 3., f32::INFINITY], device)?;
+    let grads = x.nanmean(0)?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    let grad_x = grad_x.to_vec1::<f32>()?;
+    assert_eq!(grad_x[1], 0.);
+    assert_eq!([grad_x[0], grad_x[2], grad_x[3]], [1. / 3.; 3]);
+    let grads = (x.nan_to_num(0., Some(10.), None)? * 2.)?
+        .sum_all()?
+        .backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [2., 0., 2., 0.]);
+    Ok(())
+}
+
+#[test]
+fn inplace_ops_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3.], device)?;
+    let y = (x.as_tensor() * 2.)?;
+    assert!(y.add_assign_(1f32).is_err());
+    let grads = y.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // Variables can be updated in place, e.g. for an optimizer step.
+    x.sub_assign_(&(grad_x * 0.5)?)?;
+    assert_eq!(x.to_vec1::<f32>()?, [0., 1., 2.]);
+    Ok(())
+}
+
+#[test]
+fn slice_step_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[[1f32, 2., 3., 4., 5.], [6., 7., 8., 9., 10.]], device)?;
+    let y = x.slice_step(1, 1.., 2)?;
+    let grads = (&y * &y)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.to_vec2::<f32>()?,
+        [[0., 4., 0., 8., 0.], [0., 14., 0., 18., 0.]]
+    );
+    Ok(())
+}
+
+#[test]
+fn log_sum_exp_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[[1f32, 2., 3.], [1000., 1000., 1000.]], device)?;
+    let grads = x.log_sum_exp(1)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // The gradient is the softmax over the summed dimension.
+    assert_eq!(
+        test_utils::to_vec2_round(grad_x, 4)?,
+        [[0.09, 0.2447, 0.6652], [0.3333, 0.3333, 0.3333]]
+    );
+    Ok(())
+}
+
+#[test]
+fn broadcast_where_cond_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let mask = Tensor::new(&[[1u8, 0, 1], [0, 0, 1]], device)?;
+    let on_true = Var::new(&[1f32, 2., 3.], device)?;
+    let on_false = Var::new(&[[-1f32], [-2.]], device)?;
+    let grads = mask
+        .where_cond(&on_true, &on_false)?
+        .sum_all()?
+        .backward()?;
+    let grad_true = grads.get(&on_true).context("no grad for on_true")?;
+    let grad_false = grads.get(&on_false).context("no grad for on_false")?;
+    assert_eq!(grad_true.to_vec1::<f32>()?, [1., 0., 2.]);
+    assert_eq!(grad_false.to_vec2::<f32>()?, [[1.], [2.]]);
+    Ok(())
+}
+
+#[test]
+fn checkpoint_grad() -> Result<()> {
+    use candle_core::backprop::checkpoint;
+    let device = &Device::Cpu;
+    let x = Var::new(&[[0.5f32, -1.0, 2.0], [1.5, 0.2, -0.3]], device)?;
+    let w = Var::new(&[[1f32, -2.], [0.5, 1.], [-1., 3.]], device)?;
+    let b = Tensor::new(&[0.1f32, -0.2], device)?;
+    let block = |xs: &[Tensor], w: &Tensor| xs[0].matmul(w)?.broadcast_add(&xs[1])?.tanh()?.sqr();
+
+    let eager = block(&[x.as_tensor().clone(), b.clone()], &w)?;
+
+    let res = checkpoint(&[x.as_tensor().clone(), b.clone()], {
+        let w = w.as_tensor().clone();
+        move |xs| block(xs, &w)
+    })?;
+    // Nested checkpoints are recomputed as well.
+    let res = checkpoint(&[res], |xs| xs[0].exp())?;
+    let grads = res.sum_all()?.backward()?;
+    let eager_grads = eager.exp()?.sum_all()?.backward()?;
+    for v in [&x, &w] {
+        let grad = grads.get(v).context("no grad")?;
+        let expected = eager_grads.get(v).context("no grad")?;
+        assert_eq!(
+            test_utils::to_vec2_round(grad, 4)?,
+            test_utils::to_vec2_round(expected, 4)?
+        );
+    }
+
+    // The captured variables get their gradients even when no argument tracks the gradient.
+    let res = checkpoint(std::slice::from_ref(&b), {
+        let w = w.as_tensor().clone();
+        move |xs| w.broadcast_mul(&xs[0])
+    })?;
+    let grads = res.sum_all()?.backward()?;
+    let grad_w = grads.get(&w).context("no grad for w")?;
+    assert_eq!(
+        grad_w.to_vec2::<f32>()?,
+        [[0.1, -0.2], [0.1, -0.2], [0.1, -0.2]]
+    );
+    Ok(())
+}
+
+#[test]
+fn second_order_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[0.5f32, -1.0, 2.0], device)?;
+    let w = Var::new(&[1.5f32, 2.0, -0.5], device)?;
+
+    // d/dx sum(sin(x)) = cos(x), d/dx sum(cos(x)) = -sin(x)
+    let grads = x.sin()?.sum_all()?.backward_create_graph()?;
+    let dx = grads.get(&x).context("no grad for x")?;
+    let grads = dx.sum_all()?.backward()?;
+    let dx2 = grads.get(&x).context("no grad for x")?;
+    let expected = x.sin()?.neg()?;
+    assert_eq!(
+        test_utils::to_vec1_round(dx2, 4)?,
+        test_utils::to_vec1_round(&expected, 4)?
+    );
+
+    // A gradient penalty, with y = w * x^2 we get dy/dx = 2 w x and
+    // d/dw sum((dy/dx)^2) = 8 w x^2.
+    let y = w.mul(&x.sqr()?)?.sum_all()?;
+    let grads = y.backward_create_graph()?;
+    let dx = grads.get(&x).context("no grad for x")?;
+    assert_eq!(dx.to_vec1::<f32>()?, [1.5, -4.0, -2.0]);
+    let grads = dx.sqr()?.sum_all()?.backward()?;
+    let dw = grads.get(&w).context("no grad for w")?;
+    assert_eq!(dw.to_vec1::<f32>()?, [3.0, 16.0, -16.0]);
+
+    Ok(())
+}
+
+#[test]
+fn no_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3.], device)?;
+    let (y, z) = candle_core::no_grad(|| -> candle_core::Result<_> {
+        assert!(!candle_core::backprop::is_grad_enabled());
+        let y = x.sqr()?.sum_all()?;
+        // Nested calls restore the outer state.
+        let z = candle_core::no_grad(|| x.exp())?;
+        Ok((y, z))
+    })?;
+    assert!(candle_core::backprop::is_grad_enabled());
+    assert_eq!(y.to_scalar::<f32>()?, 14.);
+    assert!(y.backward()?.get(&x).is_none());
+    assert!(z.sum_all()?.backward()?.get(&x).is_none());
+
+    let y = x.sqr()?.sum_all()?;
+    let grad = y.backward()?;
+    assert_eq!(
+        grad.get(&x).context("no grad")?.to_vec1::<f32>()?,
+        [2., 4., 6.]
+    );
+    Ok(())
+}
+
+#[test]
+fn grad_hooks() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, -2., 3.], device)?;
+    let w = Var::new(&[0.5f32, 0.5, 0.5], device)?;
+
+    // Record the gradient of x and clamp the gradient flowing through an intermediary node.
+    let seen = Arc::new(Mutex::new(vec![]));
+    let x_handle = x.register_grad_hook({
+        let seen = seen.clone();
+        move |g| {
+            seen.lock().unwrap().push(g.to_vec1::<f32>()?);
+            Ok(None)
+        }
+    });
+    let y = x.mul(&w)?;
+    let _y_handle = y.register_grad_hook(|g| Ok(Some(g.clamp(-2f32, 2f32)?)));
+    let loss = (y.sqr()?.sum_all()? + x.sum_all()?)?;
+    let grads = loss.backward()?;
+    // dloss/dy = 2 y = [1, -2, 3] clamped to [1, -2, 2], and dloss/dx = dloss/dy * w + 1.
+    let expected = [1.5f32, 0., 2.];
+    assert_eq!(
+        grads.get(&x).context("no grad for x")?.to_vec1::<f32>()?,
+        expected
+    );
+    assert_eq!(
+        grads.get(&w).context("no grad for w")?.to_vec1::<f32>()?,
+        [1., 4., 6.]
+    );
+    // The hook is called once with the total gradient.
+    assert_eq!(*seen.lock().unwrap(), [expected]);
+
+    // Hooks can modify the gradients of variables, and are not called anymore once removed.
+    let w_handle = w.register_grad_hook(|g| Ok(Some(g.zeros_like()?)));
+    x_handle.remove();
+    let grads = loss.backward()?;
+    assert_eq!(
+        grads.get(&w).context("no grad for w")?.to_vec1::<f32>()?,
+        [0., 0., 0.]
+    );
+    assert_eq!(seen.lock().unwrap().len(), 1);
+    w_handle.remove();
+
+    // Hooks returning a gradient with a different shape are reported.
+    let _handle = x.register_grad_hook(|_| Ok(Some(Tensor::zeros(2, DType::F32, &Device::Cpu)?)));
+    assert!(x.sum_all()?.backward().is_err());
+
+    // The hooks of a dropped tensor are removed, unless a hook captures the tensor itself in
+    // which case the handle has to be used.
+    let marker = Arc::new(());
+    let z = x.affine(2., 0.)?;
+    let _handle = z.register_grad_hook({
+        let marker = marker.clone();
+        move |_| {
+            let _ = &marker;
+            Ok(None)
+        }
+    });
+    drop(z);
+    assert_eq!(Arc::strong_count(&marker), 1);
+    let z = x.affine(2., 0.)?;
+    let handle = z.register_grad_hook({
+        let (z, marker) = (z.clone(), marker.clone());
+        move |_| {
+            let _ = (&z, &marker);
+            Ok(None)
+        }
+    });
+    drop(z);
+    assert_eq!(Arc::strong_count(&marker), 2);
+    handle.remove();
+    assert_eq!(Arc::strong_count(&marker), 1);
+    // Dropping a tensor can drop a hook owning another tensor with hooks.
+    let w = x.affine(3., 0.)?;
+    let _handle = w.register_grad_hook({
+        let marker = marker.clone();
+        move |_| {
+            let _ = &marker;
+            Ok(None)
+        }
+    });
+    let z = x.affine(2., 0.)?;
+    let _handle = z.register_grad_hook(move |_| {
+        let _ = &w;
+        Ok(None)
+    });
+    drop(z);
+    assert_eq!(Arc::strong_count(&marker), 1);
+    Ok(())
+}
+
+#[test]
+fn grad_hooks_checkpoint_and_sparse() -> Result<()> {
+    use candle_core::backprop::checkpoint;
+    use std::sync::{Arc, Mutex};
+    let device = &Device::Cpu;
+
+    // A variable only used within a checkpointed function.
+    let x = Var::new(&[1f32, 2.], device)?;
+    let w = Var::new(&[3f32, -1.], device)?;
+    let seen = Arc::new(Mutex::new(vec![]));
+    let handle = w.register_grad_hook({
+        let seen = seen.clone();
+        move |g| {
+            seen.lock().unwrap().push(g.to_vec1::<f32>()?);
+            Ok(Some(g.affine(2., 0.)?))
+        }
+    });
+    let y = checkpoint(&[x.as_tensor().clone()], {
+        let w = w.as_tensor().clone();
+        move |xs| xs[0].mul(&w)
+    })?;
+    // w is also used outside of the checkpoint, the hook gets the total gradient once.
+    let loss = (y.sum_all()? + w.sum_all()?)?;
+    let grads = loss.backward()?;
+    assert_eq!(*seen.lock().unwrap(), [[2f32, 3.]]);
+    assert_eq!(
+        grads.get(&w).context("no grad for w")?.to_vec1::<f32>()?,
+        [4., 6.]
+    );
+    assert_eq!(
+        grads.get(&x).context("no grad for x")?.to_vec1::<f32>()?,
+        [3., -1.]
+    );
+    handle.remove();
+
+    // A variable with a sparse gradient, the hook gets the dense gradient.
+    let table = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let ids = Tensor::new(&[2u32, 0, 2], device)?;
+    let seen = Arc::new(Mutex::new(vec![]));
+    let handle = table.register_grad_hook({
+        let seen = seen.clone();
+        move |g| {
+            seen.lock().unwrap().push(g.to_vec2::<f32>()?);
+            Ok(None)
+        }
+    });
+    let loss = table.sparse_index_select(&ids)?.sum_all()?;
+    let grads = loss.backward()?;
+    let dense = [[1f32, 1.], [0., 0.], [2., 2.]];
+    assert_eq!(*seen.lock().unwrap(), [dense]);
+    // Observing the gradient keeps it sparse.
+    assert!(grads.get(&table).is_none());
+    let sparse = grads.get_sparse(&table).context("no sparse grad")?;
+    assert_eq!(sparse.to_dense()?.to_vec2::<f32>()?, dense);
+    handle.remove();
+
+    // Replacing it stores the new dense gradient.
+    let handle = table.register_grad_hook(|g| Ok(Some(g.affine(-1., 0.)?)));
+    let grads = loss.backward()?;
+    assert!(grads.get_sparse(&table).is_none());
+    assert_eq!(
+        grads.get(&table).context("no grad")?.to_vec2::<f32>()?,
+        [[-1f32, -1.], [0., 0.], [-2., -2.]]
+    );
+    handle.remove();
+    Ok(())
+}
+
+#[test]
+fn stop_gradient_through() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Tensor::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let w_enc = Var::new(&[[1f32, -1.], [0.5, 2.]], device)?;
+    let w_head = Var::new(&[[2f32], [-1.]], device)?;
+
+    let h = x.matmul(&w_enc)?.tanh()?;
+    let y = h.matmul(&w_head)?.sum_all()?;
+    let all = y.backward()?;
+
+    // Freezing the encoder weights, the head gets the same gradient.
+    let grads = y.backward_stop_gradient_through(&[&w_enc])?;
+    assert!(grads.get(&w_enc).is_none());
+    let grad_head = grads.get(&w_head).context("no grad for w_head")?;
+    let expected = all.get(&w_head).context("no grad for w_head")?;
+    assert_eq!(grad_head.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+    // Stopping at an intermediary node prunes the whole subgraph.
+    let grads = y.backward_stop_gradient_through(std::slice::from_ref(&h))?;
+    assert!(grads.get(&w_enc).is_none());
+    assert!(grads.get(&h).is_none());
+    assert!(grads.get(&w_head).is_some());
+
+    // Variables reachable through another path still get the gradient from that path.
+    let z = (h.sum_all()? + w_enc.sum_all()?)?;
+    let grads = z.backward_stop_gradient_through(&[&h])?;
+    let grad_enc = grads.get(&w_enc).context("no grad for w_enc")?;
+    assert_eq!(grad_enc.to_vec2::<f32>()?, [[1., 1.], [1., 1.]]);
+    Ok(())
+}
+
+#[test]
+fn multiple_backward_passes() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Tensor::new(&[[1f32, -2.], [0.5, 3.]], device)?;
+    let w_gen = Var::new(&[[0.5f32, 1.], [-1., 2.]], device)?;
+    let w_disc = Var::new(&[[1f32], [-0.5]], device)?;
+
+    // A single forward pass shared by two losses.
+    let fake = x.matmul(&w_gen)?.tanh()?;
+    let score = fake.matmul(&w_disc)?;
+    let disc_loss = score.sqr()?.mean_all()?;
+    let gen_loss = score.neg()?.mean_all()?;
+
+    let disc_grads = disc_loss.backward()?;
+    let gen_grads = gen_loss.backward()?;
+    // Running the backward pass again gives the same result.
+    let disc_grads2 = disc_loss.backward()?;
+    for var in [&w_gen, &w_disc] {
+        let g1 = disc_grads.get(var).context("no grad")?;
+        let g2 = disc_grads2.get(var).context("no grad")?;
+        assert_eq!(g1.to_vec2::<f32>()?, g2.to_vec2::<f32>()?);
+    }
+
+    // Each store matches the one obtained with a fresh forward pass.
+    let fresh = x
+        .matmul(&w_gen)?
+        .tanh()?
+        .matmul(&w_disc)?
+        .neg()?
+        .mean_all()?;
+    let fresh_grads = fresh.backward()?;
+    for var in [&w_gen, &w_disc] {
+        let g1 = gen_grads.get(var).context("no grad")?;
+        let g2 = fresh_grads.get(var).context("no grad")?;
+        assert_eq!(g1.to_vec2::<f32>()?, g2.to_vec2::<f32>()?);
+    }
+    Ok(())
+}
+
+#[test]
+fn sparse_index_select_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let emb = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.], [7., 8.]], device)?;
+    let ids = Tensor::new(&[3u32, 1, 3], device)?;
+    let loss = |emb: &Tensor, sparse: bool| -> Result<Tensor> {
+        let xs = if sparse {
+            emb.sparse_index_select(&ids)?
+        } else {
+            emb.index_select(&ids, 0)?
+        };
+        Ok(xs.sqr()?.sum_all()?)
+    };
+    let dense = loss(&emb, false)?.backward()?;
+    let dense = dense.get(&emb).context("no grad for emb")?;
+    let grads = loss(&emb, true)?.backward()?;
+    assert!(grads.get(&emb).is_none());
+    let sparse = grads.get_sparse(&emb).context("no sparse grad for emb")?;
+    assert_eq!(sparse.indices().to_vec1::<u32>()?, [3, 1, 3]);
+    assert_eq!(sparse.values().dims(), [3, 2]);
+    assert_eq!(
+        sparse.to_dense()?.to_vec2::<f32>()?,
+        dense.to_vec2::<f32>()?
+    );
+    let coalesced = sparse.coalesce()?;
+    assert_eq!(coalesced.indices().to_vec1::<u32>()?, [1, 3]);
+    assert_eq!(coalesced.values().to_vec2::<f32>()?, [[6., 8.], [28., 32.]]);
+
+    // Sparse gradients from multiple uses are accumulated.
+    let l = (loss(&emb, true)? + loss(&emb, true)?)?;
+    let grads = l.backward()?;
+    let sparse = grads.get_sparse(&emb).context("no sparse grad for emb")?;
+    assert_eq!(sparse.indices().dims1()?, 6);
+    let expected = (dense * 2.)?.to_vec2::<f32>()?;
+    assert_eq!(sparse.to_dense()?.to_vec2::<f32>()?, expected);
+
+    // A variable that also gets a dense gradient ends up with a dense gradient.
+    let l = (loss(&emb, true)? + loss(&emb, false)?)?;
+    let grads = l.backward()?;
+    assert!(grads.get_sparse(&emb).is_none());
+    let grad = grads.get(&emb).context("no grad for emb")?;
+    assert_eq!(grad.to_vec2::<f32>()?, expected);
+
+    // The gradient of intermediary tensors stays dense.
+    let x = Var::new(&[1f32, 2.], device)?;
+    let table = x.unsqueeze(0)?.broadcast_mul(&emb.as_tensor().detach())?;
+    let grads = table.sparse_index_select(&ids)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [17., 20.]);
+    Ok(())
+}
+
+#[test]
+fn frozen_var_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Var::new(&[2f32, 3.], device)?;
+    let x = Var::new(&[1f32, 4.], device)?;
+    // Freezing before the forward pass, the operations only using w are not tracked.
+    w.freeze();
+    assert!(w.is_frozen());
+    assert!(!w.sqr()?.track_op());
+    let grads = (w.as_tensor() * x.as_tensor())?.sum_all()?.backward()?;
+    assert!(grads.get(&w).is_none());
+    assert_eq!(
+        grads.get(&x).context("no grad for x")?.to_vec1::<f32>()?,
+        [2., 3.]
+    );
+
+    // Freezing after the forward pass is also respected.
+    w.unfreeze();
+    let y = (w.as_tensor() * x.as_tensor())?.sum_all()?;
+    let frozen_only = w.sqr()?.sum_all()?;
+    x.freeze();
+    let grads = y.backward()?;
+    assert!(grads.get(&x).is_none());
+    assert_eq!(
+        grads.get(&w).context("no grad for w")?.to_vec1::<f32>()?,
+        [1., 4.]
+    );
+    w.freeze();
+    assert!(frozen_only.backward()?.get(&w).is_none());
+
+    // The clones of a variable share its frozen state.
+    let w2 = Var::from_tensor(w.as_tensor())?;
+    assert!(w2.is_frozen());
+    w2.unfreeze();
+    assert!(!w.is_frozen());
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use candle_core::{Device, IndexOp, Tensor};
+use candle_core::{Device, IndexMask, IndexOp, IndexStep, Tensor};
 
 #[test]
 fn integer_index() -> Result<()> {
@@ -92,6 +92,47 @@ fn index_3d() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn step_index() -> Result<()> {
+    let tensor = Tensor::from_iter(0..24u32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    assert_eq!(
+        tensor
+            .i((1, IndexStep(.., 2), IndexStep(1.., 2)))?
+            .to_vec2::<u32>()?,
+        &[[13, 15], [21, 23]]
+    );
+    assert_eq!(
+        tensor.i((.., 2, IndexStep(..3, 2)))?.to_vec2::<u32>()?,
+        &[[8, 10], [20, 22]]
+    );
+    assert_eq!(tensor.i(IndexStep(.., 5))?.dims(), &[1, 3, 4]);
+    Ok(())
+}
+
+#[test]
+fn mask_index() -> Result<()> {
+    let tensor = Tensor::from_iter(0..24u32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let rows = Tensor::new(&[[1u8, 0, 1], [0, 0, 1]], &Device::Cpu)?;
+    assert_eq!(
+        tensor.i(IndexMask(&rows))?.to_vec2::<u32>()?,
+        &[[0, 1, 2, 3], [8, 9, 10, 11], [20, 21, 22, 23]]
+    );
+    let mid = Tensor::new(&[1u8, 0, 1], &Device::Cpu)?;
+    assert_eq!(
+        tensor.i((1, IndexMask(&mid), 1..))?.to_vec2::<u32>()?,
+        &[[13, 14, 15], [21, 22, 23]]
+    );
+    let cols = tensor.i((0, 0))?.ge(2u32)?;
+    assert_eq!(tensor.i((.., .., IndexMask(&cols)))?.dims(), &[2, 3, 2]);
+    // Masking all the dimensions returns the selected elements.
+    let mask = tensor.broadcast_lt(&Tensor::new(3u32, &Device::Cpu)?)?;
+    assert_eq!(tensor.i(IndexMask(&mask))?.to_vec1::<u32>()?, &[0, 1, 2]);
+    let none = Tensor::zeros(2, candle_core::DType::U8, &Device::Cpu)?;
+    assert_eq!(tensor.i(IndexMask(&none))?.dims(), &[0, 3, 4]);
+    assert!(tensor.i(IndexMask(&cols)).is_err());
+    Ok(())
+}
+
 #[test]
 fn slice_assign() -> Result<()> {
     let dev = Device::Cpu;
@@ -0,0 +1,106 @@
+use candle::{lazy, test_device, test_utils, DType, Device, IndexOp, Result, Tensor, D};
+use candle_core as candle;
+
+fn lazy_matches_eager(device: &Device) -> Result<()> {
+    let x = Tensor::arange(0f32, 12f32, device)?.reshape((3, 4))?;
+    let w = Tensor::arange(-3f32, 5f32, device)?.reshape((4, 2))?;
+    let b = Tensor::new(&[0.5f32, -0.5], device)?;
+
+    let eager = x
+        .affine(0.1, -0.2)?
+        .silu()?
+        .exp()?
+        .matmul(&w)?
+        .broadcast_add(&b)?
+        .relu()?
+        .sum_keepdim(1)?;
+    let lazy = x
+        .lazy()
+        .affine(0.1, -0.2)?
+        .silu()?
+        .exp()?
+        .matmul(&w.lazy())?
+        .add(&b.lazy())?
+        .relu()?
+        .sum_keepdim(1)?;
+    assert_eq!(lazy.dims(), &[3, 1]);
+    assert_eq!(
+        test_utils::to_vec2_round(&lazy.materialize()?, 4)?,
+        test_utils::to_vec2_round(&eager, 4)?
+    );
+
+    let t = x.lazy().t()?.reshape((2, 6))?.max_keepdim(D::Minus1)?;
+    let t = t.to_dtype(DType::F64)?.materialize()?;
+    assert_eq!(t.to_vec2::<f64>()?, [[9.], [11.]]);
+    Ok(())
+}
+
+fn lazy_shared_nodes(device: &Device) -> Result<()> {
+    let x = Tensor::new(&[-1f32, 0., 2.], device)?.lazy();
+    // `y` is used by both outputs so it cannot be fused with its consumers.
+    let y = x.affine(2., 1.)?.abs()?;
+    let z1 = y.sqr()?.neg()?;
+    let z2 = y.affine(1., 1.)?.add(&y)?;
+    let zs = lazy::materialize(&[z1.clone(), z2, y.clone()])?;
+    assert_eq!(zs[0].to_vec1::<f32>()?, [-1., -1., -25.]);
+    assert_eq!(zs[1].to_vec1::<f32>()?, [3., 3., 11.]);
+    assert_eq!(zs[2].to_vec1::<f32>()?, [1., 1., 5.]);
+
+    // Materialized values are cached and reused by the graphs built on top of them.
+    let z1 = z1.materialize()?;
+    assert_eq!(z1.id(), zs[0].id());
+    let w = y.mul(&y)?.materialize()?;
+    assert_eq!(w.to_vec1::<f32>()?, [1., 1., 25.]);
+
+    // Shape errors are reported when building the graph.
+    assert!(x.matmul(&x).is_err());
+    assert!(x.reshape((2, 2)).is_err());
+    Ok(())
+}
+
+fn lazy_vmap(device: &Device) -> Result<()> {
+    let xs = Tensor::arange(0f32, 24f32, device)?.reshape((4, 2, 3))?;
+    let ys = Tensor::arange(-2f32, 2f32, device)?.affine(0.5, 0.)?;
+    let w = Tensor::arange(0f32, 6f32, device)?.reshape((3, 2))?;
+    let b = Tensor::new(&[[1f32], [-1.]], device)?;
+
+    // A per-example function using captured tensors, broadcasting, reductions and reshapes.
+    let f = |x: &lazy::LazyTensor, y: &lazy::LazyTensor| {
+        let w = w.lazy();
+        let b = b.lazy();
+        let z = x.matmul(&w)?.t()?.add(&b)?.relu()?.mul(y)?;
+        let z = z.sum_keepdim(1)?.reshape(2)?.broadcast_as((3, 2))?;
+        z.max_keepdim(0)?.add(&x.sum_keepdim(0)?.sum_keepdim(1)?)
+    };
+    let batched = lazy::vmap(|xs| f(&xs[0], &xs[1]));
+    let res = batched(&[xs.clone(), ys.clone()])?;
+    assert_eq!(res.dims(), &[4, 1, 2]);
+    for i in 0..4 {
+        let expected = f(&xs.i(i)?.lazy(), &ys.i(i)?.lazy())?.materialize()?;
+        assert_eq!(
+            test_utils::to_vec2_round(&res.i(i)?, 4)?,
+            test_utils::to_vec2_round(&expected, 4)?
+        );
+    }
+
+    // Outputs that do not depend on the inputs are broadcast over the batch.
+    let res = lazy::vmap(|_| b.lazy().exp()?.round())(std::slice::from_ref(&xs))?;
+    assert_eq!(res.to_vec3::<f32>()?, [[[3.], [0.]]; 4]);
+
+    assert!(lazy::vmap(|xs| Ok(xs[0].clone()))(&[xs, ys.reshape((2, 2))?]).is_err());
+    Ok(())
+}
+
+test_device!(
+    lazy_matches_eager,
+    lazy_matches_eager_cpu,
+    lazy_matches_eager_gpu,
+    lazy_matches_eager_metal
+);
+test_device!(
+    lazy_shared_nodes,
+    lazy_shared_nodes_cpu,
+    lazy_shared_nodes_gpu,
+    lazy_shared_nodes_metal
+);
+test_device!(lazy_vmap, lazy_vmap_cpu, lazy_vmap_gpu, lazy_vmap_metal);
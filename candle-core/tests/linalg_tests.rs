@@ -0,0 +1,383 @@
+#![allow(clippy::approx_constant)]
+use anyhow::{Context, Result};
+use candle_core::{test_device, test_utils, DType, Device, Tensor, Var, D};
+
+// Central finite differences of a scalar function.
+fn numerical_grad<F: Fn(&Tensor) -> Result<f64>>(f: F, xs: &Tensor) -> Result<Vec<f64>> {
+    let eps = 1e-6;
+    let shape = xs.shape().clone();
+    let values = xs.flatten_all()?.to_vec1::<f64>()?;
+    let mut grad = Vec::with_capacity(values.len());
+    for i in 0..values.len() {
+        let mut plus = values.clone();
+        plus[i] += eps;
+        let mut minus = values.clone();
+        minus[i] -= eps;
+        let plus = f(&Tensor::from_vec(plus, &shape, xs.device())?)?;
+        let minus = f(&Tensor::from_vec(minus, &shape, xs.device())?)?;
+        grad.push((plus - minus) / (2. * eps))
+    }
+    Ok(grad)
+}
+
+fn assert_close(lhs: &[f64], rhs: &[f64]) {
+    assert_eq!(lhs.len(), rhs.len());
+    for (l, r) in lhs.iter().zip(rhs.iter()) {
+        assert!((l - r).abs() < 1e-4, "{lhs:?} {rhs:?}")
+    }
+}
+
+fn cholesky(device: &Device) -> Result<()> {
+    let a = Tensor::new(
+        &[
+            [[4f32, 12., -16.], [12., 37., -43.], [-16., -43., 98.]],
+            [[2., -1., 0.], [-1., 2., -1.], [0., -1., 2.]],
+        ],
+        device,
+    )?;
+    let l = a.cholesky()?;
+    assert_eq!(
+        test_utils::to_vec3_round(&l, 4)?,
+        [
+            [[2., 0., 0.], [6., 1., 0.], [-8., 5., 3.]],
+            [
+                [1.4142, 0., 0.],
+                [-0.7071, 1.2247, 0.],
+                [0., -0.8165, 1.1547]
+            ]
+        ]
+    );
+    let llt = l.matmul(&l.t()?)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&llt, 3)?,
+        test_utils::to_vec3_round(&a, 3)?
+    );
+    let not_pd = Tensor::new(&[[1f32, 2.], [2., 1.]], device)?;
+    assert!(not_pd.cholesky().is_err());
+    Ok(())
+}
+
+fn triangular_solve(device: &Device) -> Result<()> {
+    let l = Tensor::new(&[[2f32, 0., 0.], [6., 1., 0.], [-8., 5., 3.]], device)?;
+    let b = Tensor::new(&[[2f32, 4.], [7., 13.], [0., 4.]], device)?;
+    let x = l.triangular_solve(&b, false)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&x, 4)?,
+        [[1., 2.], [1., 1.], [1., 5.]]
+    );
+    let u = l.t()?;
+    let x = u.triangular_solve(&b, true)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&u.matmul(&x)?, 4)?,
+        b.to_vec2::<f32>()?
+    );
+    // The batch dimensions get broadcasted.
+    let bs = Tensor::stack(&[&b, &b.affine(2., 0.)?], 0)?;
+    let xs = l.triangular_solve(&bs, false)?;
+    assert_eq!(xs.dims(), [2, 3, 2]);
+    assert_eq!(
+        test_utils::to_vec2_round(&xs.get(1)?, 4)?,
+        [[2., 4.], [2., 2.], [2., 10.]]
+    );
+    assert!(l.triangular_solve(&b.t()?, false).is_err());
+    Ok(())
+}
+
+test_device!(cholesky, cholesky_cpu, cholesky_gpu, cholesky_metal);
+test_device!(
+    triangular_solve,
+    triangular_solve_cpu,
+    triangular_solve_gpu,
+    triangular_solve_metal
+);
+
+#[test]
+fn cholesky_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Tensor::new(&[[4f64, 2., 0.4], [2., 5., 1.], [0.4, 1., 3.]], device)?;
+    let w = Tensor::new(&[[1f64, 0., 0.], [-2., 0.5, 0.], [3., 1., 2.]], device)?;
+    let f =
+        |a: &Tensor| -> Result<f64> { Ok((a.cholesky()? * &w)?.sum_all()?.to_scalar::<f64>()?) };
+    let a = Var::from_tensor(&a)?;
+    let grads = (a.cholesky()? * &w)?.sum_all()?.backward()?;
+    let grad = grads.get(&a).context("no grad for a")?.to_vec2::<f64>()?;
+    // Only the lower triangle is used in the forward pass, the gradient is symmetric so the
+    // derivative with respect to an off-diagonal element is split between both triangles.
+    let num_grad = numerical_grad(f, a.as_tensor())?;
+    let mut expected = vec![];
+    for (i, row) in grad.iter().enumerate() {
+        for (j, g) in row.iter().enumerate() {
+            expected.push(match i.cmp(&j) {
+                std::cmp::Ordering::Less => 0.,
+                std::cmp::Ordering::Equal => *g,
+                std::cmp::Ordering::Greater => g + grad[j][i],
+            })
+        }
+    }
+    assert_close(&num_grad, &expected);
+    Ok(())
+}
+
+#[test]
+fn triangular_solve_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Var::new(&[[2f64, 9., 9.], [6., 1., 9.], [-8., 5., 3.]], device)?;
+    let b = Var::new(&[[2f64, 4.], [7., 13.], [-2., 6.]], device)?;
+    let w = Tensor::new(&[[1f64, -1.], [0.5, 2.], [3., 1.]], device)?;
+    let grads = (a.triangular_solve(&b, false)? * &w)?
+        .sum_all()?
+        .backward()?;
+    let grad_a = grads.get(&a).context("no grad for a")?;
+    let grad_b = grads.get(&b).context("no grad for b")?;
+    let num_grad_a = numerical_grad(
+        |a| {
+            Ok((a.triangular_solve(&b, false)? * &w)?
+                .sum_all()?
+                .to_scalar()?)
+        },
+        a.as_tensor(),
+    )?;
+    let num_grad_b = numerical_grad(
+        |b| {
+            Ok((a.triangular_solve(b, false)? * &w)?
+                .sum_all()?
+                .to_scalar()?)
+        },
+        b.as_tensor(),
+    )?;
+    assert_close(&grad_a.flatten_all()?.to_vec1()?, &num_grad_a);
+    assert_close(&grad_b.flatten_all()?.to_vec1()?, &num_grad_b);
+    Ok(())
+}
+
+// Checks that u * diag(s) * vt is equal to a and that u and v have orthonormal columns.
+fn check_svd(a: &Tensor, full_matrices: bool) -> Result<(Tensor, Tensor, Tensor)> {
+    let (u, s, vt) = a.svd(full_matrices)?;
+    let k = s.dim(D::Minus1)?;
+    let us = u
+        .narrow(D::Minus1, 0, k)?
+        .broadcast_mul(&s.unsqueeze(D::Minus2)?)?;
+    let usvt = us.matmul(&vt.narrow(D::Minus2, 0, k)?)?;
+    let diff = (usvt - a)?.abs()?.max_keepdim(D::Minus1)?.flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-4);
+    for q in [u.t()?, vt.clone()] {
+        let n = q.dim(D::Minus2)?;
+        let eye = Tensor::eye(n, q.dtype(), q.device())?;
+        let diff = q
+            .matmul(&q.t()?)?
+            .broadcast_sub(&eye)?
+            .abs()?
+            .flatten_all()?;
+        assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-4);
+    }
+    Ok((u, s, vt))
+}
+
+fn svd(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[3f32, 2., 2.], [2., 3., -2.]], device)?;
+    let (u, s, vt) = check_svd(&a, false)?;
+    assert_eq!(u.dims(), [2, 2]);
+    assert_eq!(vt.dims(), [2, 3]);
+    assert_eq!(test_utils::to_vec1_round(&s, 4)?, [5., 3.]);
+    let (u, s, vt) = check_svd(&a.t()?, true)?;
+    assert_eq!(u.dims(), [3, 3]);
+    assert_eq!(vt.dims(), [2, 2]);
+    assert_eq!(test_utils::to_vec1_round(&s, 4)?, [5., 3.]);
+    // Batched and rank deficient inputs.
+    let a = Tensor::new(
+        &[
+            [[1f32, 2., 3.], [2., 4., 6.], [1., 1., 1.], [0., 0., 0.]],
+            [[1., 0., 0.], [0., 0., 0.], [0., 0., 2.], [0., 0., 0.]],
+        ],
+        device,
+    )?;
+    let (_, s, _) = check_svd(&a, false)?;
+    assert_eq!(s.dims(), [2, 3]);
+    assert_eq!(test_utils::to_vec1_round(&s.get(1)?, 4)?, [2., 1., 0.]);
+    let (u, _, vt) = check_svd(&a, true)?;
+    assert_eq!(u.dims(), [2, 4, 4]);
+    assert_eq!(vt.dims(), [2, 3, 3]);
+    Ok(())
+}
+
+test_device!(svd, svd_cpu, svd_gpu, svd_metal);
+
+fn qr(device: &Device) -> Result<()> {
+    let a = Tensor::new(
+        &[
+            [[12f32, -51., 4.], [6., 167., -68.], [-4., 24., -41.]],
+            [[1., 2., 3.], [2., 4., 6.], [0., 0., 1.]],
+        ],
+        device,
+    )?;
+    let (q, r) = a.qr(false)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&r.get(0)?, 4)?,
+        [[14., 21., -14.], [0., 175., -70.], [0., 0., 35.]]
+    );
+    assert_eq!(
+        test_utils::to_vec3_round(&q.matmul(&r)?, 3)?,
+        test_utils::to_vec3_round(&a, 3)?
+    );
+    let eye = Tensor::eye(3, q.dtype(), device)?;
+    let qtq = q.t()?.matmul(&q)?.broadcast_sub(&eye)?;
+    assert!(qtq.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()? < 1e-5);
+
+    let a = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let (q, r) = a.qr(false)?;
+    assert_eq!(q.dims(), [3, 2]);
+    assert_eq!(r.dims(), [2, 2]);
+    assert_eq!(
+        test_utils::to_vec2_round(&q.matmul(&r)?, 4)?,
+        a.to_vec2::<f32>()?
+    );
+    let (q, r) = a.qr(true)?;
+    assert_eq!(q.dims(), [3, 3]);
+    assert_eq!(test_utils::to_vec1_round(&r.get(2)?, 4)?, [0., 0.]);
+    assert_eq!(
+        test_utils::to_vec2_round(&q.matmul(&r)?, 4)?,
+        a.to_vec2::<f32>()?
+    );
+    let (q, r) = a.t()?.qr(false)?;
+    assert_eq!(q.dims(), [2, 2]);
+    assert_eq!(r.dims(), [2, 3]);
+    Ok(())
+}
+
+test_device!(qr, qr_cpu, qr_gpu, qr_metal);
+
+#[test]
+fn svd_qr_no_grad() -> Result<()> {
+    // These ops do not record a backward op so tracked inputs are rejected.
+    let a = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.]], &Device::Cpu)?;
+    assert!(a.svd(false).is_err());
+    assert!(a.qr(false).is_err());
+    assert!(a.affine(2., 0.)?.qr(true).is_err());
+    let (u, s, vt) = a.detach().svd(false)?;
+    assert_eq!(
+        (u.dims(), s.dims(), vt.dims()),
+        (&[3, 2][..], &[2][..], &[2, 2][..])
+    );
+    let (q, r) = candle_core::no_grad(|| a.qr(false))?;
+    assert_eq!((q.dims(), r.dims()), (&[3, 2][..], &[2, 2][..]));
+    Ok(())
+}
+
+fn eigh(device: &Device) -> Result<()> {
+    let a = Tensor::new(
+        &[
+            [[2f32, 0., 0.], [0., 3., 4.], [0., 4., 9.]],
+            [[4., 1., -2.], [1., 2., 0.], [-2., 0., 3.]],
+        ],
+        device,
+    )?;
+    let (values, vectors) = a.eigh()?;
+    assert_eq!(values.dims(), [2, 3]);
+    assert_eq!(vectors.dims(), [2, 3, 3]);
+    assert_eq!(
+        test_utils::to_vec1_round(&values.get(0)?, 4)?,
+        [1., 2., 11.]
+    );
+    // a v = v diag(w) and the eigenvectors are orthonormal.
+    let av = a.matmul(&vectors)?;
+    let vw = vectors.broadcast_mul(&values.unsqueeze(1)?)?;
+    let diff = (av - vw)?.abs()?.flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-4);
+    let eye = Tensor::eye(3, DType::F32, device)?;
+    let diff = vectors
+        .t()?
+        .matmul(&vectors)?
+        .broadcast_sub(&eye)?
+        .abs()?
+        .flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-5);
+    // Only the lower triangle is read.
+    let lower = Tensor::new(&[[2f32, 7.], [1., 2.]], device)?;
+    let (values, _) = lower.eigh()?;
+    assert_eq!(test_utils::to_vec1_round(&values, 4)?, [1., 3.]);
+    assert!(Tensor::zeros((2, 3), DType::F32, device)?.eigh().is_err());
+    Ok(())
+}
+
+test_device!(eigh, eigh_cpu, eigh_gpu, eigh_metal);
+
+#[test]
+fn eigh_rejected_inputs() -> Result<()> {
+    let dev = &Device::Cpu;
+    // A batch of 3x3 complex matrices in the interleaved layout.
+    let a = Tensor::zeros((4, 3, 3, 2), DType::F32, dev)?;
+    let err = a.eigh().unwrap_err().to_string();
+    assert!(err.contains("real symmetric"), "{err}");
+    let a = Var::new(&[[2f32, 1.], [1., 2.]], dev)?;
+    assert!(a.eigh().is_err());
+    let (values, _) = a.detach().eigh()?;
+    assert_eq!(test_utils::to_vec1_round(&values, 4)?, [1., 3.]);
+    Ok(())
+}
+
+fn inverse_det(device: &Device) -> Result<()> {
+    let a = Tensor::new(
+        &[
+            [[0f32, 2., 1.], [1., 1., 0.], [3., 0., 1.]],
+            [[2., 0., 0.], [0., 4., 0.], [0., 0., 0.5]],
+        ],
+        device,
+    )?;
+    let inv = a.inverse()?;
+    let eye = Tensor::eye(3, DType::F32, device)?;
+    let diff = a.matmul(&inv)?.broadcast_sub(&eye)?.abs()?.flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-5);
+    assert_eq!(
+        test_utils::to_vec2_round(&inv.get(1)?, 4)?,
+        [[0.5, 0., 0.], [0., 0.25, 0.], [0., 0., 2.]]
+    );
+    assert_eq!(test_utils::to_vec1_round(&a.det()?, 4)?, [-5., 4.]);
+    let (sign, logdet) = a.slogdet()?;
+    assert_eq!(sign.to_vec1::<f32>()?, [-1., 1.]);
+    assert_eq!(
+        test_utils::to_vec1_round(&logdet, 4)?,
+        [5f32.ln(), 4f32.ln()]
+            .iter()
+            .map(|v| (v * 1e4).round() / 1e4)
+            .collect::<Vec<_>>()
+    );
+    let singular = Tensor::new(&[[1f32, 2.], [2., 4.]], device)?;
+    assert!(singular.inverse().is_err());
+    assert_eq!(singular.det()?.to_scalar::<f32>()?, 0.);
+    let (sign, logdet) = singular.slogdet()?;
+    assert_eq!(sign.to_scalar::<f32>()?, 0.);
+    assert_eq!(logdet.to_scalar::<f32>()?, f32::NEG_INFINITY);
+    Ok(())
+}
+
+test_device!(
+    inverse_det,
+    inverse_det_cpu,
+    inverse_det_gpu,
+    inverse_det_metal
+);
+
+#[test]
+fn inverse_det_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Var::new(&[[2f64, 1., 0.5], [-1., 3., 1.], [0.2, 0.4, 1.5]], device)?;
+    let w = Tensor::new(&[[1f64, -1., 0.5], [2., 0., 1.], [-0.5, 3., 1.]], device)?;
+    let grads = (a.inverse()? * &w)?.sum_all()?.backward()?;
+    let grad_a = grads.get(&a).context("no grad for a")?;
+    let num_grad = numerical_grad(
+        |a| Ok((a.inverse()? * &w)?.sum_all()?.to_scalar()?),
+        a.as_tensor(),
+    )?;
+    assert_close(&grad_a.flatten_all()?.to_vec1()?, &num_grad);
+
+    let grads = a.det()?.backward()?;
+    let grad_a = grads.get(&a).context("no grad for a")?;
+    let num_grad = numerical_grad(|a| Ok(a.det()?.to_scalar()?), a.as_tensor())?;
+    assert_close(&grad_a.flatten_all()?.to_vec1()?, &num_grad);
+
+    let grads = a.slogdet()?.1.backward()?;
+    let grad_a = grads.get(&a).context("no grad for a")?;
+    let num_grad = numerical_grad(|a| Ok(a.slogdet()?.1.to_scalar()?), a.as_tensor())?;
+    assert_close(&grad_a.flatten_all()?.to_vec1()?, &num_grad);
+    Ok(())
+}
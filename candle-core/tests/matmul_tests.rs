@@ -1,4 +1,4 @@
-use candle_core::{test_device, DType, Device, IndexOp, Result, Tensor};
+use candle_core::{test_device, DType, Device, IndexOp, Result, Tensor, D};
 
 fn matmul(device: &Device) -> Result<()> {
     let data = vec![1.0f32, 2.0, 3.0, 4.0];
@@ -104,3 +104,187 @@ test_device!(
 );
 test_device!(squeeze_mm, squeeze_mm_cpu, squeeze_mm_gpu, squeeze_mm_metal);
 test_device!(mm_layout, mm_layout_cpu, mm_layout_gpu, mm_layout_metal);
+
+fn einsum(device: &Device) -> Result<()> {
+    let a = Tensor::arange(0f32, 24f32, device)?.reshape((2, 3, 4))?;
+    let b = Tensor::arange(0f32, 40f32, device)?.reshape((2, 4, 5))?;
+    let c = Tensor::einsum("bij,bjk->bik", &[&a, &b])?;
+    assert_eq!(c.to_vec3::<f32>()?, a.matmul(&b)?.to_vec3::<f32>()?);
+
+    // Attention style contraction with implicit output.
+    let q = Tensor::arange(0f32, 12f32, device)?.reshape((1, 2, 3, 2))?;
+    let k = Tensor::arange(0f32, 8f32, device)?.reshape((1, 2, 2, 2))?;
+    let att = Tensor::einsum("bhqd,bhkd->bhqk", &[&q, &k])?;
+    let expected = q.matmul(&k.t()?)?;
+    assert_eq!(att.dims(), &[1, 2, 3, 2]);
+    assert_eq!(
+        att.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    let m = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.], [7., 8., 9.]], device)?;
+    assert_eq!(Tensor::einsum("ii", &[&m])?.to_scalar::<f32>()?, 15.);
+    assert_eq!(
+        Tensor::einsum("ii->i", &[&m])?.to_vec1::<f32>()?,
+        [1., 5., 9.]
+    );
+    assert_eq!(
+        Tensor::einsum("ij->j", &[&m])?.to_vec1::<f32>()?,
+        [12., 15., 18.]
+    );
+    assert_eq!(
+        Tensor::einsum("ji", &[&m])?.to_vec2::<f32>()?,
+        [[1., 4., 7.], [2., 5., 8.], [3., 6., 9.]]
+    );
+
+    // Outer product and a three operand contraction.
+    let x = Tensor::new(&[1f32, 2.], device)?;
+    let y = Tensor::new(&[3f32, 4., 5.], device)?;
+    assert_eq!(
+        Tensor::einsum("i,j->ij", &[&x, &y])?.to_vec2::<f32>()?,
+        [[3., 4., 5.], [6., 8., 10.]]
+    );
+    let z = Tensor::einsum("i,ij,j->", &[&y, &m, &y])?;
+    let expected = y.unsqueeze(0)?.matmul(&m)?.matmul(&y.unsqueeze(1)?)?;
+    assert_eq!(
+        z.to_scalar::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?[0]
+    );
+
+    // Ellipsis with broadcasting on the leading dimensions.
+    let e = Tensor::einsum("...ij,jk->...ik", &[&a, &b.get(0)?])?;
+    assert_eq!(e.dims(), &[2, 3, 5]);
+    assert_eq!(
+        e.to_vec3::<f32>()?,
+        a.broadcast_matmul(&b.get(0)?)?.to_vec3::<f32>()?
+    );
+
+    assert!(Tensor::einsum("ij,jk->ik", &[&a, &b]).is_err());
+    assert!(Tensor::einsum("ij->ik", &[&m]).is_err());
+    Ok(())
+}
+
+test_device!(einsum, einsum_cpu, einsum_gpu, einsum_metal);
+
+fn tensordot_kron(device: &Device) -> Result<()> {
+    let a = Tensor::arange(0f32, 24f32, device)?.reshape((2, 3, 4))?;
+    let b = Tensor::arange(0f32, 60f32, device)?.reshape((4, 3, 5))?;
+    let c = a.tensordot(&b, ((1, 2), (1, 0)))?;
+    let expected = Tensor::einsum("ijk,kjl->il", &[&a, &b])?;
+    assert_eq!(c.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    let c = a.tensordot(&b, ([2], [0]))?;
+    assert_eq!(c.dims(), &[2, 3, 3, 5]);
+    // No contracted dims is an outer product.
+    let x = Tensor::new(&[1f32, 2.], device)?;
+    let y = Tensor::new(&[3f32, 4., 5.], device)?;
+    assert_eq!(
+        x.tensordot(&y, ((), ()))?.to_vec2::<f32>()?,
+        [[3., 4., 5.], [6., 8., 10.]]
+    );
+    assert!(a.tensordot(&b, ([1], [0])).is_err());
+    assert!(a.tensordot(&b, ([1, 2], [1])).is_err());
+
+    assert_eq!(x.kron(&y)?.to_vec1::<f32>()?, [3., 4., 5., 6., 8., 10.]);
+    let m = Tensor::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let eye = Tensor::eye(2, DType::F32, device)?;
+    assert_eq!(
+        eye.kron(&m)?.to_vec2::<f32>()?,
+        [
+            [1., 2., 0., 0.],
+            [3., 4., 0., 0.],
+            [0., 0., 1., 2.],
+            [0., 0., 3., 4.]
+        ]
+    );
+    // The vector is treated as a (1, 2) matrix.
+    assert_eq!(
+        m.kron(&x)?.to_vec2::<f32>()?,
+        [[1., 2., 2., 4.], [3., 6., 4., 8.]]
+    );
+    Ok(())
+}
+
+fn outer_cross(device: &Device) -> Result<()> {
+    let x = Tensor::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let y = Tensor::new(&[1f32, -1., 2.], device)?;
+    assert_eq!(
+        x.outer(&y)?.to_vec3::<f32>()?,
+        [
+            [[1., -1., 2.], [2., -2., 4.]],
+            [[3., -3., 6.], [4., -4., 8.]]
+        ]
+    );
+    let y = Tensor::new(&[[1f32], [10.]], device)?;
+    assert_eq!(
+        x.outer(&y)?.to_vec3::<f32>()?,
+        [[[1.], [2.]], [[30.], [40.]]]
+    );
+
+    let a = Tensor::new(&[[1f32, 2., 3.], [-1., 0., 2.]], device)?;
+    let b = Tensor::new(&[[4f32, 5., 6.], [3., 1., 1.]], device)?;
+    let c = a.cross(&b, 1)?;
+    assert_eq!(c.to_vec2::<f32>()?, [[-3., 6., -3.], [-2., 7., -1.]]);
+    // The cross product is orthogonal to both inputs.
+    assert_eq!((&c * &a)?.sum(1)?.to_vec1::<f32>()?, [0., 0.]);
+    let c = a.t()?.cross(&b.t()?, 0)?;
+    assert_eq!(c.t()?.to_vec2::<f32>()?, [[-3., 6., -3.], [-2., 7., -1.]]);
+    let c = a.cross(&b.narrow(0, 0, 1)?, D::Minus1)?;
+    assert_eq!(c.to_vec2::<f32>()?, [[-3., 6., -3.], [-10., 14., -5.]]);
+    assert!(x.cross(&x, 1).is_err());
+    Ok(())
+}
+
+test_device!(
+    outer_cross,
+    outer_cross_cpu,
+    outer_cross_gpu,
+    outer_cross_metal
+);
+test_device!(
+    tensordot_kron,
+    tensordot_kron_cpu,
+    tensordot_kron_gpu,
+    tensordot_kron_metal
+);
+
+#[test]
+fn matmul_num_threads() -> Result<()> {
+    let lhs = Tensor::arange(0f32, 256., &Device::Cpu)?.reshape((16, 16))?;
+    let rhs = lhs.t()?.affine(0.5, -3.)?;
+    let expected = lhs.matmul(&rhs)?.to_vec2::<f32>()?;
+    for n in [1, 3] {
+        let res = candle_core::utils::with_num_threads(n, || {
+            assert_eq!(candle_core::utils::get_num_threads(), n);
+            lhs.matmul(&rhs)
+        })?;
+        assert_eq!(res.to_vec2::<f32>()?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn matmul_precision() -> Result<()> {
+    use candle_core::utils::{self, MatmulPrecision};
+    let lhs = Tensor::arange(0f32, 64., &Device::Cpu)?.reshape((8, 8))?;
+    let lhs = lhs.to_dtype(DType::F16)?;
+    let expected = lhs.matmul(&lhs)?.to_dtype(DType::F32)?.to_vec2::<f32>()?;
+    let fast = MatmulPrecision::FAST;
+    let res = utils::with_matmul_precision(fast, || {
+        assert_eq!(utils::matmul_precision(), fast);
+        lhs.matmul(&lhs)
+    })?;
+    // The cpu backend always accumulates in f32.
+    assert_eq!(res.to_dtype(DType::F32)?.to_vec2::<f32>()?, expected);
+    assert_eq!(utils::matmul_precision(), MatmulPrecision::default());
+
+    let p = MatmulPrecision {
+        tf32: true,
+        ..Default::default()
+    };
+    utils::set_matmul_precision(p);
+    assert_eq!(utils::matmul_precision(), p);
+    utils::with_matmul_precision(fast, || assert_eq!(utils::matmul_precision(), fast));
+    assert_eq!(utils::matmul_precision(), p);
+    utils::set_matmul_precision(MatmulPrecision::default());
+    Ok(())
+}
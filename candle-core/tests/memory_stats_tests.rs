@@ -0,0 +1,19 @@
+#![cfg(feature = "tracking-allocator")]
+// This test lives in its own binary as the memory usage covers the whole process, the other
+// tests would allocate concurrently.
+use candle_core::{DType, Device, Result, Tensor};
+
+#[test]
+fn memory_stats_cpu() -> Result<()> {
+    let before = Device::Cpu.memory_stats()?;
+    let t = Tensor::zeros(1 << 20, DType::F32, &Device::Cpu)?;
+    let after = Device::Cpu.memory_stats()?;
+    assert!(after.allocated >= before.allocated + (4 << 20));
+    assert!(after.peak_allocated >= after.allocated);
+    drop(t);
+    Device::Cpu.reset_peak_memory_stats()?;
+    let stats = Device::Cpu.memory_stats()?;
+    assert!(stats.allocated + (4 << 20) <= after.allocated);
+    assert!(stats.peak_allocated < after.peak_allocated);
+    Ok(())
+}
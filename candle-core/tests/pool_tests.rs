@@ -1,4 +1,4 @@
-use candle_core::{test_device, test_utils, Device, IndexOp, Result, Tensor};
+use candle_core::{test_device, test_utils, Device, IndexOp, InterpolateMode, Result, Tensor};
 
 // https://github.com/huggingface/candle/issues/364
 fn avg_pool2d(dev: &Device) -> Result<()> {
@@ -101,6 +101,63 @@ fn upsample_nearest2d(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+fn interpolate2d_modes(dev: &Device) -> Result<()> {
+    let t = Tensor::arange(1f32, 5f32, dev)?.reshape((1, 1, 2, 2))?;
+    // The expectations obtained from pytorch.
+    let bilinear = InterpolateMode::Bilinear {
+        align_corners: false,
+        antialias: false,
+    };
+    let res = t.interpolate2d_with_mode(4, 4, bilinear)?.i(0)?.i(0)?;
+    assert_eq!(
+        res.to_vec2::<f32>()?,
+        [
+            [1.0, 1.25, 1.75, 2.0],
+            [1.5, 1.75, 2.25, 2.5],
+            [2.5, 2.75, 3.25, 3.5],
+            [3.0, 3.25, 3.75, 4.0]
+        ]
+    );
+    let bilinear = InterpolateMode::Bilinear {
+        align_corners: true,
+        antialias: false,
+    };
+    let res = t.interpolate2d_with_mode(3, 4, bilinear)?.i(0)?.i(0)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&res, 4)?,
+        [
+            [1.0, 1.3333, 1.6667, 2.0],
+            [2.0, 2.3333, 2.6667, 3.0],
+            [3.0, 3.3333, 3.6667, 4.0]
+        ]
+    );
+    let bicubic = InterpolateMode::Bicubic {
+        align_corners: false,
+        antialias: false,
+    };
+    let res = t.interpolate2d_with_mode(4, 4, bicubic)?.i(0)?.i(0)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&res, 4)?,
+        [
+            [0.6836, 1.0156, 1.5625, 1.8945],
+            [1.3477, 1.6797, 2.2266, 2.5586],
+            [2.4414, 2.7734, 3.3203, 3.6523],
+            [3.1055, 3.4375, 3.9844, 4.3164]
+        ]
+    );
+    // Antialiased downsampling averages over all the input pixels.
+    let t = Tensor::arange(0f32, 4f32, dev)?.reshape((1, 1, 1, 4))?;
+    let bilinear = InterpolateMode::Bilinear {
+        align_corners: false,
+        antialias: true,
+    };
+    let res = t.interpolate2d_with_mode(1, 2, bilinear)?.i(0)?.i(0)?;
+    assert_eq!(test_utils::to_vec2_round(&res, 4)?, [[0.7143, 2.2857]]);
+    let res = t.interpolate2d_with_mode(2, 2, InterpolateMode::Nearest)?;
+    assert_eq!(res.dims(), [1, 1, 2, 2]);
+    Ok(())
+}
+
 test_device!(avg_pool2d, avg_pool2d_cpu, avg_pool2d_gpu, avg_pool2d_metal);
 test_device!(
     avg_pool2d_pytorch,
@@ -115,3 +172,9 @@ test_device!(
     upsample_nearest2d_gpu,
     upsample_nearest2d_metal
 );
+test_device!(
+    interpolate2d_modes,
+    interpolate2d_modes_cpu,
+    interpolate2d_modes_gpu,
+    interpolate2d_modes_metal
+);
@@ -0,0 +1,84 @@
+use candle_core::{DType, Device, Generator, Result, Tensor};
+
+#[test]
+fn cpu_seed() -> Result<()> {
+    let device = &Device::Cpu;
+    device.set_seed(299792458)?;
+    let t1 = Tensor::randn(0f32, 1., 5, device)?;
+    let u1 = Tensor::rand(0f64, 1., 5, device)?;
+    device.set_seed(299792458)?;
+    let t2 = Tensor::randn(0f32, 1., 5, device)?;
+    let u2 = Tensor::rand(0f64, 1., 5, device)?;
+    assert_eq!(t1.to_vec1::<f32>()?, t2.to_vec1::<f32>()?);
+    assert_eq!(u1.to_vec1::<f64>()?, u2.to_vec1::<f64>()?);
+
+    // The seed is per thread, drawing values on another thread does not consume the sequence.
+    device.set_seed(299792458)?;
+    let t3 = Tensor::randn(0f32, 1., 5, device)?;
+    std::thread::spawn(|| Tensor::randn(0f32, 1., 5, &Device::Cpu))
+        .join()
+        .unwrap()?;
+    let u3 = Tensor::rand(0f64, 1., 5, device)?;
+    assert_eq!(t1.to_vec1::<f32>()?, t3.to_vec1::<f32>()?);
+    assert_eq!(u1.to_vec1::<f64>()?, u3.to_vec1::<f64>()?);
+
+    candle_core::cpu_backend::CpuDevice::clear_seed();
+    let t4 = Tensor::randn(0f32, 1., 5, device)?;
+    assert_ne!(t1.to_vec1::<f32>()?, t4.to_vec1::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn generator() -> Result<()> {
+    let device = &Device::Cpu;
+    let g = Generator::new(42);
+    let t1 = g.rand_uniform(-1., 1., (3, 4), DType::F32, device)?;
+    let t2 = g.rand_uniform(-1., 1., (3, 4), DType::F32, device)?;
+    assert_ne!(t1.to_vec2::<f32>()?, t2.to_vec2::<f32>()?);
+    let values = t1.flatten_all()?.to_vec1::<f32>()?;
+    assert!(values.iter().all(|v| (-1. ..1.).contains(v)));
+    // Resetting the seed or using a new generator replays the same sequence.
+    g.set_seed(42);
+    let t3 = g.rand_uniform(-1., 1., (3, 4), DType::F32, device)?;
+    assert_eq!(t1.to_vec2::<f32>()?, t3.to_vec2::<f32>()?);
+    let t4 = Generator::new(42).rand_uniform(-1., 1., (3, 4), DType::F32, device)?;
+    assert_eq!(t1.to_vec2::<f32>()?, t4.to_vec2::<f32>()?);
+    assert!(g.rand_normal(0., 1., 2, DType::U32, device).is_err());
+    let t = g.rand_normal(3., 0.5, 10000, DType::F64, device)?;
+    let mean = t.mean_all()?.to_scalar::<f64>()?;
+    assert!((mean - 3.).abs() < 0.05, "{mean}");
+    Ok(())
+}
+
+#[test]
+fn generator_distributions() -> Result<()> {
+    let device = &Device::Cpu;
+    let g = Generator::new(1337);
+    let probs = Tensor::new(&[0f32, 1., 0.25], device)?.broadcast_as((4000, 3))?;
+    let samples = g.bernoulli(&probs)?;
+    assert_eq!(samples.dims(), &[4000, 3]);
+    let counts = samples.sum(0)?.to_vec1::<f32>()?;
+    assert_eq!(counts[..2], [0., 4000.]);
+    assert!((900. ..1100.).contains(&counts[2]), "{counts:?}");
+    assert!(g.bernoulli(&Tensor::new(&[1.5f32], device)?).is_err());
+
+    let concentration = Tensor::new(&[0.5f64, 2., 10.], device)?.broadcast_as((4000, 3))?;
+    let samples = g.gamma(&concentration)?;
+    assert!(samples.min_keepdim(0)?.flatten_all()?.to_vec1::<f64>()?[0] > 0.);
+    // The mean of the gamma distribution is the concentration.
+    let means = samples.mean(0)?.to_vec1::<f64>()?;
+    assert!((means[0] - 0.5).abs() < 0.1, "{means:?}");
+    assert!((means[2] - 10.).abs() < 0.5, "{means:?}");
+
+    let rates = Tensor::new(&[0f32, 3.], device)?.broadcast_as((4000, 2))?;
+    let samples = g.poisson(&rates)?;
+    assert_eq!(samples.dtype(), DType::F32);
+    assert_eq!(
+        samples.round()?.to_vec2::<f32>()?,
+        samples.to_vec2::<f32>()?
+    );
+    let means = samples.mean(0)?.to_vec1::<f32>()?;
+    assert_eq!(means[0], 0.);
+    assert!((means[1] - 3.).abs() < 0.2, "{means:?}");
+    Ok(())
+}
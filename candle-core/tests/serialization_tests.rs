@@ -69,3 +69,27 @@ fn safetensors() -> Result<()> {
     assert_eq!(diff, 0f32);
     Ok(())
 }
+
+#[test]
+fn safetensors_fp8() -> Result<()> {
+    use candle_core::DType;
+
+    let tmp_file = TmpFile::create("st_fp8");
+    let t = Tensor::arange(0f32, 24f32, &candle_core::Device::Cpu)?;
+    let t1 = t.to_dtype(DType::F8E4M3)?;
+    let t2 = t.to_dtype(DType::F8E5M2)?;
+    candle_core::safetensors::save(
+        &std::collections::HashMap::from([("t1", t1.clone()), ("t2", t2.clone())]),
+        &tmp_file,
+    )?;
+    let st = candle_core::safetensors::load(&tmp_file, &candle_core::Device::Cpu)?;
+    for (name, t) in [("t1", t1), ("t2", t2)] {
+        let t_ = st.get(name).unwrap();
+        assert_eq!(t_.dtype(), t.dtype());
+        assert_eq!(
+            t_.to_dtype(DType::F32)?.to_vec1::<f32>()?,
+            t.to_dtype(DType::F32)?.to_vec1::<f32>()?
+        );
+    }
+    Ok(())
+}
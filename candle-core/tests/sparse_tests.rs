@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use candle_core::sparse::{SparseLayout, SparseTensor};
+use candle_core::{test_device, DType, Device, Tensor, Var};
+
+fn sparse_dense(device: &Device) -> Result<()> {
+    let dense = Tensor::new(
+        &[[0f32, 2., 0.], [1., 0., 0.], [0., 0., 0.], [0., 3., 4.]],
+        device,
+    )?;
+    let sparse = SparseTensor::from_dense(&dense)?;
+    assert_eq!(sparse.nnz(), 4);
+    assert_eq!(sparse.shape(), (4, 3));
+    assert_eq!(sparse.values().to_vec1::<f32>()?, [2., 1., 3., 4.]);
+    assert_eq!(
+        sparse.to_dense()?.to_vec2::<f32>()?,
+        dense.to_vec2::<f32>()?
+    );
+
+    let csr = sparse.to_csr()?;
+    match csr.layout() {
+        SparseLayout::Csr {
+            row_offsets,
+            col_indices,
+        } => {
+            assert_eq!(row_offsets.to_vec1::<i64>()?, [0, 1, 2, 2, 4]);
+            assert_eq!(col_indices.to_vec1::<i64>()?, [1, 0, 1, 2]);
+        }
+        SparseLayout::Coo { .. } => anyhow::bail!("expected a csr layout"),
+    }
+    assert_eq!(csr.to_dense()?.to_vec2::<f32>()?, dense.to_vec2::<f32>()?);
+    assert_eq!(
+        csr.to_coo()?.to_dense()?.to_vec2::<f32>()?,
+        dense.to_vec2::<f32>()?
+    );
+
+    let empty = SparseTensor::from_dense(&Tensor::zeros((2, 2), DType::F32, device)?)?;
+    assert_eq!(empty.nnz(), 0);
+    assert_eq!(
+        empty.to_csr()?.to_dense()?.to_vec2::<f32>()?,
+        [[0., 0.], [0., 0.]]
+    );
+    Ok(())
+}
+
+fn sparse_matmul(device: &Device) -> Result<()> {
+    // Unsorted coo entries with a duplicate position that gets summed.
+    let indices = Tensor::new(&[[2u32, 0, 0, 2], [1, 2, 0, 1]], device)?;
+    let values = Tensor::new(&[1f32, 2., 3., 4.], device)?;
+    let sparse = SparseTensor::coo(&indices, &values, (3, 3))?;
+    assert_eq!(
+        sparse.to_dense()?.to_vec2::<f32>()?,
+        [[3., 0., 2.], [0., 0., 0.], [0., 5., 0.]]
+    );
+    let rhs = Tensor::arange(0f32, 6., device)?.reshape((3, 2))?;
+    let expected = sparse.to_dense()?.matmul(&rhs)?.to_vec2::<f32>()?;
+    assert_eq!(sparse.matmul(&rhs)?.to_vec2::<f32>()?, expected);
+    assert_eq!(sparse.to_csr()?.matmul(&rhs)?.to_vec2::<f32>()?, expected);
+    assert!(sparse.matmul(&rhs.t()?).is_err());
+    assert!(SparseTensor::coo(&indices, &values.narrow(0, 0, 3)?, (3, 3)).is_err());
+    Ok(())
+}
+
+test_device!(
+    sparse_dense,
+    sparse_dense_cpu,
+    sparse_dense_gpu,
+    sparse_dense_metal
+);
+test_device!(
+    sparse_matmul,
+    sparse_matmul_cpu,
+    sparse_matmul_gpu,
+    sparse_matmul_metal
+);
+
+#[test]
+fn sparse_matmul_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let row_offsets = Tensor::new(&[0u32, 1, 3], device)?;
+    let col_indices = Tensor::new(&[1u32, 0, 1], device)?;
+    let values = Var::new(&[2f32, -1., 3.], device)?;
+    let rhs = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let sparse = SparseTensor::csr(&row_offsets, &col_indices, &values, (2, 2))?;
+    let res = sparse.matmul(&rhs)?;
+    assert_eq!(res.to_vec2::<f32>()?, [[6., 8.], [8., 10.]]);
+    let grads = res.sum_all()?.backward()?;
+    let grad_values = grads.get(&values).context("no grad for values")?;
+    let grad_rhs = grads.get(&rhs).context("no grad for rhs")?;
+    assert_eq!(grad_values.to_vec1::<f32>()?, [7., 3., 7.]);
+    assert_eq!(grad_rhs.to_vec2::<f32>()?, [[-1., -1.], [5., 5.]]);
+    Ok(())
+}
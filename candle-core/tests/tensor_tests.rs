@@ -1,4 +1,4 @@
-use candle_core::{test_device, test_utils, DType, Device, IndexOp, Result, Tensor, D};
+use candle_core::{test_device, test_utils, DType, Device, IndexOp, PadMode, Result, Tensor, D};
 
 fn zeros(device: &Device) -> Result<()> {
     let tensor = Tensor::zeros((5, 2), DType::F32, device)?;
@@ -57,6 +57,64 @@ fn arange(device: &Device) -> Result<()> {
         Tensor::arange_step(5i64, 0i64, -1, device)?.to_vec1::<i64>()?,
         [5, 4, 3, 2, 1],
     );
+    assert_eq!(
+        Tensor::linspace(-1f32, 1., 5, device)?.to_vec1::<f32>()?,
+        [-1., -0.5, 0., 0.5, 1.],
+    );
+    assert_eq!(
+        Tensor::linspace(0u32, 10, 3, device)?.to_vec1::<u32>()?,
+        [0, 5, 10],
+    );
+    assert_eq!(
+        Tensor::linspace(2f64, 3., 1, device)?.to_vec1::<f64>()?,
+        [2.]
+    );
+    assert_eq!(Tensor::linspace(0f32, 1., 0, device)?.dims(), [0]);
+    assert_eq!(
+        Tensor::logspace(-2f32, 2., 5, 2., device)?.to_vec1::<f32>()?,
+        [0.25, 0.5, 1., 2., 4.],
+    );
+    let t = device.linspace(0f32, 1., 3)?;
+    assert!(t.device().same_device(device));
+    assert_eq!(t.to_vec1::<f32>()?, [0., 0.5, 1.]);
+    assert_eq!(
+        device.logspace(0f64, 2., 3, 10.)?.to_vec1::<f64>()?,
+        [1., 10., 100.],
+    );
+    Ok(())
+}
+
+fn meshgrid(device: &Device) -> Result<()> {
+    use candle_core::Indexing;
+    let x = device.linspace(0f32, 1., 2)?;
+    let y = device.linspace(0f32, 2., 3)?;
+    let z = Tensor::new(&[5f32, 6., 7., 8.], device)?;
+    let xy = Tensor::meshgrid(&[&x, &y, &z], Indexing::Xy)?;
+    let ij = Tensor::meshgrid(&[&x, &y, &z], Indexing::Ij)?;
+    for grid in xy.iter() {
+        assert_eq!(grid.dims(), [3, 2, 4]);
+    }
+    for grid in ij.iter() {
+        assert_eq!(grid.dims(), [2, 3, 4]);
+    }
+    for (xy, ij) in xy.iter().zip(ij.iter()) {
+        let ij = ij.transpose(0, 1)?;
+        assert_eq!(xy.to_vec3::<f32>()?, ij.to_vec3::<f32>()?);
+    }
+    assert_eq!(
+        ij[1].i((.., .., 0))?.to_vec2::<f32>()?,
+        [[0., 1., 2.], [0., 1., 2.]]
+    );
+    // The boolean flag of the previous versions is still accepted.
+    let xy_bool = Tensor::meshgrid(&[&x, &y, &z], true)?;
+    let ij_bool = Tensor::meshgrid(&[&x, &y, &z], false)?;
+    for (a, b) in xy.iter().zip(xy_bool.iter()) {
+        assert_eq!(a.to_vec3::<f32>()?, b.to_vec3::<f32>()?);
+    }
+    for (a, b) in ij.iter().zip(ij_bool.iter()) {
+        assert_eq!(a.to_vec3::<f32>()?, b.to_vec3::<f32>()?);
+    }
+    assert!(Tensor::meshgrid(&[&x], Indexing::Ij).is_err());
     Ok(())
 }
 
@@ -93,6 +151,34 @@ fn clamp(device: &Device) -> Result<()> {
         tensor.to_vec2::<f32>()?,
         [[3.0, 1.5, 4.0, 1.5, 5.0], [2.0, 1.5, 6.2, 6.2, 2.0]],
     );
+    // Per row and per column bounds are broadcasted.
+    let tensor = Tensor::new(data, device)?;
+    let min = Tensor::new(&[[2f32], [0.]], device)?;
+    let max = Tensor::new(&[3f32, 3., 5., 5., 5.], device)?;
+    let tensor = tensor.clamp(&min, &max)?;
+    assert_eq!(
+        tensor.to_vec2::<f32>()?,
+        [[3.0, 2.0, 4.0, 2.0, 5.0], [2.0, 1.0, 5.0, 5.0, 2.0]],
+    );
+    let tensor = Tensor::new(&[1f32, 5.], device)?.clamp(&min, 4f32)?;
+    assert_eq!(tensor.to_vec2::<f32>()?, [[2.0, 4.0], [1.0, 4.0]]);
+    Ok(())
+}
+
+fn where_cond(device: &Device) -> Result<()> {
+    let mask = Tensor::new(&[[1u8, 0, 1], [0, 0, 1]], device)?;
+    let on_true = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let on_false = Tensor::new(&[[-1f32, -2., -3.], [-4., -5., -6.]], device)?;
+    let t = mask.where_cond(&on_true, &on_false)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[1., -2., 3.], [-4., -5., 6.]]);
+    // All three operands get broadcasted.
+    let mask = Tensor::new(&[[1u8], [0]], device)?;
+    let on_true = Tensor::new(&[1f32, 2., 3.], device)?;
+    let on_false = Tensor::new(0f32, device)?;
+    let t = mask.where_cond(&on_true, &on_false)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[1., 2., 3.], [0., 0., 0.]]);
+    let on_false = Tensor::zeros(2, DType::F32, device)?;
+    assert!(mask.where_cond(&on_true, &on_false).is_err());
     Ok(())
 }
 
@@ -655,6 +741,100 @@ fn narrow(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn tensor_split(device: &Device) -> Result<()> {
+    // A fused qkv projection with a hidden size of 4.
+    let qkv = Tensor::arange(0f32, 24., device)?.reshape((2, 12))?;
+    let parts = qkv.tensor_split(3, 1)?;
+    assert_eq!(parts.len(), 3);
+    assert_eq!(
+        parts[1].to_vec2::<f32>()?,
+        &[[4., 5., 6., 7.], [16., 17., 18., 19.]]
+    );
+    let parts = qkv.tensor_split(5, 1)?;
+    let sizes: Vec<usize> = parts.iter().map(|p| p.dim(1)).collect::<Result<_>>()?;
+    assert_eq!(sizes, [3, 3, 2, 2, 2]);
+    assert_eq!(parts[4].to_vec2::<f32>()?, &[[10., 11.], [22., 23.]]);
+    let parts = qkv.tensor_split(vec![1, 8, 4], D::Minus1)?;
+    let sizes: Vec<usize> = parts.iter().map(|p| p.dim(1)).collect::<Result<_>>()?;
+    assert_eq!(sizes, [1, 7, 0, 4]);
+    assert_eq!(qkv.tensor_split(3, 0)?[2].dims(), &[0, 12]);
+    assert!(qkv.tensor_split(0, 0).is_err());
+
+    let heads = qkv.reshape((2, 3, 4))?.unbind(1)?;
+    assert_eq!(heads.len(), 3);
+    assert_eq!(
+        heads[2].to_vec2::<f32>()?,
+        &[[8., 9., 10., 11.], [20., 21., 22., 23.]]
+    );
+    assert_eq!(qkv.unbind(0)?[1].dims(), &[12]);
+    Ok(())
+}
+
+fn inplace_ops(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let row = t.narrow(0, 1, 1)?;
+    t.add_assign_(&Tensor::new(&[1f32, 2., 3.], device)?)?;
+    assert_eq!(t.to_vec2::<f32>()?, &[[2., 4., 6.], [5., 7., 9.]]);
+    assert_eq!(row.to_vec2::<f32>()?, &[[5., 7., 9.]]);
+    row.mul_assign_(2f32)?;
+    t.sub_assign_(&t.narrow(0, 0, 1)?)?;
+    assert_eq!(t.to_vec2::<f32>()?, &[[0., 0., 0.], [8., 10., 12.]]);
+    t.div_assign_(&Tensor::new(&[[1f32], [2.]], device)?)?;
+    t.maximum_assign_(1f32)?;
+    t.minimum_assign_(5f32)?;
+    assert_eq!(t.to_vec2::<f32>()?, &[[1., 1., 1.], [4., 5., 5.]]);
+    let u = Tensor::new(&[3u32, 1, 4], device)?;
+    u.add_assign_(&u.flip(0)?)?;
+    assert_eq!(u.to_vec1::<u32>()?, &[7, 2, 7]);
+    assert!(t.t()?.add_assign_(1f32).is_err());
+    assert!(t.add_assign_(&Tensor::new(&[1f32, 2.], device)?).is_err());
+    assert!(t.add_assign_(&u).is_err());
+    // index_add_ only writes the selected rows, duplicated indexes are accumulated.
+    let t = Tensor::arange(0f32, 8., device)?.reshape((4, 2))?;
+    let row = t.narrow(0, 2, 1)?;
+    let ids = Tensor::new(&[2u32, 0, 2], device)?;
+    let src = Tensor::new(&[[1f32, 1.], [10., 10.], [100., 100.]], device)?;
+    t.index_add_(&ids, &src, 0)?;
+    assert_eq!(
+        t.to_vec2::<f32>()?,
+        &[[10., 11.], [2., 3.], [105., 106.], [6., 7.]]
+    );
+    assert_eq!(row.to_vec2::<f32>()?, &[[105., 106.]]);
+    assert!(t.t()?.index_add_(&ids, &src.t()?, 0).is_err());
+    Ok(())
+}
+
+fn slice_step(device: &Device) -> Result<()> {
+    let tensor = Tensor::arange(0f32, 24., device)?.reshape((2, 3, 4))?;
+    let t = tensor.slice_step(2, .., 2)?;
+    assert_eq!(
+        t.to_vec3::<f32>()?,
+        &[
+            [[0., 2.], [4., 6.], [8., 10.]],
+            [[12., 14.], [16., 18.], [20., 22.]]
+        ]
+    );
+    assert!(!t.is_contiguous());
+    // Ops on the strided view, the matmul computes the sum over each row.
+    assert_eq!((&t + 1.)?.sum_all()?.to_vec0::<f32>()?, 144.);
+    let ones = Tensor::ones((2, 2, 1), DType::F32, device)?;
+    assert_eq!(
+        t.matmul(&ones)?.squeeze(2)?.to_vec2::<f32>()?,
+        &[[2., 10., 18.], [26., 34., 42.]]
+    );
+    assert_eq!(
+        tensor.slice_step(1, 1.., 3)?.to_vec3::<f32>()?,
+        &[[[4., 5., 6., 7.]], [[16., 17., 18., 19.]]]
+    );
+    let t = tensor.t()?.slice_step(D::Minus1, 0..=2, 2)?;
+    assert_eq!(t.dims(), &[2, 4, 2]);
+    assert_eq!(t.i((1, 3))?.to_vec1::<f32>()?, &[15., 23.]);
+    assert_eq!(tensor.slice_step(0, 1..1, 2)?.dims(), &[0, 3, 4]);
+    assert!(tensor.slice_step(1, 0..4, 2).is_err());
+    assert!(tensor.slice_step(1, .., 0).is_err());
+    Ok(())
+}
+
 fn broadcast(device: &Device) -> Result<()> {
     let data = &[3f32, 1., 4.];
     let tensor = Tensor::new(data, device)?;
@@ -689,6 +869,47 @@ fn slice_set(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn roll_flip(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0u32, 12, device)?.reshape((2, 2, 3))?;
+    assert_eq!(
+        t.roll_dims(&[1, -4], (0, D::Minus1))?.to_vec3::<u32>()?,
+        [[[7, 8, 6], [10, 11, 9]], [[1, 2, 0], [4, 5, 3]]]
+    );
+    assert!(t.roll_dims(&[1], (0, 1)).is_err());
+    assert_eq!(
+        t.flip((1, 2))?.to_vec3::<u32>()?,
+        [[[5, 4, 3], [2, 1, 0]], [[11, 10, 9], [8, 7, 6]]]
+    );
+    assert_eq!(t.flip(())?.to_vec3::<u32>()?, t.to_vec3::<u32>()?);
+    assert!(t.flip((1, 1)).is_err());
+    let empty = Tensor::zeros((0, 3), DType::F32, device)?;
+    assert_eq!(empty.roll(2, 0)?.dims(), [0, 3]);
+    assert_eq!(empty.flip(0)?.dims(), [0, 3]);
+    Ok(())
+}
+
+fn repeat_interleave(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0u32, 6, device)?.reshape((1, 2, 3))?;
+    assert_eq!(
+        t.repeat_interleave(2, 1)?.to_vec3::<u32>()?,
+        [[[0, 1, 2], [0, 1, 2], [3, 4, 5], [3, 4, 5]]]
+    );
+    assert_eq!(
+        t.repeat_interleave(3, D::Minus1)?.to_vec3::<u32>()?,
+        [[[0, 0, 0, 1, 1, 1, 2, 2, 2], [3, 3, 3, 4, 4, 4, 5, 5, 5]]]
+    );
+    assert_eq!(t.repeat_interleave(0, 1)?.dims(), [1, 0, 3]);
+    let repeats = Tensor::new(&[2i64, 0, 1], device)?;
+    assert_eq!(
+        t.repeat_interleave_with(&repeats, 2)?.to_vec3::<u32>()?,
+        [[[0, 0, 2], [3, 3, 5]]]
+    );
+    assert!(t.repeat_interleave_with(&repeats, 1).is_err());
+    let repeats = Tensor::new(&[2i64, -1, 1], device)?;
+    assert!(t.repeat_interleave_with(&repeats, 2).is_err());
+    Ok(())
+}
+
 fn cat(device: &Device) -> Result<()> {
     // 1D
     let t1 = Tensor::new(&[3f32, 1., 4.], device)?;
@@ -935,6 +1156,206 @@ fn slice_scatter(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn masked_fill_select(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 6., device)?.reshape((2, 3))?;
+    let mask = Tensor::new(&[[1u8, 0, 0], [0, 1, 1]], device)?;
+    let hs = t.masked_fill(&mask, -1f32)?;
+    assert_eq!(hs.to_vec2::<f32>()?, &[[-1.0, 1.0, 2.0], [3.0, -1.0, -1.0]]);
+    // The mask gets broadcasted and can use any integer dtype.
+    let mask = Tensor::new(&[[0u32], [7]], device)?;
+    let hs = t.masked_fill(&mask, f64::NEG_INFINITY)?;
+    let inf = f32::NEG_INFINITY;
+    assert_eq!(hs.to_vec2::<f32>()?, &[[0.0, 1.0, 2.0], [inf, inf, inf]]);
+    let hs = t.masked_select(&t.ge(2f32)?)?;
+    assert_eq!(hs.to_vec1::<f32>()?, &[2.0, 3.0, 4.0, 5.0]);
+    let hs = t.masked_select(&Tensor::new(&[1u8, 0, 1], device)?)?;
+    assert_eq!(hs.to_vec1::<f32>()?, &[0.0, 2.0, 3.0, 5.0]);
+    let hs = t.masked_select(&t.zeros_like()?)?;
+    assert_eq!(hs.dims(), &[0]);
+    Ok(())
+}
+
+fn nonzero(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[0f32, 1.5, 0., -2.], device)?;
+    assert_eq!(t.nonzero()?.to_vec2::<i64>()?, &[[1], [3]]);
+    let t = Tensor::new(&[[[1u32, 0], [0, 0]], [[0, 3], [4, 0]]], device)?;
+    assert_eq!(
+        t.nonzero()?.to_vec2::<i64>()?,
+        &[[0, 0, 0], [1, 0, 1], [1, 1, 0]]
+    );
+    let t = Tensor::zeros((2, 3), DType::F32, device)?;
+    assert_eq!(t.nonzero()?.dims(), &[0, 2]);
+    Ok(())
+}
+
+fn complex(device: &Device) -> Result<()> {
+    let re = Tensor::new(&[3f32, 0., -1.], device)?;
+    let im = Tensor::new(&[4f32, 2., 0.], device)?;
+    let z = Tensor::complex(&re, &im)?;
+    assert_eq!(z.dims(), [3, 2]);
+    assert_eq!(z.real()?.to_vec1::<f32>()?, [3., 0., -1.]);
+    assert_eq!(z.imag()?.to_vec1::<f32>()?, [4., 2., 0.]);
+    assert_eq!(
+        z.conj()?.to_vec2::<f32>()?,
+        [[3., -4.], [0., -2.], [-1., 0.]]
+    );
+    assert_eq!(z.complex_abs()?.to_vec1::<f32>()?, [5., 2., 1.]);
+    assert_eq!(
+        z.complex_mul(&z)?.to_vec2::<f32>()?,
+        [[-7., 24.], [-4., 0.], [1., 0.]]
+    );
+    let rot = Tensor::polar(
+        &Tensor::new(&[2f32], device)?,
+        &Tensor::new(&[std::f32::consts::FRAC_PI_2], device)?,
+    )?;
+    assert_eq!(
+        test_utils::to_vec2_round(&z.complex_mul(&rot)?, 4)?,
+        [[-8., 6.], [-4., 0.], [0., -2.]]
+    );
+    // [[1 + i, 2], [0, i]] x [[1], [i]]
+    let a = Tensor::new(&[[[1f32, 1.], [2., 0.]], [[0., 0.], [0., 1.]]], device)?;
+    let b = Tensor::new(&[[[1f32, 0.]], [[0., 1.]]], device)?;
+    assert_eq!(
+        a.complex_matmul(&b)?.to_vec3::<f32>()?,
+        [[[1., 3.]], [[-1., 0.]]]
+    );
+    assert!(re.real().is_err());
+    Ok(())
+}
+
+fn index_put(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 12., device)?.reshape((3, 4))?;
+    let rows = Tensor::new(&[2u32, 0], device)?;
+    let values = Tensor::new(&[[-1f32, -2., -3., -4.], [-5., -6., -7., -8.]], device)?;
+    let hs = t.index_put(&[&rows], &values, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [-5.0, -6.0, -7.0, -8.0],
+            [4.0, 5.0, 6.0, 7.0],
+            [-1.0, -2.0, -3.0, -4.0]
+        ]
+    );
+    let cols = Tensor::new(&[3i64, 1], device)?;
+    let hs = t.index_put(&[&rows, &cols], &Tensor::new(&[-1f32, -2.], device)?, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, -2.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0, 7.0],
+            [8.0, 9.0, 10.0, -1.0]
+        ]
+    );
+    // The index tensors and values get broadcasted.
+    let rows = Tensor::new(&[[1u32], [2]], device)?;
+    let cols = Tensor::new(&[0u32, 2], device)?;
+    let hs = t.index_put(&[&rows, &cols], &Tensor::new(0f32, device)?, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [0.0, 1.0, 2.0, 3.0],
+            [0.0, 5.0, 0.0, 7.0],
+            [0.0, 9.0, 0.0, 11.0]
+        ]
+    );
+    // Duplicated indexes use the last value when overwriting and are summed when accumulating.
+    let rows = Tensor::new(&[1u32, 0, 1], device)?;
+    let cols = Tensor::new(&[2u32, 0, 2], device)?;
+    let values = Tensor::new(&[10f32, 20., 30.], device)?;
+    let hs = t.index_put(&[&rows, &cols], &values, false)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [20.0, 1.0, 2.0, 3.0],
+            [4.0, 5.0, 30.0, 7.0],
+            [8.0, 9.0, 10.0, 11.0]
+        ]
+    );
+    let hs = t.index_put(&[&rows, &cols], &values, true)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [20.0, 1.0, 2.0, 3.0],
+            [4.0, 5.0, 46.0, 7.0],
+            [8.0, 9.0, 10.0, 11.0]
+        ]
+    );
+    let no_index: [&Tensor; 0] = [];
+    assert!(t.index_put(&no_index, &values, false).is_err());
+    Ok(())
+}
+
+fn index_set(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 12., device)?.reshape((4, 3))?;
+    // Duplicated indexes use the value from their last occurrence.
+    let ids = Tensor::new(&[3u32, 0, 3], device)?;
+    let src = Tensor::new(
+        &[[-1f32, -2., -3.], [-4., -5., -6.], [-7., -8., -9.]],
+        device,
+    )?;
+    let hs = t.index_set(&ids, &src, 0)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [-4.0, -5.0, -6.0],
+            [3.0, 4.0, 5.0],
+            [6.0, 7.0, 8.0],
+            [-7.0, -8.0, -9.0]
+        ]
+    );
+    let ids = Tensor::new(&[2i64, 0], device)?;
+    let src = Tensor::new(&[[10f32, 20.], [30., 40.], [50., 60.], [70., 80.]], device)?;
+    let hs = t.index_set(&ids, &src, 1)?;
+    assert_eq!(
+        hs.to_vec2::<f32>()?,
+        &[
+            [20.0, 1.0, 10.0],
+            [40.0, 4.0, 30.0],
+            [60.0, 7.0, 50.0],
+            [80.0, 10.0, 70.0]
+        ]
+    );
+    let ids = Tensor::new(&[1u8], device)?;
+    let src = Tensor::new(&[[7u32, 8, 9]], device)?;
+    let hs = t.to_dtype(DType::U32)?.index_set(&ids, &src, 0)?;
+    assert_eq!(hs.to_vec2::<u32>()?[1], [7, 8, 9]);
+    // The original tensor is left unchanged.
+    assert_eq!(t.to_vec2::<f32>()?[0], [0.0, 1.0, 2.0]);
+
+    // In-place updates, including on a view with an offset.
+    let cache = Tensor::zeros((2, 4, 3), DType::F32, device)?;
+    let view = cache.narrow(0, 1, 1)?.squeeze(0)?;
+    let ids = Tensor::new(&[2u32, 0, 2], device)?;
+    let src = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.], [7., 8., 9.]], device)?;
+    view.index_set_(&ids, &src, 0)?;
+    assert_eq!(
+        cache.i(1)?.to_vec2::<f32>()?,
+        &[[4., 5., 6.], [0., 0., 0.], [7., 8., 9.], [0., 0., 0.]]
+    );
+    assert_eq!(cache.i(0)?.sum_all()?.to_scalar::<f32>()?, 0.);
+    view.index_add_(&ids, &src, 0)?;
+    assert_eq!(
+        cache.i(1)?.to_vec2::<f32>()?,
+        &[[8., 10., 12.], [0., 0., 0.], [15., 18., 21.], [0., 0., 0.]]
+    );
+    let rows = Tensor::new(&[1u32, 3], device)?;
+    let cols = Tensor::new(&[0u32, 2], device)?;
+    view.index_put_(&[&rows, &cols], &Tensor::new(&[-1f32, -2.], device)?, false)?;
+    view.index_put_(&[&rows, &cols], &Tensor::new(&[-1f32, -2.], device)?, true)?;
+    assert_eq!(
+        cache.i(1)?.to_vec2::<f32>()?,
+        &[
+            [8., 10., 12.],
+            [-2., 0., 0.],
+            [15., 18., 21.],
+            [0., 0., -4.]
+        ]
+    );
+    assert_eq!(cache.i(0)?.sum_all()?.to_scalar::<f32>()?, 0.);
+    assert!(t.t()?.index_set_(&ids, &src, 0).is_err());
+    Ok(())
+}
+
 fn scatter_add(device: &Device) -> Result<()> {
     let t = Tensor::arange(0f32, 12f32, device)?.reshape((4, 3))?;
     assert_eq!(
@@ -1141,6 +1562,333 @@ fn randn(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn sort(device: &Device) -> Result<()> {
+    let data = &[[2u32, 1, 2, 0, 1, 2], [5, 5, 3, 5, 3, 4]];
+    let tensor = Tensor::new(data, device)?;
+    let (sorted, indexes) = tensor.sort(1, false)?;
+    assert_eq!(
+        sorted.to_vec2::<u32>()?,
+        [[0, 1, 1, 2, 2, 2], [3, 3, 4, 5, 5, 5]]
+    );
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[3, 1, 4, 0, 2, 5], [2, 4, 5, 0, 1, 3]]
+    );
+    let (sorted, indexes) = tensor.sort(1, true)?;
+    assert_eq!(
+        sorted.to_vec2::<u32>()?,
+        [[2, 2, 2, 1, 1, 0], [5, 5, 5, 4, 3, 3]]
+    );
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[0, 2, 5, 1, 4, 3], [0, 1, 3, 5, 2, 4]]
+    );
+    let tensor = Tensor::new(&[[3f32, 1., 4.], [1., 5., 4.], [2., 1., 0.]], device)?;
+    let (sorted, indexes) = tensor.sort(0, true)?;
+    assert_eq!(
+        sorted.to_vec2::<f32>()?,
+        [[3., 5., 4.], [2., 1., 4.], [1., 1., 0.]]
+    );
+    assert_eq!(indexes.to_vec2::<u32>()?, [[0, 1, 0], [2, 0, 1], [1, 2, 2]]);
+    let indexes = tensor.argsort(0, false)?;
+    assert_eq!(indexes.to_vec2::<u32>()?, [[1, 0, 2], [2, 2, 0], [0, 1, 1]]);
+    Ok(())
+}
+
+fn unique(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[2f32, 1., 2., 5.], [1., 1., 7., 2.]], device)?;
+    let (values, inverse, counts) = t.unique()?;
+    assert_eq!(values.to_vec1::<f32>()?, [1., 2., 5., 7.]);
+    assert_eq!(inverse.to_vec2::<u32>()?, [[1, 0, 1, 2], [0, 0, 3, 1]]);
+    assert_eq!(counts.to_vec1::<u32>()?, [3, 3, 1, 1]);
+    let (values, inverse, counts) = t.unique_consecutive()?;
+    assert_eq!(values.to_vec1::<f32>()?, [2., 1., 2., 5., 1., 7., 2.]);
+    assert_eq!(inverse.to_vec2::<u32>()?, [[0, 1, 2, 3], [4, 4, 5, 6]]);
+    assert_eq!(counts.to_vec1::<u32>()?, [1, 1, 1, 1, 2, 1, 1]);
+    let t = Tensor::new(&[4u32; 0], device)?;
+    let (values, inverse, counts) = t.unique()?;
+    assert_eq!(values.dims(), [0]);
+    assert_eq!(inverse.dims(), [0]);
+    assert_eq!(counts.dims(), [0]);
+    Ok(())
+}
+
+fn searchsorted(device: &Device) -> Result<()> {
+    let boundaries = Tensor::new(&[1f32, 2., 2., 4.], device)?;
+    let values = Tensor::new(&[[0f32, 1., 2.], [3., 4., 5.]], device)?;
+    let idx = values.searchsorted(&boundaries, false)?;
+    assert_eq!(idx.to_vec2::<u32>()?, [[0, 0, 1], [3, 3, 4]]);
+    let idx = values.searchsorted(&boundaries, true)?;
+    assert_eq!(idx.to_vec2::<u32>()?, [[0, 1, 3], [3, 4, 4]]);
+    // One sorted sequence per row of values.
+    let seqs = Tensor::new(&[[1u32, 3, 5], [2, 4, 6]], device)?;
+    let values = Tensor::new(&[[3u32, 6], [3, 6]], device)?;
+    let idx = values.searchsorted(&seqs, false)?;
+    assert_eq!(idx.to_vec2::<u32>()?, [[1, 3], [1, 2]]);
+    assert!(values
+        .searchsorted(&seqs.t()?.contiguous()?, false)
+        .is_err());
+    Ok(())
+}
+
+fn bitwise_ops(device: &Device) -> Result<()> {
+    // The bitwise ops are not available on metal.
+    if device.is_metal() {
+        return Ok(());
+    }
+    let t = Tensor::new(&[0b1100u8, 0b1010, 0xff], device)?;
+    let u = Tensor::new(&[0b1010u8, 0b0110, 0x0f], device)?;
+    assert_eq!(t.bitwise_and(&u)?.to_vec1::<u8>()?, [0b1000, 0b0010, 0x0f]);
+    assert_eq!(t.bitwise_or(&u)?.to_vec1::<u8>()?, [0b1110, 0b1110, 0xff]);
+    assert_eq!(t.bitwise_xor(&u)?.to_vec1::<u8>()?, [0b0110, 0b1100, 0xf0]);
+    assert_eq!(t.bitwise_not()?.to_vec1::<u8>()?, [0xf3, 0xf5, 0]);
+    assert_eq!(t.shl(4u8)?.to_vec1::<u8>()?, [0xc0, 0xa0, 0xf0]);
+    let shifts = Tensor::new(&[2u8, 1, 9], device)?;
+    assert_eq!(t.shr(&shifts)?.to_vec1::<u8>()?, [0b11, 0b101, 0]);
+    assert_eq!(t.shr(3u8)?.to_vec1::<u8>()?, [1, 1, 0x1f]);
+
+    let t = Tensor::new(&[0x1234_5678u32, 1], device)?;
+    assert_eq!(
+        t.shr(16u32)?.bitwise_and(0xffu32)?.to_vec1::<u32>()?,
+        [0x34, 0]
+    );
+    assert_eq!(t.shl(31u32)?.to_vec1::<u32>()?, [0, 1 << 31]);
+    assert_eq!(t.shl(32u32)?.to_vec1::<u32>()?, [0, 0]);
+
+    let t = Tensor::new(&[-8i64, 8, -1], device)?;
+    assert_eq!(t.shr(1i64)?.to_vec1::<i64>()?, [-4, 4, -1]);
+    assert_eq!(t.shr(64i64)?.to_vec1::<i64>()?, [-1, 0, -1]);
+    assert_eq!(t.shl(-1i64)?.to_vec1::<i64>()?, [0, 0, 0]);
+    assert_eq!(t.bitwise_not()?.to_vec1::<i64>()?, [7, -9, 0]);
+    assert_eq!(
+        t.bitwise_and(&t.bitwise_not()?)?.to_vec1::<i64>()?,
+        [0, 0, 0]
+    );
+
+    let f = Tensor::new(&[1f32], device)?;
+    assert!(f.bitwise_and(&f).is_err());
+    assert!(f.bitwise_not().is_err());
+    Ok(())
+}
+
+fn integer_ops(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[3u8, 200, 7], [0, 5, 255]], device)?;
+    let u = Tensor::new(&[[2u8, 2, 7], [1, 10, 3]], device)?;
+    assert_eq!((&t * &u)?.to_vec2::<u8>()?, [[6, 144, 49], [0, 50, 253]]);
+    assert_eq!((&t / &u)?.to_vec2::<u8>()?, [[1, 100, 1], [0, 0, 85]]);
+    assert_eq!(t.minimum(&u)?.to_vec2::<u8>()?, [[2, 2, 7], [0, 5, 3]]);
+    assert_eq!(t.maximum(&u)?.to_vec2::<u8>()?, [[3, 200, 7], [1, 10, 255]]);
+    assert_eq!(t.max(1)?.to_vec1::<u8>()?, [200, 255]);
+    assert_eq!(t.min(0)?.to_vec1::<u8>()?, [0, 5, 7]);
+    assert_eq!(t.argmax(1)?.to_vec1::<u32>()?, [1, 2]);
+    assert_eq!(u.sum(1)?.to_vec1::<u8>()?, [11, 14]);
+
+    let t = Tensor::new(&[[-3i64, 4, 0], [7, -8, 2]], device)?;
+    assert_eq!(
+        t.broadcast_mul(&Tensor::new(3i64, device)?)?
+            .to_vec2::<i64>()?,
+        [[-9, 12, 0], [21, -24, 6]]
+    );
+    assert_eq!(t.sum(0)?.to_vec1::<i64>()?, [4, -4, 2]);
+    assert_eq!(t.sum_all()?.to_vec0::<i64>()?, 2);
+    assert_eq!(t.min(1)?.to_vec1::<i64>()?, [-3, -8]);
+    assert_eq!(t.argmin(0)?.to_vec1::<u32>()?, [0, 1, 0]);
+
+    // The integer unary kernels are not available on metal.
+    if device.is_metal() {
+        return Ok(());
+    }
+    assert_eq!(t.neg()?.to_vec2::<i64>()?, [[3, -4, 0], [-7, 8, -2]]);
+    assert_eq!(t.abs()?.to_vec2::<i64>()?, [[3, 4, 0], [7, 8, 2]]);
+    assert_eq!(t.sqr()?.to_vec2::<i64>()?, [[9, 16, 0], [49, 64, 4]]);
+    assert_eq!(t.relu()?.to_vec2::<i64>()?, [[0, 4, 0], [7, 0, 2]]);
+    assert_eq!(t.sign()?.to_vec2::<i64>()?, [[-1, 1, 0], [1, -1, 1]]);
+    assert_eq!(t.t()?.neg()?.to_vec2::<i64>()?, [[3, -7], [-4, 8], [0, -2]]);
+    let t = Tensor::new(&[0u8, 1, 16, 255], device)?;
+    assert_eq!(t.neg()?.to_vec1::<u8>()?, [0, 255, 240, 1]);
+    assert_eq!(t.sqr()?.to_vec1::<u8>()?, [0, 1, 0, 1]);
+    assert_eq!(t.sign()?.to_vec1::<u8>()?, [0, 1, 1, 1]);
+    assert_eq!(t.floor()?.to_vec1::<u8>()?, [0, 1, 16, 255]);
+    let t = Tensor::new(&[3u32, 70000], device)?;
+    assert_eq!(t.sqr()?.to_vec1::<u32>()?, [9, 605032704]);
+    assert_eq!(t.abs()?.to_vec1::<u32>()?, [3, 70000]);
+    Ok(())
+}
+
+fn nan_ops(device: &Device) -> Result<()> {
+    let (nan, inf) = (f32::NAN, f32::INFINITY);
+    let t = Tensor::new(&[[1f32, nan, -inf], [nan, nan, nan], [2., 4., inf]], device)?;
+    assert_eq!(
+        t.isnan()?.to_vec2::<u8>()?,
+        &[[0, 1, 0], [1, 1, 1], [0, 0, 0]]
+    );
+    assert_eq!(
+        t.isinf()?.to_vec2::<u8>()?,
+        &[[0, 0, 1], [0, 0, 0], [0, 0, 1]]
+    );
+    assert_eq!(
+        t.nan_to_num(-1., Some(100.), None)?.to_vec2::<f32>()?,
+        &[[1., -1., f32::MIN], [-1., -1., -1.], [2., 4., 100.]]
+    );
+    let t = t.nan_to_num(f64::NAN, Some(5.), Some(-5.))?;
+    assert_eq!(t.nansum(1)?.to_vec1::<f32>()?, &[-4., 0., 11.]);
+    assert_eq!(t.nansum_keepdim(0)?.to_vec2::<f32>()?, &[[3., 4., 0.]]);
+    assert_eq!(t.nansum((0, 1))?.to_vec0::<f32>()?, 7.);
+    let mean = t.nanmean(1)?.to_vec1::<f32>()?;
+    assert_eq!([mean[0], mean[2]], [-2., 11. / 3.]);
+    assert!(mean[1].is_nan());
+    assert_eq!(t.nanmean_keepdim(0)?.to_vec2::<f32>()?, &[[1.5, 4., 0.]]);
+
+    let t = Tensor::new(&[1u32, 2, 3], device)?;
+    assert_eq!(t.isnan()?.to_vec1::<u8>()?, &[0, 0, 0]);
+    assert_eq!(t.nansum(0)?.to_vec0::<u32>()?, 6);
+    Ok(())
+}
+
+fn bernoulli(device: &Device) -> Result<()> {
+    let probs = Tensor::new(&[0f32, 1., 0.5], device)?.broadcast_as((1000, 3))?;
+    let samples = probs.bernoulli()?;
+    assert_eq!(samples.dtype(), DType::F32);
+    let counts = samples.sum(0)?.to_vec1::<f32>()?;
+    assert_eq!(counts[..2], [0., 1000.]);
+    assert!((400. ..600.).contains(&counts[2]), "{counts:?}");
+    Ok(())
+}
+
+fn multinomial(device: &Device) -> Result<()> {
+    let probs = Tensor::new(&[0f32, 1., 3.], device)?;
+    let samples = probs.multinomial(4000, true)?;
+    assert_eq!(samples.dims(), &[4000]);
+    let counts = samples.bincount(None, 3)?.to_vec1::<u32>()?;
+    assert_eq!(counts[0], 0);
+    assert!((800..1200).contains(&counts[1]), "{counts:?}");
+
+    let probs = Tensor::new(&[[0f32, 2., 1., 1.], [1., 0., 0., 1.]], device)?;
+    let samples = probs.multinomial(2, false)?;
+    assert_eq!(samples.dims(), &[2, 2]);
+    let samples = samples.to_vec2::<u32>()?;
+    assert!(!samples[0].contains(&0) && samples[0][0] != samples[0][1]);
+    let mut second = samples[1].clone();
+    second.sort();
+    assert_eq!(second, [0, 3]);
+    assert_eq!(probs.multinomial(1, false)?.dims(), &[2, 1]);
+    assert!(probs.multinomial(5, false).is_err());
+    Ok(())
+}
+
+fn one_hot(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[0i64, 2], [1, -1]], device)?;
+    assert_eq!(
+        t.one_hot(4, DType::F32)?.to_vec3::<f32>()?,
+        &[
+            [[1., 0., 0., 0.], [0., 0., 1., 0.]],
+            [[0., 1., 0., 0.], [0., 0., 0., 0.]]
+        ]
+    );
+    let t = Tensor::new(&[255u8, 1], device)?;
+    let t = t.one_hot(300, DType::U8)?;
+    assert_eq!(t.dims(), &[2, 300]);
+    assert_eq!(t.argmax(1)?.to_vec1::<u32>()?, &[255, 1]);
+    assert_eq!(
+        t.sum_keepdim(1)?
+            .to_dtype(DType::U32)?
+            .flatten_all()?
+            .to_vec1::<u32>()?,
+        &[1, 1]
+    );
+    assert!(Tensor::new(&[1f32], device)?
+        .one_hot(2, DType::F32)
+        .is_err());
+    Ok(())
+}
+
+fn bincount_histc(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[2i64, 0, 2, 5, 2], device)?;
+    assert_eq!(t.bincount(None, 0)?.to_vec1::<u32>()?, [1, 0, 3, 0, 0, 1]);
+    assert_eq!(t.bincount(None, 8)?.to_vec1::<u32>()?.len(), 8);
+    let w = Tensor::new(&[1f32, 2., 3., 4., 5.], device)?;
+    assert_eq!(
+        t.bincount(Some(&w), 0)?.to_vec1::<f32>()?,
+        [2., 0., 9., 0., 0., 4.]
+    );
+    assert!(Tensor::new(&[1i64, -1], device)?.bincount(None, 0).is_err());
+    let empty = Tensor::new(&[0u32; 0], device)?;
+    assert_eq!(empty.bincount(None, 3)?.to_vec1::<u32>()?, [0, 0, 0]);
+
+    let t = Tensor::new(&[[-1f32, 0., 0.2], [0.5, 0.99, 1.]], device)?;
+    assert_eq!(t.histc(4, 0., 1.)?.to_vec1::<f32>()?, [2., 0., 1., 2.]);
+    // Use the min and max of the tensor.
+    assert_eq!(t.histc(2, 0., 0.)?.to_vec1::<f32>()?, [1., 5.]);
+    let t = Tensor::new(&[1u32, 2, 2, 3, 9], device)?;
+    assert_eq!(t.histc(3, 1., 3.)?.to_vec1::<u32>()?, [1, 2, 1]);
+    Ok(())
+}
+
+fn quantile(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[3f32, 1., 4., 1., 5.], [9., 2., 6., 5., 3.]], device)?;
+    assert_eq!(t.quantile(0., 1)?.to_vec1::<f32>()?, [1., 2.]);
+    assert_eq!(t.quantile(1., 1)?.to_vec1::<f32>()?, [5., 9.]);
+    assert_eq!(t.quantile(0.5, 1)?.to_vec1::<f32>()?, [3., 5.]);
+    assert_eq!(
+        test_utils::to_vec2_round(&t.quantile_keepdim(0.3, 1)?, 4)?,
+        [[1.4], [3.4]]
+    );
+    assert_eq!(
+        t.quantile(0.25, 0)?.to_vec1::<f32>()?,
+        [4.5, 1.25, 4.5, 2., 3.5]
+    );
+    assert!(t.quantile(1.5, 1).is_err());
+    assert_eq!(t.median(1)?.to_vec1::<f32>()?, [3., 5.]);
+    assert_eq!(
+        t.median_keepdim(0)?.to_vec2::<f32>()?,
+        [[3., 1., 4., 1., 3.]]
+    );
+    Ok(())
+}
+
+fn norm(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[1f32, -2.], [0., 2.]], [[-3., 0.], [4., 0.]]], device)?;
+    assert_eq!(
+        t.norm(1., D::Minus1)?.to_vec2::<f32>()?,
+        [[3., 2.], [3., 4.]]
+    );
+    assert_eq!(t.norm(2., (1, 2))?.to_vec1::<f32>()?, [3., 5.]);
+    assert_eq!(
+        t.norm_keepdim(f64::INFINITY, (1, 2))?.to_vec3::<f32>()?,
+        [[[2.]], [[4.]]]
+    );
+    assert_eq!(
+        t.norm(f64::NEG_INFINITY, 2)?.to_vec2::<f32>()?,
+        [[1., 0.], [0., 0.]]
+    );
+    assert_eq!(t.norm(0., (1, 2))?.to_vec1::<f32>()?, [3., 2.]);
+    assert_eq!(
+        test_utils::to_vec2_round(&t.norm(3., 1)?, 4)?,
+        [[1., 2.5198], [4.4979, 0.]]
+    );
+    assert!(t.norm(-1., 1).is_err());
+    let t = Tensor::new(&[[3f32, -4.], [0., 0.], [5., 12.]], device)?;
+    let n = t.normalize(2., 1, 1e-12)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&n, 4)?,
+        [[0.6, -0.8], [0., 0.], [0.3846, 0.9231]]
+    );
+    Ok(())
+}
+
+fn topk(device: &Device) -> Result<()> {
+    let data = &[[3f32, 1., 4., 1.1, 5.], [2.1, 1., 7., 8., 2.]];
+    let tensor = Tensor::new(data, device)?;
+    let (values, indexes) = tensor.topk(3, 1)?;
+    assert_eq!(values.to_vec2::<f32>()?, [[5.0, 4.0, 3.0], [8.0, 7.0, 2.1]]);
+    assert_eq!(indexes.to_vec2::<u32>()?, [[4, 2, 0], [3, 2, 0]]);
+    let (values, indexes) = tensor.topk(1, 0)?;
+    assert_eq!(values.to_vec2::<f32>()?, [[3.0, 1.0, 7.0, 8.0, 5.0]]);
+    assert_eq!(indexes.to_vec2::<u32>()?, [[0, 0, 1, 1, 0]]);
+    assert!(tensor.topk(6, 1).is_err());
+    Ok(())
+}
+
 fn zero_dim(device: &Device) -> Result<()> {
     let t = Tensor::zeros((4, 0, 1), DType::F32, device)?;
     assert_eq!(t.dims3()?, (4, 0, 1));
@@ -1166,12 +1914,54 @@ test_device!(zeros, zeros_cpu, zeros_gpu, zeros_metal);
 test_device!(ones, ones_cpu, ones_gpu, ones_metal);
 test_device!(full, full_cpu, full_gpu, full_metal);
 test_device!(arange, arange_cpu, arange_gpu, arange_metal);
+test_device!(meshgrid, meshgrid_cpu, meshgrid_gpu, meshgrid_metal);
 test_device!(add_mul, add_mul_cpu, add_mul_gpu, add_mul_metal);
 test_device!(tensor_2d, tensor_2d_cpu, tensor_2d_gpu, tensor_2d_metal);
 test_device!(narrow, narrow_cpu, narrow_gpu, narrow_metal);
+test_device!(one_hot, one_hot_cpu, one_hot_gpu, one_hot_metal);
+test_device!(bernoulli, bernoulli_cpu, bernoulli_gpu, bernoulli_metal);
+test_device!(nan_ops, nan_ops_cpu, nan_ops_gpu, nan_ops_metal);
+test_device!(
+    integer_ops,
+    integer_ops_cpu,
+    integer_ops_gpu,
+    integer_ops_metal
+);
+test_device!(
+    bitwise_ops,
+    bitwise_ops_cpu,
+    bitwise_ops_gpu,
+    bitwise_ops_metal
+);
+test_device!(
+    multinomial,
+    multinomial_cpu,
+    multinomial_gpu,
+    multinomial_metal
+);
+test_device!(
+    tensor_split,
+    tensor_split_cpu,
+    tensor_split_gpu,
+    tensor_split_metal
+);
+test_device!(
+    inplace_ops,
+    inplace_ops_cpu,
+    inplace_ops_gpu,
+    inplace_ops_metal
+);
+test_device!(slice_step, slice_step_cpu, slice_step_gpu, slice_step_metal);
 test_device!(broadcast, broadcast_cpu, broadcast_gpu, broadcast_metal);
 test_device!(slice_set, ss_cpu, ss_gpu, ss_metal);
 test_device!(cat, cat_cpu, cat_gpu, cat_metal);
+test_device!(
+    repeat_interleave,
+    repeat_interleave_cpu,
+    repeat_interleave_gpu,
+    repeat_interleave_metal
+);
+test_device!(roll_flip, roll_flip_cpu, roll_flip_gpu, roll_flip_metal);
 test_device!(sum, sum_cpu, sum_gpu, sum_metal);
 test_device!(min, min_cpu, min_gpu, min_metal);
 test_device!(max, max_cpu, max_gpu, max_metal);
@@ -1195,6 +1985,16 @@ test_device!(
     index_select_metal
 );
 test_device!(index_add, index_add_cpu, index_add_gpu, index_add_metal);
+test_device!(index_put, index_put_cpu, index_put_gpu, index_put_metal);
+test_device!(index_set, index_set_cpu, index_set_gpu, index_set_metal);
+test_device!(complex, complex_cpu, complex_gpu, complex_metal);
+test_device!(nonzero, nonzero_cpu, nonzero_gpu, nonzero_metal);
+test_device!(
+    masked_fill_select,
+    masked_fill_select_cpu,
+    masked_fill_select_gpu,
+    masked_fill_select_metal
+);
 test_device!(gather, gather_cpu, gather_gpu, gather_metal);
 test_device!(
     scatter_add,
@@ -1210,7 +2010,25 @@ test_device!(
 );
 test_device!(randn, randn_cpu, randn_gpu, randn_metal);
 test_device!(clamp, clamp_cpu, clamp_gpu, clamp_metal);
+test_device!(where_cond, where_cond_cpu, where_cond_gpu, where_cond_metal);
 test_device!(asort, asort_cpu, asort_gpu, asort_metal);
+test_device!(sort, sort_cpu, sort_gpu, sort_metal);
+test_device!(
+    searchsorted,
+    searchsorted_cpu,
+    searchsorted_gpu,
+    searchsorted_metal
+);
+test_device!(
+    bincount_histc,
+    bincount_histc_cpu,
+    bincount_histc_gpu,
+    bincount_histc_metal
+);
+test_device!(quantile, quantile_cpu, quantile_gpu, quantile_metal);
+test_device!(norm, norm_cpu, norm_gpu, norm_metal);
+test_device!(topk, topk_cpu, topk_gpu, topk_metal);
+test_device!(unique, unique_cpu, unique_gpu, unique_metal);
 test_device!(var, var_cpu, var_gpu, var_metal);
 test_device!(zero_dim, zero_dim_cpu, zero_dim_gpu, zero_dim_metal);
 
@@ -1241,6 +2059,40 @@ fn pad_with_same() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn pad_modes() -> Result<()> {
+    let t = Tensor::arange(1f32, 7f32, &Device::Cpu)?.reshape((2, 3))?;
+    let t0 = t.pad(1, 2, 2, PadMode::Reflect)?;
+    assert_eq!(
+        t0.to_vec2::<f32>()?,
+        [
+            [3.0, 2.0, 1.0, 2.0, 3.0, 2.0, 1.0],
+            [6.0, 5.0, 4.0, 5.0, 6.0, 5.0, 4.0]
+        ]
+    );
+    let t1 = t.pad(1, 3, 1, PadMode::Circular)?;
+    assert_eq!(
+        t1.to_vec2::<f32>()?,
+        [
+            [1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0],
+            [4.0, 5.0, 6.0, 4.0, 5.0, 6.0, 4.0]
+        ]
+    );
+    let t2 = t.pad(0, 1, 0, PadMode::Replicate)?;
+    assert_eq!(
+        t2.to_vec2::<f32>()?,
+        [[1.0, 2.0, 3.0], [1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]
+    );
+    let t3 = t.pad(D::Minus1, 0, 1, PadMode::Zeros)?;
+    assert_eq!(
+        t3.to_vec2::<f32>()?,
+        [[1.0, 2.0, 3.0, 0.0], [4.0, 5.0, 6.0, 0.0]]
+    );
+    assert!(t.pad(0, 2, 0, PadMode::Reflect).is_err());
+    assert!(t.pad(0, 1, 3, PadMode::Circular).is_err());
+    Ok(())
+}
+
 #[test]
 fn i64_abs() -> Result<()> {
     let t = Tensor::new(&[-42i64, 1337], &Device::Cpu)?;
@@ -1249,6 +2101,200 @@ fn i64_abs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn bf16_cpu() -> Result<()> {
+    use candle_core::cpu::bf16_vec;
+    // The rounding used by the vectorized kernels matches the one from the half crate.
+    for v in [
+        0f32,
+        -0.,
+        1.,
+        f32::from_bits(0x3f80_8000),
+        f32::from_bits(0x3f81_8000),
+        -123.456,
+        1e-40,
+        f32::from_bits(0x7f7f_8000),
+        f32::MAX,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+    ] {
+        let expected = half::bf16::from_f32(v);
+        let got = bf16_vec::from_f32(v);
+        assert_eq!(got.to_bits(), expected.to_bits(), "{v}");
+        assert_eq!(bf16_vec::to_f32(got).to_bits(), expected.to_f32().to_bits());
+    }
+
+    let device = &Device::Cpu;
+    let lhs = Tensor::randn(0f32, 1., (3, 4, 8), device)?.to_dtype(DType::BF16)?;
+    let rhs = Tensor::randn(0f32, 1., (3, 4, 8), device)?.to_dtype(DType::BF16)?;
+    let lhs_f32 = lhs.to_dtype(DType::F32)?;
+    let rhs_f32 = rhs.to_dtype(DType::F32)?;
+    let check = |t: Tensor, expected: Tensor| -> Result<()> {
+        assert_eq!(t.dtype(), DType::BF16);
+        let t = t.to_dtype(DType::F32)?.flatten_all()?.to_vec1::<f32>()?;
+        let expected = expected.to_dtype(DType::BF16)?.to_dtype(DType::F32)?;
+        let expected = expected.flatten_all()?.to_vec1::<f32>()?;
+        assert_eq!(t, expected);
+        Ok(())
+    };
+    check((&lhs + &rhs)?, (&lhs_f32 + &rhs_f32)?)?;
+    check((&lhs - &rhs)?, (&lhs_f32 - &rhs_f32)?)?;
+    check((&lhs * &rhs)?, (&lhs_f32 * &rhs_f32)?)?;
+    check((&lhs / &rhs)?, (&lhs_f32 / &rhs_f32)?)?;
+    check(lhs.maximum(&rhs)?, lhs_f32.maximum(&rhs_f32)?)?;
+    check(lhs.minimum(&rhs)?, lhs_f32.minimum(&rhs_f32)?)?;
+    // Broadcasted and strided operands.
+    let row = rhs.i((.., 0..1, ..))?;
+    let row_f32 = rhs_f32.i((.., 0..1, ..))?;
+    check(lhs.broadcast_mul(&row)?, lhs_f32.broadcast_mul(&row_f32)?)?;
+    check((lhs.t()? + rhs.t()?)?, (lhs_f32.t()? + rhs_f32.t()?)?)?;
+    check(lhs.exp()?, lhs_f32.exp()?)?;
+    check(lhs.abs()?.sqrt()?, lhs_f32.abs()?.sqrt()?)?;
+    check(lhs.neg()?, lhs_f32.neg()?)?;
+    check(lhs.sqr()?, lhs_f32.sqr()?)?;
+    check(lhs.relu()?, lhs_f32.relu()?)?;
+    check(lhs.silu()?, lhs_f32.silu()?)?;
+    check(lhs.t()?.exp()?, lhs_f32.t()?.exp()?)?;
+
+    // The matmul accumulates in f32.
+    let res = lhs.matmul(&rhs.t()?)?;
+    check(res, lhs_f32.matmul(&rhs_f32.t()?)?)?;
+    let res = lhs.i(0)?.matmul(&rhs.i(1)?.t()?.contiguous()?)?;
+    check(res, lhs_f32.i(0)?.matmul(&rhs_f32.i(1)?.t()?)?)?;
+    // Views that start at an offset and only cover part of their storage.
+    let res = lhs
+        .i(2)?
+        .narrow(1, 2, 4)?
+        .matmul(&rhs.i(1)?.narrow(1, 2, 4)?.t()?)?;
+    let lhs_f32 = lhs_f32.i(2)?.narrow(1, 2, 4)?;
+    check(res, lhs_f32.matmul(&rhs_f32.i(1)?.narrow(1, 2, 4)?.t()?)?)?;
+    Ok(())
+}
+
+#[test]
+fn fp8_cpu() -> Result<()> {
+    use candle_core::{F8E4M3, F8E5M2};
+    for (v, e4m3, e5m2) in [
+        (0f32, 0x00, 0x00),
+        (-0., 0x80, 0x80),
+        (1., 0x38, 0x3c),
+        (-2., 0xc0, 0xc0),
+        (0.3, 0x2a, 0x35),
+        (448., 0x7e, 0x5f),
+        // Ties are rounded to even, 464 is between 448 and 480.
+        (464., 0x7e, 0x5f),
+        (480., 0x7f, 0x60),
+        (57344., 0x7f, 0x7b),
+        (65536., 0x7f, 0x7c),
+        (-f32::INFINITY, 0xff, 0xfc),
+        // The smallest subnormal values.
+        (f32::powi(2., -9), 0x01, 0x18),
+        (f32::powi(2., -16), 0x00, 0x01),
+    ] {
+        assert_eq!(F8E4M3::from_f32(v).to_bits(), e4m3, "{v}");
+        assert_eq!(F8E5M2::from_f32(v).to_bits(), e5m2, "{v}");
+    }
+    assert!(F8E4M3::from_f32(f32::NAN).to_f32().is_nan());
+    assert!(F8E5M2::from_f32(f32::NAN).to_f32().is_nan());
+    assert_eq!(F8E4M3::MAX.to_f32(), 448.);
+    assert_eq!(F8E5M2::MAX.to_f32(), 57344.);
+    assert_eq!(F8E5M2::INFINITY.to_f32(), f32::INFINITY);
+    // All the finite values round trip through f32.
+    for bits in 0..=255u8 {
+        let v = F8E4M3::from_bits(bits);
+        if !v.is_nan() {
+            assert_eq!(F8E4M3::from_f32(v.to_f32()).to_bits(), bits);
+        }
+        let v = F8E5M2::from_bits(bits);
+        if !v.is_nan() {
+            assert_eq!(F8E5M2::from_f32(v.to_f32()).to_bits(), bits);
+        }
+    }
+
+    let device = &Device::Cpu;
+    let t = Tensor::new(&[[1f32, -2.5, 0.3], [448., 3.1, -0.0625]], device)?;
+    let t8 = t.to_dtype(DType::F8E4M3)?;
+    assert_eq!(t8.dtype(), DType::F8E4M3);
+    assert_eq!(
+        t8.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        [[1., -2.5, 0.3125], [448., 3.0, -0.0625]]
+    );
+    assert_eq!(
+        t8.to_dtype(DType::F8E5M2)?
+            .to_dtype(DType::F32)?
+            .to_vec2::<f32>()?,
+        [[1., -2.5, 0.3125], [448., 3.0, -0.0625]]
+    );
+    // The arithmetic is done in f32 and rounded back, 448 + 448 overflows to NaN.
+    let sum = (&t8 + &t8)?.to_dtype(DType::F32)?.to_vec2::<f32>()?;
+    assert_eq!(sum[0], [2., -5., 0.625]);
+    assert!(sum[1][0].is_nan());
+    assert_eq!(sum[1][1..], [6., -0.125]);
+    let t8_t = t8.t()?.contiguous()?.to_dtype(DType::F32)?;
+    assert_eq!(
+        t8_t.to_vec2::<f32>()?,
+        t8.to_dtype(DType::F32)?.t()?.to_vec2::<f32>()?
+    );
+    let cat = Tensor::cat(&[&t8, &t8], 1)?.to_dtype(DType::F32)?;
+    assert_eq!(cat.dims(), [2, 6]);
+    let t8 = t8.narrow(1, 1, 2)?;
+    let mm = t8.matmul(&t8.t()?)?;
+    assert_eq!(mm.dtype(), DType::F8E4M3);
+    let mm_f32 = t8.to_dtype(DType::F32)?;
+    let mm_f32 = mm_f32.matmul(&mm_f32.t()?)?.to_dtype(DType::F8E4M3)?;
+    assert_eq!(
+        mm.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        mm_f32.to_dtype(DType::F32)?.to_vec2::<f32>()?
+    );
+    assert_eq!(
+        format!("{t8}"),
+        format!("{}", t8.to_dtype(DType::F32)?).replace("f32", "f8e4m3")
+    );
+    Ok(())
+}
+
+#[test]
+fn bool_cpu() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t = Tensor::new(&[0f32, 1.5, -2., 0.], dev)?.to_dtype(DType::Bool)?;
+    assert_eq!(t.dtype(), DType::Bool);
+    assert_eq!(t.to_dtype(DType::U8)?.to_vec1::<u8>()?, [0, 1, 1, 0]);
+    assert_eq!(t.to_dtype(DType::F32)?.to_vec1::<f32>()?, [0., 1., 1., 0.]);
+
+    let u = Tensor::new(&[1u8, 1, 0, 0], dev)?.to_dtype(DType::Bool)?;
+    let to_u8 = |t: Tensor| t.to_dtype(DType::U8)?.to_vec1::<u8>();
+    assert_eq!(to_u8(t.logical_and(&u)?)?, [0, 1, 0, 0]);
+    assert_eq!(to_u8(t.logical_or(&u)?)?, [1, 1, 1, 0]);
+    assert_eq!(to_u8(t.logical_xor(&u)?)?, [1, 0, 1, 0]);
+    assert_eq!(to_u8(t.logical_not()?)?, [1, 0, 0, 1]);
+
+    let on_true = Tensor::new(&[1f32, 2., 3., 4.], dev)?;
+    let on_false = on_true.neg()?;
+    let w = t.where_cond(&on_true, &on_false)?;
+    assert_eq!(w.to_vec1::<f32>()?, [-1., 2., 3., -4.]);
+
+    // Booleans cannot be used in arithmetic, they have to be converted explicitly.
+    assert!(t.add(&u).is_err());
+    assert!(t.sum_all().is_err());
+    assert!(t.exp().is_err());
+    assert!(t.affine(2., 1.).is_err());
+    assert!(t.add_assign_(&u).is_err());
+    assert!(t.logical_and(&u.to_dtype(DType::U8)?).is_err());
+
+    let t = Tensor::new(&[[1u8, 0, 0, 1, 1, 1, 0, 0, 1, 0]], dev)?.to_dtype(DType::Bool)?;
+    let p = t.pack_bits()?;
+    assert_eq!(p.to_vec2::<u8>()?, [[0b1001_1100, 0b1000_0000]]);
+    let u = p.unpack_bits(10)?;
+    assert_eq!(u.dtype(), DType::Bool);
+    assert_eq!(
+        u.to_dtype(DType::U8)?.to_vec2::<u8>()?,
+        [[1, 0, 0, 1, 1, 1, 0, 0, 1, 0]]
+    );
+    assert!(p.unpack_bits(17).is_err());
+    Ok(())
+}
+
 #[test]
 fn tril_triu_eye() -> Result<()> {
     let t = Tensor::tril2(4, DType::F32, &Device::Cpu)?;
@@ -1311,6 +2357,24 @@ fn cumsum() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn cumprod() -> Result<()> {
+    let t = &[3f32, 1., 4., 1., 5.];
+    let t = Tensor::new(t, &Device::Cpu)?;
+    assert_eq!(t.cumprod(0)?.to_vec1::<f32>()?, [3., 3., 12., 12., 60.]);
+    let t = &[[3u32, 1, 4, 1, 5], [2, 1, 7, 0, 2]];
+    let t = Tensor::new(t, &Device::Cpu)?;
+    assert_eq!(
+        t.cumprod(1)?.to_vec2::<u32>()?,
+        [[3, 3, 12, 12, 60], [2, 2, 14, 0, 0]],
+    );
+    assert_eq!(
+        t.cumprod(0)?.to_vec2::<u32>()?,
+        [[3, 1, 4, 1, 5], [6, 1, 28, 0, 10]]
+    );
+    Ok(())
+}
+
 /// A helper function for floating point comparison. Both a and b must be 1D Tensor and contains the same amount of data.
 /// Assertion passes if the difference of all pairs of a and b is smaller than epsilon.
 fn assert_close(a: &Tensor, b: &Tensor, epsilon: f64) -> Result<()> {
@@ -1331,6 +2395,20 @@ fn log_sum_exp() -> Result<()> {
     // The expectations obtained from pytorch.
     let expected = Tensor::new(&[3.4076, 6.4076], &Device::Cpu)?;
     assert_close(&output, &expected, 0.00001)?;
+    let output = input.log_sum_exp((0, 1))?.to_scalar::<f64>()?;
+    assert!((output - 6.4561).abs() < 1e-4);
+    let output = input.log_sum_exp_keepdim(0)?;
+    assert_eq!(output.dims(), [1, 3]);
+    let expected = Tensor::new(&[4.0486, 5.0486, 6.0486], &Device::Cpu)?;
+    assert_close(&output.squeeze(0)?, &expected, 0.0001)?;
+    // Large values do not overflow and infinite values are handled.
+    let input = Tensor::new(
+        &[[1000f32, 1000.], [f32::NEG_INFINITY, f32::NEG_INFINITY]],
+        &Device::Cpu,
+    )?;
+    let output = input.log_sum_exp(1)?.to_vec1::<f32>()?;
+    assert!((output[0] - 1000.6931).abs() < 1e-3);
+    assert_eq!(output[1], f32::NEG_INFINITY);
     Ok(())
 }
 
@@ -1345,3 +2423,202 @@ fn pow() -> Result<()> {
     );
     Ok(())
 }
+
+fn to_device_peer(device: &Device) -> Result<()> {
+    let targets = match device {
+        // A second device on the same gpu is distinct from `device` so the transfer goes through
+        // the peer copy, a second gpu is used too when available.
+        Device::Cuda(_) => {
+            let mut targets = vec![Device::new_cuda(0)?];
+            if let Ok(target) = Device::new_cuda(1) {
+                targets.push(target)
+            }
+            targets
+        }
+        _ => vec![Device::Cpu],
+    };
+    let t = Tensor::arange(0f32, 12., device)?.reshape((3, 4))?;
+    // The transfer has to wait for the pending operations on the source device.
+    let t = (t.t()? * 2.)?;
+    for target in targets.iter() {
+        let u = t.to_device(target)?;
+        assert!(u.device().same_device(target));
+        assert_eq!(u.dims(), &[4, 3]);
+        assert_eq!(u.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+        let u = (u + 1.)?.to_device(device)?;
+        assert!(u.device().same_device(device));
+        assert_eq!(u.to_vec2::<f32>()?, (&t + 1.)?.to_vec2::<f32>()?);
+        let u = t.to_dtype(DType::U8)?.to_device(target)?;
+        assert_eq!(u.to_vec2::<u8>()?, t.to_dtype(DType::U8)?.to_vec2::<u8>()?);
+    }
+    Ok(())
+}
+
+test_device!(
+    to_device_peer,
+    to_device_peer_cpu,
+    to_device_peer_gpu,
+    to_device_peer_metal
+);
+
+fn cuda_stream_upload(device: &Device) -> Result<()> {
+    let cuda = match device {
+        Device::Cuda(cuda) => cuda,
+        _ => return Ok(()),
+    };
+    let stream = cuda.new_stream()?;
+    let w = Tensor::ones((32, 32), DType::F32, device)?;
+    let src = Tensor::arange(0f32, 1024., &Device::Cpu)?.reshape((32, 32))?;
+    let mut uploads = vec![];
+    for i in 0..4 {
+        let src = (&src + i as f64)?;
+        uploads.push((src.to_device_with_stream(&stream)?, src))
+    }
+    let t = src.t()?.to_device_with_stream(&stream)?;
+    stream.join()?;
+    for (t, src) in uploads.iter() {
+        assert!(t.device().same_device(device));
+        let expected = src.matmul(&w.to_device(&Device::Cpu)?)?;
+        assert_eq!(t.matmul(&w)?.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    }
+    assert_eq!(t.to_vec2::<f32>()?, src.t()?.to_vec2::<f32>()?);
+
+    // The stream waits for the work queued on the default stream before running the uploads.
+    let w2 = (&w * 3.)?;
+    w2.record_stream(&stream)?;
+    stream.wait_for_default()?;
+    let t = src.to_device_with_stream(&stream)?;
+    stream.synchronize()?;
+    assert_eq!(t.to_vec2::<f32>()?, src.to_vec2::<f32>()?);
+    assert_eq!(w2.sum_all()?.to_scalar::<f32>()?, 3072.);
+
+    // Only cpu tensors can be uploaded and only tensors from the stream device can be recorded.
+    assert!(w.to_device_with_stream(&stream).is_err());
+    assert!(src.record_stream(&stream).is_err());
+    Ok(())
+}
+
+test_device!(
+    cuda_stream_upload,
+    cuda_stream_upload_cpu,
+    cuda_stream_upload_gpu,
+    cuda_stream_upload_metal
+);
+
+fn cuda_graph(device: &Device) -> Result<()> {
+    let cuda = match device {
+        Device::Cuda(cuda) => cuda,
+        _ => return Ok(()),
+    };
+    // Capturing requires a device that does not use the legacy default stream.
+    assert!(cuda.capture(|| Ok(())).is_err());
+    let device = Device::new_cuda_with_stream(0)?;
+    let cuda = match &device {
+        Device::Cuda(cuda) => cuda,
+        _ => unreachable!(),
+    };
+    let x = Tensor::zeros(4, DType::F32, &device)?;
+    let out = Tensor::zeros(4, DType::F32, &device)?;
+    let graph = cuda.capture(|| {
+        let y = ((&x + 1.)? * 2.)?.exp()?.log()?;
+        out.slice_set(&y, 0, 0)
+    })?;
+    // The captured operations are not run by the capture itself.
+    assert_eq!(out.to_vec1::<f32>()?, [0., 0., 0., 0.]);
+    for step in 0..3 {
+        let v = Tensor::new(&[1f32, 2., 3., 4.], &device)?;
+        x.slice_set(&(v + step as f64)?, 0, 0)?;
+        graph.replay()?;
+        let expected: Vec<f32> = (1..5).map(|v| 2. * (v + step + 1) as f32).collect();
+        let got = out.to_vec1::<f32>()?;
+        for (g, e) in got.iter().zip(expected.iter()) {
+            assert!((g - e).abs() < 1e-4, "{got:?} {expected:?}")
+        }
+    }
+    // A failure within the closure aborts the capture.
+    assert!(cuda.capture(|| candle_core::bail!("boom")).is_err());
+    Ok(())
+}
+
+test_device!(cuda_graph, cuda_graph_cpu, cuda_graph_gpu, cuda_graph_metal);
+
+fn to_device_async(device: &Device) -> Result<()> {
+    let src = Tensor::arange(0f32, 4096., &Device::Cpu)?.reshape((64, 64))?;
+    // Several uploads in flight at the same time use distinct staging buffers.
+    let mut uploads = vec![];
+    for i in 0..4 {
+        let src = (&src * i as f64)?;
+        uploads.push((src.to_device_async(device)?, src))
+    }
+    for (t, src) in uploads.iter() {
+        assert!(t.device().same_device(device));
+        assert_eq!((t + 1.)?.to_vec2::<f32>()?, (src + 1.)?.to_vec2::<f32>()?);
+    }
+    // The staging buffers get reused once the copies have completed.
+    for dtype in [DType::U8, DType::U32, DType::F16, DType::F64] {
+        let src = src.to_dtype(dtype)?;
+        let t = src.t()?.to_device_async(device)?;
+        assert_eq!(
+            t.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+            src.t()?.to_dtype(DType::F32)?.to_vec2::<f32>()?
+        );
+    }
+    if let Device::Cuda(cuda) = device {
+        cuda.trim_pinned_pool()?;
+    }
+    // Transfers that do not start from the cpu are synchronous.
+    let t = uploads[1].0.to_device_async(&Device::Cpu)?;
+    assert_eq!(t.to_vec2::<f32>()?, uploads[1].1.to_vec2::<f32>()?);
+    Ok(())
+}
+
+test_device!(
+    to_device_async,
+    to_device_async_cpu,
+    to_device_async_gpu,
+    to_device_async_metal
+);
+
+fn caching_allocator(device: &Device) -> Result<()> {
+    let cuda = match device {
+        Device::Cuda(cuda) => cuda,
+        _ => return Ok(()),
+    };
+    if let Err(err) = cuda.enable_caching_allocator() {
+        println!("skipping the caching allocator test: {err}");
+        return Ok(());
+    }
+    assert!(cuda.caching_allocator_enabled());
+    let t = Tensor::ones(3 << 18, DType::F32, device)?;
+    assert_eq!(t.sum_all()?.to_scalar::<f32>()?, (3 << 18) as f32);
+    let used = device.memory_stats()?.allocated;
+    assert!(used >= 3 << 20, "{used}");
+    drop(t);
+    device.synchronize()?;
+    let reserved = device.memory_stats()?.reserved;
+    assert!(reserved >= 3 << 20, "{reserved}");
+    // A slightly smaller allocation falls in the same bucket and reuses the cached block.
+    let t = Tensor::zeros((3 << 18) - 100, DType::F32, device)?;
+    assert_eq!(t.sum_all()?.to_scalar::<f32>()?, 0.);
+    assert_eq!(device.memory_stats()?.reserved, reserved);
+    drop(t);
+    cuda.trim_mem_pool(0)?;
+    assert!(device.memory_stats()?.reserved < reserved);
+    Ok(())
+}
+
+test_device!(
+    caching_allocator,
+    caching_allocator_cpu,
+    caching_allocator_gpu,
+    caching_allocator_metal
+);
+
+#[cfg(not(feature = "tracking-allocator"))]
+#[test]
+fn memory_stats_cpu() {
+    // The test binary does not install the tracking allocator so the cpu usage is unknown.
+    assert!(candle_core::cpu::alloc::TrackingAllocator::stats().is_none());
+    assert!(Device::Cpu.memory_stats().is_err());
+    assert!(Device::Cpu.reset_peak_memory_stats().is_err());
+}
@@ -62,6 +62,27 @@ BINARY_OP(uint8_t, bmaximum_u8, maxg(x, y));
 BINARY_OP(uint32_t, bmaximum_u32, maxg(x, y));
 BINARY_OP(int64_t, bmaximum_i64, maxg(x, y));
 
+// Shifting by a negative amount or by at least the number of bits results in 0, or in -1 when
+// shifting a negative value to the right.
+#define SHL(x, y) ((uint64_t)(y) >= 8 * sizeof(x) ? 0 : (x) << (y))
+#define SHR(x, y) ((uint64_t)(y) >= 8 * sizeof(x) ? ((x) < 0 ? -1 : 0) : (x) >> (y))
+
+BINARY_OP(uint8_t, bbitwise_and_u8, x & y);
+BINARY_OP(uint32_t, bbitwise_and_u32, x & y);
+BINARY_OP(int64_t, bbitwise_and_i64, x & y);
+BINARY_OP(uint8_t, bbitwise_or_u8, x | y);
+BINARY_OP(uint32_t, bbitwise_or_u32, x | y);
+BINARY_OP(int64_t, bbitwise_or_i64, x | y);
+BINARY_OP(uint8_t, bbitwise_xor_u8, x ^ y);
+BINARY_OP(uint32_t, bbitwise_xor_u32, x ^ y);
+BINARY_OP(int64_t, bbitwise_xor_i64, x ^ y);
+BINARY_OP(uint8_t, bshl_u8, SHL(x, y));
+BINARY_OP(uint32_t, bshl_u32, SHL(x, y));
+BINARY_OP(int64_t, bshl_i64, SHL(x, y));
+BINARY_OP(uint8_t, bshr_u8, SHR(x, y));
+BINARY_OP(uint32_t, bshr_u32, SHR(x, y));
+BINARY_OP(int64_t, bshr_i64, SHR(x, y));
+
 BINARY_OP_OUT(float, uint8_t, eq_f32, x == y)
 BINARY_OP_OUT(double, uint8_t, eq_f64, x == y)
 BINARY_OP_OUT(uint8_t, uint8_t, eq_u8, x == y)
@@ -139,3 +139,93 @@ CAST_OP(double, uint32_t, cast_f64_u32)
 CAST_OP(double, int64_t,  cast_f64_i64 )
 CAST_OP(double, float,    cast_f64_f32)
 CAST_OP(double, double,   cast_f64_f64)
+
+#include <cuda.h>
+#if CUDA_VERSION >= 11080
+#include "cuda_fp8.h"
+
+// The 8 bits floats are stored as uint8_t and converted through f32. The conversions do not
+// saturate so that out of range values result in NaN for f8e4m3 and in infinities for f8e5m2,
+// similar to the cpu implementation.
+__device__ __forceinline__ float f8e4m3_to_f32(uint8_t v) {
+    return __half2float(__half(__nv_cvt_fp8_to_halfraw(v, __NV_E4M3)));
+}
+
+__device__ __forceinline__ float f8e5m2_to_f32(uint8_t v) {
+    return __half2float(__half(__nv_cvt_fp8_to_halfraw(v, __NV_E5M2)));
+}
+
+template <typename T>
+__device__ __forceinline__ T f8e4m3_to(uint8_t v) {
+    return static_cast<T>(f8e4m3_to_f32(v));
+}
+
+template <typename T>
+__device__ __forceinline__ T f8e5m2_to(uint8_t v) {
+    return static_cast<T>(f8e5m2_to_f32(v));
+}
+
+template <typename S>
+__device__ __forceinline__ uint8_t f8e4m3_from(S v) {
+    return __nv_cvt_float_to_fp8(static_cast<float>(v), __NV_NOSAT, __NV_E4M3);
+}
+
+template <typename S>
+__device__ __forceinline__ uint8_t f8e5m2_from(S v) {
+    return __nv_cvt_float_to_fp8(static_cast<float>(v), __NV_NOSAT, __NV_E5M2);
+}
+
+#define CAST_FN_OP(SRC_TYPENAME, DST_TYPENAME, FN, FN_NAME) \
+extern "C" __global__ void FN_NAME( \
+    const size_t numel, \
+    const size_t num_dims, \
+    const size_t *info, \
+    const SRC_TYPENAME *inp, \
+    DST_TYPENAME *out \
+) { \
+    const size_t *dims = info; \
+    const size_t *strides = info + num_dims; \
+    if (info == nullptr || is_contiguous(num_dims, dims, strides)) { \
+        for (unsigned int i = blockIdx.x * blockDim.x + threadIdx.x; i < numel; i += blockDim.x * gridDim.x) { \
+            out[i] = FN(inp[i]); \
+        } \
+    } \
+    else { \
+        for (unsigned int i = blockIdx.x * blockDim.x + threadIdx.x; i < numel; i += blockDim.x * gridDim.x) { \
+            unsigned strided_i = get_strided_index(i, num_dims, dims, strides); \
+            out[i] = FN(inp[strided_i]); \
+        } \
+    } \
+} \
+
+#define CAST_F8_OPS(F8_NAME) \
+CAST_OP(uint8_t, uint8_t, cast_##F8_NAME##_##F8_NAME) \
+CAST_FN_OP(uint8_t, uint8_t,       F8_NAME##_to<uint8_t>,       cast_##F8_NAME##_u8) \
+CAST_FN_OP(uint8_t, uint32_t,      F8_NAME##_to<uint32_t>,      cast_##F8_NAME##_u32) \
+CAST_FN_OP(uint8_t, int64_t,       F8_NAME##_to<int64_t>,       cast_##F8_NAME##_i64) \
+CAST_FN_OP(uint8_t, __nv_bfloat16, F8_NAME##_to<__nv_bfloat16>, cast_##F8_NAME##_bf16) \
+CAST_FN_OP(uint8_t, __half,        F8_NAME##_to<__half>,        cast_##F8_NAME##_f16) \
+CAST_FN_OP(uint8_t, float,         F8_NAME##_to<float>,         cast_##F8_NAME##_f32) \
+CAST_FN_OP(uint8_t, double,        F8_NAME##_to<double>,        cast_##F8_NAME##_f64) \
+CAST_FN_OP(uint8_t,       uint8_t, F8_NAME##_from<uint8_t>,       cast_u8_##F8_NAME) \
+CAST_FN_OP(uint32_t,      uint8_t, F8_NAME##_from<uint32_t>,      cast_u32_##F8_NAME) \
+CAST_FN_OP(int64_t,       uint8_t, F8_NAME##_from<int64_t>,       cast_i64_##F8_NAME) \
+CAST_FN_OP(__nv_bfloat16, uint8_t, F8_NAME##_from<__nv_bfloat16>, cast_bf16_##F8_NAME) \
+CAST_FN_OP(__half,        uint8_t, F8_NAME##_from<__half>,        cast_f16_##F8_NAME) \
+CAST_FN_OP(float,         uint8_t, F8_NAME##_from<float>,         cast_f32_##F8_NAME) \
+CAST_FN_OP(double,        uint8_t, F8_NAME##_from<double>,        cast_f64_##F8_NAME) \
+
+CAST_F8_OPS(f8e4m3)
+CAST_F8_OPS(f8e5m2)
+
+__device__ __forceinline__ uint8_t f8e4m3_to_f8e5m2(uint8_t v) {
+    return f8e5m2_from(f8e4m3_to_f32(v));
+}
+
+__device__ __forceinline__ uint8_t f8e5m2_to_f8e4m3(uint8_t v) {
+    return f8e4m3_from(f8e5m2_to_f32(v));
+}
+
+CAST_FN_OP(uint8_t, uint8_t, f8e4m3_to_f8e5m2, cast_f8e4m3_f8e5m2)
+CAST_FN_OP(uint8_t, uint8_t, f8e5m2_to_f8e4m3, cast_f8e5m2_f8e4m3)
+#endif
@@ -540,6 +540,65 @@ __device__ void upsample_nearest2d(
 }
 
 
+// Depthwise convolution, each output channel only depends on the input channel
+// `c_idx / (c_out / c_in)`. One thread computes one output element.
+template <typename T, typename A>
+__device__ void depthwise_conv2d(
+    const size_t dst_numel,
+    const size_t *info,
+    const T *src,
+    const T *kernel,
+    T *dst
+) {
+  const size_t dst_i = blockIdx.x * blockDim.x + threadIdx.x;
+  // src: (b_size, c_in, h_in, w_in), contiguous
+  // k: (c_out, 1, h_k, w_k), contiguous
+  // dst: (b_size, c_out, h_out, w_out)
+  if (dst_i >= dst_numel) {
+    return;
+  }
+  const size_t c_in = info[1];
+  const size_t h_in = info[2];
+  const size_t w_in = info[3];
+  const size_t c_out = info[4];
+  const size_t h_k = info[5];
+  const size_t w_k = info[6];
+  const size_t h_out = info[7];
+  const size_t w_out = info[8];
+  const size_t p_h = info[9];
+  const size_t p_w = info[10];
+  const size_t s_h = info[11];
+  const size_t s_w = info[12];
+  const size_t d_h = info[13];
+  const size_t d_w = info[14];
+
+  const size_t dst_w = dst_i % w_out;
+  const size_t dst_h = (dst_i / w_out) % h_out;
+  const size_t c_idx = (dst_i / (w_out * h_out)) % c_out;
+  const size_t b_idx = dst_i / (w_out * h_out * c_out);
+  const size_t src_c = c_idx / (c_out / c_in);
+  const T *src_p = src + (b_idx * c_in + src_c) * h_in * w_in;
+  const T *k_p = kernel + c_idx * h_k * w_k;
+
+  A d = 0;
+  for (size_t offset_h = 0; offset_h < h_k; ++offset_h) {
+    size_t src_h = dst_h * s_h + offset_h * d_h;
+    if (src_h < p_h || src_h >= h_in + p_h) {
+      continue;
+    }
+    src_h -= p_h;
+    for (size_t offset_w = 0; offset_w < w_k; ++offset_w) {
+      size_t src_w = dst_w * s_w + offset_w * d_w;
+      if (src_w < p_w || src_w >= w_in + p_w) {
+        continue;
+      }
+      src_w -= p_w;
+      d += static_cast<A>(src_p[src_h * w_in + src_w]) * static_cast<A>(k_p[offset_h * w_k + offset_w]);
+    }
+  }
+  dst[dst_i] = static_cast<T>(d);
+}
+
 #define CONV1D_OP(TYPENAME, TYPEACC, FN_NAME) \
 extern "C" __global__ void FN_NAME(  \
     const size_t src_numel, \
@@ -571,6 +630,17 @@ extern "C" __global__ void FN_NAME(  \
   conv2d<TYPENAME, TYPEACC>(src_numel, w_out, h_out, stride, padding, dilation, info, src, kernel, dst); \
 } \
 
+#define DEPTHWISE_CONV2D_OP(TYPENAME, TYPEACC, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const size_t dst_numel, \
+    const size_t *info, \
+    const TYPENAME *src, \
+    const TYPENAME *kernel, \
+    TYPENAME *dst \
+) {  \
+  depthwise_conv2d<TYPENAME, TYPEACC>(dst_numel, info, src, kernel, dst); \
+} \
+
 #define IM2COL1D_OP(TYPENAME, FN_NAME) \
 extern "C" __global__ void FN_NAME(  \
     const size_t dst_numel, \
@@ -694,6 +764,7 @@ extern "C" __global__ void FN_NAME(  \
 #if __CUDA_ARCH__ >= 800
 CONV1D_OP(__nv_bfloat16, float, conv1d_bf16)
 CONV2D_OP(__nv_bfloat16, float, conv2d_bf16)
+DEPTHWISE_CONV2D_OP(__nv_bfloat16, float, depthwise_conv2d_bf16)
 CONVT1D_OP(__nv_bfloat16, float, conv_transpose1d_bf16)
 CONVT2D_OP(__nv_bfloat16, float, conv_transpose2d_bf16)
 AVG_POOL2D_OP(__nv_bfloat16, float, avg_pool2d_bf16)
@@ -707,6 +778,7 @@ COL2IM1D_OP(__nv_bfloat16, col2im1d_bf16)
 #if __CUDA_ARCH__ >= 530
 CONV1D_OP(__half, float, conv1d_f16)
 CONV2D_OP(__half, float, conv2d_f16)
+DEPTHWISE_CONV2D_OP(__half, float, depthwise_conv2d_f16)
 CONVT1D_OP(__half, float, conv_transpose1d_f16)
 CONVT2D_OP(__half, float, conv_transpose2d_f16)
 AVG_POOL2D_OP(__half, float, avg_pool2d_f16)
@@ -727,6 +799,9 @@ CONV2D_OP(double, double, conv2d_f64)
 CONV2D_OP(uint8_t, uint8_t, conv2d_u8)
 CONV2D_OP(uint32_t, uint32_t, conv2d_u32)
 
+DEPTHWISE_CONV2D_OP(float, float, depthwise_conv2d_f32)
+DEPTHWISE_CONV2D_OP(double, double, depthwise_conv2d_f64)
+
 CONVT1D_OP(float, float, conv_transpose1d_f32)
 CONVT1D_OP(double, double, conv_transpose1d_f64)
 CONVT1D_OP(uint8_t, uint8_t, conv_transpose1d_u8)
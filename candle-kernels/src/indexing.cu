@@ -111,6 +111,43 @@ extern "C" __global__ void FN_NAME(  \
     const size_t right_size \
 ) { index_add(ids, ids_dim_size, inp, out, left_size, src_dim_size, dst_dim_size, right_size); } \
 
+template<typename T, typename I>
+__device__ void index_set(
+    const I *ids,
+    const size_t ids_dim_size,
+    const T *inp,
+    T *out,
+    const size_t left_size,
+    const size_t src_dim_size,
+    const size_t dst_dim_size,
+    const size_t right_size
+) {
+      const size_t numel = left_size * right_size;
+      for (unsigned int i = blockIdx.x * blockDim.x + threadIdx.x; i < numel; i += blockDim.x * gridDim.x) {
+          const size_t pre = i / right_size;
+          const size_t post = i % right_size;
+          // The ids are processed in order so the last write wins for duplicated indexes.
+          for (unsigned int j = 0; j < ids_dim_size; ++j) {
+              const size_t idx = ids[j];
+              const size_t src_i = (pre * ids_dim_size + j) * right_size + post;
+              const size_t dst_i = (pre * dst_dim_size + idx) * right_size + post;
+              out[dst_i] = inp[src_i];
+          }
+      }
+}
+
+#define ISET_OP(TYPENAME, INDEX_TYPENAME, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const INDEX_TYPENAME *ids, \
+    const size_t ids_dim_size, \
+    const TYPENAME *inp, \
+    TYPENAME *out, \
+    const size_t left_size, \
+    const size_t src_dim_size, \
+    const size_t dst_dim_size, \
+    const size_t right_size \
+) { index_set(ids, ids_dim_size, inp, out, left_size, src_dim_size, dst_dim_size, right_size); } \
+
 template<typename T, typename I>
 __device__ void scatter_add(
     const I *ids,
@@ -156,6 +193,10 @@ GATHER_OP(__nv_bfloat16, uint8_t, gather_u8_bf16)
 IA_OP(__nv_bfloat16, int64_t, ia_i64_bf16)
 IA_OP(__nv_bfloat16, uint32_t, ia_u32_bf16)
 IA_OP(__nv_bfloat16, uint8_t, ia_u8_bf16)
+
+ISET_OP(__nv_bfloat16, int64_t, iset_i64_bf16)
+ISET_OP(__nv_bfloat16, uint32_t, iset_u32_bf16)
+ISET_OP(__nv_bfloat16, uint8_t, iset_u8_bf16)
 SA_OP(__nv_bfloat16, int64_t, sa_i64_bf16)
 SA_OP(__nv_bfloat16, uint32_t, sa_u32_bf16)
 SA_OP(__nv_bfloat16, uint8_t, sa_u8_bf16)
@@ -171,6 +212,10 @@ GATHER_OP(__half, uint8_t, gather_u8_f16)
 IA_OP(__half, int64_t, ia_i64_f16)
 IA_OP(__half, uint32_t, ia_u32_f16)
 IA_OP(__half, uint8_t, ia_u8_f16)
+
+ISET_OP(__half, int64_t, iset_i64_f16)
+ISET_OP(__half, uint32_t, iset_u32_f16)
+ISET_OP(__half, uint8_t, iset_u8_f16)
 SA_OP(__half, int64_t, sa_i64_f16)
 SA_OP(__half, uint32_t, sa_u32_f16)
 SA_OP(__half, uint8_t, sa_u8_f16)
@@ -230,6 +275,24 @@ IA_OP(uint8_t, uint8_t, ia_u8_u8)
 IA_OP(uint32_t, uint8_t, ia_u8_u32)
 IA_OP(int64_t, uint8_t, ia_u8_i64)
 
+ISET_OP(float, int64_t, iset_i64_f32)
+ISET_OP(double, int64_t, iset_i64_f64)
+ISET_OP(uint8_t, int64_t, iset_i64_u8)
+ISET_OP(uint32_t, int64_t, iset_i64_u32)
+ISET_OP(int64_t, int64_t, iset_i64_i64)
+
+ISET_OP(float, uint32_t, iset_u32_f32)
+ISET_OP(double, uint32_t, iset_u32_f64)
+ISET_OP(uint8_t, uint32_t, iset_u32_u8)
+ISET_OP(uint32_t, uint32_t, iset_u32_u32)
+ISET_OP(int64_t, uint32_t, iset_u32_i64)
+
+ISET_OP(float, uint8_t, iset_u8_f32)
+ISET_OP(double, uint8_t, iset_u8_f64)
+ISET_OP(uint8_t, uint8_t, iset_u8_u8)
+ISET_OP(uint32_t, uint8_t, iset_u8_u32)
+ISET_OP(int64_t, uint8_t, iset_u8_i64)
+
 SA_OP(float, int64_t, sa_i64_f32)
 SA_OP(double, int64_t, sa_i64_f64)
 SA_OP(uint8_t, int64_t, sa_i64_u8)
@@ -11,6 +11,16 @@ static inline __device__ void ggml_cuda_swap(T & a, T & b) {
     b = tmp;
 }
 
+// Returns true if the element at index a has to be placed after the one at index b, ties are
+// broken using the original index so that the resulting sort is stable.
+template<int order, typename T>
+static inline __device__ bool sort_after(const T * x_row, int a, int b) {
+    if (order == SORT_ORDER_ASC ? x_row[a] > x_row[b] : x_row[a] < x_row[b]) {
+        return true;
+    }
+    return x_row[a] == x_row[b] && a > b;
+}
+
 template<int order, typename T>
 static __device__ void k_argsort(const T * x, uint32_t * dst, const int ncols, int ncols_pad) {
     // bitonic sort
@@ -35,17 +45,13 @@ static __device__ void k_argsort(const T * x, uint32_t * dst, const int ncols, i
             if (ixj > col) {
                 if ((col & k) == 0) {
                     if (dst_row[col] >= ncols ||
-                        (dst_row[ixj] < ncols && (order == SORT_ORDER_ASC ?
-                            x_row[dst_row[col]] > x_row[dst_row[ixj]] :
-                            x_row[dst_row[col]] < x_row[dst_row[ixj]]))
+                        (dst_row[ixj] < ncols && sort_after<order>(x_row, dst_row[col], dst_row[ixj]))
                     ) {
                         ggml_cuda_swap(dst_row[col], dst_row[ixj]);
                     }
                 } else {
                     if (dst_row[ixj] >= ncols ||
-                        (dst_row[col] < ncols && (order == SORT_ORDER_ASC ?
-                            x_row[dst_row[col]] < x_row[dst_row[ixj]] :
-                            x_row[dst_row[col]] > x_row[dst_row[ixj]]))
+                        (dst_row[col] < ncols && sort_after<order>(x_row, dst_row[ixj], dst_row[col]))
                     ) {
                         ggml_cuda_swap(dst_row[col], dst_row[ixj]);
                     }
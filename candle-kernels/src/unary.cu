@@ -154,6 +154,33 @@ UNARY_OP(__half, usigmoid_f16, sigmoid_fwd(x))
 UNARY_OP(uint8_t, ucopy_u8, x)
 UNARY_OP(uint32_t, ucopy_u32, x)
 UNARY_OP(int64_t, ucopy_i64, x)
+
+// The integer versions wrap around on overflow, negating an unsigned value returns its two's
+// complement.
+UNARY_OP(uint8_t, uneg_u8, -x)
+UNARY_OP(uint32_t, uneg_u32, -x)
+UNARY_OP(int64_t, uneg_i64, -x)
+UNARY_OP(uint8_t, usqr_u8, x*x)
+UNARY_OP(uint32_t, usqr_u32, x*x)
+UNARY_OP(int64_t, usqr_i64, x*x)
+UNARY_OP(uint8_t, uabs_u8, x)
+UNARY_OP(uint32_t, uabs_u32, x)
+UNARY_OP(int64_t, uabs_i64, x < 0 ? -x : x)
+UNARY_OP(uint8_t, urelu_u8, x)
+UNARY_OP(uint32_t, urelu_u32, x)
+UNARY_OP(int64_t, urelu_i64, x < 0 ? 0 : x)
+UNARY_OP(uint8_t, usign_u8, sign_(x))
+UNARY_OP(uint32_t, usign_u32, sign_(x))
+UNARY_OP(int64_t, usign_i64, sign_(x))
+UNARY_OP(uint8_t, uceil_u8, x)
+UNARY_OP(uint32_t, uceil_u32, x)
+UNARY_OP(int64_t, uceil_i64, x)
+UNARY_OP(uint8_t, ufloor_u8, x)
+UNARY_OP(uint32_t, ufloor_u32, x)
+UNARY_OP(int64_t, ufloor_i64, x)
+UNARY_OP(uint8_t, uround_u8, x)
+UNARY_OP(uint32_t, uround_u32, x)
+UNARY_OP(int64_t, uround_i64, x)
 UNARY_OP(float, ucopy_f32, x)
 UNARY_OP(double, ucopy_f64, x)
 UNARY_OP(float, uneg_f32, -x)
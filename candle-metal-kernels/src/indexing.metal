@@ -187,6 +187,49 @@ kernel void NAME( \
 }
 
 
+template<typename TYPENAME, typename INDEX_TYPENAME>
+METAL_FUNC void index_set( 
+    constant size_t &dst_size, 
+    constant size_t &left_size, 
+    constant size_t &src_dim_size, 
+    constant size_t &right_size, 
+    constant size_t &dst_dim_size, 
+    constant size_t &ids_dim_size, 
+    const device TYPENAME *input, 
+    const device INDEX_TYPENAME *input_ids, 
+    device TYPENAME *output, 
+    uint tid [[ thread_position_in_grid ]] 
+) { 
+    if (tid >= dst_size) { 
+        return; 
+    } 
+    const size_t right_rank_i = tid % right_size; 
+    const size_t left_rank_i = tid / right_size; 
+    // The ids are processed in order so the last write wins for duplicated indexes.
+    for (unsigned int j = 0; j < ids_dim_size; ++j) {
+        const INDEX_TYPENAME idx = input_ids[j];
+        const size_t src_i = (left_rank_i * src_dim_size + j) * right_size + right_rank_i; 
+        const size_t dst_i = (left_rank_i * dst_dim_size + idx) * right_size + right_rank_i; 
+        output[dst_i] = input[src_i]; 
+    }
+}
+
+# define INDEX_SET_OP(NAME, INDEX_TYPENAME, TYPENAME) \
+kernel void NAME( \
+    constant size_t &dst_size, \
+    constant size_t &left_size, \
+    constant size_t &src_dim_size, \
+    constant size_t &right_size, \
+    constant size_t &dst_dim_size, \
+    constant size_t &ids_dim_size, \
+    const device TYPENAME *input, \
+    const device INDEX_TYPENAME *input_ids, \
+    device TYPENAME *output, \
+    uint tid [[ thread_position_in_grid ]] \
+) { \
+    index_set<TYPENAME, INDEX_TYPENAME>(dst_size, left_size, src_dim_size, right_size, dst_dim_size, ids_dim_size, input, input_ids, output, tid); \
+}
+
 INDEX_OP(is_i64_f32, int64_t, float)
 INDEX_OP(is_i64_f16, int64_t, half)
 #if defined(__HAVE_BFLOAT__)
@@ -252,3 +295,33 @@ INDEX_ADD_OP(ia_u8_u8, uint8_t, uint8_t)
 #if defined(__HAVE_BFLOAT__)
 INDEX_ADD_OP(ia_u8_bf16, uint8_t, bfloat)
 #endif
+
+// i64
+INDEX_SET_OP(iset_i64_f16, int64_t, half)
+INDEX_SET_OP(iset_i64_f32, int64_t, float)
+INDEX_SET_OP(iset_i64_i64, int64_t, int64_t)
+INDEX_SET_OP(iset_i64_u32, int64_t, uint32_t)
+INDEX_SET_OP(iset_i64_u8, int64_t, uint8_t)
+#if defined(__HAVE_BFLOAT__)
+INDEX_SET_OP(iset_i64_bf16, int64_t, bfloat)
+#endif
+
+// u32
+INDEX_SET_OP(iset_u32_f16, uint32_t, half)
+INDEX_SET_OP(iset_u32_f32, uint32_t, float)
+INDEX_SET_OP(iset_u32_i64, uint32_t, int64_t)
+INDEX_SET_OP(iset_u32_u32, uint32_t, uint32_t)
+INDEX_SET_OP(iset_u32_u8, uint32_t, uint8_t)
+#if defined(__HAVE_BFLOAT__)
+INDEX_SET_OP(iset_u32_bf16, uint32_t, bfloat)
+#endif
+
+// u8
+INDEX_SET_OP(iset_u8_f16, uint8_t, half)
+INDEX_SET_OP(iset_u8_f32, uint8_t, float)
+INDEX_SET_OP(iset_u8_i64, uint8_t, int64_t)
+INDEX_SET_OP(iset_u8_u32, uint8_t, uint32_t)
+INDEX_SET_OP(iset_u8_u8, uint8_t, uint8_t)
+#if defined(__HAVE_BFLOAT__)
+INDEX_SET_OP(iset_u8_bf16, uint8_t, bfloat)
+#endif
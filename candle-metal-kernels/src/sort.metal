@@ -6,6 +6,16 @@ using namespace metal;
 #define SORT_ASC 1
 #define SORT_DESC 0
 
+// Returns true if the element at index a has to be placed after the one at index b, ties are
+// broken using the original index so that the resulting sort is stable.
+template<int order, typename T>
+METAL_FUNC bool sort_after(device const T * x_row, uint32_t a, uint32_t b) {
+    if (order == SORT_ASC ? x_row[a] > x_row[b] : x_row[a] < x_row[b]) {
+        return true;
+    }
+    return x_row[a] == x_row[b] && a > b;
+}
+
 template<int order, typename T>
 METAL_FUNC void argsort(
         device const T        * x,
@@ -34,17 +44,13 @@ METAL_FUNC void argsort(
             if (ixj > col) {
                 if ((col & k) == 0) {
                     if (dst_row[col] >= ncols ||
-                        (dst_row[ixj] < ncols && (order == SORT_ASC ?
-                            x_row[dst_row[col]] > x_row[dst_row[ixj]] :
-                            x_row[dst_row[col]] < x_row[dst_row[ixj]]))
+                        (dst_row[ixj] < ncols && sort_after<order>(x_row, dst_row[col], dst_row[ixj]))
                     ) {
                         SWAP(dst_row[col], dst_row[ixj]);
                     }
                 } else {
                     if (dst_row[ixj] >= ncols ||
-                        (dst_row[col] < ncols && (order == SORT_ASC ?
-                            x_row[dst_row[col]] < x_row[dst_row[ixj]] :
-                            x_row[dst_row[col]] > x_row[dst_row[ixj]]))
+                        (dst_row[col] < ncols && sort_after<order>(x_row, dst_row[ixj], dst_row[col]))
                     ) {
                         SWAP(dst_row[col], dst_row[ixj]);
                     }
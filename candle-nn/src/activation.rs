@@ -22,6 +22,12 @@ pub enum Activation {
     LeakyRelu(f64),
     #[serde(alias = "gelu_pytorch_tanh")]
     GeluPytorchTanh,
+    Mish,
+    Softplus,
+    /// Gated linear unit on the last dimension, halving its size.
+    Glu,
+    /// GELU gated linear unit on the last dimension, halving its size.
+    Geglu,
 }
 
 impl super::Module for Activation {
@@ -38,10 +44,14 @@ impl super::Module for Activation {
             Self::HardSigmoid => crate::ops::hard_sigmoid(xs),
             Self::Swiglu => crate::ops::swiglu(xs),
             Self::Swish => xs * crate::ops::sigmoid(xs)?,
-            Self::HardSwish => xs * crate::ops::hard_sigmoid(xs)?,
+            Self::HardSwish => crate::ops::hard_swish(xs),
             &Self::Elu(alpha) => xs.elu(alpha),
             &Self::LeakyRelu(negative_slope) => crate::ops::leaky_relu(xs, negative_slope),
             Self::GeluPytorchTanh => xs.gelu(),
+            Self::Mish => crate::ops::mish(xs),
+            Self::Softplus => crate::ops::softplus(xs),
+            Self::Glu => crate::ops::glu(xs, candle::D::Minus1),
+            Self::Geglu => crate::ops::geglu(xs),
         }
     }
 }
@@ -87,6 +97,19 @@ impl candle::Module for PReLU {
     }
 }
 
+impl crate::Parameters for PReLU {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        f("weight", &self.weight)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        crate::parameters::apply_tensor("weight", &mut self.weight, f)
+    }
+}
+
 /// Create or initialize a new PReLU layer.
 ///
 /// This uses some default name for weights, namely `"weight"`.
@@ -0,0 +1,309 @@
+//! Multi-head attention.
+//!
+//! [`MultiHeadAttention`] projects its inputs to queries, keys and values, applies scaled
+//! dot-product attention on each head and projects the result back. Grouped-query and multi-query
+//! attention are obtained by using fewer key/value heads than query heads.
+//!
+//! ```rust
+//! use candle::{DType, Device, Tensor};
+//! use candle_nn::{attention, kv_cache::KvCache, MultiHeadAttentionConfig, VarBuilder};
+//! let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+//! let cfg = MultiHeadAttentionConfig {
+//!     num_heads: 4,
+//!     num_kv_heads: 2,
+//!     head_dim: None,
+//!     bias: false,
+//! };
+//! let mha = candle_nn::multi_head_attention(16, cfg, vb)?;
+//! // The cache concatenates the keys and values along the sequence dimension, i.e. dimension 2.
+//! let mut cache = KvCache::new(2, 32);
+//! let xs = Tensor::zeros((1, 3, 16), DType::F32, &Device::Cpu)?;
+//! let mask = attention::causal_mask(3, 3, DType::F32, &Device::Cpu)?;
+//! let ys = mha.forward(&xs, Some(&mask), Some(&mut cache))?;
+//! assert_eq!(ys.dims(), &[1, 3, 16]);
+//! // Decode a single step, attending to the cached positions.
+//! let xs = Tensor::zeros((1, 1, 16), DType::F32, &Device::Cpu)?;
+//! let ys = mha.forward(&xs, None, Some(&mut cache))?;
+//! assert_eq!(ys.dims(), &[1, 1, 16]);
+//! # Ok::<(), candle::Error>(())
+//! ```
+use crate::kv_cache::KvCache;
+use crate::Linear;
+use candle::{DType, Device, Result, Tensor, D};
+
+/// Repeats each key/value head `n_rep` times, going from `(batch, num_kv_heads, seq_len, head_dim)`
+/// to `(batch, num_kv_heads * n_rep, seq_len, head_dim)`.
+pub fn repeat_kv(xs: Tensor, n_rep: usize) -> Result<Tensor> {
+    if n_rep == 1 {
+        Ok(xs)
+    } else {
+        xs.repeat_interleave(n_rep, 1)
+    }
+}
+
+/// Returns an additive causal mask of shape `(q_len, kv_len)`, the queries being the last `q_len`
+/// positions of the `kv_len` keys. The masked positions are set to minus infinity.
+pub fn causal_mask(q_len: usize, kv_len: usize, dtype: DType, device: &Device) -> Result<Tensor> {
+    if q_len > kv_len {
+        candle::bail!("causal-mask: more queries than keys {q_len} > {kv_len}")
+    }
+    let offset = kv_len - q_len;
+    let mask: Vec<_> = (0..q_len)
+        .flat_map(|i| {
+            (0..kv_len).map(move |j| {
+                if j > i + offset {
+                    f32::NEG_INFINITY
+                } else {
+                    0.
+                }
+            })
+        })
+        .collect();
+    Tensor::from_slice(&mask, (q_len, kv_len), device)?.to_dtype(dtype)
+}
+
+/// The ALiBi slopes, one per head, as used by MPT with a maximum bias of `max_bias`, BLOOM using
+/// a maximum bias of 8, see <https://arxiv.org/abs/2108.12409>.
+pub fn alibi_slopes(num_heads: usize, max_bias: f64) -> Vec<f32> {
+    let num_heads2 = num_heads.next_power_of_two();
+    let slopes = (1..=num_heads2)
+        .map(|v| 1f32 / 2f32.powf((v as f64 * max_bias / num_heads2 as f64) as f32))
+        .collect::<Vec<_>>();
+    if num_heads2 == num_heads {
+        slopes
+    } else {
+        slopes
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .chain(slopes.iter().step_by(2))
+            .take(num_heads)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns the ALiBi attention bias with shape `(1, num_heads, seq_len, seq_len)`, the bias
+/// between positions `i` and `j` for head `h` being `-slope[h] * |i - j|`. This has to be added to
+/// the attention scores, e.g. by passing it as the mask of [`scaled_dot_product_attention`], and
+/// can be combined with a [`causal_mask`]. When decoding with a kv-cache, the rows for the last
+/// `q_len` positions should be used.
+pub fn alibi_bias(num_heads: usize, seq_len: usize, device: &Device) -> Result<Tensor> {
+    let pos = Tensor::arange(0u32, seq_len as u32, device)?.to_dtype(DType::F32)?;
+    let dist = pos
+        .reshape((1, 1, 1, seq_len))?
+        .broadcast_sub(&pos.reshape((1, 1, seq_len, 1))?)?
+        .abs()?
+        .neg()?;
+    let slopes = Tensor::new(alibi_slopes(num_heads, 8.), device)?.reshape((1, num_heads, 1, 1))?;
+    dist.broadcast_mul(&slopes)
+}
+
+/// Scaled dot-product attention. The queries have shape `(batch, num_heads, q_len, head_dim)` and
+/// the keys and values `(batch, num_kv_heads, kv_len, head_dim)` with `num_heads` a multiple of
+/// `num_kv_heads`. The optional `mask` is an additive bias, e.g. a [`causal_mask`] or an
+/// [`alibi_bias`], that is added to the attention scores and has to be broadcastable to
+/// `(batch, num_heads, q_len, kv_len)`.
+pub fn scaled_dot_product_attention(
+    q: &Tensor,
+    k: &Tensor,
+    v: &Tensor,
+    mask: Option<&Tensor>,
+    scale: f64,
+) -> Result<Tensor> {
+    let num_heads = q.dim(1)?;
+    let num_kv_heads = k.dim(1)?;
+    if num_kv_heads == 0 || num_heads % num_kv_heads != 0 {
+        candle::bail!(
+            "attention: num_heads ({num_heads}) must be a multiple of num_kv_heads ({num_kv_heads})"
+        )
+    }
+    let n_rep = num_heads / num_kv_heads;
+    let k = repeat_kv(k.clone(), n_rep)?.contiguous()?;
+    let v = repeat_kv(v.clone(), n_rep)?.contiguous()?;
+    let attn = (q.contiguous()?.matmul(&k.t()?)? * scale)?;
+    let attn = match mask {
+        None => attn,
+        Some(mask) => attn.broadcast_add(mask)?,
+    };
+    // The fused softmax does not support backpropagation.
+    let attn = if attn.track_op() {
+        crate::ops::softmax(&attn, D::Minus1)?
+    } else {
+        crate::ops::softmax_last_dim(&attn)?
+    };
+    attn.matmul(&v)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiHeadAttentionConfig {
+    pub num_heads: usize,
+    /// The number of key/value heads, `num_heads` for standard multi-head attention and 1 for
+    /// multi-query attention.
+    pub num_kv_heads: usize,
+    /// The dimension of each head, defaults to the hidden size divided by `num_heads`.
+    pub head_dim: Option<usize>,
+    /// Whether the projections have a bias.
+    pub bias: bool,
+}
+
+/// Multi-head attention with optional grouped-query attention.
+#[derive(Clone, Debug)]
+pub struct MultiHeadAttention {
+    q_proj: Linear,
+    k_proj: Linear,
+    v_proj: Linear,
+    o_proj: Linear,
+    num_heads: usize,
+    num_kv_heads: usize,
+    head_dim: usize,
+}
+
+impl MultiHeadAttention {
+    pub fn new(
+        q_proj: Linear,
+        k_proj: Linear,
+        v_proj: Linear,
+        o_proj: Linear,
+        num_heads: usize,
+        num_kv_heads: usize,
+        head_dim: usize,
+    ) -> Result<Self> {
+        if num_kv_heads == 0 || num_heads % num_kv_heads != 0 {
+            candle::bail!(
+                "attention: num_heads ({num_heads}) must be a multiple of num_kv_heads ({num_kv_heads})"
+            )
+        }
+        Ok(Self {
+            q_proj,
+            k_proj,
+            v_proj,
+            o_proj,
+            num_heads,
+            num_kv_heads,
+            head_dim,
+        })
+    }
+
+    pub fn num_heads(&self) -> usize {
+        self.num_heads
+    }
+
+    pub fn num_kv_heads(&self) -> usize {
+        self.num_kv_heads
+    }
+
+    pub fn head_dim(&self) -> usize {
+        self.head_dim
+    }
+
+    // (batch, seq_len, num_heads * head_dim) -> (batch, num_heads, seq_len, head_dim)
+    fn split_heads(&self, xs: &Tensor, num_heads: usize) -> Result<Tensor> {
+        let (b_sz, seq_len, _) = xs.dims3()?;
+        xs.reshape((b_sz, seq_len, num_heads, self.head_dim))?
+            .transpose(1, 2)?
+            .contiguous()
+    }
+
+    /// Self-attention over `xs` of shape `(batch, seq_len, hidden_size)`, see
+    /// [`Self::forward_cross`].
+    pub fn forward(
+        &self,
+        xs: &Tensor,
+        mask: Option<&Tensor>,
+        kv_cache: Option<&mut KvCache>,
+    ) -> Result<Tensor> {
+        self.forward_cross(xs, xs, mask, kv_cache)
+    }
+
+    /// Attention of `query`, with shape `(batch, q_len, hidden_size)`, over `key_value`, with
+    /// shape `(batch, kv_len, hidden_size)`. The `mask` is added to the attention scores and has
+    /// to be broadcastable to `(batch, num_heads, q_len, kv_len)`, e.g. [`causal_mask`] or
+    /// [`alibi_bias`].
+    ///
+    /// When a cache is provided, the keys and values computed from `key_value` are appended to it
+    /// and the attention covers all the cached positions, `kv_len` then being the total length of
+    /// the cache. The cache has to concatenate along dimension 2.
+    pub fn forward_cross(
+        &self,
+        query: &Tensor,
+        key_value: &Tensor,
+        mask: Option<&Tensor>,
+        kv_cache: Option<&mut KvCache>,
+    ) -> Result<Tensor> {
+        let (b_sz, q_len, _) = query.dims3()?;
+        let q = self.split_heads(&query.apply(&self.q_proj)?, self.num_heads)?;
+        let k = self.split_heads(&key_value.apply(&self.k_proj)?, self.num_kv_heads)?;
+        let v = self.split_heads(&key_value.apply(&self.v_proj)?, self.num_kv_heads)?;
+        let (k, v) = match kv_cache {
+            None => (k, v),
+            Some(kv_cache) => {
+                if kv_cache.k_cache().dim() != 2 {
+                    candle::bail!(
+                        "attention: the kv-cache has to use dim 2, got {}",
+                        kv_cache.k_cache().dim()
+                    )
+                }
+                kv_cache.append(&k, &v)?
+            }
+        };
+        let scale = 1. / (self.head_dim as f64).sqrt();
+        let ys = scaled_dot_product_attention(&q, &k, &v, mask, scale)?;
+        ys.transpose(1, 2)?
+            .reshape((b_sz, q_len, self.num_heads * self.head_dim))?
+            .apply(&self.o_proj)
+    }
+}
+
+impl crate::Parameters for MultiHeadAttention {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        use crate::parameters::visit_prefixed;
+        visit_prefixed("q_proj", &self.q_proj, f);
+        visit_prefixed("k_proj", &self.k_proj, f);
+        visit_prefixed("v_proj", &self.v_proj, f);
+        visit_prefixed("o_proj", &self.o_proj, f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        use crate::parameters::apply_prefixed;
+        apply_prefixed("q_proj", &mut self.q_proj, f)?;
+        apply_prefixed("k_proj", &mut self.k_proj, f)?;
+        apply_prefixed("v_proj", &mut self.v_proj, f)?;
+        apply_prefixed("o_proj", &mut self.o_proj, f)
+    }
+}
+
+/// Creates a multi-head attention layer, the projections are loaded from `q_proj`, `k_proj`,
+/// `v_proj` and `o_proj`.
+pub fn multi_head_attention(
+    hidden_size: usize,
+    config: MultiHeadAttentionConfig,
+    vb: crate::VarBuilder,
+) -> Result<MultiHeadAttention> {
+    let MultiHeadAttentionConfig {
+        num_heads,
+        num_kv_heads,
+        head_dim,
+        bias,
+    } = config;
+    if num_heads == 0 {
+        candle::bail!("attention: num_heads cannot be 0")
+    }
+    let head_dim = head_dim.unwrap_or(hidden_size / num_heads);
+    let q_proj = crate::linear_b(hidden_size, num_heads * head_dim, bias, vb.pp("q_proj"))?;
+    let k_proj = crate::linear_b(hidden_size, num_kv_heads * head_dim, bias, vb.pp("k_proj"))?;
+    let v_proj = crate::linear_b(hidden_size, num_kv_heads * head_dim, bias, vb.pp("v_proj"))?;
+    let o_proj = crate::linear_b(num_heads * head_dim, hidden_size, bias, vb.pp("o_proj"))?;
+    MultiHeadAttention::new(
+        q_proj,
+        k_proj,
+        v_proj,
+        o_proj,
+        num_heads,
+        num_kv_heads,
+        head_dim,
+    )
+}
@@ -0,0 +1,128 @@
+//! Functional automatic differentiation helpers.
+//!
+//! [`grad`] and [`value_and_grad`] return the gradients of a loss with respect to some
+//! parameters, e.g. to implement custom update rules without an optimizer. [`jacobian`] and
+//! [`hessian`] are computed using reverse-mode automatic differentiation with one backward pass
+//! per element of the output, so they are mostly suited to functions with small outputs.
+use candle::{Result, Tensor, Var};
+
+/// Returns the gradients of `loss` with respect to each of the `params`, in the same order. The
+/// gradient of the parameters that `loss` does not depend on is zero.
+///
+/// ```rust
+/// use candle::{Device, Var};
+/// let w = Var::new(&[1f32, 2.], &Device::Cpu)?;
+/// let b = Var::new(&[3f32], &Device::Cpu)?;
+/// let loss = w.sqr()?.sum_all()?;
+/// let grads = candle_nn::autodiff::grad(&loss, &[w, b])?;
+/// assert_eq!(grads[0].to_vec1::<f32>()?, [2., 4.]);
+/// assert_eq!(grads[1].to_vec1::<f32>()?, [0.]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn grad(loss: &Tensor, params: &[Var]) -> Result<Vec<Tensor>> {
+    let mut grads = loss.backward()?;
+    params
+        .iter()
+        .map(|p| match (grads.remove(p), grads.remove_sparse(p)) {
+            (Some(grad), _) => Ok(grad),
+            (None, Some(grad)) => grad.to_dense(),
+            (None, None) => p.zeros_like(),
+        })
+        .collect()
+}
+
+/// Evaluates `f` on `params` and returns its value together with the gradients of this value
+/// with respect to each of the `params`, see [`grad`].
+///
+/// ```rust
+/// use candle::{Device, Var};
+/// let w = Var::new(&[1f32, 2.], &Device::Cpu)?;
+/// let (loss, grads) = candle_nn::autodiff::value_and_grad(|ps| ps[0].sqr()?.sum_all(), &[w])?;
+/// assert_eq!(loss.to_scalar::<f32>()?, 5.);
+/// assert_eq!(grads[0].to_vec1::<f32>()?, [2., 4.]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn value_and_grad<F>(f: F, params: &[Var]) -> Result<(Tensor, Vec<Tensor>)>
+where
+    F: FnOnce(&[Var]) -> Result<Tensor>,
+{
+    let value = f(params)?;
+    let grads = grad(&value, params)?;
+    Ok((value.detach(), grads))
+}
+
+// Stacks the gradients of each element of `ys` with respect to `x`, the result has shape
+// `ys.dims() ++ x.dims()`. `ys` has to be computed from `x`.
+fn stack_grads<F>(ys: &Tensor, x: &Var, backward: F) -> Result<Tensor>
+where
+    F: Fn(&Tensor) -> Result<candle::backprop::GradStore>,
+{
+    let flat_ys = ys.flatten_all()?;
+    let mut rows = Vec::with_capacity(flat_ys.elem_count());
+    for i in 0..flat_ys.elem_count() {
+        let grads = backward(&flat_ys.get(i)?)?;
+        let row = match grads.get(x) {
+            Some(grad) => grad.detach(),
+            // The element does not depend on x.
+            None => x.zeros_like()?,
+        };
+        rows.push(row)
+    }
+    let mut dims = ys.dims().to_vec();
+    dims.extend_from_slice(x.dims());
+    if rows.is_empty() {
+        return Tensor::zeros(dims, x.dtype(), x.device());
+    }
+    Tensor::stack(&rows, 0)?.reshape(dims)
+}
+
+/// Returns the Jacobian of `f` at `x`, the result has shape `f(x).dims() ++ x.dims()` with the
+/// element at index `(i, j)` being the derivative of the `i`-th output with respect to the
+/// `j`-th input.
+///
+/// ```rust
+/// use candle::{Device, Tensor};
+/// let x = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+/// let jac = candle_nn::autodiff::jacobian(|x| x.sqr()?.affine(3., 0.), &x)?;
+/// assert_eq!(jac.to_vec2::<f32>()?, [[6., 0.], [0., 12.]]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn jacobian<F>(f: F, x: &Tensor) -> Result<Tensor>
+where
+    F: Fn(&Tensor) -> Result<Tensor>,
+{
+    let x = Var::from_tensor(&x.detach())?;
+    let ys = f(x.as_tensor())?;
+    stack_grads(&ys, &x, |y| y.backward())
+}
+
+/// Returns the Hessian of `f` at `x`, `f` has to return a scalar. The result has shape
+/// `x.dims() ++ x.dims()`.
+///
+/// ```rust
+/// use candle::{Device, Tensor};
+/// let x = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+/// // f(x, y) = x^2 y
+/// let hess = candle_nn::autodiff::hessian(|x| x.sqr()?.get(0)?.mul(&x.get(1)?), &x)?;
+/// assert_eq!(hess.to_vec2::<f32>()?, [[4., 2.], [2., 0.]]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn hessian<F>(f: F, x: &Tensor) -> Result<Tensor>
+where
+    F: Fn(&Tensor) -> Result<Tensor>,
+{
+    let x = Var::from_tensor(&x.detach())?;
+    let y = f(x.as_tensor())?;
+    if y.elem_count() != 1 {
+        candle::bail!(
+            "hessian expects a function returning a scalar, got {:?}",
+            y.shape()
+        )
+    }
+    let grads = y.sum_all()?.backward_create_graph()?;
+    let grad = match grads.get(&x) {
+        Some(grad) => grad.clone(),
+        None => x.zeros_like()?,
+    };
+    stack_grads(&grad, &x, |g| g.backward())
+}
@@ -3,8 +3,11 @@
 //! This layer applies Batch Normalization over a mini-batch of inputs as described in [`Batch
 //! Normalization`]. The input is expected to have at least three dimensions.
 //!
-//! Note that this implementation is for inference only, there is no possibility to track the
-//! running stats.
+//! In training mode, see [`crate::ModuleT`], the batch statistics are used to normalize the input
+//! and the running mean and variance are updated. In evaluation mode the running statistics are
+//! used instead. When created with [`batch_norm`], the running statistics are loaded from the
+//! `running_mean` and `running_var` tensors of the var builder, as in the PyTorch checkpoints, and
+//! updating them also updates the underlying var map.
 //!
 //! [`Batch Normalization`]: https://arxiv.org/abs/1502.03167
 use candle::{DType, Result, Tensor, Var};
@@ -298,6 +301,30 @@ impl crate::ModuleT for BatchNorm {
     }
 }
 
+impl crate::Parameters for BatchNorm {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        if let Some((weight, bias)) = &self.weight_and_bias {
+            f("weight", weight);
+            f("bias", bias);
+        }
+        f("running_mean", self.running_mean.as_tensor());
+        f("running_var", self.running_var.as_tensor())
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        if let Some((weight, bias)) = &mut self.weight_and_bias {
+            crate::parameters::apply_tensor("weight", weight, f)?;
+            crate::parameters::apply_tensor("bias", bias, f)?;
+        }
+        self.running_mean = Var::from_tensor(&f("running_mean", self.running_mean.as_tensor())?)?;
+        self.running_var = Var::from_tensor(&f("running_var", self.running_var.as_tensor())?)?;
+        Ok(())
+    }
+}
+
 pub fn batch_norm<C: Into<BatchNormConfig>>(
     num_features: usize,
     config: C,
@@ -1,5 +1,5 @@
 //! Convolution Layers.
-use crate::BatchNorm;
+use crate::{BatchNorm, Module, Parameters};
 use candle::{Result, Tensor};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -70,6 +70,109 @@ impl crate::Module for Conv1d {
     }
 }
 
+/// A causal 1D convolution, the input being padded on the left only so that each output only
+/// depends on the current and past inputs, as used in state-space and conv-augmented decoders.
+///
+/// [`crate::Module::forward`] processes a whole sequence at once whereas [`CausalConv1d::step`]
+/// processes chunks of a sequence, carrying the trailing inputs over to the next call. The
+/// concatenated outputs of the successive steps match the output on the full sequence.
+#[derive(Clone, Debug)]
+pub struct CausalConv1d {
+    conv: Conv1d,
+    left_padding: usize,
+    state: Option<Tensor>,
+}
+
+impl CausalConv1d {
+    /// Wraps a convolution with no padding, the causal padding being added on the left.
+    pub fn new(conv: Conv1d) -> Result<Self> {
+        let cfg = conv.config();
+        let (_, _, k_size) = conv.weight().dims3()?;
+        if cfg.padding != 0 {
+            candle::bail!("causal-conv1d: the wrapped conv should not be padded")
+        }
+        // The stride cannot exceed the receptive field as the streaming state would then have to
+        // skip some of the upcoming inputs.
+        if k_size == 0 || cfg.stride == 0 || cfg.stride > (k_size - 1) * cfg.dilation + 1 {
+            candle::bail!(
+                "causal-conv1d: invalid stride {} for kernel size {k_size} and dilation {}",
+                cfg.stride,
+                cfg.dilation
+            )
+        }
+        let left_padding = (k_size - 1) * cfg.dilation;
+        Ok(Self {
+            conv,
+            left_padding,
+            state: None,
+        })
+    }
+
+    pub fn conv(&self) -> &Conv1d {
+        &self.conv
+    }
+
+    /// The number of zeros added on the left of the sequence.
+    pub fn left_padding(&self) -> usize {
+        self.left_padding
+    }
+
+    /// The inputs carried over to the next step, of shape `(b, c_in, len)`.
+    pub fn state(&self) -> Option<&Tensor> {
+        self.state.as_ref()
+    }
+
+    /// Resets the streaming state so that the next step starts a new sequence.
+    pub fn reset_state(&mut self) {
+        self.state = None
+    }
+
+    /// Processes a chunk of shape `(b, c_in, len)` of a sequence, returning the outputs that can
+    /// be computed from the inputs seen so far, possibly none when strided.
+    pub fn step(&mut self, xs: &Tensor) -> Result<Tensor> {
+        let xs = match &self.state {
+            None => xs.pad_with_zeros(2, self.left_padding, 0)?,
+            Some(state) => Tensor::cat(&[state, xs], 2)?,
+        };
+        let (b_size, _, len) = xs.dims3()?;
+        let stride = self.conv.config().stride;
+        let span = self.left_padding + 1;
+        if len < span {
+            let c_out = self.conv.weight().dim(0)?;
+            let ys = Tensor::zeros((b_size, c_out, 0), xs.dtype(), xs.device())?;
+            self.state = Some(xs);
+            return Ok(ys);
+        }
+        let n_out = (len - span) / stride + 1;
+        let ys = self
+            .conv
+            .forward(&xs.narrow(2, 0, (n_out - 1) * stride + span)?)?;
+        let consumed = n_out * stride;
+        self.state = Some(xs.narrow(2, consumed, len - consumed)?);
+        Ok(ys)
+    }
+}
+
+impl crate::Module for CausalConv1d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let xs = xs.pad_with_zeros(2, self.left_padding, 0)?;
+        self.conv.forward(&xs)
+    }
+}
+
+impl Parameters for CausalConv1d {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        self.conv.visit_parameters(f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        self.conv.apply_parameters(f)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ConvTranspose1dConfig {
     pub padding: usize,
@@ -298,6 +401,99 @@ impl crate::Module for ConvTranspose2d {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conv3dConfig {
+    pub padding: usize,
+    pub stride: usize,
+    pub dilation: usize,
+    pub groups: usize,
+}
+
+impl Default for Conv3dConfig {
+    fn default() -> Self {
+        Self {
+            padding: 0,
+            stride: 1,
+            dilation: 1,
+            groups: 1,
+        }
+    }
+}
+
+/// A 3D convolution over inputs of shape `(b, c, d, h, w)`, e.g. video clips or volumetric
+/// images, see [`Tensor::conv3d`].
+#[derive(Clone, Debug)]
+pub struct Conv3d {
+    weight: Tensor,
+    bias: Option<Tensor>,
+    config: Conv3dConfig,
+}
+
+impl Conv3d {
+    pub fn new(weight: Tensor, bias: Option<Tensor>, config: Conv3dConfig) -> Self {
+        Self {
+            weight,
+            bias,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Conv3dConfig {
+        &self.config
+    }
+
+    pub fn weight(&self) -> &Tensor {
+        &self.weight
+    }
+
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.bias.as_ref()
+    }
+}
+
+impl crate::Module for Conv3d {
+    fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        let x = x.conv3d(
+            &self.weight,
+            self.config.padding,
+            self.config.stride,
+            self.config.dilation,
+            self.config.groups,
+        )?;
+        match &self.bias {
+            None => Ok(x),
+            Some(bias) => {
+                let b = bias.dims1()?;
+                let bias = bias.reshape((1, b, 1, 1, 1))?;
+                Ok(x.broadcast_add(&bias)?)
+            }
+        }
+    }
+}
+
+macro_rules! impl_conv_parameters {
+    ($($t:ty),*) => {
+        $(
+            impl crate::Parameters for $t {
+                fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+                    f("weight", &self.weight);
+                    crate::parameters::visit_opt("bias", &self.bias, f)
+                }
+
+                fn apply_parameters(
+                    &mut self,
+                    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+                ) -> Result<()> {
+                    crate::parameters::apply_tensor("weight", &mut self.weight, f)?;
+                    crate::parameters::apply_opt("bias", &mut self.bias, f)
+                }
+            }
+        )*
+    };
+}
+
+impl_conv_parameters!(Conv1d, ConvTranspose1d, Conv2d, ConvTranspose2d, Conv3d);
+
 pub fn conv1d(
     in_channels: usize,
     out_channels: usize,
@@ -336,6 +532,17 @@ pub fn conv1d_no_bias(
     Ok(Conv1d::new(ws, None, cfg))
 }
 
+/// Creates a [`CausalConv1d`] with the same weights as [`conv1d`], `cfg.padding` has to be 0.
+pub fn causal_conv1d(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: Conv1dConfig,
+    vb: crate::VarBuilder,
+) -> Result<CausalConv1d> {
+    CausalConv1d::new(conv1d(in_channels, out_channels, kernel_size, cfg, vb)?)
+}
+
 pub fn conv_transpose1d(
     in_channels: usize,
     out_channels: usize,
@@ -465,3 +672,53 @@ pub fn conv_transpose2d_no_bias(
     )?;
     Ok(ConvTranspose2d::new(ws, None, cfg))
 }
+
+pub fn conv3d(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: Conv3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<Conv3d> {
+    let init_ws = crate::init::DEFAULT_KAIMING_NORMAL;
+    let ws = vb.get_with_hints(
+        (
+            out_channels,
+            in_channels / cfg.groups,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init_ws,
+    )?;
+    let bound = 1. / (in_channels as f64).sqrt();
+    let init_bs = crate::Init::Uniform {
+        lo: -bound,
+        up: bound,
+    };
+    let bs = vb.get_with_hints(out_channels, "bias", init_bs)?;
+    Ok(Conv3d::new(ws, Some(bs), cfg))
+}
+
+pub fn conv3d_no_bias(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: Conv3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<Conv3d> {
+    let init_ws = crate::init::DEFAULT_KAIMING_NORMAL;
+    let ws = vb.get_with_hints(
+        (
+            out_channels,
+            in_channels / cfg.groups,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init_ws,
+    )?;
+    Ok(Conv3d::new(ws, None, cfg))
+}
@@ -1,10 +1,12 @@
 //! Embedding Layer.
-use candle::{Result, Tensor};
+use crate::Module;
+use candle::{bail, DType, Device, Result, Tensor, D};
 
 #[derive(Clone, Debug)]
 pub struct Embedding {
     embeddings: Tensor,
     hidden_size: usize,
+    sparse_grad: bool,
 }
 
 impl Embedding {
@@ -12,9 +14,18 @@ impl Embedding {
         Self {
             embeddings,
             hidden_size,
+            sparse_grad: false,
         }
     }
 
+    /// When enabled, the gradient of the embeddings only contains the rows that have been looked
+    /// up and is returned by `GradStore::get_sparse`, see [`Tensor::sparse_index_select`]. This
+    /// avoids materializing a `vocab x hidden` gradient on each step for large vocabularies.
+    pub fn with_sparse_grad(mut self, sparse_grad: bool) -> Self {
+        self.sparse_grad = sparse_grad;
+        self
+    }
+
     pub fn embeddings(&self) -> &Tensor {
         &self.embeddings
     }
@@ -23,6 +34,13 @@ impl Embedding {
     pub fn hidden_size(&self) -> usize {
         self.hidden_size
     }
+
+    /// Returns a linear layer without bias sharing the embedding weights, e.g. to tie the output
+    /// projection of a language model to its token embedding. When the weights are a variable,
+    /// the gradients of both uses get accumulated on it.
+    pub fn tied_linear(&self) -> crate::Linear {
+        crate::Linear::new(self.embeddings.clone(), None)
+    }
 }
 
 impl crate::Module for Embedding {
@@ -30,12 +48,29 @@ impl crate::Module for Embedding {
         let mut final_dims = indexes.dims().to_vec();
         final_dims.push(self.hidden_size);
         let indexes = indexes.flatten_all()?;
-        let values = self.embeddings.index_select(&indexes, 0)?;
+        let values = if self.sparse_grad {
+            self.embeddings.sparse_index_select(&indexes)?
+        } else {
+            self.embeddings.index_select(&indexes, 0)?
+        };
         let values = values.reshape(final_dims)?;
         Ok(values)
     }
 }
 
+impl crate::Parameters for Embedding {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        f("weight", &self.embeddings)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        crate::parameters::apply_tensor("weight", &mut self.embeddings, f)
+    }
+}
+
 pub fn embedding(in_size: usize, out_size: usize, vb: crate::VarBuilder) -> Result<Embedding> {
     let embeddings = vb.get_with_hints(
         (in_size, out_size),
@@ -47,3 +82,310 @@ pub fn embedding(in_size: usize, out_size: usize, vb: crate::VarBuilder) -> Resu
     )?;
     Ok(Embedding::new(embeddings, out_size))
 }
+
+/// The reduction applied over each bag by [`EmbeddingBag`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbeddingBagMode {
+    Sum,
+    #[default]
+    Mean,
+    Max,
+}
+
+/// Looks up bags of indices and reduces the embeddings of each bag, similar to PyTorch
+/// `EmbeddingBag`. Empty bags return zeros.
+#[derive(Clone, Debug)]
+pub struct EmbeddingBag {
+    embeddings: Tensor,
+    hidden_size: usize,
+    mode: EmbeddingBagMode,
+}
+
+impl EmbeddingBag {
+    pub fn new(embeddings: Tensor, hidden_size: usize, mode: EmbeddingBagMode) -> Self {
+        Self {
+            embeddings,
+            hidden_size,
+            mode,
+        }
+    }
+
+    pub fn embeddings(&self) -> &Tensor {
+        &self.embeddings
+    }
+
+    pub fn mode(&self) -> EmbeddingBagMode {
+        self.mode
+    }
+
+    /// Returns a tensor of shape `(num_bags, hidden_size)`.
+    ///
+    /// When `offsets` is `None`, `indices` has shape `(num_bags, bag_size)` and each row is a bag.
+    /// Otherwise `indices` is one dimensional and `offsets` holds the start position of each bag
+    /// in `indices`, the last bag extending to the end of `indices`.
+    pub fn forward(&self, indices: &Tensor, offsets: Option<&Tensor>) -> Result<Tensor> {
+        let (indices, offsets) = match offsets {
+            None => {
+                let (num_bags, bag_size) = indices.dims2()?;
+                let offsets: Vec<usize> = (0..num_bags).map(|i| i * bag_size).collect();
+                (indices.flatten_all()?, offsets)
+            }
+            Some(offsets) => {
+                let offsets = offsets.to_dtype(DType::U32)?.to_vec1::<u32>()?;
+                let offsets: Vec<usize> = offsets.into_iter().map(|o| o as usize).collect();
+                (indices.clone(), offsets)
+            }
+        };
+        let num_indices = indices.dims1()?;
+        let num_bags = offsets.len();
+        let mut bag_lens = Vec::with_capacity(num_bags);
+        for (i, &start) in offsets.iter().enumerate() {
+            let end = offsets.get(i + 1).copied().unwrap_or(num_indices);
+            if start > end || end > num_indices {
+                bail!("embedding-bag: invalid offsets {offsets:?} for {num_indices} indices")
+            }
+            bag_lens.push(end - start)
+        }
+        let dev = self.embeddings.device();
+        let values = self.embeddings.index_select(&indices, 0)?;
+        match self.mode {
+            EmbeddingBagMode::Sum | EmbeddingBagMode::Mean => {
+                let bag_ids: Vec<u32> = bag_lens
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, &l)| std::iter::repeat_n(i as u32, l))
+                    .collect();
+                let bag_ids = Tensor::new(bag_ids, dev)?;
+                let zeros = Tensor::zeros((num_bags, self.hidden_size), values.dtype(), dev)?;
+                let sums = zeros.index_add(&bag_ids, &values, 0)?;
+                if self.mode == EmbeddingBagMode::Sum {
+                    Ok(sums)
+                } else {
+                    let counts: Vec<f32> = bag_lens.iter().map(|&l| l.max(1) as f32).collect();
+                    let counts = Tensor::from_vec(counts, (num_bags, 1), dev)?;
+                    sums.broadcast_div(&counts.to_dtype(sums.dtype())?)
+                }
+            }
+            EmbeddingBagMode::Max => {
+                // The bags are padded to the same length using an extra row filled with minus
+                // infinity, empty bags are set to zero afterwards.
+                let max_len = bag_lens.iter().copied().max().unwrap_or(0).max(1);
+                let mut padded = vec![num_indices as u32; num_bags * max_len];
+                for (i, (&start, &l)) in offsets.iter().zip(bag_lens.iter()).enumerate() {
+                    for j in 0..l {
+                        padded[i * max_len + j] = (start + j) as u32
+                    }
+                }
+                let padded = Tensor::new(padded, dev)?;
+                let neg_inf = Tensor::full(f32::NEG_INFINITY, (1, self.hidden_size), dev)?
+                    .to_dtype(values.dtype())?;
+                let values = Tensor::cat(&[&values, &neg_inf], 0)?;
+                let maxs = values
+                    .index_select(&padded, 0)?
+                    .reshape((num_bags, max_len, self.hidden_size))?
+                    .max(1)?;
+                let non_empty: Vec<u8> = bag_lens.iter().map(|&l| u8::from(l > 0)).collect();
+                let non_empty =
+                    Tensor::from_vec(non_empty, (num_bags, 1), dev)?.broadcast_as(maxs.shape())?;
+                non_empty.where_cond(&maxs, &maxs.zeros_like()?)
+            }
+        }
+    }
+}
+
+impl crate::Parameters for EmbeddingBag {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        f("weight", &self.embeddings)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        crate::parameters::apply_tensor("weight", &mut self.embeddings, f)
+    }
+}
+
+pub fn embedding_bag(
+    in_size: usize,
+    out_size: usize,
+    mode: EmbeddingBagMode,
+    vb: crate::VarBuilder,
+) -> Result<EmbeddingBag> {
+    let embeddings = vb.get_with_hints(
+        (in_size, out_size),
+        "weight",
+        crate::Init::Randn {
+            mean: 0.,
+            stdev: 1.,
+        },
+    )?;
+    Ok(EmbeddingBag::new(embeddings, out_size, mode))
+}
+
+/// Returns the position ids for `input_ids` of shape `(batch, seq_len)`, padding tokens getting
+/// the position `padding_idx` and the other tokens being numbered from `padding_idx + 1`, skipping
+/// the padding tokens, as done in fairseq and RoBERTa. The numbering starts after `past_len`
+/// previously processed non-padding tokens.
+pub fn make_positions(input_ids: &Tensor, padding_idx: u32, past_len: usize) -> Result<Tensor> {
+    let mask = input_ids.ne(padding_idx)?.to_dtype(DType::F32)?;
+    let positions = ((mask.cumsum(D::Minus1)? + past_len as f64)? * &mask)?;
+    (positions + padding_idx as f64)?.to_dtype(DType::U32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SinusoidalPositionalEmbeddingConfig {
+    pub max_timescale: f64,
+    /// When set, the frequencies use `dim / 2 - 1` rather than `dim / 2` as denominator, as in
+    /// fairseq, BART and Whisper.
+    pub shifted_frequencies: bool,
+    /// When set, the position `padding_idx` maps to zeros and the positions start at
+    /// `padding_idx + 1`, see [`make_positions`].
+    pub padding_idx: Option<u32>,
+}
+
+impl Default for SinusoidalPositionalEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            max_timescale: 10000.,
+            shifted_frequencies: false,
+            padding_idx: None,
+        }
+    }
+}
+
+/// Fixed sinusoidal positional embeddings, the first half of the embedding holds the sines and the
+/// second half the cosines.
+#[derive(Clone, Debug)]
+pub struct SinusoidalPositionalEmbedding {
+    embeddings: Tensor,
+    offset: usize,
+}
+
+impl SinusoidalPositionalEmbedding {
+    pub fn new(
+        num_positions: usize,
+        dim: usize,
+        cfg: SinusoidalPositionalEmbeddingConfig,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Self> {
+        if dim % 2 != 0 || (cfg.shifted_frequencies && dim < 4) {
+            candle::bail!("sinusoidal-embedding: unsupported dim {dim}")
+        }
+        let half_dim = dim / 2;
+        let denom = if cfg.shifted_frequencies {
+            half_dim - 1
+        } else {
+            half_dim
+        };
+        let log_increment = cfg.max_timescale.ln() / denom as f64;
+        let inv_freq: Vec<_> = (0..half_dim)
+            .map(|i| (-(i as f64) * log_increment).exp() as f32)
+            .collect();
+        let inv_freq = Tensor::from_vec(inv_freq, (1, half_dim), device)?;
+        let offset = cfg.padding_idx.map_or(0, |p| p as usize + 1);
+        let total = num_positions + offset;
+        let t = Tensor::arange(0u32, total as u32, device)?
+            .to_dtype(DType::F32)?
+            .reshape((total, 1))?;
+        let freqs = t.matmul(&inv_freq)?;
+        let embeddings = Tensor::cat(&[freqs.sin()?, freqs.cos()?], 1)?;
+        let embeddings = match cfg.padding_idx {
+            None => embeddings,
+            Some(padding_idx) => {
+                let mask = Tensor::arange(0u32, total as u32, device)?
+                    .ne(padding_idx)?
+                    .to_dtype(DType::F32)?
+                    .reshape((total, 1))?;
+                embeddings.broadcast_mul(&mask)?
+            }
+        };
+        Ok(Self {
+            embeddings: embeddings.to_dtype(dtype)?,
+            offset,
+        })
+    }
+
+    /// The embedding table, including the positions before the offset when a padding index is
+    /// used.
+    pub fn embeddings(&self) -> &Tensor {
+        &self.embeddings
+    }
+
+    /// Returns the embeddings for positions `past_len..past_len + seq_len`, with shape
+    /// `(seq_len, dim)`.
+    pub fn forward(&self, seq_len: usize, past_len: usize) -> Result<Tensor> {
+        self.embeddings.narrow(0, past_len + self.offset, seq_len)
+    }
+
+    /// Returns the embeddings for some explicit positions ids, e.g. as returned by
+    /// [`make_positions`], no offset being applied.
+    pub fn forward_positions(&self, position_ids: &Tensor) -> Result<Tensor> {
+        Embedding::new(self.embeddings.clone(), self.embeddings.dim(1)?).forward(position_ids)
+    }
+}
+
+/// Learned positional embeddings, the position `p` being looked up at index `p + offset` of the
+/// table, e.g. BART uses an offset of 2.
+#[derive(Clone, Debug)]
+pub struct PositionEmbedding {
+    embedding: Embedding,
+    offset: usize,
+}
+
+impl PositionEmbedding {
+    pub fn new(embedding: Embedding, offset: usize) -> Self {
+        Self { embedding, offset }
+    }
+
+    pub fn embedding(&self) -> &Embedding {
+        &self.embedding
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the embeddings for positions `past_len..past_len + seq_len`, with shape
+    /// `(seq_len, dim)`.
+    pub fn forward(&self, seq_len: usize, past_len: usize) -> Result<Tensor> {
+        let start = (past_len + self.offset) as u32;
+        let positions = Tensor::arange(
+            start,
+            start + seq_len as u32,
+            self.embedding.embeddings().device(),
+        )?;
+        self.embedding.forward(&positions)
+    }
+
+    /// Returns the embeddings for some explicit positions ids, e.g. as returned by
+    /// [`make_positions`], no offset being applied.
+    pub fn forward_positions(&self, position_ids: &Tensor) -> Result<Tensor> {
+        self.embedding.forward(position_ids)
+    }
+}
+
+impl crate::Parameters for PositionEmbedding {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        self.embedding.visit_parameters(f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        self.embedding.apply_parameters(f)
+    }
+}
+
+/// Creates a learned positional embedding with a table of `num_positions + offset` rows.
+pub fn position_embedding(
+    num_positions: usize,
+    dim: usize,
+    offset: usize,
+    vb: crate::VarBuilder,
+) -> Result<PositionEmbedding> {
+    let embedding = embedding(num_positions + offset, dim, vb)?;
+    Ok(PositionEmbedding::new(embedding, offset))
+}
@@ -50,6 +50,15 @@ impl crate::Module for GroupNorm {
                 self.num_channels
             )
         }
+        if x.is_contiguous() && (x.device().is_cpu() || x.device().is_cuda()) {
+            return crate::ops::group_norm(
+                x,
+                self.num_groups,
+                &self.weight.contiguous()?,
+                &self.bias.contiguous()?,
+                self.eps as f32,
+            );
+        }
         let x_dtype = x.dtype();
         let internal_dtype = match x_dtype {
             DType::F16 | DType::BF16 => DType::F32,
@@ -73,6 +82,21 @@ impl crate::Module for GroupNorm {
     }
 }
 
+impl crate::Parameters for GroupNorm {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        f("weight", &self.weight);
+        f("bias", &self.bias)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        crate::parameters::apply_tensor("weight", &mut self.weight, f)?;
+        crate::parameters::apply_tensor("bias", &mut self.bias, f)
+    }
+}
+
 pub fn group_norm(
     num_groups: usize,
     num_channels: usize,
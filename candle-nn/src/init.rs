@@ -91,6 +91,26 @@ pub enum Init {
         fan: FanInOut,
         non_linearity: NonLinearity,
     },
+
+    /// Xavier (or Glorot) initialization.
+    /// See "Understanding the difficulty of training deep feedforward neural networks"
+    /// Glorot, X. & Bengio, Y. (2010). The standard deviation is `gain * sqrt(2 / (fan_in + fan_out))`.
+    Xavier { dist: NormalOrUniform, gain: f64 },
+
+    /// Random normal with some mean and standard deviation, the values outside of `[lo, up]` being
+    /// redrawn.
+    TruncatedNormal {
+        mean: f64,
+        stdev: f64,
+        lo: f64,
+        up: f64,
+    },
+
+    /// (Semi) orthogonal initialization, the tensor is flattened to a matrix of shape
+    /// `(dims[0], elem_count / dims[0])` with orthonormal rows or columns, scaled by `gain`.
+    /// See "Exact solutions to the nonlinear dynamics of learning in deep linear neural networks"
+    /// Saxe, A. et al. (2013).
+    Orthogonal { gain: f64 },
 }
 
 pub const ZERO: Init = Init::Const(0.);
@@ -108,6 +128,16 @@ pub const DEFAULT_KAIMING_NORMAL: Init = Init::Kaiming {
     non_linearity: NonLinearity::ReLU,
 };
 
+pub const DEFAULT_XAVIER_UNIFORM: Init = Init::Xavier {
+    dist: NormalOrUniform::Uniform,
+    gain: 1.,
+};
+
+pub const DEFAULT_XAVIER_NORMAL: Init = Init::Xavier {
+    dist: NormalOrUniform::Normal,
+    gain: 1.,
+};
+
 impl Init {
     /// Creates a new tensor with the specified shape, device, and initialization.
     pub fn var<S: Into<Shape>>(&self, s: S, dtype: DType, device: &Device) -> Result<Var> {
@@ -136,10 +166,82 @@ impl Init {
                     NormalOrUniform::Normal => Var::randn_f64(0., std, s, dtype, device),
                 }
             }
+            Self::Xavier { dist, gain } => {
+                let s = s.into();
+                let fan_in = FanInOut::FanIn.for_shape(&s);
+                let fan_out = FanInOut::FanOut.for_shape(&s);
+                let std = gain * (2. / (fan_in + fan_out) as f64).sqrt();
+                match dist {
+                    NormalOrUniform::Uniform => {
+                        let bound = 3f64.sqrt() * std;
+                        Var::rand_f64(-bound, bound, s, dtype, device)
+                    }
+                    NormalOrUniform::Normal => Var::randn_f64(0., std, s, dtype, device),
+                }
+            }
+            Self::TruncatedNormal {
+                mean,
+                stdev,
+                lo,
+                up,
+            } => {
+                if lo >= up {
+                    candle::bail!("truncated normal requires lo < up, got {lo} and {up}")
+                }
+                let s = s.into();
+                let randn = || Var::randn_f64(*mean, *stdev, &s, dtype, device);
+                let mut xs = randn()?.into_inner();
+                // Redraw the out of bounds values, the remaining ones after a few rounds (only
+                // possible for very narrow bounds) get clamped.
+                for _ in 0..TRUNCATED_NORMAL_MAX_ROUNDS {
+                    let in_bounds = (xs.ge(*lo)? * xs.le(*up)?)?;
+                    let num_in_bounds = in_bounds.to_dtype(DType::U32)?.sum_all()?;
+                    if num_in_bounds.to_scalar::<u32>()? as usize == s.elem_count() {
+                        break;
+                    }
+                    xs = in_bounds.where_cond(&xs, &randn()?.into_inner())?;
+                }
+                Var::from_tensor(&xs.clamp(*lo, *up)?)
+            }
+            Self::Orthogonal { gain } => {
+                let s = s.into();
+                let dims = s.dims();
+                if dims.len() < 2 {
+                    candle::bail!("orthogonal init requires at least 2 dims, got {s:?}")
+                }
+                let rows = dims[0];
+                let cols = s.elem_count() / rows;
+                // Orthonormalize the columns of a tall random matrix with Gram-Schmidt, this is
+                // equivalent to the QR decomposition with the signs of the diagonal of R fixed.
+                let (n, m) = (rows.max(cols), rows.min(cols));
+                let ws = Tensor::randn(0f32, 1., (m, n), &Device::Cpu)?.to_vec2::<f32>()?;
+                let mut ws: Vec<Vec<f64>> = ws
+                    .into_iter()
+                    .map(|w| w.into_iter().map(|v| v as f64).collect())
+                    .collect();
+                for i in 0..m {
+                    for j in 0..i {
+                        let dot: f64 = ws[i].iter().zip(ws[j].iter()).map(|(a, b)| a * b).sum();
+                        let (prev, cur) = ws.split_at_mut(i);
+                        cur[0]
+                            .iter_mut()
+                            .zip(prev[j].iter())
+                            .for_each(|(a, b)| *a -= dot * b)
+                    }
+                    let norm = ws[i].iter().map(|v| v * v).sum::<f64>().sqrt();
+                    ws[i].iter_mut().for_each(|v| *v /= norm)
+                }
+                let ws = Tensor::new(ws, &Device::Cpu)?;
+                let ws = if rows < cols { ws } else { ws.t()? };
+                let ws = (ws * *gain)?.to_dtype(dtype)?.to_device(device)?;
+                Var::from_tensor(&ws.reshape(s)?)
+            }
         }
     }
 }
 
+const TRUNCATED_NORMAL_MAX_ROUNDS: usize = 100;
+
 impl Default for Init {
     fn default() -> Self {
         Self::Const(0.)
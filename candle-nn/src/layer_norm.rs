@@ -28,7 +28,7 @@
 //! ```
 //!
 //! [`Layer Normalization`]: https://arxiv.org/abs/1607.06450
-use candle::{DType, Module, Result, Tensor, D};
+use candle::{DType, Device, Module, Result, Tensor, D};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LayerNormConfig {
@@ -36,7 +36,11 @@ pub struct LayerNormConfig {
     /// Whether to remove the mean or not, the default is true and when set to false, this turns
     /// this layer into RmsNorm.
     pub remove_mean: bool,
+    /// Whether the layer has a bias, T5 and Llama style layers only having a weight. When loading
+    /// from a checkpoint, a missing bias is tolerated and results in a layer without bias.
     pub affine: bool,
+    /// When false, the layer has no learnable parameters at all, neither a weight nor a bias.
+    pub elementwise_affine: bool,
 }
 
 impl Default for LayerNormConfig {
@@ -45,6 +49,7 @@ impl Default for LayerNormConfig {
             eps: 1e-5,
             remove_mean: true,
             affine: true,
+            elementwise_affine: true,
         }
     }
 }
@@ -53,8 +58,7 @@ impl From<f64> for LayerNormConfig {
     fn from(eps: f64) -> Self {
         Self {
             eps,
-            remove_mean: true,
-            affine: true,
+            ..Default::default()
         }
     }
 }
@@ -66,6 +70,8 @@ pub struct LayerNorm {
     bias: Option<Tensor>,
     remove_mean: bool,
     eps: f64,
+    // False when the weight is not a parameter but a constant tensor of ones.
+    elementwise_affine: bool,
 }
 
 impl LayerNorm {
@@ -75,6 +81,7 @@ impl LayerNorm {
             bias: Some(bias),
             remove_mean: true,
             eps,
+            elementwise_affine: true,
         }
     }
 
@@ -84,15 +91,29 @@ impl LayerNorm {
             bias: None,
             remove_mean: true,
             eps,
+            elementwise_affine: true,
         }
     }
 
+    /// Creates a layer without learnable parameters, normalizing over a last dimension of size
+    /// `size`.
+    pub fn new_no_affine(size: usize, eps: f64, dtype: DType, device: &Device) -> Result<Self> {
+        Ok(Self {
+            weight: Tensor::ones(size, dtype, device)?,
+            bias: None,
+            remove_mean: true,
+            eps,
+            elementwise_affine: false,
+        })
+    }
+
     pub fn rms_norm(weight: Tensor, eps: f64) -> Self {
         Self {
             weight,
             bias: None,
             remove_mean: false,
             eps,
+            elementwise_affine: true,
         }
     }
 
@@ -127,7 +148,12 @@ impl Module for LayerNorm {
         };
         let norm_x = (x.sqr()?.sum_keepdim(D::Minus1)? / hidden_size as f64)?;
         let x_normed = x.broadcast_div(&(norm_x + self.eps)?.sqrt()?)?;
-        let x = x_normed.to_dtype(x_dtype)?.broadcast_mul(&self.weight)?;
+        let x = x_normed.to_dtype(x_dtype)?;
+        let x = if self.elementwise_affine {
+            x.broadcast_mul(&self.weight)?
+        } else {
+            x
+        };
         match &self.bias {
             None => Ok(x),
             Some(bias) => x.broadcast_add(bias),
@@ -135,15 +161,52 @@ impl Module for LayerNorm {
     }
 }
 
+impl crate::Parameters for LayerNorm {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        if self.elementwise_affine {
+            f("weight", &self.weight)
+        }
+        crate::parameters::visit_opt("bias", &self.bias, f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        if self.elementwise_affine {
+            crate::parameters::apply_tensor("weight", &mut self.weight, f)?
+        }
+        crate::parameters::apply_opt("bias", &mut self.bias, f)
+    }
+}
+
 pub fn layer_norm<C: Into<LayerNormConfig>>(
     size: usize,
     config: C,
     vb: crate::VarBuilder,
 ) -> Result<LayerNorm> {
     let config = config.into();
-    let weight = vb.get_with_hints(size, "weight", crate::Init::Const(1.))?;
+    if !config.elementwise_affine {
+        let mut ln = LayerNorm::new_no_affine(size, config.eps, vb.dtype(), vb.device())?;
+        ln.remove_mean = config.remove_mean;
+        return Ok(ln);
+    }
+    // Some checkpoints use the gamma/beta naming for the weight and the bias.
+    let get = |name: &str, alt_name: &str, init: f64| {
+        let name = if !vb.contains_tensor(name) && vb.contains_tensor(alt_name) {
+            alt_name
+        } else {
+            name
+        };
+        vb.get_with_hints(size, name, crate::Init::Const(init))
+    };
+    let weight = get("weight", "gamma", 1.)?;
     let bias = if config.affine {
-        Some(vb.get_with_hints(size, "bias", crate::Init::Const(0.))?)
+        match get("bias", "beta", 0.) {
+            Ok(bias) => Some(bias),
+            Err(_) if !vb.contains_tensor("bias") && !vb.contains_tensor("beta") => None,
+            Err(err) => Err(err)?,
+        }
     } else {
         None
     };
@@ -152,6 +215,7 @@ pub fn layer_norm<C: Into<LayerNormConfig>>(
         bias,
         remove_mean: config.remove_mean,
         eps: config.eps,
+        elementwise_affine: true,
     })
 }
 
@@ -164,6 +228,12 @@ impl RmsNorm {
         Self(LayerNorm::rms_norm(weight, eps))
     }
 
+    /// Creates a layer scaling the normalized values by `weight + offset` rather than `weight`,
+    /// e.g. Gemma uses an offset of 1.
+    pub fn new_with_offset(weight: Tensor, offset: f64, eps: f64) -> Result<Self> {
+        Ok(Self::new((weight + offset)?, eps))
+    }
+
     pub fn into_inner(self) -> LayerNorm {
         self.0
     }
@@ -184,11 +254,37 @@ impl Module for RmsNorm {
     }
 }
 
+impl crate::Parameters for RmsNorm {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        self.0.visit_parameters(f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        self.0.apply_parameters(f)
+    }
+}
+
 pub fn rms_norm(size: usize, eps: f64, vb: crate::VarBuilder) -> Result<RmsNorm> {
     let config = LayerNormConfig {
         eps,
         remove_mean: false,
         affine: false,
+        ..Default::default()
     };
     Ok(RmsNorm(layer_norm(size, config, vb)?))
 }
+
+/// Creates a [`RmsNorm`] layer where `offset` is added to the weight, see
+/// [`RmsNorm::new_with_offset`]. The weight is initialized so that the scaling starts at 1.
+pub fn rms_norm_with_offset(
+    size: usize,
+    offset: f64,
+    eps: f64,
+    vb: crate::VarBuilder,
+) -> Result<RmsNorm> {
+    let weight = vb.get_with_hints(size, "weight", crate::Init::Const(1. - offset))?;
+    RmsNorm::new_with_offset(weight, offset, eps)
+}
@@ -1,4 +1,6 @@
 pub mod activation;
+pub mod attention;
+pub mod autodiff;
 pub mod batch_norm;
 pub mod conv;
 pub mod embedding;
@@ -12,30 +14,51 @@ pub mod linear;
 pub mod loss;
 pub mod ops;
 pub mod optim;
+pub mod parameters;
+pub mod per_sample_grads;
+pub mod pooling;
 pub mod rnn;
 pub mod rotary_emb;
 pub mod sequential;
+pub mod spectral_norm;
 pub mod var_builder;
 pub mod var_map;
+pub mod weight_norm;
 
 pub use activation::{prelu, Activation, PReLU};
+pub use attention::{multi_head_attention, MultiHeadAttention, MultiHeadAttentionConfig};
 pub use batch_norm::{batch_norm, BatchNorm, BatchNormConfig};
 pub use conv::{
-    conv1d, conv1d_no_bias, conv2d, conv2d_no_bias, conv_transpose1d, conv_transpose1d_no_bias,
-    conv_transpose2d, conv_transpose2d_no_bias, Conv1d, Conv1dConfig, Conv2d, Conv2dConfig,
+    causal_conv1d, conv1d, conv1d_no_bias, conv2d, conv2d_no_bias, conv3d, conv3d_no_bias,
+    conv_transpose1d, conv_transpose1d_no_bias, conv_transpose2d, conv_transpose2d_no_bias,
+    CausalConv1d, Conv1d, Conv1dConfig, Conv2d, Conv2dConfig, Conv3d, Conv3dConfig,
     ConvTranspose1d, ConvTranspose1dConfig, ConvTranspose2d, ConvTranspose2dConfig,
 };
-pub use embedding::{embedding, Embedding};
+pub use embedding::{
+    embedding, embedding_bag, position_embedding, Embedding, EmbeddingBag, EmbeddingBagMode,
+    PositionEmbedding, SinusoidalPositionalEmbedding, SinusoidalPositionalEmbeddingConfig,
+};
 pub use func::{func, func_t, Func, FuncT};
 pub use group_norm::{group_norm, GroupNorm};
 pub use init::Init;
-pub use layer_norm::{layer_norm, rms_norm, LayerNorm, LayerNormConfig, RmsNorm};
+pub use layer_norm::{
+    layer_norm, rms_norm, rms_norm_with_offset, LayerNorm, LayerNormConfig, RmsNorm,
+};
 pub use linear::{linear, linear_b, linear_no_bias, Linear};
-pub use ops::Dropout;
+pub use ops::{Dropout, Dropout2d, PixelShuffle, PixelUnshuffle};
 pub use optim::{AdamW, Optimizer, ParamsAdamW, SGD};
-pub use rnn::{gru, lstm, GRUConfig, LSTMConfig, GRU, LSTM, RNN};
+pub use parameters::{ModuleList, ParamModule, Parameters};
+pub use pooling::{
+    AdaptiveAvgPool2d, AvgPool2d, AvgPool3d, MaxPool2d, MaxPool3d, Pool2dConfig, Pool3dConfig,
+};
+pub use rnn::{
+    gru, gru_cell, lstm, multi_layer_gru, multi_layer_lstm, Direction, GRUConfig, GRUState,
+    LSTMConfig, LSTMState, MultiLayerGRU, MultiLayerLSTM, GRU, LSTM, RNN,
+};
 pub use sequential::{seq, Sequential};
+pub use spectral_norm::{spectral_norm, SpectralNorm, SpectralNormConfig};
 pub use var_builder::VarBuilder;
 pub use var_map::VarMap;
+pub use weight_norm::{weight_norm, WeightNorm};
 
 pub use candle::{Module, ModuleT};
@@ -54,6 +54,21 @@ impl super::Module for Linear {
     }
 }
 
+impl crate::Parameters for Linear {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        f("weight", &self.weight);
+        crate::parameters::visit_opt("bias", &self.bias, f)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        crate::parameters::apply_tensor("weight", &mut self.weight, f)?;
+        crate::parameters::apply_opt("bias", &mut self.bias, f)
+    }
+}
+
 /// Create or initialize a new linear layer.
 ///
 /// This uses some default names for weights and biases, namely `"weight"` and `"bias"`.
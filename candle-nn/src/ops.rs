@@ -43,6 +43,39 @@ pub fn swiglu(xs: &Tensor) -> Result<Tensor> {
     &xs[0].silu()? * &xs[1]
 }
 
+/// Gated linear unit, the input is split in two halves `a` and `b` along `dim` and the result is
+/// `a * sigmoid(b)`.
+pub fn glu<D: candle::shape::Dim>(xs: &Tensor, dim: D) -> Result<Tensor> {
+    let xs = xs.chunk(2, dim)?;
+    &xs[0] * sigmoid(&xs[1])?
+}
+
+/// GELU gated linear unit, similar to [`swiglu`] but using the exact (erf based) GELU.
+pub fn geglu(xs: &Tensor) -> Result<Tensor> {
+    let xs = xs.chunk(2, D::Minus1)?;
+    &xs[0].gelu_erf()? * &xs[1]
+}
+
+/// Applies `log(1 + exp(x))` element-wise, computed as `max(x, 0) + log(1 + exp(-|x|))` to avoid
+/// overflowing for large inputs.
+///
+/// ```rust
+/// use candle::{Tensor, Device, test_utils::to_vec1_round};
+/// let a = Tensor::new(&[-100f32, -1., 0., 1., 100.], &Device::Cpu)?;
+/// let a = candle_nn::ops::softplus(&a)?;
+/// assert_eq!(to_vec1_round(&a, 4)?, &[0.0, 0.3133, 0.6931, 1.3133, 100.0]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn softplus(xs: &Tensor) -> Result<Tensor> {
+    let log1p_exp = (xs.abs()?.neg()?.exp()? + 1.0)?.log()?;
+    xs.relu()? + log1p_exp
+}
+
+/// Mish activation, `x * tanh(softplus(x))`.
+pub fn mish(xs: &Tensor) -> Result<Tensor> {
+    xs * softplus(xs)?.tanh()?
+}
+
 struct Sigmoid;
 
 impl candle::CustomOp1 for Sigmoid {
@@ -237,6 +270,20 @@ pub fn hard_sigmoid(xs: &Tensor) -> Result<Tensor> {
     ((xs + 3.0)? / 6.0)?.clamp(0f32, 1f32)
 }
 
+/// Hard swish activation, `x * hard_sigmoid(x)` where `hard_sigmoid(x)` is
+/// `clamp((x + 3) / 6, 0, 1)`, a cheaper approximation of `x * sigmoid(x)`.
+///
+/// ```rust
+/// use candle::{Tensor, Device, test_utils::to_vec1_round};
+/// let a = Tensor::new(&[-4f32, -1., 0., 1., 4.], &Device::Cpu)?;
+/// let a = candle_nn::ops::hard_swish(&a)?;
+/// assert_eq!(to_vec1_round(&a, 4)?, &[0.0, -0.3333, 0.0, 0.6667, 4.0]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn hard_swish(xs: &Tensor) -> Result<Tensor> {
+    xs * hard_sigmoid(xs)?
+}
+
 pub fn leaky_relu(xs: &Tensor, negative_slope: f64) -> Result<Tensor> {
     let zeros = xs.zeros_like()?;
     xs.maximum(&zeros)? + xs.minimum(&zeros)? * negative_slope
@@ -283,6 +330,53 @@ impl candle::ModuleT for Dropout {
     }
 }
 
+/// Zeroes entire channels of an input of shape `(batch, channels, ...)` with probability
+/// `drop_p`, the remaining channels are scaled by `1 / (1 - drop_p)`.
+pub fn dropout2d(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
+    if !(0. ..1.).contains(&drop_p) {
+        candle::bail!("dropout probability has to be in [0, 1), got {drop_p}")
+    }
+    if xs.rank() < 2 {
+        candle::bail!(
+            "dropout2d expects at least two dimensions, got {:?}",
+            xs.shape()
+        )
+    }
+    let mut mask_dims = vec![1; xs.rank()];
+    mask_dims[..2].copy_from_slice(&xs.dims()[..2]);
+    let rand = Tensor::rand(0f32, 1f32, mask_dims, xs.device())?;
+    let scale = 1.0 / (1.0 - drop_p as f64);
+    let drop_p = Tensor::new(drop_p, xs.device())?.broadcast_as(rand.shape())?;
+    let mask = (rand.ge(&drop_p)?.to_dtype(xs.dtype())? * scale)?;
+    xs.broadcast_mul(&mask)
+}
+
+/// Channel-wise dropout, see [`dropout2d`].
+#[derive(Clone, Debug)]
+pub struct Dropout2d {
+    drop_p: f32,
+}
+
+impl Dropout2d {
+    pub fn new(drop_p: f32) -> Dropout2d {
+        Self { drop_p }
+    }
+
+    pub fn forward(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        if train {
+            dropout2d(xs, self.drop_p)
+        } else {
+            Ok(xs.clone())
+        }
+    }
+}
+
+impl candle::ModuleT for Dropout2d {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        self.forward(xs, train)
+    }
+}
+
 struct SoftmaxLastDim;
 
 impl candle::CustomOp1 for SoftmaxLastDim {
@@ -303,20 +397,22 @@ impl candle::CustomOp1 for SoftmaxLastDim {
             let dims = layout.shape().dims();
             let dim_m1 = dims[dims.len() - 1];
             let mut dst = vec![T::zero(); el_count];
-            src.par_chunks(dim_m1)
-                .zip(dst.par_chunks_mut(dim_m1))
-                .for_each(|(src, dst)| {
-                    let mut max = T::neg_infinity();
-                    unsafe { T::vec_reduce_max(src.as_ptr(), &mut max, dim_m1) };
-                    for (s, d) in src.iter().zip(dst.iter_mut()) {
-                        *d = (*s - max).exp();
-                    }
-                    let mut sum_exp = T::zero();
-                    unsafe { T::vec_reduce_sum(dst.as_ptr(), &mut sum_exp, dim_m1) };
-                    for d in dst.iter_mut() {
-                        *d /= sum_exp
-                    }
-                });
+            candle::utils::install(|| {
+                src.par_chunks(dim_m1)
+                    .zip(dst.par_chunks_mut(dim_m1))
+                    .for_each(|(src, dst)| {
+                        let mut max = T::neg_infinity();
+                        unsafe { T::vec_reduce_max(src.as_ptr(), &mut max, dim_m1) };
+                        for (s, d) in src.iter().zip(dst.iter_mut()) {
+                            *d = (*s - max).exp();
+                        }
+                        let mut sum_exp = T::zero();
+                        unsafe { T::vec_reduce_sum(dst.as_ptr(), &mut sum_exp, dim_m1) };
+                        for d in dst.iter_mut() {
+                            *d /= sum_exp
+                        }
+                    })
+            });
             let storage = candle::WithDType::to_cpu_storage_owned(dst);
             Ok((storage, Shape::from_dims(dims)))
         }
@@ -451,11 +547,14 @@ impl candle::CustomOp2 for RmsNorm {
         use candle::backend::BackendStorage;
 
         let eps = self.eps;
+        // The statistics are accumulated in `A`, i.e. f32 for the f32 and half precision dtypes
+        // and f64 for f64.
         fn inner<
-            T: candle::WithDType
-                + num_traits::Float
-                + num_traits::AsPrimitive<f32>
-                + num_traits::FromPrimitive,
+            T: candle::WithDType + num_traits::Float + num_traits::AsPrimitive<A>,
+            A: num_traits::Float
+                + num_traits::FromPrimitive
+                + num_traits::AsPrimitive<T>
+                + std::iter::Sum,
         >(
             src: &[T],
             layout: &Layout,
@@ -475,31 +574,35 @@ impl candle::CustomOp2 for RmsNorm {
             let dims = layout.shape().dims();
             let dim_m1 = dims[dims.len() - 1];
             let mut dst = vec![T::zero(); el_count];
-            src.par_chunks(dim_m1)
-                .zip(dst.par_chunks_mut(dim_m1))
-                .for_each(|(src, dst)| {
-                    let sum2 = src
-                        .iter()
-                        .map(|&v| {
-                            let v = v.as_();
-                            v * v
-                        })
-                        .sum::<f32>();
-                    let m = (sum2 / dim_m1 as f32 + eps).sqrt();
-                    let m = T::from_f32(m).unwrap_or_else(T::nan);
-                    for ((d, s), alpha) in dst.iter_mut().zip(src.iter()).zip(alpha) {
-                        *d = *s / m * *alpha
-                    }
-                });
+            candle::utils::install(|| {
+                src.par_chunks(dim_m1)
+                    .zip(dst.par_chunks_mut(dim_m1))
+                    .for_each(|(src, dst)| {
+                        let sum2 = src
+                            .iter()
+                            .map(|&v| {
+                                let v: A = v.as_();
+                                v * v
+                            })
+                            .sum::<A>();
+                        let dim_m1 = A::from_usize(dim_m1).unwrap_or_else(A::nan);
+                        let eps = A::from_f32(eps).unwrap_or_else(A::nan);
+                        let m: T = (sum2 / dim_m1 + eps).sqrt().as_();
+                        for ((d, s), alpha) in dst.iter_mut().zip(src.iter()).zip(alpha) {
+                            *d = *s / m * *alpha
+                        }
+                    })
+            });
             let storage = candle::WithDType::to_cpu_storage_owned(dst);
             Ok((storage, Shape::from_dims(dims)))
         }
 
         use CpuStorage as C;
         match (s1, s2) {
-            (C::BF16(s1), C::BF16(s2)) => inner::<half::bf16>(s1, l1, s2, l2, eps),
-            (C::F16(s1), C::F16(s2)) => inner::<half::f16>(s1, l1, s2, l2, eps),
-            (C::F32(s1), C::F32(s2)) => inner::<f32>(s1, l1, s2, l2, eps),
+            (C::BF16(s1), C::BF16(s2)) => inner::<half::bf16, f32>(s1, l1, s2, l2, eps),
+            (C::F16(s1), C::F16(s2)) => inner::<half::f16, f32>(s1, l1, s2, l2, eps),
+            (C::F32(s1), C::F32(s2)) => inner::<f32, f32>(s1, l1, s2, l2, eps),
+            (C::F64(s1), C::F64(s2)) => inner::<f64, f64>(s1, l1, s2, l2, eps),
             _ => candle::bail!("unsupported dtype for rmsnorm {:?}", s1.dtype()),
         }
     }
@@ -662,11 +765,14 @@ impl candle::CustomOp3 for LayerNorm {
         use candle::backend::BackendStorage;
 
         let eps = self.eps;
+        // The statistics are accumulated in `A`, i.e. f32 for the f32 and half precision dtypes
+        // and f64 for f64.
         fn inner<
-            T: candle::WithDType
-                + num_traits::Float
-                + num_traits::AsPrimitive<f32>
-                + num_traits::FromPrimitive,
+            T: candle::WithDType + num_traits::Float + num_traits::AsPrimitive<A>,
+            A: num_traits::Float
+                + num_traits::FromPrimitive
+                + num_traits::AsPrimitive<T>
+                + std::iter::Sum,
         >(
             src: &[T],
             layout: &Layout,
@@ -692,28 +798,32 @@ impl candle::CustomOp3 for LayerNorm {
             let dims = layout.shape().dims();
             let dim_m1 = dims[dims.len() - 1];
             let mut dst = vec![T::zero(); el_count];
-            src.par_chunks(dim_m1)
-                .zip(dst.par_chunks_mut(dim_m1))
-                .for_each(|(src, dst)| {
-                    let mut sum = 0f32;
-                    let mut sum2 = 0f32;
-                    for v in src {
-                        let v = v.as_();
-                        sum += v;
-                        sum2 += v * v;
-                    }
-                    let mean = sum / dim_m1 as f32;
-                    let var = sum2 / dim_m1 as f32 - mean * mean;
-                    let inv_std = (var + eps).sqrt().recip();
-                    for ((d, s), (alpha, beta)) in
-                        dst.iter_mut().zip(src.iter()).zip(alpha.iter().zip(beta))
-                    {
-                        let alpha = alpha.as_();
-                        let beta = beta.as_();
-                        let d_ = (s.as_() - mean) * inv_std * alpha + beta;
-                        *d = T::from_f32(d_).unwrap_or_else(T::nan);
-                    }
-                });
+            candle::utils::install(|| {
+                src.par_chunks(dim_m1)
+                    .zip(dst.par_chunks_mut(dim_m1))
+                    .for_each(|(src, dst)| {
+                        let mut sum = A::zero();
+                        let mut sum2 = A::zero();
+                        for v in src {
+                            let v: A = v.as_();
+                            sum = sum + v;
+                            sum2 = sum2 + v * v;
+                        }
+                        let dim_m1 = A::from_usize(dim_m1).unwrap_or_else(A::nan);
+                        let eps = A::from_f32(eps).unwrap_or_else(A::nan);
+                        let mean = sum / dim_m1;
+                        let var = sum2 / dim_m1 - mean * mean;
+                        let inv_std = (var + eps).sqrt().recip();
+                        for ((d, s), (alpha, beta)) in
+                            dst.iter_mut().zip(src.iter()).zip(alpha.iter().zip(beta))
+                        {
+                            let alpha: A = alpha.as_();
+                            let beta: A = beta.as_();
+                            let s: A = s.as_();
+                            *d = ((s - mean) * inv_std * alpha + beta).as_();
+                        }
+                    })
+            });
             let storage = candle::WithDType::to_cpu_storage_owned(dst);
             Ok((storage, Shape::from_dims(dims)))
         }
@@ -721,11 +831,14 @@ impl candle::CustomOp3 for LayerNorm {
         use CpuStorage as C;
         match (s1, s2, s3) {
             (C::BF16(s1), C::BF16(s2), C::BF16(s3)) => {
-                inner::<half::bf16>(s1, l1, s2, l2, s3, l3, eps)
+                inner::<half::bf16, f32>(s1, l1, s2, l2, s3, l3, eps)
             }
-            (C::F16(s1), C::F16(s2), C::F16(s3)) => inner::<half::f16>(s1, l1, s2, l2, s3, l3, eps),
-            (C::F32(s1), C::F32(s2), C::F32(s3)) => inner::<f32>(s1, l1, s2, l2, s3, l3, eps),
-            _ => candle::bail!("unsupported dtype for rmsnorm {:?}", s1.dtype()),
+            (C::F16(s1), C::F16(s2), C::F16(s3)) => {
+                inner::<half::f16, f32>(s1, l1, s2, l2, s3, l3, eps)
+            }
+            (C::F32(s1), C::F32(s2), C::F32(s3)) => inner::<f32, f32>(s1, l1, s2, l2, s3, l3, eps),
+            (C::F64(s1), C::F64(s2), C::F64(s3)) => inner::<f64, f64>(s1, l1, s2, l2, s3, l3, eps),
+            _ => candle::bail!("unsupported dtype for layernorm {:?}", s1.dtype()),
         }
     }
 
@@ -888,17 +1001,342 @@ pub fn layer_norm(xs: &Tensor, alpha: &Tensor, beta: &Tensor, eps: f32) -> Resul
     xs.apply_op3_no_bwd(alpha, beta, &LayerNorm { eps })
 }
 
+#[derive(Debug, Clone)]
+struct GroupNorm {
+    num_groups: usize,
+    eps: f32,
+}
+
+impl candle::CustomOp3 for GroupNorm {
+    fn name(&self) -> &'static str {
+        "group-norm"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &CpuStorage,
+        l1: &Layout,
+        s2: &CpuStorage,
+        l2: &Layout,
+        s3: &CpuStorage,
+        l3: &Layout,
+    ) -> Result<(CpuStorage, Shape)> {
+        use candle::backend::BackendStorage;
+
+        let (num_groups, eps) = (self.num_groups, self.eps);
+        // The statistics are accumulated in `A`, i.e. f32 for the f32 and half precision dtypes
+        // and f64 for f64.
+        #[allow(clippy::too_many_arguments)]
+        fn inner<
+            T: candle::WithDType + num_traits::Float + num_traits::AsPrimitive<A>,
+            A: num_traits::Float
+                + num_traits::FromPrimitive
+                + num_traits::AsPrimitive<T>
+                + std::iter::Sum,
+        >(
+            src: &[T],
+            layout: &Layout,
+            alpha: &[T],
+            alpha_layout: &Layout,
+            beta: &[T],
+            beta_layout: &Layout,
+            num_groups: usize,
+            eps: f32,
+        ) -> Result<(CpuStorage, Shape)> {
+            let src = match layout.contiguous_offsets() {
+                None => candle::bail!("input has to be contiguous"),
+                Some((o1, o2)) => &src[o1..o2],
+            };
+            let alpha = match alpha_layout.contiguous_offsets() {
+                None => candle::bail!("alpha has to be contiguous"),
+                Some((o1, o2)) => &alpha[o1..o2],
+            };
+            let beta = match beta_layout.contiguous_offsets() {
+                None => candle::bail!("beta has to be contiguous"),
+                Some((o1, o2)) => &beta[o1..o2],
+            };
+            let el_count = layout.shape().elem_count();
+            let dims = layout.shape().dims();
+            let mut dst = vec![T::zero(); el_count];
+            if el_count > 0 {
+                let spatial = dims[2..].iter().product::<usize>();
+                let channels_per_group = dims[1] / num_groups;
+                let group_size = channels_per_group * spatial;
+                candle::utils::install(|| {
+                    src.par_chunks(group_size)
+                        .zip(dst.par_chunks_mut(group_size))
+                        .enumerate()
+                        .for_each(|(idx, (src, dst))| {
+                            let n = A::from_usize(group_size).unwrap_or_else(A::nan);
+                            let eps = A::from_f32(eps).unwrap_or_else(A::nan);
+                            let mean = src.iter().map(|&v| v.as_()).sum::<A>() / n;
+                            let var = src
+                                .iter()
+                                .map(|&v| {
+                                    let v: A = v.as_() - mean;
+                                    v * v
+                                })
+                                .sum::<A>()
+                                / n;
+                            let inv_std = (var + eps).sqrt().recip();
+                            let first_channel = (idx % num_groups) * channels_per_group;
+                            for (c_idx, (src, dst)) in
+                                src.chunks(spatial).zip(dst.chunks_mut(spatial)).enumerate()
+                            {
+                                let alpha: A = alpha[first_channel + c_idx].as_();
+                                let beta: A = beta[first_channel + c_idx].as_();
+                                let scale = inv_std * alpha;
+                                let shift = beta - mean * scale;
+                                for (d, &s) in dst.iter_mut().zip(src.iter()) {
+                                    let s: A = s.as_();
+                                    *d = (s * scale + shift).as_();
+                                }
+                            }
+                        })
+                });
+            }
+            let storage = candle::WithDType::to_cpu_storage_owned(dst);
+            Ok((storage, Shape::from_dims(dims)))
+        }
+
+        use CpuStorage as C;
+        match (s1, s2, s3) {
+            (C::BF16(s1), C::BF16(s2), C::BF16(s3)) => {
+                inner::<half::bf16, f32>(s1, l1, s2, l2, s3, l3, num_groups, eps)
+            }
+            (C::F16(s1), C::F16(s2), C::F16(s3)) => {
+                inner::<half::f16, f32>(s1, l1, s2, l2, s3, l3, num_groups, eps)
+            }
+            (C::F32(s1), C::F32(s2), C::F32(s3)) => {
+                inner::<f32, f32>(s1, l1, s2, l2, s3, l3, num_groups, eps)
+            }
+            (C::F64(s1), C::F64(s2), C::F64(s3)) => {
+                inner::<f64, f64>(s1, l1, s2, l2, s3, l3, num_groups, eps)
+            }
+            _ => candle::bail!("unsupported dtype for groupnorm {:?}", s1.dtype()),
+        }
+    }
+
+    #[cfg(feature = "cuda")]
+    fn cuda_fwd(
+        &self,
+        s1: &candle::CudaStorage,
+        l1: &Layout,
+        s2: &candle::CudaStorage,
+        l2: &Layout,
+        s3: &candle::CudaStorage,
+        l3: &Layout,
+    ) -> Result<(candle::CudaStorage, Shape)> {
+        use candle::cuda_backend::cudarc::driver::{
+            CudaSlice, DeviceRepr, LaunchAsync, LaunchConfig,
+        };
+        use candle::cuda_backend::{kernel_name, kernels, Map3, WrapErr};
+        use candle::{CudaDevice, WithDType};
+
+        struct S {
+            num_groups: usize,
+            eps: f32,
+        }
+        impl Map3 for S {
+            fn f<T: DeviceRepr + WithDType>(
+                &self,
+                src: &CudaSlice<T>,
+                layout: &Layout,
+                alpha: &CudaSlice<T>,
+                alpha_layout: &Layout,
+                beta: &CudaSlice<T>,
+                beta_layout: &Layout,
+                dev: &CudaDevice,
+            ) -> Result<CudaSlice<T>> {
+                let src = match layout.contiguous_offsets() {
+                    None => candle::bail!("input has to be contiguous"),
+                    Some((o1, o2)) => src.slice(o1..o2),
+                };
+                let alpha = match alpha_layout.contiguous_offsets() {
+                    None => candle::bail!("alpha has to be contiguous"),
+                    Some((o1, o2)) => alpha.slice(o1..o2),
+                };
+                let beta = match beta_layout.contiguous_offsets() {
+                    None => candle::bail!("beta has to be contiguous"),
+                    Some((o1, o2)) => beta.slice(o1..o2),
+                };
+                let el = layout.shape().elem_count();
+                let dims = layout.shape().dims();
+                let spatial = dims[2..].iter().product::<usize>();
+                let n_rows = dims[0] * self.num_groups;
+                let n_cols = dims[1] / self.num_groups * spatial;
+
+                let cfg = LaunchConfig {
+                    grid_dim: (n_rows as u32, 1, 1),
+                    block_dim: (1024, 1, 1),
+                    shared_mem_bytes: 0,
+                };
+                let func = dev.get_or_load_func(&kernel_name::<T>("groupnorm"), kernels::REDUCE)?;
+                // SAFETY: Set later by running the kernel.
+                let dst = unsafe { dev.alloc::<T>(el) }.w()?;
+                let params = (
+                    &src,
+                    &dst,
+                    &alpha,
+                    &beta,
+                    n_cols as i32,
+                    spatial as i32,
+                    self.num_groups as i32,
+                    self.eps,
+                );
+                // SAFETY: ffi.
+                unsafe { func.launch(cfg, params) }.w()?;
+                Ok(dst)
+            }
+        }
+
+        use candle::backend::BackendStorage;
+        let dev = s1.device();
+        let s = S {
+            num_groups: self.num_groups,
+            eps: self.eps,
+        };
+        let slice = s.map(&s1.slice, l1, &s2.slice, l2, &s3.slice, l3, dev)?;
+        let dst = candle::cuda_backend::CudaStorage {
+            slice,
+            device: dev.clone(),
+        };
+        Ok((dst, l1.shape().clone()))
+    }
+
+    fn bwd(
+        &self,
+        xs: &Tensor,
+        alpha: &Tensor,
+        beta: &Tensor,
+        _res: &Tensor,
+        grad_res: &Tensor,
+    ) -> Result<(Option<Tensor>, Option<Tensor>, Option<Tensor>)> {
+        let internal_dtype = match xs.dtype() {
+            DType::F16 | DType::BF16 => DType::F32,
+            d => d,
+        };
+        let (b_sz, n_channels) = (xs.dim(0)?, xs.dim(1)?);
+        let shape = (b_sz, self.num_groups, n_channels / self.num_groups, ());
+        let xs_ = xs.to_dtype(internal_dtype)?.reshape(shape)?;
+        let grad = grad_res.to_dtype(internal_dtype)?.reshape(shape)?;
+        let alpha_ = alpha
+            .to_dtype(internal_dtype)?
+            .reshape((1, self.num_groups, (), 1))?;
+        let xs_ = xs_.broadcast_sub(&xs_.mean_keepdim((2, 3))?)?;
+        let inv_std = (xs_.sqr()?.mean_keepdim((2, 3))? + self.eps as f64)?
+            .sqrt()?
+            .recip()?;
+        let xs_normed = xs_.broadcast_mul(&inv_std)?;
+        let grad_beta = grad.sum((0, 3))?.flatten_all()?;
+        let grad_alpha = (&grad * &xs_normed)?.sum((0, 3))?.flatten_all()?;
+        // dx = inv_std * (dx_normed - mean(dx_normed) - x_normed * mean(dx_normed * x_normed))
+        let grad_normed = grad.broadcast_mul(&alpha_)?;
+        let mean_grad = grad_normed.mean_keepdim((2, 3))?;
+        let mean_grad_x = (&grad_normed * &xs_normed)?.mean_keepdim((2, 3))?;
+        let grad_xs = grad_normed
+            .broadcast_sub(&mean_grad)?
+            .sub(&xs_normed.broadcast_mul(&mean_grad_x)?)?
+            .broadcast_mul(&inv_std)?
+            .reshape(xs.shape())?
+            .to_dtype(xs.dtype())?;
+        Ok((
+            Some(grad_xs),
+            Some(grad_alpha.to_dtype(alpha.dtype())?),
+            Some(grad_beta.to_dtype(beta.dtype())?),
+        ))
+    }
+}
+
+pub fn group_norm_slow(
+    x: &Tensor,
+    num_groups: usize,
+    alpha: &Tensor,
+    beta: &Tensor,
+    eps: f32,
+) -> Result<Tensor> {
+    let x_dtype = x.dtype();
+    let internal_dtype = match x_dtype {
+        DType::F16 | DType::BF16 => DType::F32,
+        d => d,
+    };
+    let (b_sz, n_channels) = (x.dim(0)?, x.dim(1)?);
+    let x_normed = {
+        let x = x
+            .to_dtype(internal_dtype)?
+            .reshape((b_sz, num_groups, ()))?;
+        let x = x.broadcast_sub(&x.mean_keepdim(2)?)?;
+        let norm_x = x.sqr()?.mean_keepdim(2)?;
+        x.broadcast_div(&(norm_x + eps as f64)?.sqrt()?)?
+    };
+    let mut w_dims = vec![1; x.rank()];
+    w_dims[1] = n_channels;
+    x_normed
+        .to_dtype(x_dtype)?
+        .reshape(x.shape())?
+        .broadcast_mul(&alpha.reshape(w_dims.as_slice())?)?
+        .broadcast_add(&beta.reshape(w_dims.as_slice())?)
+}
+
+/// Group normalization over an input of shape `(batch, channels, ...)`, the channels are split in
+/// `num_groups` groups that are normalized separately before applying the per-channel `alpha` and
+/// `beta`. This uses a fused kernel on cpu and cuda devices.
+pub fn group_norm(
+    xs: &Tensor,
+    num_groups: usize,
+    alpha: &Tensor,
+    beta: &Tensor,
+    eps: f32,
+) -> Result<Tensor> {
+    if xs.rank() < 2 {
+        candle::bail!(
+            "group-norm expects at least two dimensions {:?}",
+            xs.shape()
+        )
+    }
+    let n_channels = xs.dim(1)?;
+    if num_groups == 0 || n_channels % num_groups != 0 {
+        candle::bail!(
+            "group-norm: num_groups ({num_groups}) must divide num_channels ({n_channels})"
+        )
+    }
+    if alpha.dims1()? != n_channels || beta.dims1()? != n_channels {
+        candle::bail!(
+            "shape mismatch in group-norm src: {:?} alpha: {:?} beta: {:?}",
+            xs.shape(),
+            alpha.shape(),
+            beta.shape()
+        )
+    }
+    xs.apply_op3(alpha, beta, GroupNorm { num_groups, eps })
+}
+
 // https://pytorch.org/docs/stable/generated/torch.nn.PixelShuffle.html
+/// Rearranges a tensor of shape `(b, c * r * r, h, w)` into `(b, c, h * r, w * r)` with `r` the
+/// upscale factor, as used in sub-pixel convolutions.
 pub fn pixel_shuffle(xs: &Tensor, upscale_factor: usize) -> Result<Tensor> {
     let (b_size, c, h, w) = xs.dims4()?;
-    let out_c = c / upscale_factor / upscale_factor;
+    let r2 = upscale_factor * upscale_factor;
+    if upscale_factor == 0 || c % r2 != 0 {
+        candle::bail!(
+            "pixel-shuffle: the number of channels {c} should be divisible by the square of the upscale factor {upscale_factor}"
+        )
+    }
+    let out_c = c / r2;
     xs.reshape((b_size, out_c, upscale_factor, upscale_factor, h, w))?
         .permute((0, 1, 4, 2, 5, 3))?
         .reshape((b_size, out_c, h * upscale_factor, w * upscale_factor))
 }
 
+/// The inverse of [`pixel_shuffle`], rearranges a tensor of shape `(b, c, h * r, w * r)` into
+/// `(b, c * r * r, h, w)` with `r` the downscale factor.
 pub fn pixel_unshuffle(xs: &Tensor, downscale_factor: usize) -> Result<Tensor> {
     let (b_size, c, h, w) = xs.dims4()?;
+    if downscale_factor == 0 || h % downscale_factor != 0 || w % downscale_factor != 0 {
+        candle::bail!(
+            "pixel-unshuffle: the spatial dims ({h}, {w}) should be divisible by the downscale factor {downscale_factor}"
+        )
+    }
     let out_c = c * downscale_factor * downscale_factor;
     xs.reshape((
         b_size,
@@ -912,6 +1350,40 @@ pub fn pixel_unshuffle(xs: &Tensor, downscale_factor: usize) -> Result<Tensor> {
     .reshape((b_size, out_c, h / downscale_factor, w / downscale_factor))
 }
 
+#[derive(Clone, Debug)]
+pub struct PixelShuffle {
+    upscale_factor: usize,
+}
+
+impl PixelShuffle {
+    pub fn new(upscale_factor: usize) -> Self {
+        Self { upscale_factor }
+    }
+}
+
+impl Module for PixelShuffle {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        pixel_shuffle(xs, self.upscale_factor)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PixelUnshuffle {
+    downscale_factor: usize,
+}
+
+impl PixelUnshuffle {
+    pub fn new(downscale_factor: usize) -> Self {
+        Self { downscale_factor }
+    }
+}
+
+impl Module for PixelUnshuffle {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        pixel_unshuffle(xs, self.downscale_factor)
+    }
+}
+
 // https://pytorch.org/docs/stable/generated/torch.nn.ReplicationPad2d.html
 pub fn replication_pad2d(xs: &Tensor, pad: usize) -> Result<Tensor> {
     match pad {
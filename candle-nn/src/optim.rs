@@ -1,5 +1,5 @@
 //! Various optimization algorithms.
-use candle::{Result, Tensor, Var};
+use candle::{backprop::SparseGrad, Result, Tensor, Var};
 
 /// The interface optimizers should implement.
 pub trait Optimizer: Sized {
@@ -59,6 +59,9 @@ impl Optimizer for SGD {
         for var in self.vars.iter() {
             if let Some(grad) = grads.get(var) {
                 var.set(&var.sub(&(grad * self.learning_rate)?)?)?;
+            } else if let Some(grad) = grads.get_sparse(var) {
+                let update = (grad.values() * -self.learning_rate)?;
+                var.index_add_(grad.indices(), &update, 0)?;
             }
         }
         Ok(())
@@ -158,6 +161,17 @@ impl Optimizer for AdamW {
         let beta2 = self.params.beta2;
         let scale_m = 1f64 / (1f64 - beta1.powi(self.step_t as i32));
         let scale_v = 1f64 / (1f64 - beta2.powi(self.step_t as i32));
+        let eps = self.params.eps;
+        let update = |theta: &Tensor, m: &Tensor, v: &Tensor, g: &Tensor| {
+            let next_m = ((m * beta1)? + (g * (1.0 - beta1))?)?;
+            let next_v = ((v * beta2)? + (g.sqr()? * (1.0 - beta2))?)?;
+            let m_hat = (&next_m * scale_m)?;
+            let v_hat = (&next_v * scale_v)?;
+            let next_theta = (theta * (1f64 - lr_lambda))?;
+            let adjusted_grad = (m_hat / (v_hat.sqrt()? + eps)?)?;
+            let next_theta = (next_theta - (adjusted_grad * lr)?)?;
+            Ok::<_, candle::Error>((next_theta, next_m, next_v))
+        };
         for var in self.vars.iter() {
             let theta = &var.var;
             let m = &var.first_moment;
@@ -166,16 +180,23 @@ impl Optimizer for AdamW {
                 // This involves locking 3 RWLocks per params, if the parameters are large this
                 // should not be an issue but this may be problematic with models with lots of
                 // small parameters.
-                let next_m = ((m.as_tensor() * beta1)? + (g * (1.0 - beta1))?)?;
-                let next_v = ((v.as_tensor() * beta2)? + (g.sqr()? * (1.0 - beta2))?)?;
-                let m_hat = (&next_m * scale_m)?;
-                let v_hat = (&next_v * scale_v)?;
-                let next_theta = (theta.as_tensor() * (1f64 - lr_lambda))?;
-                let adjusted_grad = (m_hat / (v_hat.sqrt()? + self.params.eps)?)?;
-                let next_theta = (next_theta - (adjusted_grad * lr)?)?;
+                let (next_theta, next_m, next_v) = update(theta, m, v, g)?;
                 m.set(&next_m)?;
                 v.set(&next_v)?;
                 theta.set(&next_theta)?;
+            } else if let Some(g) = grads.get_sparse(theta) {
+                // Similar to PyTorch's SparseAdam, only the rows that have a gradient get their
+                // moments, weight decay and weights updated.
+                let g = g.coalesce()?;
+                let ids = g.indices();
+                let rows = |t: &Var| t.index_select(ids, 0);
+                let (theta_r, m_r, v_r) = (rows(theta)?, rows(m)?, rows(v)?);
+                let (next_theta, next_m, next_v) = update(&theta_r, &m_r, &v_r, g.values())?;
+                // The indexes are unique so adding the differences sets the rows, this is done in
+                // place to avoid allocating tensors with the full size of the parameters.
+                m.index_add_(ids, &(next_m - m_r)?, 0)?;
+                v.index_add_(ids, &(next_v - v_r)?, 0)?;
+                theta.index_add_(ids, &(next_theta - theta_r)?, 0)?;
             }
         }
         Ok(())
@@ -199,3 +220,72 @@ impl AdamW {
         self.params = params;
     }
 }
+
+/// Rescales the gradients of `vars` so that their global L2 norm is at most `max_norm`, and
+/// returns the norm of the gradients before clipping.
+///
+/// The variables have to be provided explicitly as the gradient store can also contain gradients
+/// for tensors that are not trained, e.g. constants. The norm is computed on device and only
+/// copied back once.
+pub fn clip_grad_norm(
+    grads: &mut candle::backprop::GradStore,
+    vars: &[Var],
+    max_norm: f64,
+) -> Result<f64> {
+    let mut sum_sqr: Option<Tensor> = None;
+    for var in vars.iter() {
+        // The duplicate rows of sparse gradients have to be summed before computing the norm.
+        if let Some(grad) = grads.remove_sparse(var) {
+            grads.insert_sparse(var, grad.coalesce()?);
+        }
+        let grad = match (grads.get(var), grads.get_sparse(var)) {
+            (Some(grad), _) => grad,
+            (None, Some(grad)) => grad.values(),
+            (None, None) => continue,
+        };
+        let s = grad.to_dtype(candle::DType::F32)?.sqr()?.sum_all()?;
+        sum_sqr = Some(match sum_sqr {
+            None => s,
+            Some(sum_sqr) => (sum_sqr + s)?,
+        })
+    }
+    let norm = match sum_sqr {
+        None => return Ok(0.),
+        Some(sum_sqr) => sum_sqr.sqrt()?,
+    };
+    // Same as PyTorch, the gradients are scaled by max_norm / (norm + eps) capped at 1.
+    let scale = ((norm.affine(1., 1e-6)?.recip()? * max_norm)?).minimum(1f32)?;
+    for var in vars.iter() {
+        if let Some(grad) = grads.get(var) {
+            let clipped = grad.broadcast_mul(&scale.to_dtype(grad.dtype())?)?;
+            grads.insert(var, clipped);
+        } else if let Some(grad) = grads.get_sparse(var) {
+            let values = grad.values();
+            let values = values.broadcast_mul(&scale.to_dtype(values.dtype())?)?;
+            let clipped = SparseGrad::new(grad.indices(), &values, grad.shape())?;
+            grads.insert_sparse(var, clipped);
+        }
+    }
+    Ok(norm.to_scalar::<f32>()? as f64)
+}
+
+/// Clamps the gradients of `vars` element-wise to the `[-clip_value, clip_value]` range.
+pub fn clip_grad_value(
+    grads: &mut candle::backprop::GradStore,
+    vars: &[Var],
+    clip_value: f64,
+) -> Result<()> {
+    for var in vars.iter() {
+        if let Some(grad) = grads.get(var) {
+            let clipped = grad.clamp(-clip_value, clip_value)?;
+            grads.insert(var, clipped);
+        } else if let Some(grad) = grads.get_sparse(var) {
+            // The duplicate rows are summed before clamping.
+            let grad = grad.coalesce()?;
+            let values = grad.values().clamp(-clip_value, clip_value)?;
+            let clipped = SparseGrad::new(grad.indices(), &values, grad.shape())?;
+            grads.insert_sparse(var, clipped);
+        }
+    }
+    Ok(())
+}
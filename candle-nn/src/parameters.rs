@@ -0,0 +1,248 @@
+//! Named parameter traversal.
+//!
+//! The [`Parameters`] trait gives access to the tensors held by a layer, named after the paths
+//! used to load them with a [`VarBuilder`](crate::VarBuilder), e.g. `weight` and `bias` for a
+//! linear layer or `q_proj.weight` for an attention layer. This makes it possible to write generic
+//! code to count the parameters of a model, move it to another device, or select the parameters
+//! that get weight decay.
+//!
+//! ```rust
+//! use candle::{DType, Device, Tensor};
+//! use candle_nn::{Module, ModuleList, Parameters, VarBuilder};
+//! # fn main() -> candle::Result<()> {
+//! let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+//! let mut layers = ModuleList::new();
+//! layers.push(candle_nn::linear(4, 8, vb.pp("0"))?);
+//! layers.push(candle_nn::linear(8, 2, vb.pp("1"))?);
+//! assert_eq!(layers.num_parameters(), 4 * 8 + 8 + 8 * 2 + 2);
+//! let names: Vec<_> = layers.named_parameters().into_iter().map(|(n, _)| n).collect();
+//! assert_eq!(names, ["0.weight", "0.bias", "1.weight", "1.bias"]);
+//! layers.to_dtype(DType::F16)?;
+//! let xs = Tensor::zeros((1, 4), DType::F16, &Device::Cpu)?;
+//! let ys = layers.iter().try_fold(xs, |xs, l| l.forward(&xs))?;
+//! assert_eq!(ys.dims(), &[1, 2]);
+//! # Ok(()) }
+//! ```
+use candle::{DType, Device, Module, Result, Tensor};
+
+/// A layer holding some named tensors. Besides the learnable parameters, this includes the
+/// buffers such as the running statistics of batch normalization.
+pub trait Parameters {
+    /// Calls `f` on each tensor together with its name.
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor));
+
+    /// Replaces each tensor with the result of `f`, e.g. to change the device or dtype.
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()>;
+
+    /// Returns the named tensors, in the order in which they are visited.
+    fn named_parameters(&self) -> Vec<(String, Tensor)> {
+        let mut params = vec![];
+        self.visit_parameters(&mut |name, t| params.push((name.to_string(), t.clone())));
+        params
+    }
+
+    /// The total number of elements across all the tensors.
+    fn num_parameters(&self) -> usize {
+        let mut n = 0;
+        self.visit_parameters(&mut |_, t| n += t.elem_count());
+        n
+    }
+
+    /// Moves all the tensors to `device`.
+    fn to_device(&mut self, device: &Device) -> Result<()> {
+        self.apply_parameters(&mut |_, t| t.to_device(device))
+    }
+
+    /// Converts all the tensors to `dtype`.
+    fn to_dtype(&mut self, dtype: DType) -> Result<()> {
+        self.apply_parameters(&mut |_, t| t.to_dtype(dtype))
+    }
+}
+
+/// Visits the tensors of a sub-layer, prefixing their names with `prefix`.
+pub fn visit_prefixed<P: Parameters + ?Sized>(
+    prefix: &str,
+    p: &P,
+    f: &mut dyn FnMut(&str, &Tensor),
+) {
+    p.visit_parameters(&mut |name, t| f(&format!("{prefix}.{name}"), t))
+}
+
+/// Applies `f` to the tensors of a sub-layer, prefixing their names with `prefix`.
+pub fn apply_prefixed<P: Parameters + ?Sized>(
+    prefix: &str,
+    p: &mut P,
+    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+) -> Result<()> {
+    p.apply_parameters(&mut |name, t| f(&format!("{prefix}.{name}"), t))
+}
+
+// Helpers for implementing the trait on layers with plain tensor fields.
+pub(crate) fn visit_opt(name: &str, t: &Option<Tensor>, f: &mut dyn FnMut(&str, &Tensor)) {
+    if let Some(t) = t {
+        f(name, t)
+    }
+}
+
+pub(crate) fn apply_tensor(
+    name: &str,
+    t: &mut Tensor,
+    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+) -> Result<()> {
+    *t = f(name, t)?;
+    Ok(())
+}
+
+pub(crate) fn apply_opt(
+    name: &str,
+    t: &mut Option<Tensor>,
+    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+) -> Result<()> {
+    if let Some(t) = t {
+        *t = f(name, t)?
+    }
+    Ok(())
+}
+
+/// The elements are named after their index.
+impl<P: Parameters> Parameters for Vec<P> {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        for (i, p) in self.iter().enumerate() {
+            visit_prefixed(&i.to_string(), p, f)
+        }
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        for (i, p) in self.iter_mut().enumerate() {
+            apply_prefixed(&i.to_string(), p, f)?
+        }
+        Ok(())
+    }
+}
+
+impl<P: Parameters> Parameters for Option<P> {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        if let Some(p) = self {
+            p.visit_parameters(f)
+        }
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        match self {
+            None => Ok(()),
+            Some(p) => p.apply_parameters(f),
+        }
+    }
+}
+
+macro_rules! impl_no_parameters {
+    ($($t:ty),*) => {
+        $(
+            impl Parameters for $t {
+                fn visit_parameters(&self, _: &mut dyn FnMut(&str, &Tensor)) {}
+
+                fn apply_parameters(
+                    &mut self,
+                    _: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+                ) -> Result<()> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+// The rotary and sinusoidal embeddings hold tables that are computed at creation rather than
+// loaded, they have to be recreated to change their device or dtype.
+impl_no_parameters!(
+    crate::Activation,
+    crate::Dropout,
+    crate::Dropout2d,
+    crate::ops::Identity,
+    crate::PixelShuffle,
+    crate::PixelUnshuffle,
+    crate::pooling::MaxPool2d,
+    crate::pooling::AvgPool2d,
+    crate::pooling::MaxPool3d,
+    crate::pooling::AvgPool3d,
+    crate::pooling::AdaptiveAvgPool2d,
+    crate::rotary_emb::RotaryEmbedding,
+    crate::embedding::SinusoidalPositionalEmbedding,
+    crate::Func<'_>,
+    crate::FuncT<'_>
+);
+
+/// A layer that can be stored in a [`ModuleList`].
+pub trait ParamModule: Module + Parameters {}
+
+impl<T: Module + Parameters> ParamModule for T {}
+
+/// A list of layers, the parameters of the layer at position `i` being prefixed with `i`. Unlike
+/// [`Sequential`](crate::Sequential), the list does not implement `forward` as the layers are
+/// not necessarily applied in order.
+#[derive(Default)]
+pub struct ModuleList {
+    modules: Vec<Box<dyn ParamModule>>,
+}
+
+impl ModuleList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a layer at the end of the list.
+    pub fn push<M: ParamModule + 'static>(&mut self, module: M) {
+        self.modules.push(Box::new(module))
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&dyn ParamModule> {
+        self.modules.get(index).map(|m| m.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ParamModule> {
+        self.modules.iter().map(|m| m.as_ref())
+    }
+}
+
+impl std::ops::Index<usize> for ModuleList {
+    type Output = dyn ParamModule;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.modules[index].as_ref()
+    }
+}
+
+impl Parameters for ModuleList {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        for (i, m) in self.modules.iter().enumerate() {
+            visit_prefixed(&i.to_string(), m.as_ref(), f)
+        }
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        for (i, m) in self.modules.iter_mut().enumerate() {
+            apply_prefixed(&i.to_string(), m.as_mut(), f)?
+        }
+        Ok(())
+    }
+}
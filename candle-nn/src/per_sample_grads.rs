@@ -0,0 +1,172 @@
+//! Per-example gradients for the Linear, Conv and Embedding layers.
+//!
+//! These are computed from the layer inputs and from the gradient of the layer outputs, the
+//! latter being captured during the backward pass with a [`GradCapture`]. The first dimension of
+//! the inputs is the batch dimension, summing the per-example gradients over this dimension gives
+//! the gradient of the whole batch. The loss should thus be a sum of per-example losses rather
+//! than a mean for the per-example gradients not to be scaled by the batch size.
+//!
+//! ```rust
+//! use candle::{Device, Module, Tensor, Var};
+//! use candle_nn::{per_sample_grads, Linear};
+//! let w = Var::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+//! let layer = Linear::new(w.as_tensor().clone(), None);
+//! let xs = Tensor::new(&[[1f32, 0.], [0., 1.], [1., 1.]], &Device::Cpu)?;
+//! let ys = layer.forward(&xs)?;
+//! let capture = per_sample_grads::GradCapture::new(&ys);
+//! let grads = ys.sum_all()?.backward()?;
+//! let (grad_w, _) = per_sample_grads::linear(&layer, &xs, &capture.grad()?)?;
+//! assert_eq!(grad_w.dims(), &[3, 2, 2]);
+//! assert_eq!(
+//!     grad_w.sum(0)?.to_vec2::<f32>()?,
+//!     grads.get(&w).unwrap().to_vec2::<f32>()?
+//! );
+//! # Ok::<(), candle::Error>(())
+//! ```
+use crate::{Conv1d, Conv2d, Embedding, Linear};
+use candle::{backprop::GradHookHandle, Result, Tensor, D};
+use std::sync::{Arc, Mutex};
+
+/// Captures the gradient of a tensor during the backward passes, only the gradient from the last
+/// backward pass is kept.
+#[derive(Debug)]
+pub struct GradCapture {
+    grad: Arc<Mutex<Option<Tensor>>>,
+    handle: Option<GradHookHandle>,
+}
+
+impl GradCapture {
+    pub fn new(t: &Tensor) -> Self {
+        let grad = Arc::new(Mutex::new(None));
+        let handle = t.register_grad_hook({
+            let grad = grad.clone();
+            move |g| {
+                *grad.lock().unwrap() = Some(g.clone());
+                Ok(None)
+            }
+        });
+        Self {
+            grad,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns the captured gradient, this fails if no backward pass went through the tensor.
+    pub fn grad(&self) -> Result<Tensor> {
+        match self.grad.lock().unwrap().as_ref() {
+            Some(grad) => Ok(grad.clone()),
+            None => candle::bail!("no gradient has been captured"),
+        }
+    }
+}
+
+impl Drop for GradCapture {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.remove()
+        }
+    }
+}
+
+/// Returns the per-example gradients of the weight and bias of a linear layer, with shapes
+/// `(batch, out_dim, in_dim)` and `(batch, out_dim)`. `xs` and `grad_ys` are the input of the
+/// layer and the gradient of its output, the dimensions between the batch dimension and the last
+/// one are summed over.
+pub fn linear(layer: &Linear, xs: &Tensor, grad_ys: &Tensor) -> Result<(Tensor, Option<Tensor>)> {
+    let b_sz = xs.dim(0)?;
+    let xs = xs.reshape((b_sz, (), xs.dim(D::Minus1)?))?;
+    let grad_ys = grad_ys.reshape((b_sz, (), grad_ys.dim(D::Minus1)?))?;
+    let grad_w = grad_ys.transpose(1, 2)?.matmul(&xs)?;
+    let grad_b = match layer.bias() {
+        None => None,
+        Some(_) => Some(grad_ys.sum(1)?),
+    };
+    Ok((grad_w, grad_b))
+}
+
+/// Returns the per-example gradients of the embeddings, with shape `(batch, vocab, hidden)`.
+/// `ids` are the indexes used as input of the layer and `grad_ys` the gradient of its output.
+pub fn embedding(layer: &Embedding, ids: &Tensor, grad_ys: &Tensor) -> Result<Tensor> {
+    let b_sz = ids.dim(0)?;
+    let vocab = layer.embeddings().dim(0)?;
+    let ids = ids.reshape((b_sz, (), 1))?;
+    let grad_ys = grad_ys.reshape((b_sz, (), layer.hidden_size()))?;
+    let all_ids = Tensor::arange(0u32, vocab as u32, ids.device())?.to_dtype(ids.dtype())?;
+    let one_hot = ids.broadcast_eq(&all_ids)?.to_dtype(grad_ys.dtype())?;
+    one_hot.transpose(1, 2)?.matmul(&grad_ys)
+}
+
+// Extracts the sliding windows of a convolution along `dim`, the dimension gets replaced with
+// the output positions and the kernel offsets are added as the last dimension.
+fn unfold(
+    xs: &Tensor,
+    dim: usize,
+    kernel_size: usize,
+    out_len: usize,
+    padding: usize,
+    stride: usize,
+    dilation: usize,
+) -> Result<Tensor> {
+    let xs = xs.pad_with_zeros(dim, padding, padding)?;
+    let len = (out_len - 1) * stride + 1;
+    let windows = (0..kernel_size)
+        .map(|i| {
+            xs.narrow(dim, i * dilation, len)?
+                .slice_step(dim, .., stride)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Tensor::stack(&windows, xs.rank())
+}
+
+fn check_groups(groups: usize) -> Result<()> {
+    if groups != 1 {
+        candle::bail!("per-example gradients are not supported for grouped convolutions")
+    }
+    Ok(())
+}
+
+/// Returns the per-example gradients of the weight and bias of a 1d convolution, with shapes
+/// `(batch, out_channels, in_channels, kernel_size)` and `(batch, out_channels)`.
+pub fn conv1d(layer: &Conv1d, xs: &Tensor, grad_ys: &Tensor) -> Result<(Tensor, Option<Tensor>)> {
+    let cfg = layer.config();
+    check_groups(cfg.groups)?;
+    let (c_out, c_in, k) = layer.weight().dims3()?;
+    let (b_sz, _, l_out) = grad_ys.dims3()?;
+    // (b, c_in, l_out, k) -> (b, c_in * k, l_out)
+    let cols = unfold(xs, 2, k, l_out, cfg.padding, cfg.stride, cfg.dilation)?
+        .permute((0, 1, 3, 2))?
+        .reshape((b_sz, c_in * k, l_out))?;
+    let grad_w = grad_ys
+        .contiguous()?
+        .matmul(&cols.t()?)?
+        .reshape((b_sz, c_out, c_in, k))?;
+    let grad_b = match layer.bias() {
+        None => None,
+        Some(_) => Some(grad_ys.sum(2)?),
+    };
+    Ok((grad_w, grad_b))
+}
+
+/// Returns the per-example gradients of the weight and bias of a 2d convolution, with shapes
+/// `(batch, out_channels, in_channels, kernel_h, kernel_w)` and `(batch, out_channels)`.
+pub fn conv2d(layer: &Conv2d, xs: &Tensor, grad_ys: &Tensor) -> Result<(Tensor, Option<Tensor>)> {
+    let cfg = layer.config();
+    check_groups(cfg.groups)?;
+    let (c_out, c_in, k_h, k_w) = layer.weight().dims4()?;
+    let (b_sz, _, h_out, w_out) = grad_ys.dims4()?;
+    let (p, s, d) = (cfg.padding, cfg.stride, cfg.dilation);
+    // (b, c_in, h_out, w_out, k_h, k_w) -> (b, c_in * k_h * k_w, h_out * w_out)
+    let cols = unfold(xs, 2, k_h, h_out, p, s, d)?;
+    let cols = unfold(&cols, 3, k_w, w_out, p, s, d)?
+        .permute((0, 1, 4, 5, 2, 3))?
+        .reshape((b_sz, c_in * k_h * k_w, h_out * w_out))?;
+    let grad_ys = grad_ys.reshape((b_sz, c_out, h_out * w_out))?;
+    let grad_w = grad_ys
+        .matmul(&cols.t()?)?
+        .reshape((b_sz, c_out, c_in, k_h, k_w))?;
+    let grad_b = match layer.bias() {
+        None => None,
+        Some(_) => Some(grad_ys.sum(2)?),
+    };
+    Ok((grad_w, grad_b))
+}
@@ -0,0 +1,483 @@
+//! Pooling Layers.
+//!
+//! The layers support padding and `ceil_mode` like their PyTorch counterparts. When the pooling
+//! only covers the input, the pooling functions from candle-core are used. Otherwise, or when a
+//! gradient is needed and the kernel size differs from the stride, the windows are gathered and
+//! reduced so that the gradient is available. For max pooling, each window has its gradient
+//! routed to a single element, the first maximum.
+//!
+//! The 3D pooling layers, e.g. for video models, pool each frame and then over the depth.
+//!
+//! [`AdaptiveAvgPool2d`] computes a fixed output size whatever the input resolution.
+use candle::{Device, Result, Tensor, D};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pool2dConfig {
+    /// The stride of the pooling window, defaults to the kernel size.
+    pub stride: Option<usize>,
+    /// The implicit padding added on both sides of the input, negative infinity for max pooling
+    /// and zero for average pooling.
+    pub padding: usize,
+    /// Use ceil rather than floor when computing the output shape.
+    pub ceil_mode: bool,
+    /// Include the zero-padding in the averaging. This is not used for max pooling.
+    pub count_include_pad: bool,
+}
+
+impl Default for Pool2dConfig {
+    fn default() -> Self {
+        Self {
+            stride: None,
+            padding: 0,
+            ceil_mode: false,
+            count_include_pad: true,
+        }
+    }
+}
+
+// The geometry of a pooling operation over a `(h, w)` input.
+#[derive(Debug, Clone, Copy)]
+struct Pool2dParams {
+    h: usize,
+    w: usize,
+    kernel_size: usize,
+    stride: usize,
+    padding: usize,
+    h_out: usize,
+    w_out: usize,
+}
+
+fn out_size(size: usize, k: usize, s: usize, p: usize, ceil_mode: bool) -> Result<usize> {
+    if size + 2 * p < k {
+        candle::bail!("pool2d: kernel-size {k} is larger than the padded input size {size}+2*{p}")
+    }
+    let span = size + 2 * p - k;
+    let out = if ceil_mode {
+        span.div_ceil(s) + 1
+    } else {
+        span / s + 1
+    };
+    // The last window has to start within the input or the left padding.
+    if ceil_mode && (out - 1) * s >= size + p {
+        Ok(out - 1)
+    } else {
+        Ok(out)
+    }
+}
+
+// For the window at output position `o` along a dimension of size `size`, the number of
+// elements of the window within the padded input and within the input itself.
+fn window_sizes(o: usize, size: usize, k: usize, s: usize, p: usize) -> (usize, usize) {
+    let start = o * s;
+    let end = usize::min(start + k, size + 2 * p);
+    let pool_size = end - start;
+    let start = start.max(p) - p;
+    let end = usize::min(end.max(p) - p, size);
+    (pool_size, end.saturating_sub(start))
+}
+
+// Pads `xs` with `value` along `dim`.
+fn pad_with(xs: &Tensor, dim: usize, left: usize, right: usize, value: f32) -> Result<Tensor> {
+    if left == 0 && right == 0 {
+        return Ok(xs.clone());
+    }
+    let mut dims = xs.dims().to_vec();
+    let mut parts = vec![];
+    if left > 0 {
+        dims[dim] = left;
+        parts.push(Tensor::full(value, dims.as_slice(), xs.device())?.to_dtype(xs.dtype())?)
+    }
+    parts.push(xs.clone());
+    if right > 0 {
+        dims[dim] = right;
+        parts.push(Tensor::full(value, dims.as_slice(), xs.device())?.to_dtype(xs.dtype())?)
+    }
+    Tensor::cat(&parts, dim)
+}
+
+impl Pool2dParams {
+    fn new(xs: &Tensor, kernel_size: usize, cfg: &Pool2dConfig) -> Result<Self> {
+        let (_b, _c, h, w) = xs.dims4()?;
+        let stride = cfg.stride.unwrap_or(kernel_size);
+        let padding = cfg.padding;
+        if kernel_size == 0 || stride == 0 {
+            candle::bail!("pool2d: kernel-size and stride have to be positive")
+        }
+        if 2 * padding > kernel_size {
+            candle::bail!(
+                "pool2d: padding {padding} should be at most half the kernel size {kernel_size}"
+            )
+        }
+        Ok(Self {
+            h,
+            w,
+            kernel_size,
+            stride,
+            padding,
+            h_out: out_size(h, kernel_size, stride, padding, cfg.ceil_mode)?,
+            w_out: out_size(w, kernel_size, stride, padding, cfg.ceil_mode)?,
+        })
+    }
+
+    // Whether the windows only cover the input, in which case the candle-core ops can be used.
+    fn covers_input_only(&self) -> bool {
+        self.padding == 0
+            && (self.h_out - 1) * self.stride + self.kernel_size <= self.h
+            && (self.w_out - 1) * self.stride + self.kernel_size <= self.w
+    }
+
+    // The size of the padded input needed for all the windows to be in bounds.
+    fn padded_dims(&self) -> (usize, usize) {
+        let p = self.padding;
+        let hp = ((self.h_out - 1) * self.stride + self.kernel_size).max(self.h + 2 * p);
+        let wp = ((self.w_out - 1) * self.stride + self.kernel_size).max(self.w + 2 * p);
+        (hp, wp)
+    }
+
+    // For each output position and window element, the index in the flattened padded input and
+    // in the flattened unpadded input (0 for padding positions).
+    fn window_indexes(&self) -> (Vec<u32>, Vec<u32>) {
+        let (_, wp) = self.padded_dims();
+        let (k, s, p) = (self.kernel_size, self.stride, self.padding);
+        let n = self.h_out * self.w_out * k * k;
+        let mut padded = Vec::with_capacity(n);
+        let mut unpadded = Vec::with_capacity(n);
+        for oi in 0..self.h_out {
+            for oj in 0..self.w_out {
+                for ki in 0..k {
+                    for kj in 0..k {
+                        let (r, c) = (oi * s + ki, oj * s + kj);
+                        padded.push((r * wp + c) as u32);
+                        let in_bounds = r >= p && c >= p && r - p < self.h && c - p < self.w;
+                        let idx = if in_bounds {
+                            (r - p) * self.w + c - p
+                        } else {
+                            0
+                        };
+                        unpadded.push(idx as u32)
+                    }
+                }
+            }
+        }
+        (padded, unpadded)
+    }
+
+    // The averaging divisor for each output position.
+    fn divisors(&self, count_include_pad: bool) -> Vec<f32> {
+        let (k, s, p) = (self.kernel_size, self.stride, self.padding);
+        let range = |o: usize, size: usize| window_sizes(o, size, k, s, p);
+        let mut divisors = Vec::with_capacity(self.h_out * self.w_out);
+        for oi in 0..self.h_out {
+            let (ph, vh) = range(oi, self.h);
+            for oj in 0..self.w_out {
+                let (pw, vw) = range(oj, self.w);
+                let d = if count_include_pad { ph * pw } else { vh * vw };
+                divisors.push(d as f32)
+            }
+        }
+        divisors
+    }
+
+    // Pads the input with `value` and gathers the windows, returning a tensor of shape
+    // `(b, c, h_out * w_out, kernel_size * kernel_size)`.
+    fn windows(&self, xs: &Tensor, value: f32, indexes: &[u32]) -> Result<Tensor> {
+        let (b, c, h, w) = xs.dims4()?;
+        let (hp, wp) = self.padded_dims();
+        let p = self.padding;
+        let xs = pad_with(xs, 2, p, hp - h - p, value)?;
+        let xs = pad_with(&xs, 3, p, wp - w - p, value)?;
+        let k2 = self.kernel_size * self.kernel_size;
+        let indexes = Tensor::new(indexes, xs.device())?;
+        xs.flatten_from(2)?
+            .index_select(&indexes, 2)?
+            .reshape((b, c, self.h_out * self.w_out, k2))
+    }
+}
+
+/// 2D max pooling, returning the pooled values and, for each of them, the index of the maximum in
+/// the flattened `(h, w)` plane of the input, similar to `return_indices` in PyTorch.
+pub fn max_pool2d_with_indices(
+    xs: &Tensor,
+    kernel_size: usize,
+    cfg: &Pool2dConfig,
+) -> Result<(Tensor, Tensor)> {
+    let params = Pool2dParams::new(xs, kernel_size, cfg)?;
+    let (b, c, _, _) = xs.dims4()?;
+    let (padded, unpadded) = params.window_indexes();
+    let windows = params.windows(xs, f32::NEG_INFINITY, &padded)?;
+    let argmax = windows.argmax_keepdim(D::Minus1)?;
+    let shape = (b, c, params.h_out, params.w_out);
+    let ys = windows.gather(&argmax, D::Minus1)?.reshape(shape)?;
+    let k2 = kernel_size * kernel_size;
+    let unpadded = Tensor::from_vec(
+        unpadded,
+        (1, 1, params.h_out * params.w_out, k2),
+        xs.device(),
+    )?
+    .broadcast_as(windows.shape())?
+    .contiguous()?;
+    let indexes = unpadded.gather(&argmax, D::Minus1)?.reshape(shape)?;
+    Ok((ys, indexes))
+}
+
+/// 2D max pooling over an input of shape `(b, c, h, w)`.
+pub fn max_pool2d(xs: &Tensor, kernel_size: usize, cfg: &Pool2dConfig) -> Result<Tensor> {
+    let params = Pool2dParams::new(xs, kernel_size, cfg)?;
+    // The candle-core max-pool gradient is split between the ties, whereas the gathered version
+    // routes it to the first maximum.
+    if params.covers_input_only() && !xs.track_op() {
+        xs.max_pool2d_with_stride(kernel_size, params.stride)
+    } else {
+        Ok(max_pool2d_with_indices(xs, kernel_size, cfg)?.0)
+    }
+}
+
+/// 2D average pooling over an input of shape `(b, c, h, w)`.
+pub fn avg_pool2d(xs: &Tensor, kernel_size: usize, cfg: &Pool2dConfig) -> Result<Tensor> {
+    let params = Pool2dParams::new(xs, kernel_size, cfg)?;
+    let has_bwd = !xs.track_op() || kernel_size == params.stride;
+    if params.covers_input_only() && has_bwd {
+        return xs.avg_pool2d_with_stride(kernel_size, params.stride);
+    }
+    let (b, c, _, _) = xs.dims4()?;
+    let (padded, _) = params.window_indexes();
+    let windows = params.windows(xs, 0., &padded)?;
+    let divisors = params.divisors(cfg.count_include_pad);
+    let divisors = Tensor::from_vec(divisors, (params.h_out, params.w_out), &Device::Cpu)?
+        .to_device(xs.device())?
+        .to_dtype(xs.dtype())?;
+    let shape = (b, c, params.h_out, params.w_out);
+    windows
+        .sum(D::Minus1)?
+        .reshape(shape)?
+        .broadcast_div(&divisors)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MaxPool2d {
+    kernel_size: usize,
+    config: Pool2dConfig,
+}
+
+impl MaxPool2d {
+    pub fn new(kernel_size: usize, config: Pool2dConfig) -> Self {
+        Self {
+            kernel_size,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Pool2dConfig {
+        &self.config
+    }
+
+    /// Returns the pooled values together with the indexes of the maximums, see
+    /// [`max_pool2d_with_indices`].
+    pub fn forward_with_indices(&self, xs: &Tensor) -> Result<(Tensor, Tensor)> {
+        max_pool2d_with_indices(xs, self.kernel_size, &self.config)
+    }
+}
+
+impl crate::Module for MaxPool2d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        max_pool2d(xs, self.kernel_size, &self.config)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AvgPool2d {
+    kernel_size: usize,
+    config: Pool2dConfig,
+}
+
+impl AvgPool2d {
+    pub fn new(kernel_size: usize, config: Pool2dConfig) -> Self {
+        Self {
+            kernel_size,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Pool2dConfig {
+        &self.config
+    }
+}
+
+impl crate::Module for AvgPool2d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        avg_pool2d(xs, self.kernel_size, &self.config)
+    }
+}
+
+/// The 3D pooling layers use the same options as the 2D ones, applied to all three dimensions.
+pub type Pool3dConfig = Pool2dConfig;
+
+// Pools an input of shape `(b, c, d, n)` over `d`, the windows being reduced by `max` or
+// averaged.
+fn pool_depth(xs: &Tensor, kernel_size: usize, cfg: &Pool3dConfig, max: bool) -> Result<Tensor> {
+    let (b, c, d, n) = xs.dims4()?;
+    let (k, p) = (kernel_size, cfg.padding);
+    let s = cfg.stride.unwrap_or(kernel_size);
+    let d_out = out_size(d, k, s, p, cfg.ceil_mode)?;
+    let dp = ((d_out - 1) * s + k).max(d + 2 * p);
+    let value = if max { f32::NEG_INFINITY } else { 0. };
+    let xs = pad_with(xs, 2, p, dp - d - p, value)?;
+    let indexes: Vec<u32> = (0..d_out)
+        .flat_map(|o| (0..k).map(move |j| (o * s + j) as u32))
+        .collect();
+    let indexes = Tensor::new(indexes, xs.device())?;
+    let windows = xs.index_select(&indexes, 2)?.reshape((b, c, d_out, k, n))?;
+    if max {
+        let argmax = windows.argmax_keepdim(3)?;
+        windows.gather(&argmax, 3)?.squeeze(3)
+    } else {
+        let divisors: Vec<f32> = (0..d_out)
+            .map(|o| {
+                let (pool_size, valid) = window_sizes(o, d, k, s, p);
+                let d = if cfg.count_include_pad {
+                    pool_size
+                } else {
+                    valid
+                };
+                d as f32
+            })
+            .collect();
+        let divisors = Tensor::from_vec(divisors, (d_out, 1), &Device::Cpu)?
+            .to_device(xs.device())?
+            .to_dtype(xs.dtype())?;
+        windows.sum(3)?.broadcast_div(&divisors)
+    }
+}
+
+// The pooling windows and divisors are separable, so 3D pooling is a 2D pooling of each frame
+// followed by a pooling over the depth.
+fn pool3d(xs: &Tensor, kernel_size: usize, cfg: &Pool3dConfig, max: bool) -> Result<Tensor> {
+    let (b, c, d, h, w) = xs.dims5()?;
+    let xs = xs.reshape((b, c * d, h, w))?;
+    let xs = if max {
+        max_pool2d(&xs, kernel_size, cfg)?
+    } else {
+        avg_pool2d(&xs, kernel_size, cfg)?
+    };
+    let (_, _, h_out, w_out) = xs.dims4()?;
+    let xs = xs.reshape((b, c, d, h_out * w_out))?;
+    let xs = pool_depth(&xs, kernel_size, cfg, max)?;
+    let (_, _, d_out, _) = xs.dims4()?;
+    xs.reshape((b, c, d_out, h_out, w_out))
+}
+
+/// 3D max pooling over an input of shape `(b, c, d, h, w)`.
+pub fn max_pool3d(xs: &Tensor, kernel_size: usize, cfg: &Pool3dConfig) -> Result<Tensor> {
+    pool3d(xs, kernel_size, cfg, true)
+}
+
+/// 3D average pooling over an input of shape `(b, c, d, h, w)`.
+pub fn avg_pool3d(xs: &Tensor, kernel_size: usize, cfg: &Pool3dConfig) -> Result<Tensor> {
+    pool3d(xs, kernel_size, cfg, false)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MaxPool3d {
+    kernel_size: usize,
+    config: Pool3dConfig,
+}
+
+impl MaxPool3d {
+    pub fn new(kernel_size: usize, config: Pool3dConfig) -> Self {
+        Self {
+            kernel_size,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Pool3dConfig {
+        &self.config
+    }
+}
+
+impl crate::Module for MaxPool3d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        max_pool3d(xs, self.kernel_size, &self.config)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AvgPool3d {
+    kernel_size: usize,
+    config: Pool3dConfig,
+}
+
+impl AvgPool3d {
+    pub fn new(kernel_size: usize, config: Pool3dConfig) -> Self {
+        Self {
+            kernel_size,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Pool3dConfig {
+        &self.config
+    }
+}
+
+impl crate::Module for AvgPool3d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        avg_pool3d(xs, self.kernel_size, &self.config)
+    }
+}
+
+// The `(out_size, size)` averaging matrix for adaptive pooling, output `i` averaging the inputs
+// from `floor(i * size / out_size)` to `ceil((i + 1) * size / out_size)`.
+fn adaptive_pool_matrix(size: usize, out_size: usize, device: &Device) -> Result<Tensor> {
+    let mut data = vec![0f32; out_size * size];
+    for i in 0..out_size {
+        let start = i * size / out_size;
+        let end = ((i + 1) * size).div_ceil(out_size);
+        for j in start..end {
+            data[i * size + j] = 1. / (end - start) as f32
+        }
+    }
+    Tensor::from_vec(data, (out_size, size), device)
+}
+
+/// 2D adaptive average pooling over an input of shape `(b, c, h, w)`, the output having shape
+/// `(b, c, output_size.0, output_size.1)`.
+pub fn adaptive_avg_pool2d<T: candle::ToUsize2>(xs: &Tensor, output_size: T) -> Result<Tensor> {
+    let (h_out, w_out) = output_size.to_usize2();
+    let (_b, _c, h, w) = xs.dims4()?;
+    if h_out == 0 || w_out == 0 {
+        candle::bail!("adaptive-avg-pool2d: invalid output size ({h_out}, {w_out})")
+    }
+    if h_out == 1 && w_out == 1 {
+        xs.mean_keepdim(D::Minus1)?.mean_keepdim(D::Minus2)
+    } else if h % h_out == 0 && w % w_out == 0 && h / h_out == w / w_out {
+        xs.avg_pool2d(h / h_out)
+    } else {
+        let a_h = adaptive_pool_matrix(h, h_out, xs.device())?.to_dtype(xs.dtype())?;
+        let a_w = adaptive_pool_matrix(w, w_out, xs.device())?.to_dtype(xs.dtype())?;
+        a_h.broadcast_matmul(xs)?.broadcast_matmul(&a_w.t()?)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveAvgPool2d {
+    output_size: (usize, usize),
+}
+
+impl AdaptiveAvgPool2d {
+    pub fn new<T: candle::ToUsize2>(output_size: T) -> Self {
+        Self {
+            output_size: output_size.to_usize2(),
+        }
+    }
+
+    pub fn output_size(&self) -> (usize, usize) {
+        self.output_size
+    }
+}
+
+impl crate::Module for AdaptiveAvgPool2d {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        adaptive_avg_pool2d(xs, self.output_size)
+    }
+}
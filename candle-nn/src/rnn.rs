@@ -46,6 +46,47 @@ pub trait RNN {
     fn states_to_tensor(&self, states: &[Self::State]) -> Result<Tensor>;
 }
 
+/// The direction in which a recurrent layer processes its input sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Forward,
+    /// The backward layers of bidirectional networks, their weights use the `_reverse` suffix.
+    /// The layer itself does not reverse the sequence when using [`RNN::seq`].
+    Backward,
+}
+
+impl Direction {
+    fn suffix(&self) -> &'static str {
+        match self {
+            Self::Forward => "",
+            Self::Backward => "_reverse",
+        }
+    }
+}
+
+// Applies `rnn` over the sequence dimension of `xs` in the given direction, the states are
+// returned in processing order.
+fn seq_with_direction<R: RNN>(
+    rnn: &R,
+    xs: &Tensor,
+    init_state: &R::State,
+    direction: Direction,
+) -> Result<Vec<R::State>> {
+    let (_b_size, seq_len, _features) = xs.dims3()?;
+    let mut states: Vec<R::State> = Vec::with_capacity(seq_len);
+    for i in 0..seq_len {
+        let seq_index = match direction {
+            Direction::Forward => i,
+            Direction::Backward => seq_len - 1 - i,
+        };
+        let input = xs.i((.., seq_index, ..))?.contiguous()?;
+        let state = rnn.step(&input, states.last().unwrap_or(init_state))?;
+        states.push(state)
+    }
+    Ok(states)
+}
+
 /// The state for a LSTM network, this contains two tensors.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
@@ -55,6 +96,10 @@ pub struct LSTMState {
 }
 
 impl LSTMState {
+    pub fn new(h: Tensor, c: Tensor) -> Self {
+        Self { h, c }
+    }
+
     /// The hidden state vector, which is also the output of the LSTM.
     pub fn h(&self) -> &Tensor {
         &self.h
@@ -74,6 +119,7 @@ pub struct LSTMConfig {
     pub b_ih_init: Option<super::Init>,
     pub b_hh_init: Option<super::Init>,
     pub layer_idx: usize,
+    pub direction: Direction,
 }
 
 impl Default for LSTMConfig {
@@ -84,6 +130,7 @@ impl Default for LSTMConfig {
             b_ih_init: Some(super::Init::Const(0.)),
             b_hh_init: Some(super::Init::Const(0.)),
             layer_idx: 0,
+            direction: Direction::Forward,
         }
     }
 }
@@ -96,6 +143,7 @@ impl LSTMConfig {
             b_ih_init: None,
             b_hh_init: None,
             layer_idx: 0,
+            direction: Direction::Forward,
         }
     }
 }
@@ -124,26 +172,31 @@ pub fn lstm(
     vb: crate::VarBuilder,
 ) -> Result<LSTM> {
     let layer_idx = config.layer_idx;
+    let suffix = config.direction.suffix();
     let w_ih = vb.get_with_hints(
         (4 * hidden_dim, in_dim),
-        &format!("weight_ih_l{layer_idx}"), // Only a single layer is supported.
+        &format!("weight_ih_l{layer_idx}{suffix}"),
         config.w_ih_init,
     )?;
     let w_hh = vb.get_with_hints(
         (4 * hidden_dim, hidden_dim),
-        &format!("weight_hh_l{layer_idx}"), // Only a single layer is supported.
+        &format!("weight_hh_l{layer_idx}{suffix}"),
         config.w_hh_init,
     )?;
     let b_ih = match config.b_ih_init {
-        Some(init) => {
-            Some(vb.get_with_hints(4 * hidden_dim, &format!("bias_ih_l{layer_idx}"), init)?)
-        }
+        Some(init) => Some(vb.get_with_hints(
+            4 * hidden_dim,
+            &format!("bias_ih_l{layer_idx}{suffix}"),
+            init,
+        )?),
         None => None,
     };
     let b_hh = match config.b_hh_init {
-        Some(init) => {
-            Some(vb.get_with_hints(4 * hidden_dim, &format!("bias_hh_l{layer_idx}"), init)?)
-        }
+        Some(init) => Some(vb.get_with_hints(
+            4 * hidden_dim,
+            &format!("bias_hh_l{layer_idx}{suffix}"),
+            init,
+        )?),
         None => None,
     };
     Ok(LSTM {
@@ -158,6 +211,16 @@ pub fn lstm(
     })
 }
 
+impl LSTM {
+    fn suffix(&self) -> String {
+        format!(
+            "_l{}{}",
+            self.config.layer_idx,
+            self.config.direction.suffix()
+        )
+    }
+}
+
 impl RNN for LSTM {
     type State = LSTMState;
 
@@ -201,6 +264,120 @@ impl RNN for LSTM {
     }
 }
 
+/// A multi-layer LSTM, optionally bidirectional, similar to PyTorch `nn.LSTM` with
+/// `batch_first=True`. The input of each layer is the output of the previous one, with the
+/// outputs of both directions concatenated for bidirectional networks.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug)]
+pub struct MultiLayerLSTM {
+    // The layers for each depth, with the backward layer last for bidirectional networks.
+    layers: Vec<Vec<LSTM>>,
+    hidden_dim: usize,
+}
+
+/// Creates a multi-layer LSTM, the weights use the PyTorch naming, e.g. `weight_ih_l1` or
+/// `weight_hh_l0_reverse`. The `layer_idx` and `direction` fields of `config` are ignored.
+pub fn multi_layer_lstm(
+    in_dim: usize,
+    hidden_dim: usize,
+    num_layers: usize,
+    bidirectional: bool,
+    config: LSTMConfig,
+    vb: crate::VarBuilder,
+) -> Result<MultiLayerLSTM> {
+    let directions: &[Direction] = if bidirectional {
+        &[Direction::Forward, Direction::Backward]
+    } else {
+        &[Direction::Forward]
+    };
+    let mut layers = Vec::with_capacity(num_layers);
+    for layer_idx in 0..num_layers {
+        let in_dim = if layer_idx == 0 {
+            in_dim
+        } else {
+            hidden_dim * directions.len()
+        };
+        let layer = directions
+            .iter()
+            .map(|&direction| {
+                let config = LSTMConfig {
+                    layer_idx,
+                    direction,
+                    ..config
+                };
+                lstm(in_dim, hidden_dim, config, vb.clone())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        layers.push(layer)
+    }
+    Ok(MultiLayerLSTM { layers, hidden_dim })
+}
+
+impl MultiLayerLSTM {
+    pub fn layers(&self) -> &[Vec<LSTM>] {
+        &self.layers
+    }
+
+    fn num_states(&self) -> usize {
+        self.layers.iter().map(|l| l.len()).sum()
+    }
+
+    /// A zero state, the `h` and `c` tensors have shape
+    /// `(num_layers * num_directions, batch_dim, hidden_dim)`.
+    pub fn zero_state(&self, batch_dim: usize) -> Result<LSTMState> {
+        let (device, dtype) = match self.layers.first().and_then(|l| l.first()) {
+            None => candle::bail!("lstm without layers"),
+            Some(l) => (&l.device, l.dtype),
+        };
+        let zeros = Tensor::zeros(
+            (self.num_states(), batch_dim, self.hidden_dim),
+            dtype,
+            device,
+        )?;
+        Ok(LSTMState::new(zeros.clone(), zeros))
+    }
+
+    /// Applies the network on an input of shape `(batch_size, seq_len, features)` starting from a
+    /// zero state, see [`Self::forward_init`].
+    pub fn forward(&self, xs: &Tensor) -> Result<(Tensor, LSTMState)> {
+        let state = self.zero_state(xs.dim(0)?)?;
+        self.forward_init(xs, &state)
+    }
+
+    /// Applies the network on an input of shape `(batch_size, seq_len, features)`. This returns
+    /// the output of the last layer with shape `(batch_size, seq_len, num_directions * hidden_dim)`
+    /// and the final state of each layer and direction with the same layout as `init_state`, i.e.
+    /// `(num_layers * num_directions, batch_size, hidden_dim)`.
+    pub fn forward_init(&self, xs: &Tensor, init_state: &LSTMState) -> Result<(Tensor, LSTMState)> {
+        if xs.dim(1)? == 0 {
+            candle::bail!("lstm input has an empty sequence {:?}", xs.shape())
+        }
+        let mut xs = xs.clone();
+        let mut hs = Vec::with_capacity(self.num_states());
+        let mut cs = Vec::with_capacity(self.num_states());
+        for layer in self.layers.iter() {
+            let mut outputs = Vec::with_capacity(layer.len());
+            for lstm in layer.iter() {
+                let idx = hs.len();
+                let init_state = LSTMState::new(init_state.h.get(idx)?, init_state.c.get(idx)?);
+                let direction = lstm.config.direction;
+                let mut states = seq_with_direction(lstm, &xs, &init_state, direction)?;
+                // The sequence is not empty so there is a last state.
+                let last = &states[states.len() - 1];
+                hs.push(last.h.clone());
+                cs.push(last.c.clone());
+                if direction == Direction::Backward {
+                    states.reverse()
+                }
+                outputs.push(lstm.states_to_tensor(&states)?)
+            }
+            xs = Tensor::cat(&outputs, 2)?;
+        }
+        let state = LSTMState::new(Tensor::stack(&hs, 0)?, Tensor::stack(&cs, 0)?);
+        Ok((xs, state))
+    }
+}
+
 /// The state for a GRU network, this contains a single tensor.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
@@ -209,7 +386,11 @@ pub struct GRUState {
 }
 
 impl GRUState {
-    /// The hidden state vector, which is also the output of the LSTM.
+    pub fn new(h: Tensor) -> Self {
+        Self { h }
+    }
+
+    /// The hidden state vector, which is also the output of the GRU.
     pub fn h(&self) -> &Tensor {
         &self.h
     }
@@ -222,6 +403,8 @@ pub struct GRUConfig {
     pub w_hh_init: super::Init,
     pub b_ih_init: Option<super::Init>,
     pub b_hh_init: Option<super::Init>,
+    pub layer_idx: usize,
+    pub direction: Direction,
 }
 
 impl Default for GRUConfig {
@@ -231,6 +414,8 @@ impl Default for GRUConfig {
             w_hh_init: super::init::DEFAULT_KAIMING_UNIFORM,
             b_ih_init: Some(super::Init::Const(0.)),
             b_hh_init: Some(super::Init::Const(0.)),
+            layer_idx: 0,
+            direction: Direction::Forward,
         }
     }
 }
@@ -242,6 +427,8 @@ impl GRUConfig {
             w_hh_init: super::init::DEFAULT_KAIMING_UNIFORM,
             b_ih_init: None,
             b_hh_init: None,
+            layer_idx: 0,
+            direction: Direction::Forward,
         }
     }
 }
@@ -258,6 +445,8 @@ pub struct GRU {
     b_hh: Option<Tensor>,
     hidden_dim: usize,
     config: GRUConfig,
+    // The suffix of the weight names, e.g. `_l0` or `_l1_reverse`.
+    suffix: String,
     device: Device,
     dtype: DType,
 }
@@ -268,23 +457,46 @@ pub fn gru(
     hidden_dim: usize,
     config: GRUConfig,
     vb: crate::VarBuilder,
+) -> Result<GRU> {
+    let suffix = format!("_l{}{}", config.layer_idx, config.direction.suffix());
+    gru_with_suffix(in_dim, hidden_dim, config, &suffix, vb)
+}
+
+/// Creates a GRU layer using the PyTorch `nn.GRUCell` weight names, i.e. `weight_ih` rather than
+/// `weight_ih_l0`. The cell is meant to be applied step by step via [`RNN::step`], e.g. in custom
+/// decoding loops. The `layer_idx` and `direction` fields of `config` are ignored.
+pub fn gru_cell(
+    in_dim: usize,
+    hidden_dim: usize,
+    config: GRUConfig,
+    vb: crate::VarBuilder,
+) -> Result<GRU> {
+    gru_with_suffix(in_dim, hidden_dim, config, "", vb)
+}
+
+fn gru_with_suffix(
+    in_dim: usize,
+    hidden_dim: usize,
+    config: GRUConfig,
+    suffix: &str,
+    vb: crate::VarBuilder,
 ) -> Result<GRU> {
     let w_ih = vb.get_with_hints(
         (3 * hidden_dim, in_dim),
-        "weight_ih_l0", // Only a single layer is supported.
+        &format!("weight_ih{suffix}"),
         config.w_ih_init,
     )?;
     let w_hh = vb.get_with_hints(
         (3 * hidden_dim, hidden_dim),
-        "weight_hh_l0", // Only a single layer is supported.
+        &format!("weight_hh{suffix}"),
         config.w_hh_init,
     )?;
     let b_ih = match config.b_ih_init {
-        Some(init) => Some(vb.get_with_hints(3 * hidden_dim, "bias_ih_l0", init)?),
+        Some(init) => Some(vb.get_with_hints(3 * hidden_dim, &format!("bias_ih{suffix}"), init)?),
         None => None,
     };
     let b_hh = match config.b_hh_init {
-        Some(init) => Some(vb.get_with_hints(3 * hidden_dim, "bias_hh_l0", init)?),
+        Some(init) => Some(vb.get_with_hints(3 * hidden_dim, &format!("bias_hh{suffix}"), init)?),
         None => None,
     };
     Ok(GRU {
@@ -294,6 +506,7 @@ pub fn gru(
         b_hh,
         hidden_dim,
         config,
+        suffix: suffix.to_string(),
         device: vb.device().clone(),
         dtype: vb.dtype(),
     })
@@ -331,6 +544,181 @@ impl RNN for GRU {
 
     fn states_to_tensor(&self, states: &[Self::State]) -> Result<Tensor> {
         let states = states.iter().map(|s| s.h.clone()).collect::<Vec<_>>();
-        Tensor::cat(&states, 1)
+        Tensor::stack(&states, 1)
     }
 }
+
+// The tensors are named as when loading the layer, e.g. `weight_ih_l0` or `bias_hh_l1_reverse`.
+macro_rules! impl_rnn_parameters {
+    ($($t:ty),*) => {
+        $(
+            impl crate::Parameters for $t {
+                fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+                    let suffix = self.suffix();
+                    f(&format!("weight_ih{suffix}"), &self.w_ih);
+                    f(&format!("weight_hh{suffix}"), &self.w_hh);
+                    crate::parameters::visit_opt(&format!("bias_ih{suffix}"), &self.b_ih, f);
+                    crate::parameters::visit_opt(&format!("bias_hh{suffix}"), &self.b_hh, f)
+                }
+
+                fn apply_parameters(
+                    &mut self,
+                    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+                ) -> Result<()> {
+                    use crate::parameters::{apply_opt, apply_tensor};
+                    let suffix = self.suffix();
+                    apply_tensor(&format!("weight_ih{suffix}"), &mut self.w_ih, f)?;
+                    apply_tensor(&format!("weight_hh{suffix}"), &mut self.w_hh, f)?;
+                    apply_opt(&format!("bias_ih{suffix}"), &mut self.b_ih, f)?;
+                    apply_opt(&format!("bias_hh{suffix}"), &mut self.b_hh, f)?;
+                    // The zero states get created on the device and with the dtype of the weights.
+                    self.device = self.w_ih.device().clone();
+                    self.dtype = self.w_ih.dtype();
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_rnn_parameters!(LSTM, GRU);
+
+impl GRU {
+    fn suffix(&self) -> String {
+        self.suffix.clone()
+    }
+}
+
+/// A multi-layer GRU, optionally bidirectional, similar to PyTorch `nn.GRU` with
+/// `batch_first=True`, see [`MultiLayerLSTM`].
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug)]
+pub struct MultiLayerGRU {
+    // The layers for each depth, with the backward layer last for bidirectional networks.
+    layers: Vec<Vec<GRU>>,
+    hidden_dim: usize,
+}
+
+/// Creates a multi-layer GRU, the weights use the PyTorch naming, e.g. `weight_ih_l1` or
+/// `weight_hh_l0_reverse`. The `layer_idx` and `direction` fields of `config` are ignored.
+pub fn multi_layer_gru(
+    in_dim: usize,
+    hidden_dim: usize,
+    num_layers: usize,
+    bidirectional: bool,
+    config: GRUConfig,
+    vb: crate::VarBuilder,
+) -> Result<MultiLayerGRU> {
+    let directions: &[Direction] = if bidirectional {
+        &[Direction::Forward, Direction::Backward]
+    } else {
+        &[Direction::Forward]
+    };
+    let mut layers = Vec::with_capacity(num_layers);
+    for layer_idx in 0..num_layers {
+        let in_dim = if layer_idx == 0 {
+            in_dim
+        } else {
+            hidden_dim * directions.len()
+        };
+        let layer = directions
+            .iter()
+            .map(|&direction| {
+                let config = GRUConfig {
+                    layer_idx,
+                    direction,
+                    ..config
+                };
+                gru(in_dim, hidden_dim, config, vb.clone())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        layers.push(layer)
+    }
+    Ok(MultiLayerGRU { layers, hidden_dim })
+}
+
+impl MultiLayerGRU {
+    pub fn layers(&self) -> &[Vec<GRU>] {
+        &self.layers
+    }
+
+    fn num_states(&self) -> usize {
+        self.layers.iter().map(|l| l.len()).sum()
+    }
+
+    /// A zero state, the `h` tensor has shape `(num_layers * num_directions, batch_dim, hidden_dim)`.
+    pub fn zero_state(&self, batch_dim: usize) -> Result<GRUState> {
+        let (device, dtype) = match self.layers.first().and_then(|l| l.first()) {
+            None => candle::bail!("gru without layers"),
+            Some(l) => (&l.device, l.dtype),
+        };
+        let h = Tensor::zeros(
+            (self.num_states(), batch_dim, self.hidden_dim),
+            dtype,
+            device,
+        )?;
+        Ok(GRUState::new(h))
+    }
+
+    /// Applies the network on an input of shape `(batch_size, seq_len, features)` starting from a
+    /// zero state, see [`Self::forward_init`].
+    pub fn forward(&self, xs: &Tensor) -> Result<(Tensor, GRUState)> {
+        let state = self.zero_state(xs.dim(0)?)?;
+        self.forward_init(xs, &state)
+    }
+
+    /// Applies the network on an input of shape `(batch_size, seq_len, features)`. This returns
+    /// the output of the last layer with shape `(batch_size, seq_len, num_directions * hidden_dim)`
+    /// and the final state of each layer and direction with the same layout as `init_state`, i.e.
+    /// `(num_layers * num_directions, batch_size, hidden_dim)`.
+    pub fn forward_init(&self, xs: &Tensor, init_state: &GRUState) -> Result<(Tensor, GRUState)> {
+        if xs.dim(1)? == 0 {
+            candle::bail!("gru input has an empty sequence {:?}", xs.shape())
+        }
+        let mut xs = xs.clone();
+        let mut hs = Vec::with_capacity(self.num_states());
+        for layer in self.layers.iter() {
+            let mut outputs = Vec::with_capacity(layer.len());
+            for gru in layer.iter() {
+                let init_state = GRUState::new(init_state.h.get(hs.len())?);
+                let direction = gru.config.direction;
+                let mut states = seq_with_direction(gru, &xs, &init_state, direction)?;
+                // The sequence is not empty so there is a last state.
+                hs.push(states[states.len() - 1].h.clone());
+                if direction == Direction::Backward {
+                    states.reverse()
+                }
+                outputs.push(gru.states_to_tensor(&states)?)
+            }
+            xs = Tensor::cat(&outputs, 2)?;
+        }
+        Ok((xs, GRUState::new(Tensor::stack(&hs, 0)?)))
+    }
+}
+
+// The layers are not prefixed, their weight names already include the layer index.
+macro_rules! impl_multi_layer_parameters {
+    ($($t:ty),*) => {
+        $(
+            impl crate::Parameters for $t {
+                fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+                    for layer in self.layers.iter().flatten() {
+                        layer.visit_parameters(f)
+                    }
+                }
+
+                fn apply_parameters(
+                    &mut self,
+                    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+                ) -> Result<()> {
+                    for layer in self.layers.iter_mut().flatten() {
+                        layer.apply_parameters(f)?
+                    }
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_multi_layer_parameters!(MultiLayerLSTM, MultiLayerGRU);
@@ -728,3 +728,194 @@ pub fn rope_thd(xs: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<Tensor> {
     }
     xs.apply_op3_no_bwd(cos, sin, &RotaryEmbThd)
 }
+
+/// Scaling of the rotary embedding frequencies, used to extend the context length of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RopeScaling {
+    #[default]
+    None,
+    /// Position interpolation, the positions are divided by `factor`.
+    Linear { factor: f64 },
+    /// NTK-aware scaling, the base is scaled so that the lowest frequency is divided by `factor`.
+    Ntk { factor: f64 },
+    /// YaRN scaling, <https://arxiv.org/abs/2309.00071>.
+    Yarn {
+        factor: f64,
+        original_max_position_embeddings: usize,
+        beta_fast: f64,
+        beta_slow: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotaryEmbeddingConfig {
+    pub head_dim: usize,
+    /// The number of dimensions of each head that get rotated, the remaining ones are left
+    /// unchanged. Defaults to `head_dim`.
+    pub rotary_dim: Option<usize>,
+    pub base: f64,
+    pub max_position_embeddings: usize,
+    pub scaling: RopeScaling,
+    /// Whether the rotated pairs are interleaved, see [`rope_i`], rather than split in two
+    /// halves, see [`rope`].
+    pub interleaved: bool,
+}
+
+impl RotaryEmbeddingConfig {
+    pub fn new(head_dim: usize, base: f64, max_position_embeddings: usize) -> Self {
+        Self {
+            head_dim,
+            rotary_dim: None,
+            base,
+            max_position_embeddings,
+            scaling: RopeScaling::None,
+            interleaved: false,
+        }
+    }
+}
+
+// The dimension for which the given number of rotations is reached over the original context.
+fn yarn_correction_dim(num_rotations: f64, dim: usize, base: f64, max_pos: usize) -> f64 {
+    (dim as f64 * (max_pos as f64 / (num_rotations * 2. * std::f64::consts::PI)).ln())
+        / (2. * base.ln())
+}
+
+/// Precomputed cos/sin tables for rotary embeddings.
+#[derive(Debug, Clone)]
+pub struct RotaryEmbedding {
+    cos: Tensor,
+    sin: Tensor,
+    head_dim: usize,
+    rotary_dim: usize,
+    interleaved: bool,
+}
+
+impl RotaryEmbedding {
+    pub fn new(
+        cfg: &RotaryEmbeddingConfig,
+        dtype: candle::DType,
+        dev: &candle::Device,
+    ) -> Result<Self> {
+        let head_dim = cfg.head_dim;
+        let dim = cfg.rotary_dim.unwrap_or(head_dim);
+        if dim % 2 != 0 || dim > head_dim {
+            candle::bail!("rotary-emb: invalid rotary dim {dim} for head dim {head_dim}")
+        }
+        let pos_freqs = |base: f64| -> Vec<f64> {
+            (0..dim)
+                .step_by(2)
+                .map(|i| base.powf(i as f64 / dim as f64))
+                .collect()
+        };
+        let mut pos_scale = 1.;
+        let mut attn_factor = 1.;
+        let inv_freq: Vec<f64> = match cfg.scaling {
+            RopeScaling::None => pos_freqs(cfg.base).iter().map(|f| 1. / f).collect(),
+            RopeScaling::Linear { factor } => {
+                pos_scale = 1. / factor;
+                pos_freqs(cfg.base).iter().map(|f| 1. / f).collect()
+            }
+            RopeScaling::Ntk { factor } => {
+                let base = cfg.base * factor.powf(dim as f64 / (dim as f64 - 2.));
+                pos_freqs(base).iter().map(|f| 1. / f).collect()
+            }
+            RopeScaling::Yarn {
+                factor,
+                original_max_position_embeddings: max_pos,
+                beta_fast,
+                beta_slow,
+            } => {
+                let low = yarn_correction_dim(beta_fast, dim, cfg.base, max_pos).floor();
+                let high = yarn_correction_dim(beta_slow, dim, cfg.base, max_pos).ceil();
+                let low = low.max(0.);
+                let high = high.min(dim as f64 - 1.);
+                let high = if low == high { high + 0.001 } else { high };
+                if factor > 1. {
+                    attn_factor = 0.1 * factor.ln() + 1.;
+                }
+                pos_freqs(cfg.base)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| {
+                        let ramp = ((i as f64 - low) / (high - low)).clamp(0., 1.);
+                        // Low dimensions (high frequencies) are extrapolated, high dimensions
+                        // are interpolated.
+                        let extrapolation = 1. - ramp;
+                        (1. / (factor * f)) * ramp + (1. / f) * extrapolation
+                    })
+                    .collect()
+            }
+        };
+        let inv_freq: Vec<f32> = inv_freq.into_iter().map(|f| f as f32).collect();
+        let inv_freq_len = inv_freq.len();
+        let inv_freq = Tensor::from_vec(inv_freq, (1, inv_freq_len), dev)?;
+        let max_pos = cfg.max_position_embeddings;
+        let t = Tensor::arange(0u32, max_pos as u32, dev)?
+            .to_dtype(candle::DType::F32)?
+            .reshape((max_pos, 1))?;
+        let freqs = (t.matmul(&inv_freq)? * pos_scale)?;
+        let cos = (freqs.cos()? * attn_factor)?.to_dtype(dtype)?;
+        let sin = (freqs.sin()? * attn_factor)?.to_dtype(dtype)?;
+        Ok(Self {
+            cos,
+            sin,
+            head_dim,
+            rotary_dim: dim,
+            interleaved: cfg.interleaved,
+        })
+    }
+
+    /// The cos table, with shape `(max_position_embeddings, rotary_dim / 2)`.
+    pub fn cos(&self) -> &Tensor {
+        &self.cos
+    }
+
+    /// The sin table, with shape `(max_position_embeddings, rotary_dim / 2)`.
+    pub fn sin(&self) -> &Tensor {
+        &self.sin
+    }
+
+    pub fn rotary_dim(&self) -> usize {
+        self.rotary_dim
+    }
+
+    /// Applies the rotary embeddings to `xs` with shape `(batch, num_heads, seq_len, head_dim)`,
+    /// the first element of the sequence being at position `seqlen_offset`.
+    pub fn apply(&self, xs: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
+        let (_b_sz, _num_heads, seq_len, head_dim) = xs.dims4()?;
+        if head_dim != self.head_dim {
+            candle::bail!(
+                "rotary-emb: unexpected head dim {head_dim}, expected {}",
+                self.head_dim
+            )
+        }
+        let cos = self.cos.narrow(0, seqlen_offset, seq_len)?;
+        let sin = self.sin.narrow(0, seqlen_offset, seq_len)?;
+        let rope = |xs: &Tensor| {
+            if self.interleaved {
+                rope_i(xs, &cos, &sin)
+            } else {
+                rope(xs, &cos, &sin)
+            }
+        };
+        if self.rotary_dim == head_dim {
+            rope(&xs.contiguous()?)
+        } else {
+            let xs_rot = xs.narrow(D::Minus1, 0, self.rotary_dim)?.contiguous()?;
+            let xs_pass = xs.narrow(D::Minus1, self.rotary_dim, head_dim - self.rotary_dim)?;
+            Tensor::cat(&[&rope(&xs_rot)?, &xs_pass], D::Minus1)
+        }
+    }
+
+    /// Applies the rotary embeddings to both the queries and the keys.
+    pub fn apply_qk(
+        &self,
+        q: &Tensor,
+        k: &Tensor,
+        seqlen_offset: usize,
+    ) -> Result<(Tensor, Tensor)> {
+        let q = self.apply(q, seqlen_offset)?;
+        let k = self.apply(k, seqlen_offset)?;
+        Ok((q, k))
+    }
+}
@@ -1,9 +1,11 @@
 //! A sequential layer used to chain multiple layers and closures.
+use crate::parameters::{apply_prefixed, visit_prefixed, ParamModule, Parameters};
 use candle::{Module, Result, Tensor};
 
-/// A sequential layer combining multiple other layers.
+/// A sequential layer combining multiple other layers. As for [`ModuleList`](crate::ModuleList),
+/// the parameters of the layer at position `i` are prefixed with `i`.
 pub struct Sequential {
-    layers: Vec<Box<dyn Module>>,
+    layers: Vec<Box<dyn ParamModule>>,
 }
 
 /// Creates a new empty sequential layer.
@@ -11,6 +13,12 @@ pub fn seq() -> Sequential {
     Sequential { layers: vec![] }
 }
 
+impl Default for Sequential {
+    fn default() -> Self {
+        seq()
+    }
+}
+
 impl Sequential {
     /// The number of sub-layers embedded in this layer.
     pub fn len(&self) -> i64 {
@@ -21,6 +29,24 @@ impl Sequential {
     pub fn is_empty(&self) -> bool {
         self.layers.is_empty()
     }
+
+    /// Returns the sub-layer at position `index`, or `None` if out of bounds.
+    pub fn get(&self, index: usize) -> Option<&dyn ParamModule> {
+        self.layers.get(index).map(|l| l.as_ref())
+    }
+
+    /// Iterates over the sub-layers in the order in which they are applied.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ParamModule> {
+        self.layers.iter().map(|l| l.as_ref())
+    }
+}
+
+impl std::ops::Index<usize> for Sequential {
+    type Output = dyn ParamModule;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.layers[index].as_ref()
+    }
 }
 
 impl Module for Sequential {
@@ -33,10 +59,28 @@ impl Module for Sequential {
     }
 }
 
+impl Parameters for Sequential {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        for (i, l) in self.layers.iter().enumerate() {
+            visit_prefixed(&i.to_string(), l.as_ref(), f)
+        }
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        for (i, l) in self.layers.iter_mut().enumerate() {
+            apply_prefixed(&i.to_string(), l.as_mut(), f)?
+        }
+        Ok(())
+    }
+}
+
 impl Sequential {
     /// Appends a layer after all the current layers.
     #[allow(clippy::should_implement_trait)]
-    pub fn add<M: Module + 'static>(mut self, layer: M) -> Self {
+    pub fn add<M: ParamModule + 'static>(mut self, layer: M) -> Self {
         self.layers.push(Box::new(layer));
         self
     }
@@ -0,0 +1,216 @@
+//! Spectral Normalization.
+//!
+//! This wraps a layer and divides its weight by its spectral norm, i.e. its largest singular
+//! value, as described in [`Spectral Normalization for Generative Adversarial Networks`]. The
+//! weight is viewed as a matrix of shape `(dims[dim], elem_count / dims[dim])` and the spectral
+//! norm is estimated with power iteration, the singular vectors `u` and `v` being refined on each
+//! forward pass in training mode, see [`crate::ModuleT`]. In evaluation mode the current estimate
+//! is used as is.
+//!
+//! When created with [`spectral_norm`], the original weight and the singular vectors are loaded
+//! from the `weight_orig`, `weight_u` and `weight_v` tensors of the var builder, as in the PyTorch
+//! checkpoints of `torch.nn.utils.spectral_norm`. The singular vectors are frozen variables so
+//! updating them also updates the underlying var map but they are not trained by the optimizers.
+//!
+//! [`Spectral Normalization for Generative Adversarial Networks`]: https://arxiv.org/abs/1802.05957
+use crate::weight_norm::{rename_weight, weight_of, with_weight};
+use crate::Parameters;
+use candle::{Module, ModuleT, Result, Tensor, Var};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectralNormConfig {
+    /// The dimension of the weight corresponding to the output features, this should be 1 for
+    /// transposed convolutions.
+    pub dim: usize,
+    /// The number of power iterations per forward pass in training mode.
+    pub n_power_iterations: usize,
+    pub eps: f64,
+}
+
+impl Default for SpectralNormConfig {
+    fn default() -> Self {
+        Self {
+            dim: 0,
+            n_power_iterations: 1,
+            eps: 1e-12,
+        }
+    }
+}
+
+fn normalize(xs: &Tensor, eps: f64) -> Result<Tensor> {
+    let norm = xs.sqr()?.sum_all()?.sqrt()?.maximum(eps)?;
+    xs.broadcast_div(&norm)
+}
+
+// Reshapes the weight to a matrix with the `dim` dimension first.
+fn weight_matrix(weight: &Tensor, dim: usize) -> Result<Tensor> {
+    if dim >= weight.rank() {
+        candle::bail!(
+            "spectral norm dim {dim} out of range for weight shape {:?}",
+            weight.shape()
+        )
+    }
+    let weight = if dim == 0 {
+        weight.clone()
+    } else {
+        let mut perm: Vec<usize> = (0..weight.rank()).filter(|&d| d != dim).collect();
+        perm.insert(0, dim);
+        weight.permute(perm)?
+    };
+    weight.flatten_from(1)
+}
+
+#[derive(Clone, Debug)]
+pub struct SpectralNorm<M> {
+    module: M,
+    weight_u: Var,
+    weight_v: Var,
+    config: SpectralNormConfig,
+}
+
+impl<M: Parameters + Clone> SpectralNorm<M> {
+    /// Wraps `module`, its weight being the original weight. The estimates of the left and right
+    /// singular vectors `weight_u` and `weight_v` get normalized.
+    pub fn new(
+        module: M,
+        weight_u: &Tensor,
+        weight_v: &Tensor,
+        config: SpectralNormConfig,
+    ) -> Result<Self> {
+        let (h, w) = weight_matrix(&weight_of(&module)?, config.dim)?.dims2()?;
+        if weight_u.dims() != [h] || weight_v.dims() != [w] {
+            candle::bail!(
+                "unexpected singular vector shapes {:?} {:?}, expected [{h}] [{w}]",
+                weight_u.shape(),
+                weight_v.shape()
+            )
+        }
+        let weight_u = Var::from_tensor(weight_u)?;
+        let weight_v = Var::from_tensor(weight_v)?;
+        weight_u.set(&normalize(&weight_u, config.eps)?)?;
+        weight_v.set(&normalize(&weight_v, config.eps)?)?;
+        weight_u.freeze();
+        weight_v.freeze();
+        Ok(Self {
+            module,
+            weight_u,
+            weight_v,
+            config,
+        })
+    }
+
+    /// Wraps `module` using random initial estimates of the singular vectors.
+    pub fn from_module(module: M, config: SpectralNormConfig) -> Result<Self> {
+        let weight = weight_of(&module)?;
+        let (h, w) = weight_matrix(&weight, config.dim)?.dims2()?;
+        let weight_u = Tensor::randn(0f32, 1., h, weight.device())?.to_dtype(weight.dtype())?;
+        let weight_v = Tensor::randn(0f32, 1., w, weight.device())?.to_dtype(weight.dtype())?;
+        Self::new(module, &weight_u, &weight_v, config)
+    }
+
+    pub fn weight_orig(&self) -> Result<Tensor> {
+        weight_of(&self.module)
+    }
+
+    pub fn weight_u(&self) -> &Tensor {
+        self.weight_u.as_tensor()
+    }
+
+    pub fn weight_v(&self) -> &Tensor {
+        self.weight_v.as_tensor()
+    }
+
+    pub fn config(&self) -> &SpectralNormConfig {
+        &self.config
+    }
+
+    /// Runs some power iterations to refine the estimates of the singular vectors.
+    pub fn power_iteration(&self, n_iterations: usize) -> Result<()> {
+        let eps = self.config.eps;
+        let weight = weight_matrix(&self.weight_orig()?, self.config.dim)?.detach();
+        let mut u = self.weight_u.as_tensor().detach();
+        let mut v = self.weight_v.as_tensor().detach();
+        for _ in 0..n_iterations {
+            v = normalize(&weight.t()?.matmul(&u.unsqueeze(1)?)?.squeeze(1)?, eps)?;
+            u = normalize(&weight.matmul(&v.unsqueeze(1)?)?.squeeze(1)?, eps)?;
+        }
+        self.weight_u.set(&u)?;
+        self.weight_v.set(&v)
+    }
+
+    /// The current estimate of the spectral norm of the weight, `u^T W v`.
+    pub fn sigma(&self) -> Result<Tensor> {
+        let weight = weight_matrix(&self.weight_orig()?, self.config.dim)?;
+        let u = self.weight_u.as_tensor().detach().unsqueeze(0)?;
+        let v = self.weight_v.as_tensor().detach().unsqueeze(1)?;
+        u.matmul(&weight.matmul(&v)?)?.reshape(())
+    }
+
+    /// The normalized weight, the singular vectors are refined first in training mode.
+    pub fn weight_t(&self, train: bool) -> Result<Tensor> {
+        if train {
+            self.power_iteration(self.config.n_power_iterations)?
+        }
+        self.weight_orig()?.broadcast_div(&self.sigma()?)
+    }
+
+    /// Returns the wrapped layer using the current normalized weight.
+    pub fn remove(&self) -> Result<M> {
+        with_weight(&self.module, &self.weight_t(false)?)
+    }
+}
+
+impl<M: Module + Parameters + Clone> ModuleT for SpectralNorm<M> {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        with_weight(&self.module, &self.weight_t(train)?)?.forward(xs)
+    }
+}
+
+impl<M: Parameters> Parameters for SpectralNorm<M> {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        self.module.visit_parameters(&mut |name, t| match name {
+            "weight" => f("weight_orig", t),
+            name => f(name, t),
+        });
+        f("weight_u", self.weight_u.as_tensor());
+        f("weight_v", self.weight_v.as_tensor())
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        self.module.apply_parameters(&mut |name, t| match name {
+            "weight" => f("weight_orig", t),
+            name => f(name, t),
+        })?;
+        self.weight_u = Var::from_tensor(&f("weight_u", self.weight_u.as_tensor())?)?;
+        self.weight_v = Var::from_tensor(&f("weight_v", self.weight_v.as_tensor())?)?;
+        self.weight_u.freeze();
+        self.weight_v.freeze();
+        Ok(())
+    }
+}
+
+/// Creates a spectral normalized layer, `f` builds the layer from a var builder where its
+/// `weight` is loaded from `weight_orig`. The singular vectors are loaded from `weight_u` and
+/// `weight_v`, and randomly initialized when missing.
+pub fn spectral_norm<M, F>(
+    config: SpectralNormConfig,
+    vb: crate::VarBuilder,
+    f: F,
+) -> Result<SpectralNorm<M>>
+where
+    M: Parameters + Clone,
+    F: FnOnce(crate::VarBuilder) -> Result<M>,
+{
+    let module = f(rename_weight(&vb, "weight_orig"))?;
+    let (h, w) = weight_matrix(&weight_of(&module)?, config.dim)?.dims2()?;
+    let init = crate::Init::Randn {
+        mean: 0.,
+        stdev: 1.,
+    };
+    let weight_u = vb.get_with_hints(h, "weight_u", init)?;
+    let weight_v = vb.get_with_hints(w, "weight_v", init)?;
+    SpectralNorm::new(module, &weight_u, &weight_v, config)
+}
@@ -553,6 +553,11 @@ impl<'a> VarBuilder<'a> {
     /// assert!(vb.get((2, 3), "bar").is_ok());
     /// assert!(vb.get((2, 3), "foo").is_ok());
     /// assert!(!vb.contains_tensor("baz"));
+    ///
+    /// // The renaming function is applied to the full path and the prefix is only added once.
+    /// let vb = vb.pp("a").rename_f(|f: &str| f.replace("a.", ""));
+    /// assert!(vb.contains_tensor("foo"));
+    /// assert!(vb.get((2, 3), "bar").is_ok());
     /// # Ok::<(), candle::Error>(())
     /// ```
     pub fn rename_f<F: Fn(&str) -> String + Sync + Send + 'static>(self, f: F) -> Self {
@@ -560,11 +565,15 @@ impl<'a> VarBuilder<'a> {
         self.rename(f)
     }
 
+    /// Returns a var builder where the names are transformed by `renamer` before being queried.
+    /// The renamer receives the full path including the current prefix, and the resulting name is
+    /// looked up as is, so the prefix is only applied once.
     pub fn rename<R: Renamer + Send + Sync + 'a>(self, renamer: R) -> Self {
         let dtype = self.dtype();
         let device = self.device().clone();
         let path = self.path.clone();
-        let backend = Rename::new(self, renamer);
+        // The renamer gets the full path, so the inner var builder must not add the prefix again.
+        let backend = Rename::new(self.root(), renamer);
         let backend: Box<dyn SimpleBackend + 'a> = Box::new(backend);
         let data = TensorData {
             backend,
@@ -577,6 +586,33 @@ impl<'a> VarBuilder<'a> {
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Returns a var builder that loads `fallback` when querying `name` and the underlying
+    /// storage does not contain it. Both names are full paths, i.e. they do not depend on the
+    /// current prefix. This is typically used for tied weights that some checkpoints omit, e.g.
+    /// loading `lm_head.weight` from the token embedding. When used with a `VarMap`, the two names
+    /// end up sharing the same variable.
+    ///
+    /// ```rust
+    /// use candle::{Tensor, DType, Device};
+    /// let wte = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((3, 2))?;
+    /// let tensors: std::collections::HashMap<_, _> =
+    ///     [("wte.weight".to_string(), wte)].into_iter().collect();
+    /// let vb = candle_nn::VarBuilder::from_tensors(tensors, DType::F32, &Device::Cpu)
+    ///     .with_fallback("lm_head.weight", "wte.weight");
+    /// assert!(vb.contains_tensor("lm_head.weight"));
+    /// let lm_head = candle_nn::linear_no_bias(2, 3, vb.pp("lm_head"))?;
+    /// assert_eq!(lm_head.weight().to_vec2::<f32>()?, [[0., 1.], [2., 3.], [4., 5.]]);
+    /// # Ok::<(), candle::Error>(())
+    /// ```
+    pub fn with_fallback(self, name: impl ToString, fallback: impl ToString) -> Self {
+        let renamer = Fallback {
+            inner: self.root(),
+            name: name.to_string(),
+            fallback: fallback.to_string(),
+        };
+        self.rename(renamer)
+    }
 }
 
 pub struct ShardedSafeTensors(candle::safetensors::MmapedSafetensors);
@@ -738,6 +774,25 @@ impl<'a, R: Renamer> Rename<'a, R> {
     }
 }
 
+struct Fallback<'a> {
+    inner: VarBuilder<'a>,
+    name: String,
+    fallback: String,
+}
+
+impl<'a> Renamer for Fallback<'a> {
+    fn rename(&self, v: &str) -> std::borrow::Cow<'_, str> {
+        if v == self.name
+            && !self.inner.contains_tensor(v)
+            && self.inner.contains_tensor(&self.fallback)
+        {
+            std::borrow::Cow::Borrowed(&self.fallback)
+        } else {
+            std::borrow::Cow::Owned(v.to_string())
+        }
+    }
+}
+
 impl Renamer for Box<dyn Fn(&str) -> String + Sync + Send> {
     fn rename(&self, v: &str) -> std::borrow::Cow<'_, str> {
         std::borrow::Cow::Owned(self(v))
@@ -26,6 +26,57 @@ impl VarMap {
         tensor_data.values().map(|c| c.clone()).collect::<Vec<_>>()
     }
 
+    /// Retrieve the variables whose name satisfies `filter`, e.g. to build parameter groups with
+    /// different optimizer settings.
+    pub fn filtered_vars<F: Fn(&str) -> bool>(&self, filter: F) -> Vec<Var> {
+        let tensor_data = self.data.lock().unwrap();
+        tensor_data
+            .iter()
+            .filter(|(name, _)| filter(name))
+            .map(|(_, var)| var.clone())
+            .collect()
+    }
+
+    /// Retrieve the variables that are not frozen.
+    pub fn trainable_vars(&self) -> Vec<Var> {
+        let tensor_data = self.data.lock().unwrap();
+        tensor_data
+            .values()
+            .filter(|var| !var.is_frozen())
+            .cloned()
+            .collect()
+    }
+
+    /// Freeze the variables whose name satisfies `filter`, see [`Var::freeze`]. Returns the
+    /// number of matching variables.
+    ///
+    /// ```rust
+    /// use candle::{DType, Device};
+    /// use candle_nn::{VarBuilder, VarMap};
+    /// # fn main() -> candle::Result<()> {
+    /// let varmap = VarMap::new();
+    /// let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    /// let _backbone = candle_nn::linear(4, 8, vb.pp("backbone"))?;
+    /// let _head = candle_nn::linear(8, 2, vb.pp("head"))?;
+    /// assert_eq!(varmap.freeze(|name| name.starts_with("backbone.")), 2);
+    /// // Only the weight and bias of the head get passed to the optimizer.
+    /// assert_eq!(varmap.trainable_vars().len(), 2);
+    /// # Ok(()) }
+    /// ```
+    pub fn freeze<F: Fn(&str) -> bool>(&self, filter: F) -> usize {
+        let vars = self.filtered_vars(filter);
+        vars.iter().for_each(|var| var.freeze());
+        vars.len()
+    }
+
+    /// Unfreeze the variables whose name satisfies `filter`. Returns the number of matching
+    /// variables.
+    pub fn unfreeze<F: Fn(&str) -> bool>(&self, filter: F) -> usize {
+        let vars = self.filtered_vars(filter);
+        vars.iter().for_each(|var| var.unfreeze());
+        vars.len()
+    }
+
     /// Save the map in the safetensors format.
     pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
         let tensor_data = self.data.lock().unwrap();
@@ -0,0 +1,184 @@
+//! Weight Normalization.
+//!
+//! This wraps a layer and reparameterizes its weight as `w = g * v / ||v||` as described in
+//! [`Weight Normalization`], the norm being computed over all the dimensions except `dim`. The
+//! magnitude `g` and the direction `v` are learned separately. When created with [`weight_norm`],
+//! they are loaded from the `weight_g` and `weight_v` tensors of the var builder, as in the
+//! PyTorch checkpoints of `torch.nn.utils.weight_norm`.
+//!
+//! ```rust
+//! use candle::{DType, Device, Tensor};
+//! use candle_nn::{Module, Parameters, VarBuilder, VarMap};
+//! # fn main() -> candle::Result<()> {
+//! let varmap = VarMap::new();
+//! let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+//! let layer = candle_nn::weight_norm(0, vb.pp("proj"), |vb| candle_nn::linear(4, 3, vb))?;
+//! let names: Vec<_> = layer.named_parameters().into_iter().map(|(n, _)| n).collect();
+//! assert_eq!(names, ["weight_v", "bias", "weight_g"]);
+//! let ys = layer.forward(&Tensor::ones((2, 4), DType::F32, &Device::Cpu)?)?;
+//! assert_eq!(ys.dims(), &[2, 3]);
+//! # Ok(()) }
+//! ```
+//!
+//! [`Weight Normalization`]: https://arxiv.org/abs/1602.07868
+use crate::Parameters;
+use candle::{Module, Result, Tensor};
+
+/// Computes the norm of `xs` over all the dimensions except `dim`, keeping these dimensions.
+pub(crate) fn norm_except_dim(xs: &Tensor, dim: usize) -> Result<Tensor> {
+    let dims: Vec<usize> = (0..xs.rank()).filter(|&d| d != dim).collect();
+    xs.sqr()?.sum_keepdim(dims)?.sqrt()
+}
+
+/// Returns a var builder where the `weight` tensor at the current path is loaded from `name`.
+pub(crate) fn rename_weight<'a>(vb: &crate::VarBuilder<'a>, name: &str) -> crate::VarBuilder<'a> {
+    let prefix = vb.prefix();
+    let (weight, target) = if prefix.is_empty() {
+        ("weight".to_string(), name.to_string())
+    } else {
+        (format!("{prefix}.weight"), format!("{prefix}.{name}"))
+    };
+    vb.clone().rename_f(move |n| {
+        if n == weight {
+            target.clone()
+        } else {
+            n.to_string()
+        }
+    })
+}
+
+// Returns a copy of the layer using `weight` as its weight.
+pub(crate) fn with_weight<M: Parameters + Clone>(module: &M, weight: &Tensor) -> Result<M> {
+    let mut module = module.clone();
+    module.apply_parameters(&mut |name, t| {
+        if name == "weight" {
+            Ok(weight.clone())
+        } else {
+            Ok(t.clone())
+        }
+    })?;
+    Ok(module)
+}
+
+pub(crate) fn weight_of<M: Parameters>(module: &M) -> Result<Tensor> {
+    let mut weight = None;
+    module.visit_parameters(&mut |name, t| {
+        if name == "weight" {
+            weight = Some(t.clone())
+        }
+    });
+    match weight {
+        Some(weight) => Ok(weight),
+        None => candle::bail!("the wrapped layer has no weight"),
+    }
+}
+
+// The shape of the magnitude, the size of the weight on `dim` and 1 on the other dimensions.
+fn magnitude_dims(weight_dims: &[usize], dim: usize) -> Result<Vec<usize>> {
+    if dim >= weight_dims.len() {
+        candle::bail!("weight norm dim {dim} out of range for weight shape {weight_dims:?}")
+    }
+    let dims = (0..weight_dims.len())
+        .map(|d| if d == dim { weight_dims[d] } else { 1 })
+        .collect();
+    Ok(dims)
+}
+
+#[derive(Clone, Debug)]
+pub struct WeightNorm<M> {
+    module: M,
+    weight_g: Tensor,
+    dim: usize,
+}
+
+impl<M: Parameters + Clone> WeightNorm<M> {
+    /// Wraps `module`, its weight being used as the direction `v`. The magnitude `weight_g` has
+    /// the size of the weight on `dim` and 1 on the other dimensions.
+    pub fn new(module: M, weight_g: Tensor, dim: usize) -> Result<Self> {
+        let expected = magnitude_dims(weight_of(&module)?.dims(), dim)?;
+        if weight_g.dims() != expected {
+            candle::bail!(
+                "unexpected weight_g shape {:?}, expected {expected:?}",
+                weight_g.shape()
+            )
+        }
+        Ok(Self {
+            module,
+            weight_g,
+            dim,
+        })
+    }
+
+    /// Wraps `module` as `torch.nn.utils.weight_norm` does: the magnitude is initialized with the
+    /// norm of the current weight so that the effective weight is unchanged.
+    pub fn from_module(module: M, dim: usize) -> Result<Self> {
+        let weight_g = norm_except_dim(&weight_of(&module)?, dim)?;
+        Self::new(module, weight_g, dim)
+    }
+
+    pub fn weight_g(&self) -> &Tensor {
+        &self.weight_g
+    }
+
+    pub fn weight_v(&self) -> Result<Tensor> {
+        weight_of(&self.module)
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The effective weight `g * v / ||v||`.
+    pub fn weight(&self) -> Result<Tensor> {
+        let weight_v = self.weight_v()?;
+        let norm = norm_except_dim(&weight_v, self.dim)?;
+        weight_v.broadcast_mul(&self.weight_g)?.broadcast_div(&norm)
+    }
+
+    /// Returns the wrapped layer using the effective weight. This avoids recomputing the weight
+    /// on each forward pass for inference.
+    pub fn remove(&self) -> Result<M> {
+        with_weight(&self.module, &self.weight()?)
+    }
+}
+
+impl<M: Module + Parameters + Clone> Module for WeightNorm<M> {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        self.remove()?.forward(xs)
+    }
+}
+
+impl<M: Parameters> Parameters for WeightNorm<M> {
+    fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+        self.module.visit_parameters(&mut |name, t| match name {
+            "weight" => f("weight_v", t),
+            name => f(name, t),
+        });
+        f("weight_g", &self.weight_g)
+    }
+
+    fn apply_parameters(
+        &mut self,
+        f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        self.module.apply_parameters(&mut |name, t| match name {
+            "weight" => f("weight_v", t),
+            name => f(name, t),
+        })?;
+        crate::parameters::apply_tensor("weight_g", &mut self.weight_g, f)
+    }
+}
+
+/// Creates a weight normalized layer, `f` builds the layer from a var builder where its `weight`
+/// is loaded from `weight_v`. The magnitude is loaded from `weight_g` and initialized to 1 when
+/// missing.
+pub fn weight_norm<M, F>(dim: usize, vb: crate::VarBuilder, f: F) -> Result<WeightNorm<M>>
+where
+    M: Parameters + Clone,
+    F: FnOnce(crate::VarBuilder) -> Result<M>,
+{
+    let module = f(rename_weight(&vb, "weight_v"))?;
+    let g_dims = magnitude_dims(weight_of(&module)?.dims(), dim)?;
+    let weight_g = vb.get_with_hints(g_dims, "weight_g", crate::Init::Const(1.))?;
+    WeightNorm::new(module, weight_g, dim)
+}
@@ -0,0 +1,123 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, IndexOp, Result, Tensor, D};
+use candle_nn::{attention, kv_cache::KvCache, MultiHeadAttentionConfig, VarBuilder, VarMap};
+
+fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+    (a - b)?.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()
+}
+
+#[test]
+fn sdpa_gqa() -> Result<()> {
+    let dev = &Device::Cpu;
+    let q = Tensor::randn(0f32, 1., (2, 4, 3, 8), dev)?;
+    let k = Tensor::randn(0f32, 1., (2, 2, 5, 8), dev)?;
+    let v = Tensor::randn(0f32, 1., (2, 2, 5, 8), dev)?;
+    let mask = attention::causal_mask(3, 5, DType::F32, dev)?;
+    let scale = 1. / 8f64.sqrt();
+    let ys = attention::scaled_dot_product_attention(&q, &k, &v, Some(&mask), scale)?;
+    assert_eq!(ys.dims(), &[2, 4, 3, 8]);
+    for h in 0..4 {
+        let q = q.narrow(1, h, 1)?;
+        let k = k.narrow(1, h / 2, 1)?;
+        let v = v.narrow(1, h / 2, 1)?;
+        let attn = (q.matmul(&k.t()?)? * scale)?.broadcast_add(&mask)?;
+        let attn = candle_nn::ops::softmax(&attn, D::Minus1)?;
+        let expected = attn.matmul(&v)?;
+        assert!(max_diff(&ys.narrow(1, h, 1)?, &expected)? < 1e-5);
+    }
+    // The first query attends to the first three keys only.
+    let mask = mask.to_vec2::<f32>()?;
+    assert_eq!(mask[0][2], 0.);
+    assert_eq!(mask[0][3], f32::NEG_INFINITY);
+    assert_eq!(mask[2][4], 0.);
+    Ok(())
+}
+
+#[test]
+fn mha_kv_cache() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let cfg = MultiHeadAttentionConfig {
+        num_heads: 4,
+        num_kv_heads: 2,
+        head_dim: None,
+        bias: true,
+    };
+    let mha = candle_nn::multi_head_attention(16, cfg, vb)?;
+    assert_eq!(mha.head_dim(), 4);
+    let xs = Tensor::randn(0f32, 1., (2, 5, 16), dev)?;
+    let mask = attention::causal_mask(5, 5, DType::F32, dev)?;
+    let full = mha.forward(&xs, Some(&mask), None)?;
+    assert_eq!(full.dims(), &[2, 5, 16]);
+
+    let mut cache = KvCache::new(2, 16);
+    let prefix = xs.narrow(1, 0, 3)?;
+    let mask = attention::causal_mask(3, 3, DType::F32, dev)?;
+    let ys1 = mha.forward(&prefix, Some(&mask), Some(&mut cache))?;
+    let ys2 = mha.forward(&xs.narrow(1, 3, 1)?, None, Some(&mut cache))?;
+    let ys3 = mha.forward(&xs.narrow(1, 4, 1)?, None, Some(&mut cache))?;
+    assert_eq!(cache.current_seq_len(), 5);
+    let incremental = Tensor::cat(&[ys1, ys2, ys3], 1)?;
+    assert!(max_diff(&full, &incremental)? < 1e-5);
+
+    // A cache concatenating along another dimension is rejected.
+    let mut cache = KvCache::new(1, 16);
+    assert!(mha.forward(&xs, None, Some(&mut cache)).is_err());
+
+    let data = varmap.data().lock().unwrap();
+    let mut names: Vec<_> = data.keys().cloned().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "k_proj.bias",
+            "k_proj.weight",
+            "o_proj.bias",
+            "o_proj.weight",
+            "q_proj.bias",
+            "q_proj.weight",
+            "v_proj.bias",
+            "v_proj.weight"
+        ]
+    );
+    assert_eq!(data["k_proj.weight"].dims(), &[8, 16]);
+    assert_eq!(data["o_proj.weight"].dims(), &[16, 16]);
+    Ok(())
+}
+
+#[test]
+fn alibi() -> Result<()> {
+    let dev = &Device::Cpu;
+    let slopes = attention::alibi_slopes(4, 8.);
+    assert_eq!(slopes, [0.25, 0.0625, 0.015625, 0.00390625]);
+    let slopes = attention::alibi_slopes(6, 8.);
+    let expected: Vec<f32> = [2, 4, 6, 8, 1, 3].iter().map(|&v| 0.5f32.powi(v)).collect();
+    assert_eq!(slopes, expected);
+
+    let bias = attention::alibi_bias(4, 3, dev)?;
+    assert_eq!(bias.dims(), &[1, 4, 3, 3]);
+    assert_eq!(
+        bias.i((0, 0))?.to_vec2::<f32>()?,
+        [[0., -0.25, -0.5], [-0.25, 0., -0.25], [-0.5, -0.25, 0.]]
+    );
+
+    // The bias gets added to the attention scores.
+    let q = Tensor::zeros((1, 4, 3, 8), DType::F32, dev)?;
+    let k = Tensor::zeros((1, 4, 3, 8), DType::F32, dev)?;
+    let v = Tensor::arange(0f32, 3., dev)?
+        .reshape((1, 1, 3, 1))?
+        .broadcast_as((1, 4, 3, 8))?;
+    let mask = bias.broadcast_add(&attention::causal_mask(3, 3, DType::F32, dev)?)?;
+    let ys = attention::scaled_dot_product_attention(&q, &k, &v, Some(&mask), 1.)?;
+    let w = [1f32, (-0.25f32).exp()];
+    let expected = w[0] / (w[0] + w[1]);
+    let got = ys.i((0, 0, 1, 0))?.to_scalar::<f32>()?;
+    assert!((got - expected).abs() < 1e-5);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::test_utils::to_vec2_round;
+use candle::{Device, Result, Tensor, Var};
+use candle_nn::autodiff::{grad, hessian, jacobian, value_and_grad};
+
+#[test]
+fn jacobian_matmul() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Tensor::new(&[[1f32, 2., 3.], [-1., 0.5, 2.]], device)?;
+    let x = Tensor::new(&[0.5f32, -1., 2.], device)?;
+    // The jacobian of x -> w.x is w.
+    let jac = jacobian(|x| w.matmul(&x.unsqueeze(1)?)?.squeeze(1), &x)?;
+    assert_eq!(jac.to_vec2::<f32>()?, w.to_vec2::<f32>()?);
+
+    // Element-wise functions have a diagonal jacobian, the output can have any shape.
+    let x = Tensor::new(&[[0.5f32, -1.], [2., 0.]], device)?;
+    let jac = jacobian(|x| x.tanh(), &x)?;
+    assert_eq!(jac.dims(), &[2, 2, 2, 2]);
+    let diag = (1. - x.tanh()?.sqr()?)?;
+    let jac = jac.reshape((4, 4))?;
+    let expected = Tensor::eye(4, candle::DType::F32, device)?
+        .broadcast_mul(&diag.flatten_all()?.unsqueeze(1)?)?;
+    assert_eq!(to_vec2_round(&jac, 4)?, to_vec2_round(&expected, 4)?);
+
+    // Outputs that do not depend on the input have a zero jacobian.
+    let jac = jacobian(|x| x.zeros_like(), &x)?;
+    assert_eq!(jac.dims(), &[2, 2, 2, 2]);
+    assert_eq!(jac.sum_all()?.to_scalar::<f32>()?, 0.);
+    Ok(())
+}
+
+#[test]
+fn hessian_quadratic() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Tensor::new(&[[2f32, 1.], [1., 3.]], device)?;
+    let x = Tensor::new(&[1f32, -2.], device)?;
+    // The hessian of x -> x^T a x is a + a^T.
+    let f = |x: &Tensor| {
+        let x = x.unsqueeze(1)?;
+        x.t()?.matmul(&a)?.matmul(&x)?.squeeze(0)?.squeeze(0)
+    };
+    let hess = hessian(f, &x)?;
+    assert_eq!(hess.to_vec2::<f32>()?, [[4., 2.], [2., 6.]]);
+
+    // sin(x0) * x1^3
+    let hess = hessian(|x| x.get(0)?.sin()?.mul(&x.get(1)?.powf(3.)?), &x)?;
+    let (s, c) = (1f32.sin(), 1f32.cos());
+    let expected = [[8. * s, 12. * c], [12. * c, -12. * s]];
+    let expected = Tensor::new(&expected, device)?;
+    assert_eq!(to_vec2_round(&hess, 4)?, to_vec2_round(&expected, 4)?);
+
+    assert!(hessian(|x| x.sqr(), &x).is_err());
+    Ok(())
+}
+
+#[test]
+fn functional_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let b = Var::new(&[0.5f32, -0.5], device)?;
+    let unused = Var::new(&[1f32, 2., 3.], device)?;
+    let x = Tensor::new(&[[1f32, -1.]], device)?;
+    let params = [w.clone(), b.clone(), unused];
+    let f = |ps: &[Var]| x.matmul(&ps[0])?.broadcast_add(&ps[1])?.sqr()?.sum_all();
+
+    let loss = f(&params)?;
+    let grads = grad(&loss, &params)?;
+    // x.w + b = [-1.5, -2.5]
+    assert_eq!(grads[0].to_vec2::<f32>()?, [[-3., -5.], [3., 5.]]);
+    assert_eq!(grads[1].to_vec1::<f32>()?, [-3., -5.]);
+    assert_eq!(grads[2].to_vec1::<f32>()?, [0., 0., 0.]);
+
+    // A plain gradient descent step.
+    let (value, grads) = value_and_grad(f, &params)?;
+    assert_eq!(value.to_scalar::<f32>()?, 8.5);
+    for (p, g) in params.iter().zip(grads.iter()) {
+        p.set(&(p.as_tensor() - (g * 0.1)?)?)?;
+    }
+    assert_eq!(to_vec2_round(&w, 4)?, [[1.3, 2.5], [2.7, 3.5]]);
+    assert_eq!(b.to_vec1::<f32>()?, [0.8, 0.]);
+    Ok(())
+}
@@ -6,7 +6,7 @@ extern crate accelerate_src;
 
 use anyhow::Result;
 use candle::{test_utils, DType, Device, Tensor};
-use candle_nn::{batch_norm, BatchNorm, BatchNormConfig, VarBuilder, VarMap};
+use candle_nn::{batch_norm, BatchNorm, BatchNormConfig, ModuleT, VarBuilder, VarMap};
 
 /* The test below has been generated using the following PyTorch code:
 import torch
@@ -126,3 +126,45 @@ fn train_batch_norm() -> Result<()> {
     );
     Ok(())
 }
+
+// Loads the running statistics from a checkpoint, these are used in evaluation mode and updated
+// in training mode.
+#[test]
+fn batch_norm_checkpoint() -> Result<()> {
+    let dev = &Device::Cpu;
+    let tensors = std::collections::HashMap::from([
+        (
+            "bn.running_mean".to_string(),
+            Tensor::new(&[1f32, -2.], dev)?,
+        ),
+        (
+            "bn.running_var".to_string(),
+            Tensor::new(&[4f32, 0.25], dev)?,
+        ),
+        ("bn.weight".to_string(), Tensor::new(&[2f32, 1.], dev)?),
+        ("bn.bias".to_string(), Tensor::new(&[0f32, 0.5], dev)?),
+    ]);
+    let vb = VarBuilder::from_tensors(tensors, DType::F32, dev);
+    let bn = batch_norm(2, 0., vb.pp("bn"))?;
+    let xs = Tensor::new(&[[[[3f32, 5.]], [[-1., -2.5]]]], dev)?;
+    let ys = bn.forward_t(&xs, false)?;
+    assert_eq!(
+        test_utils::to_vec1_round(&ys.flatten_all()?, 4)?,
+        &[2., 4., 2.5, -0.5]
+    );
+
+    let ys = bn.forward_t(&xs, true)?;
+    assert_eq!(
+        test_utils::to_vec1_round(&ys.flatten_all()?, 4)?,
+        &[-2., 2., 1.5, -0.5]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_mean(), 4)?,
+        &[1.3, -1.975]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_var(), 4)?,
+        &[3.8, 0.3375]
+    );
+    Ok(())
+}
@@ -0,0 +1,244 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::test_utils::to_vec1_round;
+use candle::{DType, Device, Tensor, Var};
+use candle_nn::{
+    Conv1d, Conv1dConfig, Conv2dConfig, Conv3dConfig, ConvTranspose2dConfig, Module, VarBuilder,
+    VarMap,
+};
+use std::collections::HashMap;
+
+#[test]
+fn conv2d_layer() -> Result<()> {
+    let dev = &Device::Cpu;
+    let weight = Tensor::arange(0f32, 72., dev)?
+        .affine(0.1, -3.)?
+        .reshape((4, 2, 3, 3))?;
+    let bias = Tensor::new(&[1f32, -1., 0.5, 2.], dev)?;
+    let ts = HashMap::from([
+        ("conv.weight".to_string(), weight.clone()),
+        ("conv.bias".to_string(), bias.clone()),
+    ]);
+    let vb = VarBuilder::from_tensors(ts, DType::F32, dev);
+    let cfg = Conv2dConfig {
+        padding: 1,
+        stride: 2,
+        dilation: 2,
+        groups: 2,
+    };
+    let conv = candle_nn::conv2d(4, 4, 3, cfg, vb.pp("conv"))?;
+    let xs = Tensor::arange(0f32, 100., dev)?
+        .affine(0.05, -2.)?
+        .cos()?
+        .reshape((1, 4, 5, 5))?;
+    let ys = conv.forward(&xs)?;
+    assert_eq!(ys.dims(), [1, 4, 2, 2]);
+
+    // Each group of output channels only sees its own group of input channels.
+    let group = |g: usize| {
+        xs.narrow(1, 2 * g, 2)?
+            .conv2d(&weight.narrow(0, 2 * g, 2)?, 1, 2, 2, 1)
+    };
+    let expected =
+        Tensor::cat(&[group(0)?, group(1)?], 1)?.broadcast_add(&bias.reshape((1, 4, 1, 1))?)?;
+    assert_eq!(
+        to_vec1_round(&ys.flatten_all()?, 4)?,
+        to_vec1_round(&expected.flatten_all()?, 4)?
+    );
+
+    // The weights have in_channels / groups input channels and mismatched checkpoints are
+    // rejected.
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let conv = candle_nn::conv2d_no_bias(6, 4, 3, cfg, vb.pp("conv"))?;
+    assert_eq!(conv.weight().dims(), [4, 3, 3, 3]);
+    assert!(conv.bias().is_none());
+    let ts = HashMap::from([("conv.weight".to_string(), weight)]);
+    let vb = VarBuilder::from_tensors(ts, DType::F32, dev);
+    assert!(candle_nn::conv2d_no_bias(4, 4, 3, Conv2dConfig::default(), vb.pp("conv")).is_err());
+    Ok(())
+}
+
+#[test]
+fn conv3d_layer() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let cfg = Conv3dConfig {
+        padding: 1,
+        stride: 2,
+        ..Default::default()
+    };
+    let conv = candle_nn::conv3d(3, 4, 3, cfg, vb.pp("conv"))?;
+    assert_eq!(conv.weight().dims(), [4, 3, 3, 3, 3]);
+    let xs = Tensor::arange(0f32, 2. * 3. * 4. * 6. * 6., dev)?
+        .affine(0.01, -1.)?
+        .sin()?
+        .reshape((2, 3, 4, 6, 6))?;
+    let ys = conv.forward(&xs)?;
+    assert_eq!(ys.dims(), [2, 4, 2, 3, 3]);
+
+    // A kernel of depth 1 applies a 2D convolution to each frame.
+    let conv = candle_nn::conv3d_no_bias(3, 4, 1, Default::default(), vb.pp("pointwise"))?;
+    let ys = conv.forward(&xs)?;
+    let weight = conv.weight().reshape((4, 3, 1, 1))?;
+    let frame = xs
+        .narrow(2, 1, 1)?
+        .squeeze(2)?
+        .conv2d(&weight, 0, 1, 1, 1)?;
+    assert_eq!(
+        to_vec1_round(&ys.narrow(2, 1, 1)?.flatten_all()?, 4)?,
+        to_vec1_round(&frame.flatten_all()?, 4)?
+    );
+    Ok(())
+}
+
+#[test]
+fn conv1d_layer() -> Result<()> {
+    let dev = &Device::Cpu;
+    let weight = Tensor::arange(0f32, 18., dev)?
+        .affine(0.2, -1.)?
+        .reshape((6, 1, 3))?;
+    let ts = HashMap::from([("conv.weight".to_string(), weight.clone())]);
+    let vb = VarBuilder::from_tensors(ts, DType::F32, dev);
+    let cfg = Conv1dConfig {
+        padding: 3,
+        stride: 1,
+        dilation: 3,
+        groups: 3,
+    };
+    let conv = candle_nn::conv1d_no_bias(3, 6, 3, cfg, vb.pp("conv"))?;
+    let xs = Tensor::arange(0f32, 36., dev)?.sin()?.reshape((2, 3, 6))?;
+    let ys = conv.forward(&xs)?;
+    assert_eq!(ys.dims(), [2, 6, 6]);
+
+    // A dilated kernel is equivalent to a kernel with dilation - 1 zeros between its elements.
+    let zeros = Tensor::zeros((6, 1, 2), DType::F32, dev)?;
+    let k = |i| weight.narrow(2, i, 1);
+    let dilated = Tensor::cat(&[k(0)?, zeros.clone(), k(1)?, zeros, k(2)?], 2)?;
+    let cfg = Conv1dConfig { dilation: 1, ..cfg };
+    let expected = Conv1d::new(dilated, None, cfg).forward(&xs)?;
+    assert_eq!(
+        to_vec1_round(&ys.flatten_all()?, 4)?,
+        to_vec1_round(&expected.flatten_all()?, 4)?
+    );
+
+    // WaveNet style stack, the receptive field of the last output of causal convs with a kernel
+    // size of 2 and dilations 1, 2, 4 spans the 8 last inputs.
+    let xs = Var::ones((1, 1, 16), DType::F32, dev)?;
+    let mut ys = xs.as_tensor().clone();
+    for dilation in [1, 2, 4] {
+        let cfg = Conv1dConfig {
+            dilation,
+            ..Default::default()
+        };
+        let w = Tensor::ones((1, 1, 2), DType::F32, dev)?;
+        ys = Conv1d::new(w, None, cfg).forward(&ys.pad_with_zeros(2, dilation, 0)?)?;
+    }
+    assert_eq!(ys.dims(), [1, 1, 16]);
+    let grads = ys.narrow(2, 15, 1)?.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap().flatten_all()?.to_vec1::<f32>()?;
+    assert_eq!(grad, [[0f32; 8], [1.; 8]].concat());
+    Ok(())
+}
+
+#[test]
+fn causal_conv1d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::arange(0f32, 2. * 3. * 12., dev)?
+        .affine(0.3, -1.)?
+        .sin()?
+        .reshape((2, 3, 12))?;
+    for (stride, dilation) in [(1, 1), (1, 2), (2, 1), (3, 2)] {
+        let varmap = VarMap::new();
+        let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+        let cfg = Conv1dConfig {
+            stride,
+            dilation,
+            ..Default::default()
+        };
+        let mut conv = candle_nn::causal_conv1d(3, 4, 3, cfg, vb)?;
+        assert_eq!(conv.left_padding(), 2 * dilation);
+        let ys = conv.forward(&xs)?;
+        assert_eq!(ys.dims(), [2, 4, (12 - 1) / stride + 1]);
+
+        // The outputs do not depend on the future inputs.
+        let shifted = Tensor::cat(&[xs.narrow(2, 0, 6)?, xs.narrow(2, 0, 6)?], 2)?;
+        let n = 5 / stride + 1;
+        assert_eq!(
+            to_vec1_round(&ys.narrow(2, 0, n)?.flatten_all()?, 4)?,
+            to_vec1_round(&conv.forward(&shifted)?.narrow(2, 0, n)?.flatten_all()?, 4)?
+        );
+
+        // Streaming over chunks of various sizes, including a single element, gives the same
+        // outputs as processing the whole sequence.
+        let mut steps = vec![];
+        let mut start = 0;
+        for len in [4, 1, 2, 5] {
+            steps.push(conv.step(&xs.narrow(2, start, len)?)?);
+            start += len;
+        }
+        let streamed = Tensor::cat(&steps, 2)?;
+        assert_eq!(
+            to_vec1_round(&streamed.flatten_all()?, 4)?,
+            to_vec1_round(&ys.flatten_all()?, 4)?,
+            "stride {stride} dilation {dilation}"
+        );
+        conv.reset_state();
+        assert!(conv.state().is_none());
+        let ys2 = conv.step(&xs)?;
+        assert_eq!(
+            to_vec1_round(&ys2.flatten_all()?, 4)?,
+            to_vec1_round(&ys.flatten_all()?, 4)?
+        );
+    }
+
+    let vb = VarBuilder::zeros(DType::F32, dev);
+    let cfg = Conv1dConfig {
+        padding: 1,
+        ..Default::default()
+    };
+    assert!(candle_nn::causal_conv1d(3, 4, 3, cfg, vb.clone()).is_err());
+    let cfg = Conv1dConfig {
+        stride: 4,
+        ..Default::default()
+    };
+    assert!(candle_nn::causal_conv1d(3, 4, 3, cfg, vb).is_err());
+    Ok(())
+}
+
+#[test]
+fn conv_transpose2d_layer() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    // The usual decoder upsampling block, doubling the spatial dimensions.
+    let cfg = ConvTranspose2dConfig {
+        padding: 1,
+        output_padding: 1,
+        stride: 2,
+        dilation: 1,
+    };
+    let conv = candle_nn::conv_transpose2d(4, 2, 3, cfg, vb.pp("up"))?;
+    assert_eq!(conv.weight().dims(), [4, 2, 3, 3]);
+    let xs = Tensor::arange(0f32, 120., dev)?
+        .sin()?
+        .reshape((2, 4, 3, 5))?;
+    let ys = conv.forward(&xs)?;
+    assert_eq!(ys.dims(), [2, 2, 6, 10]);
+
+    // The gradients flow to the weight and the bias.
+    let grads = ys.sqr()?.sum_all()?.backward()?;
+    let vars = varmap.all_vars();
+    assert_eq!(vars.len(), 2);
+    for var in vars.iter() {
+        let grad = grads.get(var).unwrap();
+        assert_eq!(grad.dims(), var.dims());
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, IndexOp, Result, Tensor};
+use candle_nn::{
+    embedding::make_positions, EmbeddingBag, EmbeddingBagMode, SinusoidalPositionalEmbedding,
+    SinusoidalPositionalEmbeddingConfig, VarBuilder, VarMap,
+};
+
+#[test]
+fn sinusoidal_embedding() -> Result<()> {
+    let dev = &Device::Cpu;
+    let emb = SinusoidalPositionalEmbedding::new(16, 8, Default::default(), DType::F32, dev)?;
+    let table = emb.embeddings().to_vec2::<f32>()?;
+    for (p, row) in table.iter().enumerate() {
+        for i in 0..4 {
+            let angle = p as f64 * 10000f64.powf(-(i as f64) / 4.);
+            assert!((row[i] - angle.sin() as f32).abs() < 1e-4);
+            assert!((row[i + 4] - angle.cos() as f32).abs() < 1e-4);
+        }
+    }
+    let ys = emb.forward(3, 5)?;
+    assert_eq!(ys.to_vec2::<f32>()?, &table[5..8]);
+
+    // Whisper and fairseq use `dim / 2 - 1` as the denominator.
+    let cfg = SinusoidalPositionalEmbeddingConfig {
+        shifted_frequencies: true,
+        padding_idx: Some(1),
+        ..Default::default()
+    };
+    let emb = SinusoidalPositionalEmbedding::new(16, 8, cfg, DType::F32, dev)?;
+    let table = emb.embeddings().to_vec2::<f32>()?;
+    assert_eq!(table.len(), 18);
+    assert_eq!(table[1], [0f32; 8]);
+    let angle = 3f64 * 10000f64.powf(-1. / 3.);
+    assert!((table[3][1] - angle.sin() as f32).abs() < 1e-4);
+    // The positions start after the padding index.
+    let ys = emb.forward(2, 0)?;
+    assert_eq!(ys.to_vec2::<f32>()?, &table[2..4]);
+
+    let ids = Tensor::new(&[[5u32, 6, 1, 1], [1, 7, 8, 9]], dev)?;
+    let positions = make_positions(&ids, 1, 0)?;
+    assert_eq!(positions.to_vec2::<u32>()?, [[2, 3, 1, 1], [1, 2, 3, 4]]);
+    let positions = make_positions(&ids, 1, 2)?;
+    assert_eq!(positions.to_vec2::<u32>()?, [[4, 5, 1, 1], [1, 4, 5, 6]]);
+    let ys = emb.forward_positions(&positions)?;
+    assert_eq!(ys.dims(), &[2, 4, 8]);
+    assert_eq!(ys.i((0, 2))?.to_vec1::<f32>()?, [0f32; 8]);
+    assert_eq!(ys.i((1, 1))?.to_vec1::<f32>()?, table[4]);
+    Ok(())
+}
+
+#[test]
+fn learned_position_embedding() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let emb = candle_nn::position_embedding(10, 4, 2, vb.pp("embed_positions"))?;
+    let weight = varmap.data().lock().unwrap()["embed_positions.weight"].clone();
+    assert_eq!(weight.dims(), &[12, 4]);
+    let ys = emb.forward(3, 1)?;
+    assert_eq!(
+        ys.to_vec2::<f32>()?,
+        weight.narrow(0, 3, 3)?.to_vec2::<f32>()?
+    );
+    let ys = emb.forward_positions(&Tensor::new(&[[0u32, 5]], dev)?)?;
+    assert_eq!(ys.dims(), &[1, 2, 4]);
+    assert_eq!(
+        ys.i((0, 1))?.to_vec1::<f32>()?,
+        weight.i(5)?.to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
+/* The expected values can be checked against PyTorch using the following snippet.
+import torch
+weight = torch.arange(10.).reshape(5, 2)
+indices, offsets = torch.tensor([0, 2, 4, 1, 3]), torch.tensor([0, 2, 2])
+for mode in ["sum", "mean", "max"]:
+    print(torch.nn.functional.embedding_bag(indices, weight, offsets, mode=mode))
+*/
+#[test]
+fn embedding_bag() -> Result<()> {
+    let dev = &Device::Cpu;
+    let weight = candle::Var::from_tensor(&Tensor::arange(0f32, 10., dev)?.reshape((5, 2))?)?;
+    let indices = Tensor::new(&[0u32, 2, 4, 1, 3], dev)?;
+    let offsets = Tensor::new(&[0u32, 2, 2], dev)?;
+    let bag = |mode| EmbeddingBag::new(weight.as_tensor().clone(), 2, mode);
+
+    let ys = bag(EmbeddingBagMode::Sum).forward(&indices, Some(&offsets))?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[4., 6.], [0., 0.], [16., 19.]]);
+    let ys = bag(EmbeddingBagMode::Mean).forward(&indices, Some(&offsets))?;
+    assert_eq!(
+        ys.to_vec2::<f32>()?,
+        [[2., 3.], [0., 0.], [16. / 3., 19. / 3.]]
+    );
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&weight).unwrap().to_vec2::<f32>()?;
+    let third = 1. / 3.;
+    assert_eq!(
+        grad,
+        [
+            [0.5, 0.5],
+            [third, third],
+            [0.5, 0.5],
+            [third, third],
+            [third, third]
+        ]
+    );
+    let ys = bag(EmbeddingBagMode::Max).forward(&indices, Some(&offsets))?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[4., 5.], [0., 0.], [8., 9.]]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&weight).unwrap().to_vec2::<f32>()?;
+    assert_eq!(grad, [[0., 0.], [0., 0.], [1., 1.], [0., 0.], [1., 1.]]);
+
+    // Without offsets, each row of the indices is a bag.
+    let indices = Tensor::new(&[[0u32, 1], [3, 4]], dev)?;
+    let ys = bag(EmbeddingBagMode::Mean).forward(&indices, None)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[1., 2.], [7., 8.]]);
+    let offsets = Tensor::new(&[0u32, 6], dev)?;
+    assert!(bag(EmbeddingBagMode::Sum)
+        .forward(&indices.flatten_all()?, Some(&offsets))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn tied_embedding() -> Result<()> {
+    use candle_nn::Module;
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev)
+        .with_fallback("lm_head.weight", "wte.weight");
+    let wte = candle_nn::embedding(5, 3, vb.pp("wte"))?;
+    let lm_head = candle_nn::linear_no_bias(3, 5, vb.pp("lm_head"))?;
+    // The fallback shares the variable rather than creating a new one.
+    assert_eq!(varmap.all_vars().len(), 1);
+    let tied = wte.tied_linear();
+    assert_eq!(tied.weight().id(), wte.embeddings().id());
+    assert_eq!(lm_head.weight().id(), wte.embeddings().id());
+
+    // The gradient accumulates the contributions of both uses.
+    let ids = Tensor::new(&[1u32, 3], dev)?;
+    let logits = tied.forward(&wte.forward(&ids)?)?;
+    let grads = logits.sum_all()?.backward()?;
+    let grad = grads.get(wte.embeddings()).expect("no grad for wte");
+    let ws = wte.embeddings();
+    // d/dW sum(E[ids] @ W.t()) = ones(vocab) x sum(E[ids]) + one_hot(ids).t() x sum_rows(W).
+    let row_sum = ws.sum_keepdim(0)?;
+    let from_head = ws
+        .i(1)?
+        .add(&ws.i(3)?)?
+        .unsqueeze(0)?
+        .broadcast_as((5, 3))?;
+    let one_hot = Tensor::new(&[[0f32], [1.], [0.], [1.], [0.]], dev)?;
+    let from_emb = one_hot.broadcast_mul(&row_sum)?;
+    let expected = (from_head + from_emb)?;
+    let diff = (grad - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-5);
+
+    // The stored weights are used when available.
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let _ = vb.get_with_hints((5, 3), "lm_head.weight", candle_nn::init::ONE)?;
+    let vb = vb.with_fallback("lm_head.weight", "wte.weight");
+    let wte = candle_nn::embedding(5, 3, vb.pp("wte"))?;
+    let lm_head = candle_nn::linear_no_bias(3, 5, vb.pp("lm_head"))?;
+    assert_ne!(lm_head.weight().id(), wte.embeddings().id());
+    assert_eq!(lm_head.weight().sum_all()?.to_scalar::<f32>()?, 15.);
+
+    // The names are full paths, the prefix of the var builder is not added to them.
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev)
+        .pp("model")
+        .with_fallback("model.lm_head.weight", "model.wte.weight");
+    let wte = candle_nn::embedding(5, 3, vb.pp("wte"))?;
+    let lm_head = candle_nn::linear_no_bias(3, 5, vb.pp("lm_head"))?;
+    assert_eq!(lm_head.weight().id(), wte.embeddings().id());
+    let mut names = varmap
+        .data()
+        .lock()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["model.wte.weight"]);
+    Ok(())
+}
@@ -0,0 +1,113 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::init::{self, NormalOrUniform};
+use candle_nn::{Init, VarBuilder, VarMap};
+
+fn mean_and_std(xs: &Tensor) -> Result<(f32, f32)> {
+    let xs = xs.flatten_all()?;
+    let mean = xs.mean_all()?;
+    let var = xs.broadcast_sub(&mean)?.sqr()?.mean_all()?;
+    Ok((mean.to_scalar()?, var.sqrt()?.to_scalar()?))
+}
+
+#[test]
+fn xavier() -> Result<()> {
+    let dev = &Device::Cpu;
+    // fan_in = 200, fan_out = 300 so the std is sqrt(2 / 500).
+    let std = (2f32 / 500.).sqrt();
+    let ws = init::DEFAULT_XAVIER_NORMAL.var((300, 200), DType::F32, dev)?;
+    let (m, s) = mean_and_std(ws.as_tensor())?;
+    assert!(m.abs() < 0.01, "{m}");
+    assert!((s - std).abs() < 0.1 * std, "{s} {std}");
+
+    let init = Init::Xavier {
+        dist: NormalOrUniform::Uniform,
+        gain: 2.,
+    };
+    let ws = init.var((300, 200), DType::F32, dev)?;
+    let bound = 2. * 3f32.sqrt() * std;
+    let max = ws.as_tensor().abs()?.flatten_all()?.max(0)?;
+    assert!(max.to_scalar::<f32>()? <= bound);
+    let (_, s) = mean_and_std(ws.as_tensor())?;
+    assert!((s - 2. * std).abs() < 0.1 * std, "{s} {std}");
+    Ok(())
+}
+
+#[test]
+fn truncated_normal() -> Result<()> {
+    let dev = &Device::Cpu;
+    let init = Init::TruncatedNormal {
+        mean: 1.,
+        stdev: 2.,
+        lo: 0.,
+        up: 1.5,
+    };
+    let ws = init.var((50, 40), DType::F32, dev)?;
+    let ws = ws.as_tensor().flatten_all()?.to_vec1::<f32>()?;
+    assert!(ws.iter().all(|&v| (0. ..=1.5).contains(&v)));
+    // Not everything got clamped to the bounds.
+    let at_bounds = ws.iter().filter(|&&v| v == 0. || v == 1.5).count();
+    assert!(at_bounds < 10, "{at_bounds}");
+
+    let init = Init::TruncatedNormal {
+        mean: 0.,
+        stdev: 1.,
+        lo: 1.,
+        up: 1.,
+    };
+    assert!(init.var(4, DType::F32, dev).is_err());
+    Ok(())
+}
+
+#[test]
+fn orthogonal() -> Result<()> {
+    let dev = &Device::Cpu;
+    for (rows, cols) in [(6, 4), (4, 6), (5, 5)] {
+        let ws = Init::Orthogonal { gain: 2. }.var((rows, cols), DType::F32, dev)?;
+        let ws = ws.as_tensor();
+        let (gram, n) = if rows < cols {
+            (ws.matmul(&ws.t()?)?, rows)
+        } else {
+            (ws.t()?.matmul(ws)?, cols)
+        };
+        let eye = (Tensor::eye(n, DType::F32, dev)? * 4.)?;
+        let diff = (gram - eye)?.abs()?.flatten_all()?.max(0)?;
+        assert!(diff.to_scalar::<f32>()? < 1e-4);
+    }
+    // Conv kernels are flattened to a matrix.
+    let ws = Init::Orthogonal { gain: 1. }.var((4, 2, 3, 3), DType::F32, dev)?;
+    let ws = ws.as_tensor().flatten_from(1)?;
+    let gram = ws.matmul(&ws.t()?)?;
+    let diff = (gram - Tensor::eye(4, DType::F32, dev)?)?
+        .abs()?
+        .flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-4);
+    assert!(Init::Orthogonal { gain: 1. }
+        .var(4, DType::F32, dev)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn var_builder_hints() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let ws = vb.get_with_hints((8, 8), "weight", Init::Orthogonal { gain: 1. })?;
+    let gram = ws.matmul(&ws.t()?)?;
+    let diff = (gram - Tensor::eye(8, DType::F32, dev)?)?
+        .abs()?
+        .flatten_all()?;
+    assert!(diff.max(0)?.to_scalar::<f32>()? < 1e-4);
+    let bs = vb.get_with_hints(8, "bias", init::DEFAULT_KAIMING_NORMAL)?;
+    assert_eq!(bs.dims(), &[8]);
+    // Already existing tensors are not re-initialized.
+    let ws2 = vb.get_with_hints((8, 8), "weight", init::ZERO)?;
+    assert_eq!(ws.to_vec2::<f32>()?, ws2.to_vec2::<f32>()?);
+    Ok(())
+}
@@ -5,8 +5,8 @@ extern crate intel_mkl_src;
 extern crate accelerate_src;
 
 use anyhow::Result;
-use candle::{test_utils, Device, Tensor};
-use candle_nn::{LayerNorm, Module};
+use candle::{test_utils, DType, Device, Tensor};
+use candle_nn::{LayerNorm, Module, RmsNorm, VarBuilder, VarMap};
 
 #[test]
 fn layer_norm() -> Result<()> {
@@ -53,3 +53,99 @@ fn layer_norm() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn rms_norm_with_offset() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Tensor::new(&[0f32, 1.], device)?;
+    let rms = RmsNorm::new_with_offset(w, 1.0, 1e-8)?;
+    let xs = Tensor::new(&[[[3f32, 4.], [-1., 1.]]], device)?;
+    let expected = [[[0.8485f32, 2.2627], [-1., 2.]]];
+    let ys = rms.forward(&xs)?;
+    assert_eq!(test_utils::to_vec3_round(&ys, 4)?, expected);
+    // Non-contiguous inputs use the non-fused path.
+    let ys = rms.forward(&xs.transpose(1, 2)?.contiguous()?.transpose(1, 2)?)?;
+    assert_eq!(test_utils::to_vec3_round(&ys, 4)?, expected);
+
+    // The initial scaling is 1.
+    let vm = VarMap::new();
+    let vb = VarBuilder::from_varmap(&vm, DType::F32, device);
+    let rms = candle_nn::rms_norm_with_offset(2, 1.0, 1e-8, vb)?;
+    let ys = rms.forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&ys, 4)?,
+        [[[0.8485f32, 1.1314], [-1., 1.]]]
+    );
+    let weight = vm.data().lock().unwrap()["weight"]
+        .as_tensor()
+        .to_vec1::<f32>()?;
+    assert_eq!(weight, [0., 0.]);
+    Ok(())
+}
+
+#[test]
+fn layer_norm_variants() -> Result<()> {
+    use candle_nn::LayerNormConfig;
+    use std::collections::HashMap;
+
+    let device = &Device::Cpu;
+    let xs = Tensor::new(&[[[1f32, 2., 3.], [4., 5., 9.]]], device)?;
+    let expected = LayerNorm::new_no_affine(3, 1e-5, DType::F32, device)?.forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&expected, 4)?,
+        [[[-1.2247, 0.0, 1.2247], [-0.9258, -0.4629, 1.3887]]]
+    );
+
+    // Without affine parameters, nothing gets created.
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let cfg = LayerNormConfig {
+        elementwise_affine: false,
+        ..Default::default()
+    };
+    let ln = candle_nn::layer_norm(3, cfg, vb.pp("ln"))?;
+    assert!(ln.bias().is_none());
+    assert!(varmap.all_vars().is_empty());
+    let ys = ln.forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&(ys - &expected)?, 4)?,
+        [[[0f32; 3]; 2]]
+    );
+
+    // Without bias, only the weight gets created.
+    let cfg = LayerNormConfig {
+        affine: false,
+        ..Default::default()
+    };
+    let ln = candle_nn::layer_norm(3, cfg, vb.pp("ln_no_bias"))?;
+    assert!(ln.bias().is_none());
+    assert_eq!(varmap.all_vars().len(), 1);
+
+    // Loading from a checkpoint using the gamma/beta naming.
+    let weight = Tensor::new(&[1f32, 2., 3.], device)?;
+    let bias = Tensor::new(&[0.5f32, 0., -0.5], device)?;
+    let ts = HashMap::from([
+        ("ln.gamma".to_string(), weight.clone()),
+        ("ln.beta".to_string(), bias.clone()),
+        ("ln_no_bias.weight".to_string(), weight.clone()),
+    ]);
+    let vb = VarBuilder::from_tensors(ts, DType::F32, device);
+    let ln = candle_nn::layer_norm(3, 1e-5, vb.pp("ln"))?;
+    let ys = ln.forward(&xs)?;
+    let ys2 = LayerNorm::new(weight.clone(), bias, 1e-5).forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&(ys - ys2)?, 4)?,
+        [[[0f32; 3]; 2]]
+    );
+    // A missing bias is tolerated, a missing weight is not.
+    let ln = candle_nn::layer_norm(3, 1e-5, vb.pp("ln_no_bias"))?;
+    assert!(ln.bias().is_none());
+    let ys = ln.forward(&xs)?;
+    let ys2 = LayerNorm::new_no_bias(weight, 1e-5).forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&(ys - ys2)?, 4)?,
+        [[[0f32; 3]; 2]]
+    );
+    assert!(candle_nn::layer_norm(3, 1e-5, vb.pp("missing")).is_err());
+    Ok(())
+}
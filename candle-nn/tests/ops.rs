@@ -4,7 +4,7 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::{test_device, test_utils::to_vec3_round, Device, Result, Tensor};
+use candle::{test_device, test_utils::to_vec3_round, DType, Device, Result, Tensor};
 
 fn softmax(device: &Device) -> Result<()> {
     let data = &[[[3f32, 1., 4.], [1., 5., 9.]], [[2., 1., 7.], [8., 2., 8.]]];
@@ -72,8 +72,23 @@ fn rms_norm(device: &Device) -> Result<()> {
             [[0.4714, 0.4714, 4.9497], [1.206, 0.603, 3.6181]]
         ]
     );
-    let diff = (t - t2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+    let diff = (&t - t2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
     assert!(diff < 1e-5);
+    if device.is_cpu() {
+        // The rows are normalized on the thread pool configured for the cpu backend.
+        let t2 =
+            candle::utils::with_num_threads(2, || candle_nn::ops::rms_norm(&tensor, &alpha, 1e-5))?;
+        assert_eq!(to_vec3_round(&t, 4)?, to_vec3_round(&t2, 4)?);
+    }
+    if !device.is_metal() {
+        let tensor = tensor.to_dtype(DType::F64)?;
+        let alpha = alpha.to_dtype(DType::F64)?;
+        let t = candle_nn::ops::rms_norm(&tensor, &alpha, 1e-5)?;
+        let t2 = candle_nn::ops::rms_norm_slow(&tensor, &alpha, 1e-5)?;
+        assert_eq!(t.dtype(), DType::F64);
+        let diff = (t - t2)?.abs()?.sum_all()?.to_vec0::<f64>()?;
+        assert!(diff < 1e-10);
+    }
     Ok(())
 }
 
@@ -98,8 +113,99 @@ fn layer_norm(device: &Device) -> Result<()> {
             [[-0.008, -1.778, 3.991], [1.2071, -2.8284, 1.9213]]
         ]
     );
-    let diff = (t - t2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+    let diff = (&t - t2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
     assert!(diff < 1e-5);
+    if device.is_cpu() {
+        let t2 = candle::utils::with_num_threads(2, || {
+            candle_nn::ops::layer_norm(&tensor, &alpha, &beta, 1e-5)
+        })?;
+        assert_eq!(to_vec3_round(&t, 4)?, to_vec3_round(&t2, 4)?);
+    }
+    if !device.is_metal() {
+        let tensor = tensor.to_dtype(DType::F64)?;
+        let alpha = alpha.to_dtype(DType::F64)?;
+        let beta = beta.to_dtype(DType::F64)?;
+        let t = candle_nn::ops::layer_norm(&tensor, &alpha, &beta, 1e-5)?;
+        let t2 = candle_nn::ops::layer_norm_slow(&tensor, &alpha, &beta, 1e-5)?;
+        assert_eq!(t.dtype(), DType::F64);
+        let diff = (t - t2)?.abs()?.sum_all()?.to_vec0::<f64>()?;
+        assert!(diff < 1e-10);
+    }
+    Ok(())
+}
+
+fn group_norm(device: &Device) -> Result<()> {
+    if device.is_metal() {
+        return Ok(());
+    }
+    let xs = Tensor::arange(0f32, 96., device)?
+        .affine(0.37, -5.)?
+        .sin()?
+        .reshape((2, 6, 2, 4))?;
+    let alpha = candle::Var::new(&[1f32, 2., -0.5, 0.3, 1.5, 0.7], device)?;
+    let beta = candle::Var::new(&[0.5f32, 0., -0.2, 1., 0.1, -1.], device)?;
+    let xs = candle::Var::from_tensor(&xs)?;
+    for num_groups in [1, 2, 3, 6] {
+        let t = candle_nn::ops::group_norm(&xs, num_groups, &alpha, &beta, 1e-5)?;
+        let t2 = candle_nn::ops::group_norm_slow(&xs, num_groups, &alpha, &beta, 1e-5)?;
+        let diff = (&t - &t2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+        assert!(diff < 1e-4, "{diff}");
+
+        let ys = Tensor::arange(0f32, 96., device)?
+            .cos()?
+            .reshape(t.shape())?;
+        let grads = (t * &ys)?.sum_all()?.backward()?;
+        let grads2 = (t2 * &ys)?.sum_all()?.backward()?;
+        for var in [&xs, &alpha, &beta] {
+            let g = grads.get(var).unwrap();
+            let g2 = grads2.get(var).unwrap();
+            let diff = (g - g2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+            assert!(diff < 1e-4, "{diff}");
+        }
+    }
+
+    let xs = xs.flatten_from(2)?.to_dtype(DType::F64)?;
+    let alpha = alpha.to_dtype(DType::F64)?;
+    let beta = beta.to_dtype(DType::F64)?;
+    let t = candle_nn::ops::group_norm(&xs, 2, &alpha, &beta, 1e-5)?;
+    let t2 = candle_nn::ops::group_norm_slow(&xs, 2, &alpha, &beta, 1e-5)?;
+    assert_eq!(t.dtype(), DType::F64);
+    let diff = (t - t2)?.abs()?.sum_all()?.to_vec0::<f64>()?;
+    assert!(diff < 1e-10);
+    Ok(())
+}
+
+#[test]
+fn dropout() -> Result<()> {
+    use candle::ModuleT;
+    let dev = &Device::Cpu;
+    let xs = Tensor::arange(1f32, 1001., dev)?.reshape((10, 4, 25))?;
+    let ys = candle_nn::Dropout::new(0.5).forward_t(&xs, true)?;
+    // The kept values are scaled by 1 / (1 - p).
+    let kept = ys.ne(0f32)?.to_dtype(DType::F32)?;
+    assert_eq!(
+        ys.to_vec3::<f32>()?,
+        (&xs * 2.)?.mul(&kept)?.to_vec3::<f32>()?
+    );
+    let n_kept = kept.sum_all()?.to_vec0::<f32>()?;
+    assert!((300. ..700.).contains(&n_kept), "{n_kept}");
+    let ys = candle_nn::Dropout::new(0.5).forward_t(&xs, false)?;
+    assert_eq!(ys.to_vec3::<f32>()?, xs.to_vec3::<f32>()?);
+
+    let ys = candle_nn::Dropout2d::new(0.25).forward_t(&xs, true)?;
+    // Each channel is either dropped entirely or kept entirely.
+    let kept = ys.ne(0f32)?.to_dtype(DType::F32)?.sum_keepdim(2)?;
+    for v in kept.flatten_all()?.to_vec1::<f32>()? {
+        assert!(v == 0. || v == 25., "{v}")
+    }
+    let kept = (kept / 25.)?;
+    assert_eq!(
+        to_vec3_round(&ys, 3)?,
+        to_vec3_round(&(&xs / 0.75)?.broadcast_mul(&kept)?, 3)?
+    );
+    let ys = candle_nn::Dropout2d::new(0.25).forward_t(&xs, false)?;
+    assert_eq!(ys.to_vec3::<f32>()?, xs.to_vec3::<f32>()?);
+    assert!(candle_nn::ops::dropout2d(&xs, 1.).is_err());
     Ok(())
 }
 
@@ -196,6 +302,113 @@ fn rope_thd(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn rotary_embedding(device: &Device) -> Result<()> {
+    use candle_nn::rotary_emb::{RopeScaling, RotaryEmbedding, RotaryEmbeddingConfig};
+
+    let (b_size, num_head, seq_len, head_dim) = (2, 3, 10, 16);
+    let xs = Tensor::randn(0f32, 1., (b_size, num_head, seq_len, head_dim), device)?;
+    let mut cfg = RotaryEmbeddingConfig::new(head_dim, 10000., 32);
+    cfg.rotary_dim = Some(8);
+    let emb = RotaryEmbedding::new(&cfg, DType::F32, device)?;
+    assert_eq!(emb.cos().dims(), &[32, 4]);
+    let ys = emb.apply(&xs, 0)?;
+    let cos = emb.cos().narrow(0, 0, seq_len)?;
+    let sin = emb.sin().narrow(0, 0, seq_len)?;
+    let rot = candle_nn::rotary_emb::rope_slow(&xs.narrow(3, 0, 8)?, &cos, &sin)?;
+    let diff = (ys.narrow(3, 0, 8)? - rot)?
+        .abs()?
+        .sum_all()?
+        .to_vec0::<f32>()?;
+    assert!(diff < 1e-4);
+    let diff = (ys.narrow(3, 8, 8)? - xs.narrow(3, 8, 8)?)?
+        .abs()?
+        .sum_all()?
+        .to_vec0::<f32>()?;
+    assert_eq!(diff, 0.);
+    // Applying the embeddings with an offset matches the full sequence.
+    let ys_offset = emb.apply(&xs.narrow(2, 3, 7)?, 3)?;
+    let diff = (ys.narrow(2, 3, 7)? - ys_offset)?
+        .abs()?
+        .sum_all()?
+        .to_vec0::<f32>()?;
+    assert!(diff < 1e-4);
+
+    let cfg = RotaryEmbeddingConfig::new(head_dim, 10000., 32);
+    let cos = RotaryEmbedding::new(&cfg, DType::F32, device)?
+        .cos()
+        .to_vec2::<f32>()?;
+    let scaled_cos = |scaling| -> Result<Vec<Vec<f32>>> {
+        let cfg = RotaryEmbeddingConfig { scaling, ..cfg };
+        RotaryEmbedding::new(&cfg, DType::F32, device)?
+            .cos()
+            .to_vec2::<f32>()
+    };
+    // Linear scaling interpolates the positions.
+    let linear = scaled_cos(RopeScaling::Linear { factor: 2. })?;
+    for i in 0..8 {
+        assert!((linear[6][i] - cos[3][i]).abs() < 1e-5);
+    }
+    // Ntk scaling keeps the highest frequency and divides the lowest one by the factor.
+    let ntk = scaled_cos(RopeScaling::Ntk { factor: 4. })?;
+    let lowest = 10000f64.powf(-14. / 16.) / 4.;
+    for p in 0..32 {
+        assert!((ntk[p][0] - cos[p][0]).abs() < 1e-5);
+        assert!((ntk[p][7] - (p as f64 * lowest).cos() as f32).abs() < 1e-4);
+    }
+    // YaRN extrapolates the high frequencies, interpolates the low ones, and scales the result.
+    let yarn = scaled_cos(RopeScaling::Yarn {
+        factor: 4.,
+        original_max_position_embeddings: 8,
+        beta_fast: 32.,
+        beta_slow: 1.,
+    })?;
+    let mscale = 0.1 * 4f32.ln() + 1.;
+    let lowest = 10000f64.powf(-14. / 16.) / 4.;
+    for p in 0..32 {
+        assert!((yarn[p][0] - cos[p][0] * mscale).abs() < 1e-5);
+        let expected = (p as f64 * lowest).cos() as f32 * mscale;
+        assert!((yarn[p][7] - expected).abs() < 1e-4);
+    }
+    Ok(())
+}
+
+/* The expected values can be checked against PyTorch using the following snippet.
+import torch
+xs = torch.arange(16.).reshape(1, 4, 2, 2)
+print(torch.nn.functional.pixel_shuffle(xs, 2))
+*/
+fn pixel_shuffle(device: &Device) -> Result<()> {
+    use candle_nn::{Module, PixelShuffle, PixelUnshuffle};
+
+    let xs = Tensor::arange(0f32, 16., device)?.reshape((1, 4, 2, 2))?;
+    let ys = PixelShuffle::new(2).forward(&xs)?;
+    assert_eq!(ys.dims(), &[1, 1, 4, 4]);
+    assert_eq!(
+        ys.squeeze(0)?.squeeze(0)?.to_vec2::<f32>()?,
+        [
+            [0., 4., 1., 5.],
+            [8., 12., 9., 13.],
+            [2., 6., 3., 7.],
+            [10., 14., 11., 15.]
+        ]
+    );
+    let xs2 = PixelUnshuffle::new(2).forward(&ys)?;
+    assert_eq!(
+        xs2.squeeze(0)?.to_vec3::<f32>()?,
+        xs.squeeze(0)?.to_vec3::<f32>()?
+    );
+
+    let xs = Tensor::randn(0f32, 1., (2, 18, 3, 5), device)?;
+    let ys = candle_nn::ops::pixel_shuffle(&xs, 3)?;
+    assert_eq!(ys.dims(), &[2, 2, 9, 15]);
+    let xs2 = candle_nn::ops::pixel_unshuffle(&ys, 3)?;
+    let diff = (xs - xs2)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+    assert_eq!(diff, 0.);
+    assert!(candle_nn::ops::pixel_shuffle(&ys, 2).is_err());
+    assert!(candle_nn::ops::pixel_unshuffle(&ys, 2).is_err());
+    Ok(())
+}
+
 fn sigmoid(device: &Device) -> Result<()> {
     let data = &[[[3f32, 1., 4.], [1., 5., 9.]], [[2., 1., 7.], [8., 2., 8.]]];
     let tensor = Tensor::new(data, device)?;
@@ -206,10 +419,67 @@ fn sigmoid(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn activations(device: &Device) -> Result<()> {
+    use candle::test_utils::to_vec1_round;
+    use candle_nn::{ops, Activation, Module};
+    let xs = Tensor::new(&[-3f32, -1., -0.5, 0., 0.5, 2., 4.], device)?;
+    assert_eq!(
+        to_vec1_round(&ops::softplus(&xs)?, 4)?,
+        [0.0486, 0.3133, 0.4741, 0.6931, 0.9741, 2.1269, 4.0182]
+    );
+    assert_eq!(
+        to_vec1_round(&Activation::Mish.forward(&xs)?, 4)?,
+        [-0.1456, -0.3034, -0.2207, 0.0, 0.3752, 1.944, 3.9974]
+    );
+    assert_eq!(
+        to_vec1_round(&Activation::HardSwish.forward(&xs)?, 4)?,
+        [0.0, -0.3333, -0.2083, 0.0, 0.2917, 1.6667, 4.0]
+    );
+    let gated = Tensor::new(&[-3f32, -1., -0.5, 0.5, 2., 4.], device)?;
+    assert_eq!(
+        to_vec1_round(&Activation::Glu.forward(&gated)?, 4)?,
+        [-1.8674, -0.8808, -0.491]
+    );
+    assert_eq!(
+        to_vec1_round(&Activation::Geglu.forward(&gated)?, 4)?,
+        [-0.002, -0.3173, -0.6171]
+    );
+    // Softplus does not overflow and its gradient is the sigmoid.
+    let xs = candle::Var::new(&[-100f32, -1., 0., 3., 100.], device)?;
+    let ys = ops::softplus(&xs)?;
+    assert_eq!(to_vec1_round(&ys, 4)?, [0.0, 0.3133, 0.6931, 3.0486, 100.0]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).expect("no grad for xs");
+    assert_eq!(
+        to_vec1_round(grad, 4)?,
+        to_vec1_round(&ops::sigmoid(&xs)?, 4)?
+    );
+    Ok(())
+}
+
 test_device!(ropei, ropei_cpu, ropei_gpu, ropei_metal);
 test_device!(rope, rope_cpu, rope_gpu, rope_metal);
 test_device!(rope_thd, rope_thd_cpu, rope_thd_gpu, rope_thd_metal);
+test_device!(
+    rotary_embedding,
+    rotary_emb_cpu,
+    rotary_emb_gpu,
+    rotary_emb_metal
+);
 test_device!(softmax, softmax_cpu, softmax_gpu, softmax_metal);
 test_device!(rms_norm, rms_norm_cpu, rms_norm_gpu, rms_norm_metal);
 test_device!(layer_norm, ln_cpu, ln_gpu, ln_metal);
+test_device!(group_norm, gn_cpu, gn_gpu, gn_metal);
 test_device!(sigmoid, sigmoid_cpu, sigmoid_gpu, sigmoid_metal);
+test_device!(
+    activations,
+    activations_cpu,
+    activations_gpu,
+    activations_metal
+);
+test_device!(
+    pixel_shuffle,
+    pixel_shuffle_cpu,
+    pixel_shuffle_gpu,
+    pixel_shuffle_metal
+);
@@ -4,7 +4,7 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::test_utils::{to_vec0_round, to_vec2_round};
+use candle::test_utils::{to_vec0_round, to_vec1_round, to_vec2_round};
 
 use anyhow::Result;
 use candle::{DType, Device, Tensor, Var};
@@ -158,3 +158,166 @@ fn adamw_linear_regression_varmap() -> Result<()> {
     assert_eq!(to_vec0_round(lin.bias().unwrap(), 4)?, 1.);
     Ok(())
 }
+
+#[test]
+fn clip_grad() -> Result<()> {
+    let w = Var::new(&[[3f32, 0.], [0., 0.]], &Device::Cpu)?;
+    let b = Var::new(&[0f32, 4.], &Device::Cpu)?;
+    let c = Tensor::new(&[100f32, 100.], &Device::Cpu)?;
+    // The gradients are 3 for w[0, 0] and 4 for b[1], so the total norm is 5.
+    let loss = |w: &Var, b: &Var| -> Result<Tensor> {
+        let l = (w.sqr()?.sum_all()? * 0.5)?;
+        let l = (l + (b.sqr()?.broadcast_add(&c)?.sum_all()? * 0.5)?)?;
+        Ok(l)
+    };
+    let vars = [w.clone(), b.clone()];
+
+    let mut grads = loss(&w, &b)?.backward()?;
+    let norm = candle_nn::optim::clip_grad_norm(&mut grads, &vars, 1.)?;
+    assert_eq!(
+        to_vec0_round(&Tensor::new(norm as f32, &Device::Cpu)?, 4)?,
+        5.
+    );
+    assert_eq!(
+        to_vec2_round(grads.get(&w).unwrap(), 4)?,
+        [[0.6, 0.], [0., 0.]]
+    );
+    assert_eq!(to_vec1_round(grads.get(&b).unwrap(), 4)?, [0., 0.8]);
+
+    // The gradients are left unchanged when their norm is below max_norm.
+    let mut grads = loss(&w, &b)?.backward()?;
+    let norm = candle_nn::optim::clip_grad_norm(&mut grads, &vars, 10.)?;
+    assert_eq!(norm, 5.);
+    assert_eq!(grads.get(&b).unwrap().to_vec1::<f32>()?, [0., 4.]);
+
+    let mut grads = loss(&w, &b)?.backward()?;
+    candle_nn::optim::clip_grad_value(&mut grads, &vars, 3.5)?;
+    assert_eq!(
+        grads.get(&w).unwrap().to_vec2::<f32>()?,
+        [[3., 0.], [0., 0.]]
+    );
+    assert_eq!(grads.get(&b).unwrap().to_vec1::<f32>()?, [0., 3.5]);
+    Ok(())
+}
+
+#[test]
+fn sparse_embedding_optim() -> Result<()> {
+    use candle_nn::Embedding;
+    let dev = &Device::Cpu;
+    let init = Tensor::arange(0f32, 12., dev)?.reshape((6, 2))?;
+    let ids = Tensor::new(&[[4u32, 1], [4, 0]], dev)?;
+    let run = |sparse: bool, use_adamw: bool| -> Result<Tensor> {
+        let emb = Var::from_tensor(&init)?;
+        let layer = Embedding::new(emb.as_tensor().clone(), 2).with_sparse_grad(sparse);
+        let mut sgd = SGD::new(vec![emb.clone()], 0.1)?;
+        let params = ParamsAdamW {
+            lr: 0.1,
+            weight_decay: 0.,
+            ..Default::default()
+        };
+        let mut adamw = AdamW::new(vec![emb.clone()], params)?;
+        for _step in 0..3 {
+            let loss = layer.forward(&ids)?.sin()?.sum_all()?;
+            let mut grads = loss.backward()?;
+            assert_eq!(grads.get_sparse(&emb).is_some(), sparse);
+            candle_nn::optim::clip_grad_norm(&mut grads, std::slice::from_ref(&emb), 1.)?;
+            if use_adamw {
+                adamw.step(&grads)?
+            } else {
+                sgd.step(&grads)?
+            };
+        }
+        Ok(emb.as_tensor().clone())
+    };
+    // Without weight decay, the sparse updates match the dense ones. This would not be the case
+    // for AdamW with rows that have been used previously but not in the current step.
+    for use_adamw in [false, true] {
+        let dense = run(false, use_adamw)?;
+        let sparse = run(true, use_adamw)?;
+        assert_eq!(to_vec2_round(&sparse, 4)?, to_vec2_round(&dense, 4)?);
+    }
+    // The rows that are not looked up are left unchanged.
+    let sparse = run(true, true)?;
+    assert_eq!(
+        sparse.narrow(0, 2, 2)?.to_vec2::<f32>()?,
+        [[4., 5.], [6., 7.]]
+    );
+    Ok(())
+}
+
+// The sparse updates are applied in place, the rows without gradients are not written to and
+// the parameters keep using the same buffer.
+#[test]
+fn sparse_embedding_optim_inplace() -> Result<()> {
+    use candle::{CpuStorage, Storage};
+    use candle_nn::Embedding;
+    let dev = &Device::Cpu;
+    let data_ptr = |t: &Tensor| match &*t.storage_and_layout().0 {
+        Storage::Cpu(CpuStorage::F32(data)) => data.as_ptr(),
+        _ => unreachable!(),
+    };
+    let ids = Tensor::new(&[[4u32, 1], [4, 0]], dev)?;
+    for use_adamw in [false, true] {
+        let emb = Var::from_tensor(&Tensor::arange(0f32, 12., dev)?.reshape((6, 2))?)?;
+        let ptr = data_ptr(&emb);
+        let used = emb.narrow(0, 4, 1)?;
+        let layer = Embedding::new(emb.as_tensor().clone(), 2).with_sparse_grad(true);
+        let params = ParamsAdamW {
+            lr: 0.1,
+            ..Default::default()
+        };
+        let mut sgd = SGD::new(vec![emb.clone()], 0.1)?;
+        let mut adamw = AdamW::new(vec![emb.clone()], params)?;
+        for _step in 0..3 {
+            let grads = layer.forward(&ids)?.sin()?.sum_all()?.backward()?;
+            if use_adamw {
+                adamw.step(&grads)?
+            } else {
+                sgd.step(&grads)?
+            };
+        }
+        assert_eq!(data_ptr(&emb), ptr);
+        assert_ne!(used.to_vec2::<f32>()?, [[8., 9.]]);
+        assert_eq!(emb.narrow(0, 2, 2)?.to_vec2::<f32>()?, [[4., 5.], [6., 7.]]);
+        assert_eq!(emb.narrow(0, 5, 1)?.to_vec2::<f32>()?, [[10., 11.]]);
+    }
+    Ok(())
+}
+
+#[test]
+fn frozen_vars_optim() -> Result<()> {
+    use candle_nn::{VarBuilder, VarMap};
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let backbone = candle_nn::linear(2, 4, vb.pp("backbone"))?;
+    let head = candle_nn::linear(4, 1, vb.pp("head"))?;
+    assert_eq!(varmap.freeze(|name| name.starts_with("backbone.")), 2);
+    assert_eq!(varmap.trainable_vars().len(), 2);
+    assert_eq!(
+        varmap.filtered_vars(|name| name.ends_with(".bias")).len(),
+        2
+    );
+
+    let backbone_ws = backbone.weight().to_vec2::<f32>()?;
+    let head_ws = head.weight().to_vec2::<f32>()?;
+    // Even if the frozen variables get passed to the optimizer, they are not updated.
+    let params = ParamsAdamW {
+        lr: 0.1,
+        ..Default::default()
+    };
+    let mut opt = AdamW::new(varmap.all_vars(), params)?;
+    let xs = Tensor::new(&[[1f32, 2.], [3., -1.]], dev)?;
+    for _step in 0..3 {
+        let loss = head.forward(&backbone.forward(&xs)?)?.sqr()?.sum_all()?;
+        opt.backward_step(&loss)?;
+    }
+    assert_eq!(backbone.weight().to_vec2::<f32>()?, backbone_ws);
+    assert_ne!(head.weight().to_vec2::<f32>()?, head_ws);
+
+    assert_eq!(varmap.unfreeze(|_| true), 4);
+    let loss = head.forward(&backbone.forward(&xs)?)?.sqr()?.sum_all()?;
+    opt.backward_step(&loss)?;
+    assert_ne!(backbone.weight().to_vec2::<f32>()?, backbone_ws);
+    Ok(())
+}
@@ -0,0 +1,151 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::{Module, ModuleList, Parameters, VarBuilder, VarMap};
+
+fn names<P: Parameters>(p: &P) -> Vec<String> {
+    p.named_parameters().into_iter().map(|(n, _)| n).collect()
+}
+
+fn sorted_varmap_names(varmap: &VarMap) -> Vec<String> {
+    let mut names: Vec<_> = varmap.data().lock().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn layer_parameters() -> Result<()> {
+    let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+    let linear = candle_nn::linear(4, 3, vb.clone())?;
+    assert_eq!(names(&linear), ["weight", "bias"]);
+    assert_eq!(linear.num_parameters(), 15);
+    let linear = candle_nn::linear_no_bias(4, 3, vb.clone())?;
+    assert_eq!(names(&linear), ["weight"]);
+
+    let conv = candle_nn::conv2d(2, 4, 3, Default::default(), vb.clone())?;
+    assert_eq!(names(&conv), ["weight", "bias"]);
+    assert_eq!(conv.num_parameters(), 4 * 2 * 9 + 4);
+
+    let ln = candle_nn::layer_norm(8, 1e-5, vb.clone())?;
+    assert_eq!(names(&ln), ["weight", "bias"]);
+    let rms = candle_nn::rms_norm(8, 1e-5, vb.clone())?;
+    assert_eq!(names(&rms), ["weight"]);
+    let ln = candle_nn::LayerNorm::new_no_affine(8, 1e-5, DType::F32, &Device::Cpu)?;
+    assert_eq!(ln.num_parameters(), 0);
+
+    let bn = candle_nn::batch_norm(3, 1e-5, vb.clone())?;
+    assert_eq!(
+        names(&bn),
+        ["weight", "bias", "running_mean", "running_var"]
+    );
+
+    let lstm = candle_nn::rnn::multi_layer_lstm(4, 5, 2, true, Default::default(), vb.clone())?;
+    let lstm_names = names(&lstm);
+    assert_eq!(lstm_names.len(), 16);
+    assert_eq!(lstm_names[0], "weight_ih_l0");
+    assert!(lstm_names.contains(&"bias_hh_l1_reverse".to_string()));
+    let gru = candle_nn::rnn::gru_cell(4, 5, Default::default(), vb.clone())?;
+    assert_eq!(
+        names(&gru),
+        ["weight_ih", "weight_hh", "bias_ih", "bias_hh"]
+    );
+    Ok(())
+}
+
+#[test]
+fn varmap_names_match() -> Result<()> {
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    let mut list = ModuleList::new();
+    list.push(candle_nn::embedding(10, 4, vb.pp("0"))?);
+    list.push(candle_nn::layer_norm(4, 1e-5, vb.pp("1"))?);
+    list.push(candle_nn::linear(4, 6, vb.pp("2"))?);
+    assert_eq!(list.len(), 3);
+    let mut visited = names(&list);
+    visited.sort();
+    assert_eq!(visited, sorted_varmap_names(&varmap));
+    let data = varmap.data().lock().unwrap();
+    for (name, t) in list.named_parameters() {
+        assert_eq!(t.dims(), data[&name].dims(), "{name}");
+    }
+
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    let cfg = candle_nn::MultiHeadAttentionConfig {
+        num_heads: 2,
+        num_kv_heads: 1,
+        head_dim: None,
+        bias: false,
+    };
+    let mha = candle_nn::multi_head_attention(4, cfg, vb.pp("attn"))?;
+    let gru = candle_nn::rnn::multi_layer_gru(4, 6, 2, true, Default::default(), vb.pp("gru"))?;
+    let mut visited = vec![];
+    candle_nn::parameters::visit_prefixed("attn", &mha, &mut |n, _| visited.push(n.to_string()));
+    candle_nn::parameters::visit_prefixed("gru", &gru, &mut |n, _| visited.push(n.to_string()));
+    visited.sort();
+    assert_eq!(visited, sorted_varmap_names(&varmap));
+    Ok(())
+}
+
+#[test]
+fn module_list_to_dtype() -> Result<()> {
+    let dev = &Device::Cpu;
+    let vb = VarBuilder::zeros(DType::F32, dev);
+    let mut list = ModuleList::new();
+    list.push(candle_nn::linear(3, 2, vb.pp("0"))?);
+    list.push(candle_nn::linear(2, 4, vb.pp("1"))?);
+    list.to_dtype(DType::F64)?;
+    list.visit_parameters(&mut |name, t| assert_eq!(t.dtype(), DType::F64, "{name}"));
+    let xs = Tensor::zeros((1, 3), DType::F64, dev)?;
+    let ys = list.iter().try_fold(xs, |xs, l| l.forward(&xs))?;
+    assert_eq!(ys.dims(), &[1, 4]);
+    assert_eq!(ys.dtype(), DType::F64);
+    assert_eq!(list[1].num_parameters(), 12);
+    assert!(list.get(2).is_none());
+
+    // The zero state of recurrent layers follows the dtype of the weights.
+    use candle_nn::rnn::RNN;
+    let mut lstm = candle_nn::rnn::lstm(2, 4, Default::default(), vb)?;
+    assert_eq!(lstm.num_parameters(), 4 * 4 * 6 + 2 * 16);
+    lstm.to_dtype(DType::F64)?;
+    let xs = Tensor::zeros((1, 5, 2), DType::F64, dev)?;
+    let states = lstm.seq(&xs)?;
+    assert_eq!(states[4].h().dtype(), DType::F64);
+    Ok(())
+}
+
+#[test]
+fn sequential_parameters() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let mut mlp = candle_nn::seq()
+        .add(candle_nn::linear(3, 4, vb.pp("0"))?)
+        .add(candle_nn::Activation::Relu)
+        .add(candle_nn::ops::Identity::new())
+        .add_fn(|xs| xs.tanh())
+        .add(candle_nn::linear(4, 2, vb.pp("4"))?);
+    assert_eq!(names(&mlp), ["0.weight", "0.bias", "4.weight", "4.bias"]);
+    let mut visited = names(&mlp);
+    visited.sort();
+    assert_eq!(visited, sorted_varmap_names(&varmap));
+    assert_eq!(mlp.num_parameters(), 3 * 4 + 4 + 4 * 2 + 2);
+    mlp.to_dtype(DType::F64)?;
+    let ys = mlp.forward(&Tensor::zeros((1, 3), DType::F64, dev)?)?;
+    assert_eq!(ys.dtype(), DType::F64);
+
+    // The parameter-free layers and sequential layers can be stored in a module list.
+    let mut list = ModuleList::new();
+    list.push(mlp);
+    list.push(candle_nn::Activation::Gelu);
+    list.push(candle_nn::PixelShuffle::new(2));
+    list.push(candle_nn::MaxPool2d::new(2, Default::default()));
+    assert_eq!(names(&list)[0], "0.0.weight");
+    assert_eq!(list.num_parameters(), 26);
+    assert_eq!(candle_nn::Dropout::new(0.1).num_parameters(), 0);
+    Ok(())
+}
@@ -0,0 +1,116 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::test_utils::{to_vec1_round, to_vec3_round};
+use candle::{Device, Module, Result, Tensor, Var};
+use candle_nn::per_sample_grads::{self, GradCapture};
+use candle_nn::{Conv1d, Conv1dConfig, Conv2d, Conv2dConfig, Embedding, Linear};
+
+// Checks that the per-example gradients match the gradients obtained when running each example
+// on its own.
+fn check<F>(per_sample: &Tensor, var: &Var, xs: &Tensor, loss: F) -> Result<()>
+where
+    F: Fn(&Tensor) -> Result<Tensor>,
+{
+    for i in 0..xs.dim(0)? {
+        let grads = loss(&xs.narrow(0, i, 1)?)?.backward()?;
+        let expected = grads.get(var).unwrap().flatten_all()?;
+        let got = per_sample.get(i)?.flatten_all()?;
+        assert_eq!(to_vec1_round(&got, 3)?, to_vec1_round(&expected, 3)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn linear() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Var::new(&[[1f32, -2., 0.5], [0.1, 0.2, 0.3]], dev)?;
+    let b = Var::new(&[0.5f32, -1.], dev)?;
+    let layer = Linear::new(w.as_tensor().clone(), Some(b.as_tensor().clone()));
+    let xs = Tensor::arange(0f32, 24., dev)?
+        .affine(0.1, -1.)?
+        .reshape((4, 2, 3))?;
+    let loss = |xs: &Tensor| layer.forward(xs)?.tanh()?.sum_all();
+
+    let ys = layer.forward(&xs)?;
+    let capture = GradCapture::new(&ys);
+    ys.tanh()?.sum_all()?.backward()?;
+    let (grad_w, grad_b) = per_sample_grads::linear(&layer, &xs, &capture.grad()?)?;
+    assert_eq!(grad_w.dims(), &[4, 2, 3]);
+    check(&grad_w, &w, &xs, loss)?;
+    check(&grad_b.unwrap(), &b, &xs, loss)?;
+    Ok(())
+}
+
+#[test]
+fn embedding() -> Result<()> {
+    let dev = &Device::Cpu;
+    let emb = Var::new(&[[1f32, 2.], [3., 4.], [5., 6.]], dev)?;
+    let layer = Embedding::new(emb.as_tensor().clone(), 2);
+    let ids = Tensor::new(&[[0u32, 2, 2], [1, 1, 0]], dev)?;
+    let loss = |ids: &Tensor| layer.forward(ids)?.sqr()?.sum_all();
+
+    let ys = layer.forward(&ids)?;
+    let capture = GradCapture::new(&ys);
+    ys.sqr()?.sum_all()?.backward()?;
+    let grad = per_sample_grads::embedding(&layer, &ids, &capture.grad()?)?;
+    assert_eq!(grad.dims(), &[2, 3, 2]);
+    check(&grad, &emb, &ids, loss)?;
+    Ok(())
+}
+
+#[test]
+fn conv() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Tensor::arange(0f32, 54., dev)?.affine(0.05, -1.)?;
+    let w = Var::from_tensor(&w.reshape((2, 3, 3, 3))?)?;
+    let b = Var::new(&[0.5f32, -0.5], dev)?;
+    let cfg = Conv2dConfig {
+        padding: 1,
+        stride: 2,
+        dilation: 1,
+        groups: 1,
+    };
+    let layer = Conv2d::new(w.as_tensor().clone(), Some(b.as_tensor().clone()), cfg);
+    let xs = Tensor::arange(0f32, 150., dev)?
+        .affine(0.02, -1.)?
+        .sin()?
+        .reshape((2, 3, 5, 5))?;
+    let loss = |xs: &Tensor| layer.forward(xs)?.sqr()?.sum_all();
+    let ys = layer.forward(&xs)?;
+    let capture = GradCapture::new(&ys);
+    ys.sqr()?.sum_all()?.backward()?;
+    let (grad_w, grad_b) = per_sample_grads::conv2d(&layer, &xs, &capture.grad()?)?;
+    assert_eq!(grad_w.dims(), &[2, 2, 3, 3, 3]);
+    check(&grad_w, &w, &xs, loss)?;
+    check(&grad_b.unwrap(), &b, &xs, loss)?;
+
+    let w = Var::new(&[[[1f32, -1., 0.5]], [[0.2, 0.3, 0.1]]], dev)?;
+    let cfg = Conv1dConfig {
+        padding: 2,
+        stride: 1,
+        dilation: 2,
+        groups: 1,
+    };
+    let layer = Conv1d::new(w.as_tensor().clone(), None, cfg);
+    let xs = Tensor::arange(0f32, 21., dev)?.reshape((3, 1, 7))?;
+    let loss = |xs: &Tensor| layer.forward(xs)?.relu()?.sum_all();
+    let ys = layer.forward(&xs)?;
+    let capture = GradCapture::new(&ys);
+    ys.relu()?.sum_all()?.backward()?;
+    let (grad_w, grad_b) = per_sample_grads::conv1d(&layer, &xs, &capture.grad()?)?;
+    assert!(grad_b.is_none());
+    check(&grad_w, &w, &xs, loss)?;
+
+    // The sum of the per-example gradients is the batch gradient.
+    let grads = loss(&xs)?.backward()?;
+    let expected = grads.get(&w).unwrap();
+    assert_eq!(
+        to_vec3_round(&grad_w.sum(0)?, 3)?,
+        to_vec3_round(expected, 3)?
+    );
+    Ok(())
+}
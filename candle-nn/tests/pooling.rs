@@ -0,0 +1,215 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{test_utils::to_vec2_round, Device, IndexOp, Result, Tensor, Var};
+use candle_nn::{
+    pooling, AdaptiveAvgPool2d, AvgPool2d, AvgPool3d, MaxPool2d, MaxPool3d, Module, Pool2dConfig,
+    Pool3dConfig,
+};
+
+/* The expected values can be checked against PyTorch using the following snippet.
+import torch
+xs = torch.arange(16.).reshape(1, 1, 4, 4)
+print(torch.nn.functional.max_pool2d(xs, 3, stride=2, padding=1, return_indices=True))
+print(torch.nn.functional.max_pool2d(xs, 3, stride=2, ceil_mode=True))
+print(torch.nn.functional.avg_pool2d(xs, 3, stride=2, ceil_mode=True))
+print(torch.nn.functional.avg_pool2d(xs, 3, stride=2, padding=1))
+print(torch.nn.functional.avg_pool2d(xs, 3, stride=2, padding=1, count_include_pad=False))
+*/
+#[test]
+fn pool2d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::arange(0f32, 16., dev)?.reshape((1, 1, 4, 4))?;
+    let padded = Pool2dConfig {
+        stride: Some(2),
+        padding: 1,
+        ..Default::default()
+    };
+    let ceil = Pool2dConfig {
+        stride: Some(2),
+        ceil_mode: true,
+        ..Default::default()
+    };
+
+    let (ys, indexes) = MaxPool2d::new(3, padded).forward_with_indices(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[5., 7.], [13., 15.]]);
+    assert_eq!(indexes.i((0, 0))?.to_vec2::<u32>()?, [[5, 7], [13, 15]]);
+    let ys = MaxPool2d::new(3, ceil).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[10., 11.], [14., 15.]]);
+    // Without padding, the same result as candle-core is returned.
+    let ys = MaxPool2d::new(2, Default::default()).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[5., 7.], [13., 15.]]);
+
+    let ys = AvgPool2d::new(3, ceil).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[5., 6.5], [11., 12.5]]);
+    let ys = AvgPool2d::new(3, padded).forward(&xs)?;
+    assert_eq!(
+        to_vec2_round(&ys.i((0, 0))?, 4)?,
+        [[1.1111, 2.6667], [5.6667, 10.0]]
+    );
+    let cfg = Pool2dConfig {
+        count_include_pad: false,
+        ..padded
+    };
+    let ys = AvgPool2d::new(3, cfg).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[2.5, 4.], [8.5, 10.]]);
+    Ok(())
+}
+
+#[test]
+fn pool2d_backward() -> Result<()> {
+    let dev = &Device::Cpu;
+    // With ties, the gradient goes to the first maximum of each window.
+    let xs = Var::ones((1, 1, 3, 3), candle::DType::F32, dev)?;
+    let cfg = Pool2dConfig {
+        stride: Some(1),
+        ..Default::default()
+    };
+    let ys = pooling::max_pool2d(&xs, 2, &cfg)?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap();
+    assert_eq!(
+        grad.i((0, 0))?.to_vec2::<f32>()?,
+        [[1., 1., 0.], [1., 1., 0.], [0., 0., 0.]]
+    );
+
+    let xs = Var::new(
+        &[[[[0f32, 1., 2., 3.], [4., 5., 6., 7.], [8., 9., 10., 11.]]]],
+        dev,
+    )?;
+    let cfg = Pool2dConfig {
+        stride: Some(2),
+        padding: 1,
+        ..Default::default()
+    };
+    let ys = pooling::max_pool2d(&xs, 3, &cfg)?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap();
+    assert_eq!(
+        grad.i((0, 0))?.to_vec2::<f32>()?,
+        [[0., 0., 0., 0.], [0., 1., 0., 1.], [0., 1., 0., 1.]]
+    );
+
+    let xs = Var::ones((1, 1, 3, 3), candle::DType::F32, dev)?;
+    let cfg = Pool2dConfig {
+        stride: Some(1),
+        ..Default::default()
+    };
+    let ys = pooling::avg_pool2d(&xs, 2, &cfg)?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap();
+    assert_eq!(
+        grad.i((0, 0))?.to_vec2::<f32>()?,
+        [[0.25, 0.5, 0.25], [0.5, 1., 0.5], [0.25, 0.5, 0.25]]
+    );
+    Ok(())
+}
+
+/* The expected values can be checked against PyTorch using the following snippet.
+import torch
+xs = torch.arange(64.).reshape(1, 1, 4, 4, 4)
+print(torch.nn.functional.max_pool3d(xs, 3, stride=2, padding=1))
+print(torch.nn.functional.avg_pool3d(xs, 2))
+print(torch.nn.functional.avg_pool3d(xs, 3, stride=2, padding=1))
+print(torch.nn.functional.avg_pool3d(xs, 3, stride=2, padding=1, count_include_pad=False))
+*/
+#[test]
+fn pool3d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::arange(0f32, 64., dev)?.reshape((1, 1, 4, 4, 4))?;
+    let padded = Pool3dConfig {
+        stride: Some(2),
+        padding: 1,
+        ..Default::default()
+    };
+    let maxs = [[[21f32, 23.], [29., 31.]], [[53., 55.], [61., 63.]]];
+    let ys = MaxPool3d::new(2, Default::default()).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec3::<f32>()?, maxs);
+    let ys = MaxPool3d::new(3, padded).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec3::<f32>()?, maxs);
+
+    let ys = AvgPool3d::new(2, Default::default()).forward(&xs)?;
+    assert_eq!(
+        ys.i((0, 0))?.to_vec3::<f32>()?,
+        [[[10.5, 12.5], [18.5, 20.5]], [[42.5, 44.5], [50.5, 52.5]]]
+    );
+    let ys = AvgPool3d::new(3, padded).forward(&xs)?;
+    assert_eq!(ys.dims(), [1, 1, 2, 2, 2]);
+    assert_eq!(
+        to_vec2_round(&ys.i((0, 0, 0))?, 4)?,
+        [[3.1111, 5.3333], [7.3333, 12.0]]
+    );
+    let cfg = Pool3dConfig {
+        count_include_pad: false,
+        ..padded
+    };
+    let ys = AvgPool3d::new(3, cfg).forward(&xs)?;
+    assert_eq!(
+        ys.i((0, 0))?.to_vec3::<f32>()?,
+        [[[10.5, 12.], [16.5, 18.]], [[34.5, 36.], [40.5, 42.]]]
+    );
+
+    // The pooling applies to each channel of a video clip of shape (b, c, t, h, w).
+    let xs = Tensor::arange(0f32, 2. * 3. * 6. * 8. * 8., dev)?.reshape((2, 3, 6, 8, 8))?;
+    let ys = MaxPool3d::new(2, Default::default()).forward(&xs)?;
+    assert_eq!(ys.dims(), [2, 3, 3, 4, 4]);
+    assert_eq!(
+        ys.i((1, 2, 2, 3, 3))?.to_scalar::<f32>()?,
+        xs.i((1, 2, 5, 7, 7))?.to_scalar::<f32>()?
+    );
+
+    // With ties, the gradient of each window goes to a single element.
+    let xs = Var::ones((1, 2, 2, 2, 2), candle::DType::F32, dev)?;
+    let ys = pooling::max_pool3d(&xs, 2, &Default::default())?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap();
+    assert_eq!(grad.sum_all()?.to_scalar::<f32>()?, 2.);
+    assert_eq!(
+        grad.max_keepdim(2)?
+            .flatten_all()?
+            .max(0)?
+            .to_scalar::<f32>()?,
+        1.
+    );
+    Ok(())
+}
+
+/* The expected values can be checked against PyTorch using the following snippet.
+import torch
+xs = torch.arange(30.).reshape(1, 1, 5, 6)
+print(torch.nn.functional.adaptive_avg_pool2d(xs, (3, 4)))
+*/
+#[test]
+fn adaptive_avg_pool2d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::arange(0f32, 30., dev)?.reshape((1, 1, 5, 6))?;
+    let ys = AdaptiveAvgPool2d::new((3, 4)).forward(&xs)?;
+    assert_eq!(
+        to_vec2_round(&ys.i((0, 0))?, 4)?,
+        [
+            [3.5, 4.5, 6.5, 7.5],
+            [12.5, 13.5, 15.5, 16.5],
+            [21.5, 22.5, 24.5, 25.5]
+        ]
+    );
+    let ys = AdaptiveAvgPool2d::new(1).forward(&xs)?;
+    assert_eq!(ys.dims(), &[1, 1, 1, 1]);
+    assert_eq!(ys.flatten_all()?.to_vec1::<f32>()?, [14.5]);
+    let xs = Tensor::arange(0f32, 16., dev)?.reshape((1, 1, 4, 4))?;
+    let ys = AdaptiveAvgPool2d::new(2).forward(&xs)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[2.5, 4.5], [10.5, 12.5]]);
+
+    // The gradient is spread over the overlapping windows.
+    let xs = Var::ones((1, 1, 3, 3), candle::DType::F32, dev)?;
+    let ys = pooling::adaptive_avg_pool2d(&xs, 2)?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).unwrap();
+    assert_eq!(
+        grad.i((0, 0))?.to_vec2::<f32>()?,
+        [[0.25, 0.5, 0.25], [0.5, 1., 0.5], [0.25, 0.5, 0.25]]
+    );
+    Ok(())
+}
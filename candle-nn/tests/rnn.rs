@@ -99,3 +99,162 @@ fn gru() -> Result<()> {
     assert_eq!(to_vec2_round(h, 4)?, &[[0.0579, 0.8836, -0.9991]]);
     Ok(())
 }
+
+// Checks a bidirectional two layers LSTM against the single layer implementation.
+#[test]
+fn multi_layer_lstm() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let vm = candle_nn::VarMap::new();
+    let vb = candle_nn::VarBuilder::from_varmap(&vm, DType::F32, cpu);
+    let lstm = candle_nn::multi_layer_lstm(3, 4, 2, true, Default::default(), vb.clone())?;
+    assert_eq!(
+        vm.data().lock().unwrap()["weight_ih_l1_reverse"].dims(),
+        &[16, 8]
+    );
+    let xs = Tensor::arange(0f32, 30., cpu)?.sin()?.reshape((2, 5, 3))?;
+    let (ys, state) = lstm.forward(&xs)?;
+    assert_eq!(ys.dims(), &[2, 5, 8]);
+    assert_eq!(state.h().dims(), &[4, 2, 4]);
+    assert_eq!(state.c().dims(), &[4, 2, 4]);
+
+    let reverse = |xs: &Tensor| -> Result<Tensor> {
+        let idxs = Tensor::new(&[4u32, 3, 2, 1, 0], cpu)?;
+        xs.index_select(&idxs, 1)
+    };
+    let mut xs = xs;
+    let (mut hs, mut cs) = (vec![], vec![]);
+    for (layer_idx, in_dim) in [(0, 3), (1, 8)] {
+        let mut outputs = vec![];
+        for direction in [
+            candle_nn::Direction::Forward,
+            candle_nn::Direction::Backward,
+        ] {
+            let config = candle_nn::LSTMConfig {
+                layer_idx,
+                direction,
+                ..Default::default()
+            };
+            let layer = candle_nn::lstm(in_dim, 4, config, vb.clone())?;
+            let (states, ys) = match direction {
+                candle_nn::Direction::Forward => {
+                    let states = layer.seq(&xs)?;
+                    let ys = layer.states_to_tensor(&states)?;
+                    (states, ys)
+                }
+                candle_nn::Direction::Backward => {
+                    let states = layer.seq(&reverse(&xs)?)?;
+                    let ys = reverse(&layer.states_to_tensor(&states)?)?;
+                    (states, ys)
+                }
+            };
+            hs.push(states[4].h().clone());
+            cs.push(states[4].c().clone());
+            outputs.push(ys)
+        }
+        xs = Tensor::cat(&outputs, 2)?;
+    }
+    let diff = (ys - xs)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+    assert!(diff < 1e-5, "{diff}");
+    let diff = (state.h() - Tensor::stack(&hs, 0)?)?.abs()?.sum_all()?;
+    assert!(diff.to_vec0::<f32>()? < 1e-5);
+    let diff = (state.c() - Tensor::stack(&cs, 0)?)?.abs()?.sum_all()?;
+    assert!(diff.to_vec0::<f32>()? < 1e-5);
+
+    // Starting from the final state.
+    let (_, state2) = lstm.forward_init(&Tensor::zeros((2, 1, 3), DType::F32, cpu)?, &state)?;
+    assert_eq!(state2.h().dims(), &[4, 2, 4]);
+    Ok(())
+}
+
+#[test]
+fn multi_layer_gru() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let vm = candle_nn::VarMap::new();
+    let vb = candle_nn::VarBuilder::from_varmap(&vm, DType::F32, cpu);
+    let gru = candle_nn::multi_layer_gru(3, 4, 2, true, Default::default(), vb.clone())?;
+    assert_eq!(
+        vm.data().lock().unwrap()["weight_hh_l1_reverse"].dims(),
+        &[12, 4]
+    );
+    let xs = Tensor::arange(0f32, 30., cpu)?.sin()?.reshape((2, 5, 3))?;
+    let (ys, state) = gru.forward(&xs)?;
+    assert_eq!(ys.dims(), &[2, 5, 8]);
+    assert_eq!(state.h().dims(), &[4, 2, 4]);
+
+    let reverse = |xs: &Tensor| -> Result<Tensor> {
+        let idxs = Tensor::new(&[4u32, 3, 2, 1, 0], cpu)?;
+        xs.index_select(&idxs, 1)
+    };
+    let mut xs = xs;
+    let mut hs = vec![];
+    for (layer_idx, in_dim) in [(0, 3), (1, 8)] {
+        let mut outputs = vec![];
+        for direction in [
+            candle_nn::Direction::Forward,
+            candle_nn::Direction::Backward,
+        ] {
+            let config = candle_nn::GRUConfig {
+                layer_idx,
+                direction,
+                ..Default::default()
+            };
+            let layer = candle_nn::gru(in_dim, 4, config, vb.clone())?;
+            let ys = match direction {
+                candle_nn::Direction::Forward => layer.states_to_tensor(&layer.seq(&xs)?)?,
+                candle_nn::Direction::Backward => {
+                    reverse(&layer.states_to_tensor(&layer.seq(&reverse(&xs)?)?)?)?
+                }
+            };
+            assert_eq!(ys.dims(), &[2, 5, 4]);
+            // The final state is the last output for the forward direction and the first one
+            // for the backward direction.
+            let last = match direction {
+                candle_nn::Direction::Forward => 4,
+                candle_nn::Direction::Backward => 0,
+            };
+            hs.push(ys.get_on_dim(1, last)?);
+            outputs.push(ys)
+        }
+        xs = Tensor::cat(&outputs, 2)?;
+    }
+    let diff = (ys - xs)?.abs()?.sum_all()?.to_vec0::<f32>()?;
+    assert!(diff < 1e-5, "{diff}");
+    let diff = (state.h() - Tensor::stack(&hs, 0)?)?.abs()?.sum_all()?;
+    assert!(diff.to_vec0::<f32>()? < 1e-5);
+    Ok(())
+}
+
+#[test]
+fn gru_cell() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let tensors: std::collections::HashMap<_, _> = [
+        (
+            "weight_ih",
+            Tensor::arange(0f32, 18f32, cpu)?.reshape((9, 2))?.cos()?,
+        ),
+        (
+            "weight_hh",
+            Tensor::arange(0f32, 27f32, cpu)?.reshape((9, 3))?.sin()?,
+        ),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    let vb = candle_nn::VarBuilder::from_tensors(tensors.clone(), DType::F32, cpu);
+    let cell = candle_nn::gru_cell(2, 3, candle_nn::GRUConfig::default_no_bias(), vb)?;
+    let tensors = tensors
+        .into_iter()
+        .map(|(k, v)| (format!("{k}_l0"), v))
+        .collect();
+    let vb = candle_nn::VarBuilder::from_tensors(tensors, DType::F32, cpu);
+    let gru = candle_nn::gru(2, 3, candle_nn::GRUConfig::default_no_bias(), vb)?;
+    let mut state = candle_nn::GRUState::new(Tensor::new(&[[0.5f32, -0.5, 0.]], cpu)?);
+    let mut state2 = state.clone();
+    for inp in [3f32, 1., 4.] {
+        let inp = Tensor::new(&[[inp, inp * 0.5]], cpu)?;
+        state = cell.step(&inp, &state)?;
+        state2 = gru.step(&inp, &state2)?;
+    }
+    assert_eq!(to_vec2_round(state.h(), 4)?, to_vec2_round(state2.h(), 4)?);
+    Ok(())
+}
@@ -0,0 +1,35 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{Device, Result, Tensor};
+use candle_nn::{Activation, Linear, Module};
+
+#[test]
+fn sequential() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w1 = Tensor::new(&[[1f32, -1.], [2., 0.], [0., 1.]], dev)?;
+    let w2 = Tensor::new(&[[1f32, 1., 1.]], dev)?;
+    let mlp = candle_nn::seq()
+        .add(Linear::new(w1, None))
+        .add(Activation::Relu)
+        .add(Linear::new(w2, Some(Tensor::new(&[0.5f32], dev)?)))
+        .add_fn(|xs| xs * 2.);
+    assert_eq!(mlp.len(), 4);
+    assert!(!mlp.is_empty());
+    assert!(mlp.get(4).is_none());
+
+    let xs = Tensor::new(&[[1f32, 2.], [3., -4.]], dev)?;
+    // [[-1, 2, 2], [7, 6, -4]] -> relu -> [[0, 2, 2], [7, 6, 0]] -> [[4.5], [13.5]] -> x2
+    assert_eq!(mlp.forward(&xs)?.to_vec2::<f32>()?, [[9.], [27.]]);
+    let hidden = mlp[1].forward(&mlp[0].forward(&xs)?)?;
+    assert_eq!(hidden.to_vec2::<f32>()?, [[0., 2., 2.], [7., 6., 0.]]);
+    let all = mlp.forward_all(&xs)?;
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[1].to_vec2::<f32>()?, hidden.to_vec2::<f32>()?);
+    let ys = mlp.iter().try_fold(xs.clone(), |xs, l| l.forward(&xs))?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[9.], [27.]]);
+    Ok(())
+}
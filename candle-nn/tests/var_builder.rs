@@ -0,0 +1,51 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{DType, Device, Result, Tensor};
+use candle_nn::{VarBuilder, VarMap};
+
+#[test]
+fn rename_prefixed() -> Result<()> {
+    let dev = &Device::Cpu;
+    let tensors = [
+        ("model.layer.w", Tensor::new(&[1f32, 2.], dev)?),
+        ("model.layer.b", Tensor::new(&[3f32], dev)?),
+    ];
+    let tensors = tensors.into_iter().map(|(k, v)| (k.to_string(), v));
+    let vb = VarBuilder::from_tensors(tensors.collect(), DType::F32, dev);
+
+    // The renamer gets the full path and its result is looked up as is.
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let vb = vb.pp("model").rename_f({
+        let seen = seen.clone();
+        move |n: &str| {
+            seen.lock().unwrap().push(n.to_string());
+            n.replace(".weight", ".w").replace(".bias", ".b")
+        }
+    });
+    let vb = vb.pp("layer");
+    assert_eq!(vb.prefix(), "model.layer");
+    assert_eq!(vb.get(2, "weight")?.to_vec1::<f32>()?, [1., 2.]);
+    assert_eq!(vb.get(1, "bias")?.to_vec1::<f32>()?, [3.]);
+    assert!(vb.contains_tensor("weight"));
+    assert!(!vb.contains_tensor("other"));
+    assert_eq!(
+        seen.lock().unwrap()[..2],
+        ["model.layer.weight", "model.layer.bias"]
+    );
+
+    // Nested renames each see the full path.
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev)
+        .pp("a")
+        .rename_f(|n: &str| n.replace("a.x", "a.y"))
+        .pp("b")
+        .rename_f(|n: &str| n.replace("b.z", "b.x"));
+    vb.get_with_hints(3, "z", candle_nn::Init::Const(0.))?;
+    let names: Vec<_> = varmap.data().lock().unwrap().keys().cloned().collect();
+    assert_eq!(names, ["a.b.x"]);
+    Ok(())
+}
@@ -0,0 +1,132 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{test_utils::to_vec2_round, DType, Device, Module, ModuleT, Result, Tensor};
+use candle_nn::{Parameters, SpectralNorm, SpectralNormConfig, VarBuilder, VarMap, WeightNorm};
+
+#[test]
+fn weight_norm() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Tensor::new(&[[3f32, 4.], [0., 2.], [1., 0.]], dev)?;
+    let linear = candle_nn::Linear::new(w.clone(), None);
+    let layer = WeightNorm::from_module(linear.clone(), 0)?;
+    assert_eq!(layer.weight_g().to_vec2::<f32>()?, [[5.], [2.], [1.]]);
+    assert_eq!(layer.weight()?.to_vec2::<f32>()?, w.to_vec2::<f32>()?);
+    let xs = Tensor::new(&[[1f32, -1.]], dev)?;
+    assert_eq!(layer.forward(&xs)?.to_vec2::<f32>()?, [[-1., -2., 1.]]);
+
+    // Loading the magnitude and direction from a checkpoint.
+    let tensors = [
+        ("proj.weight_g", Tensor::new(&[[2f32], [1.]], dev)?),
+        ("proj.weight_v", Tensor::new(&[[3f32, 4.], [0., 2.]], dev)?),
+        ("proj.bias", Tensor::new(&[0.5f32, 0.], dev)?),
+    ];
+    let tensors = tensors.into_iter().map(|(k, v)| (k.to_string(), v));
+    let vb = VarBuilder::from_tensors(tensors.collect(), DType::F32, dev);
+    let layer = candle_nn::weight_norm(0, vb.pp("proj"), |vb| candle_nn::linear(2, 2, vb))?;
+    assert_eq!(to_vec2_round(&layer.weight()?, 4)?, [[1.2, 1.6], [0., 1.]]);
+    let ys = layer.forward(&xs)?;
+    assert_eq!(to_vec2_round(&ys, 4)?, [[0.1, -1.]]);
+    assert_eq!(
+        layer.remove()?.forward(&xs)?.to_vec2::<f32>()?,
+        ys.to_vec2::<f32>()?
+    );
+
+    // The magnitude has to match the weight.
+    let g = Tensor::ones((2, 1), DType::F32, dev)?;
+    assert!(WeightNorm::new(linear.clone(), g, 0).is_err());
+    assert!(WeightNorm::from_module(linear, 2).is_err());
+    Ok(())
+}
+
+#[test]
+fn weight_norm_training() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let cfg = Default::default();
+    let layer = candle_nn::weight_norm(0, vb.pp("conv"), |vb| candle_nn::conv1d(2, 3, 3, cfg, vb))?;
+    let mut names: Vec<_> = varmap.data().lock().unwrap().keys().cloned().collect();
+    names.sort();
+    assert_eq!(names, ["conv.bias", "conv.weight_g", "conv.weight_v"]);
+    assert_eq!(layer.weight_g().dims(), &[3, 1, 1]);
+    // Each output channel of the initial weight has unit norm.
+    let norms = layer.weight()?.sqr()?.sum((1, 2))?.to_vec1::<f32>()?;
+    assert!(norms.iter().all(|n| (n - 1.).abs() < 1e-5));
+
+    let xs = Tensor::randn(0f32, 1., (1, 2, 5), dev)?;
+    let grads = layer.forward(&xs)?.sum_all()?.backward()?;
+    for (name, t) in layer.named_parameters() {
+        assert!(grads.get(&t).is_some(), "no grad for {name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn spectral_norm() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Tensor::new(&[[3f32, 0.], [0., 1.], [0., 0.]], dev)?;
+    let linear = candle_nn::Linear::new(w.clone(), None);
+    let layer = SpectralNorm::from_module(linear, SpectralNormConfig::default())?;
+    layer.power_iteration(20)?;
+    let sigma = layer.sigma()?.to_scalar::<f32>()?;
+    assert!((sigma - 3.).abs() < 1e-4, "{sigma}");
+    let xs = Tensor::new(&[[3f32, 3.]], dev)?;
+    let ys = layer.forward_t(&xs, false)?;
+    assert_eq!(to_vec2_round(&ys, 3)?, [[3., 1., 0.]]);
+
+    // Transposed convolutions use the second dimension of the weight.
+    let w = Tensor::randn(0f32, 1., (2, 4, 3), dev)?;
+    let conv = candle_nn::ConvTranspose1d::new(w, None, Default::default());
+    let config = SpectralNormConfig {
+        dim: 1,
+        ..Default::default()
+    };
+    let layer = SpectralNorm::from_module(conv, config)?;
+    assert_eq!(layer.weight_u().dims(), &[4]);
+    assert_eq!(layer.weight_v().dims(), &[6]);
+    Ok(())
+}
+
+#[test]
+fn spectral_norm_training() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let layer = candle_nn::spectral_norm(Default::default(), vb.pp("fc"), |vb| {
+        candle_nn::linear(5, 4, vb)
+    })?;
+    let names: Vec<_> = layer
+        .named_parameters()
+        .into_iter()
+        .map(|(n, _)| n)
+        .collect();
+    assert_eq!(names, ["weight_orig", "bias", "weight_u", "weight_v"]);
+    // The singular vectors are not trainable.
+    let mut trainable: Vec<_> = varmap
+        .trainable_vars()
+        .iter()
+        .map(|v| v.dims().to_vec())
+        .collect();
+    trainable.sort();
+    assert_eq!(trainable, [vec![4], vec![4, 5]]);
+
+    let u = varmap.data().lock().unwrap()["fc.weight_u"].to_vec1::<f32>()?;
+    let xs = Tensor::randn(0f32, 1., (3, 5), dev)?;
+    let ys = layer.forward_t(&xs, true)?;
+    let grads = ys.sum_all()?.backward()?;
+    assert!(grads.get(layer.weight_u()).is_none());
+    assert!(grads.get(&layer.weight_orig()?).is_some());
+    // The power iteration updates the var map.
+    let new_u = varmap.data().lock().unwrap()["fc.weight_u"].to_vec1::<f32>()?;
+    assert_ne!(u, new_u);
+    let ys_eval = layer.forward_t(&xs, false)?;
+    assert_eq!(
+        to_vec2_round(&ys_eval, 4)?,
+        to_vec2_round(&layer.remove()?.forward(&xs)?, 4)?
+    );
+    Ok(())
+}
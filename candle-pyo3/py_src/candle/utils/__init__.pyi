@@ -72,3 +72,10 @@ def save_safetensors(path: Union[str, PathLike], tensors: Dict[str, Tensor]) ->
     Saves a dictionary of tensors to a safetensors file.
     """
     pass
+
+@staticmethod
+def set_num_threads(n: int) -> None:
+    """
+    Sets the number of threads used by candle, 0 reverts to the default.
+    """
+    pass
@@ -17,7 +17,7 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use ::candle::{quantized::QTensor, DType, Device, Module, Tensor, WithDType};
+use ::candle::{quantized::QTensor, DType, Device, Module, Tensor, WithDType, F8E4M3, F8E5M2};
 
 mod utils;
 use utils::wrap_err;
@@ -86,6 +86,7 @@ impl PyDevice {
             Device::Cpu => Self::Cpu,
             Device::Cuda(_) => Self::Cuda,
             Device::Metal(_) => Self::Metal,
+            Device::Custom(_) => unreachable!("custom devices cannot be created from python"),
         }
     }
 
@@ -158,6 +159,8 @@ pydtype!(f16, f32::from);
 pydtype!(bf16, f32::from);
 pydtype!(f32, |v| v);
 pydtype!(f64, |v| v);
+pydtype!(F8E4M3, f32::from);
+pydtype!(F8E5M2, f32::from);
 
 fn actual_index(t: &Tensor, dim: usize, index: i64) -> ::candle::Result<usize> {
     let dim = t.dim(dim)?;
@@ -205,6 +208,9 @@ trait MapDType {
             DType::F16 => self.f::<f16>(t),
             DType::F32 => self.f::<f32>(t),
             DType::F64 => self.f::<f64>(t),
+            DType::F8E4M3 => self.f::<F8E4M3>(t),
+            DType::F8E5M2 => self.f::<F8E5M2>(t),
+            DType::Bool => self.f::<u8>(&t.to_dtype(DType::U8).map_err(wrap_err)?),
         }
     }
 }
@@ -1498,6 +1504,13 @@ fn get_num_threads() -> usize {
     ::candle::utils::get_num_threads()
 }
 
+#[pyfunction]
+/// Sets the number of threads used by candle, 0 reverts to the default.
+/// &RETURNS&: None
+fn set_num_threads(n: usize) {
+    ::candle::utils::set_num_threads(n)
+}
+
 fn candle_utils(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(cuda_is_available, m)?)?;
     m.add_function(wrap_pyfunction!(get_num_threads, m)?)?;
@@ -1508,6 +1521,7 @@ fn candle_utils(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(save_gguf, m)?)?;
     m.add_function(wrap_pyfunction!(load_safetensors, m)?)?;
     m.add_function(wrap_pyfunction!(save_safetensors, m)?)?;
+    m.add_function(wrap_pyfunction!(set_num_threads, m)?)?;
     Ok(())
 }
 
@@ -1622,6 +1636,9 @@ fn candle(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add("f16", PyDType(DType::F16))?;
     m.add("f32", PyDType(DType::F32))?;
     m.add("f64", PyDType(DType::F64))?;
+    m.add("f8e4m3", PyDType(DType::F8E4M3))?;
+    m.add("f8e5m2", PyDType(DType::F8E5M2))?;
+    m.add("bool", PyDType(DType::Bool))?;
     m.add_function(wrap_pyfunction!(cat, m)?)?;
     m.add_function(wrap_pyfunction!(ones, m)?)?;
     m.add_function(wrap_pyfunction!(rand, m)?)?;
@@ -1,11 +1,7 @@
 use candle::{DType, Device, IndexOp, Result, Tensor, D};
-use candle_nn::{embedding, linear_b as linear, Embedding, LayerNorm, Linear, Module, VarBuilder};
-
-fn layer_norm(size: usize, eps: f64, vb: VarBuilder) -> Result<LayerNorm> {
-    let weight = vb.get(size, "weight")?;
-    let bias = vb.get(size, "bias")?;
-    Ok(LayerNorm::new(weight, bias, eps))
-}
+use candle_nn::{
+    embedding, layer_norm, linear_b as linear, Embedding, LayerNorm, Linear, Module, VarBuilder,
+};
 
 fn make_causal_mask(t: usize, device: &Device) -> Result<Tensor> {
     let mask: Vec<_> = (0..t)
@@ -294,6 +290,8 @@ impl GPTBigCode {
 
     pub fn load(vb: VarBuilder, cfg: Config) -> Result<Self> {
         let hidden_size = cfg.hidden_size;
+        // The output projection is tied to the token embedding and usually not stored.
+        let vb = vb.with_fallback("lm_head.weight", "transformer.wte.weight");
         let vb_t = vb.pp("transformer");
         let wte = embedding(cfg.vocab_size, hidden_size, vb_t.pp("wte"))?;
         let wpe = embedding(cfg.max_position_embeddings, hidden_size, vb_t.pp("wpe"))?;
@@ -301,7 +299,7 @@ impl GPTBigCode {
             .map(|i| Block::load(vb_t.pp(&format!("h.{i}")), &cfg))
             .collect::<Result<Vec<_>>>()?;
         let ln_f = layer_norm(hidden_size, cfg.layer_norm_epsilon, vb_t.pp("ln_f"))?;
-        let lm_head = linear(hidden_size, cfg.vocab_size, false, vb_t.pp("wte"))?;
+        let lm_head = linear(hidden_size, cfg.vocab_size, false, vb.pp("lm_head"))?;
         let bias = make_causal_mask(cfg.max_position_embeddings, vb.device())?;
         Ok(Self {
             wte,
@@ -56,6 +56,17 @@ impl Module for IdentityMap {
     }
 }
 
+impl candle_nn::Parameters for IdentityMap {
+    fn visit_parameters(&self, _: &mut dyn FnMut(&str, &Tensor)) {}
+
+    fn apply_parameters(
+        &mut self,
+        _: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub struct MMProjector {
     pub modules: Sequential,
 }
@@ -1,6 +1,6 @@
 use super::with_tracing::{linear, Embedding, Linear};
 use candle::{Result, Tensor};
-use candle_nn::{layer_norm, LayerNorm, VarBuilder};
+use candle_nn::{layer_norm, LayerNorm, SinusoidalPositionalEmbedding, VarBuilder};
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -77,45 +77,6 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone)]
-struct SinusoidalPositionalEmbedding {
-    emb: Embedding,
-}
-
-impl SinusoidalPositionalEmbedding {
-    fn new(cfg: &Config, vb: VarBuilder) -> Result<Self> {
-        let dev = vb.device();
-        let dtype = vb.dtype();
-        let num_positions = cfg.max_position_embeddings;
-        let dim = cfg.d_model;
-        let inv_freq: Vec<_> = (0..dim)
-            .step_by(2)
-            .map(|i| 1f32 / 10000f32.powf(i as f32 / dim as f32))
-            .collect();
-        let inv_freq_len = inv_freq.len();
-        let inv_freq = Tensor::from_vec(inv_freq, (1, inv_freq_len), dev)?.to_dtype(dtype)?;
-        let t = Tensor::arange(0u32, num_positions as u32, dev)?
-            .to_dtype(dtype)?
-            .reshape((num_positions, 1))?;
-        let freqs = t.matmul(&inv_freq)?;
-        let sin = freqs.sin()?;
-        let cos = freqs.cos()?;
-        let weights = Tensor::cat(&[&sin, &cos], 1)?.contiguous()?;
-        let emb = Embedding::from_weights(weights)?;
-        Ok(Self { emb })
-    }
-
-    fn forward(&self, input_ids: &Tensor, past_kv_len: usize) -> Result<Tensor> {
-        let seq_len = input_ids.dim(1)?;
-        Tensor::arange(
-            past_kv_len as u32,
-            (past_kv_len + seq_len) as u32,
-            input_ids.device(),
-        )?
-        .apply(&self.emb)
-    }
-}
-
 #[derive(Debug, Clone)]
 struct Attention {
     q_proj: Linear,
@@ -339,7 +300,13 @@ pub struct Encoder {
 
 impl Encoder {
     fn new(cfg: &Config, embed_tokens: &Embedding, vb: VarBuilder) -> Result<Self> {
-        let embed_positions = SinusoidalPositionalEmbedding::new(cfg, vb.pp("embed_positions"))?;
+        let embed_positions = SinusoidalPositionalEmbedding::new(
+            cfg.max_position_embeddings,
+            cfg.d_model,
+            Default::default(),
+            vb.dtype(),
+            vb.device(),
+        )?;
         let mut layers = Vec::with_capacity(cfg.encoder_layers);
         let vb_l = vb.pp("layers");
         for idx in 0..cfg.encoder_layers {
@@ -367,7 +334,7 @@ impl Encoder {
         };
         let embed_pos = self
             .embed_positions
-            .forward(&xs, past_kv_len)?
+            .forward(xs.dim(1)?, past_kv_len)?
             .unsqueeze(0)?;
         let mut xs = xs.broadcast_add(&embed_pos)?;
         for layer in self.layers.iter_mut() {
@@ -393,7 +360,13 @@ pub struct Decoder {
 
 impl Decoder {
     fn new(cfg: &Config, embed_tokens: &Embedding, vb: VarBuilder) -> Result<Self> {
-        let embed_positions = SinusoidalPositionalEmbedding::new(cfg, vb.pp("embed_positions"))?;
+        let embed_positions = SinusoidalPositionalEmbedding::new(
+            cfg.max_position_embeddings,
+            cfg.d_model,
+            Default::default(),
+            vb.dtype(),
+            vb.device(),
+        )?;
         let mut layers = Vec::with_capacity(cfg.decoder_layers);
         let vb_l = vb.pp("layers");
         for idx in 0..cfg.decoder_layers {
@@ -427,7 +400,7 @@ impl Decoder {
         };
         let embed_pos = self
             .embed_positions
-            .forward(&xs, past_kv_len)?
+            .forward(xs.dim(1)?, past_kv_len)?
             .unsqueeze(0)?;
         let mut xs = xs.broadcast_add(&embed_pos)?;
         for layer in self.layers.iter_mut() {
@@ -203,25 +203,7 @@ pub(crate) fn build_alibi_bias(cfg: &Config) -> Result<Tensor> {
     } else {
         alibi_bias.reshape((1, 1, 1, seq_len))?
     };
-    let mut n_heads2 = 1;
-    while n_heads2 < cfg.n_heads {
-        n_heads2 *= 2
-    }
-    let slopes = (1..=n_heads2)
-        .map(|v| 1f32 / 2f32.powf((v * cfg.attn_alibi_bias_max) as f32 / n_heads2 as f32))
-        .collect::<Vec<_>>();
-    let slopes = if n_heads2 == cfg.n_heads {
-        slopes
-    } else {
-        slopes
-            .iter()
-            .skip(1)
-            .step_by(2)
-            .chain(slopes.iter().step_by(2))
-            .take(cfg.n_heads)
-            .cloned()
-            .collect::<Vec<f32>>()
-    };
+    let slopes = candle_nn::attention::alibi_slopes(cfg.n_heads, cfg.attn_alibi_bias_max as f64);
     let slopes = Tensor::new(slopes, &Device::Cpu)?.reshape((1, (), 1, 1))?;
     alibi_bias.to_dtype(DType::F32)?.broadcast_mul(&slopes)
 }
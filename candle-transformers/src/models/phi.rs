@@ -4,7 +4,8 @@ use crate::models::with_tracing::{layer_norm, linear, Embedding, LayerNorm, Line
 /// There is an alternative implementation of the phi model in mixformers.rs.
 /// This corresponds to the model update made with the following commit:
 /// https://huggingface.co/microsoft/phi-2/commit/cb2f4533604d8b67de604e7df03bfe6f3ca22869
-use candle::{DType, Device, IndexOp, Module, Result, Tensor, D};
+use candle::{DType, Device, Module, Result, Tensor};
+use candle_nn::rotary_emb::{RotaryEmbedding, RotaryEmbeddingConfig};
 use candle_nn::{Activation, VarBuilder};
 use serde::Deserialize;
 
@@ -36,44 +37,6 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone)]
-struct RotaryEmbedding {
-    dim: usize,
-    sin: Tensor,
-    cos: Tensor,
-}
-
-impl RotaryEmbedding {
-    fn new(cfg: &Config, dev: &Device) -> Result<Self> {
-        let dim = (cfg.partial_rotary_factor * cfg.head_dim() as f64) as usize;
-        let inv_freq: Vec<_> = (0..dim)
-            .step_by(2)
-            .map(|i| 1f32 / cfg.rope_theta.powf(i as f32 / dim as f32))
-            .collect();
-        let inv_freq_len = inv_freq.len();
-        let inv_freq = Tensor::from_vec(inv_freq, (1, inv_freq_len), dev)?;
-        let t = Tensor::arange(0u32, cfg.max_position_embeddings as u32, dev)?
-            .to_dtype(DType::F32)?
-            .reshape((cfg.max_position_embeddings, 1))?;
-        let freqs = t.matmul(&inv_freq)?;
-        Ok(Self {
-            dim,
-            sin: freqs.sin()?,
-            cos: freqs.cos()?,
-        })
-    }
-
-    fn apply_rotary_emb(&self, xs: &Tensor, seqlen_offset: usize) -> Result<Tensor> {
-        let (_b_size, _num_heads, seq_len, _headdim) = xs.dims4()?;
-        let xs_rot = xs.i((.., .., .., ..self.dim))?.contiguous()?;
-        let xs_pass = xs.i((.., .., .., self.dim..))?;
-        let c = self.cos.narrow(0, seqlen_offset, seq_len)?;
-        let s = self.sin.narrow(0, seqlen_offset, seq_len)?;
-        let xs_rot = candle_nn::rotary_emb::rope(&xs_rot, &c, &s)?;
-        Tensor::cat(&[&xs_rot, &xs_pass], D::Minus1)
-    }
-}
-
 #[derive(Debug, Clone)]
 #[allow(clippy::upper_case_acronyms)]
 struct MLP {
@@ -143,7 +106,15 @@ impl Attention {
         let v_proj = linear(cfg.hidden_size, num_kv_heads * head_dim, vb.pp("v_proj"))?;
         let dense = linear(num_heads * head_dim, cfg.hidden_size, vb.pp("dense"))?;
         // Alternative rope scalings are not supported.
-        let rotary_emb = RotaryEmbedding::new(cfg, vb.device())?;
+        let rotary_cfg = RotaryEmbeddingConfig {
+            rotary_dim: Some((cfg.partial_rotary_factor * head_dim as f64) as usize),
+            ..RotaryEmbeddingConfig::new(
+                head_dim,
+                cfg.rope_theta as f64,
+                cfg.max_position_embeddings,
+            )
+        };
+        let rotary_emb = RotaryEmbedding::new(&rotary_cfg, DType::F32, vb.device())?;
         let (q_layernorm, k_layernorm) = if cfg.qk_layernorm {
             let q_layernorm = layer_norm(head_dim, cfg.layer_norm_eps, vb.pp("q_layernorm"))?;
             let k_layernorm = layer_norm(head_dim, cfg.layer_norm_eps, vb.pp("k_layernorm"))?;
@@ -204,12 +175,9 @@ impl Attention {
             None => 0,
             Some((prev_k, _)) => prev_k.dim(2)?,
         };
-        let query_states = self
-            .rotary_emb
-            .apply_rotary_emb(&query_states, seqlen_offset)?;
-        let key_states = self
-            .rotary_emb
-            .apply_rotary_emb(&key_states, seqlen_offset)?;
+        let (query_states, key_states) =
+            self.rotary_emb
+                .apply_qk(&query_states, &key_states, seqlen_offset)?;
 
         // KV cache.
         let (key_states, value_states) = match &self.kv_cache {
@@ -1,7 +1,7 @@
 use super::Config;
 use crate::quantized_nn::{layer_norm, linear, linear_no_bias, Embedding, Linear};
 pub use crate::quantized_var_builder::VarBuilder;
-use candle::{Device, IndexOp, Result, Tensor, D};
+use candle::{DType, Device, IndexOp, Result, Tensor, D};
 use candle_nn::{Conv1d, Conv1dConfig, LayerNorm, Module};
 
 fn conv1d(
@@ -203,19 +203,13 @@ impl ResidualAttentionBlock {
 }
 
 fn sinusoids(length: usize, channels: usize, device: &Device) -> Result<Tensor> {
-    let max_timescale = 10000f32;
-    let log_timescale_increment = max_timescale.ln() / (channels / 2 - 1) as f32;
-    let inv_timescales: Vec<_> = (0..channels / 2)
-        .map(|i| (i as f32 * (-log_timescale_increment)).exp())
-        .collect();
-    let inv_timescales = Tensor::new(inv_timescales.as_slice(), device)?.unsqueeze(0)?;
-    let arange = Tensor::arange(0, length as u32, device)?
-        .to_dtype(candle::DType::F32)?
-        .unsqueeze(1)?;
-    let sh = (length, channels / 2);
-    let scaled_time = (arange.broadcast_as(sh)? * inv_timescales.broadcast_as(sh)?)?;
-    let sincos = Tensor::cat(&[scaled_time.sin()?, scaled_time.cos()?], 1)?;
-    Ok(sincos)
+    let cfg = candle_nn::SinusoidalPositionalEmbeddingConfig {
+        shifted_frequencies: true,
+        ..Default::default()
+    };
+    let emb =
+        candle_nn::SinusoidalPositionalEmbedding::new(length, channels, cfg, DType::F32, device)?;
+    Ok(emb.embeddings().clone())
 }
 
 // https://github.com/openai/whisper/blob/f572f2161ba831bae131364c3bffdead7af6d210/whisper/model.py#L143
@@ -1,5 +1,5 @@
 use candle::{Module, Result, Tensor};
-use candle_nn::VarBuilder;
+use candle_nn::{Parameters, VarBuilder};
 
 #[derive(Debug, Clone)]
 pub struct Embedding {
@@ -193,3 +193,25 @@ impl Module for RmsNorm {
         self.inner.forward(x)
     }
 }
+
+// The tracing wrappers expose the parameters of the layer that they wrap.
+macro_rules! impl_parameters {
+    ($($t:ty),*) => {
+        $(
+            impl Parameters for $t {
+                fn visit_parameters(&self, f: &mut dyn FnMut(&str, &Tensor)) {
+                    self.inner.visit_parameters(f)
+                }
+
+                fn apply_parameters(
+                    &mut self,
+                    f: &mut dyn FnMut(&str, &Tensor) -> Result<Tensor>,
+                ) -> Result<()> {
+                    self.inner.apply_parameters(f)
+                }
+            }
+        )*
+    };
+}
+
+impl_parameters!(Embedding, Linear, Conv2d, LayerNorm, RmsNorm);